@@ -0,0 +1,236 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use clap::{
+    arg, value_parser, Arg, ArgAction, ArgMatches, Command, ValueEnum,
+};
+use yansi::Color::Red;
+use yansi::Paint;
+use yara_x::Scanner;
+
+use crate::commands::{
+    compile_rules, external_var_parser, path_with_namespace_parser,
+};
+use crate::help;
+use crate::walk::Walker;
+
+#[derive(Clone, ValueEnum)]
+enum OutputFormats {
+    /// Default output format.
+    Text,
+    /// JSON object with all the measurements.
+    Json,
+}
+
+#[rustfmt::skip]
+pub fn bench() -> Command {
+    super::command("bench")
+        .about("Measure the performance of a set of rules")
+        .long_about(help::BENCH_LONG_HELP)
+        .arg(
+            Arg::new("[NAMESPACE:]RULES_PATH")
+                .required(true)
+                .help("Path to a YARA source file or directory (optionally prefixed with a namespace)")
+                .value_parser(path_with_namespace_parser)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(<TARGET_PATH>)
+                .help("Path to the file or directory that will be scanned")
+                .value_parser(value_parser!(PathBuf))
+        )
+        // Keep options sorted alphabetically by their long name.
+        // For instance, --bar goes before --foo.
+        .arg(
+            arg!(-d --"define")
+                .help("Define external variable")
+                .long_help(help::DEFINE_LONG_HELP)
+                .value_name("VAR=VALUE")
+                .value_parser(external_var_parser)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(-w --"disable-warnings" [WARNING_ID])
+                .help("Disable warnings")
+                .long_help(help::DISABLE_WARNINGS_LONG_HELP)
+                .default_missing_value("all")
+                .num_args(0..)
+                .require_equals(true)
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(--"ignore-module" <MODULE>)
+                .help("Ignore rules that use the specified module")
+                .long_help(help::IGNORE_MODULE_LONG_HELP)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(-n --"iterations" <N>)
+                .help("Scan each file N times")
+                .default_value("1")
+                .value_parser(value_parser!(u32).range(1..))
+        )
+        .arg(
+            arg!(-o --"output-format" <FORMAT>)
+                .help("Output format for results")
+                .value_parser(value_parser!(OutputFormats))
+        )
+        .arg(
+            arg!(--"path-as-namespace")
+                .help("Use file path as rule namespace")
+        )
+        .arg(
+            arg!(--"relaxed-re-syntax")
+                .help("Use a more relaxed syntax check while parsing regular expressions")
+        )
+        .arg(
+            arg!(--"rules-cache" <DIR>)
+                .help("Cache the compiled rules in the given directory")
+                .long_help(help::RULES_CACHE_LONG_HELP)
+                .value_parser(value_parser!(PathBuf))
+        )
+        .arg(
+            arg!(--"scan-list")
+                .help("Indicate that TARGET_PATH is a file containing the paths to be scanned")
+                .long_help(help::SCAN_LIST_LONG_HELP)
+        )
+        .arg(
+            arg!(-r --"top-rules" <N>)
+                .help("Number of rules shown in the ranking of most expensive rules")
+                .default_value("10")
+                .value_parser(value_parser!(usize))
+        )
+}
+
+pub fn exec_bench(args: &ArgMatches) -> anyhow::Result<()> {
+    let rules_path = args
+        .get_many::<(Option<String>, PathBuf)>("[NAMESPACE:]RULES_PATH")
+        .unwrap();
+
+    let target_path = args.get_one::<PathBuf>("TARGET_PATH").unwrap();
+    let iterations = *args.get_one::<u32>("iterations").unwrap();
+    let scan_list = args.get_flag("scan-list");
+
+    #[cfg(feature = "rules-profiling")]
+    let top_rules = *args.get_one::<usize>("top-rules").unwrap();
+
+    let external_vars: Option<Vec<(String, serde_json::Value)>> = args
+        .get_many::<(String, serde_json::Value)>("define")
+        .map(|var| var.cloned().collect());
+
+    let start = Instant::now();
+    let rules = compile_rules(rules_path, external_vars, false, args)?;
+    let compilation_time = start.elapsed();
+
+    let serialized_size = rules.serialize()?.len();
+
+    let mut scanner = Scanner::new(&rules);
+    let mut num_files = 0_usize;
+    let mut num_bytes = 0_usize;
+    let mut scan_time = Duration::ZERO;
+
+    let w = if scan_list {
+        Walker::file_list(target_path)
+    } else {
+        Walker::path(target_path)
+    };
+
+    // Files are scanned one by one in a single thread, and only the time
+    // spent in the scan itself is measured, not the time spent reading
+    // the files.
+    w.walk(
+        |file_path| {
+            let data = fs::read(file_path).with_context(|| {
+                format!("can not read `{}`", file_path.display())
+            })?;
+
+            for _ in 0..iterations {
+                let start = Instant::now();
+                scanner
+                    .scan(data.as_slice())
+                    .with_context(|| format!("scanning {:?}", file_path))?;
+                scan_time += start.elapsed();
+            }
+
+            num_files += 1;
+            num_bytes += data.len() * iterations as usize;
+
+            Ok(())
+        },
+        |err| {
+            eprintln!("{} {}", "error:".paint(Red).bold(), err);
+            Ok(())
+        },
+    )?;
+
+    let throughput = if scan_time.is_zero() {
+        0.0
+    } else {
+        num_bytes as f64 / 1_000_000.0 / scan_time.as_secs_f64()
+    };
+
+    #[cfg(feature = "rules-profiling")]
+    let most_expensive_rules = scanner.most_expensive_rules(top_rules);
+
+    match args.get_one::<OutputFormats>("output-format") {
+        Some(OutputFormats::Json) => {
+            #[allow(unused_mut)]
+            let mut json = serde_json::json!({
+                "compilation_time": compilation_time.as_secs_f64(),
+                "serialized_size": serialized_size,
+                "iterations": iterations,
+                "num_files": num_files,
+                "num_bytes": num_bytes,
+                "scan_time": scan_time.as_secs_f64(),
+                "throughput": throughput,
+            });
+
+            #[cfg(feature = "rules-profiling")]
+            {
+                json["most_expensive_rules"] = most_expensive_rules
+                    .iter()
+                    .map(|profile| {
+                        serde_json::json!({
+                            "namespace": profile.namespace(),
+                            "rule": profile.rule(),
+                            "time": profile
+                                .pattern_matching_time()
+                                .as_secs_f64(),
+                        })
+                    })
+                    .collect();
+            }
+
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+        Some(OutputFormats::Text) | None => {
+            println!(
+                "Compilation time:  {:.3}s",
+                compilation_time.as_secs_f64()
+            );
+            println!("Serialized size:   {} bytes", serialized_size);
+            println!("Files scanned:     {}", num_files);
+            println!("Bytes scanned:     {}", num_bytes);
+            println!("Scan time:         {:.3}s", scan_time.as_secs_f64());
+            println!("Throughput:        {:.2} MB/s", throughput);
+
+            #[cfg(feature = "rules-profiling")]
+            {
+                println!("\nMost expensive rules:");
+                for profile in most_expensive_rules {
+                    println!(
+                        "  {:>10.6}s  {}.{}",
+                        profile.pattern_matching_time().as_secs_f64(),
+                        profile.namespace(),
+                        profile.rule()
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -11,6 +11,7 @@ use yansi::Paint;
 use yara_x::SourceCode;
 
 use crate::walk::Message;
+use crate::watch::watch;
 use crate::{help, walk};
 
 pub fn check() -> Command {
@@ -45,10 +46,25 @@ pub fn check() -> Command {
                 .required(false)
                 .value_parser(value_parser!(u8).range(1..)),
         )
+        .arg(
+            arg!(--"watch")
+                .help("Check again every time the source files change")
+                .long_help(help::WATCH_LONG_HELP),
+        )
 }
 
 pub fn exec_check(args: &ArgMatches) -> anyhow::Result<()> {
     let rules_path = args.get_one::<PathBuf>("RULES_PATH").unwrap();
+
+    if args.get_flag("watch") {
+        watch(&[rules_path.as_path()], || check_files(args))
+    } else {
+        check_files(args)
+    }
+}
+
+fn check_files(args: &ArgMatches) -> anyhow::Result<()> {
+    let rules_path = args.get_one::<PathBuf>("RULES_PATH").unwrap();
     let max_depth = args.get_one::<u16>("max-depth");
     let filters = args.get_many::<String>("filter");
     let num_threads = args.get_one::<u8>("threads");
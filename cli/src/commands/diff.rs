@@ -0,0 +1,281 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::Context;
+use clap::{arg, value_parser, Arg, ArgAction, ArgMatches, Command};
+use superconsole::{Component, Line, Lines, Span};
+use yansi::Color::{Green, Red, Yellow};
+use yansi::Paint;
+use yara_x::{Rule, Scanner};
+
+use crate::commands::{
+    compile_rules, external_var_parser, path_with_namespace_parser,
+};
+use crate::walk::Message;
+use crate::{help, walk};
+
+#[rustfmt::skip]
+pub fn diff() -> Command {
+    super::command("diff")
+        .about("Compare the results of two sets of rules")
+        .long_about(help::DIFF_LONG_HELP)
+        .arg(
+            Arg::new("[NAMESPACE:]OLD_RULES_PATH")
+                .required(true)
+                .help("Path to the YARA source file or directory with the old rules (optionally prefixed with a namespace)")
+                .value_parser(path_with_namespace_parser)
+        )
+        .arg(
+            Arg::new("[NAMESPACE:]NEW_RULES_PATH")
+                .required(true)
+                .help("Path to the YARA source file or directory with the new rules (optionally prefixed with a namespace)")
+                .value_parser(path_with_namespace_parser)
+        )
+        .arg(
+            arg!(<TARGET_PATH>)
+                .help("Path to the file or directory that will be scanned")
+                .value_parser(value_parser!(PathBuf))
+        )
+        // Keep options sorted alphabetically by their long name.
+        // For instance, --bar goes before --foo.
+        .arg(
+            arg!(-d --"define")
+                .help("Define external variable")
+                .long_help(help::DEFINE_LONG_HELP)
+                .value_name("VAR=VALUE")
+                .value_parser(external_var_parser)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(-w --"disable-warnings" [WARNING_ID])
+                .help("Disable warnings")
+                .long_help(help::DISABLE_WARNINGS_LONG_HELP)
+                .default_missing_value("all")
+                .num_args(0..)
+                .require_equals(true)
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(--"ignore-module" <MODULE>)
+                .help("Ignore rules that use the specified module")
+                .long_help(help::IGNORE_MODULE_LONG_HELP)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(--"path-as-namespace")
+                .help("Use file path as rule namespace")
+        )
+        .arg(
+            arg!(--"relaxed-re-syntax")
+                .help("Use a more relaxed syntax check while parsing regular expressions")
+        )
+        .arg(
+            arg!(--"rules-cache" <DIR>)
+                .help("Cache the compiled rules in the given directory")
+                .long_help(help::RULES_CACHE_LONG_HELP)
+                .value_parser(value_parser!(PathBuf))
+        )
+        .arg(
+            arg!(--"scan-list")
+                .help("Indicate that TARGET_PATH is a file containing the paths to be scanned")
+                .long_help(help::SCAN_LIST_LONG_HELP)
+        )
+        .arg(
+            arg!(-z --"skip-larger" <FILE_SIZE>)
+                .help("Skip files larger than the given size")
+                .value_parser(value_parser!(u64))
+        )
+        .arg(
+            arg!(-p --"threads" <NUM_THREADS>)
+                .help("Use the given number of threads")
+                .long_help(help::THREADS_LONG_HELP)
+                .value_parser(value_parser!(u8).range(1..))
+        )
+}
+
+pub fn exec_diff(args: &ArgMatches) -> anyhow::Result<()> {
+    let old_rules_path = args
+        .get_one::<(Option<String>, PathBuf)>("[NAMESPACE:]OLD_RULES_PATH")
+        .unwrap();
+
+    let new_rules_path = args
+        .get_one::<(Option<String>, PathBuf)>("[NAMESPACE:]NEW_RULES_PATH")
+        .unwrap();
+
+    let target_path = args.get_one::<PathBuf>("TARGET_PATH").unwrap();
+    let num_threads = args.get_one::<u8>("threads");
+    let skip_larger = args.get_one::<u64>("skip-larger");
+    let scan_list = args.get_flag("scan-list");
+
+    let external_vars: Option<Vec<(String, serde_json::Value)>> = args
+        .get_many::<(String, serde_json::Value)>("define")
+        .map(|var| var.cloned().collect());
+
+    let old_rules = compile_rules(
+        std::iter::once(old_rules_path),
+        external_vars.clone(),
+        false,
+        args,
+    )
+    .context("compiling old rules")?;
+
+    let new_rules = compile_rules(
+        std::iter::once(new_rules_path),
+        external_vars,
+        false,
+        args,
+    )
+    .context("compiling new rules")?;
+
+    let old_rules_ref = &old_rules;
+    let new_rules_ref = &new_rules;
+
+    // Number of files gained and lost by each rule.
+    let rule_changes: Mutex<BTreeMap<String, (usize, usize)>> =
+        Mutex::new(BTreeMap::new());
+
+    let rule_changes_ref = &rule_changes;
+
+    let mut w = if scan_list {
+        walk::ParWalker::file_list(target_path)
+    } else {
+        walk::ParWalker::path(target_path)
+    };
+
+    if let Some(num_threads) = num_threads {
+        w.num_threads(*num_threads);
+    }
+
+    if let Some(max_file_size) = skip_larger {
+        w.metadata_filter(|metadata| metadata.len() <= *max_file_size);
+    }
+
+    w.walk(
+        DiffState::new(),
+        |_, _| (Scanner::new(old_rules_ref), Scanner::new(new_rules_ref)),
+        |state, output, file_path, (old_scanner, new_scanner)| {
+            let old_matches: BTreeSet<String> = old_scanner
+                .scan_file(file_path.as_path())
+                .with_context(|| format!("scanning {:?}", &file_path))?
+                .matching_rules()
+                .map(|rule| full_name(&rule))
+                .collect();
+
+            let new_matches: BTreeSet<String> = new_scanner
+                .scan_file(file_path.as_path())
+                .with_context(|| format!("scanning {:?}", &file_path))?
+                .matching_rules()
+                .map(|rule| full_name(&rule))
+                .collect();
+
+            state.num_scanned_files.fetch_add(1, Ordering::Relaxed);
+
+            if old_matches == new_matches {
+                return Ok(());
+            }
+
+            state.num_changed_files.fetch_add(1, Ordering::Relaxed);
+
+            let verdict =
+                match (old_matches.is_empty(), new_matches.is_empty()) {
+                    (true, false) => " (now matching)",
+                    (false, true) => " (no longer matching)",
+                    _ => "",
+                };
+
+            let mut lines = vec![format!(
+                "[ {} ] {}{}",
+                "CHANGED".paint(Yellow).bold(),
+                file_path.display(),
+                verdict,
+            )];
+
+            let mut rule_changes = rule_changes_ref.lock().unwrap();
+
+            for rule in new_matches.difference(&old_matches) {
+                rule_changes.entry(rule.clone()).or_default().0 += 1;
+                lines.push(format!("  {} {}", "+".paint(Green), rule));
+            }
+
+            for rule in old_matches.difference(&new_matches) {
+                rule_changes.entry(rule.clone()).or_default().1 += 1;
+                lines.push(format!("  {} {}", "-".paint(Red), rule));
+            }
+
+            output.send(Message::Info(lines.join("\n")))?;
+
+            Ok(())
+        },
+        |err, output| {
+            let _ = output.send(Message::Error(format!(
+                "{} {}",
+                "error:".paint(Red).bold(),
+                err
+            )));
+
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    let rule_changes = rule_changes.into_inner().unwrap();
+
+    if !rule_changes.is_empty() {
+        println!("\nRules with changes:");
+        for (rule, (gained, lost)) in rule_changes {
+            println!(
+                "  {}  {}  {}",
+                format!("+{}", gained).paint(Green),
+                format!("-{}", lost).paint(Red),
+                rule
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the name of a rule qualified with its namespace.
+fn full_name(rule: &Rule) -> String {
+    format!("{}.{}", rule.namespace(), rule.identifier())
+}
+
+struct DiffState {
+    num_scanned_files: AtomicUsize,
+    num_changed_files: AtomicUsize,
+}
+
+impl DiffState {
+    fn new() -> Self {
+        Self {
+            num_scanned_files: AtomicUsize::new(0),
+            num_changed_files: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Component for DiffState {
+    fn draw_unchecked(
+        &self,
+        _dimensions: superconsole::Dimensions,
+        _mode: superconsole::DrawMode,
+    ) -> anyhow::Result<Lines> {
+        let scanned = format!(
+            "{} file(s) scanned. ",
+            self.num_scanned_files.load(Ordering::Relaxed)
+        );
+
+        let changed = format!(
+            "{} file(s) changed.",
+            self.num_changed_files.load(Ordering::Relaxed)
+        );
+
+        Ok(Lines(vec![Line::from_iter([
+            Span::new_unstyled(scanned.paint(Green).bold())?,
+            Span::new_unstyled(changed.paint(Yellow).bold())?,
+        ])]))
+    }
+}
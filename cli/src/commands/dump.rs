@@ -20,6 +20,13 @@ enum SupportedModules {
     Elf,
     Pe,
     Dotnet,
+    Dex,
+    Pdf,
+    Olevba,
+    Wasm,
+    Gobin,
+    Javaclass,
+    Pyc,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -96,30 +103,62 @@ pub fn exec_dump(args: &ArgMatches) -> anyhow::Result<()> {
         // those that weren't explicitly asked for.
         let requested_modules: Vec<_> = modules.collect();
 
+        if !requested_modules.contains(&&SupportedModules::Dex) {
+            module_output.dex = MessageField::none()
+        }
         if !requested_modules.contains(&&SupportedModules::Dotnet) {
             module_output.dotnet = MessageField::none()
         }
         if !requested_modules.contains(&&SupportedModules::Elf) {
             module_output.elf = MessageField::none()
         }
+        if !requested_modules.contains(&&SupportedModules::Gobin) {
+            module_output.gobin = MessageField::none()
+        }
+        if !requested_modules.contains(&&SupportedModules::Javaclass) {
+            module_output.javaclass = MessageField::none()
+        }
         if !requested_modules.contains(&&SupportedModules::Lnk) {
             module_output.lnk = MessageField::none()
         }
         if !requested_modules.contains(&&SupportedModules::Macho) {
             module_output.macho = MessageField::none()
         }
+        if !requested_modules.contains(&&SupportedModules::Olevba) {
+            module_output.olevba = MessageField::none()
+        }
+        if !requested_modules.contains(&&SupportedModules::Pdf) {
+            module_output.pdf = MessageField::none()
+        }
         if !requested_modules.contains(&&SupportedModules::Pe) {
             module_output.pe = MessageField::none()
         }
+        if !requested_modules.contains(&&SupportedModules::Pyc) {
+            module_output.pyc = MessageField::none()
+        }
+        if !requested_modules.contains(&&SupportedModules::Wasm) {
+            module_output.wasm = MessageField::none()
+        }
     } else {
         // Module was not specified, only show those that produced meaningful
         // results, the rest are cleared out.
+        if !module_output.dex.is_dex() {
+            module_output.dex = MessageField::none()
+        }
         if !module_output.dotnet.is_dotnet() {
             module_output.dotnet = MessageField::none()
         }
         if !module_output.elf.has_type() {
             module_output.elf = MessageField::none()
         }
+        if !module_output.gobin.is_go() {
+            module_output.gobin = MessageField::none()
+        }
+        if !module_output.javaclass.is_class()
+            && !module_output.javaclass.is_jar()
+        {
+            module_output.javaclass = MessageField::none()
+        }
         if !module_output.lnk.is_lnk() {
             module_output.lnk = MessageField::none()
         }
@@ -128,9 +167,21 @@ pub fn exec_dump(args: &ArgMatches) -> anyhow::Result<()> {
         {
             module_output.macho = MessageField::none()
         }
+        if !module_output.olevba.is_ole() && !module_output.olevba.is_ooxml() {
+            module_output.olevba = MessageField::none()
+        }
+        if !module_output.pdf.is_pdf() {
+            module_output.pdf = MessageField::none()
+        }
         if !module_output.pe.is_pe() {
             module_output.pe = MessageField::none()
         }
+        if !module_output.pyc.is_pyc() {
+            module_output.pyc = MessageField::none()
+        }
+        if !module_output.wasm.is_wasm() {
+            module_output.wasm = MessageField::none()
+        }
     }
 
     match output_format {
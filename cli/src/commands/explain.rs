@@ -0,0 +1,180 @@
+use std::ops::Range;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{arg, value_parser, Arg, ArgAction, ArgMatches, Command};
+use itertools::Itertools;
+use yansi::Color::{BrightBlack, Green, Primary, Red, Yellow};
+use yansi::{Color, Paint};
+use yara_x::{Rule, Scanner, TracedExpr};
+
+use crate::commands::{
+    compile_rules, external_var_parser, path_with_namespace_parser,
+};
+use crate::help;
+
+#[rustfmt::skip]
+pub fn explain() -> Command {
+    super::command("explain")
+        .about("Explain why rules match or don't match a file")
+        .long_about(help::EXPLAIN_LONG_HELP)
+        .arg(
+            Arg::new("[NAMESPACE:]RULES_PATH")
+                .required(true)
+                .help("Path to a YARA source file or directory (optionally prefixed with a namespace)")
+                .value_parser(path_with_namespace_parser)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(<TARGET_PATH>)
+                .help("Path to the file that will be scanned")
+                .value_parser(value_parser!(PathBuf))
+        )
+        // Keep options sorted alphabetically by their long name.
+        // For instance, --bar goes before --foo.
+        .arg(
+            arg!(-d --"define")
+                .help("Define external variable")
+                .long_help(help::DEFINE_LONG_HELP)
+                .value_name("VAR=VALUE")
+                .value_parser(external_var_parser)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(-w --"disable-warnings" [WARNING_ID])
+                .help("Disable warnings")
+                .long_help(help::DISABLE_WARNINGS_LONG_HELP)
+                .default_missing_value("all")
+                .num_args(0..)
+                .require_equals(true)
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(--"ignore-module" <MODULE>)
+                .help("Ignore rules that use the specified module")
+                .long_help(help::IGNORE_MODULE_LONG_HELP)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(--"path-as-namespace")
+                .help("Use file path as rule namespace")
+        )
+        .arg(
+            arg!(--"relaxed-re-syntax")
+                .help("Use a more relaxed syntax check while parsing regular expressions")
+        )
+        .arg(
+            arg!(-r --"rule" <RULE>)
+                .help("Explain only the rule with the given name")
+                .action(ArgAction::Append)
+        )
+}
+
+pub fn exec_explain(args: &ArgMatches) -> anyhow::Result<()> {
+    let rules_path = args
+        .get_many::<(Option<String>, PathBuf)>("[NAMESPACE:]RULES_PATH")
+        .unwrap();
+
+    let target_path = args.get_one::<PathBuf>("TARGET_PATH").unwrap();
+
+    let only_rules: Vec<&String> =
+        args.get_many::<String>("rule").into_iter().flatten().collect();
+
+    let external_vars: Option<Vec<(String, serde_json::Value)>> = args
+        .get_many::<(String, serde_json::Value)>("define")
+        .map(|var| var.cloned().collect());
+
+    let rules = compile_rules(rules_path, external_vars, true, args)?;
+    let mut scanner = Scanner::new(&rules);
+
+    let scan_results = scanner
+        .scan_file(target_path.as_path())
+        .with_context(|| format!("scanning {:?}", target_path))?;
+
+    let matching_rules =
+        scan_results.matching_rules().map(|rule| (rule, true));
+
+    let non_matching_rules =
+        scan_results.non_matching_rules().map(|rule| (rule, false));
+
+    for (rule, matched) in matching_rules.chain(non_matching_rules) {
+        if only_rules.is_empty()
+            || only_rules.iter().any(|r| r.as_str() == rule.identifier())
+        {
+            print_rule_trace(&rule, matched);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the values taken by the sub-expressions in a rule's condition.
+///
+/// Each sub-expression is indented according to its nesting level within
+/// the condition.
+fn print_rule_trace(rule: &Rule, matched: bool) {
+    if matched {
+        println!("{} {}", rule.identifier(), "[matched]".paint(Green));
+    } else {
+        println!("{} {}", rule.identifier(), "[not matched]".paint(Red));
+    }
+
+    let traced_exprs: Vec<TracedExpr> = rule.condition_trace().collect();
+
+    let values: Vec<(String, Color)> =
+        traced_exprs.iter().map(describe_value).collect();
+
+    let width = values.iter().map(|(value, _)| value.len()).max().unwrap_or(0);
+
+    // Spans of the expressions that enclose the current one.
+    let mut enclosing: Vec<Range<usize>> = Vec::new();
+
+    for (expr, (value, color)) in traced_exprs.iter().zip(values) {
+        let span = expr.span();
+
+        while enclosing.last().is_some_and(|outer| {
+            span.start < outer.start || span.end > outer.end
+        }) {
+            enclosing.pop();
+        }
+
+        println!(
+            "  {}  {:indent$}{}",
+            format!("{:width$}", value).paint(color),
+            "",
+            expr.source().split_whitespace().join(" "),
+            indent = enclosing.len() * 2,
+        );
+
+        enclosing.push(span);
+    }
+
+    println!();
+}
+
+/// Returns a description of the values taken by an expression, and the
+/// color used for printing the description.
+fn describe_value(expr: &TracedExpr) -> (String, Color) {
+    let counts: Vec<(&str, usize, Color)> = [
+        ("true", expr.times_true(), Green),
+        ("false", expr.times_false(), Red),
+        ("undefined", expr.times_undefined(), Yellow),
+    ]
+    .into_iter()
+    .filter(|(_, count, _)| *count > 0)
+    .collect();
+
+    match counts.as_slice() {
+        [] => ("not evaluated".to_string(), BrightBlack),
+        [(value, 1, color)] => (value.to_string(), *color),
+        [(value, count, color)] => (format!("{} ({})", value, count), *color),
+        _ => (
+            counts
+                .iter()
+                .map(|(value, count, _)| format!("{} ({})", value, count))
+                .join(", "),
+            Primary,
+        ),
+    }
+}
@@ -31,7 +31,7 @@ pub fn fix_encoding() -> Command {
         )
         // Keep options sorted alphabetically by their long name.
         // For instance, --bar goes before --foo.
-        .arg(arg!(-d - -"dry-run").help("Don't modify source files"))
+        .arg(arg!(--"dry-run").help("Don't modify source files"))
         .arg(
             arg!(-f --filter <PATTERN>)
                 .help("Check files that match the given pattern only")
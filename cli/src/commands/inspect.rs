@@ -0,0 +1,127 @@
+use std::fs::File;
+use std::io::stdin;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{arg, value_parser, ArgMatches, Command, ValueEnum};
+use itertools::Itertools;
+use yara_x::{MetaValue, Rule, Rules};
+
+use crate::help;
+
+#[derive(Clone, ValueEnum)]
+enum OutputFormats {
+    /// Default output format.
+    Text,
+    /// JSON object with the information about the rules.
+    Json,
+}
+
+#[rustfmt::skip]
+pub fn inspect() -> Command {
+    super::command("inspect")
+        .about("Show information about compiled rules")
+        .long_about(help::INSPECT_LONG_HELP)
+        .arg(
+            arg!(<RULES_PATH>)
+                .help("Path to a file with compiled rules, or - for reading them from stdin")
+                .value_parser(value_parser!(PathBuf))
+        )
+        // Keep options sorted alphabetically by their long name.
+        // For instance, --bar goes before --foo.
+        .arg(
+            arg!(-o --"output-format" <FORMAT>)
+                .help("Output format")
+                .value_parser(value_parser!(OutputFormats))
+        )
+}
+
+pub fn exec_inspect(args: &ArgMatches) -> anyhow::Result<()> {
+    let rules_path = args.get_one::<PathBuf>("RULES_PATH").unwrap();
+
+    let rules = if rules_path.as_os_str() == "-" {
+        Rules::deserialize_from(stdin())?
+    } else {
+        let file = File::open(rules_path)
+            .with_context(|| format!("can not open {:?}", &rules_path))?;
+
+        Rules::deserialize_from(file)
+            .with_context(|| format!("can not load {:?}", &rules_path))?
+    };
+
+    match args.get_one::<OutputFormats>("output-format") {
+        Some(OutputFormats::Json) => {
+            let json = serde_json::json!({
+                "yara_x_version": rules.yara_x_version(),
+                "imports": rules.imports().collect::<Vec<_>>(),
+                "rules": rules
+                    .iter()
+                    .map(|rule| {
+                        serde_json::json!({
+                            "namespace": rule.namespace(),
+                            "identifier": rule.identifier(),
+                            "global": rule.is_global(),
+                            "private": rule.is_private(),
+                            "tags": rule
+                                .tags()
+                                .map(|tag| tag.identifier())
+                                .collect::<Vec<_>>(),
+                            "version": rule_version(&rule),
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+            });
+
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+        Some(OutputFormats::Text) | None => {
+            println!("YARA-X version: {}", rules.yara_x_version());
+            println!("Imports:        {}", rules.imports().join(", "));
+            println!("Rules:          {}", rules.iter().len());
+            println!();
+
+            for rule in rules.iter() {
+                let mut line =
+                    format!("{}.{}", rule.namespace(), rule.identifier());
+
+                if rule.is_global() {
+                    line.push_str(" global");
+                }
+
+                if rule.is_private() {
+                    line.push_str(" private");
+                }
+
+                let tags = rule.tags();
+
+                if !tags.is_empty() {
+                    line.push_str(&format!(
+                        " [{}]",
+                        tags.map(|tag| tag.identifier()).join(",")
+                    ));
+                }
+
+                if let Some(version) = rule_version(&rule) {
+                    line.push_str(&format!(" version={}", version));
+                }
+
+                println!("{}", line);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the value of the `version` metadata of a rule, if any.
+fn rule_version(rule: &Rule) -> Option<String> {
+    rule.metadata().find(|(ident, _)| *ident == "version").map(|(_, value)| {
+        match value {
+            MetaValue::Integer(v) => v.to_string(),
+            MetaValue::Float(v) => v.to_string(),
+            MetaValue::Bool(v) => v.to_string(),
+            MetaValue::String(v) => v.to_string(),
+            MetaValue::Bytes(v) => v.to_string(),
+        }
+    })
+}
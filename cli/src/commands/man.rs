@@ -0,0 +1,32 @@
+use crate::commands::cli;
+use crate::help;
+use anyhow::Context;
+use clap::{arg, value_parser, ArgMatches, Command};
+use clap_mangen::Man;
+use std::io;
+use std::path::PathBuf;
+
+pub fn man() -> Command {
+    super::command("man")
+        .about("Output the manual page in roff format")
+        .long_about(help::MAN_LONG_HELP)
+        .arg(
+            arg!(-o --"output-dir" <OUTPUT_DIR>)
+                .help("Write manual pages for all commands to the given directory")
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+pub fn exec_man(args: &ArgMatches) -> anyhow::Result<()> {
+    let cli = cli().name("yr");
+
+    if let Some(output_dir) = args.get_one::<PathBuf>("output-dir") {
+        clap_mangen::generate_to(cli, output_dir).with_context(|| {
+            format!("can not write to `{}`", output_dir.display())
+        })?;
+    } else {
+        Man::new(cli).render(&mut io::stdout())?;
+    }
+
+    Ok(())
+}
@@ -1,32 +1,44 @@
+mod bench;
 mod check;
 mod compile;
 mod completion;
 mod debug;
+mod diff;
 mod dump;
+mod explain;
 mod fix;
 mod fmt;
+mod inspect;
+mod man;
 mod scan;
+mod test;
 
+pub use bench::*;
 pub use check::*;
 pub use compile::*;
 pub use completion::*;
 #[cfg(feature = "debug-cmd")]
 pub use debug::*;
+pub use diff::*;
 pub use dump::*;
+pub use explain::*;
 pub use fix::*;
 pub use fmt::*;
+pub use inspect::*;
+pub use man::*;
 pub use scan::*;
+pub use test::*;
 
 use std::borrow::Cow;
 use std::fs;
-use std::io::stdout;
-use std::path::PathBuf;
+use std::io::{stdin, stdout, Read};
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, bail, Context};
 use clap::{command, crate_authors, ArgMatches, Command};
 use crossterm::tty::IsTty;
 use superconsole::{Component, Line, Lines, Span, SuperConsole};
-use yansi::Color::Green;
+use yansi::Color::{Green, Yellow};
 use yansi::Paint;
 
 use crate::{commands, APP_HELP_TEMPLATE};
@@ -53,13 +65,19 @@ pub fn cli() -> Command {
         .subcommands(vec![
             commands::scan(),
             commands::compile(),
+            commands::inspect(),
             commands::check(),
             #[cfg(feature = "debug-cmd")]
             commands::debug(),
+            commands::diff(),
             commands::dump(),
+            commands::bench(),
+            commands::explain(),
+            commands::test(),
             commands::fmt(),
             commands::fix(),
             commands::completion(),
+            commands::man(),
         ])
 }
 
@@ -108,6 +126,11 @@ fn meta_file_value_parser(
 fn path_with_namespace_parser(
     input: &str,
 ) -> Result<(Option<String>, PathBuf), anyhow::Error> {
+    // URLs are never prefixed by a namespace, except if the namespace is
+    // explicitly specified, as in `namespace:https://example.com/rules.yar`.
+    if is_url(input) {
+        return Ok((None, PathBuf::from(input)));
+    }
     if let Some((namespace, path)) = input.split_once(':') {
         Ok((Some(namespace.to_string()), PathBuf::from(path)))
     } else {
@@ -118,6 +141,7 @@ fn path_with_namespace_parser(
 pub fn compile_rules<'a, P>(
     paths: P,
     external_vars: Option<Vec<(String, serde_json::Value)>>,
+    condition_tracing: bool,
     args: &ArgMatches,
 ) -> Result<Rules, anyhow::Error>
 where
@@ -127,6 +151,7 @@ where
 
     compiler
         .relaxed_re_syntax(args.get_flag("relaxed-re-syntax"))
+        .condition_tracing(condition_tracing)
         .colorize_errors(stdout().is_tty());
 
     for m in args.get_many::<String>("ignore-module").into_iter().flatten() {
@@ -156,82 +181,227 @@ where
         }
     }
 
+    // The cache key includes the compiler options and global variables, so
+    // the cache is created after setting them.
+    let mut cache = args
+        .try_get_one::<PathBuf>("rules-cache")
+        .ok()
+        .flatten()
+        .map(|dir| compiler.rules_cache(dir));
+
+    // When the cache is enabled, the source files are read before compiling
+    // them, as the key for looking up the rules in the cache depends on all
+    // of them. Otherwise, each source file is compiled as soon as it's read.
+    let mut sources: Vec<(String, String, Vec<u8>)> = Vec::new();
+
     let mut console =
         if stdout().is_tty() { SuperConsole::new() } else { None };
 
     let mut state = CompileState::new();
 
+    let result =
+        read_sources(paths, args, |namespace, origin, src| {
+            match cache.as_mut() {
+                Some(cache) => {
+                    cache.add_source(
+                        namespace,
+                        SourceCode::from(src.as_slice()).with_origin(origin),
+                    );
+                    sources.push((
+                        namespace.to_string(),
+                        origin.to_string(),
+                        src,
+                    ));
+                }
+                None => compile_source(
+                    &mut compiler,
+                    &mut state,
+                    console.as_mut(),
+                    namespace,
+                    origin,
+                    &src,
+                ),
+            }
+        });
+
+    if let Err(err) = result {
+        if let Some(console) = console {
+            console.finalize(&state).unwrap();
+        }
+        return Err(err);
+    }
+
+    if let Some(cache) = &cache {
+        if let Some(rules) = cache.load() {
+            if let Some(console) = console {
+                console.finalize(&state).unwrap();
+            }
+            return Ok(rules);
+        }
+        for (namespace, origin, src) in &sources {
+            compile_source(
+                &mut compiler,
+                &mut state,
+                console.as_mut(),
+                namespace,
+                origin,
+                src,
+            );
+        }
+    }
+
+    if let Some(console) = console {
+        console.finalize(&state).unwrap();
+    }
+
+    for error in compiler.errors() {
+        eprintln!("{}", error);
+    }
+
+    for warning in compiler.warnings() {
+        eprintln!("{}", warning);
+    }
+
+    if !compiler.errors().is_empty() {
+        bail!("{} errors found", compiler.errors().len());
+    }
+
+    let rules = compiler.build();
+
+    // Failing to store the rules in the cache is not an error, the rules
+    // will be compiled again the next time.
+    if let Some(cache) = cache {
+        if let Err(err) = cache.store(&rules) {
+            eprintln!(
+                "{} can not store rules in cache: {}",
+                "warning:".paint(Yellow).bold(),
+                err
+            );
+        }
+    }
+
+    Ok(rules)
+}
+
+/// Reads the source files in `paths`, walking the directories, and calls
+/// `f` for each of them with the namespace the file goes into, the file's
+/// origin and its content.
+fn read_sources<'a, P, F>(
+    paths: P,
+    args: &ArgMatches,
+    mut f: F,
+) -> Result<(), anyhow::Error>
+where
+    P: Iterator<Item = &'a (Option<String>, PathBuf)>,
+    F: FnMut(&str, &str, Vec<u8>),
+{
     for (namespace, path) in paths {
+        let namespace = namespace
+            .as_ref()
+            .map(|namespace| namespace.as_str())
+            .unwrap_or("default");
+
+        // Rules read from stdin or downloaded from a URL are not in the
+        // file system, and don't need to be walked.
+        if let Some(src) = read_rules_source(path)? {
+            f(namespace, path.to_string_lossy().as_ref(), src);
+            continue;
+        }
+
         let mut w = Walker::path(path);
 
         w.filter("**/*.yar");
         w.filter("**/*.yara");
 
-        compiler.new_namespace(
-            namespace
-                .as_ref()
-                .map(|namespace| namespace.as_str())
-                .unwrap_or("default"),
-        );
-
-        if let Err(err) = w.walk(
+        w.walk(
             |file_path| {
-                state.file_in_progress = Some(file_path.into());
-
-                if let Some(console) = console.as_mut() {
-                    console.render(&state).unwrap();
-                }
-
                 let src = fs::read(file_path).with_context(|| {
                     format!("can not read `{}`", file_path.display())
                 })?;
 
-                let src = SourceCode::from(src.as_slice())
-                    .with_origin(file_path.as_os_str().to_str().unwrap());
+                let origin = file_path.as_os_str().to_str().unwrap();
 
                 if args.get_flag("path-as-namespace") {
-                    compiler
-                        .new_namespace(file_path.to_string_lossy().as_ref());
+                    f(file_path.to_string_lossy().as_ref(), origin, src);
+                } else {
+                    f(namespace, origin, src);
                 }
 
-                let _ = compiler.add_source(src);
-
-                state.file_in_progress = None;
-
-                state.num_compiled_files =
-                    state.num_compiled_files.saturating_add(1);
-
                 Ok(())
             },
             // Any error occurred during walk is aborts the walk.
             Err,
-        ) {
-            if let Some(console) = console {
-                console.finalize(&state).unwrap();
-            }
-            return Err(err);
-        }
+        )?;
     }
 
+    Ok(())
+}
+
+/// Adds a source file to the compiler, updating the progress shown in
+/// the console.
+fn compile_source(
+    compiler: &mut Compiler,
+    state: &mut CompileState,
+    console: Option<&mut SuperConsole>,
+    namespace: &str,
+    origin: &str,
+    src: &[u8],
+) {
+    state.file_in_progress = Some(origin.into());
+
     if let Some(console) = console {
-        console.finalize(&state).unwrap();
+        console.render(state).unwrap();
     }
 
-    for error in compiler.errors() {
-        eprintln!("{}", error);
-    }
+    compiler.new_namespace(namespace);
 
-    for warning in compiler.warnings() {
-        eprintln!("{}", warning);
+    let _ = compiler.add_source(SourceCode::from(src).with_origin(origin));
+
+    state.file_in_progress = None;
+    state.num_compiled_files = state.num_compiled_files.saturating_add(1);
+}
+
+/// Returns true if `path` is a `https://` or `http://` URL.
+fn is_url(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// Reads the source code for rules that are not in the file system.
+///
+/// If `path` is `-` the rules are read from stdin, and if it is a URL they
+/// are downloaded (this requires the `remote-rules` feature). Returns `None`
+/// for any other path.
+fn read_rules_source(path: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    let path = path.to_string_lossy();
+
+    if path == "-" {
+        let mut src = Vec::new();
+        stdin()
+            .read_to_end(&mut src)
+            .context("can not read rules from stdin")?;
+        return Ok(Some(src));
     }
 
-    if !compiler.errors().is_empty() {
-        bail!("{} errors found", compiler.errors().len());
+    if is_url(&path) {
+        #[cfg(feature = "remote-rules")]
+        return crate::remote::fetch_rules(&path).map(Some);
+        #[cfg(not(feature = "remote-rules"))]
+        bail!(
+            "can not download `{}`, this requires the `remote-rules` feature",
+            path
+        );
     }
 
-    let rules = compiler.build();
+    Ok(None)
+}
 
-    Ok(rules)
+/// Returns true if `path` is a file with compiled rules, like the ones
+/// produced by `yr compile`.
+fn is_compiled_rules(path: &Path) -> bool {
+    let mut magic = [0_u8; 6];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == b"YARA-X")
 }
 
 struct CompileState {
@@ -1,10 +1,12 @@
 use std::borrow::Cow;
 use std::cmp::min;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
+use std::{io, process};
 
 use anyhow::{bail, Context, Error};
 use clap::{
@@ -20,10 +22,12 @@ use yara_x::errors::ScanError;
 use yara_x::{MetaValue, Rule, Rules, ScanOptions, ScanResults, Scanner};
 
 use crate::commands::{
-    compile_rules, external_var_parser, meta_file_value_parser,
-    path_with_namespace_parser, truncate_with_ellipsis,
+    compile_rules, external_var_parser, is_compiled_rules,
+    meta_file_value_parser, path_with_namespace_parser,
+    truncate_with_ellipsis,
 };
 use crate::walk::Message;
+use crate::watch::watch;
 use crate::{help, walk};
 
 #[derive(Clone, ValueEnum)]
@@ -36,6 +40,11 @@ enum OutputFormats {
 
 const STRINGS_LIMIT: usize = 120;
 
+/// Exit code used with `--fail-on-match` when some file matches. It must be
+/// different from the exit code used for errors, which is 1, and from the
+/// one used for invalid arguments, which is 2.
+const EXIT_MATCH: i32 = 3;
+
 #[rustfmt::skip]
 pub fn scan() -> Command {
     super::command("scan")
@@ -86,12 +95,29 @@ pub fn scan() -> Command {
                 .value_delimiter(',')
                 .action(ArgAction::Append)
         )
+        .arg(
+            arg!(--"fail-on-match")
+                .help("Exit with code 3 if some file matches")
+                .long_help(help::FAIL_ON_MATCH_LONG_HELP)
+        )
         .arg(
             arg!(--"ignore-module" <MODULE>)
                 .help("Ignore rules that use the specified module")
                 .long_help(help::IGNORE_MODULE_LONG_HELP)
                 .action(ArgAction::Append)
         )
+        .arg(
+            arg!(--"max-loop-iterations" <N>)
+                .help("Maximum number of loop iterations per rule")
+                .long_help(help::MAX_LOOP_ITERATIONS_LONG_HELP)
+                .value_parser(value_parser!(u64).range(1..))
+        )
+        .arg(
+            arg!(--"max-matches-per-pattern" <N>)
+                .help("Maximum number of matches recorded per pattern")
+                .long_help(help::MAX_MATCHES_PER_PATTERN_LONG_HELP)
+                .value_parser(value_parser!(u64).range(1..))
+        )
         .arg(
             arg!(-x --"module-data")
                 .help("Pass FILE's content as extra data to MODULE")
@@ -127,11 +153,22 @@ pub fn scan() -> Command {
             arg!(-s --"print-strings")
                 .help("Print matching patterns, limited to the first 120 bytes")
         )
+        .arg(
+            arg!(--"print-strings-context" <N>)
+                .help("Print matching patterns with a hexdump of the N bytes around them")
+                .long_help(help::PRINT_STRINGS_CONTEXT_LONG_HELP)
+                .value_parser(value_parser!(usize))
+        )
         .arg(
             arg!(--"print-strings-limit" <N>)
                 .help("Print matching patterns, limited to the first N bytes")
                 .value_parser(value_parser!(usize))
         )
+        .arg(
+            arg!(--"print-match-variants")
+                .help("Print the variant of each matching pattern (plain, wide, base64...)")
+                .long_help(help::PRINT_MATCH_VARIANTS_LONG_HELP)
+        )
         .arg(
             arg!(-g --"print-tags")
                 .help("Print rule tags")
@@ -141,11 +178,29 @@ pub fn scan() -> Command {
                 .help("Use a more relaxed syntax check while parsing regular expressions")
                 .conflicts_with("compiled-rules")
         )
+        .arg(
+            arg!(--"rule-timeout" <MILLISECONDS>)
+                .help("Abort the evaluation of any rule that takes longer than the given number of milliseconds")
+                .long_help(help::RULE_TIMEOUT_LONG_HELP)
+                .value_parser(value_parser!(u64).range(1..))
+        )
+        .arg(
+            arg!(--"rules-cache" <DIR>)
+                .help("Cache the compiled rules in the given directory")
+                .long_help(help::RULES_CACHE_LONG_HELP)
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with("compiled-rules")
+        )
         .arg(
             arg!(--"scan-list")
                 .help("Indicate that TARGET_PATH is a file containing the paths to be scanned")
                 .long_help(help::SCAN_LIST_LONG_HELP)
         )
+        .arg(
+            arg!(--"sort-output")
+                .help("Print the results sorted by file path")
+                .long_help(help::SORT_OUTPUT_LONG_HELP)
+        )
         .arg(
             arg!(-z --"skip-larger" <FILE_SIZE>)
                 .help("Skip files larger than the given size")
@@ -167,19 +222,53 @@ pub fn scan() -> Command {
                 .help("Abort scanning after the given number of seconds")
                 .value_parser(value_parser!(u64).range(1..))
         )
+        .arg(
+            arg!(--"watch")
+                .help("Scan again every time the rules change")
+                .long_help(help::WATCH_LONG_HELP)
+                .conflicts_with("compiled-rules")
+        )
 }
 
 pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
+    if args.get_flag("watch") {
+        let rules_path: Vec<&Path> = args
+            .get_many::<(Option<String>, PathBuf)>("[NAMESPACE:]RULES_PATH")
+            .unwrap()
+            .map(|(_, path)| path.as_path())
+            .collect();
+
+        watch(rules_path.as_slice(), || scan_files(args))
+    } else {
+        scan_files(args)
+    }
+}
+
+fn scan_files(args: &ArgMatches) -> anyhow::Result<()> {
     let mut rules_path = args
         .get_many::<(Option<String>, PathBuf)>("[NAMESPACE:]RULES_PATH")
         .unwrap();
 
     let target_path = args.get_one::<PathBuf>("TARGET_PATH").unwrap();
-    let compiled_rules = args.get_flag("compiled-rules");
+
+    // Compiled rules are detected automatically when a single RULES_PATH
+    // without namespace is given, so `--compiled-rules` is required only
+    // when reading them from stdin.
+    let compiled_rules = args.get_flag("compiled-rules")
+        || matches!(
+            rules_path.clone().collect::<Vec<_>>().as_slice(),
+            [(None, path)] if is_compiled_rules(path)
+        );
+
     let num_threads = args.get_one::<u8>("threads");
     let skip_larger = args.get_one::<u64>("skip-larger");
     let disable_console_logs = args.get_flag("disable-console-logs");
     let scan_list = args.get_flag("scan-list");
+    let max_matches_per_pattern =
+        args.get_one::<u64>("max-matches-per-pattern");
+    let max_loop_iterations = args.get_one::<u64>("max-loop-iterations");
+    let rule_timeout =
+        args.get_one::<u64>("rule-timeout").map(|t| Duration::from_millis(*t));
 
     let timeout =
         args.get_one::<u64>("timeout").map(|t| Duration::from_secs(*t));
@@ -212,10 +301,14 @@ pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
             );
         }
 
-        let file = File::open(rules_path)
-            .with_context(|| format!("can not open {:?}", &rules_path))?;
+        let rules = if rules_path.as_os_str() == "-" {
+            Rules::deserialize_from(io::stdin())?
+        } else {
+            let file = File::open(rules_path)
+                .with_context(|| format!("can not open {:?}", &rules_path))?;
 
-        let rules = Rules::deserialize_from(file)?;
+            Rules::deserialize_from(file)?
+        };
 
         // If the user is defining external variables, make sure that these
         // variables are valid. A scanner is created only with the purpose
@@ -232,7 +325,7 @@ pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
         // With `take()` we pass the external variables to `compile_rules`,
         // while leaving a `None` in `external_vars`. This way external
         // variables are not set again in the scanner.
-        compile_rules(rules_path, external_vars.take(), args)?
+        compile_rules(rules_path, external_vars.take(), false, args)?
     };
 
     let rules_ref = &rules;
@@ -254,6 +347,19 @@ pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
     let start_time = Instant::now();
     let state = ScanState::new(start_time);
 
+    // Number of files matched by at least one rule. This is independent of
+    // `--negate`, and is used by `--fail-on-match`.
+    let num_files_with_matches = AtomicUsize::new(0);
+    let only_tag = args.get_one::<String>("tag");
+    let num_files_with_matches_ref = &num_files_with_matches;
+
+    // When `--sort-output` is used, the output for each file is kept here
+    // until all files are scanned.
+    let sort_output = args.get_flag("sort-output");
+    let sorted_output: Mutex<BTreeMap<PathBuf, Vec<Message>>> =
+        Mutex::new(BTreeMap::new());
+    let sorted_output_ref = &sorted_output;
+
     let all_metadata = {
         let mut all_metadata = Vec::new();
         for (module_full_name, metadata_path) in metadata {
@@ -270,6 +376,18 @@ pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
         |_, output| {
             let mut scanner = Scanner::new(rules_ref);
 
+            if let Some(n) = max_matches_per_pattern {
+                scanner.max_matches_per_pattern(*n as usize);
+            }
+
+            if let Some(n) = max_loop_iterations {
+                scanner.max_loop_iterations(*n);
+            }
+
+            if let Some(rule_timeout) = rule_timeout {
+                scanner.set_rule_timeout(rule_timeout);
+            }
+
             if !disable_console_logs {
                 let output = output.clone();
                 scanner.console_log(move |msg| {
@@ -329,8 +447,44 @@ pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
                 .retain(|(p, _)| !file_path.eq(p));
 
             let scan_results = scan_results?;
-            let matched_count =
-                process_scan_results(args, &file_path, &scan_results, output);
+
+            for rule in scan_results.timed_out_rules() {
+                output
+                    .send(Message::Error(format!(
+                        "{} rule `{}.{}` timed out while scanning {}",
+                        "warning:".paint(Yellow).bold(),
+                        rule.namespace(),
+                        rule.identifier(),
+                        file_path.display()
+                    )))
+                    .unwrap();
+            }
+
+            if scan_results.matching_rules().any(|rule| {
+                only_tag.map_or(true, |tag| {
+                    rule.tags().any(|t| t.identifier() == tag)
+                })
+            }) {
+                num_files_with_matches_ref.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let matched_count = if sort_output {
+                let (sender, receiver) = crossbeam::channel::unbounded();
+                let matched_count = process_scan_results(
+                    args,
+                    &file_path,
+                    &scan_results,
+                    &sender,
+                );
+                drop(sender);
+                sorted_output_ref
+                    .lock()
+                    .unwrap()
+                    .insert(file_path.clone(), receiver.iter().collect());
+                matched_count
+            } else {
+                process_scan_results(args, &file_path, &scan_results, output)
+            };
 
             state.num_scanned_files.fetch_add(1, Ordering::Relaxed);
             if matched_count > 0 {
@@ -368,6 +522,21 @@ pub fn exec_scan(args: &ArgMatches) -> anyhow::Result<()> {
     )
     .unwrap();
 
+    for message in sorted_output.into_inner().unwrap().into_values().flatten()
+    {
+        match message {
+            Message::Info(s) => println!("{}", s),
+            Message::Error(s) => eprintln!("{}", s),
+            Message::Abort => {}
+        }
+    }
+
+    let num_files_with_matches = num_files_with_matches.into_inner();
+
+    if args.get_flag("fail-on-match") && num_files_with_matches > 0 {
+        process::exit(EXIT_MATCH);
+    }
+
     Ok(())
 }
 
@@ -383,6 +552,8 @@ fn print_rules_as_json(
     let print_meta = args.get_flag("print-meta");
     let print_strings = args.get_flag("print-strings");
     let print_strings_limit = args.get_one::<usize>("print-strings-limit");
+    let print_strings_context = args.get_one::<usize>("print-strings-context");
+    let print_match_variants = args.get_flag("print-match-variants");
 
     // One JSON object per file, with a "rules" key that contains a list of
     // matched rules.
@@ -423,7 +594,10 @@ fn print_rules_as_json(
             json_rule["tags"] = serde_json::json!(tags);
         }
 
-        if print_strings || print_strings_limit.is_some() {
+        if print_strings
+            || print_strings_limit.is_some()
+            || print_strings_context.is_some()
+        {
             let limit = print_strings_limit.unwrap_or(&STRINGS_LIMIT);
             let mut match_vec: Vec<serde_json::Value> = Vec::new();
             for p in matching_rule.patterns() {
@@ -456,6 +630,19 @@ fn print_rules_as_json(
                         "data": s.as_str()
                     });
 
+                    if print_match_variants {
+                        match_json["variant"] =
+                            serde_json::json!(m.variant().as_str());
+                    }
+
+                    if let Some(n) = print_strings_context {
+                        let (before, after) = m.context(*n);
+                        match_json["context_before"] =
+                            serde_json::json!(hex_string(before));
+                        match_json["context_after"] =
+                            serde_json::json!(hex_string(after));
+                    }
+
                     if let Some(k) = m.xor_key() {
                         let mut p = String::with_capacity(s.len());
                         for b in &match_data[..min(match_data.len(), *limit)] {
@@ -491,6 +678,8 @@ fn print_rules_as_text(
     let print_meta = args.get_flag("print-meta");
     let print_strings = args.get_flag("print-strings");
     let print_strings_limit = args.get_one::<usize>("print-strings-limit");
+    let print_strings_context = args.get_one::<usize>("print-strings-context");
+    let print_match_variants = args.get_flag("print-match-variants");
 
     // Clippy insists on replacing the `while let` statement with
     // `for matching_rule in rules.by_ref()`, but that fails with
@@ -564,7 +753,10 @@ fn print_rules_as_text(
 
         output.send(Message::Info(line)).unwrap();
 
-        if print_strings || print_strings_limit.is_some() {
+        if print_strings
+            || print_strings_limit.is_some()
+            || print_strings_context.is_some()
+        {
             let limit = print_strings_limit.unwrap_or(&STRINGS_LIMIT);
             for p in matching_rule.patterns() {
                 for m in p.matches() {
@@ -578,6 +770,10 @@ fn print_rules_as_text(
                         p.identifier(),
                     );
 
+                    if print_match_variants {
+                        msg.push_str(format!(" [{}]", m.variant()).as_str());
+                    }
+
                     match m.xor_key() {
                         Some(k) => {
                             msg.push_str(format!(" xor({:#x},", k).as_str());
@@ -614,12 +810,82 @@ fn print_rules_as_text(
                     }
 
                     output.send(Message::Info(msg)).unwrap();
+
+                    if let Some(n) = print_strings_context {
+                        output
+                            .send(Message::Info(hexdump(&m, *n, *limit)))
+                            .unwrap();
+                    }
+                }
+
+                let suppressed = p.suppressed_matches();
+
+                if suppressed > 0 {
+                    output
+                        .send(Message::Info(format!(
+                            "{}: ... {} more matches not recorded",
+                            p.identifier(),
+                            suppressed
+                        )))
+                        .unwrap();
                 }
             }
         }
     }
 }
 
+/// Returns a hexdump of a match, including `n` bytes of context before and
+/// after it. At most `limit` bytes of the match itself are included, if the
+/// match is longer the context after it is omitted.
+///
+/// Each line contains 16 bytes, preceded by the offset of the first one, and
+/// followed by their ASCII representation. The bytes in the match are
+/// highlighted.
+fn hexdump(m: &yara_x::Match, n: usize, limit: usize) -> String {
+    let (before, after) = m.context(n);
+    let match_data = &m.data()[..min(m.data().len(), limit)];
+    let after = if match_data.len() < m.data().len() { &[] } else { after };
+
+    let start = m.range().start - before.len();
+    let highlighted = before.len()..before.len() + match_data.len();
+
+    let bytes: Vec<u8> = [before, match_data, after].concat();
+    let mut lines = Vec::new();
+
+    for (i, row) in bytes.chunks(16).enumerate() {
+        let mut hex = String::new();
+        let mut ascii = String::new();
+
+        for (j, b) in row.iter().enumerate() {
+            let c = if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            };
+
+            if highlighted.contains(&(i * 16 + j)) {
+                hex.push_str(&format!("{:02x} ", b.paint(Yellow).bold()));
+                ascii.push_str(&format!("{}", c.paint(Yellow).bold()));
+            } else {
+                hex.push_str(&format!("{:02x} ", b));
+                ascii.push(c);
+            }
+        }
+
+        // Pad the last row so that the ASCII column is aligned.
+        hex.push_str(&"   ".repeat(16 - row.len()));
+
+        lines.push(format!("  {:08x}  {} |{}|", start + i * 16, hex, ascii));
+    }
+
+    lines.join("\n")
+}
+
+/// Returns the bytes in `data` as a string of hex digits.
+fn hex_string(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 fn print_matching_rules(
     args: &ArgMatches,
     file_path: &Path,
@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use clap::{arg, value_parser, Arg, ArgAction, ArgMatches, Command};
+use itertools::Itertools;
+use yansi::Color::{Green, Red};
+use yansi::Paint;
+use yara_x::testing::TestManifest;
+
+use crate::commands::{
+    compile_rules, external_var_parser, path_with_namespace_parser,
+};
+use crate::help;
+use crate::walk::Walker;
+
+#[rustfmt::skip]
+pub fn test() -> Command {
+    super::command("test")
+        .about("Run the unit tests for a set of rules")
+        .long_about(help::TEST_LONG_HELP)
+        .arg(
+            Arg::new("[NAMESPACE:]RULES_PATH")
+                .required(true)
+                .help("Path to a YARA source file or directory (optionally prefixed with a namespace)")
+                .value_parser(path_with_namespace_parser)
+                .action(ArgAction::Append)
+        )
+        // Keep options sorted alphabetically by their long name.
+        // For instance, --bar goes before --foo.
+        .arg(
+            arg!(-d --"define")
+                .help("Define external variable")
+                .long_help(help::DEFINE_LONG_HELP)
+                .value_name("VAR=VALUE")
+                .value_parser(external_var_parser)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(-w --"disable-warnings" [WARNING_ID])
+                .help("Disable warnings")
+                .long_help(help::DISABLE_WARNINGS_LONG_HELP)
+                .default_missing_value("all")
+                .num_args(0..)
+                .require_equals(true)
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(--"ignore-module" <MODULE>)
+                .help("Ignore rules that use the specified module")
+                .long_help(help::IGNORE_MODULE_LONG_HELP)
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(-m --"manifest" <MANIFEST_PATH>)
+                .help("Path to a test manifest")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append)
+        )
+        .arg(
+            arg!(--"path-as-namespace")
+                .help("Use file path as rule namespace")
+        )
+        .arg(
+            arg!(--"relaxed-re-syntax")
+                .help("Use a more relaxed syntax check while parsing regular expressions")
+        )
+}
+
+pub fn exec_test(args: &ArgMatches) -> anyhow::Result<()> {
+    let rules_path = args
+        .get_many::<(Option<String>, PathBuf)>("[NAMESPACE:]RULES_PATH")
+        .unwrap();
+
+    let mut manifests: Vec<PathBuf> = args
+        .get_many::<PathBuf>("manifest")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+
+    // When no manifest is given explicitly, look for the manifests that
+    // accompany the rule files.
+    if manifests.is_empty() {
+        for (_, path) in rules_path.clone() {
+            if path.is_file() {
+                let manifest = path.with_extension("test.json");
+                if manifest.is_file() {
+                    manifests.push(manifest);
+                }
+            } else {
+                let mut w = Walker::path(path);
+                w.filter("**/*.test.json");
+                w.walk(
+                    |file_path| {
+                        manifests.push(file_path.to_path_buf());
+                        Ok(())
+                    },
+                    Err,
+                )?;
+            }
+        }
+    }
+
+    if manifests.is_empty() {
+        bail!("no test manifests found");
+    }
+
+    let external_vars: Option<Vec<(String, serde_json::Value)>> = args
+        .get_many::<(String, serde_json::Value)>("define")
+        .map(|var| var.cloned().collect());
+
+    let rules = compile_rules(rules_path, external_vars, true, args)?;
+
+    let mut num_checks = 0;
+    let mut num_failures = 0;
+
+    for manifest_path in manifests {
+        let report = TestManifest::from_file(&manifest_path)
+            .and_then(|manifest| manifest.run(&rules))
+            .with_context(|| {
+                format!("running tests in `{}`", manifest_path.display())
+            })?;
+
+        num_checks += report.num_checks();
+        num_failures += report.failures().len();
+
+        if report.passed() {
+            println!("{} {}", "PASS".paint(Green), manifest_path.display());
+            continue;
+        }
+
+        println!("{} {}", "FAIL".paint(Red), manifest_path.display());
+
+        for failure in report.failures() {
+            println!(
+                "  test `{}`: rule `{}.{}` {}",
+                failure.test(),
+                failure.namespace(),
+                failure.rule(),
+                if failure.expected_match() {
+                    "was expected to match, but it didn't"
+                } else {
+                    "was expected to not match, but it did"
+                }
+            );
+            for expr in failure.diverged() {
+                println!(
+                    "    {} {}",
+                    if failure.expected_match() {
+                        "never true:"
+                    } else {
+                        "true:"
+                    },
+                    expr.source().split_whitespace().join(" ")
+                );
+            }
+        }
+    }
+
+    println!(
+        "\n{} checks, {} passed, {} failed",
+        num_checks,
+        num_checks - num_failures,
+        num_failures
+    );
+
+    if num_failures > 0 {
+        bail!("{} checks failed", num_failures);
+    }
+
+    Ok(())
+}
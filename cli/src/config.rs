@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use clap::Command;
+use toml::{Table, Value};
+
+/// Name of the configuration files.
+const CONFIG_FILE_NAME: &str = ".yara-x.toml";
+
+/// Returns the configuration files that apply to the current directory,
+/// sorted from lowest to highest priority.
+///
+/// The user-level configuration file is `~/.yara-x.toml`. The project-level
+/// configuration file is the `.yara-x.toml` file found in the current
+/// directory or in its closest ancestor.
+pub fn config_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    let user_config = home::home_dir().map(|dir| dir.join(CONFIG_FILE_NAME));
+
+    if let Some(user_config) = &user_config {
+        if user_config.is_file() {
+            files.push(user_config.clone());
+        }
+    }
+
+    let project_config = std::env::current_dir().ok().and_then(|dir| {
+        dir.ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|path| path.is_file())
+    });
+
+    // When the current directory is inside the user's home directory, the
+    // project-level configuration file can be the user-level one.
+    if let Some(project_config) = project_config {
+        if user_config.as_ref() != Some(&project_config) {
+            files.push(project_config);
+        }
+    }
+
+    files
+}
+
+/// Applies the configuration in `path` to the command-line interface.
+///
+/// The configuration file is a TOML document where keys are the long names
+/// of the command-line options, and values are used as the default values
+/// for those options. Options in a table apply only to the command with the
+/// same name, while options at the top level apply to every command that
+/// accepts them.
+///
+/// ```toml
+/// disable-warnings = ["slow_pattern"]
+///
+/// [scan]
+/// threads = 4
+/// output-format = "json"
+/// ```
+pub fn apply_config_file(
+    cmd: Command,
+    path: &Path,
+) -> anyhow::Result<Command> {
+    let config = fs::read_to_string(path)?;
+    let config: Table = config.parse()?;
+
+    apply_config(cmd, &config)
+}
+
+fn apply_config(mut cmd: Command, config: &Table) -> anyhow::Result<Command> {
+    // Options at the top level apply to all commands, they are applied
+    // first so that the ones in the command's table take precedence.
+    for (key, value) in config.iter().filter(|(_, value)| !value.is_table()) {
+        let commands: Vec<String> = cmd
+            .get_subcommands()
+            .filter(|sub| has_option(sub, key))
+            .map(|sub| sub.get_name().to_string())
+            .collect();
+
+        if commands.is_empty() {
+            bail!("unknown option `{}`", key);
+        }
+
+        let values = option_values(key, value)?;
+
+        for name in commands {
+            cmd = cmd.mut_subcommand(name, |sub| {
+                sub.mut_arg(key, |arg| arg.default_values(values.clone()))
+            });
+        }
+    }
+
+    for (key, value) in config.iter() {
+        if let Value::Table(table) = value {
+            cmd = apply_subcommand_config(cmd, key, table)?;
+        }
+    }
+
+    Ok(cmd)
+}
+
+fn apply_command_config(
+    mut cmd: Command,
+    config: &Table,
+) -> anyhow::Result<Command> {
+    for (key, value) in config.iter() {
+        if let Value::Table(table) = value {
+            cmd = apply_subcommand_config(cmd, key, table)?;
+        } else {
+            if !has_option(&cmd, key) {
+                bail!("unknown option `{}`", key);
+            }
+
+            let values = option_values(key, value)?;
+
+            cmd = cmd.mut_arg(key, |arg| arg.default_values(values));
+        }
+    }
+
+    Ok(cmd)
+}
+
+fn apply_subcommand_config(
+    cmd: Command,
+    name: &str,
+    config: &Table,
+) -> anyhow::Result<Command> {
+    let sub = cmd
+        .find_subcommand(name)
+        .cloned()
+        .with_context(|| format!("unknown command `{}`", name))?;
+
+    let sub = apply_command_config(sub, config)
+        .with_context(|| format!("in section `[{}]`", name))?;
+
+    Ok(cmd.mut_subcommand(name, |_| sub))
+}
+
+/// Returns true if `cmd` has a non-positional argument with the given long
+/// name.
+fn has_option(cmd: &Command, name: &str) -> bool {
+    cmd.get_arguments().any(|arg| !arg.is_positional() && arg.get_id() == name)
+}
+
+/// Converts the value of an option in the configuration file into values
+/// that can be used as default values for the command-line argument.
+fn option_values(key: &str, value: &Value) -> anyhow::Result<Vec<String>> {
+    let to_string = |value: &Value| match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err(anyhow::anyhow!("invalid value for option `{}`", key)),
+    };
+
+    match value {
+        Value::Array(array) => array.iter().map(to_string).collect(),
+        value => Ok(vec![to_string(value)?]),
+    }
+}
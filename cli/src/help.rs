@@ -1,3 +1,21 @@
+pub const BENCH_LONG_HELP: &str = r#"Measure the performance of a set of rules
+
+Compiles the rules in <RULES_PATH> and scans the files in <TARGET_PATH> with them,
+showing the compilation time, the size of the compiled rules once serialized, and
+the scan throughput. Files are scanned in a single thread, and the time spent
+reading the files is not included in the measurements.
+
+When `yr` is built with the `rules-profiling` feature, the rules on which the
+scanner spent the most time are also shown.
+
+Use `--output-format json` for obtaining the results in a format that can be
+stored for tracking the performance of the rules over time.
+
+Examples:
+
+yr bench rules_file.yar samples_dir
+yr bench --iterations 5 --output-format json rules_dir samples_dir"#;
+
 pub const CHECK_LONG_HELP: &str = r#"Check if YARA source files are correct
 
 If <RULES_PATH> is a directory, all files with extensions `.yar` and `.yara` will be checked.
@@ -6,14 +24,20 @@ This behavior can be changed by using the `--filter` option."#;
 pub const COMPILED_RULES_LONG_HELP: &str = r#"Indicate that <RULES_PATH> is a file containing compiled rules
 
 YARA rules can be compiled with the `yr compile` command. The file produced by
-this command can be passed later to `yr scan` by using this flag."#;
+this command can be passed later to `yr scan`. Files with compiled rules are
+detected automatically, so this flag is required only when <RULES_PATH> is `-`
+and the compiled rules are read from stdin."#;
 
 pub const COMPLETION_LONG_HELP: &str = r#"Output shell completion code for the specified shell
 
+The supported shells are bash, elvish, fish, powershell and zsh.
+
 Examples:
 
 yr completion bash > $(brew --prefix)/etc/bash_completion.d/yr
-yr completion zsh > "${fpath[1]}/_yr""#;
+yr completion zsh > "${fpath[1]}/_yr"
+yr completion fish > ~/.config/fish/completions/yr.fish
+yr completion powershell >> $PROFILE"#;
 
 pub const DEFINE_LONG_HELP: &str = r#"Define external variable
 
@@ -24,6 +48,32 @@ Examples:
 --define some_bool=true
 --define some_str=\"foobar\""#;
 
+pub const MAN_LONG_HELP: &str = r#"Output the manual page in roff format
+
+With --output-dir, one manual page is written for `yr` and for each of its commands
+(e.g: yr.1, yr-scan.1, yr-compile.1).
+
+Examples:
+
+yr man > /usr/local/share/man/man1/yr.1
+yr man --output-dir /usr/local/share/man/man1"#;
+
+pub const MAX_LOOP_ITERATIONS_LONG_HELP: &str = r#"Maximum number of loop iterations per rule
+
+Loops like `for all i in (0..filesize) : (...)` can take a very long time with
+large files. This limits the number of iterations executed while evaluating
+the condition of each rule, including the iterations of nested loops. When
+the limit is exceeded the loop is aborted and its result is undefined, which
+usually means that the rule doesn't match. By default there's no limit."#;
+
+pub const MAX_MATCHES_PER_PATTERN_LONG_HELP: &str = r#"Maximum number of matches recorded per pattern
+
+When a pattern reaches this number of matches, further matches are not recorded.
+This bounds the memory used when scanning files where some pattern appears
+millions of times. Expressions like `#a` won't count more than <N> matches. When
+printing matches with --print-strings, the number of matches that were not
+recorded is shown after the matches. The default value is 1000000."#;
+
 pub const MODULE_DATA_LONG_HELP: &str = r#"Pass FILE's content as extra data to MODULE
 
 Some modules require supplementary data to work, in addition to the scanned
@@ -44,6 +94,22 @@ This is ignored if <RULES_PATH> is not a directory. When <MAX_DEPTH> is 0 it
 means that files located in the specified directory will be processed, but
 subdirectories won't be traversed. By default <MAX_DEPTH> is infinite."#;
 
+pub const DIFF_LONG_HELP: &str = r#"Compare the results of two sets of rules
+
+Scans <TARGET_PATH> with the rules in <OLD_RULES_PATH> and <NEW_RULES_PATH>, and
+shows the files for which the matching rules are different. For each of these files
+the output lists the rules that started matching (+) and the rules that stopped
+matching (-). At the end, a summary shows the number of files gained and lost by
+each rule.
+
+This is useful for validating changes to a set of rules against a corpus of samples
+before deploying them.
+
+Examples:
+
+yr diff old_rules.yar new_rules.yar samples_dir
+yr diff --threads 8 old_rules_dir new_rules_dir samples_dir"#;
+
 pub const DUMP_LONG_HELP: &str = r#"Show the data produced by YARA modules for a file
 
 YARA modules analyze files and extract information from them. This command shows all
@@ -71,6 +137,45 @@ Examples:
 --disable-warnings=slow_rules,redundant_modifier"
 --disable-warnings=slow_rules --disable-warnings=redundant_modifier"#;
 
+pub const EXPLAIN_LONG_HELP: &str = r#"Explain why rules match or don't match a file
+
+Scans <TARGET_PATH> with the rules in <RULES_PATH>, and shows the value taken by each
+sub-expression in the rule conditions. Sub-expressions that were evaluated multiple
+times, like those inside loops, show how many times they took each value. Operands
+of `and` and `or` that were skipped because the result was already known are shown
+as "not evaluated".
+
+Examples:
+
+yr explain rules_file.yar scanned_file
+yr explain --rule foo rules_file.yar scanned_file"#;
+
+pub const TEST_LONG_HELP: &str = r#"Run the unit tests for a set of rules
+
+Compiles the rules in <RULES_PATH> and runs the tests described in one or more test
+manifests. A test manifest is a JSON file that lists some files, or some data encoded
+in hex, together with the rules that are expected to match or not match them:
+
+{
+  "tests": [
+    { "file": "samples/foo.bin", "matches": ["foo"], "not_matches": ["bar"] },
+    { "hex": "4D 5A 90 00", "matches": ["is_mz"] }
+  ]
+}
+
+Paths to files are relative to the directory where the manifest is located. When
+no manifest is specified with --manifest, the manifest for a file named `rules.yar`
+is `rules.test.json`, located in the same directory. For directories, any file with
+the `.test.json` extension is used as a manifest.
+
+For each rule that doesn't produce the expected result, the sub-expressions in its
+condition that caused the divergence are shown.
+
+Examples:
+
+yr test rules_file.yar
+yr test --manifest tests.json rules_dir"#;
+
 pub const FILTER_LONG_HELP: &str = r#"Only check files that match the given pattern
 
 Patterns can contains the following wildcards:
@@ -109,10 +214,41 @@ depends directly or indirectly on such rules.
 
 This option can be used more than once for ignored different modules."#;
 
+pub const INSPECT_LONG_HELP: &str = r#"Show information about compiled rules
+
+Shows the version of YARA-X that compiled the rules, the modules imported by the
+rules, and the list of rules, including their tags and the value of their
+`version` metadata, if any.
+
+<RULES_PATH> is a file produced by `yr compile`, or `-` for reading the compiled
+rules from stdin.
+
+Examples:
+
+yr inspect rules.yarc
+yr inspect --output-format=json rules.yarc"#;
+
 pub const THREADS_LONG_HELP: &str = r#"Use the specified number of threads
 
 The default value is automatically determined based on the number of CPU cores."#;
 
+pub const RULE_TIMEOUT_LONG_HELP: &str = r#"Abort the evaluation of any rule that takes longer than the given number of milliseconds
+
+This bounds the time spent in the loops of each rule's condition, so that a
+single slow rule doesn't consume the time of the whole scan. Rules that exceed
+the limit don't match, and a warning is printed for each of them. This is
+independent of --timeout, which aborts the whole scan."#;
+
+pub const RULES_CACHE_LONG_HELP: &str = r#"Cache the compiled rules in the given directory
+
+Before compiling the rules, the directory is checked for rules that were
+compiled from exactly the same source files with the same options. If found,
+they are used instead of compiling the source files again. Otherwise, the
+rules are compiled and stored in the directory for the next time.
+
+Warnings are shown only when the rules are actually compiled. Old entries are
+never removed from the directory, it can be emptied at any time."#;
+
 pub const SCAN_LIST_LONG_HELP: &str = r#"Indicate that TARGET_PATH is a file containing the paths to be scanned
 
 <TARGET_PATH> must be a text file containing one path per line. The paths must
@@ -146,6 +282,57 @@ Examples:
 
 --output-format=ndjson"#;
 
+pub const PRINT_STRINGS_CONTEXT_LONG_HELP: &str = r#"Print matching patterns with a hexdump of the N bytes around them
+
+Each match is followed by a hexdump that contains the matching data and up
+to N bytes before and after it, with the matching data highlighted. This
+option implies --print-strings. The matching data is limited to the number
+of bytes indicated by --print-strings-limit, when the matching data is
+longer than that, the bytes that follow it are not shown.
+
+With --output-format=ndjson the bytes before and after each match are
+included as hex strings in the "context_before" and "context_after" fields."#;
+
+pub const PRINT_MATCH_VARIANTS_LONG_HELP: &str = r#"Print the variant of each matching pattern
+
+Patterns with modifiers like `wide` or `base64` are searched in multiple forms.
+With this option, each match printed by --print-strings indicates the form in
+which the pattern was found, which can be one of: plain, wide, base64 or
+base64wide. Matches for patterns with the `xor` modifier also include the XOR
+key. This option has effect only when used with --print-strings.
+
+Example output:
+
+0x10:12:$a [wide]: f\x00o\x00o\x00"#;
+
+pub const FAIL_ON_MATCH_LONG_HELP: &str = r#"Exit with code 3 if some file matches
+
+When this option is used, `yr scan` exits with exit code 3 if at least one of
+the scanned files matches some rule. This is different from the exit code 1
+used for errors, so scripts and CI pipelines that must fail when some file is
+detected can tell both cases apart. Private rules, and rules excluded by
+--tag, are not taken into account. The exit code is not affected by --negate.
+
+Examples:
+
+yr scan --fail-on-match rules.yar dist/
+yr scan --fail-on-match --count rules.yar dist/ > /dev/null"#;
+
+pub const SORT_OUTPUT_LONG_HELP: &str = r#"Print the results sorted by file path
+
+Files are scanned in parallel, and by default the results for each file are
+printed as soon as the file is scanned, which means that the order in which
+files appear in the output can change from one run to the next one. With this
+option the results are printed once all the files have been scanned, sorted by
+file path. Rules are always printed in the order in which they were declared,
+and matches are always sorted by offset."#;
+
+pub const WATCH_LONG_HELP: &str = r#"Run again every time the source files change
+
+After running, keep watching the YARA source files and run again every time any of
+them changes. When a directory is watched, only changes to files with the `.yar` or
+`.yara` extension are taken into account. Press Ctrl+C for exiting."#;
+
 pub const FMT_CHECK_MODE: &str = r#"Run in 'check' mode
 
 Doesn't modify the files. Exits with 0 if files are formatted correctly. Exits
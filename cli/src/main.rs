@@ -1,10 +1,14 @@
 mod commands;
+mod config;
 mod help;
+#[cfg(feature = "remote-rules")]
+mod remote;
 mod walk;
+mod watch;
 
 use crossterm::tty::IsTty;
 use std::{io, panic, process};
-use yansi::Color::Red;
+use yansi::Color::{Red, Yellow};
 use yansi::Paint;
 
 use crate::commands::cli;
@@ -37,7 +41,27 @@ fn main() -> anyhow::Result<()> {
         yansi::disable();
     }
 
-    let args = cli().get_matches_from(wild::args());
+    // Options in the configuration files are applied as default values for
+    // the command-line arguments, so that the arguments passed explicitly
+    // take precedence. Invalid configuration files are ignored, otherwise
+    // a bad file would prevent using any command, including `help`.
+    let mut cli = cli();
+
+    for config_file in config::config_files() {
+        match config::apply_config_file(cli.clone(), &config_file) {
+            Ok(new_cli) => cli = new_cli,
+            Err(err) => {
+                eprintln!(
+                    "{} ignoring config file `{}`: {:#}",
+                    "warning:".paint(Yellow).bold(),
+                    config_file.display(),
+                    err
+                );
+            }
+        }
+    }
+
+    let args = cli.get_matches_from(wild::args());
 
     #[cfg(feature = "profiling")]
     let guard = pprof::ProfilerGuardBuilder::default()
@@ -66,8 +90,14 @@ fn main() -> anyhow::Result<()> {
         Some(("fmt", args)) => commands::exec_fmt(args),
         Some(("scan", args)) => commands::exec_scan(args),
         Some(("dump", args)) => commands::exec_dump(args),
+        Some(("diff", args)) => commands::exec_diff(args),
+        Some(("bench", args)) => commands::exec_bench(args),
+        Some(("explain", args)) => commands::exec_explain(args),
+        Some(("test", args)) => commands::exec_test(args),
         Some(("compile", args)) => commands::exec_compile(args),
+        Some(("inspect", args)) => commands::exec_inspect(args),
         Some(("completion", args)) => commands::exec_completion(args),
+        Some(("man", args)) => commands::exec_man(args),
         _ => unreachable!(),
     };
 
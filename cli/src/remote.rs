@@ -0,0 +1,56 @@
+use std::io::Read;
+
+use anyhow::{anyhow, bail, Context};
+use sha2::{Digest, Sha256};
+
+/// Downloads the source code for some rules from a `https://` URL.
+///
+/// The URL can include the SHA-256 of the downloaded data as a fragment
+/// (e.g: `https://example.com/rules.yar#sha256=<hex digest>`). In that case
+/// the data is rejected if it doesn't match the expected digest, which
+/// guarantees that the rules are the expected ones.
+pub fn fetch_rules(url: &str) -> anyhow::Result<Vec<u8>> {
+    let (url, expected_sha256) = match url.split_once('#') {
+        Some((url, fragment)) => {
+            let sha256 = fragment.strip_prefix("sha256=").ok_or_else(|| {
+                anyhow!(
+                    "invalid fragment in `{}`, expecting `#sha256=<hex digest>`",
+                    url
+                )
+            })?;
+            (url, Some(sha256.to_ascii_lowercase()))
+        }
+        None => (url, None),
+    };
+
+    if !url.starts_with("https://") {
+        bail!("can not download `{}`, only https:// URLs are supported", url);
+    }
+
+    let mut src = Vec::new();
+
+    ureq::get(url)
+        .call()
+        .with_context(|| format!("can not download `{}`", url))?
+        .into_reader()
+        .read_to_end(&mut src)
+        .with_context(|| format!("can not download `{}`", url))?;
+
+    if let Some(expected_sha256) = expected_sha256 {
+        let sha256 = Sha256::digest(src.as_slice())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        if sha256 != expected_sha256 {
+            bail!(
+                "SHA-256 mismatch for `{}`: expected {}, got {}",
+                url,
+                expected_sha256,
+                sha256
+            );
+        }
+    }
+
+    Ok(src)
+}
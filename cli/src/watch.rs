@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::Context;
+use notify::{EventKind, RecursiveMode, Watcher};
+use yansi::Color::{BrightBlack, Red};
+use yansi::Paint;
+
+/// Events that occur within this period after a change are considered part
+/// of the same change. Editors usually produce multiple events when saving a
+/// file, and this avoids running the function more than once.
+const DEBOUNCE_PERIOD: Duration = Duration::from_millis(200);
+
+/// Runs a function, and runs it again every time a YARA source file in the
+/// given paths changes.
+///
+/// `paths` can contain both files and directories. Directories are watched
+/// recursively, but only changes to files with `.yar` or `.yara` extensions
+/// are taken into account. Errors returned by the function are printed, but
+/// they don't stop the watch. This function returns only if the file system
+/// notifications can't be received.
+///
+/// ```text
+/// watch(&[Path::new("rules")], || {
+///     // ... compile the rules and do something with them.
+///     Ok(())
+/// }).unwrap();
+/// ```
+pub fn watch<F>(paths: &[&Path], mut f: F) -> anyhow::Result<()>
+where
+    F: FnMut() -> anyhow::Result<()>,
+{
+    let (tx, rx) = mpsc::channel();
+
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mut watched_files = Vec::new();
+    let mut watched_dirs = Vec::new();
+
+    for path in paths {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("can't watch `{}`", path.display()))?;
+
+        // Individual files are watched by watching the directory that
+        // contains them. Many editors save files by replacing them with a
+        // new one, and the watch would be lost if it was on the file itself.
+        if path.is_file() {
+            if let Some(parent) = path.parent() {
+                watcher.watch(parent, RecursiveMode::NonRecursive)?;
+            }
+            watched_files.push(path);
+        } else {
+            watcher.watch(&path, RecursiveMode::Recursive)?;
+            watched_dirs.push(path);
+        }
+    }
+
+    let is_relevant = |path: &PathBuf| {
+        if watched_files.contains(path) {
+            return true;
+        }
+        matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yar" | "yara")
+        ) && watched_dirs.iter().any(|dir| path.starts_with(dir))
+    };
+
+    loop {
+        if let Err(err) = f() {
+            eprintln!("{} {:#}", "error:".paint(Red).bold(), err);
+        }
+
+        eprintln!(
+            "{}",
+            "Watching for changes, press Ctrl+C to exit...".paint(BrightBlack)
+        );
+
+        // Wait for a relevant change.
+        loop {
+            let event = rx.recv()??;
+            if !matches!(event.kind, EventKind::Access(_) | EventKind::Other)
+                && event.paths.iter().any(is_relevant)
+            {
+                break;
+            }
+        }
+
+        // Discard the events that are part of the same change.
+        while rx.recv_timeout(DEBOUNCE_PERIOD).is_ok() {}
+    }
+}
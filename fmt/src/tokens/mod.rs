@@ -333,6 +333,7 @@ impl<'a> Token<'a> {
             SyntaxKind::ALL_KW
             | SyntaxKind::AND_KW
             | SyntaxKind::ANY_KW
+            | SyntaxKind::AS_KW
             | SyntaxKind::ASCII_KW
             | SyntaxKind::AT_KW
             | SyntaxKind::BASE64_KW
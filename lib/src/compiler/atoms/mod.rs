@@ -54,6 +54,7 @@ and `"efg"` will be used because `"a"` and `"h"` are too short.
 
 mod mask;
 mod quality;
+mod searcher;
 
 use std::collections::Bound;
 use std::iter;
@@ -71,6 +72,7 @@ pub(crate) use crate::compiler::atoms::quality::best_atom_in_bytes;
 pub(crate) use crate::compiler::atoms::quality::best_range_in_bytes;
 pub(crate) use crate::compiler::atoms::quality::best_range_in_masked_bytes;
 pub(crate) use crate::compiler::atoms::quality::AtomsQuality;
+pub(crate) use crate::compiler::atoms::searcher::AtomSearcher;
 
 use crate::compiler::{SubPatternFlagSet, SubPatternFlags};
 
@@ -0,0 +1,415 @@
+/*! Searching for atoms in the scanned data.
+
+All the atoms extracted from the patterns are searched simultaneously with
+an Aho-Corasick automaton. However, when the set of atoms contains many short
+atoms (e.g: 2 or 3 bytes long) the automaton is forced to visit every single
+byte in the data, and the prefilters used internally by the automaton are
+rendered useless.
+
+For this reason, short atoms are put apart in a bucket that is searched with
+[Teddy][1], a SIMD-accelerated algorithm for searching a small number of
+patterns, while the remaining atoms are searched with the Aho-Corasick
+automaton. The matches produced by both searches are merged, so that they are
+reported in the same order in which the Aho-Corasick automaton would report
+them if all the atoms were searched with it.
+
+Teddy is used only if the CPU has the required features (SSSE3 or AVX2 in
+x86_64, NEON in aarch64). This is determined at runtime while creating the
+[`AtomSearcher`]. When the short atoms can't be searched with Teddy, either
+because the CPU doesn't support it or because there are too many of them,
+all the atoms are searched with the Aho-Corasick automaton.
+
+[1]: https://github.com/BurntSushi/aho-corasick/tree/master/src/packed/teddy
+ */
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::Range;
+
+use aho_corasick::{packed, AhoCorasick, FindOverlappingIter, Input};
+
+/// Atoms with this length or shorter are searched with Teddy.
+const SHORT_ATOM_MAX_LEN: usize = 3;
+
+/// Maximum number of distinct short atoms that can be searched with Teddy.
+const SHORT_ATOMS_MAX: usize = 64;
+
+/// Searches for a set of atoms in some data.
+pub(crate) struct AtomSearcher {
+    /// Aho-Corasick automaton for the atoms that are not in `short_atoms`.
+    /// This is `None` if there are no such atoms.
+    ac: Option<AhoCorasick>,
+    /// Maps the pattern IDs in `ac` to atom indexes. If this is empty the
+    /// pattern IDs are equal to the atom indexes.
+    ac_atoms: Vec<u32>,
+    /// Short atoms searched with Teddy.
+    short_atoms: Option<ShortAtoms>,
+}
+
+impl AtomSearcher {
+    /// Creates a new [`AtomSearcher`] for the given atoms.
+    ///
+    /// The index of each atom in the `atoms` slice is used for identifying
+    /// the atom in the matches returned by [`AtomSearcher::find_overlapping`].
+    pub fn new(atoms: &[&[u8]]) -> Self {
+        let short_atoms = ShortAtoms::new(atoms);
+
+        let ac_atoms: Vec<u32> = if short_atoms.is_some() {
+            atoms
+                .iter()
+                .enumerate()
+                .filter(|(_, atom)| !ShortAtoms::is_short(atom))
+                .map(|(i, _)| i as u32)
+                .collect()
+        } else {
+            (0..atoms.len() as u32).collect()
+        };
+
+        let ac = if ac_atoms.is_empty() {
+            None
+        } else {
+            Some(
+                AhoCorasick::new(ac_atoms.iter().map(|i| atoms[*i as usize]))
+                    .expect("failed to build Aho-Corasick automaton"),
+            )
+        };
+
+        // When all the atoms are in the Aho-Corasick automaton, its pattern
+        // IDs are the atom indexes and the mapping is not necessary.
+        let ac_atoms = if short_atoms.is_some() { ac_atoms } else { vec![] };
+
+        Self { ac, ac_atoms, short_atoms }
+    }
+
+    /// Returns the number of atoms that are searched with Teddy.
+    #[cfg(feature = "logging")]
+    pub fn num_short_atoms(&self) -> usize {
+        self.short_atoms
+            .as_ref()
+            .map(|short_atoms| short_atoms.atoms.len())
+            .unwrap_or(0)
+    }
+
+    /// Returns an iterator that yields all the atoms that match within the
+    /// given range of `data`, including overlapping matches.
+    ///
+    /// Matches are yielded in ascending order of their end offset.
+    pub fn find_overlapping<'a>(
+        &'a self,
+        data: &'a [u8],
+        span: Range<usize>,
+    ) -> AtomMatches<'a> {
+        let ac_matches = self.ac.as_ref().map(|ac| {
+            ac.find_overlapping_iter(Input::new(data).span(span.clone()))
+        });
+
+        let short_matches =
+            self.short_atoms.as_ref().map(|short_atoms| ShortAtomMatches {
+                short_atoms,
+                data,
+                span_end: span.end,
+                next_candidate: short_atoms.next_candidate(data, span),
+                pending: BinaryHeap::new(),
+                current: None,
+            });
+
+        AtomMatches {
+            ac_atoms: self.ac_atoms.as_slice(),
+            ac_matches,
+            next_ac_match: None,
+            short_matches,
+            next_short_match: None,
+        }
+    }
+}
+
+/// A match found by [`AtomSearcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AtomMatch {
+    atom: usize,
+    range: Range<usize>,
+}
+
+impl AtomMatch {
+    /// Index of the atom that matched.
+    #[inline]
+    pub fn atom(&self) -> usize {
+        self.atom
+    }
+
+    /// Offset where the match starts.
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.range.start
+    }
+
+    /// Offset where the match ends (exclusive).
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.range.end
+    }
+}
+
+/// Iterator returned by [`AtomSearcher::find_overlapping`].
+pub(crate) struct AtomMatches<'a> {
+    ac_atoms: &'a [u32],
+    ac_matches: Option<FindOverlappingIter<'a, 'a>>,
+    next_ac_match: Option<AtomMatch>,
+    short_matches: Option<ShortAtomMatches<'a>>,
+    next_short_match: Option<AtomMatch>,
+}
+
+impl Iterator for AtomMatches<'_> {
+    type Item = AtomMatch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_ac_match.is_none() {
+            self.next_ac_match =
+                self.ac_matches.as_mut().and_then(|m| m.next()).map(|m| {
+                    let pattern = m.pattern().as_usize();
+                    AtomMatch {
+                        atom: match self.ac_atoms.get(pattern) {
+                            Some(atom) => *atom as usize,
+                            None => pattern,
+                        },
+                        range: m.range(),
+                    }
+                });
+        }
+
+        if self.next_short_match.is_none() {
+            self.next_short_match =
+                self.short_matches.as_mut().and_then(|m| m.next());
+        }
+
+        match (&self.next_ac_match, &self.next_short_match) {
+            (Some(ac_match), Some(short_match)) => {
+                if short_match.end() < ac_match.end() {
+                    self.next_short_match.take()
+                } else {
+                    self.next_ac_match.take()
+                }
+            }
+            (Some(_), None) => self.next_ac_match.take(),
+            (None, Some(_)) => self.next_short_match.take(),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Set of short atoms that are searched with Teddy.
+struct ShortAtoms {
+    /// Teddy searcher that finds the offsets where some of the short atoms
+    /// may start.
+    teddy: packed::Searcher,
+    /// Distinct short atoms, sorted lexicographically. Each of them is
+    /// accompanied by the indexes of the atoms that are equal to it.
+    atoms: Vec<(Vec<u8>, Vec<u32>)>,
+    /// The atoms in `atoms` that start with byte `b` are the ones in the
+    /// range `first_byte[b]..first_byte[b + 1]`.
+    first_byte: [u8; 257],
+}
+
+impl ShortAtoms {
+    /// Returns true if the atom must be searched with Teddy.
+    fn is_short(atom: &[u8]) -> bool {
+        (1..=SHORT_ATOM_MAX_LEN).contains(&atom.len())
+    }
+
+    /// Creates the set of short atoms from all the atoms.
+    ///
+    /// Returns `None` if there are no short atoms, there are too many of
+    /// them, or the current CPU doesn't support Teddy.
+    fn new(atoms: &[&[u8]]) -> Option<Self> {
+        let mut short_atoms: Vec<(Vec<u8>, Vec<u32>)> = Vec::new();
+
+        for (i, atom) in atoms.iter().enumerate() {
+            if !Self::is_short(atom) {
+                continue;
+            }
+            match short_atoms.binary_search_by(|(a, _)| a.as_slice().cmp(atom))
+            {
+                Ok(pos) => short_atoms[pos].1.push(i as u32),
+                Err(pos) => {
+                    if short_atoms.len() == SHORT_ATOMS_MAX {
+                        return None;
+                    }
+                    short_atoms.insert(pos, (atom.to_vec(), vec![i as u32]))
+                }
+            }
+        }
+
+        if short_atoms.is_empty() {
+            return None;
+        }
+
+        // The packed searcher is built only if Teddy can be used, which
+        // depends on the features supported by the CPU.
+        let teddy = packed::Config::new()
+            .builder()
+            .extend(short_atoms.iter().map(|(atom, _)| atom))
+            .build()?;
+
+        let mut first_byte = [0_u8; 257];
+
+        for (atom, _) in short_atoms.iter() {
+            first_byte[atom[0] as usize + 1] += 1;
+        }
+
+        for b in 1..first_byte.len() {
+            first_byte[b] += first_byte[b - 1];
+        }
+
+        Some(Self { teddy, atoms: short_atoms, first_byte })
+    }
+
+    /// Returns the offset of the next position within `span` where at least
+    /// one of the short atoms matches.
+    #[inline]
+    fn next_candidate(
+        &self,
+        data: &[u8],
+        span: Range<usize>,
+    ) -> Option<usize> {
+        if span.is_empty() {
+            return None;
+        }
+        self.teddy.find_in(data, span.into()).map(|m| m.start())
+    }
+
+    /// Returns the indexes within `self.atoms` of the short atoms that start
+    /// with the given byte.
+    #[inline]
+    fn starting_with(&self, byte: u8) -> Range<usize> {
+        self.first_byte[byte as usize] as usize
+            ..self.first_byte[byte as usize + 1] as usize
+    }
+}
+
+/// Iterator that yields the matches for the short atoms, in ascending order
+/// of their end offset.
+///
+/// Teddy returns the positions where at least one short atom starts, in
+/// ascending order. At each of these positions all the short atoms that
+/// start with the byte at that position are verified, and the ones that
+/// match are kept in `pending` until it's guaranteed that no other match
+/// will end before them.
+struct ShortAtomMatches<'a> {
+    short_atoms: &'a ShortAtoms,
+    data: &'a [u8],
+    span_end: usize,
+    /// Next position where some short atom starts.
+    next_candidate: Option<usize>,
+    /// Matches not yet reported, represented by their end offset, their
+    /// start offset, and the index of the short atom in `short_atoms.atoms`.
+    pending: BinaryHeap<Reverse<(usize, usize, usize)>>,
+    /// Match that is being currently reported, and the number of atoms that
+    /// were reported for it. Multiple atoms may be equal, in that case one
+    /// match is reported for each of them.
+    current: Option<(Range<usize>, &'a [u32], usize)>,
+}
+
+impl Iterator for ShortAtomMatches<'_> {
+    type Item = AtomMatch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((range, atoms, i)) = &mut self.current {
+                if let Some(atom) = atoms.get(*i) {
+                    *i += 1;
+                    return Some(AtomMatch {
+                        atom: *atom as usize,
+                        range: range.clone(),
+                    });
+                }
+                self.current = None;
+            }
+
+            // Verify the short atoms at the candidate positions until the
+            // match that ends first among the pending ones can't be preceded
+            // by a match found at a later position. Matches at position `pos`
+            // end at `pos + 1` or later.
+            while let Some(pos) = self.next_candidate {
+                if let Some(Reverse((end, _, _))) = self.pending.peek() {
+                    if *end <= pos + 1 {
+                        break;
+                    }
+                }
+                for i in self.short_atoms.starting_with(self.data[pos]) {
+                    let atom = self.short_atoms.atoms[i].0.as_slice();
+                    let end = pos + atom.len();
+                    if end <= self.span_end
+                        && self.data[pos..].starts_with(atom)
+                    {
+                        self.pending.push(Reverse((end, pos, i)));
+                    }
+                }
+                self.next_candidate = self
+                    .short_atoms
+                    .next_candidate(self.data, pos + 1..self.span_end);
+            }
+
+            let Reverse((end, start, i)) = self.pending.pop()?;
+
+            self.current =
+                Some((start..end, self.short_atoms.atoms[i].1.as_slice(), 0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AtomMatch, AtomSearcher};
+
+    fn find(atoms: &[&[u8]], data: &[u8]) -> Vec<(usize, usize, usize)> {
+        AtomSearcher::new(atoms)
+            .find_overlapping(data, 0..data.len())
+            .map(|m: AtomMatch| (m.atom(), m.start(), m.end()))
+            .collect()
+    }
+
+    #[test]
+    fn short_and_long_atoms() {
+        let atoms: &[&[u8]] =
+            &[b"abc", b"bcdef", b"c", b"cd", b"abc", b"xyz", b"defg"];
+
+        let data = b"xxabcdefgxyzabc";
+
+        let mut expected = vec![
+            (0, 2, 5),
+            (4, 2, 5),
+            (2, 4, 5),
+            (3, 4, 6),
+            (1, 3, 8),
+            (6, 5, 9),
+            (5, 9, 12),
+            (0, 12, 15),
+            (4, 12, 15),
+            (2, 14, 15),
+        ];
+
+        let mut matches = find(atoms, data);
+
+        // Matches must be sorted by end offset.
+        assert!(matches.windows(2).all(|w| w[0].2 <= w[1].2));
+
+        matches.sort();
+        expected.sort();
+
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn span() {
+        let atoms: &[&[u8]] = &[b"ab", b"b", b"abcd"];
+        let searcher = AtomSearcher::new(atoms);
+        let data = b"abcdab";
+
+        let matches: Vec<(usize, usize, usize)> = searcher
+            .find_overlapping(data, 1..5)
+            .map(|m| (m.atom(), m.start(), m.end()))
+            .collect();
+
+        assert_eq!(matches, vec![(1, 1, 2)]);
+    }
+}
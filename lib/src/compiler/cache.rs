@@ -0,0 +1,241 @@
+/*! On-disk cache for compiled rules.
+
+See [`RulesCache`] for details.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+
+use crate::compiler::errors::SerializationError;
+use crate::compiler::{Compiler, Rules, SourceCode};
+
+/// Cache of compiled rules stored in a directory.
+///
+/// Compiling a large set of rules can take a long time. When the same
+/// rules are compiled again and again, for instance by a command-line tool
+/// that is invoked multiple times with the same rules, the compiled rules
+/// can be stored in a cache and loaded from there the next time.
+///
+/// Each entry in the cache is a file with serialized [`Rules`], named
+/// after a key that is computed from everything that affects the
+/// compilation: the version of YARA-X, the options, global variables and
+/// namespace labels of the [`Compiler`] that created the cache (see
+/// [`Compiler::rules_cache`]),
+/// and the namespace, origin and content of each source file passed to
+/// [`RulesCache::add_source`]. Any change produces a different key, so
+/// stale entries are never used.
+///
+/// As the key must be known before compiling the rules, all the source
+/// files must be added to the cache before calling [`RulesCache::load`].
+/// If the rules are not in the cache, they must be compiled with the same
+/// [`Compiler`] and passed to [`RulesCache::store`].
+///
+/// ```no_run
+/// # use yara_x::{Compiler, SourceCode};
+/// let src = "rule test { condition: true }";
+///
+/// let mut compiler = Compiler::new();
+/// let mut cache = compiler.rules_cache("cache");
+///
+/// cache.add_source("default", src);
+///
+/// let rules = match cache.load() {
+///     Some(rules) => rules,
+///     None => {
+///         compiler.new_namespace("default").add_source(src)?;
+///         let rules = compiler.build();
+///         cache.store(&rules)?;
+///         rules
+///     }
+/// };
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct RulesCache {
+    dir: PathBuf,
+    hasher: Sha256,
+}
+
+impl RulesCache {
+    /// Creates a cache that stores its entries in `dir`. The directory is
+    /// created when the first entry is stored.
+    fn new(dir: &Path) -> Self {
+        let mut cache = Self { dir: dir.to_path_buf(), hasher: Sha256::new() };
+        cache.update(env!("CARGO_PKG_VERSION"));
+        cache.update([
+            u8::from(cfg!(feature = "constant-folding")),
+            u8::from(cfg!(feature = "exact-atoms")),
+        ]);
+        cache
+    }
+
+    /// Adds a source file to the cache key.
+    ///
+    /// `namespace` is the namespace where the rules in the source file are
+    /// put when compiled. Source files must be added in the same order in
+    /// which they are compiled.
+    pub fn add_source<'src, S>(&mut self, namespace: &str, src: S) -> &mut Self
+    where
+        S: Into<SourceCode<'src>>,
+    {
+        let src = src.into();
+        self.update(namespace);
+        self.update(src.origin.as_deref().unwrap_or_default());
+        self.update(src.raw);
+        self
+    }
+
+    /// Adds arbitrary data to the cache key.
+    ///
+    /// This is useful when the compiled rules depend on something that is
+    /// not known to the cache, like a custom compiler pass.
+    ///
+    /// The data is prefixed with its length, so that the key for `ab`
+    /// followed by `c` is different from the key for `a` followed by `bc`.
+    pub fn update<D: AsRef<[u8]>>(&mut self, data: D) -> &mut Self {
+        let data = data.as_ref();
+        self.hasher.update((data.len() as u64).to_le_bytes());
+        self.hasher.update(data);
+        self
+    }
+
+    /// Returns the rules stored for the current key, if any.
+    ///
+    /// Entries that can't be read or deserialized, for instance because
+    /// they were written by an incompatible version, are ignored.
+    pub fn load(&self) -> Option<Rules> {
+        let data = fs::read(self.path()).ok()?;
+        Rules::deserialize(data).ok()
+    }
+
+    /// Stores the rules for the current key.
+    pub fn store(&self, rules: &Rules) -> Result<(), SerializationError> {
+        fs::create_dir_all(&self.dir)?;
+
+        let path = self.path();
+
+        // The rules are written to a temporary file that is renamed after
+        // that, so that a concurrent process never sees an incomplete
+        // entry.
+        let tmp_path =
+            path.with_extension(format!("{}.tmp", std::process::id()));
+
+        fs::write(&tmp_path, rules.serialize()?)?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(())
+    }
+
+    /// Returns the path of the file for the current key.
+    fn path(&self) -> PathBuf {
+        let key = self
+            .hasher
+            .clone()
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        self.dir.join(key).with_extension("yarc")
+    }
+}
+
+impl Compiler<'_> {
+    /// Creates a [`RulesCache`] that stores its entries in `dir`.
+    ///
+    /// The key for the cache entries includes the options, global
+    /// variables, namespace labels and warning settings of this compiler at
+    /// the moment this function is called, so it must be called after
+    /// setting them, and before adding any source code to the compiler.
+    /// Custom compiler passes added with [`Compiler::add_pass`] are not
+    /// part of the key, if they change the compiled rules use
+    /// [`RulesCache::update`] for identifying them.
+    pub fn rules_cache<P: AsRef<Path>>(&self, dir: P) -> RulesCache {
+        let mut cache = RulesCache::new(dir.as_ref());
+
+        cache.update([
+            u8::from(self.relaxed_re_syntax),
+            u8::from(self.yara_compat_mode),
+            u8::from(self.error_on_slow_pattern),
+            u8::from(self.error_on_slow_loop),
+            u8::from(self.condition_tracing),
+        ]);
+
+        for limit in [
+            self.max_patterns_per_namespace,
+            self.max_atoms,
+            self.max_condition_nodes,
+        ] {
+            cache.update(limit.map_or(u64::MAX, |n| n as u64).to_le_bytes());
+        }
+
+        cache.update(
+            bincode::serialize(&self.word_boundary)
+                .expect("failed to serialize word boundary"),
+        );
+
+        for modules in [
+            self.ignored_modules.iter().sorted().join(","),
+            self.forbidden_modules.iter().sorted().join(","),
+            self.required_modules.iter().sorted().join(","),
+        ] {
+            cache.update(modules);
+        }
+
+        match &self.allowed_modules {
+            Some(allowed) => cache.update(allowed.iter().sorted().join(",")),
+            None => cache.update([]),
+        };
+
+        for plugin in &self.module_plugins {
+            cache.update(
+                bincode::serialize(plugin)
+                    .expect("failed to serialize module plugin"),
+            );
+        }
+
+        // Labels are stored in the compiled rules.
+        for (namespace, labels) in self
+            .namespace_labels
+            .iter()
+            .map(|(namespace, labels)| {
+                (
+                    self.ident_pool.get(*namespace).unwrap(),
+                    labels
+                        .iter()
+                        .map(|(key, value)| {
+                            (
+                                self.ident_pool.get(*key).unwrap(),
+                                self.ident_pool.get(*value).unwrap(),
+                            )
+                        })
+                        .sorted()
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .sorted()
+        {
+            cache.update(namespace);
+            for (key, value) in labels {
+                cache.update(key);
+                cache.update(value);
+            }
+        }
+
+        // The warnings raised while compiling each rule are also stored in
+        // the compiled rules.
+        cache.update((self.warnings.max_warnings as u64).to_le_bytes());
+        cache
+            .update(self.warnings.disabled_warnings.iter().sorted().join(","));
+
+        // The global variables are fields in the root structure.
+        cache.update(
+            bincode::serialize(&self.root_struct)
+                .expect("failed to serialize global variables"),
+        );
+
+        cache
+    }
+}
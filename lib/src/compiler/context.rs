@@ -1,4 +1,6 @@
+use bumpalo::Bump;
 use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
 use std::mem::size_of;
 use std::rc::Rc;
 
@@ -7,14 +9,17 @@ use yara_x_parser::ast::{Ident, WithSpan};
 use crate::compiler::errors::{CompileError, UnknownPattern};
 use crate::compiler::ir::PatternIdx;
 use crate::compiler::report::ReportBuilder;
-use crate::compiler::{ir, Warnings};
+use crate::compiler::{
+    ir, IdentId, LiteralId, RuleInfo, TracePoint, Warnings,
+};
+use crate::string_pool::{BStringPool, StringPool};
 use crate::symbols::{StackedSymbolTable, SymbolLookup};
 use crate::types::Type;
 use crate::wasm;
 
 /// Structure that contains information and data structures required during the
 /// current compilation process.
-pub(in crate::compiler) struct CompileContext<'a, 'src, 'sym> {
+pub(in crate::compiler) struct CompileContext<'a, 'src, 'sym, 'ir> {
     /// Builder for creating error and warning reports.
     pub report_builder: &'a ReportBuilder,
 
@@ -34,6 +39,15 @@ pub(in crate::compiler) struct CompileContext<'a, 'src, 'sym> {
     /// Warnings generated during the compilation.
     pub warnings: &'a mut Warnings,
 
+    /// Reference to a set that contains the paths of the module fields
+    /// accessed by the current rule (e.g: `pe.sections[].name`).
+    pub current_rule_fields: &'a mut FxHashSet<String>,
+
+    /// Maps loop variables and `with` identifiers to the path of the module
+    /// field they refer to. In `for any s in pe.sections : (...)` the loop
+    /// variable `s` is mapped to `pe.sections[]`.
+    pub field_path_vars: FxHashMap<String, String>,
+
     /// Stack of variables. These are local variables used during the
     /// evaluation of rule conditions, for example for storing loop variables.
     pub vars: VarStack,
@@ -41,16 +55,37 @@ pub(in crate::compiler) struct CompileContext<'a, 'src, 'sym> {
     /// Allow invalid escape sequences in regular expressions.
     pub relaxed_re_syntax: bool,
 
+    /// Accept legacy YARA constructs with a warning instead of an error.
+    pub yara_compat_mode: bool,
+
     /// If true, a slow loop produces an error instead of a warning. A slow
     /// rule is one where the upper bound of the loop is potentially large.
     /// Like for example: `for all x in (0..filesize) : (...)`
     pub error_on_slow_loop: bool,
 
+    /// Vector where the compiler registers the sub-expressions of rule
+    /// conditions that must be traced during the scan. This is `None` when
+    /// condition tracing is disabled.
+    pub condition_traces: Option<&'a mut Vec<TracePoint>>,
+
     /// Indicates how deep we are inside `for .. of` statements.
     pub(crate) for_of_depth: usize,
+
+    /// Rules compiled so far. The last one is the rule being compiled. This
+    /// is used for resolving sets of rules like `any of (foo*)`.
+    pub rules: &'a [RuleInfo],
+
+    /// Pool with the identifiers of rules and namespaces.
+    pub ident_pool: &'a StringPool<IdentId>,
+
+    /// Pool where the string literals in the IR are interned.
+    pub lit_pool: &'a mut BStringPool<LiteralId>,
+
+    /// Arena where the IR nodes for the current rule are allocated.
+    pub arena: &'ir Bump,
 }
 
-impl<'a, 'src, 'sym> CompileContext<'a, 'src, 'sym> {
+impl<'a, 'src, 'sym, 'ir> CompileContext<'a, 'src, 'sym, 'ir> {
     /// Given a pattern identifier (e.g. `$a`, `#a`, `@a`) search for it in
     /// the current rule and return a tuple containing the [`PatternIdx`]
     /// associated to the pattern and a mutable reference the
@@ -93,7 +93,7 @@ macro_rules! emit_arithmetic_op {
                 }
                 $instr.binop(BinaryOp::$float_op);
             } else {
-                $instr.binop(BinaryOp::$int_op);
+                emit_checked_int_op($ctx, $instr, BinaryOp::$int_op);
             }
         }
     }};
@@ -189,6 +189,9 @@ pub(in crate::compiler) struct EmitContext<'a> {
     /// Information about the rule whose condition is being emitted.
     pub current_rule: &'a RuleInfo,
 
+    /// ID of the rule whose condition is being emitted.
+    pub current_rule_id: RuleId,
+
     /// Table with all the symbols (functions, variables) used by WASM.
     pub wasm_symbols: &'a WasmSymbols,
 
@@ -239,7 +242,7 @@ impl<'a> EmitContext<'a> {
     /// The index of a pattern is the position of the pattern in the `strings`
     /// section of the rule.
     pub fn pattern_id(&self, index: PatternIdx) -> PatternId {
-        self.current_rule.patterns[index.as_usize()].1
+        self.current_rule.patterns[index.as_usize()].pattern_id
     }
 }
 
@@ -263,6 +266,20 @@ pub(super) fn emit_rule_condition(
         );
     }
 
+    // Reset the number of loop iterations and the limit for the rule, the
+    // limit set with `Scanner::max_loop_iterations` applies to each rule
+    // individually.
+    instr.i64_const(0);
+    instr.global_set(ctx.wasm_symbols.loop_iterations);
+    instr.global_get(ctx.wasm_symbols.max_loop_iterations);
+    instr.global_set(ctx.wasm_symbols.loop_limit);
+
+    // Increment the number of rules evaluated during the scan.
+    instr.global_get(ctx.wasm_symbols.rules_evaluated);
+    instr.i64_const(1);
+    instr.binop(BinaryOp::I64Add);
+    instr.global_set(ctx.wasm_symbols.rules_evaluated);
+
     // Emit WASM code for the rule's condition.
     catch_undef(
         ctx,
@@ -311,6 +328,9 @@ fn emit_expr(
             }
             t => unreachable!("{:?}", t),
         },
+        Expr::Literal { id, .. } => {
+            instr.i64_const(RuntimeString::Literal(*id).into_wasm());
+        }
 
         Expr::Filesize { .. } => {
             instr.global_get(ctx.wasm_symbols.filesize);
@@ -425,6 +445,9 @@ fn emit_expr(
         Expr::Not { operand } => emit_not(ctx, instr, operand),
         Expr::And { operands } => emit_and(ctx, instr, operands.as_mut()),
         Expr::Or { operands } => emit_or(ctx, instr, operands.as_mut()),
+        Expr::Trace { trace_id, operand } => {
+            emit_trace(ctx, instr, *trace_id, operand)
+        }
 
         Expr::Minus { operand } => {
             match operand.ty() {
@@ -437,7 +460,7 @@ fn emit_expr(
                     // is implemented as i64.sub(0, x).
                     instr.i64_const(0);
                     emit_expr(ctx, instr, operand);
-                    instr.binop(BinaryOp::I64Sub);
+                    emit_checked_int_op(ctx, instr, BinaryOp::I64Sub);
                 }
                 _ => unreachable!(),
             };
@@ -718,6 +741,47 @@ fn emit_not(
     );
 }
 
+/// Emits the code for expressions traced during the scan.
+fn emit_trace(
+    ctx: &mut EmitContext,
+    instr: &mut InstrSeqBuilder,
+    trace_id: usize,
+    operand: &mut Expr,
+) {
+    // The traced expression is emitted as:
+    //
+    //   try {
+    //     trace_expr(trace_id, evaluate_operand())
+    //   } catch undefined {
+    //     trace_expr_undef(trace_id)
+    //     throw undefined
+    //   }
+    //
+    // The undefined value is propagated to the enclosing expression, so
+    // tracing doesn't alter the result of the condition.
+    let trace_id: i32 = trace_id.try_into().unwrap();
+
+    instr.i32_const(trace_id);
+
+    catch_undef(
+        ctx,
+        I32,
+        instr,
+        |ctx, instr| {
+            emit_bool_expr(ctx, instr, operand);
+        },
+        move |ctx, instr| {
+            instr.i32_const(trace_id);
+            instr.call(
+                ctx.function_id(wasm::export__trace_expr_undef.mangled_name),
+            );
+            throw_undef(ctx, instr);
+        },
+    );
+
+    instr.call(ctx.function_id(wasm::export__trace_expr.mangled_name));
+}
+
 /// Emits the code for `and` operations.
 fn emit_and(
     ctx: &mut EmitContext,
@@ -878,7 +942,7 @@ fn emit_div(
             // In integer division make sure that the divisor is not
             // zero, if that's the case the result is undefined.
             throw_undef_if_zero(ctx, instr);
-            instr.binop(BinaryOp::I64DivS);
+            emit_checked_int_op(ctx, instr, BinaryOp::I64DivS);
         }
     }
 }
@@ -1266,7 +1330,7 @@ fn emit_array_indexing(
         Array::Floats(_) => &wasm::export__array_indexing_float,
         Array::Bools(_) => &wasm::export__array_indexing_bool,
         Array::Strings(_) => &wasm::export__array_indexing_string,
-        Array::Structs(_) => &wasm::export__array_indexing_struct,
+        Array::Structs { .. } => &wasm::export__array_indexing_struct,
     };
 
     emit_call_and_handle_undef(ctx, instr, ctx.function_id(func.mangled_name));
@@ -1906,6 +1970,13 @@ fn emit_for<I, B, C, A>(
             // Emit code that checks if loop should finish.
             after_cond(ctx, instr, n);
 
+            // Count the iteration, and throw an exception if the maximum
+            // number of iterations was exceeded. The exception is raised
+            // outside the `catch_undef` block that surrounds the loop's
+            // condition, so that it aborts the loop instead of being handled
+            // as a false condition.
+            emit_count_loop_iteration(ctx, instr);
+
             // Increment `i`.
             incr_var(ctx, instr, i);
 
@@ -2138,6 +2209,63 @@ fn emit_for<I, B, C, A>(
     });
 }
 
+/// Emits code that increments the number of loop iterations executed while
+/// evaluating the current rule, and throws an exception if the maximum number
+/// of iterations is exceeded.
+///
+/// Every 1024 iterations the code also asks the host whether the rule has
+/// exceeded its time limit. When that happens the rule's loop limit is set
+/// to zero, so that any other loop in the rule is aborted too.
+fn emit_count_loop_iteration(
+    ctx: &mut EmitContext,
+    instr: &mut InstrSeqBuilder,
+) {
+    instr.global_get(ctx.wasm_symbols.loop_iterations);
+    instr.i64_const(1);
+    instr.binop(BinaryOp::I64Add);
+    instr.global_set(ctx.wasm_symbols.loop_iterations);
+
+    instr.global_get(ctx.wasm_symbols.loop_iterations);
+    instr.global_get(ctx.wasm_symbols.loop_limit);
+    instr.binop(BinaryOp::I64GtS);
+
+    instr.if_else(
+        None,
+        |then_| {
+            throw_undef(ctx, then_);
+        },
+        |_| {},
+    );
+
+    // Check the time limit in the first iteration, and every 1024 iterations
+    // after that.
+    instr.global_get(ctx.wasm_symbols.loop_iterations);
+    instr.i64_const(1023);
+    instr.binop(BinaryOp::I64And);
+    instr.i64_const(1);
+    instr.binop(BinaryOp::I64Eq);
+
+    instr.if_else(
+        None,
+        |then_| {
+            then_.i32_const(ctx.current_rule_id.into());
+            then_.call(
+                ctx.function_id(wasm::export__rule_timeout_check.mangled_name),
+            );
+            then_.if_else(
+                None,
+                |then_| {
+                    then_.i64_const(0);
+                    then_.global_set(ctx.wasm_symbols.loop_limit);
+                    throw_undef(ctx, then_);
+                },
+                |_| {},
+            );
+        },
+        |_| {},
+    );
+}
+
 /// Emits the code for a `with` statement.
 ///
 /// Each `with` statement has a corresponding <identifier> = <expression> pair.
@@ -2151,11 +2279,24 @@ fn emit_with(
     instr: &mut InstrSeqBuilder,
     with: &mut With,
 ) {
-    // Emit the code that sets the variables in the `with` statement.
+    // Emit the code that sets the variables in the `with` statement. If the
+    // expression assigned to some variable calls `throw_undef`, we capture
+    // the exception and flag the variable as undefined.
     for (id, expr) in with.declarations.iter_mut() {
-        set_var(ctx, instr, *id, |ctx, instr| {
-            emit_expr(ctx, instr, expr);
-        });
+        let var = *id;
+        catch_undef(
+            ctx,
+            None,
+            instr,
+            |ctx, instr| {
+                set_var(ctx, instr, var, |ctx, instr| {
+                    emit_expr(ctx, instr, expr);
+                });
+            },
+            move |ctx, instr| {
+                set_var_undef(ctx, instr, var, true);
+            },
+        );
     }
 
     // Emit the code that evaluates the condition of the `with` statement.
@@ -2811,6 +2952,117 @@ fn throw_undef(ctx: &mut EmitContext, instr: &mut InstrSeqBuilder) {
     ctx.exception_handler_stack.push(innermost_handler);
 }
 
+/// Emits an integer arithmetic operation that throws an exception when the
+/// result overflows.
+///
+/// The operands must be at the top of the stack, the right-hand operand
+/// being the topmost one. Arithmetic operations in rule conditions don't
+/// wrap around, when the result doesn't fit in an `i64` the result is
+/// undefined (see: [`throw_undef`]). `op` must be one of `I64Add`, `I64Sub`,
+/// `I64Mul` or `I64DivS`.
+fn emit_checked_int_op(
+    ctx: &mut EmitContext,
+    instr: &mut InstrSeqBuilder,
+    op: BinaryOp,
+) {
+    let a = ctx.wasm_symbols.i64_tmp_a;
+    let b = ctx.wasm_symbols.i64_tmp_b;
+
+    instr.local_set(b);
+    instr.local_set(a);
+
+    match op {
+        BinaryOp::I64Add | BinaryOp::I64Sub => {
+            // The addition overflows if both operands have the same sign,
+            // and the sign of the result is different. The subtraction
+            // overflows if the operands have different signs, and the sign
+            // of the result is different from the sign of the first operand.
+            // In both cases the sign bit of the following expression is set
+            // when the operation overflows:
+            //
+            //   add: ~(a ^ b) & (a ^ r)
+            //   sub:  (a ^ b) & (a ^ r)
+            //
+            instr.local_get(a);
+            instr.local_get(b);
+            instr.binop(BinaryOp::I64Xor);
+            if matches!(op, BinaryOp::I64Add) {
+                instr.i64_const(-1);
+                instr.binop(BinaryOp::I64Xor);
+            }
+            // Compute the result and keep it in `b`, which is not needed
+            // anymore.
+            instr.local_get(a);
+            instr.local_get(b);
+            instr.binop(op);
+            instr.local_tee(b);
+            instr.local_get(a);
+            instr.binop(BinaryOp::I64Xor);
+            instr.binop(BinaryOp::I64And);
+            instr.i64_const(0);
+            instr.binop(BinaryOp::I64LtS);
+            instr.if_else(None, |then_| throw_undef(ctx, then_), |_| {});
+            instr.local_get(b);
+        }
+        BinaryOp::I64Mul => {
+            // The multiplication overflows if `a` is not zero and either
+            // `(a * b) / a != b`, or `a == -1` and `b == i64::MIN`. The
+            // latter case must be handled separately because `i64::MIN / -1`
+            // traps in WebAssembly.
+            instr.local_get(a);
+            instr.unop(UnaryOp::I64Eqz);
+            instr.if_else(
+                I32,
+                |then_| {
+                    then_.i32_const(0);
+                },
+                |else_| {
+                    else_.local_get(a);
+                    else_.i64_const(-1);
+                    else_.binop(BinaryOp::I64Eq);
+                    else_.if_else(
+                        I32,
+                        |then_| {
+                            then_.local_get(b);
+                            then_.i64_const(i64::MIN);
+                            then_.binop(BinaryOp::I64Eq);
+                        },
+                        |else_| {
+                            else_.local_get(a);
+                            else_.local_get(b);
+                            else_.binop(BinaryOp::I64Mul);
+                            else_.local_get(a);
+                            else_.binop(BinaryOp::I64DivS);
+                            else_.local_get(b);
+                            else_.binop(BinaryOp::I64Ne);
+                        },
+                    );
+                },
+            );
+            instr.if_else(None, |then_| throw_undef(ctx, then_), |_| {});
+            instr.local_get(a);
+            instr.local_get(b);
+            instr.binop(op);
+        }
+        BinaryOp::I64DivS => {
+            // The only case in which the division overflows is
+            // `i64::MIN / -1`, which traps in WebAssembly.
+            instr.local_get(a);
+            instr.i64_const(i64::MIN);
+            instr.binop(BinaryOp::I64Eq);
+            instr.local_get(b);
+            instr.i64_const(-1);
+            instr.binop(BinaryOp::I64Eq);
+            instr.binop(BinaryOp::I32And);
+            instr.if_else(None, |then_| throw_undef(ctx, then_), |_| {});
+            instr.local_get(a);
+            instr.local_get(b);
+            instr.binop(op);
+        }
+        _ => unreachable!(),
+    }
+}
+
 /// Similar to [`throw_undef`], but throws the exception if the top of the
 /// stack is zero. If the top of the stack is non-zero, calling this function
 /// is a no-op.
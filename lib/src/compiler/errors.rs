@@ -47,13 +47,16 @@ pub struct EmitWasmError(#[from] anyhow::Error);
 #[serde(tag = "type")]
 pub enum CompileError {
     AssignmentMismatch(Box<AssignmentMismatch>),
+    ConflictingImport(Box<ConflictingImport>),
     ConflictingRuleIdentifier(Box<ConflictingRuleIdentifier>),
     DuplicateModifier(Box<DuplicateModifier>),
     DuplicatePattern(Box<DuplicatePattern>),
     DuplicateRule(Box<DuplicateRule>),
     DuplicateTag(Box<DuplicateTag>),
     EmptyPatternSet(Box<EmptyPatternSet>),
+    EmptyRuleSet(Box<EmptyRuleSet>),
     EntrypointUnsupported(Box<EntrypointUnsupported>),
+    ForbiddenModule(Box<ForbiddenModule>),
     InvalidBase64Alphabet(Box<InvalidBase64Alphabet>),
     InvalidEscapeSequence(Box<InvalidEscapeSequence>),
     InvalidFloat(Box<InvalidFloat>),
@@ -66,9 +69,12 @@ pub enum CompileError {
     InvalidRegexpModifier(Box<InvalidRegexpModifier>),
     InvalidUTF8(Box<InvalidUTF8>),
     MismatchingTypes(Box<MismatchingTypes>),
+    MissingRequiredModule(Box<MissingRequiredModule>),
     MixedGreediness(Box<MixedGreediness>),
     NumberOutOfRange(Box<NumberOutOfRange>),
     PotentiallySlowLoop(Box<PotentiallySlowLoop>),
+    QuotaExceeded(Box<QuotaExceeded>),
+    RuleRejected(Box<RuleRejected>),
     SlowPattern(Box<SlowPattern>),
     SyntaxError(Box<SyntaxError>),
     UnexpectedEscapeSequence(Box<UnexpectedEscapeSequence>),
@@ -390,6 +396,30 @@ pub struct EmptyPatternSet {
     note: Option<String>,
 }
 
+/// A set of rules doesn't contain any rules.
+///
+/// # Example
+///
+/// ```text
+/// error[E036]: no matching rules
+///  --> line:1:32
+///   |
+/// 1 | rule test { condition: any of (foo*) }
+///   |                                ^^^^ there's no rule in this set
+///   |
+///   = note: `foo*` doesn't match any rule declared before this one
+/// ```
+#[derive(ErrorStruct, Clone, Debug, PartialEq, Eq)]
+#[associated_enum(CompileError)]
+#[error(code = "E036", title = "no matching rules")]
+#[label("there's no rule in this set", error_loc)]
+#[footer(note)]
+pub struct EmptyRuleSet {
+    report: Report,
+    error_loc: CodeLoc,
+    note: Option<String>,
+}
+
 /// The `entrypoint` keyword is not supported.
 #[derive(ErrorStruct, Clone, Debug, PartialEq, Eq)]
 #[associated_enum(CompileError)]
@@ -623,3 +653,141 @@ pub struct PotentiallySlowLoop {
     report: Report,
     loc: CodeLoc,
 }
+
+/// A rule was rejected by a custom compiler pass.
+///
+/// See [`crate::passes::CompilerPass`] for details.
+///
+/// # Example
+///
+/// ```text
+/// error[E035]: rule rejected
+///  --> test.yar:1:6
+///   |
+/// 1 | rule test { condition: true }
+///   |      ^^^^ rules must have a `filesize` guard
+///   |
+/// ```
+#[derive(ErrorStruct, Clone, Debug, PartialEq, Eq)]
+#[associated_enum(CompileError)]
+#[error(code = "E035", title = "rule rejected")]
+#[label("{error}", error_loc)]
+pub struct RuleRejected {
+    report: Report,
+    error: String,
+    error_loc: CodeLoc,
+}
+
+/// A module was imported with a name that already refers to some other
+/// module or rule.
+///
+/// This happens when the alias in an import statement like
+/// `import "pe" as p` is already in use.
+///
+/// # Example
+///
+/// ```text
+/// error[E037]: conflicting import `math`
+///  --> line:2:1
+///   |
+/// 2 | import "pe" as math
+///   | ^^^^^^^^^^^^^^^^^^^ `math` already refers to another module or rule
+///   |
+/// ```
+#[derive(ErrorStruct, Clone, Debug, PartialEq, Eq)]
+#[associated_enum(CompileError)]
+#[error(code = "E037", title = "conflicting import `{name}`")]
+#[label("`{name}` already refers to another module or rule", error_loc)]
+pub struct ConflictingImport {
+    report: Report,
+    name: String,
+    error_loc: CodeLoc,
+}
+
+/// A module that is not allowed by the compiler's policy was imported.
+///
+/// See [`crate::Compiler::forbid_module`] and
+/// [`crate::Compiler::allow_only_modules`].
+///
+/// # Example
+///
+/// ```text
+/// error[E038]: forbidden module `cuckoo`
+///  --> line:1:1
+///   |
+/// 1 | import "cuckoo"
+///   | ^^^^^^^^^^^^^^^ module `cuckoo` can't be used
+///   |
+///   = note: allowed modules: math, pe
+/// ```
+#[derive(ErrorStruct, Clone, Debug, PartialEq, Eq)]
+#[associated_enum(CompileError)]
+#[error(code = "E038", title = "forbidden module `{module}`")]
+#[label("module `{module}` can't be used", error_loc)]
+#[footer(note)]
+pub struct ForbiddenModule {
+    report: Report,
+    module: String,
+    error_loc: CodeLoc,
+    note: Option<String>,
+}
+
+/// A source file with rules doesn't import a module required by the
+/// compiler's policy.
+///
+/// See [`crate::Compiler::require_module`].
+///
+/// # Example
+///
+/// ```text
+/// error[E040]: missing import for required module `pe`
+///  --> line:1:6
+///   |
+/// 1 | rule test { condition: true }
+///   |      ^^^^ this rule must be in a source file that imports `pe`
+///   |
+/// ```
+#[derive(ErrorStruct, Clone, Debug, PartialEq, Eq)]
+#[associated_enum(CompileError)]
+#[error(
+    code = "E040",
+    title = "missing import for required module `{module}`"
+)]
+#[label(
+    "this rule must be in a source file that imports `{module}`",
+    error_loc
+)]
+pub struct MissingRequiredModule {
+    report: Report,
+    module: String,
+    error_loc: CodeLoc,
+}
+
+/// A rule exceeds one of the limits set in the compiler.
+///
+/// See [`crate::Compiler::max_patterns_per_namespace`],
+/// [`crate::Compiler::max_atoms`] and
+/// [`crate::Compiler::max_condition_nodes`].
+///
+/// # Example
+///
+/// ```text
+/// error[E039]: quota exceeded
+///  --> line:1:33
+///   |
+/// 1 | rule test { strings: $a = "foo" $b = "bar" condition: all of them }
+///   |                                 ^^^^^^^^^^ this pattern exceeds the limit of 1 patterns
+///   |
+///   = note: namespace `default` already has 0 patterns
+/// ```
+#[derive(ErrorStruct, Clone, Debug, PartialEq, Eq)]
+#[associated_enum(CompileError)]
+#[error(code = "E039", title = "quota exceeded")]
+#[label("{error}", error_loc)]
+#[footer(note)]
+pub struct QuotaExceeded {
+    report: Report,
+    error: String,
+    error_loc: CodeLoc,
+    note: Option<String>,
+}
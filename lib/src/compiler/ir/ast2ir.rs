@@ -7,6 +7,7 @@ use std::ops::RangeInclusive;
 use std::rc::Rc;
 
 use bstr::{BString, ByteSlice};
+use bumpalo::boxed::Box;
 use itertools::Itertools;
 use yara_x_parser::ast;
 use yara_x_parser::ast::WithSpan;
@@ -14,11 +15,11 @@ use yara_x_parser::Span;
 
 use crate::compiler::errors::{
     AssignmentMismatch, DuplicateModifier, DuplicatePattern, EmptyPatternSet,
-    EntrypointUnsupported, InvalidBase64Alphabet, InvalidModifier,
-    InvalidModifierCombination, InvalidPattern, InvalidRange, InvalidRegexp,
-    MismatchingTypes, MixedGreediness, NumberOutOfRange, SyntaxError,
-    UnexpectedNegativeNumber, UnknownField, UnknownIdentifier, WrongArguments,
-    WrongType,
+    EmptyRuleSet, EntrypointUnsupported, InvalidBase64Alphabet,
+    InvalidModifier, InvalidModifierCombination, InvalidPattern, InvalidRange,
+    InvalidRegexp, MismatchingTypes, MixedGreediness, NumberOutOfRange,
+    SyntaxError, UnexpectedNegativeNumber, UnknownField, UnknownIdentifier,
+    WrongArguments, WrongType,
 };
 use crate::compiler::ir::hex2hir::hex_pattern_hir_from_ast;
 use crate::compiler::ir::{
@@ -27,7 +28,7 @@ use crate::compiler::ir::{
     PatternIdx, PatternInRule, Quantifier, Range, RegexpPattern, With,
 };
 use crate::compiler::report::ReportBuilder;
-use crate::compiler::{warnings, CompileContext, CompileError};
+use crate::compiler::{warnings, CompileContext, CompileError, TracePoint};
 use crate::errors::PotentiallySlowLoop;
 use crate::modules::BUILTIN_MODULES;
 use crate::re;
@@ -36,7 +37,7 @@ use crate::symbols::{Symbol, SymbolKind, SymbolLookup, SymbolTable};
 use crate::types::{Map, Regexp, Type, TypeValue, Value};
 
 pub(in crate::compiler) fn patterns_from_ast<'src>(
-    ctx: &mut CompileContext<'_, 'src, '_>,
+    ctx: &mut CompileContext<'_, 'src, '_, '_>,
     patterns: Option<&Vec<ast::Pattern<'src>>>,
 ) -> Result<(), CompileError> {
     for pattern_ast in patterns.into_iter().flatten() {
@@ -66,14 +67,23 @@ fn pattern_from_ast<'src>(
     ctx: &mut CompileContext,
     pattern: &ast::Pattern<'src>,
 ) -> Result<PatternInRule<'src>, CompileError> {
-    // Check for duplicate pattern modifiers.
+    // Check for duplicate pattern modifiers. In YARA 4.x compatibility
+    // mode they are accepted with a warning.
     let mut modifiers = BTreeSet::new();
     for modifier in pattern.modifiers().iter() {
         if !modifiers.insert(modifier.as_text()) {
-            return Err(DuplicateModifier::build(
-                ctx.report_builder,
-                modifier.span().into(),
-            ));
+            if !ctx.yara_compat_mode {
+                return Err(DuplicateModifier::build(
+                    ctx.report_builder,
+                    modifier.span().into(),
+                ));
+            }
+            ctx.warnings.add(|| {
+                warnings::DuplicateModifier::build(
+                    ctx.report_builder,
+                    modifier.span().into(),
+                )
+            });
         }
     }
     match pattern {
@@ -252,15 +262,32 @@ pub(in crate::compiler) fn hex_pattern_from_ast<'src>(
         }
     }
 
+    let hir = re::hir::Hir::from(hex_pattern_hir_from_ast(ctx, pattern)?);
+    let flags = PatternFlagSet::from(PatternFlags::Ascii);
+
+    // Hex patterns without wildcards, jumps or alternatives, like
+    // `{ 66 6F 6F }`, are converted into literal patterns. This way they
+    // are shared with identical text patterns (e.g: "foo") declared in
+    // this or any other rule.
+    let pattern_ir = match hir.as_literal_bytes() {
+        Some(literal) => Pattern::Literal(LiteralPattern {
+            flags,
+            text: BString::from(literal),
+            anchored_at: None,
+            xor_range: None,
+            base64_alphabet: None,
+            base64wide_alphabet: None,
+        }),
+        None => {
+            Pattern::Regexp(RegexpPattern { flags, hir, anchored_at: None })
+        }
+    };
+
     Ok(PatternInRule {
         identifier: pattern.identifier.clone(),
         in_use: false,
         span: pattern.span(),
-        pattern: Pattern::Regexp(RegexpPattern {
-            flags: PatternFlagSet::from(PatternFlags::Ascii),
-            hir: re::hir::Hir::from(hex_pattern_hir_from_ast(ctx, pattern)?),
-            anchored_at: None,
-        }),
+        pattern: pattern_ir,
     })
 }
 
@@ -349,14 +376,13 @@ pub(in crate::compiler) fn regexp_pattern_from_ast<'src>(
     // matches that start at the same offset are found while scanning backwards
     // (right-to-left). However, if the regexp contains a mix of greedy and
     // non-greedy repetitions the decision becomes impossible.
-    let hir = re::parser::Parser::new()
-        .force_case_insensitive(flags.contains(PatternFlags::Nocase))
-        .allow_mixed_greediness(false)
-        .relaxed_re_syntax(ctx.relaxed_re_syntax)
-        .parse(&pattern.regexp)
-        .map_err(|err| {
-            re_error_to_compile_error(ctx.report_builder, &pattern.regexp, err)
-        })?;
+    let hir = parse_regexp(
+        ctx,
+        re::parser::Parser::new()
+            .force_case_insensitive(flags.contains(PatternFlags::Nocase))
+            .allow_mixed_greediness(false),
+        &pattern.regexp,
+    )?;
 
     // TODO: raise warning when .* used, propose using the non-greedy
     // variant .*?
@@ -374,13 +400,53 @@ pub(in crate::compiler) fn regexp_pattern_from_ast<'src>(
 }
 
 /// Given the AST for some expression, creates its IR.
-pub(in crate::compiler) fn expr_from_ast(
-    ctx: &mut CompileContext,
+pub(in crate::compiler) fn expr_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     expr: &ast::Expr,
-) -> Result<Expr, CompileError> {
+) -> Result<Expr<'ir>, CompileError> {
     match expr {
         ast::Expr::Entrypoint { span } => {
-            Err(EntrypointUnsupported::build(ctx.report_builder, span.into()))
+            // In YARA 4.x compatibility mode `entrypoint` is replaced with
+            // the `entry_point` field of the `pe` or `elf` module, whichever
+            // is imported first.
+            let module = ["pe", "elf"].into_iter().find(|module| {
+                ctx.yara_compat_mode
+                    && ctx.symbol_table.lookup(module).is_some_and(|symbol| {
+                        matches!(
+                            (symbol.kind(), symbol.type_value()),
+                            (SymbolKind::Field(_, true), TypeValue::Struct(_))
+                        )
+                    })
+            });
+
+            let Some(module) = module else {
+                return Err(EntrypointUnsupported::build(
+                    ctx.report_builder,
+                    span.into(),
+                ));
+            };
+
+            ctx.warnings.add(|| {
+                warnings::DeprecatedEntrypoint::build(
+                    ctx.report_builder,
+                    format!("{}.entry_point", module),
+                    span.into(),
+                )
+            });
+
+            expr_from_ast(
+                ctx,
+                &ast::Expr::FieldAccess(std::boxed::Box::new(
+                    ast::NAryExpr::from(vec![
+                        ast::Expr::Ident(std::boxed::Box::new(
+                            ast::Ident::new(module),
+                        )),
+                        ast::Expr::Ident(std::boxed::Box::new(
+                            ast::Ident::new("entry_point"),
+                        )),
+                    ]),
+                )),
+            )
         }
         ast::Expr::Filesize { .. } => Ok(Expr::Filesize),
 
@@ -400,17 +466,14 @@ pub(in crate::compiler) fn expr_from_ast(
             Ok(Expr::Const(TypeValue::const_float_from(literal.value)))
         }
 
-        ast::Expr::LiteralString(literal) => Ok(Expr::Const(
-            TypeValue::const_string_from(literal.value.as_bytes()),
+        ast::Expr::LiteralString(literal) => Ok(Expr::literal(
+            ctx.arena,
+            ctx.lit_pool,
+            literal.value.as_bytes(),
         )),
 
         ast::Expr::Regexp(regexp) => {
-            re::parser::Parser::new()
-                .relaxed_re_syntax(ctx.relaxed_re_syntax)
-                .parse(regexp.as_ref())
-                .map_err(|err| {
-                    re_error_to_compile_error(ctx.report_builder, regexp, err)
-                })?;
+            parse_regexp(ctx, re::parser::Parser::new(), regexp)?;
 
             Ok(Expr::Const(TypeValue::Regexp(Some(Regexp::new(
                 regexp.literal,
@@ -459,7 +522,7 @@ pub(in crate::compiler) fn expr_from_ast(
             let replacement = match (lhs.type_value(), rhs.type_value()) {
                 (TypeValue::Bool(_), TypeValue::Integer(Value::Const(0))) => {
                     Some((
-                        Expr::not(lhs),
+                        Expr::not(ctx.arena, lhs),
                         format!(
                             "not {}",
                             ctx.report_builder.get_snippet(&lhs_span.into())
@@ -468,7 +531,7 @@ pub(in crate::compiler) fn expr_from_ast(
                 }
                 (TypeValue::Integer(Value::Const(0)), TypeValue::Bool(_)) => {
                     Some((
-                        Expr::not(rhs),
+                        Expr::not(ctx.arena, rhs),
                         format!(
                             "not {}",
                             ctx.report_builder.get_snippet(&rhs_span.into())
@@ -551,10 +614,18 @@ pub(in crate::compiler) fn expr_from_ast(
             // If the last operand is constant, the whole expression is
             // constant.
             #[cfg(feature = "constant-folding")]
-            if let Expr::Const(type_value) = last_operand {
+            if let Expr::Const(_) | Expr::Literal { .. } = last_operand {
                 // A constant always have a defined value.
-                assert!(type_value.is_const());
-                return Ok(Expr::Const(type_value));
+                assert!(last_operand.type_value().is_const());
+                return Ok(last_operand);
+            }
+
+            // Keep track of the module fields accessed by the rule. Calls to
+            // module functions are not taken into account.
+            if !matches!(last_operand.type_value(), TypeValue::Func(_)) {
+                if let Some(path) = field_access_path(ctx, &expr.operands) {
+                    ctx.current_rule_fields.insert(path);
+                }
             }
 
             operands.push(last_operand);
@@ -604,12 +675,19 @@ pub(in crate::compiler) fn expr_from_ast(
             #[cfg(feature = "constant-folding")]
             {
                 let type_value = symbol.type_value();
+                if let TypeValue::String(Value::Const(value)) = type_value {
+                    return Ok(Expr::literal(
+                        ctx.arena,
+                        ctx.lit_pool,
+                        value.as_slice(),
+                    ));
+                }
                 if type_value.is_const() {
                     return Ok(Expr::Const(type_value.clone()));
                 }
             }
 
-            Ok(Expr::Ident { symbol })
+            Ok(Expr::Ident { symbol: Box::new_in(symbol, ctx.arena) })
         }
 
         ast::Expr::PatternMatch(p) => {
@@ -632,7 +710,10 @@ pub(in crate::compiler) fn expr_from_ast(
                     // contain an entry for `$`, corresponding to the variable
                     // that holds the current PatternId for the loop.
                     Ok(Expr::PatternMatchVar {
-                        symbol: ctx.symbol_table.lookup("$").unwrap(),
+                        symbol: Box::new_in(
+                            ctx.symbol_table.lookup("$").unwrap(),
+                            ctx.arena,
+                        ),
                         anchor,
                     })
                 }
@@ -642,8 +723,8 @@ pub(in crate::compiler) fn expr_from_ast(
 
                     pattern.mark_as_used();
 
-                    if let Some(offset) = anchor.at() {
-                        pattern.anchor_at(offset as usize);
+                    if let Some(anchor) = anchor.anchor() {
+                        pattern.anchor_at(anchor);
                     } else {
                         pattern.make_non_anchorable();
                     }
@@ -666,11 +747,17 @@ pub(in crate::compiler) fn expr_from_ast(
             match (p.ident.name, &p.range) {
                 // Cases where the identifier is `#`.
                 ("#", Some(range)) => Ok(Expr::PatternCountVar {
-                    symbol: ctx.symbol_table.lookup("$").unwrap(),
+                    symbol: Box::new_in(
+                        ctx.symbol_table.lookup("$").unwrap(),
+                        ctx.arena,
+                    ),
                     range: Some(range_from_ast(ctx, range)?),
                 }),
                 ("#", None) => Ok(Expr::PatternCountVar {
-                    symbol: ctx.symbol_table.lookup("$").unwrap(),
+                    symbol: Box::new_in(
+                        ctx.symbol_table.lookup("$").unwrap(),
+                        ctx.arena,
+                    ),
                     range: None,
                 }),
                 // Cases where the identifier is not `#`.
@@ -708,15 +795,20 @@ pub(in crate::compiler) fn expr_from_ast(
             match (p.ident.name, &p.index) {
                 // Cases where the identifier is `@`.
                 ("@", Some(index)) => Ok(Expr::PatternOffsetVar {
-                    symbol: ctx.symbol_table.lookup("$").unwrap(),
-                    index: Some(Box::new(integer_in_range_from_ast(
-                        ctx,
-                        index,
-                        1..=i64::MAX,
-                    )?)),
+                    symbol: Box::new_in(
+                        ctx.symbol_table.lookup("$").unwrap(),
+                        ctx.arena,
+                    ),
+                    index: Some(Box::new_in(
+                        integer_in_range_from_ast(ctx, index, 1..=i64::MAX)?,
+                        ctx.arena,
+                    )),
                 }),
                 ("@", None) => Ok(Expr::PatternOffsetVar {
-                    symbol: ctx.symbol_table.lookup("$").unwrap(),
+                    symbol: Box::new_in(
+                        ctx.symbol_table.lookup("$").unwrap(),
+                        ctx.arena,
+                    ),
                     index: None,
                 }),
                 // Cases where the identifier is not `@`.
@@ -726,11 +818,14 @@ pub(in crate::compiler) fn expr_from_ast(
                     pattern.make_non_anchorable().mark_as_used();
                     Ok(Expr::PatternOffset {
                         pattern: pattern_idx,
-                        index: Some(Box::new(integer_in_range_from_ast(
-                            ctx,
-                            index,
-                            1..=i64::MAX,
-                        )?)),
+                        index: Some(Box::new_in(
+                            integer_in_range_from_ast(
+                                ctx,
+                                index,
+                                1..=i64::MAX,
+                            )?,
+                            ctx.arena,
+                        )),
                     })
                 }
                 (_, None) => {
@@ -758,15 +853,20 @@ pub(in crate::compiler) fn expr_from_ast(
             match (p.ident.name, &p.index) {
                 // Cases where the identifier is `!`.
                 ("!", Some(index)) => Ok(Expr::PatternLengthVar {
-                    symbol: ctx.symbol_table.lookup("$").unwrap(),
-                    index: Some(Box::new(integer_in_range_from_ast(
-                        ctx,
-                        index,
-                        1..=i64::MAX,
-                    )?)),
+                    symbol: Box::new_in(
+                        ctx.symbol_table.lookup("$").unwrap(),
+                        ctx.arena,
+                    ),
+                    index: Some(Box::new_in(
+                        integer_in_range_from_ast(ctx, index, 1..=i64::MAX)?,
+                        ctx.arena,
+                    )),
                 }),
                 ("!", None) => Ok(Expr::PatternLengthVar {
-                    symbol: ctx.symbol_table.lookup("$").unwrap(),
+                    symbol: Box::new_in(
+                        ctx.symbol_table.lookup("$").unwrap(),
+                        ctx.arena,
+                    ),
                     index: None,
                 }),
                 // Cases where the identifier is not `!`.
@@ -776,11 +876,14 @@ pub(in crate::compiler) fn expr_from_ast(
                     pattern.make_non_anchorable().mark_as_used();
                     Ok(Expr::PatternLength {
                         pattern: pattern_idx,
-                        index: Some(Box::new(integer_in_range_from_ast(
-                            ctx,
-                            index,
-                            1..=i64::MAX,
-                        )?)),
+                        index: Some(Box::new_in(
+                            integer_in_range_from_ast(
+                                ctx,
+                                index,
+                                1..=i64::MAX,
+                            )?,
+                            ctx.arena,
+                        )),
                     })
                 }
                 (_, None) => {
@@ -796,20 +899,20 @@ pub(in crate::compiler) fn expr_from_ast(
         }
 
         ast::Expr::Lookup(expr) => {
-            let primary = Box::new(expr_from_ast(ctx, &expr.primary)?);
+            let primary =
+                Box::new_in(expr_from_ast(ctx, &expr.primary)?, ctx.arena);
 
             match primary.type_value() {
                 TypeValue::Array(array) => {
-                    let index = Box::new(non_negative_integer_from_ast(
-                        ctx,
-                        &expr.index,
-                    )?);
-
-                    Ok(Expr::Lookup(Box::new(Lookup {
-                        type_value: array.deputy(),
-                        primary,
-                        index,
-                    })))
+                    let index = Box::new_in(
+                        non_negative_integer_from_ast(ctx, &expr.index)?,
+                        ctx.arena,
+                    );
+
+                    Ok(Expr::Lookup(Box::new_in(
+                        Lookup { type_value: array.deputy(), primary, index },
+                        ctx.arena,
+                    )))
                 }
                 TypeValue::Map(map) => {
                     let (key_ty, deputy_value) = match map.borrow() {
@@ -822,7 +925,10 @@ pub(in crate::compiler) fn expr_from_ast(
                         _ => unreachable!(),
                     };
 
-                    let index = Box::new(expr_from_ast(ctx, &expr.index)?);
+                    let index = Box::new_in(
+                        expr_from_ast(ctx, &expr.index)?,
+                        ctx.arena,
+                    );
                     let ty = index.ty();
 
                     // The type of the key/index expression should correspond
@@ -837,11 +943,14 @@ pub(in crate::compiler) fn expr_from_ast(
                         ));
                     }
 
-                    Ok(Expr::Lookup(Box::new(Lookup {
-                        type_value: deputy_value.clone(),
-                        primary,
-                        index,
-                    })))
+                    Ok(Expr::Lookup(Box::new_in(
+                        Lookup {
+                            type_value: deputy_value.clone(),
+                            primary,
+                            index,
+                        },
+                        ctx.arena,
+                    )))
                 }
                 type_value => Err(WrongType::build(
                     ctx.report_builder,
@@ -855,10 +964,33 @@ pub(in crate::compiler) fn expr_from_ast(
     }
 }
 
-pub(in crate::compiler) fn bool_expr_from_ast(
-    ctx: &mut CompileContext,
+/// If condition tracing is enabled, wraps `expr` in an [`Expr::Trace`]
+/// that records the value of the expression during the scan. Expressions
+/// whose value is known at compile time are not traced.
+pub(in crate::compiler) fn trace_expr<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
+    expr: Expr<'ir>,
+    span: Span,
+) -> Expr<'ir> {
+    if ctx.condition_traces.is_none() || expr.type_value().is_const() {
+        return expr;
+    }
+
+    let source = ctx.report_builder.get_snippet(&(&span).into());
+    let traces = ctx.condition_traces.as_mut().unwrap();
+
+    traces.push(TracePoint { span: span.range(), source });
+
+    Expr::Trace {
+        trace_id: traces.len() - 1,
+        operand: Box::new_in(expr, ctx.arena),
+    }
+}
+
+pub(in crate::compiler) fn bool_expr_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     ast: &ast::Expr,
-) -> Result<Expr, CompileError> {
+) -> Result<Expr<'ir>, CompileError> {
     let code_loc = ast.span().into();
     let expr = expr_from_ast(ctx, ast)?;
 
@@ -919,11 +1051,11 @@ pub(in crate::compiler) fn bool_expr_from_ast(
     Ok(expr)
 }
 
-fn of_expr_from_ast(
-    ctx: &mut CompileContext,
+fn of_expr_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     of: &ast::Of,
-) -> Result<Expr, CompileError> {
-    let quantifier = quantifier_from_ast(ctx, &of.quantifier)?;
+) -> Result<Expr<'ir>, CompileError> {
+    let mut quantifier = quantifier_from_ast(ctx, &of.quantifier)?;
     // Create new stack frame with 5 slots:
     //   1 slot for the loop variable, a bool in this case.
     //   4 up to slots used for loop control variables (see: emit::emit_for)
@@ -944,6 +1076,12 @@ fn of_expr_from_ast(
             let num_items = tuple.len();
             (OfItems::BoolExprTuple(tuple), num_items)
         }
+        // `x of (foo*, bar.*)`
+        ast::OfItems::RuleSet(rule_set) => {
+            let tuple = rule_set_from_ast(ctx, rule_set)?;
+            let num_items = tuple.len();
+            (OfItems::BoolExprTuple(tuple), num_items)
+        }
         // `x of them`, `x of ($a*, $b)`
         ast::OfItems::PatternSet(pattern_set) => {
             let pattern_indexes = pattern_set_from_ast(ctx, pattern_set)?;
@@ -1019,17 +1157,35 @@ fn of_expr_from_ast(
         }
     }
 
+    // When the percentage is known at compile time, and so is the number of
+    // items, the percentage is replaced with the minimum number of items that
+    // must be true. `50% of ($a, $b, $c)` is equivalent to `2 of ($a, $b, $c)`,
+    // the result is rounded up, like in YARA.
+    if let Quantifier::Percentage(expr) = &quantifier {
+        if let TypeValue::Integer(Value::Const(percentage)) = expr.type_value()
+        {
+            let num_items: i64 = num_items.try_into().unwrap();
+            quantifier =
+                Quantifier::Expr(Expr::Const(TypeValue::const_integer_from(
+                    (num_items * percentage + 99) / 100,
+                )));
+        }
+    }
+
     let anchor = anchor_from_ast(ctx, &of.anchor)?;
 
     ctx.vars.unwind(&stack_frame);
 
-    Ok(Expr::Of(Box::new(Of { quantifier, items, anchor, stack_frame })))
+    Ok(Expr::Of(Box::new_in(
+        Of { quantifier, items, anchor, stack_frame },
+        ctx.arena,
+    )))
 }
 
-fn for_of_expr_from_ast(
-    ctx: &mut CompileContext,
+fn for_of_expr_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     for_of: &ast::ForOf,
-) -> Result<Expr, CompileError> {
+) -> Result<Expr<'ir>, CompileError> {
     let quantifier = quantifier_from_ast(ctx, &for_of.quantifier)?;
     let pattern_set = pattern_set_from_ast(ctx, &for_of.pattern_set)?;
     // Create new stack frame with 5 slots:
@@ -1056,13 +1212,16 @@ fn for_of_expr_from_ast(
     ctx.symbol_table.pop();
     ctx.vars.unwind(&stack_frame);
 
-    Ok(Expr::ForOf(Box::new(ForOf {
-        quantifier,
-        pattern_set,
-        condition,
-        stack_frame,
-        variable: next_pattern_id,
-    })))
+    Ok(Expr::ForOf(Box::new_in(
+        ForOf {
+            quantifier,
+            pattern_set,
+            condition,
+            stack_frame,
+            variable: next_pattern_id,
+        },
+        ctx.arena,
+    )))
 }
 
 fn is_potentially_large_range(range: &Range) -> bool {
@@ -1098,10 +1257,10 @@ fn is_potentially_large_range(range: &Range) -> bool {
         .is_some()
 }
 
-fn for_in_expr_from_ast(
-    ctx: &mut CompileContext,
+fn for_in_expr_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     for_in: &ast::ForIn,
-) -> Result<Expr, CompileError> {
+) -> Result<Expr<'ir>, CompileError> {
     let quantifier = quantifier_from_ast(ctx, &for_in.quantifier)?;
     let iterable = iterable_from_ast(ctx, &for_in.iterable)?;
 
@@ -1183,7 +1342,10 @@ fn for_in_expr_from_ast(
 
     // TODO: raise warning when the loop identifier (e.g: "i") hides
     // an existing identifier with the same name.
+    let mut symbols_types = Vec::with_capacity(loop_vars.len());
+
     for (loop_var, type_value) in iter::zip(loop_vars, expected_vars) {
+        symbols_types.push(matches!(type_value, TypeValue::Struct(_)));
         let var = stack_frame.new_var(type_value.ty());
         variables.push(var);
         symbols.insert(
@@ -1192,6 +1354,30 @@ fn for_in_expr_from_ast(
         );
     }
 
+    // When iterating over an array or map that is a module field, the last
+    // loop variable refers to the items in the array or map. If the items
+    // are structures, accessing their fields is accessing the module field
+    // `<path>[].<field>`.
+    let item_path = match &for_in.iterable {
+        ast::Iterable::Expr(expr) => {
+            field_path(ctx, expr).map(|path| format!("{}[]", path))
+        }
+        _ => None,
+    };
+
+    let saved_paths = set_field_path_vars(
+        ctx,
+        loop_vars.iter().zip(symbols_types).enumerate().map(
+            |(i, (loop_var, is_struct))| {
+                if i == loop_vars.len() - 1 && is_struct {
+                    (loop_var.name, item_path.clone())
+                } else {
+                    (loop_var.name, None)
+                }
+            },
+        ),
+    );
+
     // Put the loop variables into scope.
     ctx.symbol_table.push(Rc::new(symbols));
 
@@ -1199,22 +1385,20 @@ fn for_in_expr_from_ast(
 
     // Leaving the condition's scope. Remove loop variables.
     ctx.symbol_table.pop();
+    restore_field_path_vars(ctx, saved_paths);
 
     ctx.vars.unwind(&stack_frame);
 
-    Ok(Expr::ForIn(Box::new(ForIn {
-        quantifier,
-        variables,
-        iterable,
-        condition,
-        stack_frame,
-    })))
+    Ok(Expr::ForIn(Box::new_in(
+        ForIn { quantifier, variables, iterable, condition, stack_frame },
+        ctx.arena,
+    )))
 }
 
-fn with_expr_from_ast(
-    ctx: &mut CompileContext,
+fn with_expr_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     with: &ast::With,
-) -> Result<Expr, CompileError> {
+) -> Result<Expr<'ir>, CompileError> {
     // Create stack frame with capacity for the with statement variables
     let mut stack_frame = ctx.vars.new_frame(with.declarations.len() as i32);
     let mut symbols = SymbolTable::new();
@@ -1238,6 +1422,16 @@ fn with_expr_from_ast(
         );
     }
 
+    // Identifiers declared in the `with` statement can refer to module
+    // fields, like in `with section = pe.sections[0] : (...)`.
+    let paths = with
+        .declarations
+        .iter()
+        .map(|item| (item.identifier.name, field_path(ctx, &item.expression)))
+        .collect::<Vec<_>>();
+
+    let saved_paths = set_field_path_vars(ctx, paths);
+
     // Put the with variables into scope.
     ctx.symbol_table.push(Rc::new(symbols));
 
@@ -1245,16 +1439,108 @@ fn with_expr_from_ast(
 
     // Leaving with statement condition's scope. Remove with statement variables.
     ctx.symbol_table.pop();
+    restore_field_path_vars(ctx, saved_paths);
 
     ctx.vars.unwind(&stack_frame);
 
-    Ok(Expr::With(Box::new(With { declarations, condition })))
+    Ok(Expr::With(Box::new_in(With { declarations, condition }, ctx.arena)))
 }
 
-fn iterable_from_ast(
+/// Returns the path of the module field referenced by `expr`, like
+/// `pe.sections[].name`. Array and map lookups are represented by `[]`,
+/// regardless of the actual index or key.
+///
+/// Returns `None` if the expression doesn't refer to a module field.
+fn field_path(ctx: &CompileContext, expr: &ast::Expr) -> Option<String> {
+    match expr {
+        ast::Expr::Ident(ident) => {
+            if let Some(path) = ctx.field_path_vars.get(ident.name) {
+                return Some(path.clone());
+            }
+            let symbol = ctx.symbol_table.lookup(ident.name)?;
+            match (symbol.kind(), symbol.type_value()) {
+                (SymbolKind::Field(_, true), TypeValue::Struct(_)) => {
+                    Some(ident.name.to_string())
+                }
+                _ => None,
+            }
+        }
+        ast::Expr::Lookup(lookup) => {
+            Some(format!("{}[]", field_path(ctx, &lookup.primary)?))
+        }
+        ast::Expr::FieldAccess(expr) => field_access_path(ctx, &expr.operands),
+        _ => None,
+    }
+}
+
+/// Like [`field_path`], but receives the operands of a field access
+/// expression.
+fn field_access_path(
+    ctx: &CompileContext,
+    operands: &[ast::Expr],
+) -> Option<String> {
+    fn field_name(expr: &ast::Expr) -> Option<String> {
+        match expr {
+            ast::Expr::Ident(ident) => Some(ident.name.to_string()),
+            ast::Expr::Lookup(lookup) => {
+                Some(format!("{}[]", field_name(&lookup.primary)?))
+            }
+            _ => None,
+        }
+    }
+
+    let (first, rest) = operands.split_first()?;
+    let mut path = field_path(ctx, first)?;
+
+    for operand in rest {
+        path.push('.');
+        path.push_str(field_name(operand)?.as_str());
+    }
+
+    Some(path)
+}
+
+/// Sets the module field paths associated to variables that are entering
+/// into scope. Variables with a `None` path don't refer to a module field,
+/// but they can hide other variables that do.
+///
+/// Returns the previous paths associated to the same identifiers, which must
+/// be restored with [`restore_field_path_vars`] when the variables go out of
+/// scope.
+fn set_field_path_vars<'a>(
+    ctx: &mut CompileContext,
+    vars: impl IntoIterator<Item = (&'a str, Option<String>)>,
+) -> Vec<(String, Option<String>)> {
+    vars.into_iter()
+        .map(|(ident, path)| {
+            let prev = match path {
+                Some(path) => {
+                    ctx.field_path_vars.insert(ident.to_string(), path)
+                }
+                None => ctx.field_path_vars.remove(ident),
+            };
+            (ident.to_string(), prev)
+        })
+        .collect()
+}
+
+/// Restores the module field paths saved by [`set_field_path_vars`].
+fn restore_field_path_vars(
     ctx: &mut CompileContext,
+    saved: Vec<(String, Option<String>)>,
+) {
+    for (ident, path) in saved.into_iter().rev() {
+        match path {
+            Some(path) => ctx.field_path_vars.insert(ident, path),
+            None => ctx.field_path_vars.remove(&ident),
+        };
+    }
+}
+
+fn iterable_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     iter: &ast::Iterable,
-) -> Result<Iterable, CompileError> {
+) -> Result<Iterable<'ir>, CompileError> {
     match iter {
         ast::Iterable::Range(range) => {
             Ok(Iterable::Range(range_from_ast(ctx, range)?))
@@ -1303,30 +1589,36 @@ fn iterable_from_ast(
     }
 }
 
-fn anchor_from_ast(
-    ctx: &mut CompileContext,
+fn anchor_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     anchor: &Option<ast::MatchAnchor>,
-) -> Result<MatchAnchor, CompileError> {
+) -> Result<MatchAnchor<'ir>, CompileError> {
     match anchor {
-        Some(ast::MatchAnchor::At(at_)) => Ok(MatchAnchor::At(Box::new(
+        Some(ast::MatchAnchor::At(at_)) => Ok(MatchAnchor::At(Box::new_in(
             non_negative_integer_from_ast(ctx, &at_.expr)?,
+            ctx.arena,
+        ))),
+        Some(ast::MatchAnchor::In(in_)) => Ok(MatchAnchor::In(Box::new_in(
+            range_from_ast(ctx, &in_.range)?,
+            ctx.arena,
         ))),
-        Some(ast::MatchAnchor::In(in_)) => {
-            Ok(MatchAnchor::In(range_from_ast(ctx, &in_.range)?))
-        }
         None => Ok(MatchAnchor::None),
     }
 }
 
-fn range_from_ast(
-    ctx: &mut CompileContext,
+fn range_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     range: &ast::Range,
-) -> Result<Range, CompileError> {
-    let lower_bound =
-        Box::new(non_negative_integer_from_ast(ctx, &range.lower_bound)?);
+) -> Result<Range<'ir>, CompileError> {
+    let lower_bound = Box::new_in(
+        non_negative_integer_from_ast(ctx, &range.lower_bound)?,
+        ctx.arena,
+    );
 
-    let upper_bound =
-        Box::new(non_negative_integer_from_ast(ctx, &range.upper_bound)?);
+    let upper_bound = Box::new_in(
+        non_negative_integer_from_ast(ctx, &range.upper_bound)?,
+        ctx.arena,
+    );
 
     // If both the lower and upper bounds are known at compile time, make sure
     // that lower_bound <= upper_bound. If they are not know (because they are
@@ -1352,10 +1644,10 @@ fn range_from_ast(
     Ok(Range { lower_bound, upper_bound })
 }
 
-fn non_negative_integer_from_ast(
-    ctx: &mut CompileContext,
+fn non_negative_integer_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     expr: &ast::Expr,
-) -> Result<Expr, CompileError> {
+) -> Result<Expr<'ir>, CompileError> {
     let span = expr.span();
     let expr = expr_from_ast(ctx, expr)?;
     let type_value = expr.type_value();
@@ -1374,11 +1666,11 @@ fn non_negative_integer_from_ast(
     Ok(expr)
 }
 
-fn integer_in_range_from_ast(
-    ctx: &mut CompileContext,
+fn integer_in_range_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     expr: &ast::Expr,
     range: RangeInclusive<i64>,
-) -> Result<Expr, CompileError> {
+) -> Result<Expr<'ir>, CompileError> {
     let span = expr.span();
     let expr = expr_from_ast(ctx, expr)?;
     let type_value = expr.type_value();
@@ -1401,10 +1693,10 @@ fn integer_in_range_from_ast(
     Ok(expr)
 }
 
-fn quantifier_from_ast(
-    ctx: &mut CompileContext,
+fn quantifier_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     quantifier: &ast::Quantifier,
-) -> Result<Quantifier, CompileError> {
+) -> Result<Quantifier<'ir>, CompileError> {
     match quantifier {
         ast::Quantifier::None { .. } => Ok(Quantifier::None),
         ast::Quantifier::All { .. } => Ok(Quantifier::All),
@@ -1423,6 +1715,61 @@ fn quantifier_from_ast(
     }
 }
 
+/// Given a set of rules like `(foo*, bar.*)`, returns a vector with one
+/// expression per rule in the set.
+///
+/// The set is resolved against the rules declared before the current one,
+/// items without a namespace refer to rules in the current namespace.
+fn rule_set_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
+    rule_set: &[ast::RuleSetItem],
+) -> Result<Vec<Expr<'ir>>, CompileError> {
+    // The last rule is the one being compiled, which can't be part of the
+    // set.
+    let (current_rule, rules) = ctx.rules.split_last().unwrap();
+
+    let current_namespace =
+        ctx.ident_pool.get(current_rule.namespace_ident_id).unwrap();
+
+    let mut rule_ids = BTreeSet::new();
+
+    for item in rule_set {
+        let mut matched = false;
+        for (rule_id, rule) in rules.iter().enumerate() {
+            let namespace =
+                ctx.ident_pool.get(rule.namespace_ident_id).unwrap();
+            let ident = ctx.ident_pool.get(rule.ident_id).unwrap();
+            if item.matches(current_namespace, namespace, ident) {
+                rule_ids.insert(rule_id);
+                matched = true;
+            }
+        }
+        if !matched {
+            return Err(EmptyRuleSet::build(
+                ctx.report_builder,
+                item.span().into(),
+                Some(format!(
+                    "`{}` doesn't match any rule declared before this one",
+                    ctx.report_builder.get_snippet(&item.span().into())
+                )),
+            ));
+        }
+    }
+
+    Ok(rule_ids
+        .into_iter()
+        .map(|rule_id| Expr::Ident {
+            symbol: Box::new_in(
+                Symbol::new(
+                    TypeValue::Bool(Value::Unknown),
+                    SymbolKind::Rule(rule_id.into()),
+                ),
+                ctx.arena,
+            ),
+        })
+        .collect())
+}
+
 fn pattern_set_from_ast(
     ctx: &mut CompileContext,
     pattern_set: &ast::PatternSet,
@@ -1496,10 +1843,10 @@ fn pattern_set_from_ast(
     Ok(pattern_indexes)
 }
 
-fn func_call_from_ast(
-    ctx: &mut CompileContext,
+fn func_call_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     func_call: &ast::FuncCall,
-) -> Result<Expr, CompileError> {
+) -> Result<Expr<'ir>, CompileError> {
     let callable = expr_from_ast(ctx, &func_call.callable)?;
     let type_value = callable.type_value();
 
@@ -1570,24 +1917,22 @@ fn func_call_from_ast(
 
     let (signature_index, type_value) = matching_signature.unwrap();
 
-    Ok(Expr::FuncCall(Box::new(FuncCall {
-        callable,
-        type_value,
-        signature_index,
-        args,
-    })))
+    Ok(Expr::FuncCall(Box::new_in(
+        FuncCall { callable, type_value, signature_index, args },
+        ctx.arena,
+    )))
 }
 
-fn matches_expr_from_ast(
-    ctx: &mut CompileContext,
+fn matches_expr_from_ast<'ir>(
+    ctx: &mut CompileContext<'_, '_, '_, 'ir>,
     expr: &ast::BinaryExpr,
-) -> Result<Expr, CompileError> {
+) -> Result<Expr<'ir>, CompileError> {
     let span = expr.span();
     let lhs_span = expr.lhs.span();
     let rhs_span = expr.rhs.span();
 
-    let lhs = Box::new(expr_from_ast(ctx, &expr.lhs)?);
-    let rhs = Box::new(expr_from_ast(ctx, &expr.rhs)?);
+    let lhs = Box::new_in(expr_from_ast(ctx, &expr.lhs)?, ctx.arena);
+    let rhs = Box::new_in(expr_from_ast(ctx, &expr.rhs)?, ctx.arena);
 
     check_type(ctx, lhs.ty(), lhs_span, &[Type::String])?;
     check_type(ctx, rhs.ty(), rhs_span, &[Type::Regexp])?;
@@ -1660,7 +2005,63 @@ fn check_operands(
     Ok(())
 }
 
-fn re_error_to_compile_error(
+/// Parses a regular expression with the given parser, according to the
+/// `relaxed_re_syntax` and `yara_compat_mode` settings.
+///
+/// In YARA 4.x compatibility mode the regular expression is parsed with the
+/// strict syntax first. If it's valid only with the relaxed syntax, it's
+/// accepted with a warning.
+fn parse_regexp(
+    ctx: &mut CompileContext,
+    parser: re::parser::Parser,
+    regexp: &ast::Regexp,
+) -> Result<re::hir::Hir, CompileError> {
+    if !ctx.yara_compat_mode {
+        return parser
+            .relaxed_re_syntax(ctx.relaxed_re_syntax)
+            .parse(regexp)
+            .map_err(|err| {
+                re_error_to_compile_error(ctx.report_builder, regexp, err)
+            });
+    }
+
+    let parser = parser.relaxed_re_syntax(false);
+
+    match parser.parse(regexp) {
+        Err(Error::SyntaxError { msg, span, .. }) => {
+            let hir = parser.relaxed_re_syntax(true).parse(regexp).map_err(
+                |err| {
+                    re_error_to_compile_error(ctx.report_builder, regexp, err)
+                },
+            )?;
+
+            ctx.warnings.add(|| {
+                warnings::RelaxedRegexpSyntax::build(
+                    ctx.report_builder,
+                    msg,
+                    // See `re_error_to_compile_error` for an explanation
+                    // about the adjustment of the span.
+                    regexp
+                        .span()
+                        .subspan(span.start.offset, span.end.offset)
+                        .offset(1)
+                        .into(),
+                    Some(
+                        "YARA-X interprets this regular expression in the same way as YARA 4.x"
+                            .to_string(),
+                    ),
+                )
+            });
+
+            Ok(hir)
+        }
+        result => result.map_err(|err| {
+            re_error_to_compile_error(ctx.report_builder, regexp, err)
+        }),
+    }
+}
+
+pub(in crate::compiler) fn re_error_to_compile_error(
     report_builder: &ReportBuilder,
     regexp: &ast::Regexp,
     err: re::parser::Error,
@@ -1744,11 +2145,11 @@ pub(in crate::compiler) fn warn_if_not_bool(
 }
 
 macro_rules! gen_unary_op {
-    ($name:ident, $variant:ident, $( $accepted_types:path )|+, $check_fn:expr) => {
-        fn $name(
-            ctx: &mut CompileContext,
+    ($name:ident, $variant:ident, $( $accepted_types:path )|+, $check_fn:expr $(, $operand_fn:ident)?) => {
+        fn $name<'ir>(
+            ctx: &mut CompileContext<'_, '_, '_, 'ir>,
             expr: &ast::UnaryExpr,
-        ) -> Result<Expr, CompileError> {
+        ) -> Result<Expr<'ir>, CompileError> {
             let span = expr.span();
             let operand = expr_from_ast(ctx, &expr.operand)?;
 
@@ -1767,7 +2168,9 @@ macro_rules! gen_unary_op {
                 check_fn(ctx, &operand, expr.operand.span())?;
             }
 
-            let expr = Expr::$variant(operand);
+            $( let operand = $operand_fn(ctx, operand, expr.operand.span()); )?
+
+            let expr = Expr::$variant(ctx.arena, operand);
 
             if cfg!(feature = "constant-folding") {
                 expr.fold(ctx, span)
@@ -1780,10 +2183,10 @@ macro_rules! gen_unary_op {
 
 macro_rules! gen_binary_op {
     ($name:ident, $variant:ident, $( $accepted_types:path )|+, $( $compatible_types:path )|+, $check_fn:expr) => {
-        fn $name(
-            ctx: &mut CompileContext,
+        fn $name<'ir>(
+            ctx: &mut CompileContext<'_, '_, '_, 'ir>,
             expr: &ast::BinaryExpr,
-        ) -> Result<Expr, CompileError> {
+        ) -> Result<Expr<'ir>, CompileError> {
             let span = expr.span();
             let lhs_span = expr.lhs.span();
             let rhs_span = expr.rhs.span();
@@ -1809,7 +2212,7 @@ macro_rules! gen_binary_op {
                 check_fn(ctx, &lhs, &rhs, lhs_span, rhs_span)?;
             }
 
-            let expr = Expr::$variant(lhs, rhs);
+            let expr = Expr::$variant(ctx.arena, lhs, rhs);
 
             if cfg!(feature = "constant-folding") {
                 expr.fold(ctx, span)
@@ -1822,10 +2225,10 @@ macro_rules! gen_binary_op {
 
 macro_rules! gen_string_op {
     ($name:ident, $variant:ident) => {
-        fn $name(
-            ctx: &mut CompileContext,
+        fn $name<'ir>(
+            ctx: &mut CompileContext<'_, '_, '_, 'ir>,
             expr: &ast::BinaryExpr,
-        ) -> Result<Expr, CompileError> {
+        ) -> Result<Expr<'ir>, CompileError> {
             let span = expr.span();
             let lhs_span = expr.lhs.span();
             let rhs_span = expr.rhs.span();
@@ -1843,7 +2246,7 @@ macro_rules! gen_string_op {
                 &[Type::String],
             )?;
 
-            let expr = Expr::$variant(lhs, rhs);
+            let expr = Expr::$variant(ctx.arena, lhs, rhs);
 
             if cfg!(feature = "constant-folding") {
                 expr.fold(ctx, span)
@@ -1855,11 +2258,11 @@ macro_rules! gen_string_op {
 }
 
 macro_rules! gen_n_ary_operation {
-    ($name:ident, $variant:ident, $( $accepted_types:path )|+, $( $compatible_types:path )|+, $check_fn:expr) => {
-        fn $name(
-            ctx: &mut CompileContext,
+    ($name:ident, $variant:ident, $( $accepted_types:path )|+, $( $compatible_types:path )|+, $check_fn:expr $(, $operand_fn:ident)?) => {
+        fn $name<'ir>(
+            ctx: &mut CompileContext<'_, '_, '_, 'ir>,
             expr: &ast::NAryExpr,
-        ) -> Result<Expr, CompileError> {
+        ) -> Result<Expr<'ir>, CompileError> {
             let span = expr.span();
             let accepted_types = &[$( $accepted_types ),+];
             let compatible_types = &[$( $compatible_types ),+];
@@ -1911,6 +2314,12 @@ macro_rules! gen_n_ary_operation {
                 }
             }
 
+            $(
+                let operands_hir = iter::zip(operands_hir, expr.operands())
+                    .map(|(hir, ast)| $operand_fn(ctx, hir, ast.span()))
+                    .collect();
+            )?
+
             let expr = Expr::$variant(operands_hir);
 
             if cfg!(feature = "constant-folding") {
@@ -1939,7 +2348,9 @@ gen_unary_op!(
     Some(|ctx, operand, span| {
         warn_if_not_bool(ctx, operand.ty(), span);
         Ok(())
-    })
+    }),
+    // Record the value of each operand if condition tracing is enabled.
+    trace_expr
 );
 
 gen_n_ary_operation!(
@@ -1954,7 +2365,9 @@ gen_n_ary_operation!(
     Some(|ctx, operand, span| {
         warn_if_not_bool(ctx, operand.ty(), span);
         Ok(())
-    })
+    }),
+    // Record the value of each operand if condition tracing is enabled.
+    trace_expr
 );
 
 gen_n_ary_operation!(
@@ -1969,7 +2382,9 @@ gen_n_ary_operation!(
     Some(|ctx, operand, span| {
         warn_if_not_bool(ctx, operand.ty(), span);
         Ok(())
-    })
+    }),
+    // Record the value of each operand if condition tracing is enabled.
+    trace_expr
 );
 
 gen_unary_op!(minus_expr_from_ast, minus, Type::Integer | Type::Float, None);
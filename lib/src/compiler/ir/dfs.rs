@@ -1,9 +1,9 @@
 use crate::compiler::ir::{Expr, Iterable, MatchAnchor, Quantifier};
 
 #[allow(dead_code)]
-pub enum Event<'a> {
-    Enter(&'a Expr),
-    Leave(&'a Expr),
+pub enum Event<'a, 'ir> {
+    Enter(&'a Expr<'ir>),
+    Leave(&'a Expr<'ir>),
 }
 
 /// An iterator that conducts a Depth First Search (DFS) traversal of the IR
@@ -36,13 +36,13 @@ pub enum Event<'a> {
 /// Leave(a)
 /// ```
 ///
-pub struct DepthFirstSearch<'a> {
-    stack: Vec<Event<'a>>,
+pub struct DepthFirstSearch<'a, 'ir> {
+    stack: Vec<Event<'a, 'ir>>,
 }
 
-impl<'a> DepthFirstSearch<'a> {
+impl<'a, 'ir> DepthFirstSearch<'a, 'ir> {
     /// Creates a new [`DepthFirstSearch`] that traverses the given expression.
-    pub fn new(expr: &'a Expr) -> Self {
+    pub fn new(expr: &'a Expr<'ir>) -> Self {
         Self { stack: vec![Event::Enter(expr)] }
     }
 
@@ -71,14 +71,15 @@ impl<'a> DepthFirstSearch<'a> {
     }
 }
 
-impl<'a> Iterator for DepthFirstSearch<'a> {
-    type Item = Event<'a>;
+impl<'a, 'ir> Iterator for DepthFirstSearch<'a, 'ir> {
+    type Item = Event<'a, 'ir>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let next = self.stack.pop()?;
 
         let push_quantifier =
-            |quantifier: &'a Quantifier, stack: &mut Vec<Event<'a>>| {
+            |quantifier: &'a Quantifier<'ir>,
+             stack: &mut Vec<Event<'a, 'ir>>| {
                 match quantifier {
                     Quantifier::None => {}
                     Quantifier::All => {}
@@ -89,15 +90,16 @@ impl<'a> Iterator for DepthFirstSearch<'a> {
             };
 
         let push_anchor =
-            |anchor: &'a MatchAnchor, stack: &mut Vec<Event<'a>>| match anchor
-            {
-                MatchAnchor::None => {}
-                MatchAnchor::At(expr) => {
-                    stack.push(Event::Enter(expr));
-                }
-                MatchAnchor::In(range) => {
-                    stack.push(Event::Enter(&range.upper_bound));
-                    stack.push(Event::Enter(&range.lower_bound));
+            |anchor: &'a MatchAnchor<'ir>, stack: &mut Vec<Event<'a, 'ir>>| {
+                match anchor {
+                    MatchAnchor::None => {}
+                    MatchAnchor::At(expr) => {
+                        stack.push(Event::Enter(expr));
+                    }
+                    MatchAnchor::In(range) => {
+                        stack.push(Event::Enter(&range.upper_bound));
+                        stack.push(Event::Enter(&range.lower_bound));
+                    }
                 }
             };
 
@@ -105,10 +107,12 @@ impl<'a> Iterator for DepthFirstSearch<'a> {
             self.stack.push(Event::Leave(expr));
             match expr {
                 Expr::Const(_) => {}
+                Expr::Literal { .. } => {}
                 Expr::Filesize => {}
                 Expr::Ident { .. } => {}
 
                 Expr::Not { operand }
+                | Expr::Trace { operand, .. }
                 | Expr::Defined { operand }
                 | Expr::Minus { operand }
                 | Expr::BitwiseNot { operand } => {
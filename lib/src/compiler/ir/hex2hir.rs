@@ -6,17 +6,24 @@ use yara_x_parser::ast::WithSpan;
 
 use crate::compiler::context::CompileContext;
 use crate::compiler::errors::{CompileError, InvalidPattern};
-use crate::compiler::{warnings, ByteMaskCombinator};
+use crate::compiler::report::ReportBuilder;
+use crate::compiler::{warnings, ByteMaskCombinator, Warnings};
 
 pub(in crate::compiler) fn hex_pattern_hir_from_ast(
     ctx: &mut CompileContext,
     pattern: &ast::HexPattern,
 ) -> Result<hir::Hir, CompileError> {
-    hex_tokens_hir_from_ast(ctx, &pattern.identifier, &pattern.tokens)
+    hex_tokens_hir_from_ast(
+        ctx.report_builder,
+        ctx.warnings,
+        &pattern.identifier,
+        &pattern.tokens,
+    )
 }
 
-fn hex_tokens_hir_from_ast(
-    ctx: &mut CompileContext,
+pub(in crate::compiler) fn hex_tokens_hir_from_ast(
+    report_builder: &ReportBuilder,
+    warnings: &mut Warnings,
     pattern_ident: &ast::Ident,
     tokens: &ast::HexTokens,
 ) -> Result<hir::Hir, CompileError> {
@@ -32,7 +39,7 @@ fn hex_tokens_hir_from_ast(
                 // ~?? is not allowed.
                 if byte.mask == 0 {
                     return Err(InvalidPattern::build(
-                        ctx.report_builder,
+                        report_builder,
                         pattern_ident.name.to_string(),
                         "negation of `??` is not allowed".to_string(),
                         token.span().into(),
@@ -64,7 +71,8 @@ fn hex_tokens_hir_from_ast(
 
                 for alt in &alt.as_ref().alternatives {
                     alternatives.push(hex_tokens_hir_from_ast(
-                        ctx,
+                        report_builder,
+                        warnings,
                         pattern_ident,
                         alt,
                     )?);
@@ -100,9 +108,9 @@ fn hex_tokens_hir_from_ast(
                 }
 
                 if coalesced {
-                    ctx.warnings.add(|| {
+                    warnings.add(|| {
                         warnings::ConsecutiveJumps::build(
-                            ctx.report_builder,
+                            report_builder,
                             pattern_ident.name.to_string(),
                             format!("{jump}"),
                             (&span).into(),
@@ -113,7 +121,7 @@ fn hex_tokens_hir_from_ast(
                 match (jump.start, jump.end) {
                     (Some(0), Some(0)) => {
                         return Err(InvalidPattern::build(
-                            ctx.report_builder,
+                            report_builder,
                             pattern_ident.name.to_string(),
                             "zero-length jumps are useless, remove it"
                                 .to_string(),
@@ -123,7 +131,7 @@ fn hex_tokens_hir_from_ast(
                     }
                     (Some(start), Some(end)) if start > end => {
                         return Err(InvalidPattern::build(
-                            ctx.report_builder,
+                            report_builder,
                             pattern_ident.name.to_string(),
                             format!(
                                 "lower bound ({start}) is greater than upper bound ({end})"),
@@ -174,11 +182,13 @@ fn hex_byte_to_class(b: &ast::HexByte) -> hir::ClassBytes {
 
 #[cfg(test)]
 mod tests {
+    use bumpalo::Bump;
     use pretty_assertions::assert_eq;
 
     use regex_syntax::hir::{
         Class, ClassBytes, ClassBytesRange, Dot, Hir, HirKind, Repetition,
     };
+    use rustc_hash::{FxHashMap, FxHashSet};
     use yara_x_parser::ast;
 
     use yara_x_parser::ast::{
@@ -191,6 +201,7 @@ mod tests {
     use crate::compiler::Warnings;
     use crate::re::hir;
     use crate::re::hir::class_to_masked_byte;
+    use crate::string_pool::{BStringPool, StringPool};
     use crate::symbols::StackedSymbolTable;
 
     #[test]
@@ -214,17 +225,26 @@ mod tests {
         let mut symbol_table = StackedSymbolTable::new();
         let mut warnings = Warnings::default();
         let mut rule_patterns = vec![];
+        let mut rule_fields = FxHashSet::default();
 
         let mut ctx = CompileContext {
             relaxed_re_syntax: false,
+            yara_compat_mode: false,
             error_on_slow_loop: false,
+            condition_traces: None,
             current_symbol_table: None,
             symbol_table: &mut symbol_table,
             report_builder: &mut report_builder,
             current_rule_patterns: &mut rule_patterns,
             warnings: &mut warnings,
+            current_rule_fields: &mut rule_fields,
+            field_path_vars: FxHashMap::default(),
             vars: VarStack::new(),
             for_of_depth: 0,
+            rules: &[],
+            ident_pool: &StringPool::new(),
+            lit_pool: &mut BStringPool::new(),
+            arena: &Bump::new(),
         };
 
         let mut pattern = HexPattern {
@@ -16,6 +16,12 @@ exactly. This could be done for example for optimization purposes. Another
 example is constant folding, which is done while the IR is being built,
 converting expressions like `2+2+2` into the constant `6`.
 
+The IR for a rule's condition only lives while the rule is being compiled,
+its nodes are allocated in a [Bump] arena that is reset after each rule.
+String literals are interned in the compiler's pool of literals, and the IR
+refers to them by their [`LiteralId`], so equal literals are stored once
+and can be compared without looking at their content.
+
 The portions of the IR representing regular expressions and hex patterns
 are entrusted to the [regex_syntax] crate, particularly to its [Hir] type. This
 crate parses regular expressions and produce the corresponding [Hir]. For hex
@@ -34,21 +40,31 @@ use std::hash::Hash;
 use std::ops::RangeInclusive;
 
 use bitmask::bitmask;
-use bstr::BString;
+use bstr::{BStr, BString};
+use bumpalo::boxed::Box;
+use bumpalo::Bump;
 use serde::{Deserialize, Serialize};
 
 use crate::compiler::context::{CompileContext, Var, VarStackFrame};
+use crate::compiler::{Anchor, LiteralId};
+use crate::string_pool::BStringPool;
 use crate::symbols::Symbol;
 use crate::types::{Type, TypeValue, Value};
 
 pub(in crate::compiler) use ast2ir::bool_expr_from_ast;
 pub(in crate::compiler) use ast2ir::patterns_from_ast;
+pub(in crate::compiler) use ast2ir::re_error_to_compile_error;
+pub(in crate::compiler) use ast2ir::trace_expr;
+pub(in crate::compiler) use hex2hir::hex_tokens_hir_from_ast;
 
 use yara_x_parser::ast::Ident;
 use yara_x_parser::Span;
 
-use crate::compiler::errors::{CompileError, NumberOutOfRange};
-use crate::compiler::ir::dfs::{DepthFirstSearch, Event};
+use crate::compiler::errors::CompileError;
+pub(in crate::compiler) use crate::compiler::ir::dfs::{
+    DepthFirstSearch, Event,
+};
+use crate::compiler::warnings;
 use crate::re;
 
 mod ast2ir;
@@ -122,7 +138,7 @@ impl<'src> PatternInRule<'src> {
     }
 
     #[inline]
-    pub fn anchored_at(&self) -> Option<usize> {
+    pub fn anchored_at(&self) -> Option<Anchor> {
         self.pattern.anchored_at()
     }
 
@@ -140,10 +156,10 @@ impl<'src> PatternInRule<'src> {
     /// different offsets.
     ///
     /// This is used when the condition contains an expression like `$a at 0`
-    /// in order to indicate that the pattern (the `$a` pattern in this case)
-    /// can match only at a fixed offset.
-    pub fn anchor_at(&mut self, offset: usize) -> &mut Self {
-        self.pattern.anchor_at(offset);
+    /// or `$a at filesize - 4` in order to indicate that the pattern (the
+    /// `$a` pattern in this case) can match only at a fixed offset.
+    pub fn anchor_at(&mut self, anchor: Anchor) -> &mut Self {
+        self.pattern.anchor_at(anchor);
         self
     }
 
@@ -208,7 +224,7 @@ impl Pattern {
     }
 
     #[inline]
-    pub fn anchored_at(&self) -> Option<usize> {
+    pub fn anchored_at(&self) -> Option<Anchor> {
         match self {
             Pattern::Literal(literal) => literal.anchored_at,
             Pattern::Regexp(regexp) => regexp.anchored_at,
@@ -224,9 +240,9 @@ impl Pattern {
     /// different offsets.
     ///
     /// This is used when the condition contains an expression like `$a at 0`
-    /// in order to indicate that the pattern (the `$a` pattern in this case)
-    /// can match only at a fixed offset.
-    pub fn anchor_at(&mut self, offset: usize) {
+    /// or `$a at filesize - 4` in order to indicate that the pattern (the
+    /// `$a` pattern in this case) can match only at a fixed offset.
+    pub fn anchor_at(&mut self, anchor: Anchor) {
         let is_anchorable =
             !self.flags().contains(PatternFlags::NonAnchorable);
 
@@ -236,13 +252,13 @@ impl Pattern {
         };
 
         match anchored_at {
-            Some(o) if *o != offset => {
+            Some(a) if *a != anchor => {
                 *anchored_at = None;
                 self.flags_mut().set(PatternFlags::NonAnchorable);
             }
             None => {
                 if is_anchorable {
-                    *anchored_at = Some(offset);
+                    *anchored_at = Some(anchor);
                 }
             }
             _ => {}
@@ -271,7 +287,7 @@ impl Pattern {
 pub(in crate::compiler) struct LiteralPattern {
     pub flags: PatternFlagSet,
     pub text: BString,
-    pub anchored_at: Option<usize>,
+    pub anchored_at: Option<Anchor>,
     pub xor_range: Option<RangeInclusive<u8>>,
     pub base64_alphabet: Option<String>,
     pub base64wide_alphabet: Option<String>,
@@ -281,7 +297,7 @@ pub(in crate::compiler) struct LiteralPattern {
 pub(in crate::compiler) struct RegexpPattern {
     pub flags: PatternFlagSet,
     pub hir: re::hir::Hir,
-    pub anchored_at: Option<usize>,
+    pub anchored_at: Option<Anchor>,
 }
 
 /// The index of a pattern in the rule that declares it.
@@ -306,274 +322,290 @@ impl From<usize> for PatternIdx {
 }
 
 /// Intermediate representation (IR) for an expression.
-pub(in crate::compiler) enum Expr {
+pub(in crate::compiler) enum Expr<'ir> {
     /// Constant value (i.e: the value is known at compile time).
     /// The value in `TypeValue` is not `None`.
     Const(TypeValue),
 
+    /// String literal. The literal is interned in the compiler's pool of
+    /// literals, equal literals have the same `id`. `value` is a copy of the
+    /// literal allocated in the IR arena.
+    Literal {
+        id: LiteralId,
+        value: &'ir BStr,
+    },
+
     /// `filesize` expression.
     Filesize,
 
     /// Boolean `not` expression.
     Not {
-        operand: Box<Expr>,
+        operand: Box<'ir, Expr<'ir>>,
     },
 
     /// Boolean `and` expression.
     And {
-        operands: Vec<Expr>,
+        operands: Vec<Expr<'ir>>,
     },
 
     /// Boolean `or` expression.
     Or {
-        operands: Vec<Expr>,
+        operands: Vec<Expr<'ir>>,
+    },
+
+    /// A boolean expression whose value is recorded during the scan. This
+    /// is used only when condition tracing is enabled. `trace_id` is the
+    /// index of the corresponding [`crate::compiler::TracePoint`].
+    Trace {
+        trace_id: usize,
+        operand: Box<'ir, Expr<'ir>>,
     },
 
     /// Arithmetic minus.
     Minus {
-        operand: Box<Expr>,
+        operand: Box<'ir, Expr<'ir>>,
     },
 
     /// Arithmetic addition (`+`) expression.
     Add {
-        operands: Vec<Expr>,
+        operands: Vec<Expr<'ir>>,
     },
 
     /// Arithmetic subtraction (`-`) expression.
     Sub {
-        operands: Vec<Expr>,
+        operands: Vec<Expr<'ir>>,
     },
 
     /// Arithmetic multiplication (`*`) expression.
     Mul {
-        operands: Vec<Expr>,
+        operands: Vec<Expr<'ir>>,
     },
 
     /// Arithmetic division (`\`) expression.
     Div {
-        operands: Vec<Expr>,
+        operands: Vec<Expr<'ir>>,
     },
 
     /// Arithmetic modulus (`%`) expression.
     Mod {
-        operands: Vec<Expr>,
+        operands: Vec<Expr<'ir>>,
     },
 
     /// Bitwise not (`~`) expression.
     BitwiseNot {
-        operand: Box<Expr>,
+        operand: Box<'ir, Expr<'ir>>,
     },
 
     /// Bitwise and (`&`) expression.
     BitwiseAnd {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Bitwise shift left (`<<`) expression.
     Shl {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Bitwise shift right (`>>`) expression.
     Shr {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Bitwise or (`|`) expression.
     BitwiseOr {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Bitwise xor (`^`) expression.
     BitwiseXor {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Equal (`==`) expression.
     Eq {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Not equal (`!=`) expression.
     Ne {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Less than (`<`) expression.
     Lt {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Greater than (`>`) expression.
     Gt {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Less or equal (`<=`) expression.
     Le {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Greater or equal (`>=`) expression.
     Ge {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// `contains` expression.
     Contains {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// `icontains` expression
     IContains {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// `startswith` expression.
     StartsWith {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// `istartswith` expression
     IStartsWith {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// `endswith` expression.
     EndsWith {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// `iendswith` expression
     IEndsWith {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// `iequals` expression.
     IEquals {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// `matches` expression.
     Matches {
-        rhs: Box<Expr>,
-        lhs: Box<Expr>,
+        rhs: Box<'ir, Expr<'ir>>,
+        lhs: Box<'ir, Expr<'ir>>,
     },
 
     /// Field access expression (e.g. `foo.bar.baz`)
     FieldAccess {
-        operands: Vec<Expr>,
+        operands: Vec<Expr<'ir>>,
     },
 
     /// A `defined` expression (e.g. `defined foo`)
     Defined {
-        operand: Box<Expr>,
+        operand: Box<'ir, Expr<'ir>>,
     },
 
     Ident {
-        symbol: Symbol,
+        symbol: Box<'ir, Symbol>,
     },
 
     /// Pattern match expression (e.g. `$a`)
     PatternMatch {
         pattern: PatternIdx,
-        anchor: MatchAnchor,
+        anchor: MatchAnchor<'ir>,
     },
 
     /// Pattern match expression where the pattern is variable (e.g: `$`).
     PatternMatchVar {
-        symbol: Symbol,
-        anchor: MatchAnchor,
+        symbol: Box<'ir, Symbol>,
+        anchor: MatchAnchor<'ir>,
     },
 
     /// Pattern count expression (e.g. `#a`, `#a in (0..10)`)
     PatternCount {
         pattern: PatternIdx,
-        range: Option<Range>,
+        range: Option<Range<'ir>>,
     },
 
     /// Pattern count expression where the pattern is variable (e.g. `#`, `# in (0..10)`)
     PatternCountVar {
-        symbol: Symbol,
-        range: Option<Range>,
+        symbol: Box<'ir, Symbol>,
+        range: Option<Range<'ir>>,
     },
 
     /// Pattern offset expression (e.g. `@a`, `@a[1]`)
     PatternOffset {
         pattern: PatternIdx,
-        index: Option<Box<Expr>>,
+        index: Option<Box<'ir, Expr<'ir>>>,
     },
 
     /// Pattern count expression where the pattern is variable (e.g. `@`, `@[1]`)
     PatternOffsetVar {
-        symbol: Symbol,
-        index: Option<Box<Expr>>,
+        symbol: Box<'ir, Symbol>,
+        index: Option<Box<'ir, Expr<'ir>>>,
     },
 
     /// Pattern length expression (e.g. `!a`, `!a[1]`)
     PatternLength {
         pattern: PatternIdx,
-        index: Option<Box<Expr>>,
+        index: Option<Box<'ir, Expr<'ir>>>,
     },
 
     /// Pattern count expression where the pattern is variable (e.g. `!`, `![1]`)
     PatternLengthVar {
-        symbol: Symbol,
-        index: Option<Box<Expr>>,
+        symbol: Box<'ir, Symbol>,
+        index: Option<Box<'ir, Expr<'ir>>>,
     },
 
     /// Function call.
-    FuncCall(Box<FuncCall>),
+    FuncCall(Box<'ir, FuncCall<'ir>>),
 
     /// An `of` expression (e.g. `1 of ($a, $b)`, `all of them`)
-    Of(Box<Of>),
+    Of(Box<'ir, Of<'ir>>),
 
     /// A `for <quantifier> of ...` expression. (e.g. `for any of ($a, $b) : ( ... )`)
-    ForOf(Box<ForOf>),
+    ForOf(Box<'ir, ForOf<'ir>>),
 
     /// A `for <quantifier> <vars> in ...` expression. (e.g. `for all i in (1..100) : ( ... )`)
-    ForIn(Box<ForIn>),
+    ForIn(Box<'ir, ForIn<'ir>>),
 
     /// A `with <identifiers> : ...` expression. (e.g. `with $a, $b : ( ... )`)
-    With(Box<With>),
+    With(Box<'ir, With<'ir>>),
 
     /// Array or dictionary lookup expression (e.g. `array[1]`, `dict["key"]`)
-    Lookup(Box<Lookup>),
+    Lookup(Box<'ir, Lookup<'ir>>),
 }
 
 /// A lookup operation in an array or dictionary.
 #[derive(Debug)]
-pub(in crate::compiler) struct Lookup {
+pub(in crate::compiler) struct Lookup<'ir> {
     pub type_value: TypeValue,
-    pub primary: Box<Expr>,
-    pub index: Box<Expr>,
+    pub primary: Box<'ir, Expr<'ir>>,
+    pub index: Box<'ir, Expr<'ir>>,
 }
 
 /// An expression representing a function call.
 #[derive(Debug)]
-pub(in crate::compiler) struct FuncCall {
+pub(in crate::compiler) struct FuncCall<'ir> {
     /// The callable expression, which must resolve in some function identifier.
-    pub callable: Expr,
+    pub callable: Expr<'ir>,
     /// The arguments passed to the function in this call.
-    pub args: Vec<Expr>,
+    pub args: Vec<Expr<'ir>>,
     /// Type and value for the function's result.
     pub type_value: TypeValue,
     /// Due to function overloading, the same function may have multiple
@@ -585,49 +617,49 @@ pub(in crate::compiler) struct FuncCall {
 /// An `of` expression (e.g. `1 of ($a, $b)`, `all of them`,
 /// `any of (true, false)`)
 #[derive(Debug)]
-pub(in crate::compiler) struct Of {
-    pub quantifier: Quantifier,
-    pub items: OfItems,
-    pub anchor: MatchAnchor,
+pub(in crate::compiler) struct Of<'ir> {
+    pub quantifier: Quantifier<'ir>,
+    pub items: OfItems<'ir>,
+    pub anchor: MatchAnchor<'ir>,
     pub stack_frame: VarStackFrame,
 }
 
 /// A `for .. of` expression (e.g `for all of them : (..)`,
 /// `for 1 of ($a,$b) : (..)`)
 #[derive(Debug)]
-pub(in crate::compiler) struct ForOf {
-    pub quantifier: Quantifier,
+pub(in crate::compiler) struct ForOf<'ir> {
+    pub quantifier: Quantifier<'ir>,
     pub variable: Var,
     pub pattern_set: Vec<PatternIdx>,
-    pub condition: Expr,
+    pub condition: Expr<'ir>,
     pub stack_frame: VarStackFrame,
 }
 
 /// A `for .. in` expression (e.g `for all x in iterator : (..)`)
 #[derive(Debug)]
-pub(in crate::compiler) struct ForIn {
-    pub quantifier: Quantifier,
+pub(in crate::compiler) struct ForIn<'ir> {
+    pub quantifier: Quantifier<'ir>,
     pub variables: Vec<Var>,
-    pub iterable: Iterable,
-    pub condition: Expr,
+    pub iterable: Iterable<'ir>,
+    pub condition: Expr<'ir>,
     pub stack_frame: VarStackFrame,
 }
 
 /// A `with` expression (e.g `with $a, $b : (..)`)
 #[derive(Debug)]
-pub(in crate::compiler) struct With {
-    pub declarations: Vec<(Var, Expr)>,
-    pub condition: Expr,
+pub(in crate::compiler) struct With<'ir> {
+    pub declarations: Vec<(Var, Expr<'ir>)>,
+    pub condition: Expr<'ir>,
 }
 
 /// A quantifier used in `for` and `of` expressions.
 #[derive(Debug)]
-pub(in crate::compiler) enum Quantifier {
+pub(in crate::compiler) enum Quantifier<'ir> {
     None,
     All,
     Any,
-    Percentage(Expr),
-    Expr(Expr),
+    Percentage(Expr<'ir>),
+    Expr(Expr<'ir>),
 }
 
 /// In expressions like `$a at 0` and `$b in (0..10)`, this type represents the
@@ -637,214 +669,311 @@ pub(in crate::compiler) enum Quantifier {
 /// where the match can occur.
 /// (e.g. `at <expr>`, `in <range>`).
 #[derive(Debug)]
-pub(in crate::compiler) enum MatchAnchor {
+pub(in crate::compiler) enum MatchAnchor<'ir> {
     None,
-    At(Box<Expr>),
-    In(Range),
+    At(Box<'ir, Expr<'ir>>),
+    In(Box<'ir, Range<'ir>>),
 }
 
-impl MatchAnchor {
-    /// If this anchor is `at <expr>`, and `<expr>` is a constant value,
-    /// return this value. Otherwise, returns `None`.
-    pub fn at(&self) -> Option<i64> {
-        match self {
-            Self::At(expr) => {
-                let value = expr.type_value();
-                if value.is_const() {
-                    value.try_as_integer()
-                } else {
-                    None
-                }
+impl<'ir> MatchAnchor<'ir> {
+    /// If this anchor is `at <expr>`, and `<expr>` is either a non-negative
+    /// constant or `filesize - <constant>`, returns the [`Anchor`] that
+    /// indicates where the pattern must match. Otherwise, returns `None`.
+    pub fn anchor(&self) -> Option<Anchor> {
+        let expr = match self {
+            Self::At(expr) => expr,
+            _ => return None,
+        };
+
+        let const_offset = |expr: &Expr| {
+            let value = expr.type_value();
+            if value.is_const() {
+                value.try_as_integer().and_then(|v| usize::try_from(v).ok())
+            } else {
+                None
             }
-            _ => None,
+        };
+
+        match expr.as_ref() {
+            Expr::Sub { operands } => match operands.as_slice() {
+                [Expr::Filesize, offset] => {
+                    const_offset(offset).map(Anchor::End)
+                }
+                _ => None,
+            },
+            expr => const_offset(expr).map(Anchor::Start),
         }
     }
 }
 
 /// Items in a `of` expression.
 #[derive(Debug)]
-pub(in crate::compiler) enum OfItems {
+pub(in crate::compiler) enum OfItems<'ir> {
     PatternSet(Vec<PatternIdx>),
-    BoolExprTuple(Vec<Expr>),
+    BoolExprTuple(Vec<Expr<'ir>>),
 }
 
 /// A pair of values conforming a range (e.g. `(0..10)`).
 #[derive(Debug)]
-pub(in crate::compiler) struct Range {
-    pub lower_bound: Box<Expr>,
-    pub upper_bound: Box<Expr>,
+pub(in crate::compiler) struct Range<'ir> {
+    pub lower_bound: Box<'ir, Expr<'ir>>,
+    pub upper_bound: Box<'ir, Expr<'ir>>,
 }
 
 /// Possible iterable expressions that can use in a [`ForIn`].
 #[derive(Debug)]
-pub(in crate::compiler) enum Iterable {
-    Range(Range),
-    ExprTuple(Vec<Expr>),
-    Expr(Expr),
+pub(in crate::compiler) enum Iterable<'ir> {
+    Range(Range<'ir>),
+    ExprTuple(Vec<Expr<'ir>>),
+    Expr(Expr<'ir>),
 }
 
-impl Expr {
+impl<'ir> Expr<'ir> {
+    /// Creates a new [`Expr::Literal`], interning `value` in `lit_pool`.
+    pub fn literal(
+        arena: &'ir Bump,
+        lit_pool: &mut BStringPool<LiteralId>,
+        value: &[u8],
+    ) -> Self {
+        Self::Literal {
+            id: lit_pool.get_or_intern(value),
+            value: BStr::new(arena.alloc_slice_copy(value)),
+        }
+    }
+
     /// Creates a new [`Expr::Not`].
-    pub fn not(operand: Expr) -> Self {
-        Self::Not { operand: Box::new(operand) }
+    pub fn not(arena: &'ir Bump, operand: Expr<'ir>) -> Self {
+        Self::Not { operand: Box::new_in(operand, arena) }
     }
 
     /// Creates a new [`Expr::And`].
-    pub fn and(operands: Vec<Expr>) -> Self {
+    pub fn and(operands: Vec<Expr<'ir>>) -> Self {
         Self::And { operands }
     }
 
     /// Creates a new [`Expr::Or`].
-    pub fn or(operands: Vec<Expr>) -> Self {
+    pub fn or(operands: Vec<Expr<'ir>>) -> Self {
         Self::Or { operands }
     }
 
     /// Creates a new [`Expr::Minus`].
-    pub fn minus(operand: Expr) -> Self {
-        Self::Minus { operand: Box::new(operand) }
+    pub fn minus(arena: &'ir Bump, operand: Expr<'ir>) -> Self {
+        Self::Minus { operand: Box::new_in(operand, arena) }
     }
 
     /// Creates a new [`Expr::Defined`].
-    pub fn defined(operand: Expr) -> Self {
-        Self::Defined { operand: Box::new(operand) }
+    pub fn defined(arena: &'ir Bump, operand: Expr<'ir>) -> Self {
+        Self::Defined { operand: Box::new_in(operand, arena) }
     }
 
     /// Creates a new [`Expr::BitwiseNot`].
-    pub fn bitwise_not(operand: Expr) -> Self {
-        Self::BitwiseNot { operand: Box::new(operand) }
+    pub fn bitwise_not(arena: &'ir Bump, operand: Expr<'ir>) -> Self {
+        Self::BitwiseNot { operand: Box::new_in(operand, arena) }
     }
 
     /// Creates a new [`Expr::BitwiseAnd`].
-    pub fn bitwise_and(lhs: Expr, rhs: Expr) -> Self {
-        Self::BitwiseAnd { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn bitwise_and(
+        arena: &'ir Bump,
+        lhs: Expr<'ir>,
+        rhs: Expr<'ir>,
+    ) -> Self {
+        Self::BitwiseAnd {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::BitwiseOr`].
-    pub fn bitwise_or(lhs: Expr, rhs: Expr) -> Self {
-        Self::BitwiseOr { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn bitwise_or(
+        arena: &'ir Bump,
+        lhs: Expr<'ir>,
+        rhs: Expr<'ir>,
+    ) -> Self {
+        Self::BitwiseOr {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::BitwiseXor`].
-    pub fn bitwise_xor(lhs: Expr, rhs: Expr) -> Self {
-        Self::BitwiseXor { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn bitwise_xor(
+        arena: &'ir Bump,
+        lhs: Expr<'ir>,
+        rhs: Expr<'ir>,
+    ) -> Self {
+        Self::BitwiseXor {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::Shl`].
-    pub fn shl(lhs: Expr, rhs: Expr) -> Self {
-        Self::Shl { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn shl(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::Shl {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::Shr`].
-    pub fn shr(lhs: Expr, rhs: Expr) -> Self {
-        Self::Shr { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn shr(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::Shr {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::Add`].
-    pub fn add(operands: Vec<Expr>) -> Self {
+    pub fn add(operands: Vec<Expr<'ir>>) -> Self {
         Self::Add { operands }
     }
 
     /// Creates a new [`Expr::Sub`].
-    pub fn sub(operands: Vec<Expr>) -> Self {
+    pub fn sub(operands: Vec<Expr<'ir>>) -> Self {
         Self::Sub { operands }
     }
 
     /// Creates a new [`Expr::Mul`].
-    pub fn mul(operands: Vec<Expr>) -> Self {
+    pub fn mul(operands: Vec<Expr<'ir>>) -> Self {
         Self::Mul { operands }
     }
 
     /// Creates a new [`Expr::Div`].
-    pub fn div(operands: Vec<Expr>) -> Self {
+    pub fn div(operands: Vec<Expr<'ir>>) -> Self {
         Self::Div { operands }
     }
 
     /// Creates a new [`Expr::Mod`].
-    pub fn modulus(operands: Vec<Expr>) -> Self {
+    pub fn modulus(operands: Vec<Expr<'ir>>) -> Self {
         Self::Mod { operands }
     }
 
     /// Creates a new [`Expr::FieldAccess`].
-    pub fn field_access(operands: Vec<Expr>) -> Self {
+    pub fn field_access(operands: Vec<Expr<'ir>>) -> Self {
         Self::FieldAccess { operands }
     }
 
     /// Creates a new [`Expr::Eq`].
-    pub fn eq(lhs: Expr, rhs: Expr) -> Self {
-        Self::Eq { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn eq(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::Eq { lhs: Box::new_in(lhs, arena), rhs: Box::new_in(rhs, arena) }
     }
 
     /// Creates a new [`Expr::Ne`].
-    pub fn ne(lhs: Expr, rhs: Expr) -> Self {
-        Self::Ne { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn ne(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::Ne { lhs: Box::new_in(lhs, arena), rhs: Box::new_in(rhs, arena) }
     }
 
     /// Creates a new [`Expr::Ge`].
-    pub fn ge(lhs: Expr, rhs: Expr) -> Self {
-        Self::Ge { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn ge(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::Ge { lhs: Box::new_in(lhs, arena), rhs: Box::new_in(rhs, arena) }
     }
 
     /// Creates a new [`Expr::Gt`].
-    pub fn gt(lhs: Expr, rhs: Expr) -> Self {
-        Self::Gt { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn gt(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::Gt { lhs: Box::new_in(lhs, arena), rhs: Box::new_in(rhs, arena) }
     }
 
     /// Creates a new [`Expr::Le`].
-    pub fn le(lhs: Expr, rhs: Expr) -> Self {
-        Self::Le { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn le(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::Le { lhs: Box::new_in(lhs, arena), rhs: Box::new_in(rhs, arena) }
     }
 
     /// Creates a new [`Expr::Lt`].
-    pub fn lt(lhs: Expr, rhs: Expr) -> Self {
-        Self::Lt { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn lt(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::Lt { lhs: Box::new_in(lhs, arena), rhs: Box::new_in(rhs, arena) }
     }
 
     /// Creates a new [`Expr::Contains`].
-    pub fn contains(lhs: Expr, rhs: Expr) -> Self {
-        Self::Contains { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn contains(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::Contains {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::IContains`].
-    pub fn icontains(lhs: Expr, rhs: Expr) -> Self {
-        Self::IContains { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn icontains(
+        arena: &'ir Bump,
+        lhs: Expr<'ir>,
+        rhs: Expr<'ir>,
+    ) -> Self {
+        Self::IContains {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::StartsWith`].
-    pub fn starts_with(lhs: Expr, rhs: Expr) -> Self {
-        Self::StartsWith { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn starts_with(
+        arena: &'ir Bump,
+        lhs: Expr<'ir>,
+        rhs: Expr<'ir>,
+    ) -> Self {
+        Self::StartsWith {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::IStartsWith`].
-    pub fn istarts_with(lhs: Expr, rhs: Expr) -> Self {
-        Self::IStartsWith { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn istarts_with(
+        arena: &'ir Bump,
+        lhs: Expr<'ir>,
+        rhs: Expr<'ir>,
+    ) -> Self {
+        Self::IStartsWith {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::EndsWith`].
-    pub fn ends_with(lhs: Expr, rhs: Expr) -> Self {
-        Self::EndsWith { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn ends_with(
+        arena: &'ir Bump,
+        lhs: Expr<'ir>,
+        rhs: Expr<'ir>,
+    ) -> Self {
+        Self::EndsWith {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::IEndsWith`].
-    pub fn iends_with(lhs: Expr, rhs: Expr) -> Self {
-        Self::IEndsWith { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn iends_with(
+        arena: &'ir Bump,
+        lhs: Expr<'ir>,
+        rhs: Expr<'ir>,
+    ) -> Self {
+        Self::IEndsWith {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Creates a new [`Expr::IEquals`].
-    pub fn iequals(lhs: Expr, rhs: Expr) -> Self {
-        Self::IEquals { lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    pub fn iequals(arena: &'ir Bump, lhs: Expr<'ir>, rhs: Expr<'ir>) -> Self {
+        Self::IEquals {
+            lhs: Box::new_in(lhs, arena),
+            rhs: Box::new_in(rhs, arena),
+        }
     }
 
     /// Returns an iterator that does a DFS traversal of the IR tree.
     ///
     /// See [`DepthFirstSearch`] for details.
-    pub fn dfs_iter(&self) -> DepthFirstSearch {
+    pub fn dfs_iter(&self) -> DepthFirstSearch<'_, 'ir> {
         DepthFirstSearch::new(self)
     }
 
     /// Finds the first expression in DFS order that matches the given
     /// `predicate`, but avoids traversing the descendants of nodes
     /// matching the condition indicated by `prune_if`.
-    pub fn dfs_find<P, C>(&self, predicate: P, prune_if: C) -> Option<&Expr>
+    pub fn dfs_find<P, C>(
+        &self,
+        predicate: P,
+        prune_if: C,
+    ) -> Option<&Expr<'ir>>
     where
         P: Fn(&Expr) -> bool,
         C: Fn(&Expr) -> bool,
@@ -869,11 +998,13 @@ impl Expr {
     pub fn ty(&self) -> Type {
         match self {
             Expr::Const(type_value) => type_value.ty(),
+            Expr::Literal { .. } => Type::String,
 
             Expr::Defined { .. }
             | Expr::Not { .. }
             | Expr::And { .. }
             | Expr::Or { .. }
+            | Expr::Trace { .. }
             | Expr::Eq { .. }
             | Expr::Ne { .. }
             | Expr::Ge { .. }
@@ -939,6 +1070,12 @@ impl Expr {
     pub fn type_value(&self) -> TypeValue {
         match self {
             Expr::Const(type_value) => type_value.clone(),
+            Expr::Literal { value, .. } => TypeValue::const_string_from(value),
+
+            // The value of a traced expression is the value of its operand
+            // casted to bool, which allows folding the traced expression
+            // when the operand is constant.
+            Expr::Trace { operand, .. } => operand.type_value().cast_to_bool(),
 
             Expr::Defined { .. }
             | Expr::Not { .. }
@@ -1014,9 +1151,15 @@ impl Expr {
     ) -> Result<Self, CompileError> {
         match self {
             Expr::Minus { ref operand } => match operand.type_value() {
-                TypeValue::Integer(Value::Const(v)) => {
-                    Ok(Expr::Const(TypeValue::const_integer_from(-v)))
-                }
+                TypeValue::Integer(Value::Const(v)) => match v.checked_neg() {
+                    Some(v) => {
+                        Ok(Expr::Const(TypeValue::const_integer_from(v)))
+                    }
+                    None => {
+                        Self::warn_overflow(ctx, span);
+                        Ok(self)
+                    }
+                },
                 TypeValue::Float(Value::Const(v)) => {
                     Ok(Expr::Const(TypeValue::const_float_from(-v)))
                 }
@@ -1078,7 +1221,17 @@ impl Expr {
                     return Ok(Expr::Add { operands });
                 }
 
-                Self::fold_arithmetic(ctx, span, operands, |acc, x| acc + x)
+                match Self::fold_arithmetic(
+                    &operands,
+                    i64::checked_add,
+                    |acc, x| acc + x,
+                ) {
+                    Some(folded) => Ok(folded),
+                    None => {
+                        Self::warn_overflow(ctx, span);
+                        Ok(Expr::Add { operands })
+                    }
+                }
             }
             Expr::Sub { operands } => {
                 // If not all operands are constant, there's nothing to fold.
@@ -1086,7 +1239,17 @@ impl Expr {
                     return Ok(Expr::Sub { operands });
                 }
 
-                Self::fold_arithmetic(ctx, span, operands, |acc, x| acc - x)
+                match Self::fold_arithmetic(
+                    &operands,
+                    i64::checked_sub,
+                    |acc, x| acc - x,
+                ) {
+                    Some(folded) => Ok(folded),
+                    None => {
+                        Self::warn_overflow(ctx, span);
+                        Ok(Expr::Sub { operands })
+                    }
+                }
             }
             Expr::Mul { operands } => {
                 // If not all operands are constant, there's nothing to fold.
@@ -1094,56 +1257,86 @@ impl Expr {
                     return Ok(Expr::Mul { operands });
                 }
 
-                Self::fold_arithmetic(ctx, span, operands, |acc, x| acc * x)
+                match Self::fold_arithmetic(
+                    &operands,
+                    i64::checked_mul,
+                    |acc, x| acc * x,
+                ) {
+                    Some(folded) => Ok(folded),
+                    None => {
+                        Self::warn_overflow(ctx, span);
+                        Ok(Expr::Mul { operands })
+                    }
+                }
             }
             _ => Ok(self),
         }
     }
 
-    pub fn fold_arithmetic<F>(
-        ctx: &mut CompileContext,
-        span: Span,
-        operands: Vec<Expr>,
-        f: F,
-    ) -> Result<Self, CompileError>
+    /// Folds an arithmetic operation where all the operands are constant.
+    ///
+    /// If all the operands are integers, the operation is performed with
+    /// `int_op`, which returns `None` when the result overflows. If any of
+    /// the operands is a float, the operation is performed with `float_op`.
+    /// Returns `None` if the integer operation overflows.
+    pub fn fold_arithmetic<I, F>(
+        operands: &[Expr],
+        int_op: I,
+        float_op: F,
+    ) -> Option<Self>
     where
-        F: FnMut(f64, f64) -> f64,
+        I: Fn(i64, i64) -> Option<i64>,
+        F: Fn(f64, f64) -> f64,
     {
         debug_assert!(!operands.is_empty());
 
-        let mut is_float = false;
+        let is_float = operands.iter().any(|operand| {
+            matches!(operand.type_value(), TypeValue::Float(_))
+        });
 
-        let result = operands
-            .iter()
-            .map(|operand| match operand.type_value() {
-                TypeValue::Integer(Value::Const(v)) => v as f64,
-                TypeValue::Float(Value::Const(v)) => {
-                    is_float = true;
-                    v
-                }
+        if is_float {
+            let result = operands
+                .iter()
+                .map(|operand| match operand.type_value() {
+                    TypeValue::Integer(Value::Const(v)) => v as f64,
+                    TypeValue::Float(Value::Const(v)) => v,
+                    _ => unreachable!(),
+                })
+                .reduce(float_op)
+                // It's safe to call unwrap because there must be at least
+                // one operand.
+                .unwrap();
+
+            return Some(Expr::Const(TypeValue::const_float_from(result)));
+        }
+
+        let mut values =
+            operands.iter().map(|operand| match operand.type_value() {
+                TypeValue::Integer(Value::Const(v)) => v,
                 _ => unreachable!(),
-            })
-            .reduce(f)
-            // It's safe to call unwrap because there must be at least
-            // one iterator.
-            .unwrap();
+            });
 
-        if is_float {
-            Ok(Expr::Const(TypeValue::const_float_from(result)))
-        } else if result >= i64::MIN as f64 && result <= i64::MAX as f64 {
-            Ok(Expr::Const(TypeValue::const_integer_from(result as i64)))
-        } else {
-            Err(NumberOutOfRange::build(
+        // It's safe to call unwrap because there must be at least one
+        // operand.
+        let first = values.next().unwrap();
+
+        values
+            .try_fold(first, int_op)
+            .map(|result| Expr::Const(TypeValue::const_integer_from(result)))
+    }
+
+    /// Raises a warning indicating that the expression at `span` overflows.
+    fn warn_overflow(ctx: &mut CompileContext, span: Span) {
+        ctx.warnings.add(|| {
+            warnings::ArithmeticOverflow::build(
                 ctx.report_builder,
-                i64::MIN,
-                i64::MAX,
                 span.into(),
-            ))
-        }
+            )
+        });
     }
 }
 
-impl Debug for Expr {
+impl Debug for Expr<'_> {
     #[rustfmt::skip]
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut level = 1;
@@ -1172,10 +1365,17 @@ impl Debug for Expr {
                     level += 1;
                     match expr {
                         Expr::Const(c) => writeln!(f, "CONST {}", c)?,
+                        Expr::Literal { id, value } => writeln!(
+                            f,
+                            "LITERAL {} {:?}",
+                            u32::from(*id),
+                            value
+                        )?,
                         Expr::Filesize => writeln!(f, "FILESIZE")?,
                         Expr::Not { .. } => writeln!(f, "NOT")?,
                         Expr::And { .. } => writeln!(f, "AND")?,
                         Expr::Or { .. } => writeln!(f, "OR")?,
+                        Expr::Trace { trace_id, .. } => writeln!(f, "TRACE {}", trace_id)?,
                         Expr::Minus { .. } => writeln!(f, "MINUS")?,
                         Expr::Add { .. } => writeln!(f, "ADD")?,
                         Expr::Sub { .. } => writeln!(f, "SUB")?,
@@ -1,6 +1,8 @@
+use crate::compiler::ir::Expr;
 use crate::Compiler;
 use std::fs;
 use std::io::BufWriter;
+use std::mem::size_of;
 
 #[test]
 fn ir() {
@@ -31,3 +33,11 @@ fn ir() {
         compiler.set_ir_writer(w).add_source(source.as_str()).unwrap();
     });
 }
+
+#[test]
+fn expr_size() {
+    // Conditions can have a large number of nodes, any variant that makes
+    // `Expr` larger increases the memory used by all of them. Large fields
+    // must be allocated in the arena instead of stored inline.
+    assert_eq!(size_of::<Expr>(), 32);
+}
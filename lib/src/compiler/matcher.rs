@@ -0,0 +1,252 @@
+/*! Standalone matching of hex patterns and regular expressions.
+
+The engine used by YARA-X for matching hex patterns and regular expressions
+can be used on its own, without creating a whole set of YARA rules. A
+[`Matcher`] is created from a single pattern, written with the same syntax
+used in the `strings` section of a YARA rule, and finds all the matches for
+that pattern in a buffer.
+
+# Example
+
+```rust
+# use yara_x::matcher::Matcher;
+let matcher = Matcher::new("{ 4D 5A [2] ( 00 | FF ) }").unwrap();
+assert_eq!(matcher.find_all(b"..MZ..\x00.."), vec![2..7]);
+
+let matcher = Matcher::new("/fo+bar/i").unwrap();
+assert_eq!(matcher.find_all(b"FOObar, foooobar"), vec![0..6, 8..16]);
+```
+*/
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use aho_corasick::AhoCorasick;
+use yara_x_parser::ast::{self, WithSpan};
+use yara_x_parser::{Parser, Span};
+
+use crate::compiler::errors::{
+    CompileError, InvalidModifier, InvalidRegexp, SyntaxError,
+};
+use crate::compiler::report::ReportBuilder;
+use crate::compiler::{
+    hex_tokens_hir_from_ast, re_error_to_compile_error, SourceCode, Warnings,
+};
+use crate::re;
+use crate::re::thompson::PikeVM;
+use crate::re::{Action, RegexpAtom, WordBoundary, DEFAULT_SCAN_LIMIT};
+
+/// Finds the matches of a hex pattern, regular expression or text string.
+///
+/// The pattern is compiled into code for the same VM that is used while
+/// scanning with YARA rules, and it follows the same semantics. This means
+/// that for each offset in the data there's at most one match, the longest
+/// one if the pattern is greedy, and that the portion of the data matched
+/// before and after the pattern's atom is limited by the scan limit (see
+/// [`Matcher::scan_limit`]).
+///
+/// Patterns can't have modifiers, like `nocase` or `wide`. However,
+/// regular expressions accept the `/i` and `/s` suffixes.
+pub struct Matcher {
+    code: Vec<u8>,
+    atoms: Vec<RegexpAtom>,
+    ac: AhoCorasick,
+    greedy: bool,
+    scan_limit: u16,
+    word_boundary: WordBoundary,
+}
+
+impl Matcher {
+    /// Creates a new [`Matcher`] for the given pattern.
+    ///
+    /// The pattern can be a hex pattern (e.g. `{ 4D 5A ?? 00 }`), a regular
+    /// expression (e.g. `/foo.*bar/i`) or a text string (e.g. `"foo"`).
+    pub fn new(pattern: &str) -> Result<Self, CompileError> {
+        let src = format!(
+            "rule matcher {{ strings: $a = {} condition: $a }}",
+            pattern
+        );
+
+        let report_builder = ReportBuilder::new();
+
+        report_builder.register_source(&SourceCode::from(src.as_str()));
+
+        let ast = ast::AST::from(Parser::new(src.as_bytes()));
+
+        if !ast.errors().is_empty() {
+            return Err(CompileError::from(
+                &report_builder,
+                ast.into_errors().remove(0),
+            ));
+        }
+
+        let pattern = match ast.rules() {
+            [rule] => match rule.patterns.as_deref() {
+                Some([pattern]) => Some(pattern),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| {
+            SyntaxError::build(
+                &report_builder,
+                "expecting a single pattern".to_string(),
+                Span(0..src.len() as u32).into(),
+            )
+        })?;
+
+        let modifiers = match pattern {
+            ast::Pattern::Text(p) => &p.modifiers,
+            ast::Pattern::Hex(p) => &p.modifiers,
+            ast::Pattern::Regexp(p) => &p.modifiers,
+        };
+
+        if let Some(modifier) = modifiers.iter().next() {
+            return Err(InvalidModifier::build(
+                &report_builder,
+                "modifiers are not accepted by standalone patterns"
+                    .to_string(),
+                modifier.span().into(),
+            ));
+        }
+
+        let hir = match pattern {
+            ast::Pattern::Text(p) => re::hir::Hir::from(
+                regex_syntax::hir::Hir::literal(&**p.text.value),
+            ),
+            ast::Pattern::Hex(p) => {
+                re::hir::Hir::from(hex_tokens_hir_from_ast(
+                    &report_builder,
+                    &mut Warnings::default(),
+                    &p.identifier,
+                    &p.tokens,
+                )?)
+            }
+            ast::Pattern::Regexp(p) => re::parser::Parser::new()
+                .allow_mixed_greediness(false)
+                .parse(&p.regexp)
+                .map_err(|err| {
+                    re_error_to_compile_error(&report_builder, &p.regexp, err)
+                })?,
+        };
+
+        if matches!(hir.minimum_len(), Some(0)) {
+            return Err(InvalidRegexp::build(
+                &report_builder,
+                "this pattern can match empty strings".to_string(),
+                pattern.span().into(),
+                None,
+            ));
+        }
+
+        let mut code = Vec::new();
+
+        let atoms = re::thompson::Compiler::new()
+            .compile(&hir, &mut code)
+            .map_err(|_| {
+                InvalidRegexp::build(
+                    &report_builder,
+                    "pattern is too large".to_string(),
+                    pattern.span().into(),
+                    None,
+                )
+            })?;
+
+        let ac = AhoCorasick::new(atoms.iter().map(|atom| atom.atom.as_ref()))
+            .expect("failed to build Aho-Corasick automaton");
+
+        Ok(Self {
+            code,
+            atoms,
+            ac,
+            greedy: matches!(hir.is_greedy(), Some(true)),
+            scan_limit: DEFAULT_SCAN_LIMIT,
+            word_boundary: WordBoundary::default(),
+        })
+    }
+
+    /// Sets the maximum number of bytes that are examined before and after
+    /// each occurrence of the pattern's atom.
+    ///
+    /// Matches that are longer than this limit on either side of the atom
+    /// are not found. The default value is 4096.
+    pub fn scan_limit(mut self, limit: u16) -> Self {
+        self.scan_limit = limit;
+        self
+    }
+
+    /// Specifies which characters are considered word characters by word
+    /// boundary assertions like `\b`. See [`WordBoundary`].
+    pub fn word_boundary(mut self, word_boundary: WordBoundary) -> Self {
+        self.word_boundary = word_boundary;
+        self
+    }
+
+    /// Returns the ranges of `data` where the pattern matches, sorted by
+    /// starting offset.
+    pub fn find_all(&self, data: &[u8]) -> Vec<Range<usize>> {
+        let mut pike_vm = PikeVM::new(&self.code)
+            .scan_limit(self.scan_limit)
+            .word_boundary(self.word_boundary);
+        let mut matches = BTreeMap::new();
+
+        for ac_match in self.ac.find_overlapping_iter(data) {
+            let atom = &self.atoms[ac_match.pattern().as_usize()];
+
+            let atom_pos = match ac_match
+                .start()
+                .checked_sub(atom.atom.backtrack() as usize)
+            {
+                Some(atom_pos) => atom_pos,
+                None => continue,
+            };
+
+            let (fwd_code, bck_code) = match (atom.fwd_code, atom.bck_code) {
+                (Some(fwd_code), Some(bck_code)) => (fwd_code, bck_code),
+                _ => unreachable!(),
+            };
+
+            let mut fwd_match_len = None;
+
+            pike_vm.try_match(
+                fwd_code,
+                &data[atom_pos..],
+                &data[..atom_pos],
+                false,
+                |match_len| {
+                    fwd_match_len = Some(match_len);
+                    Action::Stop
+                },
+            );
+
+            let fwd_match_len = match fwd_match_len {
+                Some(len) => len,
+                None => continue,
+            };
+
+            pike_vm.try_match(
+                bck_code,
+                &data[atom_pos..],
+                &data[..atom_pos],
+                false,
+                |bck_match_len| {
+                    let end = atom_pos + fwd_match_len;
+                    match matches.entry(atom_pos - bck_match_len) {
+                        Entry::Vacant(entry) => {
+                            entry.insert(end);
+                        }
+                        Entry::Occupied(mut entry) => {
+                            if self.greedy && *entry.get() < end {
+                                entry.insert(end);
+                            }
+                        }
+                    }
+                    Action::Continue
+                },
+            );
+        }
+
+        matches.into_iter().map(|(start, end)| start..end).collect()
+    }
+}
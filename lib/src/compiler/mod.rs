@@ -9,6 +9,7 @@ use std::collections::hash_map::Entry;
 use std::collections::HashSet;
 #[cfg(test)]
 use std::io::Write;
+use std::mem;
 use std::ops::RangeInclusive;
 use std::path::Path;
 use std::rc::Rc;
@@ -19,7 +20,8 @@ use std::{fmt, iter};
 use bincode::Options;
 use bitmask::bitmask;
 use bstr::{BStr, ByteSlice};
-use itertools::izip;
+use bumpalo::Bump;
+use itertools::{izip, Itertools};
 #[cfg(feature = "logging")]
 use log::*;
 use regex_syntax::hir;
@@ -35,14 +37,19 @@ use yara_x_parser::{Parser, Span};
 use crate::compiler::base64::base64_patterns;
 use crate::compiler::emit::{emit_rule_condition, EmitContext};
 use crate::compiler::errors::{
-    CompileError, ConflictingRuleIdentifier, DuplicateRule, DuplicateTag,
-    EmitWasmError, InvalidRegexp, InvalidUTF8, UnknownModule, UnusedPattern,
+    CompileError, ConflictingImport, ConflictingRuleIdentifier, DuplicateRule,
+    DuplicateTag, EmitWasmError, ForbiddenModule, InvalidRegexp, InvalidUTF8,
+    MissingRequiredModule, QuotaExceeded, RuleRejected, UnknownModule,
+    UnusedPattern,
 };
+use crate::compiler::passes::{CompilerPass, Condition, MAX_ADDED_NODES};
 use crate::compiler::report::{CodeLoc, ReportBuilder};
 use crate::compiler::{CompileContext, VarStack};
-use crate::modules::BUILTIN_MODULES;
+use crate::models::PatternModifier;
+use crate::modules::{ModulePlugin, BUILTIN_MODULES};
 use crate::re;
 use crate::re::hir::ChainedPattern;
+use crate::re::WordBoundary;
 use crate::string_pool::{BStringPool, StringPool};
 use crate::symbols::{
     StackedSymbolTable, Symbol, SymbolKind, SymbolLookup, SymbolTable,
@@ -63,7 +70,13 @@ pub use crate::compiler::rules::*;
 #[doc(inline)]
 pub use crate::compiler::warnings::*;
 
+#[cfg(feature = "rules-cache")]
+#[doc(inline)]
+pub use crate::compiler::cache::RulesCache;
+
 mod atoms;
+#[cfg(feature = "rules-cache")]
+mod cache;
 mod context;
 mod emit;
 mod ir;
@@ -75,6 +88,9 @@ mod tests;
 
 pub mod base64;
 pub mod errors;
+pub mod matcher;
+pub mod passes;
+pub mod templates;
 pub mod warnings;
 
 /// A structure that describes some YARA source code.
@@ -240,6 +256,10 @@ pub struct Compiler<'a> {
     /// escape sequences.
     relaxed_re_syntax: bool,
 
+    /// Accepts some legacy YARA constructs that are invalid in YARA-X,
+    /// raising warnings instead of errors.
+    yara_compat_mode: bool,
+
     /// If true, slow patterns produce an error instead of a warning. A slow
     /// pattern is one with atoms shorter than 2 bytes.
     error_on_slow_pattern: bool,
@@ -249,6 +269,28 @@ pub struct Compiler<'a> {
     /// Like for example: `for all x in (0..filesize) : (...)`
     error_on_slow_loop: bool,
 
+    /// Maximum number of patterns in a namespace, see
+    /// [`Compiler::max_patterns_per_namespace`].
+    max_patterns_per_namespace: Option<usize>,
+
+    /// Maximum number of atoms in the compiled rules, see
+    /// [`Compiler::max_atoms`].
+    max_atoms: Option<usize>,
+
+    /// Maximum number of nodes in a rule's condition, see
+    /// [`Compiler::max_condition_nodes`].
+    max_condition_nodes: Option<usize>,
+
+    /// Number of patterns declared by the rules in the current namespace.
+    namespace_patterns: usize,
+
+    /// If true, the compiler emits code that records the value of each
+    /// boolean sub-expression in rule conditions while scanning.
+    condition_tracing: bool,
+
+    /// Semantics of word boundary assertions in regexp patterns.
+    word_boundary: WordBoundary,
+
     /// Used for generating error and warning reports.
     report_builder: ReportBuilder,
 
@@ -339,10 +381,29 @@ pub struct Compiler<'a> {
     /// vector. This vector contains both forward and backward code.
     re_code: Vec<u8>,
 
+    /// Sub-expressions of rule conditions that are traced during the scan.
+    /// Only populated when `condition_tracing` is true.
+    condition_traces: Vec<TracePoint>,
+
     /// Vector with the names of all the imported modules. The vector contains
     /// the [`IdentId`] corresponding to the module's identifier.
     imported_modules: Vec<IdentId>,
 
+    /// Modules imported with an alias, as in `import "pe" as p`. Each item
+    /// contains the [`IdentId`] of the module's name and the alias.
+    module_aliases: Vec<(IdentId, IdentId)>,
+
+    /// Modules that can't be imported, see [`Compiler::forbid_module`].
+    forbidden_modules: FxHashSet<String>,
+
+    /// If not `None`, contains the only modules that can be imported, see
+    /// [`Compiler::allow_only_modules`].
+    allowed_modules: Option<FxHashSet<String>>,
+
+    /// Modules that must be imported by every source file that contains
+    /// rules, see [`Compiler::require_module`].
+    required_modules: FxHashSet<String>,
+
     /// Names of modules that are known, but not supported. When an `import`
     /// statement with one of these modules is found, the statement is accepted
     /// without causing an error, but a warning is raised to let the user know
@@ -355,6 +416,19 @@ pub struct Compiler<'a> {
     /// the names of the unsupported modules they depend on.
     ignored_rules: FxHashMap<String, String>,
 
+    /// Modules implemented as WASM plugins that were registered with
+    /// [`Compiler::add_module_plugin`].
+    module_plugins: Vec<ModulePlugin>,
+
+    /// Custom passes registered with [`Compiler::add_pass`].
+    passes: Vec<Box<dyn CompilerPass>>,
+
+    /// Labels attached to namespaces with [`Compiler::add_namespace_label`].
+    /// Keys in this map are the [`IdentId`] of the namespace's name, values
+    /// are (key, value) pairs where both the key and the value are stored
+    /// in the identifiers pool.
+    namespace_labels: FxHashMap<IdentId, Vec<(IdentId, IdentId)>>,
+
     /// Structure where each field corresponds to a global identifier or a module
     /// imported by the rules. For fields corresponding to modules, the value is
     /// the structure that describes the module.
@@ -366,6 +440,11 @@ pub struct Compiler<'a> {
     /// Errors generated while compiling the rules.
     errors: Vec<CompileError>,
 
+    /// Arena where the IR for rule conditions is allocated. The IR of a rule
+    /// is not needed after the rule is compiled, so the arena is reset after
+    /// each rule, reusing the same memory for the next one.
+    ir_arena: Bump,
+
     /// Optional writer where the compiler writes the IR produced by each rule.
     /// This is used for test cases and debugging.
     #[cfg(test)]
@@ -429,8 +508,15 @@ impl<'a> Compiler<'a> {
             wasm_symbols,
             wasm_exports,
             relaxed_re_syntax: false,
+            yara_compat_mode: false,
             error_on_slow_pattern: false,
             error_on_slow_loop: false,
+            max_patterns_per_namespace: None,
+            max_atoms: None,
+            max_condition_nodes: None,
+            namespace_patterns: 0,
+            condition_tracing: false,
+            word_boundary: WordBoundary::default(),
             next_pattern_id: PatternId(0),
             current_pattern_id: PatternId(0),
             current_namespace: default_namespace,
@@ -441,14 +527,23 @@ impl<'a> Compiler<'a> {
             anchored_sub_patterns: Vec::new(),
             atoms: Vec::new(),
             re_code: Vec::new(),
+            condition_traces: Vec::new(),
             imported_modules: Vec::new(),
+            module_aliases: Vec::new(),
+            forbidden_modules: FxHashSet::default(),
+            allowed_modules: None,
+            required_modules: FxHashSet::default(),
             ignored_modules: FxHashSet::default(),
             ignored_rules: FxHashMap::default(),
+            module_plugins: Vec::new(),
+            passes: Vec::new(),
+            namespace_labels: FxHashMap::default(),
             root_struct: Struct::new().make_root(),
             report_builder: ReportBuilder::new(),
             lit_pool: BStringPool::new(),
             regexp_pool: StringPool::new(),
             patterns: FxHashMap::default(),
+            ir_arena: Bump::new(),
             #[cfg(test)]
             ir_writer: None,
         }
@@ -482,6 +577,11 @@ impl<'a> Compiler<'a> {
         // else, like a &str.
         let mut src = src.into();
 
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("add_source", origin = src.origin.as_deref())
+                .entered();
+
         // Register source code, even before validating that it is UTF-8. In
         // case of UTF-8 encoding errors we want to report that error too,
         // and we need the source code registered for creating the report.
@@ -492,6 +592,8 @@ impl<'a> Compiler<'a> {
         let ast = match src.as_str() {
             Ok(src) => {
                 // Parse the source code and build the Abstract Syntax Tree.
+                #[cfg(feature = "tracing")]
+                let _span = tracing::info_span!("parse").entered();
                 Parser::new(src.as_bytes()).into_ast()
             }
             Err(err) => {
@@ -514,6 +616,55 @@ impl<'a> Compiler<'a> {
             }
         };
 
+        self.c_ast(ast)
+    }
+
+    /// Adds rules from an Abstract Syntax Tree (AST) to the compiler.
+    ///
+    /// This is an alternative to [`Compiler::add_source`] for tools that
+    /// generate rules programmatically. The AST can be built with the types
+    /// in [`yara_x_parser::ast`], and there is no need to convert it to YARA
+    /// source code and parse it again.
+    ///
+    /// `src` is the source code that the spans in the AST refer to, errors
+    /// and warnings point to locations in it. This is the source code the
+    /// AST was parsed from, if any. For an AST that was built from scratch,
+    /// where all spans are empty, `src` can be empty too.
+    ///
+    /// ```
+    /// # use yara_x::Compiler;
+    /// use yara_x_parser::ast::{self, Expr, Ident, RuleFlags, AST};
+    ///
+    /// let rule = ast::Rule {
+    ///     flags: RuleFlags::none(),
+    ///     identifier: Ident::new("test"),
+    ///     tags: None,
+    ///     meta: None,
+    ///     patterns: None,
+    ///     condition: Expr::True { span: Default::default() },
+    /// };
+    ///
+    /// let mut compiler = Compiler::new();
+    /// compiler.add_ast(AST::new(vec![], vec![rule]), "")?;
+    ///
+    /// assert_eq!(compiler.build().iter().len(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn add_ast<'src, S>(
+        &mut self,
+        ast: ast::AST,
+        src: S,
+    ) -> Result<&mut Self, CompileError>
+    where
+        S: Into<SourceCode<'src>>,
+    {
+        // Errors and warnings are reported in the context of the source code
+        // that the AST refers to.
+        self.report_builder.register_source(&src.into());
+        self.c_ast(ast)
+    }
+
+    fn c_ast(&mut self, ast: ast::AST) -> Result<&mut Self, CompileError> {
         // Store the current length of the `errors` vector, so that we can
         // know if more errors were added.
         let existing_errors = self.errors.len();
@@ -526,7 +677,7 @@ impl<'a> Compiler<'a> {
         // symbol to the current namespace.
         for import in &ast.imports {
             if let Some(span) =
-                already_imported.insert(&import.module_name, import.span())
+                already_imported.insert(import.name(), import.span())
             {
                 self.warnings.add(|| {
                     warnings::DuplicateImport::build(
@@ -544,11 +695,28 @@ impl<'a> Compiler<'a> {
             }
         }
 
+        // Check that the source imports the modules required by the
+        // compiler's policy, if it doesn't, none of its rules is compiled.
+        let missing_module = self
+            .required_modules
+            .iter()
+            .sorted()
+            .find(|module| {
+                !ast.imports.iter().any(|import| import.module_name == *module)
+            })
+            .cloned();
+
         // Iterate over the list of declared rules and verify that their
         // conditions are semantically valid. For each rule add a symbol
         // to the current namespace.
         for rule in ast.rules() {
-            if let Err(err) = self.c_rule(rule) {
+            if let Some(module) = &missing_module {
+                self.errors.push(MissingRequiredModule::build(
+                    &self.report_builder,
+                    module.clone(),
+                    rule.identifier.span().into(),
+                ));
+            } else if let Err(err) = self.c_rule(rule) {
                 self.errors.push(err);
             }
         }
@@ -669,17 +837,79 @@ impl<'a> Compiler<'a> {
             symbols: self.symbol_table.push_new(),
         };
         self.ignored_rules.clear();
+        self.namespace_patterns = 0;
         self.wasm_mod.new_namespace();
         self
     }
 
+    /// Attaches a label to the current namespace.
+    ///
+    /// Labels are arbitrary key/value pairs (e.g. `feed=vendor_x`) that are
+    /// included in the compiled [`Rules`], and are preserved when the rules
+    /// are serialized. Every rule in the namespace exposes the labels via
+    /// [`crate::Rule::labels`], which allows embedders to route the results
+    /// of a scan without maintaining their own mapping between rules and
+    /// their origin. If the namespace already has a label with the same key,
+    /// its value is replaced.
+    ///
+    /// Labels belong to the namespace's name, they apply to all the rules in
+    /// the namespace, including those added before the label.
+    ///
+    /// ```
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler
+    ///     .new_namespace("vendor_x")
+    ///     .add_namespace_label("feed", "vendor_x")
+    ///     .add_namespace_label("revision", "2024.06")
+    ///     .add_source("rule foo {condition: true}")?;
+    ///
+    /// let rules = compiler.build();
+    /// let rule = rules.iter().next().unwrap();
+    ///
+    /// assert_eq!(
+    ///     rule.labels().collect::<Vec<_>>(),
+    ///     [("feed", "vendor_x"), ("revision", "2024.06")]
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn add_namespace_label(
+        &mut self,
+        key: &str,
+        value: &str,
+    ) -> &mut Self {
+        let key = self.ident_pool.get_or_intern(key);
+        let value = self.ident_pool.get_or_intern(value);
+
+        let labels = self
+            .namespace_labels
+            .entry(self.current_namespace.ident_id)
+            .or_default();
+
+        match labels.iter_mut().find(|(k, _)| *k == key) {
+            Some(label) => label.1 = value,
+            None => labels.push((key, value)),
+        }
+
+        self
+    }
+
     /// Builds the source code previously added to the compiler.
     ///
     /// This function consumes the compiler and returns an instance of
     /// [`Rules`].
     pub fn build(self) -> Rules {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("build").entered();
+
         // Finish building the WASM module.
-        let wasm_mod = self.wasm_mod.build().emit_wasm();
+        let wasm_mod = {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("emit_wasm").entered();
+            self.wasm_mod.build().emit_wasm()
+        };
 
         #[cfg(feature = "logging")]
         let start = Instant::now();
@@ -688,11 +918,15 @@ impl<'a> Compiler<'a> {
         // if the WASM code is invalid, which should not happen as the code is
         // emitted by YARA itself. If this ever happens is probably because
         // wrong WASM code is being emitted.
-        let compiled_wasm_mod = wasmtime::Module::from_binary(
-            &crate::wasm::ENGINE,
-            wasm_mod.as_slice(),
-        )
-        .expect("WASM module is not valid");
+        let compiled_wasm_mod = {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("compile_wasm").entered();
+            wasmtime::Module::from_binary(
+                &crate::wasm::ENGINE,
+                wasm_mod.as_slice(),
+            )
+            .expect("WASM module is not valid")
+        };
 
         #[cfg(feature = "logging")]
         info!("WASM module build time: {:?}", Instant::elapsed(&start));
@@ -713,25 +947,31 @@ impl<'a> Compiler<'a> {
             .expect("failed to serialize global variables");
 
         let mut rules = Rules {
+            yara_x_version: env!("CARGO_PKG_VERSION").to_string(),
             serialized_globals,
             wasm_mod,
             compiled_wasm_mod: Some(compiled_wasm_mod),
-            relaxed_re_syntax: self.relaxed_re_syntax,
-            ac: None,
+            relaxed_re_syntax: self.relaxed_re_syntax || self.yara_compat_mode,
+            word_boundary: self.word_boundary,
+            atom_searcher: None,
             num_patterns: self.next_pattern_id.0 as usize,
             ident_pool: self.ident_pool,
             regexp_pool: self.regexp_pool,
             lit_pool: self.lit_pool,
             imported_modules: self.imported_modules,
+            module_aliases: self.module_aliases,
+            module_plugins: self.module_plugins,
+            namespace_labels: self.namespace_labels,
             rules: self.rules,
             sub_patterns: self.sub_patterns,
             anchored_sub_patterns: self.anchored_sub_patterns,
             atoms: self.atoms,
             re_code: self.re_code,
+            condition_traces: self.condition_traces,
             warnings: self.warnings.into(),
         };
 
-        rules.build_ac_automaton();
+        rules.build_atom_searcher();
         rules
     }
 
@@ -746,6 +986,93 @@ impl<'a> Compiler<'a> {
         self
     }
 
+    /// Forbids the use of a YARA module.
+    ///
+    /// Import statements for forbidden modules produce a
+    /// [`CompileError::ForbiddenModule`] error. This allows restricting the
+    /// capabilities available to rules, for instance, forbidding modules
+    /// that are not meaningful in a given environment. Forbidden modules
+    /// take precedence over [`Compiler::ignore_module`].
+    pub fn forbid_module<M: Into<String>>(&mut self, module: M) -> &mut Self {
+        self.forbidden_modules.insert(module.into());
+        self
+    }
+
+    /// Restricts the YARA modules that can be used to the given ones.
+    ///
+    /// Import statements for any other module produce a
+    /// [`CompileError::ForbiddenModule`] error. Calling this function more
+    /// than once replaces the previous list of allowed modules.
+    ///
+    /// ```rust
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler.allow_only_modules(["pe", "math"]);
+    ///
+    /// assert!(compiler.add_source(r#"import "pe""#).is_ok());
+    /// assert!(compiler.add_source(r#"import "elf""#).is_err());
+    /// ```
+    pub fn allow_only_modules<I, M>(&mut self, modules: I) -> &mut Self
+    where
+        I: IntoIterator<Item = M>,
+        M: Into<String>,
+    {
+        self.allowed_modules =
+            Some(modules.into_iter().map(|m| m.into()).collect());
+        self
+    }
+
+    /// Requires the rules to import a YARA module.
+    ///
+    /// Every source file that contains rules must have an import statement
+    /// for the given module, otherwise each of its rules produces a
+    /// [`CompileError::MissingRequiredModule`] error. This allows enforcing
+    /// conventions like having all rules depend on some module that
+    /// restricts the files they apply to.
+    ///
+    /// ```rust
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler.require_module("pe");
+    ///
+    /// assert!(compiler
+    ///     .add_source(r#"import "pe" rule a { condition: pe.is_dll() }"#)
+    ///     .is_ok());
+    ///
+    /// assert!(compiler.add_source(r#"rule b { condition: true }"#).is_err());
+    /// ```
+    pub fn require_module<M: Into<String>>(&mut self, module: M) -> &mut Self {
+        self.required_modules.insert(module.into());
+        self
+    }
+
+    /// Registers a YARA module implemented as a WASM plugin.
+    ///
+    /// Once registered, the module can be imported by rules as any other
+    /// module. If a plugin with the same name was already registered, it is
+    /// replaced by the new one. Plugins must be registered before adding the
+    /// rules that import them.
+    pub fn add_module_plugin(&mut self, plugin: ModulePlugin) -> &mut Self {
+        self.module_plugins.retain(|p| p.name() != plugin.name());
+        self.module_plugins.push(plugin);
+        self
+    }
+
+    /// Registers a custom compiler pass.
+    ///
+    /// Passes are executed for every rule added after the pass is registered,
+    /// in the order in which they were registered. See
+    /// [`crate::passes::CompilerPass`] for details.
+    pub fn add_pass<P: CompilerPass + 'static>(
+        &mut self,
+        pass: P,
+    ) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
     /// Specifies whether the compiler should produce colorful error messages.
     ///
     /// Colorized error messages contain ANSI escape sequences that make them
@@ -813,6 +1140,40 @@ impl<'a> Compiler<'a> {
         self
     }
 
+    /// Enables the YARA 4.x compatibility mode.
+    ///
+    /// In this mode the compiler accepts some legacy constructs that are
+    /// valid in YARA 4.x but not in YARA-X, and raises a warning for each
+    /// of them instead of an error. This is intended for migrating large
+    /// collections of existing rules. The accepted constructs are:
+    ///
+    /// * Regular expressions that are valid only with the syntax described
+    ///   in [`Compiler::relaxed_re_syntax`], like `/foo{}bar/` or `/\Rbar/`.
+    ///   They raise a `relaxed_re_syntax` warning.
+    /// * Patterns with duplicate modifiers, like `"foo" wide wide`. They
+    ///   raise a `duplicate_modifier` warning.
+    /// * The deprecated `entrypoint` keyword, which is replaced with
+    ///   `pe.entry_point` or `elf.entry_point`, depending on the module
+    ///   imported by the rule's namespace. It raises a
+    ///   `deprecated_entrypoint` warning, and is still an error if neither
+    ///   module is imported.
+    ///
+    /// Other differences with YARA 4.x, like duplicate rule modifiers, are
+    /// still an error.
+    ///
+    /// This should be called before any rule is added to the compiler.
+    ///
+    /// # Panics
+    ///
+    /// If called after adding rules to the compiler.
+    pub fn yara_compat_mode(&mut self, yes: bool) -> &mut Self {
+        if !self.rules.is_empty() {
+            panic!("calling yara_compat_mode in non-empty compiler")
+        }
+        self.yara_compat_mode = yes;
+        self
+    }
+
     /// When enabled, slow patterns produce an error instead of a warning.
     ///
     /// This is disabled by default.
@@ -830,6 +1191,94 @@ impl<'a> Compiler<'a> {
         self
     }
 
+    /// Sets the maximum number of patterns that rules in a namespace can
+    /// declare.
+    ///
+    /// Once the limit is reached, rules that declare more patterns in the
+    /// same namespace are rejected with [`CompileError::QuotaExceeded`].
+    /// Each namespace has its own count, so this is useful for limiting the
+    /// resources used by rules that come from different sources, when each
+    /// source is compiled in its own namespace.
+    ///
+    /// There's no limit by default.
+    ///
+    /// ```
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler
+    ///     .max_patterns_per_namespace(2)
+    ///     .add_source(r#"rule foo { strings: $a = "foo" condition: $a }"#)?;
+    ///
+    /// assert!(compiler
+    ///     .add_source(r#"rule bar {
+    ///          strings: $a = "bar" $b = "baz"
+    ///          condition: all of them
+    ///     }"#)
+    ///     .is_err());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn max_patterns_per_namespace(&mut self, n: usize) -> &mut Self {
+        self.max_patterns_per_namespace = Some(n);
+        self
+    }
+
+    /// Sets the maximum number of atoms in the compiled rules.
+    ///
+    /// Atoms are the short fragments extracted from each pattern that are
+    /// searched for during the scan, the larger the number of atoms, the
+    /// larger the Aho-Corasick automaton and the slower the scan. Rules that
+    /// would exceed the limit are rejected with
+    /// [`CompileError::QuotaExceeded`].
+    ///
+    /// There's no limit by default.
+    pub fn max_atoms(&mut self, n: usize) -> &mut Self {
+        self.max_atoms = Some(n);
+        self
+    }
+
+    /// Sets the maximum number of nodes in the condition of a rule.
+    ///
+    /// The number of nodes in the condition's intermediate representation
+    /// is a measure of the condition's complexity. Rules with conditions
+    /// that exceed the limit are rejected with
+    /// [`CompileError::QuotaExceeded`].
+    ///
+    /// There's no limit by default.
+    pub fn max_condition_nodes(&mut self, n: usize) -> &mut Self {
+        self.max_condition_nodes = Some(n);
+        self
+    }
+
+    /// When enabled, the value of every boolean sub-expression in rule
+    /// conditions is recorded while scanning.
+    ///
+    /// This includes the condition as a whole and each operand of `and`,
+    /// `or` and `not` expressions. After the scan, the recorded values can
+    /// be obtained with [`crate::Rule::condition_trace`], which is useful
+    /// for understanding why a rule matched or didn't match. Tracing makes
+    /// scanning slower, it shouldn't be enabled in production.
+    ///
+    /// This is disabled by default.
+    pub fn condition_tracing(&mut self, yes: bool) -> &mut Self {
+        self.condition_tracing = yes;
+        self
+    }
+
+    /// Specifies which characters are considered word characters by word
+    /// boundary assertions (`\b`, `\B`, `\b{start}` and `\b{end}`) in
+    /// regexp patterns.
+    ///
+    /// By default ASCII letters, digits and the underscore are word
+    /// characters (see [`WordBoundary`] for other options). The setting
+    /// applies to all the rules built by this compiler, but not to the
+    /// regular expressions used with the `matches` operator.
+    pub fn word_boundary(&mut self, word_boundary: WordBoundary) -> &mut Self {
+        self.word_boundary = word_boundary;
+        self
+    }
+
     /// Retrieves all errors generated by the compiler.
     ///
     /// This method returns every error encountered during the compilation,
@@ -889,6 +1338,41 @@ impl<'a> Compiler<'a> {
         sub_pattern_id
     }
 
+    /// Runs the custom passes registered with [`Compiler::add_pass`] on the
+    /// condition of the given rule.
+    fn run_post_ir_passes<'ir>(
+        &self,
+        rule: &ast::Rule,
+        condition: &mut Expr<'ir>,
+        arena: &'ir Bump,
+    ) -> Result<(), CompileError> {
+        let mut condition =
+            Condition::new(rule.identifier.name, condition, arena);
+
+        for pass in self.passes.iter() {
+            let result = pass.post_ir(&mut condition).and_then(|_| {
+                if condition.added_nodes > MAX_ADDED_NODES {
+                    Err(format!(
+                        "compiler passes added more than {} nodes to the condition",
+                        MAX_ADDED_NODES
+                    ))
+                } else {
+                    Ok(())
+                }
+            });
+
+            if let Err(err) = result {
+                return Err(RuleRejected::build(
+                    &self.report_builder,
+                    err,
+                    rule.identifier.span().into(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Checks if another rule, module or variable has the given identifier and
     /// return an error in that case.
     fn check_for_existing_identifier(
@@ -938,6 +1422,96 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    /// Checks that the patterns declared by the rule don't exceed the limit
+    /// set with [`Compiler::max_patterns_per_namespace`].
+    fn check_patterns_quota(
+        &self,
+        rule: &ast::Rule,
+    ) -> Result<(), CompileError> {
+        let max_patterns = match self.max_patterns_per_namespace {
+            Some(max_patterns) => max_patterns,
+            None => return Ok(()),
+        };
+
+        let available = max_patterns.saturating_sub(self.namespace_patterns);
+
+        // The error points to the first pattern that exceeds the limit.
+        if let Some(pattern) = rule.patterns.iter().flatten().nth(available) {
+            let namespace =
+                self.ident_pool.get(self.current_namespace.ident_id).unwrap();
+
+            return Err(QuotaExceeded::build(
+                &self.report_builder,
+                format!(
+                    "this pattern exceeds the limit of {} patterns",
+                    max_patterns
+                ),
+                pattern.span().into(),
+                Some(format!(
+                    "namespace `{}` already has {} patterns",
+                    namespace, self.namespace_patterns
+                )),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the number of atoms doesn't exceed the limit set with
+    /// [`Compiler::max_atoms`]. This is called after compiling each pattern,
+    /// and `span` is the span of the pattern.
+    fn check_atoms_quota(&self, span: Span) -> Result<(), CompileError> {
+        match self.max_atoms {
+            Some(max_atoms) if self.atoms.len() > max_atoms => {
+                Err(QuotaExceeded::build(
+                    &self.report_builder,
+                    format!(
+                        "this pattern exceeds the limit of {} atoms",
+                        max_atoms
+                    ),
+                    span.into(),
+                    Some(format!(
+                        "the rules compiled so far would have {} atoms",
+                        self.atoms.len()
+                    )),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the number of nodes in the rule's condition doesn't exceed
+    /// the limit set with [`Compiler::max_condition_nodes`].
+    fn check_condition_quota(
+        &self,
+        rule: &ast::Rule,
+        condition: &Expr,
+    ) -> Result<(), CompileError> {
+        let max_nodes = match self.max_condition_nodes {
+            Some(max_nodes) => max_nodes,
+            None => return Ok(()),
+        };
+
+        let num_nodes = condition
+            .dfs_iter()
+            .filter(|evt| matches!(evt, Event::Enter(_)))
+            .count();
+
+        if num_nodes > max_nodes {
+            return Err(QuotaExceeded::build(
+                &self.report_builder,
+                format!(
+                    "this condition exceeds the limit of {} nodes",
+                    max_nodes
+                ),
+                rule.condition.span().into(),
+                Some(format!("the condition has {} nodes", num_nodes)),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Interns a literal in the literals pool.
     ///
     /// If `wide` is true the literal gets zeroes interleaved between each byte
@@ -969,6 +1543,7 @@ impl<'a> Compiler<'a> {
             atoms_len: self.atoms.len(),
             re_code_len: self.re_code.len(),
             sub_patterns_len: self.sub_patterns.len(),
+            condition_traces_len: self.condition_traces.len(),
             symbol_table_len: self.symbol_table.len(),
         }
     }
@@ -983,7 +1558,37 @@ impl<'a> Compiler<'a> {
         self.sub_patterns.truncate(snapshot.sub_patterns_len);
         self.re_code.truncate(snapshot.re_code_len);
         self.atoms.truncate(snapshot.atoms_len);
+        self.condition_traces.truncate(snapshot.condition_traces_len);
         self.symbol_table.truncate(snapshot.symbol_table_len);
+        // Forget the patterns that were added after the snapshot, they don't
+        // have sub-patterns and can't be shared with other rules.
+        self.patterns.retain(|_, id| id.0 < snapshot.next_pattern_id.0);
+    }
+
+    /// Returns the source code of a pattern's value, without the identifier
+    /// and the modifiers.
+    ///
+    /// Whitespaces in hex patterns are normalized, so that patterns that
+    /// span multiple lines are returned in a single line.
+    fn pattern_source(
+        report_builder: &ReportBuilder,
+        pattern: &ast::Pattern,
+    ) -> String {
+        match pattern {
+            ast::Pattern::Text(p) => {
+                report_builder.get_snippet(&p.text.span().into())
+            }
+            ast::Pattern::Regexp(p) => {
+                report_builder.get_snippet(&p.regexp.span().into())
+            }
+            ast::Pattern::Hex(p) => format!(
+                "{{ {} }}",
+                report_builder
+                    .get_snippet(&p.tokens.span().into())
+                    .split_whitespace()
+                    .join(" ")
+            ),
+        }
     }
 
     /// Sets a writer where the compiler will write the Intermediate
@@ -1029,6 +1634,27 @@ impl<'a> Compiler<'a> {
 
 impl<'a> Compiler<'a> {
     fn c_rule(&mut self, rule: &ast::Rule) -> Result<(), CompileError> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("compile_rule", rule = rule.identifier.name)
+                .entered();
+
+        // The arena is moved out of `self` while the rule is being compiled,
+        // as the IR borrows from it while `self` is mutated.
+        let mut arena = mem::take(&mut self.ir_arena);
+        let result = self.c_rule_in_arena(rule, &arena);
+
+        arena.reset();
+        self.ir_arena = arena;
+
+        result
+    }
+
+    fn c_rule_in_arena(
+        &mut self,
+        rule: &ast::Rule,
+        arena: &Bump,
+    ) -> Result<(), CompileError> {
         // Check if another rule, module or variable has the same identifier
         // and return an error in that case.
         self.check_for_existing_identifier(&rule.identifier)?;
@@ -1038,6 +1664,21 @@ impl<'a> Compiler<'a> {
             self.check_for_duplicate_tags(tags.as_slice())?;
         }
 
+        // Run the custom passes that operate on the rule's AST.
+        for pass in self.passes.iter() {
+            if let Err(err) = pass.pre_ir(rule) {
+                return Err(RuleRejected::build(
+                    &self.report_builder,
+                    err,
+                    rule.identifier.span().into(),
+                ));
+            }
+        }
+
+        // Check that the rule doesn't exceed the number of patterns allowed
+        // in the current namespace.
+        self.check_patterns_quota(rule)?;
+
         let tags: Vec<IdentId> = rule
             .tags
             .iter()
@@ -1054,6 +1695,11 @@ impl<'a> Compiler<'a> {
         // added to one of these pools it can't be removed.
         let snapshot = self.take_snapshot();
 
+        // Number of warnings before compiling the rule. Warnings added after
+        // this point are raised while compiling the rule, and are attached to
+        // it once the rule is successfully compiled.
+        let num_warnings = self.warnings.as_slice().len();
+
         // The RuleId for the new rule is current length of `self.rules`. The
         // first rule has RuleId = 0.
         let rule_id = RuleId(self.rules.len() as i32);
@@ -1103,20 +1749,39 @@ impl<'a> Compiler<'a> {
             is_global: rule.flags.contains(RuleFlag::Global),
             is_private: rule.flags.contains(RuleFlag::Private),
             metadata: meta,
+            warnings: vec![],
+            module_fields: vec![],
+            condition_traces: 0..0,
+            condition_source: self
+                .report_builder
+                .get_snippet(&rule.condition.span().into()),
         });
 
         let mut rule_patterns = Vec::new();
+        let mut rule_fields = FxHashSet::default();
 
         let mut ctx = CompileContext {
             relaxed_re_syntax: self.relaxed_re_syntax,
+            yara_compat_mode: self.yara_compat_mode,
             error_on_slow_loop: self.error_on_slow_loop,
+            condition_traces: if self.condition_tracing {
+                Some(&mut self.condition_traces)
+            } else {
+                None
+            },
             current_symbol_table: None,
             symbol_table: &mut self.symbol_table,
             report_builder: &self.report_builder,
             current_rule_patterns: &mut rule_patterns,
             warnings: &mut self.warnings,
+            current_rule_fields: &mut rule_fields,
+            field_path_vars: FxHashMap::default(),
             vars: VarStack::new(),
             for_of_depth: 0,
+            rules: &self.rules,
+            ident_pool: &self.ident_pool,
+            lit_pool: &mut self.lit_pool,
+            arena,
         };
 
         // Convert the patterns from AST to IR. This populates the
@@ -1130,7 +1795,10 @@ impl<'a> Compiler<'a> {
         // Convert the rule condition's AST to the intermediate representation
         // (IR). Also updates the patterns with information about whether they
         // are used in the condition and if they are anchored or not.
-        let condition = bool_expr_from_ast(&mut ctx, &rule.condition);
+        let condition =
+            bool_expr_from_ast(&mut ctx, &rule.condition).map(|condition| {
+                trace_expr(&mut ctx, condition, rule.condition.span())
+            });
 
         drop(ctx);
 
@@ -1231,6 +1899,18 @@ impl<'a> Compiler<'a> {
             }
         };
 
+        // Run the custom passes that operate on the rule's condition.
+        if let Err(err) = self.run_post_ir_passes(rule, &mut condition, arena)
+        {
+            self.restore_snapshot(snapshot);
+            return Err(err);
+        }
+
+        if let Err(err) = self.check_condition_quota(rule, &condition) {
+            self.restore_snapshot(snapshot);
+            return Err(err);
+        }
+
         #[cfg(test)]
         if let Some(w) = &mut self.ir_writer {
             writeln!(w, "RULE {}", rule.identifier.name).unwrap();
@@ -1279,7 +1959,9 @@ impl<'a> Compiler<'a> {
 
         let current_rule = self.rules.last_mut().unwrap();
 
-        for pattern in &rule_patterns {
+        for (pattern, ast_pattern) in
+            rule_patterns.iter().zip(rule.patterns.iter().flatten())
+        {
             // Raise error is some pattern was not used, except if the pattern
             // identifier starts with underscore.
             if !pattern.in_use() && !pattern.identifier().starts_with("$_") {
@@ -1311,10 +1993,22 @@ impl<'a> Compiler<'a> {
                     }
                 };
 
-            current_rule.patterns.push((
-                self.ident_pool.get_or_intern(pattern.identifier().name),
+            current_rule.patterns.push(PatternInfo {
+                ident_id: self
+                    .ident_pool
+                    .get_or_intern(pattern.identifier().name),
                 pattern_id,
-            ));
+                kind: ast_pattern.into(),
+                modifiers: ast_pattern
+                    .modifiers()
+                    .iter()
+                    .map(PatternModifier::from)
+                    .collect(),
+                source: Self::pattern_source(
+                    &self.report_builder,
+                    ast_pattern,
+                ),
+            });
 
             pattern_ids.push(pattern_id);
         }
@@ -1336,18 +2030,55 @@ impl<'a> Compiler<'a> {
                         self.c_literal_pattern(pattern, anchored_at);
                     }
                     Pattern::Regexp(pattern) => {
-                        if let Err(err) =
-                            self.c_regexp_pattern(pattern, anchored_at, span)
-                        {
+                        if let Err(err) = self.c_regexp_pattern(
+                            pattern,
+                            anchored_at,
+                            span.clone(),
+                        ) {
                             self.restore_snapshot(snapshot);
                             return Err(err);
                         }
                     }
                 };
                 pending_patterns.remove(pattern_id);
+                if let Err(err) = self.check_atoms_quota(span) {
+                    self.restore_snapshot(snapshot);
+                    return Err(err);
+                }
             }
         }
 
+        self.namespace_patterns +=
+            rule.patterns.as_ref().map_or(0, |patterns| patterns.len());
+
+        // Attach to the rule the warnings raised while compiling it.
+        self.rules.last_mut().unwrap().warnings = self.warnings.as_slice()
+            [num_warnings..]
+            .iter()
+            .map(RuleWarning::from)
+            .collect();
+
+        // Attach to the rule the module fields accessed by its condition.
+        // Fields that are only accessed for reaching some other field are
+        // not included. For instance, if the rule uses `pe.sections[0].name`,
+        // `pe.sections[].name` is included but `pe.sections` is not.
+        let module_fields = rule_fields
+            .iter()
+            .filter(|field| {
+                !rule_fields.iter().any(|other| {
+                    other.len() > field.len()
+                        && other.starts_with(field.as_str())
+                        && matches!(other.as_bytes()[field.len()], b'.' | b'[')
+                })
+            })
+            .sorted()
+            .map(|field| self.ident_pool.get_or_intern(field))
+            .collect();
+
+        self.rules.last_mut().unwrap().module_fields = module_fields;
+        self.rules.last_mut().unwrap().condition_traces =
+            snapshot.condition_traces_len..self.condition_traces.len();
+
         // The last step is emitting the WASM code corresponding to the rule's
         // condition. This is done after every fallible function has been called
         // because once the code is emitted it cannot be undone, which means
@@ -1355,6 +2086,7 @@ impl<'a> Compiler<'a> {
         // will remain in the WASM module.
         let mut ctx = EmitContext {
             current_rule: self.rules.last_mut().unwrap(),
+            current_rule_id: rule_id,
             current_signature: None,
             lit_pool: &mut self.lit_pool,
             regexp_pool: &mut self.regexp_pool,
@@ -1376,10 +2108,34 @@ impl<'a> Compiler<'a> {
 
     fn c_import(&mut self, import: &Import) -> Result<(), CompileError> {
         let module_name = import.module_name;
+
+        // Is the module allowed by the compiler's policy?
+        let not_allowed = self
+            .allowed_modules
+            .as_ref()
+            .is_some_and(|allowed| !allowed.contains(module_name));
+
+        if not_allowed || self.forbidden_modules.contains(module_name) {
+            let note = self.allowed_modules.as_ref().map(|allowed| {
+                format!(
+                    "allowed modules: {}",
+                    allowed.iter().sorted().join(", ")
+                )
+            });
+            return Err(ForbiddenModule::build(
+                &self.report_builder,
+                module_name.to_string(),
+                import.span().into(),
+                note,
+            ));
+        }
+
         let module = BUILTIN_MODULES.get(module_name);
+        let plugin =
+            self.module_plugins.iter().find(|p| p.name() == module_name);
 
         // Does a module with the given name actually exist? ...
-        if module.is_none() {
+        if module.is_none() && plugin.is_none() {
             // The module does not exist, but it is included in the list
             // of unsupported modules. In such cases we don't raise an error,
             // only a warning.
@@ -1404,8 +2160,14 @@ impl<'a> Compiler<'a> {
             };
         }
 
-        // Yes, module exists.
-        let module = module.unwrap();
+        // Yes, module exists. It can be either a built-in module or a plugin,
+        // plugins don't have an associated Rust module.
+        let (root_struct_descriptor, rust_module_name) = match module {
+            Some(module) => {
+                (&module.root_struct_descriptor, module.rust_module_name)
+            }
+            None => (plugin.unwrap().root_struct_descriptor(), None),
+        };
 
         // If the module has not been added to `self.root_struct` and
         // `self.imported_modules`, do it.
@@ -1416,17 +2178,17 @@ impl<'a> Compiler<'a> {
 
             // Create the structure that describes the module.
             let mut module_struct = Struct::from_proto_descriptor_and_msg(
-                &module.root_struct_descriptor,
+                root_struct_descriptor,
                 None,
                 true,
             );
 
             // Does the YARA module has an associated Rust module? If
             // yes, search for functions exported by the module.
-            if let Some(rust_module_name) = module.rust_module_name {
+            if let Some(rust_module_name) = rust_module_name {
                 // Find all WASM public functions that belong to the current module.
                 let mut functions = WasmExport::get_functions(|e| {
-                    e.public && e.rust_module_path.contains(rust_module_name)
+                    e.public && e.rust_module_name() == Some(rust_module_name)
                 });
 
                 // Insert the functions in the module's struct.
@@ -1458,13 +2220,43 @@ impl<'a> Compiler<'a> {
         let mut symbol_table =
             self.current_namespace.symbols.as_ref().borrow_mut();
 
+        let module_symbol = self.root_struct.lookup(module_name).unwrap();
+
+        // The module is referred to by its alias, if any. An alias can't
+        // be the name of another module or rule in the same namespace, and
+        // a module can't be imported with a name that is already an alias
+        // for some other module.
+        if let Some(existing) = symbol_table.lookup(import.name()) {
+            let conflicts = match (existing.kind(), module_symbol.kind()) {
+                (
+                    SymbolKind::Field(existing, true),
+                    SymbolKind::Field(module, true),
+                ) => existing != module,
+                _ => import.alias.is_some(),
+            };
+            if conflicts {
+                return Err(ConflictingImport::build(
+                    &self.report_builder,
+                    import.name().to_string(),
+                    import.span().into(),
+                ));
+            }
+        }
+
         // Create a symbol for the module and insert it in the symbol
         // table for this namespace, if it doesn't exist.
-        if !symbol_table.contains(module_name) {
-            symbol_table.insert(
-                module_name,
-                self.root_struct.lookup(module_name).unwrap(),
+        if !symbol_table.contains(import.name()) {
+            symbol_table.insert(import.name(), module_symbol);
+        }
+
+        if let Some(alias) = &import.alias {
+            let alias = (
+                self.ident_pool.get_or_intern(module_name),
+                self.ident_pool.get_or_intern(alias.name),
             );
+            if !self.module_aliases.contains(&alias) {
+                self.module_aliases.push(alias);
+            }
         }
 
         Ok(())
@@ -1473,7 +2265,7 @@ impl<'a> Compiler<'a> {
     fn c_literal_pattern(
         &mut self,
         pattern: LiteralPattern,
-        anchored_at: Option<usize>,
+        anchored_at: Option<Anchor>,
     ) {
         let full_word = pattern.flags.contains(PatternFlags::Fullword);
         let mut flags = SubPatternFlagSet::none();
@@ -1650,7 +2442,7 @@ impl<'a> Compiler<'a> {
     fn c_regexp_pattern(
         &mut self,
         pattern: RegexpPattern,
-        anchored_at: Option<usize>,
+        anchored_at: Option<Anchor>,
         span: Span,
     ) -> Result<(), CompileError> {
         // Try splitting the regexp into multiple chained sub-patterns if it
@@ -1726,7 +2518,7 @@ impl<'a> Compiler<'a> {
     fn c_alternation_literal(
         &mut self,
         hir: re::hir::Hir,
-        anchored_at: Option<usize>,
+        anchored_at: Option<Anchor>,
         flags: PatternFlagSet,
     ) -> Result<(), CompileError> {
         let ascii = flags.contains(PatternFlags::Ascii);
@@ -1909,7 +2701,7 @@ impl<'a> Compiler<'a> {
                     prev_sub_pattern_wide = self.c_literal_chain_tail(
                         literal,
                         prev_sub_pattern_wide,
-                        p.gap.clone(),
+                        p.wide_gap(),
                         flags | SubPatternFlags::Wide,
                     );
                 };
@@ -1937,7 +2729,7 @@ impl<'a> Compiler<'a> {
                     prev_sub_pattern_wide = self.add_sub_pattern(
                         SubPattern::RegexpChainTail {
                             chained_to: prev_sub_pattern_wide,
-                            gap: p.gap.clone(),
+                            gap: p.wide_gap(),
                             flags: flags | SubPatternFlags::Wide,
                         },
                         atoms.iter().cloned().map(|atom| atom.make_wide()),
@@ -2150,7 +2942,9 @@ impl From<LiteralId> for u64 {
 pub(crate) struct NamespaceId(i32);
 
 /// ID associated to each rule.
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(
+    Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd,
+)]
 pub(crate) struct RuleId(i32);
 
 impl From<i32> for RuleId {
@@ -2327,6 +3121,32 @@ bitmask! {
     }
 }
 
+/// Indicates where an anchored sub-pattern must match.
+///
+/// Anchored sub-patterns are not searched with Aho-Corasick, they are
+/// verified only at the offset indicated by the anchor.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Anchor {
+    /// The sub-pattern matches at the given offset from the start of the
+    /// data, like in `$a at 100`.
+    Start(usize),
+    /// The sub-pattern matches at the given offset from the end of the data,
+    /// like in `$a at filesize - 100`.
+    End(usize),
+}
+
+impl Anchor {
+    /// Returns the offset where the sub-pattern must match in data of the
+    /// given length. Returns `None` if the data is too short for containing
+    /// such offset.
+    pub fn offset(&self, data_len: usize) -> Option<usize> {
+        match self {
+            Anchor::Start(offset) => (*offset <= data_len).then_some(*offset),
+            Anchor::End(offset) => data_len.checked_sub(*offset),
+        }
+    }
+}
+
 /// A sub-pattern in the compiled rules.
 ///
 /// Each pattern in a rule has one or more associated sub-patterns. For
@@ -2340,7 +3160,7 @@ bitmask! {
 pub(crate) enum SubPattern {
     Literal {
         pattern: LiteralId,
-        anchored_at: Option<usize>,
+        anchored_at: Option<Anchor>,
         flags: SubPatternFlagSet,
     },
 
@@ -2420,6 +3240,7 @@ struct Snapshot {
     atoms_len: usize,
     re_code_len: usize,
     sub_patterns_len: usize,
+    condition_traces_len: usize,
     symbol_table_len: usize,
 }
 
@@ -0,0 +1,150 @@
+/*! Custom passes executed by the compiler for each rule.
+
+Compiler passes allow embedders to observe, and to some extent transform,
+each rule while it is compiled, without modifying the compiler itself. This
+can be used for enforcing organization-specific policies, like rejecting
+rules that don't satisfy some requirement, or adding a `filesize` guard to
+every rule.
+
+Passes are implemented by types that implement the [`CompilerPass`] trait,
+and they are registered with [`crate::Compiler::add_pass`].
+*/
+
+use std::mem;
+
+use bumpalo::Bump;
+
+use yara_x_parser::ast;
+
+use crate::compiler::ir::{Event, Expr};
+use crate::types::{TypeValue, Value};
+
+/// Maximum number of nodes that passes can add to the condition of a single
+/// rule. This prevents passes from producing huge conditions that would take
+/// too long to compile and evaluate.
+pub(in crate::compiler) const MAX_ADDED_NODES: usize = 1_000;
+
+/// Trait implemented by custom compiler passes.
+///
+/// Passes are executed for each rule in the order in which they were
+/// registered. Both methods have default implementations that do nothing,
+/// so implementors only need to provide the ones they are interested in.
+///
+/// Any of the methods can reject the rule by returning an error with a
+/// message that explains the reason. In that case the rule is not compiled,
+/// and [`crate::Compiler::add_source`] fails with
+/// [`crate::errors::CompileError::RuleRejected`].
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::Compiler;
+/// use yara_x::passes::{CompilerPass, Condition};
+///
+/// struct FilesizeGuard;
+///
+/// impl CompilerPass for FilesizeGuard {
+///     fn post_ir(&self, condition: &mut Condition) -> Result<(), String> {
+///         if !condition.uses_filesize() {
+///             condition.require_max_filesize(10 * 1024 * 1024);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut compiler = Compiler::new();
+///
+/// compiler
+///     .add_pass(FilesizeGuard)
+///     .add_source(r#"rule test { condition: true }"#)
+///     .unwrap();
+/// ```
+pub trait CompilerPass {
+    /// Invoked with the rule's abstract syntax tree (AST), before the rule
+    /// is translated into the intermediate representation (IR).
+    fn pre_ir(&self, _rule: &ast::Rule) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Invoked with the rule's condition, after it has been translated into
+    /// the intermediate representation (IR) and before the code for the
+    /// condition is generated.
+    fn post_ir(&self, _condition: &mut Condition) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// The condition of a rule in its intermediate representation (IR).
+///
+/// The IR is not exposed directly, this type provides a restricted set of
+/// operations that can be performed with it. These operations guarantee that
+/// the condition remains valid after being transformed.
+pub struct Condition<'a, 'ir> {
+    rule_identifier: &'a str,
+    pub(in crate::compiler) expr: &'a mut Expr<'ir>,
+    pub(in crate::compiler) added_nodes: usize,
+    arena: &'ir Bump,
+}
+
+impl<'a, 'ir> Condition<'a, 'ir> {
+    pub(in crate::compiler) fn new(
+        rule_identifier: &'a str,
+        expr: &'a mut Expr<'ir>,
+        arena: &'ir Bump,
+    ) -> Self {
+        Self { rule_identifier, expr, added_nodes: 0, arena }
+    }
+
+    /// Returns the identifier of the rule this condition belongs to.
+    pub fn rule_identifier(&self) -> &str {
+        self.rule_identifier
+    }
+
+    /// Returns the number of nodes in the condition's IR tree. This gives an
+    /// idea of the condition's complexity.
+    pub fn num_nodes(&self) -> usize {
+        self.expr
+            .dfs_iter()
+            .filter(|evt| matches!(evt, Event::Enter(_)))
+            .count()
+    }
+
+    /// Returns true if the condition uses `filesize`.
+    pub fn uses_filesize(&self) -> bool {
+        self.expr
+            .dfs_find(|expr| matches!(expr, Expr::Filesize), |_| false)
+            .is_some()
+    }
+
+    /// Returns true if the condition is a constant known at compile time.
+    pub fn is_constant(&self) -> bool {
+        matches!(self.expr, Expr::Const(_) | Expr::Literal { .. })
+    }
+
+    /// Transforms the condition `<cond>` into `filesize <= <size> and <cond>`.
+    pub fn require_max_filesize(&mut self, size: i64) -> &mut Self {
+        self.guard(Expr::le(self.arena, Expr::Filesize, Self::integer(size)))
+    }
+
+    /// Transforms the condition `<cond>` into `filesize >= <size> and <cond>`.
+    pub fn require_min_filesize(&mut self, size: i64) -> &mut Self {
+        self.guard(Expr::ge(self.arena, Expr::Filesize, Self::integer(size)))
+    }
+
+    fn integer(value: i64) -> Expr<'ir> {
+        Expr::Const(TypeValue::Integer(Value::Const(value)))
+    }
+
+    fn guard(&mut self, guard: Expr<'ir>) -> &mut Self {
+        // The guard has 3 nodes, plus the `and` node. Once the limit is
+        // exceeded the condition is not modified anymore, and the rule will
+        // be rejected when the pass finishes.
+        self.added_nodes += 4;
+        if self.added_nodes > MAX_ADDED_NODES {
+            return self;
+        }
+        let condition = mem::replace(self.expr, Expr::Filesize);
+        *self.expr = Expr::and(vec![guard, condition]);
+        self
+    }
+}
@@ -1,25 +1,29 @@
+use std::collections::BTreeMap;
 use std::fmt;
 use std::io::{BufWriter, Read, Write};
+use std::ops::Range;
 use std::slice::Iter;
 #[cfg(feature = "logging")]
 use std::time::Instant;
 
-use aho_corasick::AhoCorasick;
 use bincode::Options;
 #[cfg(feature = "logging")]
 use log::*;
 use regex_automata::meta::Regex;
+use rustc_hash::FxHashMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::compiler::atoms::Atom;
+use crate::compiler::atoms::{Atom, AtomSearcher};
 use crate::compiler::errors::SerializationError;
 use crate::compiler::report::CodeLoc;
-use crate::compiler::warnings::Warning;
+use crate::compiler::warnings::{RuleWarning, Warning};
 use crate::compiler::{
     IdentId, Imports, LiteralId, NamespaceId, PatternId, RegexpId, RuleId,
     SubPattern, SubPatternId,
 };
-use crate::re::{BckCodeLoc, FwdCodeLoc, RegexpAtom};
+use crate::models::{PatternKind, PatternModifier};
+use crate::modules::ModulePlugin;
+use crate::re::{BckCodeLoc, FwdCodeLoc, RegexpAtom, WordBoundary};
 use crate::string_pool::{BStringPool, StringPool};
 use crate::{re, types, Rule};
 
@@ -28,6 +32,11 @@ use crate::{re, types, Rule};
 /// This is the result from [`crate::Compiler::build`].
 #[derive(Serialize, Deserialize)]
 pub struct Rules {
+    /// Version of YARA-X that compiled the rules. This is the first field
+    /// so that it can be read from serialized rules even if the rest of the
+    /// format changed in a later version.
+    pub(in crate::compiler) yara_x_version: String,
+
     /// Pool with identifiers used in the rules. Each identifier has its
     /// own [`IdentId`], which can be used for retrieving the identifier
     /// from the pool as a `&str`.
@@ -43,6 +52,9 @@ pub struct Rules {
     /// contain invalid escape sequences.
     pub(in crate::compiler) relaxed_re_syntax: bool,
 
+    /// Semantics of word boundary assertions in regexp patterns.
+    pub(in crate::compiler) word_boundary: WordBoundary,
+
     /// Pool with literal strings used in the rules. Each literal has its
     /// own [`LiteralId`], which can be used for retrieving the literal
     /// string as `&BStr`.
@@ -64,6 +76,20 @@ pub struct Rules {
     /// the [`IdentId`] corresponding to the module's identifier.
     pub(in crate::compiler) imported_modules: Vec<IdentId>,
 
+    /// Modules imported with an alias, as in `import "pe" as p`. Each item
+    /// contains the [`IdentId`] of the module's name and the alias.
+    pub(in crate::compiler) module_aliases: Vec<(IdentId, IdentId)>,
+
+    /// Modules implemented as WASM plugins. These plugins are serialized
+    /// together with the rules, so that the scanner can run them.
+    pub(in crate::compiler) module_plugins: Vec<ModulePlugin>,
+
+    /// Labels attached to namespaces. Keys are the [`IdentId`] of the
+    /// namespace's name, values are (key, value) pairs stored in the
+    /// identifiers pool.
+    pub(in crate::compiler) namespace_labels:
+        FxHashMap<IdentId, Vec<(IdentId, IdentId)>>,
+
     /// Vector containing all the compiled rules. A [`RuleId`] is an index
     /// in this vector.
     pub(in crate::compiler) rules: Vec<RuleInfo>,
@@ -101,23 +127,32 @@ pub struct Rules {
     /// vector. This vector contains both forward and backward code.
     pub(in crate::compiler) re_code: Vec<u8>,
 
+    /// Sub-expressions of rule conditions that are traced during the scan.
+    /// This is empty unless the rules were compiled with
+    /// [`crate::Compiler::condition_tracing`]. The traced sub-expressions
+    /// of each rule are a contiguous range in this vector, indicated by
+    /// [`RuleInfo::condition_traces`].
+    pub(in crate::compiler) condition_traces: Vec<TracePoint>,
+
     /// A [`types::Struct`] in serialized form that contains all the global
     /// variables. Each field in the structure corresponds to a global variable
     /// defined at compile time using [`crate::compiler::Compiler`].
     pub(in crate::compiler) serialized_globals: Vec<u8>,
 
-    /// Aho-Corasick automaton containing the atoms extracted from the patterns.
-    /// This allows to search for all the atoms in the scanned data at the same
-    /// time in an efficient manner. The automaton is not serialized during when
+    /// Searcher for the atoms extracted from the patterns. This allows to
+    /// search for all the atoms in the scanned data at the same time in an
+    /// efficient manner. The searcher is not serialized during when
     /// [`Rules::serialize`] is called, it needs to be wrapped in [`Option`] so
-    /// that we can use `#[serde(skip)]` on it because [`AhoCorasick`] doesn't
+    /// that we can use `#[serde(skip)]` on it because [`AtomSearcher`] doesn't
     /// implement the [`Default`] trait.
     #[serde(skip)]
-    pub(in crate::compiler) ac: Option<AhoCorasick>,
+    pub(in crate::compiler) atom_searcher: Option<AtomSearcher>,
 
     /// Warnings that were produced while compiling these rules. These warnings
     /// are not serialized, rules that are obtained by deserializing previously
-    /// serialized rules won't have any warnings.
+    /// serialized rules won't have any warnings. However, the warnings that
+    /// are specific to a rule are also stored in [`RuleInfo`], and those are
+    /// serialized.
     #[serde(skip)]
     pub(in crate::compiler) warnings: Vec<Warning>,
 }
@@ -132,7 +167,30 @@ impl Rules {
         }
     }
 
+    /// Version of YARA-X that compiled the rules.
+    ///
+    /// For rules obtained with [`Rules::deserialize`] this is the version
+    /// that produced the serialized rules, which is not necessarily the
+    /// current one.
+    pub fn yara_x_version(&self) -> &str {
+        self.yara_x_version.as_str()
+    }
+
+    /// Returns the module plugin with the given name, if any.
+    pub(crate) fn module_plugin(&self, name: &str) -> Option<&ModulePlugin> {
+        self.module_plugins.iter().find(|p| p.name() == name)
+    }
+
+    /// Returns an iterator over the module plugins included in these rules.
+    pub(crate) fn module_plugins(&self) -> Iter<'_, ModulePlugin> {
+        self.module_plugins.iter()
+    }
+
     /// Warnings produced while compiling these rules.
+    ///
+    /// These warnings are not preserved when the rules are serialized. For
+    /// obtaining the warnings associated to each individual rule, which are
+    /// preserved, use [`Rule::warnings`].
     pub fn warnings(&self) -> &[Warning] {
         self.warnings.as_slice()
     }
@@ -190,7 +248,7 @@ impl Rules {
         #[cfg(feature = "logging")]
         info!("Deserialization time: {:?}", Instant::elapsed(&start));
 
-        rules.build_ac_automaton();
+        rules.build_atom_searcher();
 
         Ok(rules)
     }
@@ -226,7 +284,8 @@ impl Rules {
         Self::deserialize(bytes)
     }
 
-    /// Returns an iterator that yields the compiled rules.
+    /// Returns an iterator that yields the compiled rules, in the order in
+    /// which they were added to the compiler.
     ///
     /// ```rust
     /// # use yara_x::Compiler;
@@ -249,6 +308,111 @@ impl Rules {
         RulesIter { rules: self, iterator: self.rules.iter() }
     }
 
+    /// Returns a best-effort reconstruction of the source code for all the
+    /// rules.
+    ///
+    /// This is useful for auditing rules that are available only in
+    /// compiled form. The result starts with the `import` statements for
+    /// the modules used by the rules, followed by the rules themselves, as
+    /// returned by [`Rule::to_source`]. YARA doesn't have a syntax for
+    /// namespaces, rules in namespaces other than the default one are
+    /// preceded by a comment indicating the namespace they belong to.
+    pub fn to_source(&self) -> String {
+        let mut src = String::new();
+
+        for import in self.imports() {
+            src.push_str(&format!("import \"{}\"\n", import));
+        }
+
+        for (module, alias) in &self.module_aliases {
+            src.push_str(&format!(
+                "import \"{}\" as {}\n",
+                self.ident_pool.get(*module).unwrap(),
+                self.ident_pool.get(*alias).unwrap()
+            ));
+        }
+
+        let mut current_namespace = "default";
+
+        for rule in self.iter() {
+            if !src.is_empty() {
+                src.push('\n');
+            }
+            if rule.namespace() != current_namespace {
+                current_namespace = rule.namespace();
+                src.push_str(&format!(
+                    "// namespace: {}\n\n",
+                    current_namespace
+                ));
+            }
+            src.push_str(&rule.to_source());
+        }
+
+        src
+    }
+
+    /// Returns the module fields accessed by the rules, together with the
+    /// rules that access each field.
+    ///
+    /// Fields are identified by their path, like `pe.number_of_sections`.
+    /// Arrays and maps are indexed with `[]` regardless of the actual index
+    /// or key, so both `pe.sections[0].name` and `pe.sections[1].name` are
+    /// reported as `pe.sections[].name`. Fields accessed through loop
+    /// variables are also taken into account, in
+    /// `for any s in pe.sections : (s.name == ".text")` the accessed field
+    /// is `pe.sections[].name`. Calls to module functions are not included.
+    ///
+    /// This is useful for finding out which fields are used the most, or
+    /// which are used by a few rules only.
+    ///
+    /// ```rust
+    /// # use yara_x::Compiler;
+    /// let mut compiler = Compiler::new();
+    ///
+    /// compiler
+    ///     .add_source(r#"
+    ///         import "test_proto2"
+    ///         rule foo {condition: test_proto2.int64_one == 1}
+    ///         rule bar {condition: test_proto2.int64_one > 0}"#)
+    ///     .unwrap();
+    ///
+    /// let rules = compiler.build();
+    /// let usage = rules.field_usage();
+    ///
+    /// assert_eq!(usage["test_proto2.int64_one"].len(), 2);
+    /// ```
+    pub fn field_usage(&self) -> BTreeMap<&str, Vec<Rule<'_, '_>>> {
+        let mut usage: BTreeMap<&str, Vec<Rule>> = BTreeMap::new();
+        for rule in self.iter() {
+            for field in rule.module_fields() {
+                usage.entry(field).or_default().push(Rule {
+                    ctx: None,
+                    data: None,
+                    rules: self,
+                    rule_info: rule.rule_info,
+                });
+            }
+        }
+        usage
+    }
+
+    /// Returns the [`RuleId`] of the rule with the given namespace and name,
+    /// if any.
+    pub(crate) fn find_rule(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Option<RuleId> {
+        self.rules
+            .iter()
+            .position(|rule| {
+                self.ident_pool.get(rule.ident_id) == Some(name)
+                    && self.ident_pool.get(rule.namespace_ident_id)
+                        == Some(namespace)
+            })
+            .map(RuleId::from)
+    }
+
     /// Returns a [`RuleInfo`] given its [`RuleId`].
     ///
     /// # Panics
@@ -308,16 +472,26 @@ impl Rules {
     ) -> Option<(RuleId, IdentId)> {
         let (target_pattern_id, _) = self.get_sub_pattern(sub_pattern_id);
         for (rule_id, rule) in self.rules.iter().enumerate() {
-            for (ident_id, pattern_id) in &rule.patterns {
-                if pattern_id == target_pattern_id {
-                    return Some((rule_id.into(), *ident_id));
+            for pattern in &rule.patterns {
+                if pattern.pattern_id == *target_pattern_id {
+                    return Some((rule_id.into(), pattern.ident_id));
                 };
             }
         }
         None
     }
 
-    #[cfg(feature = "rules-profiling")]
+    /// Returns the labels attached to the namespace with the given name.
+    pub(crate) fn namespace_labels(
+        &self,
+        namespace_ident_id: IdentId,
+    ) -> &[(IdentId, IdentId)] {
+        self.namespace_labels
+            .get(&namespace_ident_id)
+            .map(|labels| labels.as_slice())
+            .unwrap_or_default()
+    }
+
     #[inline]
     pub(crate) fn rules(&self) -> &[RuleInfo] {
         self.rules.as_slice()
@@ -333,6 +507,11 @@ impl Rules {
         self.anchored_sub_patterns.as_slice()
     }
 
+    #[inline]
+    pub(crate) fn word_boundary(&self) -> WordBoundary {
+        self.word_boundary
+    }
+
     #[inline]
     pub(crate) fn re_code(&self) -> &[u8] {
         self.re_code.as_slice()
@@ -343,30 +522,45 @@ impl Rules {
         self.rules.len()
     }
 
+    #[inline]
+    pub(crate) fn condition_traces(&self) -> &[TracePoint] {
+        self.condition_traces.as_slice()
+    }
+
+    /// Returns the number of rules that are not private.
+    pub(crate) fn num_non_private_rules(&self) -> usize {
+        self.rules.iter().filter(|rule| !rule.is_private).count()
+    }
+
     #[inline]
     pub(crate) fn num_patterns(&self) -> usize {
         self.num_patterns
     }
 
-    /// Returns the Aho-Corasick automaton that allows to search for pattern
-    /// atoms.
+    /// Returns the searcher that allows to search for pattern atoms.
     #[inline]
-    pub(crate) fn ac_automaton(&self) -> &AhoCorasick {
-        self.ac.as_ref().expect("Aho-Corasick automaton not compiled")
+    pub(crate) fn atom_searcher(&self) -> &AtomSearcher {
+        self.atom_searcher.as_ref().expect("atom searcher not built")
     }
 
-    pub(crate) fn build_ac_automaton(&mut self) {
-        if self.ac.is_some() {
+    pub(crate) fn build_atom_searcher(&mut self) {
+        if self.atom_searcher.is_some() {
             return;
         }
 
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("build_atom_searcher").entered();
+
         #[cfg(feature = "logging")]
         let start = Instant::now();
 
         #[cfg(feature = "logging")]
         let mut num_atoms = [0_usize; 6];
 
-        let atoms = self.atoms.iter().map(|x| {
+        let atoms: Vec<&[u8]> = self
+            .atoms
+            .iter()
+            .map(|x| {
             #[cfg(feature = "logging")]
             {
                 match x.atom.len() {
@@ -396,19 +590,14 @@ impl Rules {
             }
 
             x.atom.as_ref()
-        });
+        })
+            .collect();
 
-        self.ac = Some(
-            AhoCorasick::new(atoms)
-                .expect("failed to build Aho-Corasick automaton"),
-        );
+        self.atom_searcher = Some(AtomSearcher::new(atoms.as_slice()));
 
         #[cfg(feature = "logging")]
         {
-            info!(
-                "Aho-Corasick automaton build time: {:?}",
-                Instant::elapsed(&start)
-            );
+            info!("Atom searcher build time: {:?}", Instant::elapsed(&start));
 
             info!("Number of rules: {}", self.num_rules());
             info!("Number of patterns: {}", self.num_patterns());
@@ -423,6 +612,10 @@ impl Rules {
             info!("Atoms with len = 3: {}", num_atoms[3]);
             info!("Atoms with len = 4: {}", num_atoms[4]);
             info!("Atoms with len > 4: {}", num_atoms[5]);
+            info!(
+                "Atoms searched with Teddy: {}",
+                self.atom_searcher().num_short_atoms()
+            );
         }
     }
 
@@ -533,9 +726,9 @@ impl fmt::Debug for Rules {
             writeln!(f, "  namespace: {}", namespace)?;
             writeln!(f, "  name: {}", name)?;
             writeln!(f, "  patterns:")?;
-            for (pattern_ident_id, pattern_id) in &rule.patterns {
-                let ident = self.ident_pool.get(*pattern_ident_id).unwrap();
-                writeln!(f, "    {:?} {} ", pattern_id, ident)?;
+            for pattern in &rule.patterns {
+                let ident = self.ident_pool.get(pattern.ident_id).unwrap();
+                writeln!(f, "    {:?} {} ", pattern.pattern_id, ident)?;
             }
         }
 
@@ -577,11 +770,52 @@ pub(crate) struct RuleInfo {
     /// Metadata associated to the rule.
     pub(crate) metadata: Vec<(IdentId, MetaValue)>,
     /// Vector with all the patterns defined by this rule.
-    pub(crate) patterns: Vec<(IdentId, PatternId)>,
+    pub(crate) patterns: Vec<PatternInfo>,
     /// True if the rule is global.
     pub(crate) is_global: bool,
     /// True if the rule is private.
     pub(crate) is_private: bool,
+    /// Warnings raised while compiling the rule.
+    pub(crate) warnings: Vec<RuleWarning>,
+    /// Paths of the module fields accessed by the rule's condition (e.g:
+    /// `pe.sections[].name`). The paths are stored in the identifiers pool.
+    pub(crate) module_fields: Vec<IdentId>,
+    /// Range within [`Rules::condition_traces`] that contains the traced
+    /// sub-expressions of this rule's condition. The range is empty if
+    /// condition tracing was not enabled.
+    pub(crate) condition_traces: Range<usize>,
+    /// Source code of the rule's condition, as it appears in the original
+    /// rule. Used for reconstructing the rule's source code.
+    pub(crate) condition_source: String,
+}
+
+/// Information about a pattern declared by a rule.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PatternInfo {
+    /// The ID of the pattern identifier (e.g: `$a`) in the identifiers pool.
+    pub(crate) ident_id: IdentId,
+    /// The ID of the pattern. Patterns that are exactly equal share the
+    /// same ID, even if they are declared by different rules.
+    pub(crate) pattern_id: PatternId,
+    /// Kind of pattern.
+    pub(crate) kind: PatternKind,
+    /// Modifiers associated to the pattern, in the same order they appear
+    /// in the source code.
+    pub(crate) modifiers: Vec<PatternModifier>,
+    /// Source code of the pattern's value, without the identifier and the
+    /// modifiers (e.g: `"foo"`, `{ 01 02 03 }` or `/foo/i`).
+    pub(crate) source: String,
+}
+
+/// A sub-expression of a rule condition whose value is recorded during the
+/// scan.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TracePoint {
+    /// Byte range of the sub-expression within the source code where the
+    /// rule was declared.
+    pub(crate) span: Range<usize>,
+    /// Source code of the sub-expression.
+    pub(crate) source: String,
 }
 
 /// Represents an atom extracted from a pattern and added to the Aho-Corasick
@@ -0,0 +1,531 @@
+/*! Rule templates.
+
+Detections often come in families of near-identical rules that only differ
+in a few literals, like the pattern being searched for or some threshold.
+A [`RuleTemplate`] is a rule skeleton that contains placeholders in the form
+`${name}`, and that can be instantiated multiple times with different values
+for those placeholders. Each instance is a regular YARA source that can be
+passed to [`crate::Compiler::add_source`].
+
+Placeholders are replaced with values of type [`TemplateValue`], which are
+always rendered as valid YARA literals. Strings are quoted and escaped, and
+byte sequences are rendered as hex patterns, so values are never interpreted
+as YARA code.
+
+Placeholders are recognized only where a token can appear. The text inside
+string literals, regular expressions and comments is left untouched, and a
+placeholder can't be glued to other identifier characters, as in `foo${bar}`.
+Use `$${` for producing a literal `${` outside string literals, regular
+expressions and comments.
+
+Each instance receives a distinct name, formed by appending a suffix to the
+identifier of the rule in the template, and its own origin, so errors and
+warnings point to the instance that produced them.
+
+# Example
+
+```rust
+# use yara_x::Compiler;
+use yara_x::templates::RuleTemplate;
+
+let template = RuleTemplate::new(
+    r#"rule large_file { condition: filesize > ${size} }"#,
+)
+.unwrap();
+
+let mut compiler = Compiler::new();
+
+for (suffix, size) in [("1kb", 1024), ("1mb", 1024 * 1024)] {
+    let instance =
+        template.instantiate(suffix, &[("size", size.into())]).unwrap();
+    compiler.add_source(&instance).unwrap();
+}
+```
+*/
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use rustc_hash::FxHashMap;
+use thiserror::Error;
+use yara_x_parser::ast::WithSpan;
+use yara_x_parser::Parser;
+
+use crate::compiler::SourceCode;
+use crate::variables::is_valid_identifier;
+
+/// Error returned while creating or instantiating a [`RuleTemplate`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A placeholder was opened with `${` but never closed.
+    #[error("unclosed placeholder at offset {0}")]
+    UnclosedPlaceholder(usize),
+
+    /// The name of a placeholder is not a valid identifier.
+    #[error("invalid placeholder name `{0}`")]
+    InvalidPlaceholder(String),
+
+    /// The suffix used for an instance can't be appended to an identifier.
+    #[error("invalid instance suffix `{0}`")]
+    InvalidSuffix(String),
+
+    /// The template has a placeholder that didn't receive any value.
+    #[error("missing value for placeholder `{0}`")]
+    MissingValue(String),
+
+    /// A value was provided for a placeholder that the template doesn't
+    /// have.
+    #[error("unknown placeholder `{0}`")]
+    UnknownPlaceholder(String),
+
+    /// A placeholder is not delimited by token boundaries, like in
+    /// `foo${bar}`.
+    #[error("placeholder at offset {0} is not a separate token")]
+    PlaceholderInsideToken(usize),
+
+    /// The value for a placeholder can't be represented as a YARA literal,
+    /// like infinite or NaN floats, or empty byte sequences.
+    #[error("invalid value for placeholder `{0}`")]
+    InvalidValue(String),
+
+    /// The template must contain exactly one rule.
+    #[error("template must contain exactly one rule, found {0}")]
+    WrongNumberOfRules(usize),
+}
+
+/// A value that replaces a placeholder in a [`RuleTemplate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateValue {
+    /// Rendered as a decimal integer literal.
+    Integer(i64),
+    /// Rendered as a float literal. Must be finite.
+    Float(f64),
+    /// Rendered as `true` or `false`.
+    Bool(bool),
+    /// Rendered as a quoted string literal, with special characters escaped.
+    String(String),
+    /// Rendered as a hex pattern (e.g. `{ 4D 5A }`). Must not be empty.
+    Bytes(Vec<u8>),
+}
+
+impl TemplateValue {
+    /// Returns true if the value can be rendered as a YARA literal.
+    fn is_valid(&self) -> bool {
+        match self {
+            Self::Float(v) => v.is_finite(),
+            Self::Bytes(v) => !v.is_empty(),
+            _ => true,
+        }
+    }
+
+    fn render(&self, output: &mut String) {
+        match self {
+            Self::Integer(v) => write!(output, "{}", v).unwrap(),
+            Self::Float(v) => {
+                // YARA float literals don't accept exponents, and must have
+                // a fractional part. `Display` for `f64` never uses
+                // exponents, but omits the fractional part when it is zero.
+                let rendered = v.to_string();
+                output.push_str(&rendered);
+                if !rendered.contains('.') {
+                    output.push_str(".0");
+                }
+            }
+            Self::Bool(v) => write!(output, "{}", v).unwrap(),
+            Self::String(v) => {
+                output.push('"');
+                for b in v.bytes() {
+                    match b {
+                        b'"' => output.push_str(r#"\""#),
+                        b'\\' => output.push_str(r"\\"),
+                        b'\n' => output.push_str(r"\n"),
+                        b'\r' => output.push_str(r"\r"),
+                        b'\t' => output.push_str(r"\t"),
+                        0x20..=0x7e => output.push(b as char),
+                        _ => write!(output, r"\x{:02x}", b).unwrap(),
+                    }
+                }
+                output.push('"');
+            }
+            Self::Bytes(v) => {
+                output.push('{');
+                for b in v {
+                    write!(output, " {:02X}", b).unwrap();
+                }
+                output.push_str(" }");
+            }
+        }
+    }
+}
+
+macro_rules! impl_from_int {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for TemplateValue {
+                fn from(value: $ty) -> Self {
+                    Self::Integer(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_int!(i64, i32, i16, i8, u32, u16, u8);
+
+impl From<f64> for TemplateValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<bool> for TemplateValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<&str> for TemplateValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for TemplateValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&[u8]> for TemplateValue {
+    fn from(value: &[u8]) -> Self {
+        Self::Bytes(value.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for TemplateValue {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+/// A rule skeleton with placeholders that can be instantiated multiple
+/// times with different values.
+///
+/// See the [module documentation](crate::templates) for details.
+#[derive(Debug, Clone)]
+pub struct RuleTemplate {
+    source: String,
+    origin: Option<String>,
+    /// The template split in fragments of literal text and placeholders,
+    /// in the order in which they appear in `source`.
+    fragments: Vec<Fragment>,
+}
+
+/// Each of the fragments in a [`RuleTemplate`].
+#[derive(Debug, Clone)]
+enum Fragment {
+    /// Text that is copied as is. The range is relative to the template's
+    /// source.
+    Text(Range<usize>),
+    /// A placeholder with the given name.
+    Placeholder(String),
+}
+
+impl RuleTemplate {
+    /// Creates a new template from its source code.
+    ///
+    /// Returns an error if some placeholder is not properly closed, if its
+    /// name is not a valid identifier, or if it is not delimited by token
+    /// boundaries.
+    pub fn new(source: &str) -> Result<Self, TemplateError> {
+        let bytes = source.as_bytes();
+        let mut fragments = Vec::new();
+        let mut text_start = 0;
+        let mut pos = 0;
+
+        while pos < bytes.len() {
+            let rest = &bytes[pos..];
+
+            if let Some(len) =
+                literal_or_comment_len(rest, operand_expected(&bytes[..pos]))
+            {
+                pos += len;
+            } else if rest.starts_with(b"$${") {
+                // Escaped placeholder, the first `$` is dropped.
+                fragments.push(Fragment::Text(text_start..pos));
+                text_start = pos + 1;
+                pos += 3;
+            } else if rest.starts_with(b"${") {
+                let name_start = pos + 2;
+                let end = source[name_start..]
+                    .find('}')
+                    .map(|e| e + name_start)
+                    .ok_or(TemplateError::UnclosedPlaceholder(pos))?;
+
+                let name = &source[name_start..end];
+
+                if !is_valid_identifier(name) {
+                    return Err(TemplateError::InvalidPlaceholder(
+                        name.to_string(),
+                    ));
+                }
+
+                let glued_before = pos > 0 && is_ident_byte(bytes[pos - 1]);
+                let glued_after =
+                    bytes.get(end + 1).is_some_and(|b| is_ident_byte(*b));
+
+                if glued_before || glued_after {
+                    return Err(TemplateError::PlaceholderInsideToken(pos));
+                }
+
+                fragments.push(Fragment::Text(text_start..pos));
+                fragments.push(Fragment::Placeholder(name.to_string()));
+                pos = end + 1;
+                text_start = pos;
+            } else {
+                pos += 1;
+            }
+        }
+
+        fragments.push(Fragment::Text(text_start..bytes.len()));
+
+        Ok(Self { source: source.to_string(), origin: None, fragments })
+    }
+
+    /// Sets a string that describes the origin of the template.
+    ///
+    /// This is usually the path of the file that contained the template. The
+    /// origin of each instance is formed by this string followed by the
+    /// instance's rule identifier between brackets (e.g. `foo.yar[foo_1]`).
+    pub fn with_origin(self, origin: &str) -> Self {
+        Self { origin: Some(origin.to_owned()), ..self }
+    }
+
+    /// Returns the names of the placeholders in the template, in the order
+    /// in which they appear for the first time.
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        let mut seen = Vec::new();
+        self.fragments.iter().filter_map(move |fragment| match fragment {
+            Fragment::Placeholder(name) if !seen.contains(&name) => {
+                seen.push(name);
+                Some(name.as_str())
+            }
+            _ => None,
+        })
+    }
+
+    /// Creates an instance of the template.
+    ///
+    /// The rule identifier in the instance is the one in the template,
+    /// followed by an underscore and `suffix`. `values` contains the value
+    /// for each placeholder, and all placeholders must receive a value.
+    ///
+    /// If the rendered source code has syntax errors the rule is not
+    /// renamed, the errors are reported when the instance is passed to
+    /// [`crate::Compiler::add_source`].
+    pub fn instantiate(
+        &self,
+        suffix: &str,
+        values: &[(&str, TemplateValue)],
+    ) -> Result<TemplateInstance, TemplateError> {
+        if suffix.is_empty()
+            || !suffix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(TemplateError::InvalidSuffix(suffix.to_string()));
+        }
+
+        let values: FxHashMap<&str, &TemplateValue> =
+            values.iter().map(|(name, value)| (*name, value)).collect();
+
+        if let Some(name) = values
+            .keys()
+            .find(|name| !self.placeholders().any(|p| p == **name))
+        {
+            return Err(TemplateError::UnknownPlaceholder(name.to_string()));
+        }
+
+        let mut rendered = String::with_capacity(self.source.len());
+
+        for fragment in &self.fragments {
+            match fragment {
+                Fragment::Text(range) => {
+                    rendered.push_str(&self.source[range.clone()])
+                }
+                Fragment::Placeholder(name) => {
+                    let value =
+                        values.get(name.as_str()).ok_or_else(|| {
+                            TemplateError::MissingValue(name.clone())
+                        })?;
+
+                    if !value.is_valid() {
+                        return Err(TemplateError::InvalidValue(name.clone()));
+                    }
+
+                    value.render(&mut rendered);
+                }
+            }
+        }
+
+        let ast = Parser::new(rendered.as_bytes()).into_ast();
+
+        if !ast.errors().is_empty() {
+            return Ok(TemplateInstance {
+                origin: self.origin.clone(),
+                source: rendered,
+            });
+        }
+
+        if ast.rules().len() != 1 {
+            return Err(TemplateError::WrongNumberOfRules(ast.rules().len()));
+        }
+
+        let ident = &ast.rules()[0].identifier;
+        let identifier = format!("{}_{}", ident.name, suffix);
+        let span = ident.span();
+
+        let mut source = String::with_capacity(rendered.len() + suffix.len());
+
+        source.push_str(&rendered[..span.start()]);
+        source.push_str(&identifier);
+        source.push_str(&rendered[span.end()..]);
+
+        let origin = match &self.origin {
+            Some(origin) => format!("{}[{}]", origin, identifier),
+            None => identifier,
+        };
+
+        Ok(TemplateInstance { origin: Some(origin), source })
+    }
+}
+
+/// Returns true if `b` can be part of an identifier.
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Returns true if the text that follows `before` is at a position where
+/// an operand is expected.
+///
+/// A slash is the start of a regular expression only where an operand is
+/// expected. In any other place it is a misspelled division operator, like
+/// in `filesize / 2` (YARA uses `\` for division), and the placeholders that
+/// follow it must be substituted so that the error is reported where it
+/// is. After an identifier, a literal, a placeholder or a closing
+/// parenthesis or bracket an operator is expected, except after the
+/// `matches` operator.
+fn operand_expected(before: &[u8]) -> bool {
+    let len = before
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(0, |p| p + 1);
+    let before = &before[..len];
+    match before.last() {
+        Some(b')' | b']' | b'}') => false,
+        Some(b) if is_ident_byte(*b) => {
+            let word_start = before
+                .iter()
+                .rposition(|b| !is_ident_byte(*b))
+                .map_or(0, |p| p + 1);
+            &before[word_start..] == b"matches"
+        }
+        _ => true,
+    }
+}
+
+/// If `s` starts with a string literal, a regular expression or a comment,
+/// returns its length. `operand_expected` indicates whether `s` is at a
+/// position where an operand is expected, a regular expression can't start
+/// otherwise.
+///
+/// This follows the rules used by the YARA tokenizer for recognizing these
+/// tokens, placeholders inside them are not substituted.
+fn literal_or_comment_len(s: &[u8], operand_expected: bool) -> Option<usize> {
+    match s {
+        [b'"', b'"', b'"', ..] => {
+            // Multi-line string literals can't contain quotes, except in
+            // escape sequences. If the closing quotes are not found, this
+            // is an empty string literal followed by a quote.
+            let mut i = 3;
+            while i < s.len() {
+                match s[i] {
+                    b'\\' => i += 2,
+                    b'"' if s[i..].starts_with(b"\"\"\"") => {
+                        return Some(i + 3)
+                    }
+                    b'"' => return Some(2),
+                    _ => i += 1,
+                }
+            }
+            Some(2)
+        }
+        [b'"', ..] => {
+            let mut i = 1;
+            while i < s.len() {
+                match s[i] {
+                    b'\\' => i += 2,
+                    b'"' => return Some(i + 1),
+                    b'\n' => return None,
+                    _ => i += 1,
+                }
+            }
+            None
+        }
+        [b'/', b'/', ..] => {
+            Some(s.iter().position(|b| *b == b'\n').unwrap_or(s.len()))
+        }
+        [b'/', b'*', ..] => {
+            s[2..].windows(2).position(|w| w == b"*/").map(|p| p + 4)
+        }
+        [b'/', first, ..]
+            if operand_expected && !matches!(first, b'*' | b'/' | b'\n') =>
+        {
+            let mut i = 1;
+            while i < s.len() {
+                match s[i] {
+                    b'\\' => i += 2,
+                    b'/' => {
+                        // Up to two modifiers, like `i` and `s`.
+                        let modifiers = s[i + 1..]
+                            .iter()
+                            .take(2)
+                            .take_while(|b| b.is_ascii_alphabetic())
+                            .count();
+                        return Some(i + 1 + modifiers);
+                    }
+                    b'\n' => return None,
+                    _ => i += 1,
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// An instance of a [`RuleTemplate`].
+///
+/// Instances are passed to [`crate::Compiler::add_source`] like any other
+/// source code.
+#[derive(Debug, Clone)]
+pub struct TemplateInstance {
+    origin: Option<String>,
+    source: String,
+}
+
+impl TemplateInstance {
+    /// Returns the source code produced for this instance.
+    pub fn source(&self) -> &str {
+        self.source.as_str()
+    }
+}
+
+impl<'a> From<&'a TemplateInstance> for SourceCode<'a> {
+    /// Creates a new [`SourceCode`] from a [`TemplateInstance`].
+    fn from(instance: &'a TemplateInstance) -> Self {
+        let src = SourceCode::from(instance.source.as_str());
+        match &instance.origin {
+            Some(origin) => src.with_origin(origin),
+            None => src,
+        }
+    }
+}
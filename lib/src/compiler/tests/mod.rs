@@ -4,10 +4,20 @@ use std::fs;
 use std::io::Write;
 use std::mem::size_of;
 
+use yara_x_parser::ast;
+
 use crate::compiler::{SubPattern, Var, VarStack};
-use crate::errors::{SerializationError, VariableError};
+use crate::errors::{
+    CompileError, SerializationError, TemplateError, VariableError,
+};
+use crate::matcher::Matcher;
+use crate::passes::{CompilerPass, Condition};
+use crate::templates::RuleTemplate;
 use crate::types::Type;
-use crate::{compile, Compiler, Rules, Scanner, SourceCode};
+use crate::{
+    compile, Compiler, PatternKind, PatternModifier, Rules, Scanner,
+    SourceCode, WordBoundary,
+};
 
 #[test]
 fn serialization() {
@@ -28,6 +38,8 @@ fn serialization() {
 
     let rules = Rules::deserialize(rules).unwrap();
 
+    assert_eq!(rules.yara_x_version(), env!("CARGO_PKG_VERSION"));
+
     let mut scanner = Scanner::new(&rules);
     assert_eq!(
         scanner
@@ -541,6 +553,82 @@ fn relaxed_re_syntax() {
     );
 }
 
+#[test]
+fn yara_compat_mode() {
+    let mut compiler = Compiler::new();
+
+    compiler.yara_compat_mode(true);
+    compiler
+        .add_source(r#"rule test_1 { strings: $a = /\X\Y\Z/ condition: $a }"#)
+        .unwrap()
+        .add_source(r#"rule test_2 { strings: $a = /xyz{/ condition: $a }"#)
+        .unwrap()
+        .add_source(r#"rule test_3 { condition: "xyz{" matches /z{/ }"#)
+        .unwrap()
+        .add_source(
+            r#"rule test_4 { strings: $a = "foo" wide wide condition: $a }"#,
+        )
+        .unwrap()
+        .add_source(r#"rule test_5 { strings: $a = /xyz/ condition: $a }"#)
+        .unwrap()
+        .add_source(
+            r#"import "pe"
+               rule test_6 { strings: $a = "MZ" condition: $a at entrypoint }"#,
+        )
+        .unwrap();
+
+    let warnings: Vec<_> =
+        compiler.warnings().iter().map(|w| w.code()).collect();
+
+    assert_eq!(
+        warnings,
+        vec![
+            "relaxed_re_syntax",
+            "relaxed_re_syntax",
+            "relaxed_re_syntax",
+            "duplicate_modifier",
+            "deprecated_entrypoint"
+        ]
+    );
+
+    // `entrypoint` is an error if neither `pe` nor `elf` are imported.
+    assert_eq!(
+        compiler
+            .new_namespace("foo")
+            .add_source(r#"rule test_7 { condition: entrypoint == 0 }"#)
+            .err()
+            .unwrap()
+            .code(),
+        "E017"
+    );
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    assert_eq!(scanner.scan(b"XYZ").unwrap().matching_rules().len(), 2);
+    assert_eq!(scanner.scan(b"xyz{").unwrap().matching_rules().len(), 3);
+    assert_eq!(scanner.scan(b"f\0o\0o\0").unwrap().matching_rules().len(), 2);
+
+    // Without the compatibility mode, these are errors.
+    let mut compiler = Compiler::new();
+
+    assert!(compiler
+        .add_source(
+            r#"import "pe" rule test_1 { condition: entrypoint == 0 }"#
+        )
+        .is_err());
+
+    assert!(compiler
+        .add_source(r#"rule test_1 { strings: $a = /xyz{/ condition: $a }"#)
+        .is_err());
+
+    assert!(compiler
+        .add_source(
+            r#"rule test_2 { strings: $a = "foo" wide wide condition: $a }"#,
+        )
+        .is_err());
+}
+
 #[test]
 fn unsupported_modules() {
     let mut compiler = Compiler::new();
@@ -746,24 +834,30 @@ fn errors_2() {
   |"
     );
 
+    // Overflows in constant expressions are not errors, the result of the
+    // expression is undefined, but a warning is raised.
     #[cfg(feature = "constant-folding")]
-    assert_eq!(
-        Compiler::new()
+    {
+        let mut compiler = Compiler::new();
+        compiler
             .add_source(
                 "rule test {
 condition:
   	9223372036854775807 + 1000000000 == 0
-}"
+}",
             )
-            .unwrap_err()
-            .to_string(),
-        "error[E007]: number out of range
+            .unwrap();
+
+        assert_eq!(
+            compiler.warnings()[0].to_string(),
+            "warning[arithmetic_overflow]: arithmetic overflow
  --> line:3:4
   |
 3 |    9223372036854775807 + 1000000000 == 0
-  |    ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ this number is out of the allowed range [-9223372036854775808-9223372036854775807]
+  |    -------------------------------- this expression overflows, its value is undefined
   |"
-    );
+        );
+    }
 }
 
 #[test]
@@ -907,3 +1001,1090 @@ fn test_warnings() {
         }
     }
 }
+
+#[test]
+fn rule_warnings() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+            rule foo {
+              strings:
+                $a = { 00 00 00 00 }
+              condition:
+                $a
+            }
+            rule bar {
+              condition:
+                true
+            }
+            rule baz {
+              strings:
+                $a = "baz"
+              condition:
+                $a
+            }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+
+    // Rule warnings must be preserved after serialization.
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+
+    assert!(rules.warnings().is_empty());
+
+    let warnings: Vec<_> = rules
+        .iter()
+        .map(|rule| {
+            (
+                rule.identifier(),
+                rule.warnings().iter().map(|w| w.code()).collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        warnings,
+        vec![
+            ("foo", vec!["slow_pattern"]),
+            ("bar", vec!["invariant_expr"]),
+            ("baz", vec![]),
+        ]
+    );
+
+    let warning = &rules.iter().next().unwrap().warnings()[0];
+
+    assert_eq!(warning.title(), "slow pattern");
+    assert!(warning.to_string().starts_with("warning[slow_pattern]"));
+}
+
+#[test]
+fn field_usage() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+            import "pe"
+            rule foo {
+              condition:
+                pe.machine == pe.MACHINE_AMD64 and
+                pe.number_of_sections > 2 and
+                pe.sections[0].name == ".text" and
+                pe.imphash() == "c0e4cfb8f8e33ee13b6d8ff0ac2cf72c"
+            }
+            rule bar {
+              condition:
+                for any section in pe.sections : (
+                  section.raw_data_size > 0 and
+                  for any i in (0..1) : (section.name == ".text")
+                )
+            }
+            rule baz {
+              condition:
+                with section = pe.sections[1] : (
+                  section.virtual_size > 0 and
+                  for any section in (1, 2) : (section == 1)
+                )
+            }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+
+    // Module fields must be preserved after serialization.
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+
+    assert_eq!(
+        rules
+            .iter()
+            .map(|rule| rule.module_fields().collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        vec![
+            vec!["pe.machine", "pe.number_of_sections", "pe.sections[].name"],
+            vec!["pe.sections[].name", "pe.sections[].raw_data_size"],
+            vec!["pe.sections[].virtual_size"],
+        ]
+    );
+
+    let usage = rules
+        .field_usage()
+        .into_iter()
+        .map(|(field, rules)| {
+            (field, rules.iter().map(|r| r.identifier()).collect::<Vec<_>>())
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        usage,
+        vec![
+            ("pe.machine", vec!["foo"]),
+            ("pe.number_of_sections", vec!["foo"]),
+            ("pe.sections[].name", vec!["foo", "bar"]),
+            ("pe.sections[].raw_data_size", vec!["bar"]),
+            ("pe.sections[].virtual_size", vec!["baz"]),
+        ]
+    );
+}
+
+#[test]
+fn rules_introspection() {
+    let alphabet =
+        "!@#$%^&*(){}[].,|ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstu";
+
+    let rules = compile(
+        r#"
+        import "pe"
+
+        global private rule foo : bar baz {
+            meta:
+                author = "foo"
+                version = 2
+            strings:
+                $a = "foo" ascii wide private
+                $b = { 01 02 03 }
+                $c = /foo.*bar/ nocase
+                $d = "bar" base64("!@#$%^&*(){}[].,|ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstu")
+                $e = "baz" xor(1-2)
+            condition:
+                all of them and pe.is_pe
+        }
+        "#,
+    )
+    .unwrap();
+
+    // The information must be preserved after serialization.
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+
+    assert_eq!(rules.imports().collect::<Vec<_>>(), vec!["pe"]);
+
+    let rule = rules.iter().next().unwrap();
+
+    assert_eq!(rule.identifier(), "foo");
+    assert_eq!(rule.namespace(), "default");
+    assert!(rule.is_global());
+    assert!(rule.is_private());
+    assert_eq!(
+        rule.tags().map(|tag| tag.identifier()).collect::<Vec<_>>(),
+        vec!["bar", "baz"]
+    );
+    assert_eq!(
+        rule.metadata().into_json(),
+        json!([("author", "foo"), ("version", 2)])
+    );
+
+    let patterns = rule.patterns().collect::<Vec<_>>();
+
+    assert_eq!(
+        patterns
+            .iter()
+            .map(|p| (p.identifier(), p.kind(), p.is_private()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("$a", PatternKind::Text, true),
+            ("$b", PatternKind::Hex, false),
+            ("$c", PatternKind::Regexp, false),
+            ("$d", PatternKind::Text, false),
+            ("$e", PatternKind::Text, false),
+        ]
+    );
+
+    assert_eq!(
+        patterns[0].modifiers(),
+        &[
+            PatternModifier::Ascii,
+            PatternModifier::Wide,
+            PatternModifier::Private
+        ]
+    );
+    assert!(patterns[1].modifiers().is_empty());
+    assert_eq!(patterns[2].modifiers(), &[PatternModifier::Nocase]);
+    assert_eq!(
+        patterns[3].modifiers(),
+        &[PatternModifier::Base64 { alphabet: Some(alphabet.to_string()) }]
+    );
+    assert_eq!(
+        patterns[4].modifiers(),
+        &[PatternModifier::Xor { start: 1, end: 2 }]
+    );
+}
+
+#[test]
+fn rules_to_source() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+            import "pe"
+            // This comment is not preserved.
+            global rule foo : bar baz {
+              meta:
+                str = "quote \" and \x00"
+                int = -1
+                float = 1.0
+                bool = true
+              strings:
+                $a = "foo" ascii wide
+                $b = { 01 02 [0-2]
+                       03 ?? 04 }
+                $c = /foo.*bar/is nocase
+              condition:
+                all of them and pe.is_pe
+            }"#,
+        )
+        .unwrap()
+        .new_namespace("other")
+        .add_source(r#"rule qux { condition: true }"#)
+        .unwrap();
+
+    let rules = compiler.build();
+
+    // The source code must be preserved after serialization.
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+
+    let expected = r#"import "pe"
+
+global rule foo : bar baz {
+  meta:
+    str = "quote \" and \x00"
+    int = -1
+    float = 1.0
+    bool = true
+  strings:
+    $a = "foo" ascii wide
+    $b = { 01 02 [0-2] 03 ?? 04 }
+    $c = /foo.*bar/is nocase
+  condition:
+    all of them and pe.is_pe
+}
+
+// namespace: other
+
+rule qux {
+  condition:
+    true
+}
+"#;
+
+    assert_eq!(rules.to_source(), expected);
+
+    // The reconstructed source code can be compiled again.
+    assert!(compile(expected).is_ok());
+}
+
+#[test]
+fn compiler_passes() {
+    struct FilesizeGuard;
+
+    impl CompilerPass for FilesizeGuard {
+        fn post_ir(&self, condition: &mut Condition) -> Result<(), String> {
+            if !condition.uses_filesize() {
+                condition.require_max_filesize(3);
+            }
+            Ok(())
+        }
+    }
+
+    struct RequireMetadata;
+
+    impl CompilerPass for RequireMetadata {
+        fn pre_ir(&self, rule: &ast::Rule) -> Result<(), String> {
+            if rule.meta.is_none() {
+                return Err("rules must have metadata".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    struct Bloat;
+
+    impl CompilerPass for Bloat {
+        fn post_ir(&self, condition: &mut Condition) -> Result<(), String> {
+            for _ in 0..10_000 {
+                condition.require_min_filesize(0);
+            }
+            Ok(())
+        }
+    }
+
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_pass(FilesizeGuard)
+        .add_source(
+            r#"
+            rule foo { condition: true }
+            rule bar { condition: filesize > 0 }"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    let matching = |scanner: &mut Scanner, data: &[u8]| {
+        scanner
+            .scan(data)
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.identifier().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matching(&mut scanner, b"foo"), vec!["foo", "bar"]);
+    assert_eq!(matching(&mut scanner, b"foobar"), vec!["bar"]);
+
+    let mut compiler = Compiler::new();
+
+    compiler.add_pass(RequireMetadata);
+
+    let err =
+        compiler.add_source(r#"rule foo { condition: true }"#).err().unwrap();
+
+    assert!(matches!(err, CompileError::RuleRejected(_)));
+    assert_eq!(
+        err.to_string(),
+        r#"error[E035]: rule rejected
+ --> line:1:6
+  |
+1 | rule foo { condition: true }
+  |      ^^^ rules must have metadata
+  |"#
+    );
+
+    assert!(compiler
+        .add_source(r#"rule foo { meta: a = 1 condition: true }"#)
+        .is_ok());
+
+    let mut compiler = Compiler::new();
+
+    compiler.add_pass(Bloat);
+
+    assert!(matches!(
+        compiler.add_source(r#"rule foo { condition: true }"#),
+        Err(CompileError::RuleRejected(_))
+    ));
+}
+
+#[test]
+fn namespace_labels() {
+    let mut compiler = Compiler::new();
+
+    compiler
+        .add_source("rule foo { condition: true }")
+        .unwrap()
+        .new_namespace("vendor_x")
+        .add_namespace_label("feed", "vendor_x")
+        .add_namespace_label("revision", "2024.05")
+        .add_source("rule bar { condition: true }")
+        .unwrap()
+        .new_namespace("vendor_y")
+        .add_namespace_label("feed", "vendor_y")
+        .add_source("rule baz { condition: true }")
+        .unwrap()
+        .new_namespace("vendor_x")
+        .add_namespace_label("revision", "2024.06")
+        .add_source("rule qux { condition: true }")
+        .unwrap();
+
+    let rules = compiler.build();
+    let rules = Rules::deserialize(rules.serialize().unwrap()).unwrap();
+    let mut scanner = Scanner::new(&rules);
+    let results = scanner.scan(b"").unwrap();
+
+    let labels = results
+        .matching_rules()
+        .map(|rule| {
+            (rule.identifier(), rule.labels().collect::<Vec<(&str, &str)>>())
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        labels,
+        vec![
+            ("foo", vec![]),
+            ("bar", vec![("feed", "vendor_x"), ("revision", "2024.06")]),
+            ("baz", vec![("feed", "vendor_y")]),
+            ("qux", vec![("feed", "vendor_x"), ("revision", "2024.06")]),
+        ]
+    );
+}
+
+#[test]
+fn rule_templates() {
+    let mut compiler = Compiler::new();
+
+    let template = RuleTemplate::new(
+        r#"
+rule magic {
+  strings:
+    $a = ${pattern}
+    $b = ${bytes}
+  condition:
+    #a + #b >= ${count} and ${enabled}
+}"#,
+    )
+    .unwrap()
+    .with_origin("magic.yar");
+
+    assert_eq!(
+        template.placeholders().collect::<Vec<_>>(),
+        vec!["pattern", "bytes", "count", "enabled"]
+    );
+
+    for (suffix, pattern, count) in
+        [("foo", "foo\"", 1), ("bar", "bar\n", 2), ("baz", "baz", 3)]
+    {
+        let instance = template
+            .instantiate(
+                suffix,
+                &[
+                    ("pattern", pattern.into()),
+                    ("bytes", b"\x00\xff".as_slice().into()),
+                    ("count", count.into()),
+                    ("enabled", true.into()),
+                ],
+            )
+            .unwrap();
+
+        compiler.add_source(&instance).unwrap();
+    }
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    let matching = |scanner: &mut Scanner, data: &[u8]| {
+        scanner
+            .scan(data)
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.identifier().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matching(&mut scanner, b"foo\" bar\n"), vec!["magic_foo"]);
+    assert_eq!(
+        matching(&mut scanner, b"foo\" bar\n\x00\xff"),
+        vec!["magic_foo", "magic_bar"]
+    );
+    assert_eq!(
+        matching(&mut scanner, b"baz\x00\xff baz"),
+        vec!["magic_foo", "magic_baz"]
+    );
+
+    let template =
+        RuleTemplate::new(r#"rule big { condition: filesize > ${size} }"#)
+            .unwrap()
+            .with_origin("big.yar");
+
+    let instance =
+        template.instantiate("str", &[("size", "1".into())]).unwrap();
+
+    assert_eq!(
+        instance.source(),
+        r#"rule big_str { condition: filesize > "1" }"#
+    );
+
+    assert_eq!(
+        Compiler::new().add_source(&instance).err().unwrap().to_string(),
+        r#"error[E003]: mismatching types
+ --> big.yar[big_str]:1:27
+  |
+1 | rule big_str { condition: filesize > "1" }
+  |                           ^^^^^^^^ this expression is `integer`
+  |                                      ^^^ this expression is `string`
+  |"#
+    );
+
+    assert_eq!(
+        template.instantiate("1", &[]).err().unwrap(),
+        TemplateError::MissingValue("size".to_string())
+    );
+
+    assert_eq!(
+        template
+            .instantiate("1", &[("size", 1.into()), ("foo", 1.into())])
+            .err()
+            .unwrap(),
+        TemplateError::UnknownPlaceholder("foo".to_string())
+    );
+
+    assert_eq!(
+        template.instantiate("a-b", &[("size", 1.into())]).err().unwrap(),
+        TemplateError::InvalidSuffix("a-b".to_string())
+    );
+
+    assert_eq!(
+        template.instantiate("é", &[("size", 1.into())]).err().unwrap(),
+        TemplateError::InvalidSuffix("é".to_string())
+    );
+
+    assert_eq!(
+        template.instantiate("1", &[("size", 1e100.into())]).unwrap().source(),
+        format!(
+            "rule big_1 {{ condition: filesize > 1{}.0 }}",
+            "0".repeat(100)
+        )
+    );
+
+    assert_eq!(
+        template.instantiate("1", &[("size", f64::NAN.into())]).err().unwrap(),
+        TemplateError::InvalidValue("size".to_string())
+    );
+
+    // Placeholders inside string literals, regular expressions and comments
+    // are not substituted, and `$${` produces a literal `${`.
+    let template = RuleTemplate::new(
+        r#"rule a {
+  strings:
+    $a = "${b}" // ${b}
+    $b = /${b}/ /* ${b} */
+  condition:
+    $${b} or ${b}
+}"#,
+    )
+    .unwrap();
+
+    assert_eq!(template.placeholders().collect::<Vec<_>>(), vec!["b"]);
+
+    assert_eq!(
+        template.instantiate("x", &[("b", true.into())]).unwrap().source(),
+        r#"rule a {
+  strings:
+    $a = "${b}" // ${b}
+    $b = /${b}/ /* ${b} */
+  condition:
+    ${b} or true
+}"#
+    );
+
+    // A slash is a regular expression only where an operand is expected.
+    // YARA uses `\` for division, but placeholders after a slash used as
+    // division are still substituted.
+    let template = RuleTemplate::new(
+        r#"rule a {
+  condition:
+    filesize \ ${divisor} > 2 and "${b}" matches /${b}/ and
+    filesize / ${divisor} > 2 and (filesize) / ${divisor} > 2
+}"#,
+    )
+    .unwrap();
+
+    assert_eq!(template.placeholders().collect::<Vec<_>>(), vec!["divisor"]);
+
+    assert_eq!(
+        template.instantiate("x", &[("divisor", 4.into())]).unwrap().source(),
+        r#"rule a {
+  condition:
+    filesize \ 4 > 2 and "${b}" matches /${b}/ and
+    filesize / 4 > 2 and (filesize) / 4 > 2
+}"#
+    );
+
+    assert_eq!(
+        RuleTemplate::new("rule a { condition: x${b} }").err().unwrap(),
+        TemplateError::PlaceholderInsideToken(21)
+    );
+
+    assert_eq!(
+        RuleTemplate::new("rule a { condition: ${b}x }").err().unwrap(),
+        TemplateError::PlaceholderInsideToken(20)
+    );
+
+    assert_eq!(
+        RuleTemplate::new("rule a { condition: ${b }").err().unwrap(),
+        TemplateError::InvalidPlaceholder("b ".to_string())
+    );
+
+    assert_eq!(
+        RuleTemplate::new("rule a { condition: ${b").err().unwrap(),
+        TemplateError::UnclosedPlaceholder(20)
+    );
+
+    assert_eq!(
+        RuleTemplate::new(
+            "rule a { condition: ${b} } rule c { condition: true }"
+        )
+        .unwrap()
+        .instantiate("x", &[("b", true.into())])
+        .err()
+        .unwrap(),
+        TemplateError::WrongNumberOfRules(2)
+    );
+}
+
+#[test]
+fn standalone_matcher() {
+    let matcher = Matcher::new("{ 4D 5A [2-4] ( 00 | ?F ) }").unwrap();
+
+    assert_eq!(
+        matcher.find_all(b"MZ..\x00 MZ...\x1f MZ\x00"),
+        vec![0..5, 6..12]
+    );
+
+    let matcher = Matcher::new("{ 61 ?? ~62 }").unwrap();
+    assert_eq!(matcher.find_all(b"axbaxcab"), vec![3..6]);
+
+    let matcher = Matcher::new("/ab[0-9]+c/").unwrap();
+    assert_eq!(matcher.find_all(b"ab1c ab12c abc"), vec![0..4, 5..10]);
+
+    // Greedy regexps produce the longest match at each offset.
+    let matcher = Matcher::new("/a.*b/s").unwrap();
+    assert_eq!(matcher.find_all(b"a\nb b"), vec![0..5]);
+
+    let matcher = Matcher::new(r#""foo""#).unwrap();
+    assert_eq!(matcher.find_all(b"foofoo"), vec![0..3, 3..6]);
+
+    // The scan limit restricts the length of the data examined after the
+    // atom.
+    let matcher = Matcher::new("{ 61 62 63 [0-50] 64 }").unwrap();
+    let data = [b"abc".as_slice(), &[0; 40], b"d"].concat();
+    assert_eq!(matcher.find_all(&data), vec![0..44]);
+    assert!(matcher.scan_limit(20).find_all(&data).is_empty());
+
+    let matcher = Matcher::new(r"/\bfoo\b/").unwrap();
+    assert!(matcher.find_all(b"_foo_").is_empty());
+    let matcher = matcher.word_boundary(WordBoundary::Ascii);
+    assert_eq!(matcher.find_all(b"_foo_"), vec![1..4]);
+
+    assert!(matches!(
+        Matcher::new("{ 61 62 63 }  wide").err().unwrap(),
+        CompileError::InvalidModifier(_)
+    ));
+
+    assert!(matches!(
+        Matcher::new("/a*/").err().unwrap(),
+        CompileError::InvalidRegexp(_)
+    ));
+
+    assert!(matches!(
+        Matcher::new("{ 61 [0-0] 62 }").err().unwrap(),
+        CompileError::InvalidPattern(_)
+    ));
+
+    assert!(matches!(
+        Matcher::new("{ 61 6").err().unwrap(),
+        CompileError::SyntaxError(_)
+    ));
+}
+
+#[test]
+fn import_alias() {
+    let rules = compile(
+        r#"
+        import "test_proto2" as t
+        rule test { condition: t.int64_one == 1 }"#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+    assert_eq!(scanner.scan(b"").unwrap().matching_rules().len(), 1);
+
+    assert!(rules.to_source().contains(r#"import "test_proto2" as t"#));
+
+    // `as` is a keyword only in import statements.
+    assert!(compile(
+        r#"
+        import "test_proto2" as as
+        rule test { condition: as.int64_one == 1 }"#
+    )
+    .is_ok());
+
+    // The module is referred to only by its alias.
+    assert!(matches!(
+        compile(
+            r#"
+            import "test_proto2" as t
+            rule test { condition: test_proto2.int64_one == 1 }"#
+        ),
+        Err(CompileError::UnknownIdentifier(_))
+    ));
+
+    // The same module can be imported more than once with different names.
+    assert!(compile(
+        r#"
+        import "test_proto2"
+        import "test_proto2" as t
+        rule test { condition: t.int64_one == test_proto2.int64_one }"#
+    )
+    .is_ok());
+
+    assert!(matches!(
+        compile(
+            r#"
+            import "test_proto2"
+            import "test_proto3" as test_proto2"#
+        ),
+        Err(CompileError::ConflictingImport(_))
+    ));
+
+    assert!(matches!(
+        compile(
+            r#"
+            import "test_proto3" as test_proto2
+            import "test_proto2""#
+        ),
+        Err(CompileError::ConflictingImport(_))
+    ));
+}
+
+#[test]
+fn module_policy() {
+    let mut compiler = Compiler::new();
+
+    compiler.forbid_module("test_proto2").ignore_module("test_proto2");
+
+    let err = compiler.add_source(r#"import "test_proto2""#).err().unwrap();
+
+    assert!(matches!(err, CompileError::ForbiddenModule(_)));
+    assert_eq!(
+        err.to_string(),
+        r#"error[E038]: forbidden module `test_proto2`
+ --> line:1:1
+  |
+1 | import "test_proto2"
+  | ^^^^^^^^^^^^^^^^^^^^ module `test_proto2` can't be used
+  |"#
+    );
+
+    assert!(compiler.add_source(r#"import "test_proto3""#).is_ok());
+
+    let mut compiler = Compiler::new();
+
+    compiler.allow_only_modules(["test_proto3", "math"]);
+
+    let err =
+        compiler.add_source(r#"import "test_proto2" as t"#).err().unwrap();
+
+    assert_eq!(
+        err.to_string(),
+        r#"error[E038]: forbidden module `test_proto2`
+ --> line:1:1
+  |
+1 | import "test_proto2" as t
+  | ^^^^^^^^^^^^^^^^^^^^^^^^^ module `test_proto2` can't be used
+  |
+  = note: allowed modules: math, test_proto3"#
+    );
+
+    assert!(compiler.add_source(r#"import "test_proto3""#).is_ok());
+
+    let mut compiler = Compiler::new();
+
+    compiler.require_module("test_proto2");
+
+    let err = compiler
+        .add_source(r#"import "test_proto3" rule a { condition: true }"#)
+        .err()
+        .unwrap();
+
+    assert!(matches!(err, CompileError::MissingRequiredModule(_)));
+    assert_eq!(
+        err.to_string(),
+        r#"error[E040]: missing import for required module `test_proto2`
+ --> line:1:27
+  |
+1 | import "test_proto3" rule a { condition: true }
+  |                           ^ this rule must be in a source file that imports `test_proto2`
+  |"#
+    );
+
+    // Sources without rules, and sources that import the module with an
+    // alias, are accepted.
+    assert!(compiler.add_source(r#"import "test_proto3""#).is_ok());
+    assert!(compiler
+        .add_source(r#"import "test_proto2" as t rule a { condition: true }"#)
+        .is_ok());
+}
+
+#[test]
+fn quotas() {
+    let mut compiler = Compiler::new();
+
+    compiler.max_patterns_per_namespace(2);
+
+    assert!(compiler
+        .add_source(r#"rule a { strings: $a = "foo" condition: $a }"#)
+        .is_ok());
+
+    let err = compiler
+        .add_source(
+            r#"rule b { strings: $a = "bar" $b = "baz" condition: all of them }"#,
+        )
+        .err()
+        .unwrap();
+
+    assert!(matches!(err, CompileError::QuotaExceeded(_)));
+    assert_eq!(
+        err.to_string(),
+        r#"error[E039]: quota exceeded
+ --> line:1:30
+  |
+1 | rule b { strings: $a = "bar" $b = "baz" condition: all of them }
+  |                              ^^^^^^^^^^ this pattern exceeds the limit of 2 patterns
+  |
+  = note: namespace `default` already has 1 patterns"#
+    );
+
+    // The limit applies to each namespace separately.
+    compiler.new_namespace("foo");
+
+    assert!(compiler
+        .add_source(
+            r#"rule b { strings: $a = "bar" $b = "baz" condition: all of them }"#,
+        )
+        .is_ok());
+
+    let mut compiler = Compiler::new();
+
+    compiler.max_atoms(1);
+
+    assert!(compiler
+        .add_source(r#"rule a { strings: $a = "foo" condition: $a }"#)
+        .is_ok());
+
+    // The rule is rejected, and the pattern it contains is not shared with
+    // other rules.
+    assert!(matches!(
+        compiler.add_source(r#"rule b { strings: $a = "bar" condition: $a }"#),
+        Err(CompileError::QuotaExceeded(_))
+    ));
+
+    // Patterns that already exist don't add new atoms.
+    assert!(compiler
+        .add_source(r#"rule c { strings: $a = "foo" condition: $a }"#)
+        .is_ok());
+
+    let mut compiler = Compiler::new();
+
+    compiler.max_condition_nodes(3);
+
+    assert!(compiler
+        .add_source(r#"rule a { condition: filesize > 10 }"#)
+        .is_ok());
+
+    let err = compiler
+        .add_source(r#"rule b { condition: filesize > 10 and filesize < 20 }"#)
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        err.to_string(),
+        r#"error[E039]: quota exceeded
+ --> line:1:21
+  |
+1 | rule b { condition: filesize > 10 and filesize < 20 }
+  |                     ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ this condition exceeds the limit of 3 nodes
+  |
+  = note: the condition has 7 nodes"#
+    );
+}
+
+#[test]
+fn shared_patterns() {
+    let rules = compile(
+        r#"
+        rule test_1 { strings: $a = "foobar" condition: $a }
+        rule test_2 { strings: $b = "foobar" private condition: $b }
+        rule test_3 { strings: $c = { 66 6F 6F 62 61 72 } condition: $c }
+        rule test_4 { strings: $d = "foobar" wide condition: $d }
+        "#,
+    )
+    .unwrap();
+
+    // Identical patterns are stored once, regardless of the rule they
+    // belong to, their identifier, or whether they are private.
+    assert_eq!(rules.num_patterns(), 2);
+
+    let mut scanner = Scanner::new(&rules);
+    let results = scanner.scan(b"foobar").unwrap();
+
+    let matches: Vec<_> = results
+        .matching_rules()
+        .flat_map(|rule| {
+            rule.patterns()
+                .map(|pattern| {
+                    (
+                        rule.identifier(),
+                        pattern.identifier(),
+                        pattern.is_private(),
+                        pattern.matches().len(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    assert_eq!(
+        matches,
+        vec![
+            ("test_1", "$a", false, 1),
+            ("test_2", "$b", true, 1),
+            ("test_3", "$c", false, 1),
+        ]
+    );
+}
+
+#[test]
+fn add_ast() {
+    use bstr::BStr;
+    use yara_x_parser::ast::{
+        BinaryExpr, Expr, Ident, LiteralInteger, LiteralString, Meta,
+        MetaValue, NAryExpr, Pattern, PatternMatch, PatternModifiers,
+        RuleFlags, TextPattern, AST,
+    };
+    use yara_x_parser::Parser;
+
+    let rule = ast::Rule {
+        flags: RuleFlags::none(),
+        identifier: Ident::new("test"),
+        tags: Some(vec![Ident::new("foo")]),
+        meta: Some(vec![Meta {
+            identifier: Ident::new("author"),
+            value: MetaValue::String("bar"),
+        }]),
+        patterns: Some(vec![Pattern::Text(Box::new(TextPattern {
+            identifier: Ident::new("$a"),
+            text: LiteralString::new(BStr::new("foobar")),
+            modifiers: PatternModifiers::default(),
+        }))]),
+        // $a and filesize > 3
+        condition: Expr::And(Box::new(NAryExpr::from(vec![
+            Expr::PatternMatch(Box::new(PatternMatch {
+                identifier: Ident::new("$a"),
+                anchor: None,
+            })),
+            Expr::Gt(Box::new(BinaryExpr {
+                lhs: Expr::Filesize { span: Default::default() },
+                rhs: Expr::LiteralInteger(Box::new(LiteralInteger::new(3))),
+            })),
+        ]))),
+    };
+
+    let mut compiler = Compiler::new();
+
+    compiler.add_ast(AST::new(vec![], vec![rule]), "").unwrap();
+
+    // Errors are reported without pointing to any source code.
+    let err = compiler
+        .add_ast(
+            AST::new(
+                vec![],
+                vec![ast::Rule {
+                    flags: RuleFlags::none(),
+                    identifier: Ident::new("test"),
+                    tags: None,
+                    meta: None,
+                    patterns: None,
+                    condition: Expr::True { span: Default::default() },
+                }],
+            ),
+            "",
+        )
+        .unwrap_err();
+
+    assert!(matches!(err, CompileError::DuplicateRule(_)));
+    assert!(err.to_string().contains("duplicate rule `test`"));
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    assert_eq!(scanner.scan(b"xxfoobarxx").unwrap().matching_rules().len(), 1);
+    assert_eq!(scanner.scan(b"foobar").unwrap().matching_rules().len(), 1);
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 0);
+
+    // Errors in an AST that was parsed from source code point to that
+    // source code.
+    let src = SourceCode::from("rule foo {\n  condition: bar\n}")
+        .with_origin("foo.yar");
+
+    let err = Compiler::new()
+        .add_ast(Parser::new(src.raw.as_ref()).into_ast(), src.clone())
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        r#"error[E009]: unknown identifier `bar`
+ --> foo.yar:2:14
+  |
+2 |   condition: bar
+  |              ^^^ this identifier has not been declared
+  |"#
+    );
+}
+
+#[cfg(feature = "rules-cache")]
+#[test]
+fn rules_cache() {
+    let dir = std::env::temp_dir()
+        .join(format!("yara-x-rules-cache-{}", std::process::id()));
+
+    let src = "rule test { condition: true }";
+
+    let mut compiler = Compiler::new();
+    let mut cache = compiler.rules_cache(&dir);
+
+    cache.add_source("default", src);
+    assert!(cache.load().is_none());
+
+    compiler.add_source(src).unwrap();
+    cache.store(&compiler.build()).unwrap();
+
+    // The same source with the same options is found in the cache.
+    let mut cache = Compiler::new().rules_cache(&dir);
+    cache.add_source("default", src);
+    assert_eq!(cache.load().unwrap().iter().len(), 1);
+
+    // A different namespace, origin or compiler option produces a
+    // different key.
+    let mut cache = Compiler::new().rules_cache(&dir);
+    cache.add_source("foo", src);
+    assert!(cache.load().is_none());
+
+    let mut cache = Compiler::new().rules_cache(&dir);
+    cache.add_source("default", SourceCode::from(src).with_origin("foo"));
+    assert!(cache.load().is_none());
+
+    let mut cache = Compiler::new().relaxed_re_syntax(true).rules_cache(&dir);
+    cache.add_source("default", src);
+    assert!(cache.load().is_none());
+
+    let mut compiler = Compiler::new();
+    compiler.define_global("foo", 1).unwrap();
+    let mut cache = compiler.rules_cache(&dir);
+    cache.add_source("default", src);
+    assert!(cache.load().is_none());
+
+    let mut compiler = Compiler::new();
+    compiler.switch_warning("slow_pattern", false).unwrap();
+    let mut cache = compiler.rules_cache(&dir);
+    cache.add_source("default", src);
+    assert!(cache.load().is_none());
+
+    // Rules with a label are stored in the cache, and only a compiler with
+    // the same label finds them.
+    let mut compiler = Compiler::new();
+    compiler.add_namespace_label("feed", "foo");
+    let mut cache = compiler.rules_cache(&dir);
+    cache.add_source("default", src);
+    assert!(cache.load().is_none());
+
+    compiler.add_source(src).unwrap();
+    cache.store(&compiler.build()).unwrap();
+
+    let mut compiler = Compiler::new();
+    compiler.add_namespace_label("feed", "foo");
+    let mut cache = compiler.rules_cache(&dir);
+    cache.add_source("default", src);
+    assert!(cache.load().is_some());
+
+    let mut compiler = Compiler::new();
+    compiler.add_namespace_label("feed", "bar");
+    let mut cache = compiler.rules_cache(&dir);
+    cache.add_source("default", src);
+    assert!(cache.load().is_none());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
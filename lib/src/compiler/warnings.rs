@@ -1,7 +1,7 @@
 #![cfg_attr(any(), rustfmt::skip)]
 
 use std::fmt::{Debug, Display, Formatter};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use thiserror::Error;
 
@@ -28,6 +28,10 @@ pub enum Warning {
     SlowPattern(Box<SlowPattern>),
     IgnoredModule(Box<IgnoredModule>),
     IgnoredRule(Box<IgnoredRule>),
+    ArithmeticOverflow(Box<ArithmeticOverflow>),
+    DuplicateModifier(Box<DuplicateModifier>),
+    RelaxedRegexpSyntax(Box<RelaxedRegexpSyntax>),
+    DeprecatedEntrypoint(Box<DeprecatedEntrypoint>),
 }
 
 /// A hex pattern contains two or more consecutive jumps.
@@ -425,3 +429,177 @@ pub struct IgnoredRule {
 }
 
 
+/// An arithmetic operation with constant operands overflows.
+///
+/// Integer arithmetic in conditions doesn't wrap around, when the result of
+/// an operation doesn't fit in a 64-bit signed integer, the result is
+/// undefined.
+///
+/// ## Example
+///
+/// ```text
+/// warning[arithmetic_overflow]: arithmetic overflow
+///  --> line:1:24
+///   |
+/// 1 | rule test { condition: 0x7fffffffffffffff + 1 == 0 }
+///   |                        ---------------------- this expression overflows, its value is undefined
+///   |
+/// ```
+#[derive(ErrorStruct, Debug, PartialEq, Eq)]
+#[associated_enum(Warning)]
+#[warning(
+    code = "arithmetic_overflow",
+    title = "arithmetic overflow"
+)]
+#[label(
+    "this expression overflows, its value is undefined",
+    expr_loc
+)]
+pub struct ArithmeticOverflow {
+    report: Report,
+    expr_loc: CodeLoc,
+}
+
+/// A pattern has duplicate modifiers.
+///
+/// Duplicate modifiers are an error, but they are accepted with this warning
+/// when [`crate::Compiler::yara_compat_mode`] is enabled.
+///
+/// ## Example
+///
+/// ```text
+/// warning[duplicate_modifier]: duplicate pattern modifier
+///  --> line:1:38
+///   |
+/// 1 | rule test { strings: $a = "foo" wide wide condition: $a }
+///   |                                      ---- duplicate modifier
+///   |
+/// ```
+#[derive(ErrorStruct, Debug, PartialEq, Eq)]
+#[associated_enum(Warning)]
+#[warning(
+    code = "duplicate_modifier",
+    title = "duplicate pattern modifier"
+)]
+#[label(
+    "duplicate modifier",
+    modifier_loc
+)]
+pub struct DuplicateModifier {
+    report: Report,
+    modifier_loc: CodeLoc,
+}
+
+/// A regular expression is valid only with the relaxed syntax used by
+/// YARA 4.x.
+///
+/// Such regular expressions are an error, but they are accepted with this
+/// warning when [`crate::Compiler::yara_compat_mode`] is enabled. See
+/// [`crate::Compiler::relaxed_re_syntax`] for details about how they are
+/// interpreted.
+///
+/// ## Example
+///
+/// ```text
+/// warning[relaxed_re_syntax]: regular expression accepted with relaxed syntax
+///  --> line:1:31
+///   |
+/// 1 | rule test { strings: $a = /foo\Rbar/ condition: $a }
+///   |                               -- unrecognized escape sequence
+///   |
+///   = note: YARA-X interprets this regular expression in the same way as YARA 4.x
+/// ```
+#[derive(ErrorStruct, Debug, PartialEq, Eq)]
+#[associated_enum(Warning)]
+#[warning(
+    code = "relaxed_re_syntax",
+    title = "regular expression accepted with relaxed syntax"
+)]
+#[label(
+    "{error}",
+    error_loc
+)]
+#[footer(note)]
+pub struct RelaxedRegexpSyntax {
+    report: Report,
+    error: String,
+    error_loc: CodeLoc,
+    note: Option<String>,
+}
+
+/// The deprecated `entrypoint` keyword was used.
+///
+/// The `entrypoint` keyword is an error, but it is accepted with this
+/// warning when [`crate::Compiler::yara_compat_mode`] is enabled and the
+/// `pe` or `elf` module is imported. The keyword is replaced with the
+/// `entry_point` field of the imported module.
+///
+/// ## Example
+///
+/// ```text
+/// warning[deprecated_entrypoint]: `entrypoint` is deprecated
+///  --> line:3:32
+///   |
+/// 3 | rule test { condition: $a at entrypoint }
+///   |                              ---------- replaced with `pe.entry_point`
+///   |
+/// ```
+#[derive(ErrorStruct, Debug, PartialEq, Eq)]
+#[associated_enum(Warning)]
+#[warning(
+    code = "deprecated_entrypoint",
+    title = "`entrypoint` is deprecated"
+)]
+#[label(
+    "replaced with `{replacement}`",
+    keyword_loc
+)]
+pub struct DeprecatedEntrypoint {
+    report: Report,
+    replacement: String,
+    keyword_loc: CodeLoc,
+}
+
+/// A warning associated to a compiled rule.
+///
+/// Contrary to [`Warning`], which contains all the details about the
+/// warning, this type contains only its code, title and textual description,
+/// and it is serialized together with the compiled rules. This means that
+/// the warnings raised while compiling a rule are available even when the
+/// rules are obtained by deserializing them. See [`crate::Rule::warnings`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleWarning {
+    code: String,
+    title: String,
+    text: String,
+}
+
+impl RuleWarning {
+    /// Code that identifies the type of warning (e.g: `slow_pattern`).
+    #[inline]
+    pub fn code(&self) -> &str {
+        self.code.as_str()
+    }
+
+    /// Title of the warning.
+    #[inline]
+    pub fn title(&self) -> &str {
+        self.title.as_str()
+    }
+}
+
+impl Display for RuleWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl From<&Warning> for RuleWarning {
+    fn from(warning: &Warning) -> Self {
+        Self {
+            code: warning.code().to_string(),
+            title: warning.title().to_string(),
+            text: warning.to_string(),
+        }
+    }
+}
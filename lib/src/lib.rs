@@ -11,6 +11,12 @@ instance of the scanner can be used for scanning a single file or memory buffer
 at a time. The scanner can be re-used for scanning multiple files or memory-buffers,
 though.
 
+[`Rules`] are `Send` and `Sync`, so they can be shared by scanners running in
+different threads. A [`Scanner`] must be used by the thread that created it.
+For applications that scan data from multiple threads, [`ScannerPool`]
+maintains a set of scanners that can be shared among threads and are reused
+across scans.
+
 # Example
 
 ```rust
@@ -46,23 +52,43 @@ assert_eq!(results.matching_rules().len(), 1);
 pub use compiler::compile;
 pub use compiler::Compiler;
 pub use compiler::Rules;
+#[cfg(feature = "rules-cache")]
+pub use compiler::RulesCache;
 pub use compiler::RulesIter;
 pub use compiler::SourceCode;
 pub use models::Match;
+pub use models::MatchVariant;
 pub use models::Matches;
 pub use models::MetaValue;
 pub use models::Metadata;
 pub use models::Pattern;
+pub use models::PatternKind;
+pub use models::PatternModifier;
 pub use models::Patterns;
 pub use models::Rule;
+pub use models::TracedExpr;
 pub use modules::mods;
+pub use modules::ModulePlugin;
+pub use re::WordBoundary;
+pub use scanner::CapturedModuleOutputs;
+#[cfg(feature = "macho-module")]
+pub use scanner::MachoSlice;
 pub use scanner::MatchingRules;
 pub use scanner::ModuleOutputs;
+pub use scanner::ModuleStats;
 pub use scanner::NonMatchingRules;
+pub use scanner::PooledScanner;
+#[cfg(feature = "rules-profiling")]
+pub use scanner::RuleProfile;
 pub use scanner::ScanError;
 pub use scanner::ScanOptions;
+pub use scanner::ScanPhase;
+pub use scanner::ScanProgress;
 pub use scanner::ScanResults;
+pub use scanner::ScanStats;
 pub use scanner::Scanner;
+pub use scanner::ScannerPool;
+pub use scanner::TimedOutRules;
 pub use variables::Variable;
 
 mod compiler;
@@ -85,7 +111,10 @@ pub mod errors {
     //! This module contains the definitions for all error types returned by this
     //! crate.
     pub use crate::compiler::errors::*;
+    pub use crate::compiler::templates::TemplateError;
     pub use crate::compiler::InvalidWarningCode;
+    pub use crate::modules::PluginError;
+    pub use crate::scanner::testing::TestError;
     pub use crate::scanner::ScanError;
     pub use crate::variables::VariableError;
 }
@@ -95,6 +124,31 @@ pub mod warnings {
     pub use crate::compiler::warnings::*;
 }
 
+pub mod matcher {
+    //! Standalone matching of hex patterns and regular expressions.
+    pub use crate::compiler::matcher::*;
+}
+
+pub mod passes {
+    //! Custom passes executed by the compiler.
+    pub use crate::compiler::passes::*;
+}
+
+pub mod aggregate {
+    //! Aggregation of results from multiple related scans.
+    pub use crate::scanner::aggregate::*;
+}
+
+pub mod testing {
+    //! Unit testing of rules.
+    pub use crate::scanner::testing::*;
+}
+
+pub mod templates {
+    //! Rule templates with placeholders.
+    pub use crate::compiler::templates::*;
+}
+
 mod utils {
     /// Tries to match `target` as the enum variant `pat`. Returns the
     /// inner value contained in the variant, or panics if `target` does
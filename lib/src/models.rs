@@ -1,10 +1,13 @@
-use crate::compiler::{IdentId, PatternId, RuleInfo};
-use crate::scanner::{ScanContext, ScannedData};
+use crate::compiler::{IdentId, PatternInfo, RuleInfo, RuleWarning};
+use crate::scanner::{ScanContext, ScannedData, TraceCounters};
 use crate::{compiler, scanner, Rules};
 use bstr::{BStr, ByteSlice};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fmt::Write;
 use std::ops::Range;
 use std::slice::Iter;
+use yara_x_parser::ast;
 
 /// A structure that describes a rule.
 pub struct Rule<'a, 'r> {
@@ -25,6 +28,16 @@ impl<'a, 'r> Rule<'a, 'r> {
         self.rules.ident_pool().get(self.rule_info.namespace_ident_id).unwrap()
     }
 
+    /// Returns `true` if the rule is global.
+    pub fn is_global(&self) -> bool {
+        self.rule_info.is_global
+    }
+
+    /// Returns `true` if the rule is private.
+    pub fn is_private(&self) -> bool {
+        self.rule_info.is_private
+    }
+
     /// Returns the metadata associated to this rule.
     pub fn metadata(&self) -> Metadata<'a, 'r> {
         Metadata {
@@ -43,7 +56,44 @@ impl<'a, 'r> Rule<'a, 'r> {
         }
     }
 
-    /// Returns the patterns defined by this rule.
+    /// Returns the labels attached to the rule's namespace, as (key, value)
+    /// pairs.
+    ///
+    /// See [`crate::Compiler::add_namespace_label`].
+    pub fn labels(&self) -> impl ExactSizeIterator<Item = (&'r str, &'r str)> {
+        let rules = self.rules;
+        rules.namespace_labels(self.rule_info.namespace_ident_id).iter().map(
+            |(key, value)| {
+                (
+                    rules.ident_pool().get(*key).unwrap(),
+                    rules.ident_pool().get(*value).unwrap(),
+                )
+            },
+        )
+    }
+
+    /// Returns the warnings raised while compiling this rule.
+    ///
+    /// Contrary to [`Rules::warnings`], these warnings are preserved when the
+    /// rules are serialized and deserialized.
+    pub fn warnings(&self) -> &'r [RuleWarning] {
+        self.rule_info.warnings.as_slice()
+    }
+
+    /// Returns the paths of the module fields accessed by this rule's
+    /// condition, like `pe.sections[].name`.
+    ///
+    /// See [`Rules::field_usage`] for details.
+    pub fn module_fields(&self) -> impl ExactSizeIterator<Item = &'r str> {
+        let rules = self.rules;
+        self.rule_info
+            .module_fields
+            .iter()
+            .map(|ident_id| rules.ident_pool().get(*ident_id).unwrap())
+    }
+
+    /// Returns the patterns defined by this rule, in the order in which they
+    /// were declared.
     pub fn patterns(&self) -> Patterns<'a, 'r> {
         Patterns {
             ctx: self.ctx,
@@ -53,6 +103,184 @@ impl<'a, 'r> Rule<'a, 'r> {
             len: self.rule_info.patterns.len(),
         }
     }
+
+    /// Returns a best-effort reconstruction of the rule's source code.
+    ///
+    /// The result includes the rule's flags, tags, metadata, patterns and
+    /// condition. Comments and formatting in the original source code are
+    /// not preserved, and the condition is reproduced as it appears in the
+    /// original rule.
+    ///
+    /// See also [`Rules::to_source`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let rules = yara_x::compile(r#"
+    /// private rule test : foo {
+    ///   strings:
+    ///     $a = "bar" wide xor(1-3)
+    ///   condition:
+    ///     $a
+    /// }"#).unwrap();
+    ///
+    /// assert_eq!(
+    ///     rules.iter().next().unwrap().to_source(),
+    ///     r#"private rule test : foo {
+    ///   strings:
+    ///     $a = "bar" wide xor(1-3)
+    ///   condition:
+    ///     $a
+    /// }
+    /// "#
+    /// );
+    /// ```
+    pub fn to_source(&self) -> String {
+        let mut src = String::new();
+
+        if self.is_global() {
+            src.push_str("global ");
+        }
+
+        if self.is_private() {
+            src.push_str("private ");
+        }
+
+        write!(src, "rule {}", self.identifier()).unwrap();
+
+        for (i, tag) in self.tags().enumerate() {
+            let sep = if i == 0 { " : " } else { " " };
+            write!(src, "{}{}", sep, tag.identifier()).unwrap();
+        }
+
+        src.push_str(" {\n");
+
+        if !self.metadata().is_empty() {
+            src.push_str("  meta:\n");
+            for (ident, value) in self.metadata() {
+                let value = match value {
+                    MetaValue::Integer(i) => i.to_string(),
+                    MetaValue::Float(f) => format!("{:?}", f),
+                    MetaValue::Bool(b) => b.to_string(),
+                    MetaValue::String(s) => escape(s.as_bytes()),
+                    MetaValue::Bytes(b) => escape(b),
+                };
+                writeln!(src, "    {} = {}", ident, value).unwrap();
+            }
+        }
+
+        if self.patterns().len() > 0 {
+            src.push_str("  strings:\n");
+            for pattern in self.patterns() {
+                write!(
+                    src,
+                    "    {} = {}",
+                    pattern.identifier(),
+                    pattern.source()
+                )
+                .unwrap();
+                for modifier in pattern.modifiers() {
+                    write!(src, " {}", modifier).unwrap();
+                }
+                src.push('\n');
+            }
+        }
+
+        writeln!(
+            src,
+            "  condition:\n    {}\n}}",
+            self.rule_info.condition_source
+        )
+        .unwrap();
+
+        src
+    }
+
+    /// Returns the sub-expressions of this rule's condition, together with
+    /// the values they took during the scan.
+    ///
+    /// The condition as a whole is the first item, followed by its
+    /// sub-expressions in the order they appear in the source code. A
+    /// sub-expression always appears after the expressions that contain it.
+    ///
+    /// This is empty unless the rules were compiled with
+    /// [`crate::Compiler::condition_tracing`].
+    pub fn condition_trace(
+        &self,
+    ) -> impl ExactSizeIterator<Item = TracedExpr<'r>> {
+        let trace_points = &self.rules.condition_traces()
+            [self.rule_info.condition_traces.clone()];
+
+        let mut traced_exprs = trace_points
+            .iter()
+            .zip(self.rule_info.condition_traces.clone())
+            .map(|(trace_point, trace_id)| TracedExpr {
+                source: trace_point.source.as_str(),
+                span: trace_point.span.clone(),
+                counters: self
+                    .ctx
+                    .map(|ctx| ctx.condition_traces[trace_id])
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+
+        // Outer expressions go before the expressions they contain.
+        traced_exprs.sort_by(|a, b| {
+            a.span.start.cmp(&b.span.start).then(b.span.end.cmp(&a.span.end))
+        });
+
+        traced_exprs.into_iter()
+    }
+}
+
+/// A sub-expression of a rule's condition, together with the values it
+/// took during a scan.
+///
+/// See [`Rule::condition_trace`].
+pub struct TracedExpr<'r> {
+    source: &'r str,
+    span: Range<usize>,
+    counters: TraceCounters,
+}
+
+impl<'r> TracedExpr<'r> {
+    /// Returns the source code of the expression.
+    #[inline]
+    pub fn source(&self) -> &'r str {
+        self.source
+    }
+
+    /// Returns the range of the expression within the source code where
+    /// the rule was declared.
+    #[inline]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Number of times that the expression evaluated to `true`.
+    #[inline]
+    pub fn times_true(&self) -> usize {
+        self.counters.times_true
+    }
+
+    /// Number of times that the expression evaluated to `false`.
+    #[inline]
+    pub fn times_false(&self) -> usize {
+        self.counters.times_false
+    }
+
+    /// Number of times that the expression was undefined.
+    #[inline]
+    pub fn times_undefined(&self) -> usize {
+        self.counters.times_undefined
+    }
+
+    /// Returns `true` if the expression was evaluated at least once. The
+    /// operands of `and` and `or` are evaluated lazily, so an operand is
+    /// not evaluated when the result is determined by the previous ones.
+    pub fn evaluated(&self) -> bool {
+        self.times_true() + self.times_false() + self.times_undefined() > 0
+    }
 }
 
 /// A metadata value.
@@ -219,7 +447,7 @@ pub struct Patterns<'a, 'r> {
     ctx: Option<&'a ScanContext<'r>>,
     data: Option<&'a ScannedData<'a>>,
     rules: &'r Rules,
-    iterator: Iter<'a, (IdentId, PatternId)>,
+    iterator: Iter<'r, PatternInfo>,
     len: usize,
 }
 
@@ -227,13 +455,12 @@ impl<'a, 'r> Iterator for Patterns<'a, 'r> {
     type Item = Pattern<'a, 'r>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (ident_id, pattern_id) = self.iterator.next()?;
+        let pattern_info = self.iterator.next()?;
         Some(Pattern {
             ctx: self.ctx,
             rules: self.rules,
             data: self.data,
-            pattern_id: *pattern_id,
-            ident_id: *ident_id,
+            pattern_info,
         })
     }
 }
@@ -250,27 +477,173 @@ pub struct Pattern<'a, 'r> {
     ctx: Option<&'a ScanContext<'r>>,
     data: Option<&'a ScannedData<'a>>,
     rules: &'r Rules,
-    pattern_id: PatternId,
-    ident_id: IdentId,
+    pattern_info: &'r PatternInfo,
 }
 
 impl<'a, 'r> Pattern<'a, 'r> {
     /// Returns the pattern's identifier (e.g: $a, $b).
     pub fn identifier(&self) -> &'r str {
-        self.rules.ident_pool().get(self.ident_id).unwrap()
+        self.rules.ident_pool().get(self.pattern_info.ident_id).unwrap()
+    }
+
+    /// Returns the kind of pattern (text, hex or regexp).
+    pub fn kind(&self) -> PatternKind {
+        self.pattern_info.kind
+    }
+
+    /// Returns the modifiers applied to the pattern, in the order they
+    /// appear in the source code.
+    pub fn modifiers(&self) -> &'r [PatternModifier] {
+        self.pattern_info.modifiers.as_slice()
+    }
+
+    /// Returns the source code of the pattern's value, without the
+    /// identifier and the modifiers (e.g: `"foo"`, `{ 01 02 03 }` or
+    /// `/foo/i`).
+    pub fn source(&self) -> &'r str {
+        self.pattern_info.source.as_str()
+    }
+
+    /// Returns `true` if the pattern has the `private` modifier.
+    pub fn is_private(&self) -> bool {
+        self.pattern_info
+            .modifiers
+            .iter()
+            .any(|m| matches!(m, PatternModifier::Private))
     }
 
-    /// Returns the matches found for this pattern.
+    /// Returns the matches found for this pattern, sorted by offset.
     pub fn matches(&self) -> Matches<'a> {
         Matches {
             data: self.data,
             iterator: self.ctx.and_then(|ctx| {
                 ctx.pattern_matches
-                    .get(self.pattern_id)
+                    .get(self.pattern_info.pattern_id)
                     .map(|matches| matches.iter())
             }),
         }
     }
+
+    /// Returns the number of matches that were found for this pattern, but
+    /// not recorded because the pattern reached the maximum number of
+    /// matches.
+    ///
+    /// See [`crate::Scanner::max_matches_per_pattern`].
+    pub fn suppressed_matches(&self) -> usize {
+        self.ctx
+            .and_then(|ctx| {
+                ctx.suppressed_matches.get(&self.pattern_info.pattern_id)
+            })
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// The kind of a pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PatternKind {
+    /// A text pattern, like `$a = "foo"`.
+    Text,
+    /// A hex pattern, like `$a = { 01 02 03 }`.
+    Hex,
+    /// A regular expression, like `$a = /foo.*bar/`.
+    Regexp,
+}
+
+/// A modifier applied to a pattern.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PatternModifier {
+    /// The `ascii` modifier.
+    Ascii,
+    /// The `wide` modifier.
+    Wide,
+    /// The `nocase` modifier.
+    Nocase,
+    /// The `private` modifier.
+    Private,
+    /// The `fullword` modifier.
+    Fullword,
+    /// The `base64` modifier.
+    Base64 {
+        /// Custom alphabet, if any.
+        alphabet: Option<String>,
+    },
+    /// The `base64wide` modifier.
+    Base64Wide {
+        /// Custom alphabet, if any.
+        alphabet: Option<String>,
+    },
+    /// The `xor` modifier.
+    Xor {
+        /// First key in the range of XOR keys.
+        start: u8,
+        /// Last key in the range of XOR keys (inclusive).
+        end: u8,
+    },
+}
+
+impl fmt::Display for PatternModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternModifier::Ascii => write!(f, "ascii"),
+            PatternModifier::Wide => write!(f, "wide"),
+            PatternModifier::Nocase => write!(f, "nocase"),
+            PatternModifier::Private => write!(f, "private"),
+            PatternModifier::Fullword => write!(f, "fullword"),
+            PatternModifier::Base64 { alphabet: None } => write!(f, "base64"),
+            PatternModifier::Base64 { alphabet: Some(alphabet) } => {
+                write!(f, "base64({})", escape(alphabet.as_bytes()))
+            }
+            PatternModifier::Base64Wide { alphabet: None } => {
+                write!(f, "base64wide")
+            }
+            PatternModifier::Base64Wide { alphabet: Some(alphabet) } => {
+                write!(f, "base64wide({})", escape(alphabet.as_bytes()))
+            }
+            PatternModifier::Xor { start: 0, end: 255 } => write!(f, "xor"),
+            PatternModifier::Xor { start, end } if start == end => {
+                write!(f, "xor({})", start)
+            }
+            PatternModifier::Xor { start, end } => {
+                write!(f, "xor({}-{})", start, end)
+            }
+        }
+    }
+}
+
+impl From<&ast::Pattern<'_>> for PatternKind {
+    fn from(pattern: &ast::Pattern<'_>) -> Self {
+        match pattern {
+            ast::Pattern::Text(_) => PatternKind::Text,
+            ast::Pattern::Hex(_) => PatternKind::Hex,
+            ast::Pattern::Regexp(_) => PatternKind::Regexp,
+        }
+    }
+}
+
+impl From<&ast::PatternModifier<'_>> for PatternModifier {
+    fn from(modifier: &ast::PatternModifier<'_>) -> Self {
+        match modifier {
+            ast::PatternModifier::Ascii { .. } => PatternModifier::Ascii,
+            ast::PatternModifier::Wide { .. } => PatternModifier::Wide,
+            ast::PatternModifier::Nocase { .. } => PatternModifier::Nocase,
+            ast::PatternModifier::Private { .. } => PatternModifier::Private,
+            ast::PatternModifier::Fullword { .. } => PatternModifier::Fullword,
+            ast::PatternModifier::Base64 { alphabet, .. } => {
+                PatternModifier::Base64 {
+                    alphabet: alphabet.as_ref().map(|a| a.value.to_string()),
+                }
+            }
+            ast::PatternModifier::Base64Wide { alphabet, .. } => {
+                PatternModifier::Base64Wide {
+                    alphabet: alphabet.as_ref().map(|a| a.value.to_string()),
+                }
+            }
+            ast::PatternModifier::Xor { start, end, .. } => {
+                PatternModifier::Xor { start: *start, end: *end }
+            }
+        }
+    }
 }
 
 /// Iterator that returns the matches for a pattern.
@@ -313,10 +686,95 @@ impl<'a> Match<'a> {
         self.data.as_ref().get(self.inner.range.clone()).unwrap()
     }
 
+    /// Returns up to `n` bytes of the scanned data that precede the match,
+    /// and up to `n` bytes that follow it.
+    ///
+    /// Fewer than `n` bytes are returned when the match is close to the
+    /// start or the end of the scanned data.
+    pub fn context(&self, n: usize) -> (&'a [u8], &'a [u8]) {
+        let data = self.data.as_ref();
+        let range = &self.inner.range;
+        let before = &data[range.start.saturating_sub(n)..range.start];
+        let after =
+            &data[range.end..range.end.saturating_add(n).min(data.len())];
+        (before, after)
+    }
+
     /// XOR key used for decrypting the data if the pattern had the `xor`
     /// modifier, or `None` if otherwise.
     #[inline]
     pub fn xor_key(&self) -> Option<u8> {
         self.inner.xor_key
     }
+
+    /// Variant of the pattern that produced this match.
+    ///
+    /// Patterns with modifiers like `wide` or `base64` are searched in
+    /// multiple forms, this indicates the form in which the pattern was
+    /// found. Patterns with the `xor` modifier additionally have a
+    /// [`Match::xor_key`].
+    #[inline]
+    pub fn variant(&self) -> MatchVariant {
+        self.inner.variant
+    }
+}
+
+/// Form in which a pattern was found in the scanned data.
+///
+/// See [`Match::variant`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchVariant {
+    /// The pattern was found as is. For text patterns this is the `ascii`
+    /// form, which is the default one when `wide` is not used.
+    Plain,
+    /// The pattern was found in wide form, with each character encoded as
+    /// two bytes, due to the `wide` modifier.
+    Wide,
+    /// The pattern was found encoded as base64, due to the `base64`
+    /// modifier.
+    Base64,
+    /// The pattern was found encoded as base64 in wide form, due to the
+    /// `base64wide` modifier.
+    Base64Wide,
+}
+
+impl MatchVariant {
+    /// Returns the variant name, which is the name of the modifier that
+    /// produces it, except for [`MatchVariant::Plain`].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchVariant::Plain => "plain",
+            MatchVariant::Wide => "wide",
+            MatchVariant::Base64 => "base64",
+            MatchVariant::Base64Wide => "base64wide",
+        }
+    }
+}
+
+impl std::fmt::Display for MatchVariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Returns a YARA string literal, including the quotes, that represents
+/// the given bytes. Quotes, backslashes and non-printable characters are
+/// escaped.
+fn escape(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len() + 2);
+    result.push('"');
+    for b in bytes {
+        match b {
+            b'"' => result.push_str("\\\""),
+            b'\\' => result.push_str("\\\\"),
+            b'\n' => result.push_str("\\n"),
+            b'\r' => result.push_str("\\r"),
+            b'\t' => result.push_str("\\t"),
+            0x20..=0x7e => result.push(*b as char),
+            _ => write!(result, "\\x{:02x}", b).unwrap(),
+        }
+    }
+    result.push('"');
+    result
 }
@@ -4,12 +4,18 @@
 add_module!(modules, "console", console, "console.Console", Some("console"), Some(console::__main__ as MainFn));
 #[cfg(feature = "cuckoo-module")]
 add_module!(modules, "cuckoo", cuckoo, "cuckoo.Cuckoo", Some("cuckoo"), Some(cuckoo::__main__ as MainFn));
+#[cfg(feature = "dex-module")]
+add_module!(modules, "dex", dex, "dex.Dex", Some("dex"), Some(dex::__main__ as MainFn));
 #[cfg(feature = "dotnet-module")]
 add_module!(modules, "dotnet", dotnet, "dotnet.Dotnet", Some("dotnet"), Some(dotnet::__main__ as MainFn));
 #[cfg(feature = "elf-module")]
 add_module!(modules, "elf", elf, "elf.ELF", Some("elf"), Some(elf::__main__ as MainFn));
+#[cfg(feature = "gobin-module")]
+add_module!(modules, "gobin", gobin, "gobin.GoBin", Some("gobin"), Some(gobin::__main__ as MainFn));
 #[cfg(feature = "hash-module")]
 add_module!(modules, "hash", hash, "hash.Hash", Some("hash"), Some(hash::__main__ as MainFn));
+#[cfg(feature = "javaclass-module")]
+add_module!(modules, "javaclass", javaclass, "javaclass.JavaClass", Some("javaclass"), Some(javaclass::__main__ as MainFn));
 #[cfg(feature = "lnk-module")]
 add_module!(modules, "lnk", lnk, "lnk.Lnk", Some("lnk"), Some(lnk::__main__ as MainFn));
 #[cfg(feature = "macho-module")]
@@ -18,8 +24,14 @@ add_module!(modules, "macho", macho, "macho.Macho", Some("macho"), Some(macho::_
 add_module!(modules, "magic", magic, "magic.Magic", Some("magic"), Some(magic::__main__ as MainFn));
 #[cfg(feature = "math-module")]
 add_module!(modules, "math", math, "math.Math", Some("math"), Some(math::__main__ as MainFn));
+#[cfg(feature = "olevba-module")]
+add_module!(modules, "olevba", olevba, "olevba.OleVba", Some("olevba"), Some(olevba::__main__ as MainFn));
+#[cfg(feature = "pdf-module")]
+add_module!(modules, "pdf", pdf, "pdf.PDF", Some("pdf"), Some(pdf::__main__ as MainFn));
 #[cfg(feature = "pe-module")]
 add_module!(modules, "pe", pe, "pe.PE", Some("pe"), Some(pe::__main__ as MainFn));
+#[cfg(feature = "pyc-module")]
+add_module!(modules, "pyc", pyc, "pyc.Pyc", Some("pyc"), Some(pyc::__main__ as MainFn));
 #[cfg(feature = "string-module")]
 add_module!(modules, "string", string, "string.String", Some("string"), Some(string::__main__ as MainFn));
 #[cfg(feature = "test_proto2-module")]
@@ -30,4 +42,6 @@ add_module!(modules, "test_proto3", test_proto3, "test_proto3.TestProto3", Some(
 add_module!(modules, "text", text, "text.Text", Some("text"), Some(text::__main__ as MainFn));
 #[cfg(feature = "time-module")]
 add_module!(modules, "time", time, "time.Time", Some("time"), Some(time::__main__ as MainFn));
+#[cfg(feature = "wasm-module")]
+add_module!(modules, "wasm", wasm, "wasm.Wasm", Some("wasm"), Some(wasm::__main__ as MainFn));
 }
\ No newline at end of file
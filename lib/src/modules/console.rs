@@ -1,3 +1,5 @@
+use bstr::ByteSlice;
+
 use crate::modules::prelude::*;
 use crate::modules::protos::console::*;
 
@@ -9,7 +11,7 @@ fn main(_data: &[u8], _meta: Option<&[u8]>) -> Console {
 
 #[module_export(name = "log")]
 fn log_str(ctx: &mut ScanContext, string: RuntimeString) -> bool {
-    ctx.console_log(format!("{}", string.as_bstr(ctx)));
+    ctx.console_log(escape(string.as_bstr(ctx)));
     true
 }
 
@@ -22,7 +24,7 @@ fn log_msg_str(
     ctx.console_log(format!(
         "{}{}",
         message.as_bstr(ctx),
-        string.as_bstr(ctx)
+        escape(string.as_bstr(ctx))
     ));
     true
 }
@@ -67,6 +69,30 @@ fn log_msg_hex(ctx: &mut ScanContext, message: RuntimeString, i: i64) -> bool {
     true
 }
 
+/// Returns a printable representation of `s`, where control characters and
+/// bytes that are not valid UTF-8 are replaced by `\xNN` escape sequences.
+/// This allows logging strings that contain binary data, while keeping
+/// valid UTF-8 text as is.
+fn escape(s: &[u8]) -> String {
+    let mut result = String::with_capacity(s.len());
+    for chunk in ByteSlice::utf8_chunks(s) {
+        for c in chunk.valid().chars() {
+            if c.is_control() {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    result.push_str(&format!("\\x{:02x}", b));
+                }
+            } else {
+                result.push(c);
+            }
+        }
+        for b in chunk.invalid() {
+            result.push_str(&format!("\\x{:02x}", b));
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -83,7 +109,9 @@ mod tests {
                     console.log(10) and
                     console.log(6.28) and
                     console.hex(10) and 
-                    console.hex("qux: ", 255)
+                    console.hex("qux: ", 255) and
+                    console.log("\x00\xffA\n") and
+                    console.log("é\x7f\xc3")
             }
             "#,
         )
@@ -105,7 +133,9 @@ mod tests {
                 "10",
                 "6.28",
                 "0xa",
-                "qux: 0xff"
+                "qux: 0xff",
+                "\\x00\\xffA\\x0a",
+                "é\\x7f\\xc3",
             ]
         );
     }
@@ -17,6 +17,10 @@ thread_local! {
 
 #[module_main]
 fn main(_data: &[u8], meta: Option<&[u8]>) -> Cuckoo {
+    // The report from a previous scan must not be used with the current
+    // one, which may not have a report at all.
+    CUCKOO_REPORT.set(None);
+
     if let Some(meta) = meta {
         match serde_json::from_slice::<Value>(meta) {
             Ok(Value::Object(json)) => CUCKOO_REPORT.set(Some(json)),
@@ -48,3 +48,33 @@ fn cuckoo() {
         }
     });
 }
+
+#[test]
+fn report_not_reused() {
+    let rules = crate::compile(
+        r#"
+        import "cuckoo"
+        rule t {
+          condition:
+            cuckoo.sync.mutex(/ShimCacheMutex/)
+        }"#,
+    )
+    .unwrap();
+
+    let report =
+        fs::read_to_string("src/modules/cuckoo/tests/testdata/1.json")
+            .unwrap();
+
+    let mut scanner = crate::scanner::Scanner::new(&rules);
+
+    let options = crate::ScanOptions::default()
+        .set_module_metadata("cuckoo", report.as_bytes());
+
+    let scan_results = scanner.scan_with_options(&[], options).unwrap();
+    assert_eq!(scan_results.matching_rules().len(), 1);
+
+    // A scan without a report must not see the report from the previous
+    // scan.
+    let scan_results = scanner.scan(&[]).unwrap();
+    assert_eq!(scan_results.matching_rules().len(), 0);
+}
@@ -0,0 +1,122 @@
+/*! YARA module that parses DEX files.
+
+DEX (Dalvik Executable) is the format used by Android for storing compiled
+code. This module parses the DEX header and the tables of strings, types,
+prototypes, fields, methods and classes.
+
+APK files are also supported. An APK is a ZIP archive that contains the
+application's code in a DEX file named `classes.dex`, when the scanned data
+is an APK, the module locates `classes.dex` inside the archive and parses
+it as if it was scanned directly.
+
+This module is based on the [`Dalvik executable format`][1] specification.
+
+[1]: https://source.android.com/docs/core/runtime/dex-format
+ */
+
+use std::io::{Cursor, Read};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::dex::*;
+
+mod parser;
+#[cfg(test)]
+mod tests;
+
+/// Maximum size of the `classes.dex` file that will be extracted from an
+/// APK. Larger files are ignored.
+const MAX_DEX_SIZE: u64 = 64 * 1024 * 1024;
+
+#[module_main]
+fn main(data: &[u8], _meta: Option<&[u8]>) -> Dex {
+    let result = if data.starts_with(b"PK\x03\x04") {
+        extract_classes_dex(data).and_then(|dex| {
+            let mut result = parse_dex(dex.as_slice())?;
+            result.is_apk = Some(true);
+            Some(result)
+        })
+    } else {
+        parse_dex(data)
+    };
+
+    match result {
+        Some(dex) => dex,
+        None => {
+            let mut dex = Dex::new();
+            dex.is_dex = Some(false);
+            dex.is_apk = Some(false);
+            dex
+        }
+    }
+}
+
+fn parse_dex(data: &[u8]) -> Option<Dex> {
+    let mut dex: Dex = parser::Dex::parse(data).ok()?.into();
+    dex.is_apk = Some(false);
+    Some(dex)
+}
+
+/// Returns the content of `classes.dex` if `data` is a ZIP archive that
+/// contains that file.
+fn extract_classes_dex(data: &[u8]) -> Option<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).ok()?;
+    let file = archive.by_name("classes.dex").ok()?;
+
+    if file.size() > MAX_DEX_SIZE {
+        return None;
+    }
+
+    let mut dex = Vec::with_capacity(file.size() as usize);
+
+    // The size declared in the archive can't be trusted, limit the number
+    // of bytes read from the decompressor.
+    file.take(MAX_DEX_SIZE).read_to_end(&mut dex).ok()?;
+
+    Some(dex)
+}
+
+/// Returns true if the DEX file defines a class with the given descriptor
+/// (e.g. `Lcom/example/Foo;`).
+#[module_export]
+fn has_class(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let dex = ctx.module_output::<Dex>()?;
+    let name = name.as_bstr(ctx);
+
+    Some(
+        dex.classes
+            .iter()
+            .any(|class| class.name.as_ref().is_some_and(|n| name == n)),
+    )
+}
+
+/// Returns true if the DEX file references a method with the given name,
+/// regardless of the class it belongs to.
+#[module_export(name = "has_method")]
+fn has_method(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let dex = ctx.module_output::<Dex>()?;
+    let name = name.as_bstr(ctx);
+
+    Some(
+        dex.methods
+            .iter()
+            .any(|method| method.name.as_ref().is_some_and(|n| name == n)),
+    )
+}
+
+/// Returns true if the DEX file references a method with the given name
+/// that belongs to the class with the given descriptor.
+#[module_export(name = "has_method")]
+fn has_class_method(
+    ctx: &ScanContext,
+    class: RuntimeString,
+    name: RuntimeString,
+) -> Option<bool> {
+    let dex = ctx.module_output::<Dex>()?;
+    let class = class.as_bstr(ctx);
+    let name = name.as_bstr(ctx);
+
+    Some(dex.methods.iter().any(|method| {
+        method.class.as_ref().is_some_and(|c| class == c)
+            && method.name.as_ref().is_some_and(|n| name == n)
+    }))
+}
@@ -0,0 +1,600 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use nom::bytes::complete::{tag, take};
+use nom::combinator::{map, verify};
+use nom::multi::count;
+use nom::number::complete::{le_u16, le_u32, u8};
+use nom::sequence::tuple;
+use nom::IResult;
+use rustc_hash::FxHashMap;
+
+use crate::modules::protos::dex;
+use crate::modules::utils::decode_mutf8;
+
+/// Value used in some fields for indicating that there's no index.
+const NO_INDEX: u32 = 0xffffffff;
+
+/// Value of `endian_tag` in little-endian files.
+const ENDIAN_CONSTANT: u32 = 0x12345678;
+
+/// Size of a `class_def_item` structure.
+const CLASS_DEF_SIZE: usize = 32;
+
+/// Maximum length of a string in the string table, in bytes. Longer strings
+/// are truncated.
+const MAX_STRING_LEN: usize = 64 * 1024;
+
+/// Limits the amount of data in the module's output. Each string, type,
+/// prototype, field, method and class consumes one unit, plus the length of
+/// the strings it contains. As strings and type lists can be referenced
+/// multiple times, this prevents specially crafted files from producing a
+/// huge output.
+const BUDGET: usize = 16 * 1024 * 1024;
+
+/// Header of a DEX file.
+pub struct Header {
+    version: [u8; 3],
+    checksum: u32,
+    signature: [u8; 20],
+    file_size: u32,
+    header_size: u32,
+    endian_tag: u32,
+    link_size: u32,
+    link_off: u32,
+    map_off: u32,
+    string_ids_size: u32,
+    string_ids_off: u32,
+    type_ids_size: u32,
+    type_ids_off: u32,
+    proto_ids_size: u32,
+    proto_ids_off: u32,
+    field_ids_size: u32,
+    field_ids_off: u32,
+    method_ids_size: u32,
+    method_ids_off: u32,
+    class_defs_size: u32,
+    class_defs_off: u32,
+    data_size: u32,
+    data_off: u32,
+}
+
+/// A prototype, as defined by a `proto_id_item`.
+struct Prototype {
+    shorty: Option<Rc<str>>,
+    return_type: Option<Rc<str>>,
+    parameters: Rc<[Rc<str>]>,
+}
+
+/// Amount of data that can still be added to the module's output.
+struct Budget(usize);
+
+impl Budget {
+    /// Consumes `n` units from the budget, returns false if the budget is
+    /// exhausted.
+    fn consume(&mut self, n: usize) -> bool {
+        match self.0.checked_sub(n) {
+            Some(budget) => {
+                self.0 = budget;
+                true
+            }
+            None => {
+                self.0 = 0;
+                false
+            }
+        }
+    }
+}
+
+/// Returns the length of an optional string.
+fn len(s: &Option<Rc<str>>) -> usize {
+    s.as_ref().map_or(0, |s| s.len())
+}
+
+/// Converts an optional shared string into an optional [`String`].
+fn owned(s: &Option<Rc<str>>) -> Option<String> {
+    s.as_deref().map(String::from)
+}
+
+/// A DEX file.
+///
+/// Only little-endian DEX files are supported, big-endian ones are valid
+/// according to the specification but are not produced by any tool in
+/// practice.
+pub struct Dex<'a> {
+    data: &'a [u8],
+    header: Header,
+    strings: Vec<Rc<str>>,
+    types: Vec<Option<Rc<str>>>,
+    prototypes: Vec<Prototype>,
+    /// Type lists already parsed, indexed by offset. Type lists can be
+    /// shared by multiple prototypes and classes.
+    type_lists: RefCell<FxHashMap<u32, Rc<[Rc<str>]>>>,
+}
+
+impl<'a> Dex<'a> {
+    /// Parses a DEX file.
+    pub fn parse(data: &'a [u8]) -> Result<Self, nom::Err<()>> {
+        let (_, header) = Self::parse_header(data)?;
+
+        let mut dex = Self {
+            data,
+            header,
+            strings: Vec::new(),
+            types: Vec::new(),
+            prototypes: Vec::new(),
+            type_lists: RefCell::new(FxHashMap::default()),
+        };
+
+        dex.strings = dex.parse_strings();
+        dex.types = dex.parse_types();
+        dex.prototypes = dex.parse_prototypes();
+
+        Ok(dex)
+    }
+
+    /// Parses the DEX header, which has a fixed size of 0x70 bytes.
+    fn parse_header(input: &[u8]) -> IResult<&[u8], Header, ()> {
+        let (input, (_magic, version, _, checksum, signature)) = tuple((
+            tag(b"dex\n"),
+            take(3_usize),
+            tag(b"\0"),
+            le_u32,
+            take(20_usize),
+        ))(input)?;
+
+        let (
+            input,
+            (
+                file_size,
+                header_size,
+                endian_tag,
+                link_size,
+                link_off,
+                map_off,
+                string_ids_size,
+                string_ids_off,
+            ),
+        ) = tuple((
+            le_u32,
+            le_u32,
+            verify(le_u32, |tag| *tag == ENDIAN_CONSTANT),
+            le_u32,
+            le_u32,
+            le_u32,
+            le_u32,
+            le_u32,
+        ))(input)?;
+
+        let (
+            input,
+            (
+                type_ids_size,
+                type_ids_off,
+                proto_ids_size,
+                proto_ids_off,
+                field_ids_size,
+                field_ids_off,
+                method_ids_size,
+                method_ids_off,
+                class_defs_size,
+                class_defs_off,
+                data_size,
+                data_off,
+            ),
+        ) = tuple((
+            le_u32, le_u32, le_u32, le_u32, le_u32, le_u32, le_u32, le_u32,
+            le_u32, le_u32, le_u32, le_u32,
+        ))(input)?;
+
+        Ok((
+            input,
+            Header {
+                version: version.try_into().unwrap(),
+                checksum,
+                signature: signature.try_into().unwrap(),
+                file_size,
+                header_size,
+                endian_tag,
+                link_size,
+                link_off,
+                map_off,
+                string_ids_size,
+                string_ids_off,
+                type_ids_size,
+                type_ids_off,
+                proto_ids_size,
+                proto_ids_off,
+                field_ids_size,
+                field_ids_off,
+                method_ids_size,
+                method_ids_off,
+                class_defs_size,
+                class_defs_off,
+                data_size,
+                data_off,
+            },
+        ))
+    }
+
+    /// Returns a slice with the table that starts at `offset` and contains
+    /// `size` items of `item_size` bytes each.
+    ///
+    /// If the table extends beyond the end of the data, the returned slice
+    /// is truncated to the last complete item.
+    fn table(&self, offset: u32, size: u32, item_size: usize) -> &'a [u8] {
+        let start = offset as usize;
+        let len = (size as usize).saturating_mul(item_size);
+
+        let Some(data) = self.data.get(start..) else {
+            return &[];
+        };
+
+        let len = len.min(data.len() - data.len() % item_size);
+
+        &data[..len]
+    }
+
+    fn string(&self, index: u32) -> Option<Rc<str>> {
+        self.strings.get(index as usize).cloned()
+    }
+
+    fn type_name(&self, index: u32) -> Option<Rc<str>> {
+        self.types.get(index as usize)?.clone()
+    }
+
+    /// Returns the `type_list` structure located at `offset`. Each type
+    /// list is parsed only once, no matter how many times it is referenced.
+    fn type_list(&self, offset: u32) -> Rc<[Rc<str>]> {
+        self.type_lists
+            .borrow_mut()
+            .entry(offset)
+            .or_insert_with(|| self.parse_type_list(offset).into())
+            .clone()
+    }
+
+    /// Parses a `type_list` structure located at `offset`, which consists
+    /// in a `u32` with the number of items, followed by `u16` indexes in the
+    /// type table.
+    fn parse_type_list(&self, offset: u32) -> Vec<Rc<str>> {
+        if offset == 0 {
+            return Vec::new();
+        }
+
+        let Some(input) = self.data.get(offset as usize..) else {
+            return Vec::new();
+        };
+
+        let Ok((input, size)) = le_u32::<&[u8], ()>(input) else {
+            return Vec::new();
+        };
+
+        // Each item is 2 bytes long, don't try to parse more items than
+        // those that fit in the remaining data.
+        let size = (size as usize).min(input.len() / 2);
+
+        match count(le_u16::<&[u8], ()>, size)(input) {
+            Ok((_, indexes)) => indexes
+                .into_iter()
+                .filter_map(|idx| self.type_name(idx as u32))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn parse_strings(&self) -> Vec<Rc<str>> {
+        // Multiple entries in the table can point to the same string data,
+        // each string is decoded only once.
+        let mut decoded: FxHashMap<u32, Rc<str>> = FxHashMap::default();
+
+        self.table(self.header.string_ids_off, self.header.string_ids_size, 4)
+            .chunks_exact(4)
+            .map(|offset| {
+                let offset = u32::from_le_bytes(offset.try_into().unwrap());
+                decoded
+                    .entry(offset)
+                    .or_insert_with(|| {
+                        self.data
+                            .get(offset as usize..)
+                            .and_then(|data| string_data_item(data).ok())
+                            .map(|(_, s)| s)
+                            .unwrap_or_default()
+                            .into()
+                    })
+                    .clone()
+            })
+            .collect()
+    }
+
+    fn parse_types(&self) -> Vec<Option<Rc<str>>> {
+        self.table(self.header.type_ids_off, self.header.type_ids_size, 4)
+            .chunks_exact(4)
+            .map(|idx| {
+                let idx = u32::from_le_bytes(idx.try_into().unwrap());
+                self.string(idx)
+            })
+            .collect()
+    }
+
+    fn parse_prototypes(&self) -> Vec<Prototype> {
+        let table = self.table(
+            self.header.proto_ids_off,
+            self.header.proto_ids_size,
+            12,
+        );
+
+        let mut prototypes = Vec::with_capacity(table.len() / 12);
+
+        for item in table.chunks_exact(12) {
+            let Ok((_, (shorty_idx, return_type_idx, parameters_off))) =
+                tuple((le_u32::<&[u8], ()>, le_u32, le_u32))(item)
+            else {
+                break;
+            };
+
+            prototypes.push(Prototype {
+                shorty: self.string(shorty_idx),
+                return_type: self.type_name(return_type_idx),
+                parameters: self.type_list(parameters_off),
+            });
+        }
+
+        prototypes
+    }
+
+    fn fields(&self, budget: &mut Budget) -> Vec<dex::Field> {
+        let table = self.table(
+            self.header.field_ids_off,
+            self.header.field_ids_size,
+            8,
+        );
+
+        let mut fields = Vec::with_capacity(table.len() / 8);
+
+        for item in table.chunks_exact(8) {
+            let Ok((_, (class_idx, type_idx, name_idx))) =
+                tuple((le_u16::<&[u8], ()>, le_u16, le_u32))(item)
+            else {
+                break;
+            };
+
+            let class = self.type_name(class_idx as u32);
+            let type_ = self.type_name(type_idx as u32);
+            let name = self.string(name_idx);
+
+            if !budget.consume(1 + len(&class) + len(&type_) + len(&name)) {
+                break;
+            }
+
+            let mut field = dex::Field::new();
+
+            field.class = owned(&class);
+            field.type_ = owned(&type_);
+            field.name = owned(&name);
+
+            fields.push(field);
+        }
+
+        fields
+    }
+
+    fn methods(&self, budget: &mut Budget) -> Vec<dex::Method> {
+        let table = self.table(
+            self.header.method_ids_off,
+            self.header.method_ids_size,
+            8,
+        );
+
+        let mut methods = Vec::with_capacity(table.len() / 8);
+
+        for item in table.chunks_exact(8) {
+            let Ok((_, (class_idx, proto_idx, name_idx))) =
+                tuple((le_u16::<&[u8], ()>, le_u16, le_u32))(item)
+            else {
+                break;
+            };
+
+            let class = self.type_name(class_idx as u32);
+            let name = self.string(name_idx);
+
+            if !budget.consume(1 + len(&class) + len(&name)) {
+                break;
+            }
+
+            let mut method = dex::Method::new();
+
+            method.class = owned(&class);
+            method.name = owned(&name);
+
+            if let Some(proto) = self.prototypes.get(proto_idx as usize) {
+                match proto.to_proto(budget) {
+                    Some(proto) => method.prototype = Some(proto).into(),
+                    None => break,
+                }
+            }
+
+            methods.push(method);
+        }
+
+        methods
+    }
+
+    fn classes(&self, budget: &mut Budget) -> Vec<dex::Class> {
+        let table = self.table(
+            self.header.class_defs_off,
+            self.header.class_defs_size,
+            CLASS_DEF_SIZE,
+        );
+
+        let mut classes = Vec::with_capacity(table.len() / CLASS_DEF_SIZE);
+
+        for item in table.chunks_exact(CLASS_DEF_SIZE) {
+            let Ok((
+                _,
+                (
+                    class_idx,
+                    access_flags,
+                    superclass_idx,
+                    interfaces_off,
+                    source_file_idx,
+                ),
+            )) = tuple((le_u32::<&[u8], ()>, le_u32, le_u32, le_u32, le_u32))(
+                item,
+            )
+            else {
+                break;
+            };
+
+            let name = self.type_name(class_idx);
+
+            let superclass = if superclass_idx != NO_INDEX {
+                self.type_name(superclass_idx)
+            } else {
+                None
+            };
+
+            let source_file = if source_file_idx != NO_INDEX {
+                self.string(source_file_idx)
+            } else {
+                None
+            };
+
+            let interfaces = self.type_list(interfaces_off);
+
+            if !budget.consume(
+                1 + len(&name)
+                    + len(&superclass)
+                    + len(&source_file)
+                    + interfaces.iter().map(|i| 1 + i.len()).sum::<usize>(),
+            ) {
+                break;
+            }
+
+            let mut class = dex::Class::new();
+
+            class.name = owned(&name);
+            class.access_flags = Some(access_flags);
+            class.superclass = owned(&superclass);
+            class.source_file = owned(&source_file);
+            class.interfaces =
+                interfaces.iter().map(|i| i.to_string()).collect();
+
+            classes.push(class);
+        }
+
+        classes
+    }
+}
+
+impl Prototype {
+    /// Converts the prototype into [`dex::Prototype`], returns `None` if
+    /// the budget is exhausted.
+    fn to_proto(&self, budget: &mut Budget) -> Option<dex::Prototype> {
+        if !budget.consume(
+            1 + len(&self.shorty)
+                + len(&self.return_type)
+                + self.parameters.iter().map(|p| 1 + p.len()).sum::<usize>(),
+        ) {
+            return None;
+        }
+
+        let mut result = dex::Prototype::new();
+        result.shorty = owned(&self.shorty);
+        result.return_type = owned(&self.return_type);
+        result.parameters =
+            self.parameters.iter().map(|p| p.to_string()).collect();
+        Some(result)
+    }
+}
+
+/// Converts a list of shared strings into a list of [`String`], stopping
+/// when the budget is exhausted.
+fn strings<'a>(
+    items: impl Iterator<Item = &'a Rc<str>>,
+    budget: &mut Budget,
+) -> Vec<String> {
+    items
+        .take_while(|s| budget.consume(1 + s.len()))
+        .map(|s| s.to_string())
+        .collect()
+}
+
+impl From<Dex<'_>> for dex::Dex {
+    fn from(dex: Dex<'_>) -> Self {
+        let mut result = dex::Dex::new();
+        let mut header = dex::Header::new();
+
+        header.version =
+            Some(String::from_utf8_lossy(&dex.header.version).into_owned());
+        header.checksum = Some(dex.header.checksum);
+        header.signature = Some(
+            dex.header.signature.iter().map(|b| format!("{:02x}", b)).collect(),
+        );
+        header.file_size = Some(dex.header.file_size);
+        header.header_size = Some(dex.header.header_size);
+        header.endian_tag = Some(dex.header.endian_tag);
+        header.link_size = Some(dex.header.link_size);
+        header.link_offset = Some(dex.header.link_off);
+        header.map_offset = Some(dex.header.map_off);
+        header.string_ids_size = Some(dex.header.string_ids_size);
+        header.string_ids_offset = Some(dex.header.string_ids_off);
+        header.type_ids_size = Some(dex.header.type_ids_size);
+        header.type_ids_offset = Some(dex.header.type_ids_off);
+        header.proto_ids_size = Some(dex.header.proto_ids_size);
+        header.proto_ids_offset = Some(dex.header.proto_ids_off);
+        header.field_ids_size = Some(dex.header.field_ids_size);
+        header.field_ids_offset = Some(dex.header.field_ids_off);
+        header.method_ids_size = Some(dex.header.method_ids_size);
+        header.method_ids_offset = Some(dex.header.method_ids_off);
+        header.class_defs_size = Some(dex.header.class_defs_size);
+        header.class_defs_offset = Some(dex.header.class_defs_off);
+        header.data_size = Some(dex.header.data_size);
+        header.data_offset = Some(dex.header.data_off);
+
+        let mut budget = Budget(BUDGET);
+
+        result.is_dex = Some(true);
+        result.header = Some(header).into();
+        result.string_table = strings(dex.strings.iter(), &mut budget);
+        result.types = strings(dex.types.iter().flatten(), &mut budget);
+        result.prototypes = dex
+            .prototypes
+            .iter()
+            .map_while(|proto| proto.to_proto(&mut budget))
+            .collect();
+        result.fields = dex.fields(&mut budget);
+        result.methods = dex.methods(&mut budget);
+        result.classes = dex.classes(&mut budget);
+
+        result
+    }
+}
+
+/// Parses an unsigned LEB128 value of up to 32 bits.
+fn uleb128(input: &[u8]) -> IResult<&[u8], u32, ()> {
+    let mut result = 0_u32;
+    let mut input = input;
+
+    for i in 0..5 {
+        let (remainder, byte) = u8(input)?;
+        input = remainder;
+        result |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    Ok((input, result))
+}
+
+/// Parses a `string_data_item`, which consists in the length of the string
+/// in UTF-16 code units, encoded as an ULEB128, followed by the string
+/// itself encoded in MUTF-8 and terminated by a null character.
+///
+/// Strings longer than [`MAX_STRING_LEN`] bytes are truncated.
+fn string_data_item(input: &[u8]) -> IResult<&[u8], String, ()> {
+    let (input, _utf16_size) = uleb128(input)?;
+    let max_len = input.len().min(MAX_STRING_LEN);
+    let len = memchr::memchr(0, &input[..max_len]).unwrap_or(max_len);
+    map(take(len), decode_mutf8)(input)
+}
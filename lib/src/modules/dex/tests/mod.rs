@@ -0,0 +1,156 @@
+use crate::modules::tests::create_binary_from_zipped_ihex;
+use crate::tests::rule_false;
+use crate::tests::rule_true;
+use crate::tests::test_rule;
+
+#[test]
+fn test_dex_module() {
+    let dex = create_binary_from_zipped_ihex(
+        "src/modules/dex/tests/testdata/classes.dex.in.zip",
+    );
+
+    let apk = create_binary_from_zipped_ihex(
+        "src/modules/dex/tests/testdata/sample.apk.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "dex"
+        rule test {
+          condition:
+            dex.is_dex and
+            not dex.is_apk and
+            dex.header.version == "035" and
+            dex.header.checksum == 0xdc2a5ae1 and
+            dex.header.signature == "6eb2d618908fe18123b9e1a2f713e69a9166f138"
+        }
+        "#,
+        &dex
+    );
+
+    rule_true!(
+        r#"
+        import "dex"
+        rule test {
+          condition:
+            dex.is_dex and dex.is_apk and dex.header.version == "035"
+        }
+        "#,
+        &apk
+    );
+
+    rule_false!(
+        r#"
+        import "dex"
+        rule test {
+          condition:
+            dex.is_dex or dex.is_apk
+        }
+        "#,
+        b"PK\x03\x04 not really a zip"
+    );
+
+    rule_true!(
+        r#"
+        import "dex"
+        rule test {
+          condition:
+            for any s in dex.string_table : (s == "http://evil.example.com/c2")
+        }
+        "#,
+        &dex
+    );
+
+    rule_true!(
+        r#"
+        import "dex"
+        rule test {
+          condition:
+            dex.classes[0].name == "Lcom/example/Foo;" and
+            dex.classes[0].access_flags & dex.ACC_PUBLIC != 0 and
+            dex.classes[0].superclass == "Ljava/lang/Object;" and
+            dex.classes[0].interfaces[0] == "Ljava/lang/Runnable;" and
+            dex.classes[0].source_file == "Foo.java" and
+            dex.classes[1].access_flags & dex.ACC_FINAL != 0 and
+            not defined dex.classes[1].source_file
+        }
+        "#,
+        &dex
+    );
+
+    rule_true!(
+        r#"
+        import "dex"
+        rule test {
+          condition:
+            for any m in dex.methods : (
+              m.class == "Lcom/example/Foo;" and
+              m.name == "compute" and
+              m.prototype.shorty == "ILI" and
+              m.prototype.return_type == "I" and
+              m.prototype.parameters[0] == "Ljava/lang/String;" and
+              m.prototype.parameters[1] == "I"
+            )
+        }
+        "#,
+        &dex
+    );
+
+    rule_true!(
+        r#"
+        import "dex"
+        rule test {
+          condition:
+            dex.has_class("Lcom/example/Bar;") and
+            dex.has_method("send") and
+            dex.has_method("Lcom/example/Foo;", "run") and
+            not dex.has_method("Lcom/example/Bar;", "run") and
+            not dex.has_class("Lcom/example/Baz;")
+        }
+        "#,
+        &apk
+    );
+}
+
+#[test]
+fn test_dex_output_budget() {
+    // A DEX file where 1000 entries in the string table point to the same
+    // string, which is 100KB long and not terminated by a null character.
+    let num_strings = 1000_u32;
+    let string_ids_off = 0x70_u32;
+    let string_data_off = string_ids_off + 4 * num_strings;
+
+    let mut dex = Vec::new();
+
+    dex.extend_from_slice(b"dex\n035\0");
+    dex.resize(0x28, 0);
+    // endian_tag
+    dex.extend_from_slice(&0x12345678_u32.to_le_bytes());
+    dex.resize(0x38, 0);
+    dex.extend_from_slice(&num_strings.to_le_bytes());
+    dex.extend_from_slice(&string_ids_off.to_le_bytes());
+    dex.resize(0x70, 0);
+
+    for _ in 0..num_strings {
+        dex.extend_from_slice(&string_data_off.to_le_bytes());
+    }
+
+    // `utf16_size` followed by the string data.
+    dex.push(0);
+    dex.resize(dex.len() + 100 * 1024, b'A');
+
+    // Strings are truncated to 64KB, and the string table stops growing
+    // when the output budget is exhausted.
+    rule_true!(
+        r#"
+        import "dex"
+        rule test {
+          condition:
+            dex.is_dex and
+            defined dex.string_table[254] and
+            not defined dex.string_table[255]
+        }
+        "#,
+        &dex
+    );
+}
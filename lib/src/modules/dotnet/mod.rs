@@ -8,6 +8,12 @@ use crate::modules::protos::dotnet::*;
 
 pub mod parser;
 
+/// Returns false if `data` can't be a .NET file.
+pub(crate) fn sniff(data: &[u8]) -> bool {
+    // .NET files are PE files.
+    data.starts_with(b"MZ")
+}
+
 #[module_main]
 fn main(data: &[u8], _meta: Option<&[u8]>) -> Dotnet {
     match parser::Dotnet::parse(data) {
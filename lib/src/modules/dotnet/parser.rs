@@ -86,7 +86,7 @@ pub struct Dotnet<'a> {
     /// The value of a custom attribute named `GuidAttribute`.
     typelib: OnceCell<Option<&'a str>>,
     /// Modules table.
-    modules: Vec<Option<&'a str>>,
+    modules: Vec<Module<'a>>,
     /// TypeRef table.
     type_refs: Vec<TypeRef<'a>>,
     /// TypeDef table.
@@ -270,6 +270,17 @@ impl<'a> Dotnet<'a> {
         .and_then(|s| s.to_str().ok())
     }
 
+    /// Given an index into the `#GUID` stream, returns the GUID.
+    ///
+    /// Indexes in the `#GUID` stream are 1-based, a zero index means that
+    /// there's no GUID.
+    fn get_guid(&self, index: GuidIndex) -> Option<Uuid> {
+        let guid_stream = self.get_stream(self.guid_stream?)?;
+        let start = (index.0.checked_sub(1)? as usize).checked_mul(16)?;
+        Uuid::from_slice_le(guid_stream.get(start..start.checked_add(16)?)?)
+            .ok()
+    }
+
     /// Given an index into the `#Blob` stream, returns the blob's data.
     ///
     /// ECMA-335 II.24.2.4
@@ -1458,7 +1469,7 @@ impl<'a> Dotnet<'a> {
     /// ECMA-335 Section II.22.30
     fn parse_module_row(
         &self,
-    ) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Option<&'a str>> + '_ {
+    ) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Module<'a>> + '_ {
         map(
             tuple((
                 // generation (reserved, shall be zero).
@@ -1466,13 +1477,13 @@ impl<'a> Dotnet<'a> {
                 // name (index into the `#String` heap)
                 map(self.string_index(), |index| self.get_string(index)),
                 // mvid (index into the `#GUID` heap)
-                self.guid_index(),
+                map(self.guid_index(), |index| self.get_guid(index)),
                 // enc_id (index into the `#GUID` heap)
                 self.guid_index(),
                 // enc_base_id (index into the `#GUID` heap)
                 self.guid_index(),
             )),
-            |(_, name, _, _, _)| name,
+            |(_, name, mvid, _, _)| Module { name, mvid },
         )
     }
 
@@ -2221,7 +2232,6 @@ struct StreamHeader<'a> {
 struct BlobIndex(u32);
 
 /// An index into the `#GUID` stream.
-#[allow(dead_code)]
 #[derive(Clone, Copy)]
 struct GuidIndex(u32);
 
@@ -2409,6 +2419,16 @@ impl CodedIndex {
     }
 }
 
+/// Module
+///
+/// ECMA-335 Section II.22.30
+struct Module<'a> {
+    name: Option<&'a str>,
+    /// Module version ID, a GUID that changes every time the module is
+    /// compiled.
+    mvid: Option<Uuid>,
+}
+
 #[derive(Debug)]
 struct TypeRef<'a> {
     name: Option<&'a str>,
@@ -2701,11 +2721,10 @@ impl From<Dotnet<'_>> for protos::dotnet::Dotnet {
         result.typelib =
             dotnet.get_typelib().map(|typelib| typelib.to_string());
 
-        result.module_name = dotnet
-            .modules
-            .first()
-            .and_then(|first| *first)
-            .map(|first| first.to_string());
+        if let Some(module) = dotnet.modules.first() {
+            result.module_name = module.name.map(|name| name.to_string());
+            result.mvid = module.mvid.map(|mvid| mvid.to_string());
+        }
 
         result.assembly = dotnet
             .assemblies
@@ -18,6 +18,11 @@ pub mod parser;
 #[cfg(test)]
 mod tests;
 
+/// Returns false if `data` can't be an ELF file.
+pub(crate) fn sniff(data: &[u8]) -> bool {
+    data.starts_with(b"\x7fELF")
+}
+
 #[module_main]
 fn main(data: &[u8], _meta: Option<&[u8]>) -> ELF {
     match parser::ElfParser::new().parse(data) {
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::mem;
 use std::ops::Range;
 
@@ -120,6 +121,11 @@ impl ElfParser {
         self.result.dynamic_section_entries =
             Some(self.result.dynamic.len().try_into().unwrap());
 
+        self.result.needed_libraries = self.parse_needed_libraries(
+            elf,
+            segments.as_deref().unwrap_or(&[]),
+        );
+
         // If the number of sections is greater than ELF_SHN_LORESERVE the
         // header is probably corrupt, exit early.
         if ehdr.sh_entry_count >= Self::ELF_SHN_LORESERVE {
@@ -184,6 +190,13 @@ impl ElfParser {
         self.result.dynsym_entries =
             Some(self.result.dynsym.len().try_into().unwrap());
 
+        // Assign versions to the dynamic linking symbols.
+        let versions = self.parse_sym_versions(elf, sections.as_slice());
+
+        for (sym, version) in self.result.dynsym.iter_mut().zip(versions) {
+            sym.version = version;
+        }
+
         Ok(mem::take(&mut self.result))
     }
 }
@@ -202,6 +215,17 @@ impl ElfParser {
     const ELF_SHT_SYMTAB: u32 = 2;
     const ELF_SHT_NOBITS: u32 = 8;
     const ELF_SHT_DYNSYM: u32 = 11;
+    const ELF_SHT_GNU_VERDEF: u32 = 0x6ffffffd;
+    const ELF_SHT_GNU_VERNEED: u32 = 0x6ffffffe;
+    const ELF_SHT_GNU_VERSYM: u32 = 0x6fffffff;
+    const ELF_DT_NEEDED: i32 = 1;
+    const ELF_DT_STRTAB: i32 = 5;
+    // Version indexes 0 and 1 are reserved for local and global symbols,
+    // respectively. These symbols don't have a version.
+    const ELF_VER_NDX_GLOBAL: u16 = 1;
+    // The most significant bit in a version index indicates that the
+    // symbol is hidden, the remaining bits are the actual index.
+    const ELF_VERSYM_VERSION: u16 = 0x7fff;
 
     /// Parses an offset or address.
     ///
@@ -230,13 +254,7 @@ impl ElfParser {
     ) -> Option<u64> {
         match elf_type.enum_value() {
             Ok(elf::Type::ET_EXEC) => {
-                for segment in segments.iter() {
-                    if segment.virt_addr_range()?.contains(&rva) {
-                        return segment
-                            .offset
-                            .checked_add(rva - segment.virt_addr);
-                    }
-                }
+                return Self::virt_addr_to_offset(segments, rva);
             }
             _ => {
                 for section in sections.iter() {
@@ -252,6 +270,17 @@ impl ElfParser {
         None
     }
 
+    /// Converts a virtual address to a file offset by finding the segment
+    /// that contains the address.
+    fn virt_addr_to_offset(segments: &[Phdr], addr: u64) -> Option<u64> {
+        for segment in segments.iter() {
+            if segment.virt_addr_range()?.contains(&addr) {
+                return segment.offset.checked_add(addr - segment.virt_addr);
+            }
+        }
+        None
+    }
+
     fn parse_segments(&self, ehdr: &Ehdr, input: &[u8]) -> Option<Vec<Phdr>> {
         input.get(ehdr.ph_offset as usize..).and_then(|segments| {
             count(self.parse_phdr(), ehdr.ph_entry_count as usize)
@@ -543,10 +572,18 @@ impl ElfParser {
         // 71adb87ee8ee76f32f54c70584ef14f67a4bc6f55df3f847c344726405927a1e
         // have an invalid size in `Shdr` even though they have a perfectly
         // valid string table.
-        let str_table = match elf.get(str_table?.offset as usize..elf.len()) {
-            Some(data) => data,
-            None => return None,
-        };
+        Self::parse_string(elf, str_table?.offset, str_idx)
+    }
+
+    /// Given the raw data for an ELF file, the offset where some string
+    /// table starts, and the index of some string within the string table,
+    /// returns the string.
+    fn parse_string(
+        elf: &[u8],
+        str_table_offset: u64,
+        str_idx: u32,
+    ) -> Option<String> {
+        let str_table = elf.get(str_table_offset.try_into().ok()?..)?;
         // Take `str_idx` bytes from `section` and from the remaining bytes
         // read the string until the null terminator is found.
         let (_, (_, str_bytes)) =
@@ -594,6 +631,282 @@ impl ElfParser {
     }
 }
 
+impl ElfParser {
+    /// Returns the names of the libraries needed by the ELF file, as
+    /// indicated by the `DT_NEEDED` entries in the dynamic section.
+    ///
+    /// The names are read from the dynamic string table, which is located
+    /// by means of the `DT_STRTAB` entry. As this entry contains a virtual
+    /// address, it is translated to a file offset using the segments, as
+    /// the dynamic loader does. This way the names can be obtained even if
+    /// the section table is missing.
+    fn parse_needed_libraries(
+        &self,
+        elf: &[u8],
+        segments: &[Phdr],
+    ) -> Vec<String> {
+        let dyn_entries = || {
+            self.result.dynamic.iter().filter_map(|entry| {
+                Some((entry.type_?.value(), entry.val?))
+            })
+        };
+
+        let str_table_offset = dyn_entries()
+            .find(|(tag, _)| *tag == Self::ELF_DT_STRTAB)
+            .and_then(|(_, addr)| Self::virt_addr_to_offset(segments, addr));
+
+        let str_table_offset = match str_table_offset {
+            Some(offset) => offset,
+            None => return vec![],
+        };
+
+        dyn_entries()
+            .filter(|(tag, _)| *tag == Self::ELF_DT_NEEDED)
+            .filter_map(|(_, str_idx)| {
+                Self::parse_string(
+                    elf,
+                    str_table_offset,
+                    str_idx.try_into().ok()?,
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the version of each symbol in the dynamic symbol table, in
+    /// the same order as the symbols.
+    ///
+    /// The version index of each symbol is obtained from the `.gnu.version`
+    /// section, and the version names are obtained from the version
+    /// requirements (`.gnu.version_r`) and version definitions
+    /// (`.gnu.version_d`) sections. Symbols without a version, or whose
+    /// version can't be determined, get `None`.
+    fn parse_sym_versions(
+        &self,
+        elf: &[u8],
+        sections: &[Shdr],
+    ) -> Vec<Option<String>> {
+        let versym = match sections
+            .iter()
+            .find(|s| s.type_ == Self::ELF_SHT_GNU_VERSYM)
+            .and_then(|s| elf.get(s.offset_range()?))
+        {
+            Some(versym) => versym,
+            None => return vec![],
+        };
+
+        let indexes: Vec<u16> = many0(u16(self.endianness))
+            .parse(versym)
+            .map(|(_, indexes)| indexes)
+            .unwrap_or_else(|_: Err<nom::error::Error<&[u8]>>| vec![]);
+
+        let mut names = HashMap::new();
+
+        for section in sections.iter() {
+            let str_table = sections.get(section.link as usize);
+            let data = section.offset_range().and_then(|r| elf.get(r));
+            match (section.type_, data) {
+                (Self::ELF_SHT_GNU_VERNEED, Some(data)) => {
+                    for (index, name) in self.parse_verneed(data, section.info)
+                    {
+                        if let Some(name) =
+                            Self::parse_name(elf, str_table, name)
+                        {
+                            names.insert(index, name);
+                        }
+                    }
+                }
+                (Self::ELF_SHT_GNU_VERDEF, Some(data)) => {
+                    for (index, name) in self.parse_verdef(data, section.info)
+                    {
+                        if let Some(name) =
+                            Self::parse_name(elf, str_table, name)
+                        {
+                            names.insert(index, name);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        indexes
+            .into_iter()
+            .map(|index| {
+                let index = index & Self::ELF_VERSYM_VERSION;
+                if index <= Self::ELF_VER_NDX_GLOBAL {
+                    None
+                } else {
+                    names.get(&index).cloned()
+                }
+            })
+            .collect()
+    }
+
+    /// Parses the content of a version requirements section
+    /// (`.gnu.version_r`) that has `count` entries, and returns a vector
+    /// of tuples (version index, name index). The name index is the index
+    /// within the string table associated to the section.
+    ///
+    /// Each entry is followed by a list of auxiliary entries, one for each
+    /// version required from some library. Both entries and auxiliary
+    /// entries are chained by means of offsets relative to the current one.
+    fn parse_verneed(&self, data: &[u8], count: u32) -> Vec<(u16, u32)> {
+        let mut result = Vec::new();
+        let mut entry_offset = 0_usize;
+
+        for _ in 0..count {
+            let (aux_count, aux_offset, next_offset) = match data
+                .get(entry_offset..)
+                .and_then(|entry| self.parse_verneed_entry()(entry).ok())
+            {
+                Some((_, entry)) => entry,
+                None => break,
+            };
+
+            let mut aux_entry_offset =
+                entry_offset.saturating_add(aux_offset as usize);
+
+            for _ in 0..aux_count {
+                let (version_index, name, aux_next) = match data
+                    .get(aux_entry_offset..)
+                    .and_then(|aux| self.parse_vernaux_entry()(aux).ok())
+                {
+                    Some((_, aux)) => aux,
+                    None => break,
+                };
+
+                result.push((version_index & Self::ELF_VERSYM_VERSION, name));
+
+                if aux_next == 0 {
+                    break;
+                }
+
+                aux_entry_offset =
+                    aux_entry_offset.saturating_add(aux_next as usize);
+            }
+
+            if next_offset == 0 {
+                break;
+            }
+
+            entry_offset = entry_offset.saturating_add(next_offset as usize);
+        }
+
+        result
+    }
+
+    /// Parses the content of a version definitions section
+    /// (`.gnu.version_d`) that has `count` entries, and returns a vector
+    /// of tuples (version index, name index). The name index is the index
+    /// within the string table associated to the section.
+    fn parse_verdef(&self, data: &[u8], count: u32) -> Vec<(u16, u32)> {
+        let mut result = Vec::new();
+        let mut entry_offset = 0_usize;
+
+        for _ in 0..count {
+            let (version_index, aux_offset, next_offset) = match data
+                .get(entry_offset..)
+                .and_then(|entry| self.parse_verdef_entry()(entry).ok())
+            {
+                Some((_, entry)) => entry,
+                None => break,
+            };
+
+            // The first auxiliary entry contains the name of the version
+            // being defined, the remaining ones contain the names of its
+            // predecessors, which are not relevant here.
+            let name = data
+                .get(entry_offset.saturating_add(aux_offset as usize)..)
+                .and_then(|aux| self.parse_verdaux_entry()(aux).ok())
+                .map(|(_, name)| name);
+
+            if let Some(name) = name {
+                result.push((version_index & Self::ELF_VERSYM_VERSION, name));
+            }
+
+            if next_offset == 0 {
+                break;
+            }
+
+            entry_offset = entry_offset.saturating_add(next_offset as usize);
+        }
+
+        result
+    }
+
+    /// Parses a `Verneed` structure, returning a tuple with the number of
+    /// auxiliary entries, the offset of the first auxiliary entry and the
+    /// offset of the next `Verneed` structure.
+    fn parse_verneed_entry(
+        &self,
+    ) -> impl FnMut(&[u8]) -> IResult<&[u8], (u16, u32, u32)> + '_ {
+        move |input: &[u8]| {
+            map(
+                tuple((
+                    u16(self.endianness), // vn_version
+                    u16(self.endianness), // vn_cnt
+                    u32(self.endianness), // vn_file
+                    u32(self.endianness), // vn_aux
+                    u32(self.endianness), // vn_next
+                )),
+                |(_, cnt, _, aux, next)| (cnt, aux, next),
+            )
+            .parse(input)
+        }
+    }
+
+    /// Parses a `Vernaux` structure, returning a tuple with the version
+    /// index, the name index and the offset of the next `Vernaux`
+    /// structure.
+    fn parse_vernaux_entry(
+        &self,
+    ) -> impl FnMut(&[u8]) -> IResult<&[u8], (u16, u32, u32)> + '_ {
+        move |input: &[u8]| {
+            map(
+                tuple((
+                    u32(self.endianness), // vna_hash
+                    u16(self.endianness), // vna_flags
+                    u16(self.endianness), // vna_other
+                    u32(self.endianness), // vna_name
+                    u32(self.endianness), // vna_next
+                )),
+                |(_, _, other, name, next)| (other, name, next),
+            )
+            .parse(input)
+        }
+    }
+
+    /// Parses a `Verdaux` structure, returning the name index.
+    fn parse_verdaux_entry(
+        &self,
+    ) -> impl FnMut(&[u8]) -> IResult<&[u8], u32> + '_ {
+        move |input: &[u8]| u32(self.endianness)(input) // vda_name
+    }
+
+    /// Parses a `Verdef` structure, returning a tuple with the version
+    /// index, the offset of the first auxiliary entry and the offset of
+    /// the next `Verdef` structure.
+    fn parse_verdef_entry(
+        &self,
+    ) -> impl FnMut(&[u8]) -> IResult<&[u8], (u16, u32, u32)> + '_ {
+        move |input: &[u8]| {
+            map(
+                tuple((
+                    u16(self.endianness), // vd_version
+                    u16(self.endianness), // vd_flags
+                    u16(self.endianness), // vd_ndx
+                    u16(self.endianness), // vd_cnt
+                    u32(self.endianness), // vd_hash
+                    u32(self.endianness), // vd_aux
+                    u32(self.endianness), // vd_next
+                )),
+                |(_, _, ndx, _, _, aux, next)| (ndx, aux, next),
+            )
+            .parse(input)
+        }
+    }
+}
+
 /// ELF executable header.
 #[derive(Default)]
 struct Ehdr {
@@ -39,3 +39,44 @@ fn telfhash() {
         &elf
     );
 }
+
+#[test]
+fn needed_libraries() {
+    let elf = create_binary_from_zipped_ihex(
+        "src/modules/elf/tests/testdata/8bfe885838b4d1fba194b761ca900a0425aa892e4b358bf5a9bf4304e571df1b.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "elf"
+        rule test {
+          condition:
+            elf.needed_libraries[0] == "libdyn.so" and
+            elf.needed_libraries[1] == "libc.so.6" and
+            not defined elf.needed_libraries[2]
+        }
+        "#,
+        &elf
+    );
+}
+
+#[test]
+fn symbol_versions() {
+    let elf = create_binary_from_zipped_ihex(
+        "src/modules/elf/tests/testdata/8bfe885838b4d1fba194b761ca900a0425aa892e4b358bf5a9bf4304e571df1b.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "elf"
+        rule test {
+          condition:
+            elf.dynsym[17].name == "__cxa_finalize" and
+            elf.dynsym[17].version == "GLIBC_2.2.5" and
+            not defined elf.dynsym[2].version and
+            for 1 sym in elf.dynsym : (defined sym.version)
+        }
+        "#,
+        &elf
+    );
+}
@@ -0,0 +1,210 @@
+/*! Parser for the build information embedded in Go binaries.
+
+Go binaries contain a blob that starts with the magic `\xff Go buildinf:`,
+which holds the version of the Go toolchain that produced the binary, and
+a text describing the main module, its dependencies and the build
+settings, as in the output of `go version -m`.
+
+Since Go 1.18 the strings are stored inline, right after the 32-bytes
+header. Older versions store pointers to the strings instead, which are
+virtual addresses that can't be resolved without parsing the binary, those
+are not supported.
+
+See: https://github.com/golang/go/blob/master/src/debug/buildinfo/buildinfo.go
+ */
+
+use memchr::memmem;
+
+use crate::modules::protos::gobin::{BuildSetting, Module, Replacement};
+
+/// Magic that indicates the start of the build information.
+const BUILDINFO_MAGIC: &[u8] = b"\xff Go buildinf:";
+
+/// Size of the build information header.
+const BUILDINFO_HEADER_SIZE: usize = 32;
+
+/// Flag that indicates that the strings are stored inline.
+const FLAG_INLINE: u8 = 0x2;
+
+/// Build information extracted from a Go binary.
+#[derive(Default)]
+pub struct BuildInfo {
+    pub version: Option<String>,
+    pub path: Option<String>,
+    pub main: Option<Module>,
+    pub deps: Vec<Module>,
+    pub settings: Vec<BuildSetting>,
+}
+
+impl BuildInfo {
+    /// Searches for the build information in `data` and parses it.
+    ///
+    /// Returns [`None`] if the build information was not found. If it was
+    /// found, but it uses the format prior to Go 1.18, the result is an
+    /// empty [`BuildInfo`].
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        // The build information is aligned to 16 bytes, and the byte that
+        // follows the magic is the pointer size. This prevents false
+        // positives with occurrences of the magic that are not the actual
+        // build information, like the string constant in binaries that
+        // use the `debug/buildinfo` package.
+        let start = memmem::find_iter(data, BUILDINFO_MAGIC).find(|start| {
+            start % 16 == 0
+                && data.len() >= start + BUILDINFO_HEADER_SIZE
+                && matches!(data[start + 14], 4 | 8)
+        })?;
+
+        let mut info = BuildInfo::default();
+
+        if data[start + 15] & FLAG_INLINE == 0 {
+            return Some(info);
+        }
+
+        let input = &data[start + BUILDINFO_HEADER_SIZE..];
+
+        let Some((version, input)) = varint_string(input) else {
+            return Some(info);
+        };
+
+        if !version.is_empty() {
+            info.version = Some(String::from_utf8_lossy(version).into_owned());
+        }
+
+        if let Some((modinfo, _)) = varint_string(input) {
+            info.parse_modinfo(modinfo);
+        }
+
+        Some(info)
+    }
+
+    /// Parses the module information, which is a text where each line
+    /// starts with a keyword (`path`, `mod`, `dep`, `=>` or `build`),
+    /// followed by tab-separated fields.
+    fn parse_modinfo(&mut self, modinfo: &[u8]) {
+        // The module information is surrounded by two 16-bytes sentinels.
+        let modinfo = if modinfo.len() >= 33 && modinfo[modinfo.len() - 17] == b'\n' {
+            &modinfo[16..modinfo.len() - 16]
+        } else {
+            modinfo
+        };
+
+        let modinfo = String::from_utf8_lossy(modinfo);
+
+        for line in modinfo.lines() {
+            let mut fields = line.split('\t');
+            match fields.next() {
+                Some("path") => {
+                    self.path = fields.next().map(String::from);
+                }
+                Some("mod") => {
+                    self.main = Some(module(fields));
+                }
+                Some("dep") => {
+                    self.deps.push(module(fields));
+                }
+                Some("=>") => {
+                    // The replacement applies to the module in the
+                    // previous line, which is the last dependency or the
+                    // main module if there are no dependencies yet.
+                    let replaced = match self.deps.last_mut() {
+                        Some(dep) => Some(dep),
+                        None => self.main.as_mut(),
+                    };
+                    if let Some(replaced) = replaced {
+                        let mut replacement = Replacement::new();
+                        replacement.path = field(&mut fields);
+                        replacement.version = field(&mut fields);
+                        replacement.sum = field(&mut fields);
+                        replaced.replace = Some(replacement).into();
+                    }
+                }
+                Some("build") => {
+                    if let Some((key, value)) =
+                        fields.next().and_then(build_setting)
+                    {
+                        let mut setting = BuildSetting::new();
+                        setting.key = Some(key);
+                        setting.value = Some(value);
+                        self.settings.push(setting);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn module<'a>(mut fields: impl Iterator<Item = &'a str>) -> Module {
+    let mut module = Module::new();
+    module.path = field(&mut fields);
+    module.version = field(&mut fields);
+    module.sum = field(&mut fields);
+    module
+}
+
+/// Returns the next field in a line, or [`None`] if the field is missing
+/// or empty. The main module, for instance, doesn't have a checksum.
+fn field<'a>(fields: &mut impl Iterator<Item = &'a str>) -> Option<String> {
+    fields.next().filter(|f| !f.is_empty()).map(String::from)
+}
+
+/// Parses a build setting with the form `key=value`. Keys and values that
+/// contain special characters are quoted.
+fn build_setting(s: &str) -> Option<(String, String)> {
+    let (key, value) = if s.starts_with('"') {
+        let (key, len) = unquote(s)?;
+        (key, s[len..].strip_prefix('=')?)
+    } else {
+        let (key, value) = s.split_once('=')?;
+        (key.to_owned(), value)
+    };
+
+    let value = if value.starts_with('"') {
+        unquote(value)?.0
+    } else {
+        value.to_owned()
+    };
+
+    Some((key, value))
+}
+
+/// Unquotes a string quoted by Go's `strconv.Quote`. Returns the unquoted
+/// string and the number of bytes consumed from `s`, including the quotes.
+fn unquote(s: &str) -> Option<(String, usize)> {
+    let mut result = String::new();
+    let mut chars = s.char_indices().skip(1);
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((result, i + 1)),
+            '\\' => match chars.next()?.1 {
+                'n' => result.push('\n'),
+                't' => result.push('\t'),
+                'r' => result.push('\r'),
+                c => result.push(c),
+            },
+            c => result.push(c),
+        }
+    }
+
+    None
+}
+
+/// Parses a string prefixed by its length, which is encoded as an unsigned
+/// varint.
+fn varint_string(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let mut len = 0_u64;
+    let mut input = input;
+
+    for i in 0..10 {
+        let (&byte, remainder) = input.split_first()?;
+        input = remainder;
+        len |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            let len = usize::try_from(len).ok()?;
+            return input.get(..len).map(|s| (s, &input[len..]));
+        }
+    }
+
+    None
+}
@@ -0,0 +1,82 @@
+/*! YARA module that extracts metadata from Go binaries.
+
+Binaries produced by the Go compiler contain information that is useful
+for identifying them, even when they are stripped. This module extracts
+the build information (Go version, main module, dependencies and build
+settings), and the names of the functions listed in the pclntab.
+
+PE, ELF and Mach-O files are supported. The module doesn't rely on the
+section table of these formats, the build information and the pclntab are
+located by their magic numbers instead.
+ */
+
+use crate::modules::prelude::*;
+use crate::modules::protos::gobin::*;
+
+mod buildinfo;
+mod pclntab;
+#[cfg(test)]
+mod tests;
+
+#[module_main]
+fn main(data: &[u8], _meta: Option<&[u8]>) -> GoBin {
+    let mut gobin = GoBin::new();
+
+    if !is_executable(data) {
+        gobin.is_go = Some(false);
+        return gobin;
+    }
+
+    let buildinfo = buildinfo::BuildInfo::parse(data);
+    let pclntab = pclntab::Pclntab::parse(data);
+
+    gobin.is_go = Some(buildinfo.is_some() || pclntab.is_some());
+
+    if let Some(buildinfo) = buildinfo {
+        gobin.version = buildinfo.version;
+        gobin.path = buildinfo.path;
+        gobin.main = buildinfo.main.into();
+        gobin.deps = buildinfo.deps;
+        gobin.settings = buildinfo.settings;
+    }
+
+    if let Some(pclntab) = pclntab {
+        gobin.pclntab_version = Some(pclntab.version.into());
+        gobin.functions = pclntab.functions;
+    }
+
+    gobin
+}
+
+/// Returns true if the binary depends on a module with the given path.
+#[module_export]
+fn has_dependency(ctx: &ScanContext, path: RuntimeString) -> Option<bool> {
+    let gobin = ctx.module_output::<GoBin>()?;
+    let path = path.as_bstr(ctx);
+
+    Some(
+        gobin
+            .deps
+            .iter()
+            .any(|dep| dep.path.as_ref().is_some_and(|p| path == p)),
+    )
+}
+
+/// Returns true if the pclntab contains a function with the given name.
+#[module_export]
+fn has_function(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let gobin = ctx.module_output::<GoBin>()?;
+    let name = name.as_bstr(ctx);
+
+    Some(gobin.functions.iter().any(|f| name == f))
+}
+
+/// Returns true if `data` is a PE, ELF or Mach-O file.
+fn is_executable(data: &[u8]) -> bool {
+    data.starts_with(b"MZ")
+        || data.starts_with(b"\x7fELF")
+        || data.starts_with(&[0xfe, 0xed, 0xfa, 0xce])
+        || data.starts_with(&[0xfe, 0xed, 0xfa, 0xcf])
+        || data.starts_with(&[0xce, 0xfa, 0xed, 0xfe])
+        || data.starts_with(&[0xcf, 0xfa, 0xed, 0xfe])
+}
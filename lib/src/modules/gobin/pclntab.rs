@@ -0,0 +1,193 @@
+/*! Parser for the pclntab of Go binaries.
+
+The pclntab (program counter line table) is a structure used by the Go
+runtime for mapping program counters to function names, source files and
+line numbers. It's present even in stripped binaries, which makes it a
+reliable source for the names of the functions in the binary.
+
+The format of the pclntab has changed a few times. Each version starts with
+a different magic: 0xfffffffb (Go 1.2), 0xfffffffa (Go 1.16), 0xfffffff0
+(Go 1.18) and 0xfffffff1 (Go 1.20).
+
+See: https://github.com/golang/go/blob/master/src/debug/gosym/pclntab.go
+ */
+
+use memchr::memmem;
+
+use crate::modules::protos::gobin::PclntabVersion;
+
+/// The pclntab of a Go binary.
+pub struct Pclntab {
+    pub version: PclntabVersion,
+    pub functions: Vec<String>,
+}
+
+impl Pclntab {
+    /// Searches for the pclntab in `data` and parses it.
+    ///
+    /// The pclntab is located by looking for the magic of each known
+    /// version, both in little-endian and big-endian. Candidates that are
+    /// not followed by a valid header are ignored.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        for (magic, version) in [
+            (0xfffffff1_u32, PclntabVersion::GO_1_20),
+            (0xfffffff0_u32, PclntabVersion::GO_1_18),
+            (0xfffffffa_u32, PclntabVersion::GO_1_16),
+            (0xfffffffb_u32, PclntabVersion::GO_1_2),
+        ] {
+            for big_endian in [false, true] {
+                let magic = if big_endian {
+                    magic.to_be_bytes()
+                } else {
+                    magic.to_le_bytes()
+                };
+
+                // The magic is followed by two padding bytes that must be
+                // zero.
+                let pattern = [magic[0], magic[1], magic[2], magic[3], 0, 0];
+
+                for start in memmem::find_iter(data, &pattern) {
+                    let functions = Reader::new(&data[start..], big_endian)
+                        .and_then(|reader| reader.functions(version));
+                    if let Some(functions) = functions {
+                        return Some(Self { version, functions });
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Reads the fields of a pclntab.
+struct Reader<'a> {
+    tab: &'a [u8],
+    ptr_size: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a reader for the pclntab at the start of `tab`. Returns
+    /// [`None`] if the header is not valid.
+    fn new(tab: &'a [u8], big_endian: bool) -> Option<Self> {
+        // The header contains the minimum instruction size, also known as
+        // the "PC quantum", and the size of a pointer.
+        let (min_lc, ptr_size) = (*tab.get(6)?, *tab.get(7)?);
+
+        if !matches!(min_lc, 1 | 2 | 4) || !matches!(ptr_size, 4 | 8) {
+            return None;
+        }
+
+        Some(Self { tab, ptr_size: ptr_size as usize, big_endian })
+    }
+
+    /// Returns the names of the functions in the pclntab, or [`None`] if
+    /// the pclntab is not valid.
+    fn functions(&self, version: PclntabVersion) -> Option<Vec<String>> {
+        let ptr = self.ptr_size;
+        let nfunc = usize::try_from(self.uintptr(8)?).ok()?;
+
+        // The function table is a sequence of pairs (entry, funcoff) where
+        // `funcoff` is the offset of the `_func` structure that describes
+        // the function. `funcnametab` is the offset where function names
+        // are stored, and `funcdata` is the offset `funcoff` is relative
+        // to. The size of each pair, and the offset within the pair where
+        // `funcoff` is stored, also depend on the version.
+        let (functab, funcnametab, funcdata, entry_size, entry_funcoff) =
+            match version {
+                PclntabVersion::GO_1_2 => (8 + ptr, 0, 0, 2 * ptr, ptr),
+                PclntabVersion::GO_1_16 => {
+                    let pcln = self.offset(8 + 6 * ptr)?;
+                    (pcln, self.offset(8 + 2 * ptr)?, pcln, 2 * ptr, ptr)
+                }
+                PclntabVersion::GO_1_18 | PclntabVersion::GO_1_20 => {
+                    let pcln = self.offset(8 + 7 * ptr)?;
+                    (pcln, self.offset(8 + 3 * ptr)?, pcln, 8, 4)
+                }
+            };
+
+        // The function table must fit in the data, which also puts an
+        // upper bound to the number of functions.
+        if nfunc == 0
+            || nfunc.checked_mul(entry_size)?.checked_add(functab)?
+                > self.tab.len()
+        {
+            return None;
+        }
+
+        // In `_func` the function's entry point (a pointer in Go 1.2 and
+        // 1.16, and a 32-bits offset since Go 1.18) is followed by the
+        // offset of the function's name within `funcnametab`.
+        let name_off = match version {
+            PclntabVersion::GO_1_2 | PclntabVersion::GO_1_16 => ptr,
+            PclntabVersion::GO_1_18 | PclntabVersion::GO_1_20 => 4,
+        };
+
+        let mut functions = Vec::with_capacity(nfunc);
+
+        for i in 0..nfunc {
+            let entry = functab + i * entry_size;
+            let funcoff = if entry_size == 8 {
+                self.u32(entry + entry_funcoff)? as usize
+            } else {
+                self.offset(entry + entry_funcoff)?
+            };
+            let name = funcdata
+                .checked_add(funcoff)
+                .and_then(|func| self.u32(func.checked_add(name_off)?))
+                .and_then(|nameoff| {
+                    self.cstr(funcnametab.checked_add(nameoff as usize)?)
+                });
+            if let Some(name) = name {
+                functions.push(name);
+            }
+        }
+
+        if functions.is_empty() {
+            return None;
+        }
+
+        Some(functions)
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.tab.get(offset..offset.checked_add(4)?)?;
+        let bytes = bytes.try_into().unwrap();
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn uintptr(&self, offset: usize) -> Option<u64> {
+        if self.ptr_size == 4 {
+            return self.u32(offset).map(u64::from);
+        }
+        let bytes = self.tab.get(offset..offset.checked_add(8)?)?;
+        let bytes = bytes.try_into().unwrap();
+        Some(if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    }
+
+    /// Reads a pointer-sized offset within the pclntab, returns [`None`]
+    /// if the offset is beyond the end of the pclntab.
+    fn offset(&self, offset: usize) -> Option<usize> {
+        let offset = usize::try_from(self.uintptr(offset)?).ok()?;
+        (offset < self.tab.len()).then_some(offset)
+    }
+
+    /// Reads a null-terminated string. Empty strings are not valid
+    /// function names, so [`None`] is returned for them.
+    fn cstr(&self, offset: usize) -> Option<String> {
+        let s = self.tab.get(offset..)?;
+        let len = memchr::memchr(0, s)?;
+        if len == 0 {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&s[..len]).into_owned())
+    }
+}
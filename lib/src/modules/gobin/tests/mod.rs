@@ -0,0 +1,106 @@
+use crate::modules::tests::create_binary_from_zipped_ihex;
+use crate::tests::rule_false;
+use crate::tests::rule_true;
+use crate::tests::test_rule;
+
+#[test]
+fn test_gobin_module() {
+    let elf = create_binary_from_zipped_ihex(
+        "src/modules/gobin/tests/testdata/miner.elf.in.zip",
+    );
+
+    let exe = create_binary_from_zipped_ihex(
+        "src/modules/gobin/tests/testdata/old.exe.in.zip",
+    );
+
+    rule_false!(
+        r#"
+        import "gobin"
+        rule test {
+          condition:
+            gobin.is_go
+        }
+        "#,
+        b"\xff Go buildinf:\x08\x02"
+    );
+
+    rule_true!(
+        r#"
+        import "gobin"
+        rule test {
+          condition:
+            gobin.is_go and
+            gobin.version == "go1.21.5" and
+            gobin.path == "github.com/evil/miner/cmd/miner" and
+            gobin.main.path == "github.com/evil/miner" and
+            gobin.main.version == "(devel)" and
+            not defined gobin.main.replace.path
+        }
+        "#,
+        &elf
+    );
+
+    rule_true!(
+        r#"
+        import "gobin"
+        rule test {
+          condition:
+            gobin.deps[0].path == "github.com/gorilla/websocket" and
+            gobin.deps[0].version == "v1.5.0" and
+            gobin.deps[0].sum startswith "h1:" and
+            gobin.deps[1].path == "golang.org/x/crypto" and
+            gobin.deps[1].replace.path == "github.com/evil/crypto" and
+            gobin.deps[1].replace.version == "v0.0.1"
+        }
+        "#,
+        &elf
+    );
+
+    rule_true!(
+        r#"
+        import "gobin"
+        rule test {
+          condition:
+            for any s in gobin.settings : (
+              s.key == "-ldflags" and
+              s.value == "-s -w -X main.pool=pool.example.com:3333"
+            ) and
+            for any s in gobin.settings : (
+              s.key == "GOOS" and s.value == "linux"
+            )
+        }
+        "#,
+        &elf
+    );
+
+    rule_true!(
+        r#"
+        import "gobin"
+        rule test {
+          condition:
+            gobin.pclntab_version == gobin.PclntabVersion.GO_1_20 and
+            gobin.functions[1] == "main.main" and
+            gobin.has_function("main.mine") and
+            not gobin.has_function("main.foo") and
+            gobin.has_dependency("golang.org/x/crypto") and
+            not gobin.has_dependency("golang.org/x/net")
+        }
+        "#,
+        &elf
+    );
+
+    rule_true!(
+        r#"
+        import "gobin"
+        rule test {
+          condition:
+            gobin.is_go and
+            not defined gobin.version and
+            not defined gobin.path and
+            gobin.pclntab_version == gobin.PclntabVersion.GO_1_16 and
+            gobin.has_function("github.com/gorilla/websocket.(*Conn).WriteMessage")
+        }
+        "#,
+        &exe
+    );
+}
@@ -1,42 +1,82 @@
 use std::cell::RefCell;
+use std::thread::LocalKey;
 
 use md5::Md5;
 use rustc_hash::FxHashMap;
 use sha1::Sha1;
 use sha2::{Digest, Sha256};
+use tlsh_fixed as tlsh;
 
 use crate::modules::prelude::*;
 use crate::modules::protos::hash::*;
 
+mod ssdeep;
 #[cfg(test)]
 mod tests;
 
-thread_local!(
-    static SHA256_CACHE: RefCell<FxHashMap<(i64, i64), String>> =
-        RefCell::new(FxHashMap::default());
-
-    static SHA1_CACHE: RefCell<FxHashMap<(i64, i64), String>> =
-        RefCell::new(FxHashMap::default());
-
-    static MD5_CACHE: RefCell<FxHashMap<(i64, i64), String>> =
-        RefCell::new(FxHashMap::default());
+/// Cache for the values computed by some hash function during a scan.
+///
+/// Keys are the `(offset, size)` arguments passed to the function. The
+/// values are valid only for the scan identified by `scan_id`, and they are
+/// discarded as soon as the cache is used in a different scan.
+struct Cache<V> {
+    scan_id: u64,
+    values: FxHashMap<(i64, i64), V>,
+}
 
-    static CRC32_CACHE: RefCell<FxHashMap<(i64, i64), i64>> =
-        RefCell::new(FxHashMap::default());
+impl<V> Cache<V> {
+    fn new() -> Self {
+        Self { scan_id: 0, values: FxHashMap::default() }
+    }
+}
 
-    static CHECKSUM32_CACHE: RefCell<FxHashMap<(i64, i64), i64>> =
-        RefCell::new(FxHashMap::default());
+thread_local!(
+    static SHA256_CACHE: RefCell<Cache<String>> = RefCell::new(Cache::new());
+    static SHA1_CACHE: RefCell<Cache<String>> = RefCell::new(Cache::new());
+    static MD5_CACHE: RefCell<Cache<String>> = RefCell::new(Cache::new());
+    static CRC32_CACHE: RefCell<Cache<i64>> = RefCell::new(Cache::new());
+    static CHECKSUM32_CACHE: RefCell<Cache<i64>> = RefCell::new(Cache::new());
+    static SSDEEP_CACHE: RefCell<Cache<String>> = RefCell::new(Cache::new());
+    static TLSH_CACHE: RefCell<Cache<Option<String>>> =
+        RefCell::new(Cache::new());
 );
 
+/// Applies `f` to the range of the scanned data that starts at `offset` and
+/// has `size` bytes, and returns the result.
+///
+/// The result is stored in `cache`, if the same function is invoked again
+/// for the same range during the current scan, the cached value is returned
+/// without calling `f`. Returns `None` if the range is not within the
+/// scanned data.
+fn memoize<V: Clone>(
+    cache: &'static LocalKey<RefCell<Cache<V>>>,
+    ctx: &ScanContext,
+    offset: i64,
+    size: i64,
+    f: impl FnOnce(&[u8]) -> V,
+) -> Option<V> {
+    cache.with_borrow_mut(|cache| {
+        if cache.scan_id != ctx.scan_id {
+            cache.scan_id = ctx.scan_id;
+            cache.values.clear();
+        }
+
+        if let Some(value) = cache.values.get(&(offset, size)) {
+            return Some(value.clone());
+        }
+
+        let start = offset.try_into().ok()?;
+        let end = offset.checked_add(size)?.try_into().ok()?;
+        let value = f(ctx.scanned_data().get(start..end)?);
+
+        cache.values.insert((offset, size), value.clone());
+
+        Some(value)
+    })
+}
+
 #[module_main]
 fn main(_data: &[u8], _meta: Option<&[u8]>) -> Hash {
-    // With every scanned file the cache must be cleared.
-    SHA256_CACHE.with(|cache| cache.borrow_mut().clear());
-    SHA1_CACHE.with(|cache| cache.borrow_mut().clear());
-    MD5_CACHE.with(|cache| cache.borrow_mut().clear());
-    CRC32_CACHE.with(|cache| cache.borrow_mut().clear());
-    CHECKSUM32_CACHE.with(|cache| cache.borrow_mut().clear());
-
     Hash::new()
 }
 
@@ -46,30 +86,12 @@ fn md5_data(
     offset: i64,
     size: i64,
 ) -> Option<RuntimeString> {
-    let cached = MD5_CACHE.with(|cache| -> Option<RuntimeString> {
-        Some(RuntimeString::from_slice(
-            ctx,
-            cache.borrow().get(&(offset, size))?.as_bytes(),
-        ))
-    });
-
-    if cached.is_some() {
-        return cached;
-    }
-
-    let range = offset.try_into().ok()?..(offset + size).try_into().ok()?;
-    let data = ctx.scanned_data().get(range)?;
-    let mut hasher = Md5::new();
-
-    hasher.update(data);
-
-    let digest = format!("{:x}", hasher.finalize());
-
-    MD5_CACHE.with(|cache| {
-        cache.borrow_mut().insert((offset, size), digest.clone());
-    });
-
-    Some(RuntimeString::new(digest))
+    memoize(&MD5_CACHE, ctx, offset, size, |data| {
+        let mut hasher = Md5::new();
+        hasher.update(data);
+        format!("{:x}", hasher.finalize())
+    })
+    .map(RuntimeString::new)
 }
 
 #[module_export(name = "md5")]
@@ -86,30 +108,12 @@ fn sha1_data(
     offset: i64,
     size: i64,
 ) -> Option<RuntimeString> {
-    let cached = SHA1_CACHE.with(|cache| -> Option<RuntimeString> {
-        Some(RuntimeString::from_slice(
-            ctx,
-            cache.borrow().get(&(offset, size))?.as_bytes(),
-        ))
-    });
-
-    if cached.is_some() {
-        return cached;
-    }
-
-    let range = offset.try_into().ok()?..(offset + size).try_into().ok()?;
-    let data = ctx.scanned_data().get(range)?;
-    let mut hasher = Sha1::new();
-
-    hasher.update(data);
-
-    let digest = format!("{:x}", hasher.finalize());
-
-    SHA1_CACHE.with(|cache| {
-        cache.borrow_mut().insert((offset, size), digest.clone());
-    });
-
-    Some(RuntimeString::new(digest))
+    memoize(&SHA1_CACHE, ctx, offset, size, |data| {
+        let mut hasher = Sha1::new();
+        hasher.update(data);
+        format!("{:x}", hasher.finalize())
+    })
+    .map(RuntimeString::new)
 }
 
 #[module_export(name = "sha1")]
@@ -126,30 +130,12 @@ fn sha256_data(
     offset: i64,
     size: i64,
 ) -> Option<RuntimeString> {
-    let cached = SHA256_CACHE.with(|cache| -> Option<RuntimeString> {
-        Some(RuntimeString::from_slice(
-            ctx,
-            cache.borrow().get(&(offset, size))?.as_bytes(),
-        ))
-    });
-
-    if cached.is_some() {
-        return cached;
-    }
-
-    let range = offset.try_into().ok()?..(offset + size).try_into().ok()?;
-    let data = ctx.scanned_data().get(range)?;
-    let mut hasher = Sha256::new();
-
-    hasher.update(data);
-
-    let digest = format!("{:x}", hasher.finalize());
-
-    SHA256_CACHE.with(|cache| {
-        cache.borrow_mut().insert((offset, size), digest.clone());
-    });
-
-    Some(RuntimeString::new(digest))
+    memoize(&SHA256_CACHE, ctx, offset, size, |data| {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        format!("{:x}", hasher.finalize())
+    })
+    .map(RuntimeString::new)
 }
 
 #[module_export(name = "sha256")]
@@ -165,23 +151,9 @@ fn sha256_str(
 
 #[module_export(name = "crc32")]
 fn crc_data(ctx: &ScanContext, offset: i64, size: i64) -> Option<i64> {
-    let cached = CRC32_CACHE.with(|cache| -> Option<i64> {
-        Some(*cache.borrow().get(&(offset, size))?)
-    });
-
-    if cached.is_some() {
-        return cached;
-    }
-
-    let range = offset.try_into().ok()?..(offset + size).try_into().ok()?;
-    let data = ctx.scanned_data().get(range)?;
-    let crc = crc32fast::hash(data);
-
-    CRC32_CACHE.with(|cache| {
-        cache.borrow_mut().insert((offset, size), crc.into());
-    });
-
-    Some(crc.into())
+    memoize(&CRC32_CACHE, ctx, offset, size, |data| {
+        crc32fast::hash(data).into()
+    })
 }
 
 #[module_export(name = "crc32")]
@@ -192,34 +164,105 @@ fn crc_str(ctx: &ScanContext, s: RuntimeString) -> Option<i64> {
 
 #[module_export(name = "checksum32")]
 fn checksum_data(ctx: &ScanContext, offset: i64, size: i64) -> Option<i64> {
-    let cached = CHECKSUM32_CACHE.with(|cache| -> Option<i64> {
-        Some(*cache.borrow().get(&(offset, size))?)
-    });
+    memoize(&CHECKSUM32_CACHE, ctx, offset, size, |data| {
+        let mut checksum = 0_u32;
+        for byte in data {
+            checksum = checksum.wrapping_add(*byte as u32)
+        }
+        checksum.into()
+    })
+}
 
-    if cached.is_some() {
-        return cached;
+#[module_export(name = "checksum32")]
+fn checksum_str(ctx: &ScanContext, s: RuntimeString) -> Option<i64> {
+    let mut checksum = 0_u32;
+    for byte in s.as_bstr(ctx).as_bytes() {
+        checksum = checksum.wrapping_add(*byte as u32)
     }
+    Some(checksum.into())
+}
 
-    let range = offset.try_into().ok()?..(offset + size).try_into().ok()?;
-    let data = ctx.scanned_data().get(range)?;
-    let mut checksum = 0_u32;
+#[module_export(name = "ssdeep")]
+fn ssdeep_data(
+    ctx: &mut ScanContext,
+    offset: i64,
+    size: i64,
+) -> Option<RuntimeString> {
+    memoize(&SSDEEP_CACHE, ctx, offset, size, ssdeep::hash)
+        .map(RuntimeString::new)
+}
 
-    for byte in data {
-        checksum = checksum.wrapping_add(*byte as u32)
+#[module_export(name = "ssdeep")]
+fn ssdeep_str(
+    ctx: &mut ScanContext,
+    s: RuntimeString,
+) -> Option<RuntimeString> {
+    Some(RuntimeString::new(ssdeep::hash(s.as_bstr(ctx))))
+}
+
+#[module_export]
+fn ssdeep_compare(
+    ctx: &ScanContext,
+    a: RuntimeString,
+    b: RuntimeString,
+) -> Option<i64> {
+    ssdeep::compare(a.as_bstr(ctx), b.as_bstr(ctx))
+}
+
+#[module_export(name = "tlsh")]
+fn tlsh_data(
+    ctx: &mut ScanContext,
+    offset: i64,
+    size: i64,
+) -> Option<RuntimeString> {
+    // TLSH is not defined for some inputs, in that case the cache contains
+    // `None`.
+    memoize(&TLSH_CACHE, ctx, offset, size, tlsh_hash)?
+        .map(RuntimeString::new)
+}
+
+#[module_export(name = "tlsh")]
+fn tlsh_str(ctx: &mut ScanContext, s: RuntimeString) -> Option<RuntimeString> {
+    tlsh_hash(s.as_bstr(ctx)).map(RuntimeString::new)
+}
+
+#[module_export]
+fn tlsh_diff(
+    ctx: &ScanContext,
+    a: RuntimeString,
+    b: RuntimeString,
+) -> Option<i64> {
+    let a = tlsh_parse(a.as_bstr(ctx))?;
+    let b = tlsh_parse(b.as_bstr(ctx))?;
+
+    // Hashes of different kinds can't be compared.
+    if a.hash().len() != b.hash().len() {
+        return None;
     }
 
-    CHECKSUM32_CACHE.with(|cache| {
-        cache.borrow_mut().insert((offset, size), checksum.into());
-    });
+    Some(a.diff(&b, true) as i64)
+}
 
-    Some(checksum.into())
+/// Computes the TLSH of `data`, in the standard format used by most tools
+/// (128 buckets, 1-byte checksum, "T1" prefix). Returns [`None`] if the
+/// data is too short or doesn't have enough variability.
+fn tlsh_hash(data: &[u8]) -> Option<String> {
+    let mut builder = tlsh::TlshBuilder::new(
+        tlsh::BucketKind::Bucket128,
+        tlsh::ChecksumKind::OneByte,
+        tlsh::Version::Version4,
+    );
+
+    builder.update(data);
+
+    Some(builder.build().ok()?.hash())
 }
 
-#[module_export(name = "checksum32")]
-fn checksum_str(ctx: &ScanContext, s: RuntimeString) -> Option<i64> {
-    let mut checksum = 0_u32;
-    for byte in s.as_bstr(ctx).as_bytes() {
-        checksum = checksum.wrapping_add(*byte as u32)
+fn tlsh_parse(hash: &[u8]) -> Option<tlsh::Tlsh> {
+    // `Tlsh::from_str` slices the string assuming that it contains only
+    // ASCII characters.
+    if !hash.iter().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
     }
-    Some(checksum.into())
+    std::str::from_utf8(hash).ok()?.parse().ok()
 }
@@ -0,0 +1,317 @@
+/*! Implementation of the ssdeep fuzzy hash (also known as spamsum).
+
+ssdeep splits the data in pieces, whose boundaries are determined by a
+rolling hash computed over a small window of bytes. Each piece contributes
+a single character to the resulting hash, which means that similar data
+produces similar hashes.
+
+See: https://github.com/ssdeep-project/ssdeep/blob/master/fuzzy.c
+ */
+
+use std::cmp::{max, min};
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCKSIZE: u64 = 3;
+const SPAMSUM_LENGTH: usize = 64;
+const NUM_BLOCKHASHES: usize = 31;
+
+const HASH_PRIME: u32 = 0x01000193;
+const HASH_INIT: u32 = 0x28021967;
+
+const B64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn block_size(index: usize) -> u64 {
+    MIN_BLOCKSIZE << index
+}
+
+fn sum_hash(c: u8, h: u32) -> u32 {
+    h.wrapping_mul(HASH_PRIME) ^ c as u32
+}
+
+fn b64(h: u32) -> u8 {
+    B64[(h % 64) as usize]
+}
+
+/// Rolling hash computed over the last [`ROLLING_WINDOW`] bytes.
+#[derive(Default)]
+struct Roll {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl Roll {
+    fn update(&mut self, c: u8) {
+        let c32 = c as u32;
+        self.h2 = self
+            .h2
+            .wrapping_sub(self.h1)
+            .wrapping_add(ROLLING_WINDOW as u32 * c32);
+        self.h1 = self
+            .h1
+            .wrapping_add(c32)
+            .wrapping_sub(self.window[self.n % ROLLING_WINDOW] as u32);
+        self.window[self.n % ROLLING_WINDOW] = c;
+        self.n += 1;
+        self.h3 = (self.h3 << 5) ^ c32;
+    }
+
+    fn sum(&self) -> u32 {
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+/// State of the hash for one of the possible block sizes.
+#[derive(Clone, Copy)]
+struct BlockHash {
+    h: u32,
+    half_h: u32,
+    digest: [u8; SPAMSUM_LENGTH],
+    half_digest: u8,
+    len: usize,
+}
+
+impl BlockHash {
+    fn new(h: u32, half_h: u32) -> Self {
+        Self { h, half_h, digest: [0; SPAMSUM_LENGTH], half_digest: 0, len: 0 }
+    }
+}
+
+/// Computes the ssdeep hash of `data`.
+pub(crate) fn hash(data: &[u8]) -> String {
+    let mut roll = Roll::default();
+    let mut bh = vec![BlockHash::new(HASH_INIT, HASH_INIT)];
+    let mut start = 0;
+    let mut last_h = 0;
+    let mut need_last_h = false;
+
+    for &c in data {
+        roll.update(c);
+        let h = roll.sum();
+
+        for b in &mut bh[start..] {
+            b.h = sum_hash(c, b.h);
+            b.half_h = sum_hash(c, b.half_h);
+        }
+
+        if need_last_h {
+            last_h = sum_hash(c, last_h);
+        }
+
+        let mut i = start;
+
+        while i < bh.len() {
+            let bs = block_size(i);
+
+            if h as u64 % bs != bs - 1 {
+                break;
+            }
+
+            // This is the first piece for this block size, start computing
+            // the hash for the next block size.
+            if bh[i].len == 0 {
+                let last = bh[bh.len() - 1];
+                if bh.len() < NUM_BLOCKHASHES {
+                    bh.push(BlockHash::new(last.h, last.half_h));
+                } else if !need_last_h {
+                    need_last_h = true;
+                    last_h = last.h;
+                }
+            }
+
+            let b = &mut bh[i];
+
+            b.digest[b.len] = b64(b.h);
+            b.half_digest = b64(b.half_h);
+
+            if b.len < SPAMSUM_LENGTH - 1 {
+                b.len += 1;
+                b.digest[b.len] = 0;
+                b.h = HASH_INIT;
+                if b.len < SPAMSUM_LENGTH / 2 {
+                    b.half_h = HASH_INIT;
+                    b.half_digest = 0;
+                }
+            } else if bh.len() - start >= 2
+                && block_size(start) * (SPAMSUM_LENGTH as u64)
+                    < data.len() as u64
+                && bh[start + 1].len >= SPAMSUM_LENGTH / 2
+            {
+                // The hash for the smallest block size is full, and the
+                // next block size produces a hash long enough, the
+                // smallest block size is not needed anymore.
+                start += 1;
+            }
+
+            i += 1;
+        }
+    }
+
+    let h = roll.sum();
+
+    // Find the smallest block size that would produce a hash of at most
+    // SPAMSUM_LENGTH characters, and then reduce it while the hash is too
+    // short.
+    let mut i = start;
+
+    while i < NUM_BLOCKHASHES - 1
+        && block_size(i) * (SPAMSUM_LENGTH as u64) < data.len() as u64
+    {
+        i += 1;
+    }
+
+    i = min(i, bh.len() - 1);
+
+    while i > start && bh[i].len < SPAMSUM_LENGTH / 2 {
+        i -= 1;
+    }
+
+    let mut result = format!("{}:", block_size(i)).into_bytes();
+    let b = &bh[i];
+
+    result.extend_from_slice(&b.digest[..b.len]);
+
+    if h != 0 {
+        result.push(b64(b.h));
+    } else if b.digest[b.len] != 0 {
+        result.push(b.digest[b.len]);
+    }
+
+    result.push(b':');
+
+    if i < bh.len() - 1 {
+        let b = &bh[i + 1];
+        let len = min(b.len, SPAMSUM_LENGTH / 2 - 1);
+
+        result.extend_from_slice(&b.digest[..len]);
+
+        if h != 0 {
+            result.push(b64(b.half_h));
+        } else if b.half_digest != 0 {
+            result.push(b.half_digest);
+        }
+    } else if h != 0 {
+        result.push(b64(if i == 0 { b.h } else { last_h }));
+    }
+
+    // The result contains only ASCII characters.
+    String::from_utf8(result).unwrap()
+}
+
+/// Compares two ssdeep hashes, returning a score between 0 (completely
+/// different) and 100 (identical). Returns [`None`] if any of the hashes is
+/// not valid.
+pub(crate) fn compare(a: &[u8], b: &[u8]) -> Option<i64> {
+    let (bs1, a1, a2) = parse(a)?;
+    let (bs2, b1, b2) = parse(b)?;
+
+    if bs1 == bs2 && a1 == b1 && a2 == b2 {
+        return Some(100);
+    }
+
+    let score = if bs1 == bs2 {
+        max(score(&a1, &b1, bs1), score(&a2, &b2, bs1.saturating_mul(2)))
+    } else if bs1.checked_mul(2) == Some(bs2) {
+        score(&a2, &b1, bs2)
+    } else if bs2.checked_mul(2) == Some(bs1) {
+        score(&a1, &b2, bs1)
+    } else {
+        0
+    };
+
+    Some(score.into())
+}
+
+/// Parses a ssdeep hash, returning the block size and the two parts of the
+/// hash, where sequences of more than three identical characters have been
+/// reduced to three characters.
+fn parse(hash: &[u8]) -> Option<(u64, Vec<u8>, Vec<u8>)> {
+    let mut parts = hash.splitn(3, |c| *c == b':');
+
+    let bs = std::str::from_utf8(parts.next()?).ok()?.parse::<u64>().ok()?;
+    let p1 = parts.next()?;
+    let p2 = parts.next()?;
+
+    // The second part may be followed by the name of the file, as in the
+    // output of the ssdeep tool.
+    let p2 = p2.split(|c| *c == b',').next()?;
+
+    if p1.len() > SPAMSUM_LENGTH || p2.len() > SPAMSUM_LENGTH {
+        return None;
+    }
+
+    Some((bs, eliminate_sequences(p1), eliminate_sequences(p2)))
+}
+
+fn eliminate_sequences(s: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(s.len());
+    for (i, c) in s.iter().enumerate() {
+        if i < 3 || s[i - 1] != *c || s[i - 2] != *c || s[i - 3] != *c {
+            result.push(*c);
+        }
+    }
+    result
+}
+
+/// Computes the similarity score of two parts of ssdeep hashes that use
+/// the same block size.
+fn score(s1: &[u8], s2: &[u8], block_size: u64) -> u32 {
+    if s1.len() < ROLLING_WINDOW || s2.len() < ROLLING_WINDOW {
+        return 0;
+    }
+
+    // The parts must have at least one substring of ROLLING_WINDOW
+    // characters in common.
+    if !s1
+        .windows(ROLLING_WINDOW)
+        .any(|w| s2.windows(ROLLING_WINDOW).any(|v| v == w))
+    {
+        return 0;
+    }
+
+    let total_len = (s1.len() + s2.len()) as u32;
+    let mut score = edit_distance(s1, s2) * SPAMSUM_LENGTH as u32 / total_len;
+
+    score = 100 * score / SPAMSUM_LENGTH as u32;
+
+    if score >= 100 {
+        return 0;
+    }
+
+    score = 100 - score;
+
+    // When the block size is small, the score is capped so that small
+    // pieces of data don't produce exaggerated scores.
+    let threshold = (99 + ROLLING_WINDOW as u64) / ROLLING_WINDOW as u64
+        * MIN_BLOCKSIZE;
+
+    if block_size < threshold {
+        let cap = block_size / MIN_BLOCKSIZE
+            * min(s1.len(), s2.len()) as u64;
+        score = min(score as u64, cap) as u32;
+    }
+
+    score
+}
+
+/// Weighted edit distance where insertions and deletions cost 1, and
+/// substitutions cost 2.
+fn edit_distance(s1: &[u8], s2: &[u8]) -> u32 {
+    let mut prev: Vec<u32> = (0..=s2.len() as u32).collect();
+    let mut curr = vec![0; s2.len() + 1];
+
+    for (i, c1) in s1.iter().enumerate() {
+        curr[0] = i as u32 + 1;
+        for (j, c2) in s2.iter().enumerate() {
+            let cost = if c1 == c2 { 0 } else { 2 };
+            curr[j + 1] =
+                min(min(prev[j + 1] + 1, curr[j] + 1), prev[j] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[s2.len()]
+}
@@ -82,3 +82,90 @@ fn test_hash_module() {
         b"TEST STRING"
     );
 }
+
+#[test]
+#[cfg(feature = "hash-module")]
+fn test_hash_module_fuzzy_hashes() {
+    rule_true!(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            hash.ssdeep(0, filesize) == "3:AXGBicFlgVNogkc8pdRDDCNZP0Yn:AXGHsNoHlD8ZcY" and
+            hash.ssdeep(0, filesize) == hash.ssdeep("Also called fuzzy hashes, Ctph are based on context triggered piecewise hashes.") and
+            hash.ssdeep("") == "3::"
+        }
+        "#,
+        b"Also called fuzzy hashes, Ctph are based on context triggered piecewise hashes."
+    );
+
+    rule_true!(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            hash.ssdeep_compare(
+              "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C",
+              "3:AXGBicFlIHBGcL6wCrFQEv:AXGH6xLsr2C") == 22 and
+            hash.ssdeep_compare(
+              "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C",
+              "3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C") == 100 and
+            hash.ssdeep_compare(
+              hash.ssdeep(0, filesize),
+              "6:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C") == 0 and
+            not defined hash.ssdeep_compare("foo", "3::")
+        }
+        "#,
+        b"Also called fuzzy hashes, Ctph are based on context triggered piecewise hashes."
+    );
+}
+
+#[test]
+#[cfg(feature = "hash-module")]
+fn test_hash_module_tlsh() {
+    rule_true!(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            hash.tlsh(0, filesize) == "T146B0024A211D1794A58A2888438D95B2D2C9C9106521145179706042195C2259C98561" and
+            hash.tlsh(0, 80) == "T14AA00246311C17D4758F18C443CDD5F3D2CDC91071211411757070421D4C235DCDC551" and
+            hash.tlsh(0, filesize) == hash.tlsh("The quick brown fox jumps over the lazy dog. The quick brown fox jumps over the lazy cat.") and
+            not defined hash.tlsh(0, 40) and
+            hash.tlsh_diff(hash.tlsh(0, filesize), hash.tlsh(0, filesize)) == 0 and
+            hash.tlsh_diff(hash.tlsh(0, filesize), hash.tlsh(0, 80)) == 30 and
+            not defined hash.tlsh_diff(hash.tlsh(0, filesize), "T1foo")
+        }
+        "#,
+        b"The quick brown fox jumps over the lazy dog. The quick brown fox jumps over the lazy cat."
+    );
+}
+
+#[test]
+#[cfg(feature = "hash-module")]
+fn test_hash_module_cache() {
+    let rules = crate::compile(
+        r#"
+        import "hash"
+        rule test {
+          condition:
+            hash.md5(0, filesize) == hash.md5("foo") and
+            hash.md5(0, filesize) == hash.md5("foo")
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = crate::Scanner::new(&rules);
+
+    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+
+    // Values cached during the previous scan must be discarded, even if the
+    // module's main function is not invoked because its output is provided
+    // by the user.
+    scanner
+        .set_module_output(Box::new(crate::modules::protos::hash::Hash::new()))
+        .unwrap();
+
+    assert_eq!(scanner.scan(b"bar").unwrap().matching_rules().len(), 0);
+}
@@ -0,0 +1,194 @@
+/*! YARA module that parses Java class files.
+
+This module parses Java class files and exposes the name of the class, its
+superclass and interfaces, the fields and methods it declares, and the
+classes, methods, fields and string constants referenced in its constant
+pool.
+
+JAR files are also supported. A JAR is a ZIP archive that contains class
+files, when the scanned data is a JAR, the module parses every class file
+in the archive.
+
+This module is based on [`The Java Virtual Machine Specification`][1].
+
+[1]: https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html
+ */
+
+use std::io::{Cursor, Read};
+
+use crate::modules::prelude::*;
+use crate::modules::protos::javaclass::*;
+
+mod parser;
+#[cfg(test)]
+mod tests;
+
+/// Maximum number of class files that will be parsed from a JAR.
+const MAX_CLASSES: usize = 16384;
+
+/// Maximum number of bytes that will be decompressed from a JAR, adding up
+/// the sizes of all the class files extracted from it.
+const MAX_DECOMPRESSED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Limits the amount of data in the module's output, adding up all the
+/// classes in a JAR. See [`parser::Budget`].
+const MAX_OUTPUT_SIZE: usize = 16 * 1024 * 1024;
+
+/// Returns false if `data` is neither a class file nor a JAR file.
+pub(crate) fn sniff(data: &[u8]) -> bool {
+    data.starts_with(b"\xCA\xFE\xBA\xBE") || data.starts_with(b"PK\x03\x04")
+}
+
+#[module_main]
+fn main(data: &[u8], _meta: Option<&[u8]>) -> JavaClass {
+    let mut java = JavaClass::new();
+
+    if data.starts_with(b"PK\x03\x04") {
+        java.classes = parse_jar(data);
+        java.is_class = Some(false);
+        java.is_jar = Some(!java.classes.is_empty());
+    } else if let Ok(class) = parser::ClassFile::parse(data) {
+        let mut budget = parser::Budget::new(MAX_OUTPUT_SIZE);
+        java.classes.push(class.into_proto(&mut budget));
+        java.is_class = Some(true);
+        java.is_jar = Some(false);
+    } else {
+        java.is_class = Some(false);
+        java.is_jar = Some(false);
+    }
+
+    java
+}
+
+/// Parses the class files in a JAR archive.
+fn parse_jar(data: &[u8]) -> Vec<Class> {
+    let mut classes = Vec::new();
+
+    let Ok(mut archive) = zip::ZipArchive::new(Cursor::new(data)) else {
+        return classes;
+    };
+
+    let mut budget = MAX_DECOMPRESSED_SIZE;
+    let mut output_budget = parser::Budget::new(MAX_OUTPUT_SIZE);
+
+    for i in 0..archive.len() {
+        if classes.len() >= MAX_CLASSES
+            || budget == 0
+            || output_budget.is_exhausted()
+        {
+            break;
+        }
+
+        let Ok(file) = archive.by_index(i) else {
+            continue;
+        };
+
+        if !file.is_file() || !file.name().ends_with(".class") {
+            continue;
+        }
+
+        let path = file.name().to_string();
+        let mut content = Vec::new();
+
+        // The size declared in the archive can't be trusted, limit the
+        // number of bytes read from the decompressor.
+        if file.take(budget).read_to_end(&mut content).is_err() {
+            continue;
+        }
+
+        budget -= content.len() as u64;
+
+        if let Ok(class) = parser::ClassFile::parse(content.as_slice()) {
+            let mut class = class.into_proto(&mut output_budget);
+            class.path = Some(path);
+            classes.push(class);
+        }
+    }
+
+    classes
+}
+
+/// Returns true if the file contains a class with the given name, in
+/// internal form (e.g. `org/example/Main`).
+#[module_export]
+fn has_class(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let java = ctx.module_output::<JavaClass>()?;
+    let name = name.as_bstr(ctx);
+
+    Some(
+        java.classes
+            .iter()
+            .any(|class| class.name.as_ref().is_some_and(|n| name == n)),
+    )
+}
+
+/// Returns true if any class in the file declares a method with the given
+/// name.
+#[module_export(name = "has_method")]
+fn has_method(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let java = ctx.module_output::<JavaClass>()?;
+    let name = name.as_bstr(ctx);
+
+    Some(java.classes.iter().any(|class| {
+        class
+            .methods
+            .iter()
+            .any(|method| method.name.as_ref().is_some_and(|n| name == n))
+    }))
+}
+
+/// Returns true if the class with the given name declares a method with
+/// the given name.
+#[module_export(name = "has_method")]
+fn has_class_method(
+    ctx: &ScanContext,
+    class: RuntimeString,
+    name: RuntimeString,
+) -> Option<bool> {
+    let java = ctx.module_output::<JavaClass>()?;
+    let class_name = class.as_bstr(ctx);
+    let name = name.as_bstr(ctx);
+
+    Some(java.classes.iter().any(|class| {
+        class.name.as_ref().is_some_and(|c| class_name == c)
+            && class
+                .methods
+                .iter()
+                .any(|method| method.name.as_ref().is_some_and(|n| name == n))
+    }))
+}
+
+/// Returns true if any class in the file references the method with the
+/// given name that belongs to the given class (e.g.
+/// `javax/naming/Context`, `lookup`).
+#[module_export]
+fn has_method_ref(
+    ctx: &ScanContext,
+    class: RuntimeString,
+    name: RuntimeString,
+) -> Option<bool> {
+    let java = ctx.module_output::<JavaClass>()?;
+    let class_name = class.as_bstr(ctx);
+    let name = name.as_bstr(ctx);
+
+    Some(java.classes.iter().any(|class| {
+        class.method_refs.iter().any(|method| {
+            method.class_name.as_ref().is_some_and(|c| class_name == c)
+                && method.name.as_ref().is_some_and(|n| name == n)
+        })
+    }))
+}
+
+/// Returns true if any class in the file has a string constant with the
+/// given value.
+#[module_export]
+fn has_string(ctx: &ScanContext, s: RuntimeString) -> Option<bool> {
+    let java = ctx.module_output::<JavaClass>()?;
+    let s = s.as_bstr(ctx);
+
+    Some(
+        java.classes
+            .iter()
+            .any(|class| class.string_constants.iter().any(|c| s == c)),
+    )
+}
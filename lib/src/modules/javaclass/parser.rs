@@ -0,0 +1,380 @@
+use std::collections::HashSet;
+
+use nom::bytes::complete::{tag, take};
+use nom::combinator::{map, verify};
+use nom::multi::{length_count, length_data};
+use nom::number::complete::{be_u16, be_u32, u8};
+use nom::sequence::tuple;
+use nom::IResult;
+
+use crate::modules::protos::javaclass;
+use crate::modules::utils::decode_mutf8;
+
+/// The first major version of the class file format (JDK 1.0.2).
+const MIN_MAJOR_VERSION: u16 = 45;
+
+/// Limits the amount of data in the module's output. Each class, member and
+/// constant consumes one unit, plus the length of the strings it contains.
+/// A single UTF-8 constant can be referenced by many other constants, this
+/// prevents specially crafted files from producing a huge output.
+pub struct Budget(usize);
+
+impl Budget {
+    pub fn new(budget: usize) -> Self {
+        Self(budget)
+    }
+
+    /// Returns true if the budget is exhausted.
+    pub fn is_exhausted(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Consumes `n` units from the budget, returns false if the budget is
+    /// exhausted.
+    fn consume(&mut self, n: usize) -> bool {
+        match self.0.checked_sub(n) {
+            Some(budget) => {
+                self.0 = budget;
+                true
+            }
+            None => {
+                self.0 = 0;
+                false
+            }
+        }
+    }
+}
+
+/// Returns the length of an optional string.
+fn len(s: Option<&str>) -> usize {
+    s.map_or(0, |s| s.len())
+}
+
+/// An entry in the constant pool. Only the entries that are relevant for
+/// the module are fully parsed.
+enum Constant {
+    Utf8(String),
+    Class(u16),
+    String(u16),
+    FieldRef(u16, u16),
+    MethodRef(u16, u16),
+    NameAndType(u16, u16),
+    Other,
+}
+
+/// A parsed field or method.
+struct Member {
+    access_flags: u16,
+    name: u16,
+    descriptor: u16,
+}
+
+/// A parsed class file.
+pub struct ClassFile {
+    minor_version: u16,
+    major_version: u16,
+    constants: Vec<Option<Constant>>,
+    access_flags: u16,
+    this_class: u16,
+    super_class: u16,
+    interfaces: Vec<u16>,
+    fields: Vec<Member>,
+    methods: Vec<Member>,
+    source_file: Option<u16>,
+}
+
+impl ClassFile {
+    /// Parses a class file.
+    ///
+    /// The header and the constant pool must be valid. Fields, methods and
+    /// attributes are parsed on a best-effort basis, if any of them is
+    /// corrupt it is ignored, as well as anything that comes after it.
+    pub fn parse(data: &[u8]) -> Result<Self, nom::Err<()>> {
+        let (input, (_magic, minor_version, major_version)) = tuple((
+            tag(b"\xCA\xFE\xBA\xBE".as_slice()),
+            be_u16,
+            verify(be_u16, |v| *v >= MIN_MAJOR_VERSION),
+        ))(data)?;
+
+        let (input, constants) = Self::parse_constant_pool(input)?;
+
+        let (input, (access_flags, this_class, super_class, interfaces)) =
+            tuple((be_u16, be_u16, be_u16, length_count(be_u16, be_u16)))(
+                input,
+            )?;
+
+        let mut class = Self {
+            minor_version,
+            major_version,
+            constants,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces,
+            fields: Vec::new(),
+            methods: Vec::new(),
+            source_file: None,
+        };
+
+        let Ok((input, fields)) = length_count(be_u16, member)(input) else {
+            return Ok(class);
+        };
+
+        class.fields = fields;
+
+        let Ok((input, methods)) = length_count(be_u16, member)(input) else {
+            return Ok(class);
+        };
+
+        class.methods = methods;
+
+        if let Ok((_, attributes)) = length_count(be_u16, attribute)(input) {
+            class.source_file =
+                attributes.iter().find_map(|(name, data)| {
+                    if class.utf8(*name) == Some("SourceFile") {
+                        be_u16::<&[u8], ()>(*data).ok().map(|(_, i)| i)
+                    } else {
+                        None
+                    }
+                });
+        }
+
+        Ok(class)
+    }
+
+    /// Parses the constant pool. The resulting vector is indexed by the
+    /// constant's index, as indexes start at 1 the first item is always
+    /// [`None`]. Long and double constants use two indexes, the second one
+    /// is also [`None`].
+    fn parse_constant_pool(
+        input: &[u8],
+    ) -> IResult<&[u8], Vec<Option<Constant>>, ()> {
+        let (mut input, constant_pool_count) = be_u16(input)?;
+        let mut constants = Vec::with_capacity(constant_pool_count as usize);
+
+        constants.push(None);
+
+        while constants.len() < constant_pool_count as usize {
+            let (remainder, tag) = u8(input)?;
+            let (remainder, constant) = match tag {
+                1 => map(length_data(be_u16), |s| {
+                    Constant::Utf8(decode_mutf8(s))
+                })(remainder)?,
+                7 => map(be_u16, Constant::Class)(remainder)?,
+                8 => map(be_u16, Constant::String)(remainder)?,
+                9 => map(tuple((be_u16, be_u16)), |(class, nat)| {
+                    Constant::FieldRef(class, nat)
+                })(remainder)?,
+                // Methodref and InterfaceMethodref.
+                10 | 11 => map(tuple((be_u16, be_u16)), |(class, nat)| {
+                    Constant::MethodRef(class, nat)
+                })(remainder)?,
+                12 => map(tuple((be_u16, be_u16)), |(name, desc)| {
+                    Constant::NameAndType(name, desc)
+                })(remainder)?,
+                // Integer, Float, Dynamic and InvokeDynamic.
+                3 | 4 | 17 | 18 => {
+                    map(take(4_usize), |_| Constant::Other)(remainder)?
+                }
+                // Long and Double.
+                5 | 6 => map(take(8_usize), |_| Constant::Other)(remainder)?,
+                // MethodHandle.
+                15 => map(take(3_usize), |_| Constant::Other)(remainder)?,
+                // MethodType, Module and Package.
+                16 | 19 | 20 => {
+                    map(take(2_usize), |_| Constant::Other)(remainder)?
+                }
+                _ => return Err(nom::Err::Error(())),
+            };
+
+            constants.push(Some(constant));
+
+            // Long and Double constants take two entries in the pool.
+            if matches!(tag, 5 | 6) {
+                constants.push(None);
+            }
+
+            input = remainder;
+        }
+
+        Ok((input, constants))
+    }
+
+    fn constant(&self, index: u16) -> Option<&Constant> {
+        self.constants.get(index as usize)?.as_ref()
+    }
+
+    fn utf8(&self, index: u16) -> Option<&str> {
+        match self.constant(index)? {
+            Constant::Utf8(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    fn class_name(&self, index: u16) -> Option<&str> {
+        match self.constant(index)? {
+            Constant::Class(name) => self.utf8(*name),
+            _ => None,
+        }
+    }
+
+    /// Converts a Fieldref or Methodref constant into
+    /// [`javaclass::MemberRef`], returns `None` if the budget is exhausted.
+    fn member_ref(
+        &self,
+        class: u16,
+        nat: u16,
+        budget: &mut Budget,
+    ) -> Option<javaclass::MemberRef> {
+        let class_name = self.class_name(class);
+        let (name, descriptor) = match self.constant(nat) {
+            Some(Constant::NameAndType(name, descriptor)) => {
+                (self.utf8(*name), self.utf8(*descriptor))
+            }
+            _ => (None, None),
+        };
+
+        if !budget.consume(1 + len(class_name) + len(name) + len(descriptor))
+        {
+            return None;
+        }
+
+        let mut member_ref = javaclass::MemberRef::new();
+        member_ref.class_name = class_name.map(String::from);
+        member_ref.name = name.map(String::from);
+        member_ref.descriptor = descriptor.map(String::from);
+        Some(member_ref)
+    }
+
+    /// Converts a field or method into [`javaclass::Member`], returns
+    /// `None` if the budget is exhausted.
+    fn member(
+        &self,
+        member: &Member,
+        budget: &mut Budget,
+    ) -> Option<javaclass::Member> {
+        let name = self.utf8(member.name);
+        let descriptor = self.utf8(member.descriptor);
+
+        if !budget.consume(1 + len(name) + len(descriptor)) {
+            return None;
+        }
+
+        let mut result = javaclass::Member::new();
+        result.name = name.map(String::from);
+        result.descriptor = descriptor.map(String::from);
+        result.access_flags = Some(member.access_flags.into());
+        Some(result)
+    }
+
+    /// Converts the class file into [`javaclass::Class`].
+    ///
+    /// The data added to the result is deducted from `budget`, once the
+    /// budget is exhausted the remaining members and constants are
+    /// ignored. Constants that appear multiple times in the constant pool,
+    /// or that point to the same UTF-8 constant, are included only once.
+    pub fn into_proto(self, budget: &mut Budget) -> javaclass::Class {
+        let mut result = javaclass::Class::new();
+
+        let name = self.class_name(self.this_class);
+        let super_class = self.class_name(self.super_class);
+        let source_file = self.source_file.and_then(|i| self.utf8(i));
+
+        if !budget.consume(1 + len(name) + len(super_class) + len(source_file))
+        {
+            return result;
+        }
+
+        result.minor_version = Some(self.minor_version.into());
+        result.major_version = Some(self.major_version.into());
+        result.access_flags = Some(self.access_flags.into());
+        result.name = name.map(String::from);
+        result.super_class = super_class.map(String::from);
+        result.source_file = source_file.map(String::from);
+
+        result.interfaces = self
+            .interfaces
+            .iter()
+            .filter_map(|i| self.class_name(*i))
+            .take_while(|i| budget.consume(1 + i.len()))
+            .map(String::from)
+            .collect();
+
+        result.fields = self
+            .fields
+            .iter()
+            .map_while(|f| self.member(f, budget))
+            .collect();
+
+        result.methods = self
+            .methods
+            .iter()
+            .map_while(|m| self.member(m, budget))
+            .collect();
+
+        let mut seen_strings = HashSet::new();
+        let mut seen_classes = HashSet::new();
+        let mut seen_field_refs = HashSet::new();
+        let mut seen_method_refs = HashSet::new();
+
+        for constant in self.constants.iter().flatten() {
+            match constant {
+                Constant::String(s) if seen_strings.insert(*s) => {
+                    if let Some(s) = self.utf8(*s) {
+                        if !budget.consume(1 + s.len()) {
+                            break;
+                        }
+                        result.string_constants.push(s.to_string());
+                    }
+                }
+                Constant::Class(name) if seen_classes.insert(*name) => {
+                    if let Some(name) = self.utf8(*name) {
+                        if !budget.consume(1 + name.len()) {
+                            break;
+                        }
+                        result.class_refs.push(name.to_string());
+                    }
+                }
+                Constant::FieldRef(c, nat)
+                    if seen_field_refs.insert((*c, *nat)) =>
+                {
+                    match self.member_ref(*c, *nat, budget) {
+                        Some(field_ref) => result.field_refs.push(field_ref),
+                        None => break,
+                    }
+                }
+                Constant::MethodRef(c, nat)
+                    if seen_method_refs.insert((*c, *nat)) =>
+                {
+                    match self.member_ref(*c, *nat, budget) {
+                        Some(method_ref) => {
+                            result.method_refs.push(method_ref)
+                        }
+                        None => break,
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        result
+    }
+}
+
+/// Parses a `field_info` or `method_info` structure.
+fn member(input: &[u8]) -> IResult<&[u8], Member, ()> {
+    map(
+        tuple((be_u16, be_u16, be_u16, length_count(be_u16, attribute))),
+        |(access_flags, name, descriptor, _attributes)| Member {
+            access_flags,
+            name,
+            descriptor,
+        },
+    )(input)
+}
+
+/// Parses an `attribute_info` structure, returning the index of the
+/// attribute's name and its data.
+fn attribute(input: &[u8]) -> IResult<&[u8], (u16, &[u8]), ()> {
+    tuple((be_u16, length_data(be_u32)))(input)
+}
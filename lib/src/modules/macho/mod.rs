@@ -5,6 +5,8 @@
 //! both protobuf structure fields and constants. This together with
 //! also exported functions can be later used in YARA rules.
 
+use std::ops::Range;
+
 use crate::modules::prelude::*;
 use crate::modules::protos::macho::*;
 use itertools::Itertools;
@@ -446,6 +448,12 @@ fn import_hash(ctx: &mut ScanContext) -> Option<RuntimeString> {
     Some(RuntimeString::new(digest))
 }
 
+/// Returns false if `data` can't be a Mach-O file, either FAT or
+/// single-architecture.
+pub(crate) fn sniff(data: &[u8]) -> bool {
+    parser::MachO::has_magic(data)
+}
+
 #[module_main]
 fn main(data: &[u8], _meta: Option<&[u8]>) -> Macho {
     match parser::MachO::parse(data) {
@@ -453,3 +461,43 @@ fn main(data: &[u8], _meta: Option<&[u8]>) -> Macho {
         Err(_) => Macho::new(),
     }
 }
+
+/// Splits a FAT binary into its architecture slices.
+///
+/// For each slice returns its range within `data`, and the output that the
+/// module produces when the slice is scanned on its own. This output
+/// describes the single-architecture binary in the slice, but it also
+/// includes the FAT header and the index of the slice in `fat_slice`.
+///
+/// Returns `None` if `data` is not a FAT binary. Slices that lay outside
+/// `data` are ignored.
+pub(crate) fn fat_slices(data: &[u8]) -> Option<Vec<(Range<usize>, Macho)>> {
+    let fat = parser::MachO::parse(data).ok()?;
+    let fat_magic = fat.fat_magic()?;
+    let fat_arch: Vec<FatArch> = fat.fat_archs().collect();
+
+    let mut slices = Vec::with_capacity(fat_arch.len());
+
+    for (index, arch) in fat_arch.iter().enumerate() {
+        let start = arch.offset() as usize;
+        let end = start.saturating_add(arch.size() as usize);
+
+        let Some(slice) = data.get(start..end) else {
+            continue;
+        };
+
+        let mut output = match parser::MachO::parse(slice) {
+            Ok(macho) => Macho::from(macho),
+            Err(_) => Macho::new(),
+        };
+
+        output.set_fat_magic(fat_magic);
+        output.set_nfat_arch(fat_arch.len() as u32);
+        output.set_fat_slice(index as u64);
+        output.fat_arch.clone_from(&fat_arch);
+
+        slices.push((start..end, output));
+    }
+
+    Some(slices)
+}
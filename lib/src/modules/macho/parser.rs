@@ -12,7 +12,7 @@ use nom::error::ErrorKind;
 use nom::multi::{count, length_count};
 use nom::number::complete::{be_u32, le_u32, u16, u32, u64, u8};
 use nom::number::Endianness;
-use nom::sequence::tuple;
+use nom::sequence::{preceded, tuple};
 use nom::{Err, IResult, Parser};
 use protobuf::MessageField;
 
@@ -33,12 +33,15 @@ const FAT_CIGAM_64: u32 = 0xbfbafeca;
 /// Mach-O code signature constants
 const _CS_MAGIC_REQUIREMENT: u32 = 0xfade0c00;
 const _CS_MAGIC_REQUIREMENTS: u32 = 0xfade0c01;
-const _CS_MAGIC_CODEDIRECTORY: u32 = 0xfade0c02;
+const CS_MAGIC_CODEDIRECTORY: u32 = 0xfade0c02;
 const _CS_MAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade0cc0;
 const _CS_MAGIC_DETACHED_SIGNATURE: u32 = 0xfade0cc1;
 const _CS_MAGIC_BLOBWRAPPER: u32 = 0xfade0b01;
 const CS_MAGIC_EMBEDDED_ENTITLEMENTS: u32 = 0xfade7171;
 
+/// First CodeDirectory version that includes the team ID.
+const CS_SUPPORTSTEAMID: u32 = 0x20200;
+
 /// Mach-O export flag constants
 const EXPORT_SYMBOL_FLAGS_WEAK_DEFINITION: u64 = 0x00000004;
 const EXPORT_SYMBOL_FLAGS_REEXPORT: u64 = 0x00000008;
@@ -136,6 +139,39 @@ impl<'a> MachO<'a> {
             })
         }
     }
+
+    /// Returns true if `data` starts with the magic number of either a FAT
+    /// binary or a single-architecture binary.
+    pub fn has_magic(data: &[u8]) -> bool {
+        // Each magic number is accompanied by its byte-swapped counterpart,
+        // so the byte order used for reading it doesn't matter.
+        le_u32::<&[u8], ()>(data).is_ok_and(|(_, magic)| {
+            matches!(
+                magic,
+                MH_MAGIC
+                    | MH_CIGAM
+                    | MH_MAGIC_64
+                    | MH_CIGAM_64
+                    | FAT_MAGIC
+                    | FAT_CIGAM
+                    | FAT_MAGIC_64
+                    | FAT_CIGAM_64
+            )
+        })
+    }
+
+    /// Returns the magic number of a FAT binary, or `None` if this is a
+    /// single-architecture binary.
+    pub fn fat_magic(&self) -> Option<u32> {
+        self.fat_magic
+    }
+
+    /// Returns the architectures included in a FAT binary.
+    pub fn fat_archs(
+        &self,
+    ) -> impl Iterator<Item = protos::macho::FatArch> + '_ {
+        self.archs.iter().map(|arch| arch.into())
+    }
 }
 
 impl<'a> MachO<'a> {
@@ -290,6 +326,8 @@ impl<'a> MachO<'a> {
             entry_point_rva: None,
             stack_size: None,
             code_signature_data: None,
+            signing_id: None,
+            team_id: None,
             entitlements: Vec::new(),
             certificates: None,
             uuid: None,
@@ -417,6 +455,8 @@ pub struct MachOFile<'a> {
     rpaths: Vec<&'a [u8]>,
     uuid: Option<&'a [u8]>,
     code_signature_data: Option<LinkedItData>,
+    signing_id: Option<String>,
+    team_id: Option<String>,
     entitlements: Vec<String>,
     certificates: Option<Certificates>,
     build_version: Option<BuildVersionCommand>,
@@ -835,6 +875,58 @@ impl<'a> MachOFile<'a> {
         }
     }
 
+    /// Parses a CodeDirectory blob, returning the signing ID and the team
+    /// ID. The team ID is present only in recent versions of the
+    /// CodeDirectory structure.
+    fn cs_code_directory(
+        input: &'a [u8],
+    ) -> IResult<&'a [u8], (Option<String>, Option<String>)> {
+        let (remainder, (_magic, _length, version, _flags, _hash_offset)) =
+            tuple((
+                u32(Endianness::Big), // magic
+                u32(Endianness::Big), // length
+                u32(Endianness::Big), // version
+                u32(Endianness::Big), // flags
+                u32(Endianness::Big), // hashOffset
+            ))(input)?;
+
+        let (remainder, ident_offset) = u32(Endianness::Big)(remainder)?;
+
+        let (_, team_offset) = cond(
+            version >= CS_SUPPORTSTEAMID,
+            preceded(
+                tuple((
+                    u32(Endianness::Big), // nSpecialSlots
+                    u32(Endianness::Big), // nCodeSlots
+                    u32(Endianness::Big), // codeLimit
+                    take(4_usize),        // hashSize, hashType, platform, etc.
+                    u32(Endianness::Big), // spare2
+                    u32(Endianness::Big), // scatterOffset
+                )),
+                u32(Endianness::Big), // teamOffset
+            ),
+        )(remainder)?;
+
+        // Both the signing ID and the team ID are null-terminated
+        // strings located at the given offsets, which are relative to
+        // the start of the CodeDirectory.
+        let string_at = |offset: u32| -> Option<String> {
+            if offset == 0 {
+                return None;
+            }
+            let (_, s) = take_till::<_, _, nom::error::Error<&[u8]>>(
+                |b| b == b'\x00',
+            )(input.get(offset as usize..)?)
+            .ok()?;
+            Some(String::from_utf8_lossy(s).to_string())
+        };
+
+        Ok((
+            &[],
+            (string_at(ident_offset), team_offset.and_then(string_at)),
+        ))
+    }
+
     fn cs_superblob(
         &mut self,
     ) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], CSSuperBlob> + '_ {
@@ -875,6 +967,25 @@ impl<'a> MachOFile<'a> {
             for (offset, blob) in blobs {
                 let length = blob.length as usize;
                 let size_of_blob = std::mem::size_of::<CSBlob>();
+                // A signature can contain multiple code directories (e.g:
+                // one with SHA-1 hashes and another with SHA-256 hashes),
+                // but the signing ID and team ID are the same in all of
+                // them, so the first one is enough.
+                if blob.magic == CS_MAGIC_CODEDIRECTORY
+                    && self.signing_id.is_none()
+                {
+                    if let Some(code_directory) =
+                        super_data.get(offset..offset.saturating_add(length))
+                    {
+                        if let Ok((_, (signing_id, team_id))) =
+                            Self::cs_code_directory(code_directory)
+                        {
+                            self.signing_id = signing_id;
+                            self.team_id = team_id;
+                        }
+                    }
+                }
+
                 if blob.magic == CS_MAGIC_EMBEDDED_ENTITLEMENTS {
                     let xml_data = match super_data
                         .get(offset + size_of_blob..offset + length)
@@ -1660,6 +1771,9 @@ impl From<MachO<'_>> for protos::macho::Macho {
                     MessageField::some(cs_data.into());
             }
 
+            m.signing_id.clone_into(&mut result.signing_id);
+            m.team_id.clone_into(&mut result.team_id);
+
             if let Some(cert_data) = &m.certificates {
                 result.certificates = MessageField::some(cert_data.into());
             }
@@ -1743,6 +1857,9 @@ impl From<&MachOFile<'_>> for protos::macho::File {
             result.code_signature_data = MessageField::some(cs_data.into());
         }
 
+        macho.signing_id.clone_into(&mut result.signing_id);
+        macho.team_id.clone_into(&mut result.team_id);
+
         if let Some(cert_data) = &macho.certificates {
             result.certificates = MessageField::some(cert_data.into());
         }
@@ -2,6 +2,7 @@ use crate::modules::tests::create_binary_from_zipped_ihex;
 use crate::tests::rule_false;
 use crate::tests::rule_true;
 use crate::tests::test_rule;
+use crate::{compile, Scanner};
 
 #[test]
 fn test_macho_module() {
@@ -17,6 +18,10 @@ fn test_macho_module() {
         "src/modules/macho/tests/testdata/chess.in.zip",
     );
 
+    let signed_macho_data = create_binary_from_zipped_ihex(
+        "src/modules/macho/tests/testdata/01ac68a14f0ff5faa72bb33e768bfaae4d21de61f776e2405324c498ef52b21b.in.zip",
+    );
+
     rule_true!(
         r#"
         import "macho"
@@ -474,4 +479,115 @@ fn test_macho_module() {
         "#,
         &tiny_universal_macho_data
     );
+
+    rule_true!(
+        r#"
+        import "macho"
+        rule macho_test {
+            condition:
+                macho.signing_id == "com.apple.Chess" and
+                not defined macho.team_id
+        }
+        "#,
+        &chess_macho_data
+    );
+
+    rule_true!(
+        r#"
+        import "macho"
+        rule macho_test {
+            condition:
+                not defined macho.signing_id and
+                not defined macho.team_id
+        }
+        "#,
+        &x86_macho_data
+    );
+
+    rule_true!(
+        r#"
+        import "macho"
+        rule macho_test {
+            condition:
+                macho.signing_id == "com.efi.APF_HAccountData" and
+                macho.team_id == "82PCFB3NFC"
+        }
+        "#,
+        &signed_macho_data
+    );
+}
+
+#[test]
+fn scan_fat_slices() {
+    let tiny_universal_macho_data = create_binary_from_zipped_ihex(
+        "src/modules/macho/tests/testdata/tiny_universal.in.zip",
+    );
+
+    let rules = compile(
+        r#"
+        import "macho"
+        rule slice {
+            condition:
+                filesize == macho.fat_arch[macho.fat_slice].size and
+                macho.cputype == macho.fat_arch[macho.fat_slice].cputype and
+                macho.nfat_arch == 2
+        }
+        rule x86 {
+            condition:
+                uint32(0) == macho.MH_MAGIC and
+                macho.cputype == macho.CPU_TYPE_X86
+        }
+        rule x86_64 {
+            condition:
+                uint32(0) == macho.MH_MAGIC_64 and
+                macho.cputype == macho.CPU_TYPE_X86_64
+        }
+        rule fat {
+            condition:
+                uint32be(0) == macho.FAT_MAGIC
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+    let mut results = Vec::new();
+
+    scanner
+        .scan_macho_slices(&tiny_universal_macho_data, |slice, scan_results| {
+            let slice = slice.unwrap();
+            results.push((
+                slice.index(),
+                slice.cputype(),
+                slice.range(),
+                scan_results
+                    .matching_rules()
+                    .map(|r| r.identifier().to_string())
+                    .collect::<Vec<_>>(),
+            ));
+        })
+        .unwrap();
+
+    assert_eq!(
+        results,
+        vec![
+            (0, 0x7, 4096..12608, vec!["slice".to_string(), "x86".to_string()]),
+            (
+                1,
+                0x1000007,
+                16384..24928,
+                vec!["slice".to_string(), "x86_64".to_string()]
+            ),
+        ]
+    );
+
+    let mut results = Vec::new();
+
+    scanner
+        .scan_macho_slices(b"not a fat binary", |slice, scan_results| {
+            results.push((slice.is_none(), scan_results.matching_rules().len()));
+        })
+        .unwrap();
+
+    assert_eq!(results, vec![(true, 0)]);
 }
@@ -1,103 +1,65 @@
-/*! YARA module that uses [libmagic][1] for recognizing file types.
+/*! YARA module that recognizes file types.
 
-This allows creating YARA rules that use the file type provided by [libmagic][1].
+This allows creating YARA rules that use the type of the scanned file, in a
+similar way to the Unix `file` command. File types are recognized by means
+of an embedded database of signatures, which doesn't depend on [libmagic][1]
+and works in all platforms.
 
 [1]: https://man7.org/linux/man-pages/man3/libmagic.3.html
  */
 
-use crate::modules::prelude::*;
-use crate::modules::protos::magic::*;
 use std::cell::RefCell;
 
-#[cfg(feature = "logging")]
-use log::*;
+use crate::modules::prelude::*;
+use crate::modules::protos::magic::*;
 
+mod signatures;
 #[cfg(test)]
 mod tests;
 
 thread_local! {
-    static MAGIC: magic::Cookie<magic::cookie::Load> = {
-        magic::Cookie::open(Default::default())
-            .expect("initialized libmagic")
-            .load(&Default::default())
-            .expect("loaded libmagic database")
-    };
-
-    static TYPE_CACHE: RefCell<Option<String>> = {
-        RefCell::new(None)
-    };
-
-    static MIME_TYPE_CACHE: RefCell<Option<String>> = {
-        RefCell::new(None)
-    };
-
+    /// Type of the file being scanned, together with the ID of the scan
+    /// it belongs to.
+    static FILE_TYPE_CACHE: RefCell<Option<(u64, signatures::FileType)>> =
+        const { RefCell::new(None) };
 }
 
 #[module_main]
 fn main(_data: &[u8], _meta: Option<&[u8]>) -> Magic {
-    // With every scanned file the cache must be cleared.
-    TYPE_CACHE.set(None);
-    MIME_TYPE_CACHE.set(None);
-
     Magic::new()
 }
 
 #[module_export(name = "type")]
 fn file_type(ctx: &mut ScanContext) -> Option<RuntimeString> {
-    let cached = TYPE_CACHE.with(|cache| cache.borrow().clone());
-
-    if let Some(cached) = cached {
-        return Some(RuntimeString::new(cached));
-    }
-
-    match get_type(ctx.scanned_data()) {
-        Ok(type_) => {
-            TYPE_CACHE.replace(Some(type_.clone()));
-            Some(RuntimeString::new(type_))
-        }
-        #[allow(unused_variables)]
-        Err(err) => {
-            #[cfg(feature = "logging")]
-            error!("libmagic error: {}", err);
-            None
-        }
-    }
+    Some(RuntimeString::new(get_file_type(ctx).description))
 }
 
 #[module_export(name = "mime_type")]
 fn mime_type(ctx: &mut ScanContext) -> Option<RuntimeString> {
-    let cached = MIME_TYPE_CACHE.with(|cache| cache.borrow().clone());
-
-    if let Some(cached) = cached {
-        return Some(RuntimeString::new(cached));
-    }
-
-    match get_mime_type(ctx.scanned_data()) {
-        Ok(type_) => {
-            MIME_TYPE_CACHE.replace(Some(type_.clone()));
-            Some(RuntimeString::new(type_))
-        }
-        #[allow(unused_variables)]
-        Err(err) => {
-            #[cfg(feature = "logging")]
-            error!("libmagic error: {}", err);
-            None
-        }
-    }
+    Some(RuntimeString::new(get_file_type(ctx).mime_type))
 }
 
-fn get_type(data: &[u8]) -> Result<String, magic::cookie::Error> {
-    MAGIC
-        .with(|magic| magic.set_flags(Default::default()))
-        .expect("set libmagic options");
-
-    MAGIC.with(|magic| magic.buffer(data))
+#[cfg(test)]
+fn get_type(data: &[u8]) -> Option<String> {
+    Some(signatures::detect(data).description)
 }
 
-fn get_mime_type(data: &[u8]) -> Result<String, magic::cookie::Error> {
-    MAGIC
-        .with(|magic| magic.set_flags(magic::cookie::Flags::MIME_TYPE))
-        .expect("set libmagic options");
+#[cfg(test)]
+fn get_mime_type(data: &[u8]) -> Option<String> {
+    Some(signatures::detect(data).mime_type.to_string())
+}
 
-    MAGIC.with(|magic| magic.buffer(data))
+/// Returns the type of the scanned data. The type is computed only once
+/// per scanned file.
+fn get_file_type(ctx: &ScanContext) -> signatures::FileType {
+    FILE_TYPE_CACHE.with_borrow_mut(|cache| match cache {
+        Some((scan_id, file_type)) if *scan_id == ctx.scan_id => {
+            file_type.clone()
+        }
+        _ => {
+            let file_type = signatures::detect(ctx.scanned_data());
+            *cache = Some((ctx.scan_id, file_type.clone()));
+            file_type
+        }
+    })
 }
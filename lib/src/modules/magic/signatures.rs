@@ -0,0 +1,615 @@
+use std::io::{Cursor, Read};
+
+use memchr::memmem;
+
+/// Maximum number of bytes that are inspected for determining if the data
+/// is text.
+const MAX_TEXT_BYTES: usize = 1024 * 1024;
+
+/// A file type, as returned by [`detect`].
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FileType {
+    /// Human-readable description (e.g. "PDF document").
+    pub description: String,
+    /// MIME type (e.g. "application/pdf").
+    pub mime_type: &'static str,
+}
+
+impl FileType {
+    fn new(description: impl Into<String>, mime_type: &'static str) -> Self {
+        Self { description: description.into(), mime_type }
+    }
+}
+
+/// Identifies a file type by a sequence of bytes at a fixed offset.
+struct Signature {
+    offset: usize,
+    bytes: &'static [u8],
+    description: &'static str,
+    /// An empty MIME type means that the signature doesn't have a specific
+    /// one. Like in libmagic, the MIME type is then `text/plain` or
+    /// `application/octet-stream`, depending on whether the data is text.
+    mime_type: &'static str,
+}
+
+macro_rules! signature {
+    ($offset:expr, $bytes:expr, $description:expr, $mime_type:expr) => {
+        Signature {
+            offset: $offset,
+            bytes: $bytes,
+            description: $description,
+            mime_type: $mime_type,
+        }
+    };
+}
+
+/// File types that can be identified by a simple signature. File types
+/// that require inspecting their structure, like executables or ZIP-based
+/// documents, are handled by [`detect`].
+const SIGNATURES: &[Signature] = &[
+    signature!(0, b"%PDF-", "PDF document", "application/pdf"),
+    signature!(0, b"\x1f\x8b", "gzip compressed data", "application/gzip"),
+    signature!(0, b"BZh", "bzip2 compressed data", "application/x-bzip2"),
+    signature!(0, b"\xfd7zXZ\x00", "XZ compressed data", "application/x-xz"),
+    signature!(
+        0,
+        b"7z\xbc\xaf\x27\x1c",
+        "7-zip archive data",
+        "application/x-7z-compressed"
+    ),
+    signature!(
+        0,
+        b"Rar!\x1a\x07\x01\x00",
+        "RAR archive data, v5",
+        "application/x-rar"
+    ),
+    signature!(0, b"Rar!\x1a\x07\x00", "RAR archive data", "application/x-rar"),
+    signature!(
+        0,
+        b"\x28\xb5\x2f\xfd",
+        "Zstandard compressed data",
+        "application/zstd"
+    ),
+    signature!(
+        0,
+        b"\x04\x22\x4d\x18",
+        "LZ4 compressed data",
+        "application/x-lz4"
+    ),
+    signature!(
+        0,
+        b"MSCF\x00\x00\x00\x00",
+        "Microsoft Cabinet archive data",
+        "application/vnd.ms-cab-compressed"
+    ),
+    signature!(
+        0,
+        b"!<arch>\ndebian-binary",
+        "Debian binary package",
+        "application/vnd.debian.binary-package"
+    ),
+    signature!(0, b"!<arch>\n", "current ar archive", "application/x-archive"),
+    signature!(0, b"\xed\xab\xee\xdb", "RPM package", "application/x-rpm"),
+    signature!(257, b"ustar", "POSIX tar archive", "application/x-tar"),
+    signature!(
+        32769,
+        b"CD001",
+        "ISO 9660 CD-ROM filesystem data",
+        "application/x-iso9660-image"
+    ),
+    signature!(0, b"\x89PNG\r\n\x1a\n", "PNG image data", "image/png"),
+    signature!(0, b"\xff\xd8\xff", "JPEG image data", "image/jpeg"),
+    signature!(0, b"GIF87a", "GIF image data, version 87a", "image/gif"),
+    signature!(0, b"GIF89a", "GIF image data, version 89a", "image/gif"),
+    signature!(0, b"II*\x00", "TIFF image data, little-endian", "image/tiff"),
+    signature!(0, b"MM\x00*", "TIFF image data, big-endian", "image/tiff"),
+    signature!(0, b"OggS", "Ogg data", "audio/ogg"),
+    signature!(0, b"fLaC", "FLAC audio bitstream data", "audio/flac"),
+    signature!(0, b"ID3", "Audio file with ID3", "audio/mpeg"),
+    signature!(0, b"\x1a\x45\xdf\xa3", "Matroska data", "video/x-matroska"),
+    signature!(
+        0,
+        b"\x00asm",
+        "WebAssembly (wasm) binary module",
+        "application/wasm"
+    ),
+    signature!(0, b"dex\n", "Dalvik dex file", "application/vnd.android.dex"),
+    signature!(
+        0,
+        b"L\x00\x00\x00\x01\x14\x02\x00",
+        "MS Windows shortcut",
+        "application/x-ms-shortcut"
+    ),
+    signature!(
+        0,
+        b"SQLite format 3\x00",
+        "SQLite 3.x database",
+        "application/vnd.sqlite3"
+    ),
+    signature!(
+        0,
+        b"\xd4\xc3\xb2\xa1",
+        "pcap capture file, little-endian",
+        "application/vnd.tcpdump.pcap"
+    ),
+    signature!(
+        0,
+        b"\xa1\xb2\xc3\xd4",
+        "pcap capture file, big-endian",
+        "application/vnd.tcpdump.pcap"
+    ),
+    signature!(
+        0,
+        b"\x0a\x0d\x0d\x0a",
+        "pcapng capture file",
+        "application/x-pcapng"
+    ),
+    signature!(0, b"{\\rtf", "Rich Text Format data", "text/rtf"),
+    signature!(0, b"Maestro\r", "RISC OS music file", ""),
+];
+
+/// Determines the type of a file.
+pub(crate) fn detect(data: &[u8]) -> FileType {
+    if data.is_empty() {
+        return FileType::new("empty", "application/x-empty");
+    }
+
+    if let Some(file_type) = elf(data)
+        .or_else(|| pe(data))
+        .or_else(|| macho(data))
+        .or_else(|| zip(data))
+        .or_else(|| ole(data))
+        .or_else(|| riff(data))
+        .or_else(|| iso_media(data))
+        .or_else(|| bmp(data))
+        .or_else(|| pyc(data))
+    {
+        return file_type;
+    }
+
+    for signature in SIGNATURES {
+        if data
+            .get(signature.offset..)
+            .is_some_and(|d| d.starts_with(signature.bytes))
+        {
+            let mime_type = match signature.mime_type {
+                "" if text(data).is_some() => "text/plain",
+                "" => "application/octet-stream",
+                mime_type => mime_type,
+            };
+            return FileType::new(signature.description, mime_type);
+        }
+    }
+
+    text(data)
+        .unwrap_or_else(|| FileType::new("data", "application/octet-stream"))
+}
+
+fn u16_at(data: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn u32_at(data: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+fn elf(data: &[u8]) -> Option<FileType> {
+    if !data.starts_with(b"\x7fELF") {
+        return None;
+    }
+
+    let bits = match data.get(4)? {
+        1 => "32-bit",
+        2 => "64-bit",
+        _ => return None,
+    };
+
+    let (endianness, big_endian) = match data.get(5)? {
+        1 => ("LSB", false),
+        2 => ("MSB", true),
+        _ => return None,
+    };
+
+    let (kind, mime_type) = match u16_at(data, 16, big_endian)? {
+        1 => ("relocatable", "application/x-object"),
+        2 => ("executable", "application/x-executable"),
+        3 => ("shared object", "application/x-sharedlib"),
+        4 => ("core file", "application/x-coredump"),
+        _ => ("unknown type", "application/octet-stream"),
+    };
+
+    Some(FileType::new(format!("ELF {bits} {endianness} {kind}"), mime_type))
+}
+
+fn pe(data: &[u8]) -> Option<FileType> {
+    if !data.starts_with(b"MZ") {
+        return None;
+    }
+
+    let dos = FileType::new("MS-DOS executable", "application/x-dosexec");
+
+    let Some(pe) = u32_at(data, 0x3c, false)
+        .and_then(|offset| data.get(offset as usize..))
+        .filter(|pe| pe.starts_with(b"PE\x00\x00"))
+    else {
+        return Some(dos);
+    };
+
+    let (Some(machine), Some(characteristics), Some(magic)) = (
+        u16_at(pe, 4, false),
+        u16_at(pe, 22, false),
+        u16_at(pe, 24, false),
+    ) else {
+        return Some(dos);
+    };
+
+    let machine = match machine {
+        0x014c => " Intel 80386",
+        0x8664 => " x86-64",
+        0x01c0 | 0x01c4 => " ARM",
+        0xaa64 => " Aarch64",
+        _ => "",
+    };
+
+    let format = match magic {
+        0x10b => "PE32",
+        0x20b => "PE32+",
+        _ => return Some(dos),
+    };
+
+    // The subsystem is at the same offset in PE32 and PE32+.
+    let subsystem = match u16_at(pe, 24 + 68, false) {
+        Some(1) => " (native)",
+        Some(2) => " (GUI)",
+        Some(3) => " (console)",
+        Some(10..=13) => " (EFI application)",
+        _ => "",
+    };
+
+    let kind = if characteristics & 0x2000 != 0 {
+        "executable (DLL)"
+    } else {
+        "executable"
+    };
+
+    Some(FileType::new(
+        format!("{format} {kind}{subsystem}{machine}, for MS Windows"),
+        "application/vnd.microsoft.portable-executable",
+    ))
+}
+
+fn macho(data: &[u8]) -> Option<FileType> {
+    let (bits, big_endian) = match data.get(0..4)? {
+        b"\xfe\xed\xfa\xce" => ("", true),
+        b"\xce\xfa\xed\xfe" => ("", false),
+        b"\xfe\xed\xfa\xcf" => (" 64-bit", true),
+        b"\xcf\xfa\xed\xfe" => (" 64-bit", false),
+        b"\xca\xfe\xba\xbe" => return fat_or_class(data),
+        _ => return None,
+    };
+
+    let kind = match u32_at(data, 12, big_endian)? {
+        1 => "object",
+        2 => "executable",
+        4 => "core",
+        6 => "dynamically linked shared library",
+        7 => "dynamic linker",
+        8 => "bundle",
+        _ => "file",
+    };
+
+    Some(FileType::new(
+        format!("Mach-O{bits} {kind}"),
+        "application/x-mach-binary",
+    ))
+}
+
+/// Mach-O universal binaries and Java class files share the same magic.
+/// They are distinguished by the next four bytes, which are the number of
+/// architectures in a universal binary, and the minor and major versions
+/// in a class file. Real universal binaries have a few architectures, while
+/// class files have a major version of at least 45.
+fn fat_or_class(data: &[u8]) -> Option<FileType> {
+    let n = u32_at(data, 4, true)?;
+
+    if n > 0 && n < 20 {
+        return Some(FileType::new(
+            format!("Mach-O universal binary with {n} architectures"),
+            "application/x-mach-binary",
+        ));
+    }
+
+    let minor = u16_at(data, 4, true)?;
+    let major = u16_at(data, 6, true)?;
+
+    Some(FileType::new(
+        format!("compiled Java class data, version {major}.{minor}"),
+        "application/x-java-applet",
+    ))
+}
+
+fn zip(data: &[u8]) -> Option<FileType> {
+    if !data.starts_with(b"PK\x03\x04") {
+        return None;
+    }
+
+    let zip = FileType::new("Zip archive data", "application/zip");
+
+    let Ok(mut archive) = zip::ZipArchive::new(Cursor::new(data)) else {
+        return Some(zip);
+    };
+
+    // OpenDocument files and EPUB books contain a file named `mimetype`,
+    // which contains the MIME type of the file.
+    if let Ok(file) = archive.by_name("mimetype") {
+        let mut content = String::new();
+        if file.take(128).read_to_string(&mut content).is_ok() {
+            match content.trim() {
+                "application/epub+zip" => {
+                    return Some(FileType::new(
+                        "EPUB document",
+                        "application/epub+zip",
+                    ))
+                }
+                "application/vnd.oasis.opendocument.text" => {
+                    return Some(FileType::new(
+                        "OpenDocument Text",
+                        "application/vnd.oasis.opendocument.text",
+                    ))
+                }
+                "application/vnd.oasis.opendocument.spreadsheet" => {
+                    return Some(FileType::new(
+                        "OpenDocument Spreadsheet",
+                        "application/vnd.oasis.opendocument.spreadsheet",
+                    ))
+                }
+                "application/vnd.oasis.opendocument.presentation" => {
+                    return Some(FileType::new(
+                        "OpenDocument Presentation",
+                        "application/vnd.oasis.opendocument.presentation",
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let names = archive.file_names().collect::<Vec<_>>();
+    let has_prefix = |prefix: &str| names.iter().any(|n| n.starts_with(prefix));
+    let has_name = |name: &str| names.contains(&name);
+
+    if has_name("[Content_Types].xml") {
+        if has_prefix("word/") {
+            return Some(FileType::new(
+                "Microsoft Word 2007+",
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            ));
+        }
+        if has_prefix("xl/") {
+            return Some(FileType::new(
+                "Microsoft Excel 2007+",
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            ));
+        }
+        if has_prefix("ppt/") {
+            return Some(FileType::new(
+                "Microsoft PowerPoint 2007+",
+                "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            ));
+        }
+    }
+
+    if has_name("AndroidManifest.xml") && has_name("classes.dex") {
+        return Some(FileType::new(
+            "Android package (APK)",
+            "application/vnd.android.package-archive",
+        ));
+    }
+
+    if has_name("META-INF/MANIFEST.MF")
+        || names.iter().any(|n| n.ends_with(".class"))
+    {
+        return Some(FileType::new(
+            "Java archive data (JAR)",
+            "application/java-archive",
+        ));
+    }
+
+    Some(zip)
+}
+
+fn ole(data: &[u8]) -> Option<FileType> {
+    if !data.starts_with(b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1") {
+        return None;
+    }
+
+    // The type of document is determined by the names of the streams it
+    // contains, which are stored in UTF-16, or by the CLSID of the root
+    // storage in the case of MSI installers.
+    let contains = |needle: &[u8]| memmem::find(data, needle).is_some();
+    let utf16 = |s: &str| -> Vec<u8> {
+        s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    };
+
+    if contains(&utf16("WordDocument")) {
+        Some(FileType::new("Microsoft Word document", "application/msword"))
+    } else if contains(&utf16("Workbook")) || contains(&utf16("Book\0")) {
+        Some(FileType::new(
+            "Microsoft Excel document",
+            "application/vnd.ms-excel",
+        ))
+    } else if contains(&utf16("PowerPoint Document")) {
+        Some(FileType::new(
+            "Microsoft PowerPoint document",
+            "application/vnd.ms-powerpoint",
+        ))
+    } else if contains(
+        b"\x84\x10\x0c\x00\x00\x00\x00\x00\xc0\x00\x00\x00\x00\x00\x00\x46",
+    ) {
+        Some(FileType::new("MSI Installer", "application/x-msi"))
+    } else {
+        Some(FileType::new(
+            "Composite Document File V2 Document",
+            "application/CDFV2",
+        ))
+    }
+}
+
+fn riff(data: &[u8]) -> Option<FileType> {
+    if !data.starts_with(b"RIFF") {
+        return None;
+    }
+    let file_type = match data.get(8..12)? {
+        b"WAVE" => FileType::new(
+            "RIFF (little-endian) data, WAVE audio",
+            "audio/x-wav",
+        ),
+        b"AVI " => {
+            FileType::new("RIFF (little-endian) data, AVI", "video/x-msvideo")
+        }
+        b"WEBP" => {
+            FileType::new("RIFF (little-endian) data, Web/P image", "image/webp")
+        }
+        _ => FileType::new("RIFF (little-endian) data", "application/x-riff"),
+    };
+    Some(file_type)
+}
+
+/// Files based on the ISO base media file format, like MP4 or QuickTime.
+fn iso_media(data: &[u8]) -> Option<FileType> {
+    if data.get(4..8)? != b"ftyp" {
+        return None;
+    }
+    let file_type = match data.get(8..12)? {
+        b"qt  " => FileType::new("Apple QuickTime movie", "video/quicktime"),
+        b"M4A " => FileType::new("ISO Media, Apple iTunes audio", "audio/x-m4a"),
+        b"heic" | b"heix" | b"mif1" => {
+            FileType::new("ISO Media, HEIF image", "image/heic")
+        }
+        _ => FileType::new("ISO Media", "video/mp4"),
+    };
+    Some(file_type)
+}
+
+fn bmp(data: &[u8]) -> Option<FileType> {
+    // The signature `BM` is too short, the reserved fields and the size of
+    // the header that follows the signature are checked too.
+    if !data.starts_with(b"BM") || u32_at(data, 6, false)? != 0 {
+        return None;
+    }
+    match u32_at(data, 14, false)? {
+        12 | 40 | 52 | 56 | 64 | 108 | 124 => {
+            Some(FileType::new("PC bitmap", "image/bmp"))
+        }
+        _ => None,
+    }
+}
+
+fn pyc(data: &[u8]) -> Option<FileType> {
+    if data.get(2..4)? != b"\r\n" {
+        return None;
+    }
+    match u16_at(data, 0, false)? {
+        3000..=3699 | 62161..=62211 => Some(FileType::new(
+            "Byte-compiled Python module",
+            "application/x-bytecode.python",
+        )),
+        _ => None,
+    }
+}
+
+fn text(data: &[u8]) -> Option<FileType> {
+    if data.starts_with(b"\xff\xfe") || data.starts_with(b"\xfe\xff") {
+        let endianness =
+            if data[0] == 0xff { "little-endian" } else { "big-endian" };
+        return Some(FileType::new(
+            format!("Unicode text, UTF-16, {endianness} text"),
+            "text/plain",
+        ));
+    }
+
+    let truncated = data.len() > MAX_TEXT_BYTES;
+    let data = &data[..data.len().min(MAX_TEXT_BYTES)];
+
+    // The data is text if it is valid UTF-8, ignoring an incomplete
+    // character at the end if the data was truncated, and doesn't contain
+    // control characters other than the usual ones.
+    let valid_utf8 = match std::str::from_utf8(data) {
+        Ok(_) => true,
+        Err(err) => truncated && err.error_len().is_none(),
+    };
+
+    if !valid_utf8
+        || data.iter().any(|c| {
+            c.is_ascii_control()
+                && !matches!(c, b'\t' | b'\n' | b'\r' | b'\x0c' | b'\x1b')
+        })
+    {
+        return None;
+    }
+
+    let encoding = if data.is_ascii() {
+        "ASCII text"
+    } else {
+        "Unicode text, UTF-8 text"
+    };
+
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+
+    if let Some(line) = data.strip_prefix(b"#!") {
+        let line = &line[..memchr::memchr(b'\n', line).unwrap_or(line.len())];
+        let (description, mime_type) = if memmem::find(line, b"python").is_some()
+        {
+            ("Python script", "text/x-script.python")
+        } else if memmem::find(line, b"perl").is_some() {
+            ("Perl script", "text/x-perl")
+        } else if memmem::find(line, b"sh").is_some() {
+            ("POSIX shell script", "text/x-shellscript")
+        } else {
+            ("script", "text/plain")
+        };
+        return Some(FileType::new(
+            format!("{description}, {encoding} executable"),
+            mime_type,
+        ));
+    }
+
+    let start = &data[data
+        .iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .unwrap_or(data.len())..];
+    let starts_with = |prefix: &[u8]| {
+        start
+            .get(..prefix.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(prefix))
+    };
+
+    let (description, mime_type) = if starts_with(b"<?php") {
+        ("PHP script", "text/x-php")
+    } else if starts_with(b"<!doctype html") || starts_with(b"<html") {
+        ("HTML document", "text/html")
+    } else if starts_with(b"<?xml") {
+        ("XML document", "text/xml")
+    } else if starts_with(b"-----BEGIN CERTIFICATE-----") {
+        ("PEM certificate", "application/x-pem-file")
+    } else if (starts_with(b"{") || starts_with(b"["))
+        && !truncated
+        && serde_json::from_slice::<serde_json::Value>(data).is_ok()
+    {
+        ("JSON data", "application/json")
+    } else {
+        return Some(FileType::new(encoding, "text/plain"));
+    };
+
+    Some(FileType::new(format!("{description}, {encoding}"), mime_type))
+}
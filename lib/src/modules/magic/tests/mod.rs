@@ -1,5 +1,14 @@
 use pretty_assertions::assert_eq;
 
+use crate::modules::magic::signatures::detect;
+use crate::modules::tests::create_binary_from_zipped_ihex;
+
+fn assert_type(data: &[u8], description: &str, mime_type: &str) {
+    let file_type = detect(data);
+    assert_eq!(file_type.description, description);
+    assert_eq!(file_type.mime_type, mime_type);
+}
+
 #[test]
 fn get_filetype() {
     assert_eq!(
@@ -16,18 +25,101 @@ fn get_mimetype() {
     )
 }
 
+#[test]
+fn get_filetype_from_signatures() {
+    assert_type(b"", "empty", "application/x-empty");
+    assert_type(b"foobar", "ASCII text", "text/plain");
+    assert_type(
+        "fóóbar".as_bytes(),
+        "Unicode text, UTF-8 text",
+        "text/plain",
+    );
+    assert_type(b"\x00\x01\x02\x03", "data", "application/octet-stream");
+    assert_type(b"%PDF-1.7\n", "PDF document", "application/pdf");
+    assert_type(
+        b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR",
+        "PNG image data",
+        "image/png",
+    );
+    assert_type(
+        b"\x1f\x8b\x08\x00\x00\x00\x00\x00",
+        "gzip compressed data",
+        "application/gzip",
+    );
+    assert_type(
+        b"#!/bin/sh\necho hello\n",
+        "POSIX shell script, ASCII text executable",
+        "text/x-shellscript",
+    );
+    assert_type(
+        b"  <!DOCTYPE html>\n<html></html>",
+        "HTML document, ASCII text",
+        "text/html",
+    );
+    assert_type(
+        b"{\"foo\": [1, 2, 3]}",
+        "JSON data, ASCII text",
+        "application/json",
+    );
+    assert_type(
+        b"[section]\nkey=value\n",
+        "ASCII text",
+        "text/plain",
+    );
+    assert_type(b"MZ\x90\x00", "MS-DOS executable", "application/x-dosexec");
+    assert_type(
+        b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x03\x00",
+        "ELF 64-bit LSB shared object",
+        "application/x-sharedlib",
+    );
+    assert_type(
+        b"\xcf\xfa\xed\xfe\x07\x00\x00\x01\x03\x00\x00\x00\x02\x00\x00\x00",
+        "Mach-O 64-bit executable",
+        "application/x-mach-binary",
+    );
+    assert_type(
+        b"\xca\xfe\xba\xbe\x00\x00\x00\x02",
+        "Mach-O universal binary with 2 architectures",
+        "application/x-mach-binary",
+    );
+    assert_type(
+        b"\xca\xfe\xba\xbe\x00\x00\x00\x34",
+        "compiled Java class data, version 52.0",
+        "application/x-java-applet",
+    );
+}
+
+#[test]
+fn get_filetype_from_files() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/2e9c671b8a0411f2b397544b368c44d7f095eb395779de0ad1ac946914dfa34c.in.zip",
+    );
+
+    assert_type(
+        &pe,
+        "PE32+ executable (GUI) x86-64, for MS Windows",
+        "application/vnd.microsoft.portable-executable",
+    );
+
+    let jar = create_binary_from_zipped_ihex(
+        "src/modules/javaclass/tests/testdata/sample.jar.in.zip",
+    );
+
+    assert_type(&jar, "Java archive data (JAR)", "application/java-archive");
+}
+
 #[test]
 fn e2e_test() {
     let rules = crate::compile(
         r#"
     import "magic"
-    rule t { 
-      condition: 
-        magic.type() == "RISC OS music file" and 
-        magic.mime_type() == "text/plain" and 
+    rule t {
+      condition:
+        magic.type() == "RISC OS music file" and
+        magic.mime_type() == "text/plain" and
         // Call the functions twice, in order to exercise the caching
         // mechanism.
-        magic.type() == "RISC OS music file" and 
+        magic.type() == "RISC OS music file" and
         magic.mime_type() == "text/plain"
     }"#,
     )
@@ -38,3 +130,30 @@ fn e2e_test() {
 
     assert_eq!(results.matching_rules().len(), 1);
 }
+
+#[test]
+fn e2e_cache_test() {
+    let rules = crate::compile(
+        r#"
+    import "magic"
+    rule t {
+      condition:
+        magic.type() == "PDF document" and
+        magic.mime_type() == "application/pdf" and
+        // Call the functions twice, in order to exercise the caching
+        // mechanism.
+        magic.type() == "PDF document" and
+        magic.mime_type() == "application/pdf"
+    }"#,
+    )
+    .unwrap();
+
+    let mut scanner = crate::scanner::Scanner::new(&rules);
+
+    let results = scanner.scan(b"%PDF-1.7\n").unwrap();
+    assert_eq!(results.matching_rules().len(), 1);
+
+    // The cache must be cleared between scans.
+    let results = scanner.scan(b"foobar").unwrap();
+    assert_eq!(results.matching_rules().len(), 0);
+}
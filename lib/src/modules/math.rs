@@ -6,6 +6,7 @@ use num_traits::Pow;
 
 use crate::modules::prelude::*;
 use crate::modules::protos::math::*;
+use crate::modules::utils::entropy;
 
 #[module_main]
 fn main(_data: &[u8], _meta: Option<&[u8]>) -> Math {
@@ -215,25 +216,51 @@ fn monte_carlo_pi_string(ctx: &ScanContext, s: RuntimeString) -> Option<f64> {
     monte_carlo_pi(s.as_bstr(ctx).as_bytes())
 }
 
-fn entropy(data: &[u8]) -> f64 {
-    if data.is_empty() {
-        return 0.0;
-    }
+#[module_export(name = "chi2")]
+fn chi2_data(ctx: &ScanContext, offset: i64, length: i64) -> Option<f64> {
+    let length: usize = length.try_into().ok()?;
+    let start: usize = offset.try_into().ok()?;
+    let end = cmp::min(ctx.scanned_data().len(), start.saturating_add(length));
+    chi2(ctx.scanned_data().get(start..end)?)
+}
 
-    let mut distribution = [0u64; 256];
-    for byte in data {
-        distribution[*byte as usize] += 1;
-    }
+#[module_export(name = "chi2")]
+fn chi2_string(ctx: &ScanContext, s: RuntimeString) -> Option<f64> {
+    chi2(s.as_bstr(ctx).as_bytes())
+}
 
-    let mut entropy: f64 = 0.0;
-    for value in &distribution {
-        if *value != 0 {
-            let x = *value as f64 / data.len() as f64;
-            entropy -= x * f64::log2(x);
-        }
-    }
+#[module_export]
+fn min_window_entropy(
+    ctx: &ScanContext,
+    offset: i64,
+    length: i64,
+    window_size: i64,
+) -> Option<f64> {
+    let length: usize = length.try_into().ok()?;
+    let start: usize = offset.try_into().ok()?;
+    let end = cmp::min(ctx.scanned_data().len(), start.saturating_add(length));
+    window_entropy(
+        ctx.scanned_data().get(start..end)?,
+        window_size.try_into().ok()?,
+    )
+    .map(|(min, _)| min)
+}
 
-    entropy
+#[module_export]
+fn max_window_entropy(
+    ctx: &ScanContext,
+    offset: i64,
+    length: i64,
+    window_size: i64,
+) -> Option<f64> {
+    let length: usize = length.try_into().ok()?;
+    let start: usize = offset.try_into().ok()?;
+    let end = cmp::min(ctx.scanned_data().len(), start.saturating_add(length));
+    window_entropy(
+        ctx.scanned_data().get(start..end)?,
+        window_size.try_into().ok()?,
+    )
+    .map(|(_, max)| max)
 }
 
 fn deviation(data: &[u8], mean: f64) -> Option<f64> {
@@ -348,6 +375,76 @@ fn monte_carlo_pi(data: &[u8]) -> Option<f64> {
     Some((mpi - PI).abs() / PI)
 }
 
+/// Computes the chi-square statistic of the byte distribution in `data`,
+/// assuming that all byte values are equally likely. Random data produces
+/// values close to 255, while data with a biased distribution, like text
+/// or code, produces much larger values.
+fn chi2(data: &[u8]) -> Option<f64> {
+    if data.is_empty() {
+        return None;
+    }
+
+    let mut distribution = [0u64; 256];
+    for byte in data {
+        distribution[*byte as usize] += 1;
+    }
+
+    let expected = data.len() as f64 / 256.0;
+    let mut chi2 = 0.0;
+
+    for value in distribution {
+        chi2 += (value as f64 - expected).pow(2) / expected;
+    }
+
+    Some(chi2)
+}
+
+/// Computes the entropy of every window of `window_size` bytes in `data`,
+/// returning the minimum and maximum values. If `data` is shorter than
+/// `window_size`, the whole data is a single window.
+fn window_entropy(data: &[u8], window_size: usize) -> Option<(f64, f64)> {
+    if data.is_empty() || window_size == 0 {
+        return None;
+    }
+
+    let window_size = cmp::min(window_size, data.len());
+
+    // The entropy of a window is `log2(n) - sum(c * log2(c)) / n`, where `n`
+    // is the window size and `c` are the number of occurrences of each
+    // byte value. When the window slides only two byte values change their
+    // number of occurrences, so the sum can be updated instead of being
+    // computed from scratch.
+    let f = |c: u64| if c == 0 { 0.0 } else { c as f64 * f64::log2(c as f64) };
+
+    let mut distribution = [0u64; 256];
+    for byte in &data[..window_size] {
+        distribution[*byte as usize] += 1;
+    }
+
+    let mut sum: f64 = distribution.iter().map(|c| f(*c)).sum();
+    let n = window_size as f64;
+    let entropy = |sum: f64| (n.log2() - sum / n).clamp(0.0, 8.0);
+
+    let mut min = entropy(sum);
+    let mut max = min;
+
+    for (old, new) in data.iter().zip(&data[window_size..]) {
+        if old == new {
+            continue;
+        }
+        let old = &mut distribution[*old as usize];
+        sum += f(*old - 1) - f(*old);
+        *old -= 1;
+        let new = &mut distribution[*new as usize];
+        sum += f(*new + 1) - f(*new);
+        *new += 1;
+        min = min.min(entropy(sum));
+        max = max.max(entropy(sum));
+    }
+
+    Some((min, max))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::tests::rule_false;
@@ -632,6 +729,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn chi2() {
+        rule_true!(
+            r#"
+            import "math"
+            rule test {
+                condition:
+                    math.chi2("AAAA") == 1020.0
+            }"#,
+            &[]
+        );
+
+        rule_true!(
+            r#"
+            import "math"
+            rule test {
+                condition:
+                    math.chi2(0, filesize) == 0.0 and
+                    math.chi2(0, 4) == 252.0 and
+                    not defined math.chi2(0, 0)
+            }"#,
+            &(0..=255).collect::<Vec<u8>>()
+        );
+    }
+
+    #[test]
+    fn window_entropy() {
+        let mut data = vec![0_u8; 16];
+        data.extend(0..16);
+
+        rule_true!(
+            r#"
+            import "math"
+            rule test {
+                condition:
+                    math.min_window_entropy(0, filesize, 16) == 0.0 and
+                    math.max_window_entropy(0, filesize, 16) == 4.0 and
+                    math.max_window_entropy(0, 16, 16) == 0.0 and
+                    math.min_window_entropy(16, 16, 1000) == 4.0 and
+                    math.max_window_entropy(0, filesize, 1000) == math.entropy(0, filesize) and
+                    not defined math.min_window_entropy(0, filesize, 0) and
+                    not defined math.max_window_entropy(filesize, 10, 16)
+            }"#,
+            &data
+        );
+    }
+
     #[test]
     fn count() {
         rule_true!(
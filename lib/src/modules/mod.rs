@@ -7,9 +7,15 @@ pub mod protos {
     include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
 }
 
+mod plugin;
+mod utils;
+
 #[cfg(test)]
 mod tests;
 
+pub use plugin::ModulePlugin;
+pub use plugin::PluginError;
+
 #[allow(unused_imports)]
 pub(crate) mod prelude {
     pub(crate) use crate::scanner::ScanContext;
@@ -23,9 +29,16 @@ pub(crate) mod prelude {
 
 include!("modules.rs");
 
+#[cfg(feature = "macho-module")]
+pub(crate) use macho::fat_slices as macho_fat_slices;
+
 /// Type of module's main function.
 type MainFn = fn(&[u8], Option<&[u8]>) -> Box<dyn MessageDyn>;
 
+/// Type of the function that tells if some data could be in the file format
+/// parsed by a module.
+type SniffFn = fn(&[u8]) -> bool;
+
 /// Describes a YARA module.
 pub(crate) struct Module {
     /// Pointer to the module's main function.
@@ -38,6 +51,13 @@ pub(crate) struct Module {
     /// for the YARA module. It allows iterating the fields declared by the
     /// module and obtaining their names and types.
     pub root_struct_descriptor: MessageDescriptor,
+    /// Function that quickly determines if the scanned data could be in the
+    /// file format parsed by the module, by looking at its magic bytes. When
+    /// this function returns `false` the data is not passed to the module's
+    /// main function, which receives an empty slice instead. Therefore, the
+    /// function must return `false` only if the module would fail to parse
+    /// the data anyway, so that the module's output doesn't change.
+    pub sniff_fn: Option<SniffFn>,
 }
 
 /// Macro that adds a module to the `BUILTIN_MODULES` map.
@@ -69,6 +89,7 @@ macro_rules! add_module {
                 main_fn: $main_fn,
                 rust_module_name: $rust_module_name,
                 root_struct_descriptor,
+                sniff_fn: None,
             },
         );
     }};
@@ -114,6 +135,31 @@ lazy_static! {
         // `add_modules.rs` will contain an `add_module!` statement for each
         // protobuf in `src/modules/protos` defining a YARA module.
         include!("add_modules.rs");
+
+        // Modules that parse some specific file format provide a function
+        // that determines if the data could be in that format.
+        #[allow(unused_macros)]
+        macro_rules! set_sniff_fn {
+            ($name:literal, $sniff_fn:path) => {
+                if let Some(module) = modules.get_mut($name) {
+                    module.sniff_fn = Some($sniff_fn as SniffFn);
+                }
+            };
+        }
+
+        #[cfg(feature = "dotnet-module")]
+        set_sniff_fn!("dotnet", dotnet::sniff);
+        #[cfg(feature = "elf-module")]
+        set_sniff_fn!("elf", elf::sniff);
+        #[cfg(feature = "javaclass-module")]
+        set_sniff_fn!("javaclass", javaclass::sniff);
+        #[cfg(feature = "macho-module")]
+        set_sniff_fn!("macho", macho::sniff);
+        #[cfg(feature = "pe-module")]
+        set_sniff_fn!("pe", pe::sniff);
+        #[cfg(feature = "wasm-module")]
+        set_sniff_fn!("wasm", wasm::sniff);
+
         modules
     };
 }
@@ -138,6 +184,15 @@ pub mod mods {
     ```
      */
 
+    /// Data structures defined by the `dex` module.
+    ///
+    /// The main structure produced by the module is [`dex::Dex`]. The rest of
+    /// them are used by one or more fields in the main structure.
+    ///
+    pub use super::protos::dex;
+    /// Data structure returned by the `dex` module.
+    pub use super::protos::dex::Dex;
+
     /// Data structures defined by the `dotnet` module.
     ///
     /// The main structure produced by the module is [`dotnet::Dotnet`]. The
@@ -156,6 +211,25 @@ pub mod mods {
     /// Data structure returned by the `elf` module.
     pub use super::protos::elf::ELF;
 
+    /// Data structures defined by the `gobin` module.
+    ///
+    /// The main structure produced by the module is [`gobin::GoBin`]. The
+    /// rest of them are used by one or more fields in the main structure.
+    ///
+    pub use super::protos::gobin;
+    /// Data structure returned by the `gobin` module.
+    pub use super::protos::gobin::GoBin;
+
+    /// Data structures defined by the `javaclass` module.
+    ///
+    /// The main structure produced by the module is
+    /// [`javaclass::JavaClass`]. The rest of them are used by one or more
+    /// fields in the main structure.
+    ///
+    pub use super::protos::javaclass;
+    /// Data structure returned by the `javaclass` module.
+    pub use super::protos::javaclass::JavaClass;
+
     /// Data structures defined by the `lnk` module.
     ///
     /// The main structure produced by the module is [`lnk::Lnk`]. The rest of
@@ -174,6 +248,24 @@ pub mod mods {
     /// Data structure returned by the `macho` module.
     pub use super::protos::macho::Macho;
 
+    /// Data structures defined by the `olevba` module.
+    ///
+    /// The main structure produced by the module is [`olevba::OleVba`]. The
+    /// rest of them are used by one or more fields in the main structure.
+    ///
+    pub use super::protos::olevba;
+    /// Data structure returned by the `olevba` module.
+    pub use super::protos::olevba::OleVba;
+
+    /// Data structures defined by the `pdf` module.
+    ///
+    /// The main structure produced by the module is [`pdf::PDF`]. The rest
+    /// of them are used by one or more fields in the main structure.
+    ///
+    pub use super::protos::pdf;
+    /// Data structure returned by the `pdf` module.
+    pub use super::protos::pdf::PDF;
+
     /// Data structures defined by the `pe` module.
     ///
     /// The main structure produced by the module is [`pe::PE`]. The rest
@@ -183,6 +275,24 @@ pub mod mods {
     /// Data structure returned by the `pe` module.
     pub use super::protos::pe::PE;
 
+    /// Data structures defined by the `pyc` module.
+    ///
+    /// The main structure produced by the module is [`pyc::Pyc`]. The rest
+    /// of them are used by one or more fields in the main structure.
+    ///
+    pub use super::protos::pyc;
+    /// Data structure returned by the `pyc` module.
+    pub use super::protos::pyc::Pyc;
+
+    /// Data structures defined by the `wasm` module.
+    ///
+    /// The main structure produced by the module is [`wasm::Wasm`]. The
+    /// rest of them are used by one or more fields in the main structure.
+    ///
+    pub use super::protos::wasm;
+    /// Data structure returned by the `wasm` module.
+    pub use super::protos::wasm::Wasm;
+
     /// A data structure contains the data returned by all modules.
     pub use super::protos::mods::Modules;
 
@@ -268,6 +378,13 @@ pub mod mods {
         info.dotnet = protobuf::MessageField(invoke::<Dotnet>(data));
         info.macho = protobuf::MessageField(invoke::<Macho>(data));
         info.lnk = protobuf::MessageField(invoke::<Lnk>(data));
+        info.dex = protobuf::MessageField(invoke::<Dex>(data));
+        info.pdf = protobuf::MessageField(invoke::<PDF>(data));
+        info.olevba = protobuf::MessageField(invoke::<OleVba>(data));
+        info.wasm = protobuf::MessageField(invoke::<Wasm>(data));
+        info.gobin = protobuf::MessageField(invoke::<GoBin>(data));
+        info.javaclass = protobuf::MessageField(invoke::<JavaClass>(data));
+        info.pyc = protobuf::MessageField(invoke::<Pyc>(data));
         info
     }
 
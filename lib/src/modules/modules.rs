@@ -3,12 +3,18 @@
 mod console;
 #[cfg(feature = "cuckoo-module")]
 mod cuckoo;
+#[cfg(feature = "dex-module")]
+mod dex;
 #[cfg(feature = "dotnet-module")]
 mod dotnet;
 #[cfg(feature = "elf-module")]
 mod elf;
+#[cfg(feature = "gobin-module")]
+mod gobin;
 #[cfg(feature = "hash-module")]
 mod hash;
+#[cfg(feature = "javaclass-module")]
+mod javaclass;
 #[cfg(feature = "lnk-module")]
 mod lnk;
 #[cfg(feature = "macho-module")]
@@ -17,8 +23,14 @@ mod macho;
 mod magic;
 #[cfg(feature = "math-module")]
 mod math;
+#[cfg(feature = "olevba-module")]
+mod olevba;
+#[cfg(feature = "pdf-module")]
+mod pdf;
 #[cfg(feature = "pe-module")]
 mod pe;
+#[cfg(feature = "pyc-module")]
+mod pyc;
 #[cfg(feature = "string-module")]
 mod string;
 #[cfg(feature = "test_proto2-module")]
@@ -28,4 +40,6 @@ mod test_proto3;
 #[cfg(feature = "text-module")]
 mod text;
 #[cfg(feature = "time-module")]
-mod time;
\ No newline at end of file
+mod time;
+#[cfg(feature = "wasm-module")]
+mod wasm;
\ No newline at end of file
@@ -0,0 +1,111 @@
+/*! YARA module that parses OLE2 compound files and extracts VBA macros.
+
+OLE2 compound files (also known as Compound File Binary Format) are used by
+legacy Microsoft Office documents (.doc, .xls, .ppt) among many others. This
+module lists the storages and streams in a compound file, and extracts the
+source code of the VBA macros it contains, similarly to `olevba` from
+[`oletools`][1].
+
+OOXML documents (.docm, .xlsm, .pptm) are ZIP archives that store their VBA
+project in a compound file named `vbaProject.bin`. When the scanned file is an
+OOXML document, the module locates `vbaProject.bin` inside the archive and
+parses it instead.
+
+This module is based on the [`MS-CFB`][2] and [`MS-OVBA`][3] specifications.
+
+[1]: https://github.com/decalage2/oletools
+[2]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-cfb/53989ce4-7b05-4f8d-829b-d08d6148375b
+[3]: https://learn.microsoft.com/en-us/openspecs/office_file_formats/ms-ovba/575462ba-bf67-4190-9fac-c275523c75fc
+ */
+
+use std::io::{Cursor, Read};
+
+use protobuf::EnumOrUnknown;
+
+use crate::modules::prelude::*;
+use crate::modules::protos::olevba::*;
+
+mod parser;
+#[cfg(test)]
+mod tests;
+mod vba;
+
+/// Maximum size of the `vbaProject.bin` file that will be extracted from
+/// an OOXML document. Larger files are ignored.
+const MAX_VBA_PROJECT_SIZE: u64 = 64 * 1024 * 1024;
+
+#[module_main]
+fn main(data: &[u8], _meta: Option<&[u8]>) -> OleVba {
+    let mut result = OleVba::new();
+
+    result.is_ole = Some(false);
+    result.is_ooxml = Some(false);
+    result.has_macros = Some(false);
+
+    if data.starts_with(parser::SIGNATURE) {
+        if let Some(cf) = parser::CompoundFile::parse(data) {
+            result.is_ole = Some(true);
+            parse_compound_file(&cf, &mut result);
+        }
+    } else if data.starts_with(b"PK\x03\x04") {
+        if let Some(vba_project) = extract_vba_project(data) {
+            if let Some(cf) = parser::CompoundFile::parse(&vba_project) {
+                result.is_ooxml = Some(true);
+                parse_compound_file(&cf, &mut result);
+            }
+        }
+    }
+
+    result
+}
+
+fn parse_compound_file(cf: &parser::CompoundFile, result: &mut OleVba) {
+    for (path, entry) in cf.walk() {
+        let mut e = Entry::new();
+        e.name = Some(path);
+        e.type_ =
+            Some(EnumOrUnknown::<EntryType>::from_i32(entry.entry_type as i32));
+        e.size = Some(if entry.entry_type == parser::STREAM {
+            entry.size
+        } else {
+            0
+        });
+        result.entries.push(e);
+    }
+
+    for module in vba::modules(cf) {
+        let mut m = Macro::new();
+        m.name = Some(module.name);
+        m.stream = Some(module.stream);
+        m.code = Some(module.code);
+        result.macros.push(m);
+    }
+
+    result.has_macros = Some(!result.macros.is_empty());
+}
+
+/// Returns the content of `vbaProject.bin` if `data` is a ZIP archive that
+/// contains that file. The file is usually at `word/vbaProject.bin`,
+/// `xl/vbaProject.bin` or `ppt/vbaProject.bin`.
+fn extract_vba_project(data: &[u8]) -> Option<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).ok()?;
+
+    let name = archive
+        .file_names()
+        .find(|name| name.to_ascii_lowercase().ends_with("vbaproject.bin"))?
+        .to_string();
+
+    let file = archive.by_name(name.as_str()).ok()?;
+
+    if file.size() > MAX_VBA_PROJECT_SIZE {
+        return None;
+    }
+
+    let mut vba_project = Vec::with_capacity(file.size() as usize);
+
+    // The size declared in the archive can't be trusted, limit the number
+    // of bytes read from the decompressor.
+    file.take(MAX_VBA_PROJECT_SIZE).read_to_end(&mut vba_project).ok()?;
+
+    Some(vba_project)
+}
@@ -0,0 +1,428 @@
+use nom::bytes::complete::{tag, take};
+use nom::multi::count;
+use nom::number::complete::{le_u16, le_u32, le_u64, u8};
+use nom::sequence::tuple;
+use nom::IResult;
+
+/// Signature at the start of every compound file.
+pub const SIGNATURE: &[u8] = b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1";
+
+/// Sector numbers greater than this one have special meanings (end of
+/// chain, free sector, etc.)
+const MAX_REG_SECT: u32 = 0xfffffffa;
+
+/// Value used in directory entries for indicating that there's no sibling
+/// or child.
+const NO_STREAM: u32 = 0xffffffff;
+
+/// Size of a directory entry.
+const DIR_ENTRY_SIZE: usize = 128;
+
+/// Size of a sector in the mini stream.
+const MINI_SECTOR_SIZE: usize = 64;
+
+/// Maximum nesting level for storages. Deeper storages are ignored.
+const MAX_DEPTH: usize = 32;
+
+pub const STORAGE: u8 = 1;
+pub const STREAM: u8 = 2;
+pub const ROOT: u8 = 5;
+
+/// An entry in the directory of a compound file.
+pub struct DirEntry {
+    pub name: String,
+    pub entry_type: u8,
+    left: u32,
+    right: u32,
+    child: u32,
+    start: u32,
+    pub size: u64,
+}
+
+/// A compound file, also known as OLE2 file, as described in [MS-CFB].
+///
+/// [MS-CFB]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-cfb/53989ce4-7b05-4f8d-829b-d08d6148375b
+pub struct CompoundFile<'a> {
+    data: &'a [u8],
+    sector_size: usize,
+    mini_stream_cutoff: u64,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    mini_stream: Vec<u8>,
+    entries: Vec<DirEntry>,
+}
+
+impl<'a> CompoundFile<'a> {
+    /// Parses a compound file. Returns [`None`] if the data is not a valid
+    /// compound file.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let (_, header) = header(data).ok()?;
+
+        // Version 3 files use 512-bytes sectors, version 4 use 4096-bytes
+        // sectors.
+        if header.sector_shift != 9 && header.sector_shift != 12 {
+            return None;
+        }
+
+        let mut cf = Self {
+            data,
+            sector_size: 1 << header.sector_shift,
+            mini_stream_cutoff: header.mini_stream_cutoff as u64,
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            mini_stream: Vec::new(),
+            entries: Vec::new(),
+        };
+
+        cf.fat = cf.load_fat(&header);
+
+        cf.mini_fat = cf
+            .read_chain(header.first_mini_fat_sector)
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+
+        let dir = cf.read_chain(header.first_dir_sector);
+
+        for entry in dir.chunks_exact(DIR_ENTRY_SIZE) {
+            match dir_entry(entry) {
+                Ok((_, mut entry)) => {
+                    // In version 3 files the most significant 32 bits of
+                    // the size may contain garbage.
+                    if header.sector_shift == 9 {
+                        entry.size &= 0xffffffff;
+                    }
+                    cf.entries.push(entry)
+                }
+                Err(_) => break,
+            }
+        }
+
+        // The first entry must be the root entry, which contains the
+        // mini stream.
+        let root = cf.entries.first()?;
+
+        if root.entry_type != ROOT {
+            return None;
+        }
+
+        let mut mini_stream = cf.read_chain(root.start);
+        mini_stream.truncate(root.size as usize);
+        cf.mini_stream = mini_stream;
+
+        Some(cf)
+    }
+
+    /// Returns the entries in the compound file with their full path,
+    /// walking the directory tree in depth-first order. The root entry is
+    /// not included.
+    pub fn walk(&self) -> Vec<(String, &DirEntry)> {
+        let mut result = Vec::new();
+        let mut visited = vec![false; self.entries.len()];
+
+        visited[0] = true;
+
+        self.walk_storage(
+            self.entries[0].child,
+            "",
+            0,
+            &mut visited,
+            &mut result,
+        );
+
+        result
+    }
+
+    fn walk_storage<'b>(
+        &'b self,
+        child: u32,
+        prefix: &str,
+        depth: usize,
+        visited: &mut [bool],
+        result: &mut Vec<(String, &'b DirEntry)>,
+    ) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+
+        // The children of a storage form a red-black tree, which is
+        // traversed in order.
+        let mut stack = Vec::new();
+        let mut current = child;
+
+        loop {
+            while let Some(entry) = self.unvisited(current, visited) {
+                visited[current as usize] = true;
+                stack.push(current);
+                current = entry.left;
+            }
+
+            let Some(index) = stack.pop() else {
+                break;
+            };
+
+            let entry = &self.entries[index as usize];
+
+            let path = if prefix.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{}/{}", prefix, entry.name)
+            };
+
+            result.push((path.clone(), entry));
+
+            if entry.entry_type == STORAGE {
+                self.walk_storage(
+                    entry.child,
+                    path.as_str(),
+                    depth + 1,
+                    visited,
+                    result,
+                );
+            }
+
+            current = entry.right;
+        }
+    }
+
+    fn unvisited(&self, index: u32, visited: &[bool]) -> Option<&DirEntry> {
+        if index == NO_STREAM || *visited.get(index as usize)? {
+            return None;
+        }
+        self.entries.get(index as usize)
+    }
+
+    /// Returns the content of a stream.
+    pub fn stream(&self, entry: &DirEntry) -> Vec<u8> {
+        let mut data = if entry.size < self.mini_stream_cutoff {
+            self.read_mini_chain(entry.start)
+        } else {
+            self.read_chain(entry.start)
+        };
+        data.truncate(entry.size as usize);
+        data
+    }
+
+    fn load_fat(&self, header: &Header) -> Vec<u32> {
+        // The first 109 FAT sectors are listed in the header, the rest
+        // are listed in a chain of DIFAT sectors. The last item in each
+        // DIFAT sector is the number of the next DIFAT sector.
+        let mut fat_sectors: Vec<u32> = header.difat.clone();
+        let mut difat_sector = header.first_difat_sector;
+
+        for _ in 0..header.num_difat_sectors {
+            let Some(sector) = self.sector(difat_sector) else {
+                break;
+            };
+            let mut items: Vec<u32> = sector
+                .chunks_exact(4)
+                .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+                .collect();
+            difat_sector = items.pop().unwrap_or(NO_STREAM);
+            fat_sectors.extend(items);
+        }
+
+        let mut fat = Vec::new();
+
+        for sector in fat_sectors
+            .into_iter()
+            .filter(|s| *s < MAX_REG_SECT)
+            .take(header.num_fat_sectors as usize)
+        {
+            if let Some(sector) = self.sector(sector) {
+                fat.extend(
+                    sector
+                        .chunks_exact(4)
+                        .map(|c| u32::from_le_bytes(c.try_into().unwrap())),
+                );
+            }
+        }
+
+        fat
+    }
+
+    /// Returns the sector with the given number. The last sector in the
+    /// file can be incomplete.
+    fn sector(&self, sector: u32) -> Option<&'a [u8]> {
+        if sector >= MAX_REG_SECT {
+            return None;
+        }
+        let start = (sector as usize + 1).checked_mul(self.sector_size)?;
+        let end = start.saturating_add(self.sector_size).min(self.data.len());
+        self.data.get(start..end)
+    }
+
+    /// Reads the chain of sectors that starts at `start`.
+    fn read_chain(&self, start: u32) -> Vec<u8> {
+        let mut result = Vec::new();
+        let mut sector = start;
+
+        // A chain can't be longer than the FAT itself, this prevents
+        // infinite loops with circular chains.
+        for _ in 0..self.fat.len() {
+            let Some(data) = self.sector(sector) else {
+                break;
+            };
+            result.extend_from_slice(data);
+            sector = match self.fat.get(sector as usize) {
+                Some(next) => *next,
+                None => break,
+            };
+        }
+
+        result
+    }
+
+    /// Reads the chain of sectors in the mini stream that starts at
+    /// `start`.
+    fn read_mini_chain(&self, start: u32) -> Vec<u8> {
+        let mut result = Vec::new();
+        let mut sector = start;
+
+        for _ in 0..self.mini_fat.len() {
+            let Some(data) = (sector as usize)
+                .checked_mul(MINI_SECTOR_SIZE)
+                .and_then(|start| {
+                    self.mini_stream.get(start..start + MINI_SECTOR_SIZE)
+                })
+            else {
+                break;
+            };
+            result.extend_from_slice(data);
+            sector = match self.mini_fat.get(sector as usize) {
+                Some(next) => *next,
+                None => break,
+            };
+        }
+
+        result
+    }
+}
+
+struct Header {
+    sector_shift: u16,
+    num_fat_sectors: u32,
+    first_dir_sector: u32,
+    mini_stream_cutoff: u32,
+    first_mini_fat_sector: u32,
+    first_difat_sector: u32,
+    num_difat_sectors: u32,
+    difat: Vec<u32>,
+}
+
+fn header(input: &[u8]) -> IResult<&[u8], Header> {
+    let (
+        input,
+        (
+            _signature,
+            _clsid,
+            _minor_version,
+            _major_version,
+            _byte_order,
+            sector_shift,
+            _mini_sector_shift,
+            _reserved,
+            _num_dir_sectors,
+            num_fat_sectors,
+            first_dir_sector,
+        ),
+    ) = tuple((
+        tag(SIGNATURE),
+        take(16_usize),
+        le_u16,
+        le_u16,
+        tag(b"\xFE\xFF"),
+        le_u16,
+        le_u16,
+        take(6_usize),
+        le_u32,
+        le_u32,
+        le_u32,
+    ))(input)?;
+
+    let (
+        input,
+        (
+            _transaction_signature,
+            mini_stream_cutoff,
+            first_mini_fat_sector,
+            _num_mini_fat_sectors,
+            first_difat_sector,
+            num_difat_sectors,
+            difat,
+        ),
+    ) = tuple((
+        le_u32,
+        le_u32,
+        le_u32,
+        le_u32,
+        le_u32,
+        le_u32,
+        count(le_u32, 109),
+    ))(input)?;
+
+    Ok((
+        input,
+        Header {
+            sector_shift,
+            num_fat_sectors,
+            first_dir_sector,
+            mini_stream_cutoff,
+            first_mini_fat_sector,
+            first_difat_sector,
+            num_difat_sectors,
+            difat,
+        },
+    ))
+}
+
+fn dir_entry(input: &[u8]) -> IResult<&[u8], DirEntry> {
+    let (
+        input,
+        (
+            name,
+            name_len,
+            entry_type,
+            _color,
+            left,
+            right,
+            child,
+            _clsid,
+            _state_bits,
+            _creation_time,
+            _modified_time,
+            start,
+            size,
+        ),
+    ) = tuple((
+        count(le_u16, 32),
+        le_u16,
+        u8,
+        u8,
+        le_u32,
+        le_u32,
+        le_u32,
+        take(16_usize),
+        le_u32,
+        le_u64,
+        le_u64,
+        le_u32,
+        le_u64,
+    ))(input)?;
+
+    // The name length is in bytes, and includes the null terminator.
+    let name_len = (name_len as usize / 2).saturating_sub(1).min(name.len());
+
+    Ok((
+        input,
+        DirEntry {
+            name: String::from_utf16_lossy(&name[..name_len]),
+            entry_type,
+            left,
+            right,
+            child,
+            start,
+            size,
+        },
+    ))
+}
@@ -0,0 +1,92 @@
+use crate::modules::olevba::vba::decompress;
+use crate::modules::tests::create_binary_from_zipped_ihex;
+use crate::tests::rule_false;
+use crate::tests::rule_true;
+use crate::tests::test_rule;
+
+#[test]
+fn test_olevba_module() {
+    let doc = create_binary_from_zipped_ihex(
+        "src/modules/olevba/tests/testdata/macro.doc.in.zip",
+    );
+
+    let docm = create_binary_from_zipped_ihex(
+        "src/modules/olevba/tests/testdata/macro.docm.in.zip",
+    );
+
+    rule_false!(
+        r#"
+        import "olevba"
+        rule test {
+          condition:
+            olevba.is_ole or olevba.is_ooxml or olevba.has_macros
+        }
+        "#,
+        &[]
+    );
+
+    rule_true!(
+        r#"
+        import "olevba"
+        rule test {
+          condition:
+            olevba.is_ole and
+            not olevba.is_ooxml and
+            olevba.has_macros and
+            olevba.entries[0].name == "WordDocument" and
+            olevba.entries[0].type == olevba.EntryType.STREAM and
+            olevba.entries[0].size == 5120 and
+            olevba.entries[1].name == "Macros" and
+            olevba.entries[1].type == olevba.EntryType.STORAGE and
+            olevba.entries[4].name == "Macros/VBA/_VBA_PROJECT"
+        }
+        "#,
+        &doc
+    );
+
+    rule_true!(
+        r#"
+        import "olevba"
+        rule test {
+          condition:
+            olevba.macros[0].name == "ThisDocument" and
+            olevba.macros[0].stream == "Macros/VBA/ThisDocument" and
+            olevba.macros[0].code contains "Sub AutoOpen()" and
+            olevba.macros[0].code icontains "POWERSHELL -ENC" and
+            olevba.macros[1].name == "Module1" and
+            olevba.macros[1].code endswith "' padding padding padding padding\r\n"
+        }
+        "#,
+        &doc
+    );
+
+    rule_true!(
+        r#"
+        import "olevba"
+        rule test {
+          condition:
+            not olevba.is_ole and
+            olevba.is_ooxml and
+            olevba.entries[0].name == "PROJECT" and
+            olevba.macros[0].stream == "VBA/ThisDocument" and
+            for any m in olevba.macros : (m.code contains "Shell \"powershell")
+        }
+        "#,
+        &docm
+    );
+}
+
+#[test]
+fn test_decompress_limit() {
+    // A compressed container with three uncompressed chunks of 4096 bytes.
+    let mut data = vec![0x01];
+
+    for _ in 0..3 {
+        data.extend_from_slice(&0x3fff_u16.to_le_bytes());
+        data.extend_from_slice(&[b'A'; 4096]);
+    }
+
+    assert_eq!(decompress(&data, usize::MAX).unwrap().len(), 3 * 4096);
+    assert_eq!(decompress(&data, 5000).unwrap().len(), 5000);
+    assert_eq!(decompress(&data, 0).unwrap().len(), 0);
+}
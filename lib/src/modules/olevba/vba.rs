@@ -0,0 +1,261 @@
+/*! Extraction of VBA macros from compound files.
+
+VBA projects are stored in a storage named `VBA`, which contains a stream
+named `dir` that describes the project's modules, and one stream for each
+module. The `dir` stream and the module's source code are compressed with
+the algorithm described in [MS-OVBA] section 2.4.1.
+
+[MS-OVBA]: https://learn.microsoft.com/en-us/openspecs/office_file_formats/ms-ovba/575462ba-bf67-4190-9fac-c275523c75fc
+ */
+
+use std::collections::HashSet;
+
+use crate::modules::olevba::parser::{CompoundFile, STREAM};
+
+/// Size of a decompressed chunk.
+const CHUNK_SIZE: usize = 4096;
+
+/// Maximum size of all the data decompressed from a compound file, including
+/// `dir` streams and the source code of all modules. Any data beyond this
+/// limit is discarded.
+const MAX_DECOMPRESSED_SIZE: usize = 16 * 1024 * 1024;
+
+/// A VBA module.
+pub struct Module {
+    pub name: String,
+    pub stream: String,
+    pub code: Vec<u8>,
+}
+
+/// Information about a module, as found in the `dir` stream.
+#[derive(Default)]
+struct ModuleInfo {
+    name: Option<String>,
+    stream_name: Option<String>,
+    offset: u32,
+}
+
+/// Returns the VBA modules in a compound file.
+pub fn modules(cf: &CompoundFile) -> Vec<Module> {
+    let entries = cf.walk();
+    let mut result = Vec::new();
+    let mut budget = MAX_DECOMPRESSED_SIZE;
+
+    // Module streams already processed. Multiple MODULE records can point
+    // to the same stream, but each stream is decompressed only once.
+    let mut processed = HashSet::new();
+
+    // Find every `dir` stream inside a `VBA` storage. In Word documents it
+    // lives in `Macros/VBA/dir`, in Excel workbooks in
+    // `_VBA_PROJECT_CUR/VBA/dir`, and in `vbaProject.bin` at `VBA/dir`.
+    for (path, entry) in entries.iter() {
+        if entry.entry_type != STREAM {
+            continue;
+        }
+
+        let Some(storage) = path.strip_suffix("/dir") else {
+            continue;
+        };
+
+        if !storage.rsplit('/').next().unwrap().eq_ignore_ascii_case("VBA") {
+            continue;
+        }
+
+        let Some(dir) = decompress(cf.stream(entry).as_slice(), budget)
+        else {
+            continue;
+        };
+
+        budget -= dir.len();
+
+        for info in parse_dir(dir.as_slice()) {
+            let Some(stream_name) = info.stream_name else {
+                continue;
+            };
+
+            let stream_path = format!("{}/{}", storage, stream_name);
+
+            let Some((stream_path, stream)) = entries.iter().find(|(p, e)| {
+                e.entry_type == STREAM && p.eq_ignore_ascii_case(&stream_path)
+            }) else {
+                continue;
+            };
+
+            if budget == 0 {
+                return result;
+            }
+
+            if !processed.insert(stream_path.to_ascii_lowercase()) {
+                continue;
+            }
+
+            // The module stream starts with a performance cache, the
+            // compressed source code starts at the offset indicated in
+            // the `dir` stream.
+            let code = cf
+                .stream(stream)
+                .get(info.offset as usize..)
+                .and_then(|data| decompress(data, budget));
+
+            if let Some(code) = code {
+                budget -= code.len();
+                result.push(Module {
+                    name: info.name.unwrap_or_else(|| stream_name.clone()),
+                    stream: stream_path.clone(),
+                    code,
+                });
+            }
+        }
+    }
+
+    result
+}
+
+/// Parses the decompressed `dir` stream, which is a sequence of records
+/// that start with a 16-bits identifier and a 32-bits size.
+fn parse_dir(mut dir: &[u8]) -> Vec<ModuleInfo> {
+    let mut modules = Vec::new();
+    let mut module = ModuleInfo::default();
+
+    while dir.len() >= 6 {
+        let id = u16::from_le_bytes([dir[0], dir[1]]);
+        let mut size =
+            u32::from_le_bytes([dir[2], dir[3], dir[4], dir[5]]) as usize;
+
+        // PROJECTVERSION is the only record where the size doesn't match
+        // the size of the data, its size field is always 4, but it's
+        // followed by 6 bytes.
+        if id == 0x0009 {
+            size = 6;
+        }
+
+        let Some(data) = dir.get(6..6 + size) else {
+            break;
+        };
+
+        match id {
+            // MODULENAME
+            0x0019 => {
+                module.name = Some(String::from_utf8_lossy(data).into_owned())
+            }
+            // MODULENAMEUNICODE
+            0x0047 => module.name = Some(utf16(data)),
+            // MODULESTREAMNAME
+            0x001a => {
+                module.stream_name =
+                    Some(String::from_utf8_lossy(data).into_owned())
+            }
+            // MODULESTREAMNAME unicode version.
+            0x0032 => module.stream_name = Some(utf16(data)),
+            // MODULEOFFSET
+            0x0031 if size == 4 => {
+                module.offset = u32::from_le_bytes(data.try_into().unwrap())
+            }
+            // Terminator of a MODULE record.
+            0x002b => modules.push(std::mem::take(&mut module)),
+            // Terminator of the `dir` stream.
+            0x0010 => break,
+            _ => {}
+        }
+
+        dir = &dir[6 + size..];
+    }
+
+    modules
+}
+
+fn utf16(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Decompresses a compressed container, as described in [MS-OVBA] section
+/// 2.4.1. Returns [`None`] if the data doesn't start with a valid
+/// signature. The decompressed data is truncated to `limit` bytes.
+///
+/// [MS-OVBA]: https://learn.microsoft.com/en-us/openspecs/office_file_formats/ms-ovba/575462ba-bf67-4190-9fac-c275523c75fc
+pub fn decompress(data: &[u8], limit: usize) -> Option<Vec<u8>> {
+    let (&signature, mut data) = data.split_first()?;
+
+    if signature != 0x01 {
+        return None;
+    }
+
+    let mut result = Vec::with_capacity((data.len() * 2).min(limit));
+
+    while data.len() >= 2 && result.len() < limit {
+        let header = u16::from_le_bytes([data[0], data[1]]);
+
+        // The chunk size in the header is the size of the chunk minus 3,
+        // including the 2 bytes of the header.
+        let chunk_size = (header & 0x0fff) as usize + 3;
+        let compressed = header & 0x8000 != 0;
+
+        let chunk = &data[2..chunk_size.min(data.len())];
+
+        if compressed {
+            decompress_chunk(chunk, &mut result);
+        } else {
+            result.extend_from_slice(&chunk[..chunk.len().min(CHUNK_SIZE)]);
+        }
+
+        data = &data[chunk_size.min(data.len())..];
+    }
+
+    result.truncate(limit);
+
+    Some(result)
+}
+
+fn decompress_chunk(mut chunk: &[u8], result: &mut Vec<u8>) {
+    let chunk_start = result.len();
+
+    while let Some((&flags, rest)) = chunk.split_first() {
+        chunk = rest;
+        for bit in 0..8 {
+            // A chunk can't be decompressed into more than 4096 bytes.
+            if result.len() - chunk_start >= CHUNK_SIZE {
+                return;
+            }
+            if flags & (1 << bit) == 0 {
+                // Literal token.
+                let Some((&byte, rest)) = chunk.split_first() else {
+                    return;
+                };
+                result.push(byte);
+                chunk = rest;
+            } else {
+                // Copy token.
+                if chunk.len() < 2 {
+                    return;
+                }
+
+                let token = u16::from_le_bytes([chunk[0], chunk[1]]);
+                chunk = &chunk[2..];
+
+                // The number of bits used for the offset depends on the
+                // position within the decompressed chunk.
+                let difference = result.len() - chunk_start;
+                let bit_count = (usize::BITS - difference.saturating_sub(1).leading_zeros())
+                    .max(4);
+
+                let length_mask = 0xffff_u16 >> bit_count;
+                let length = (token & length_mask) as usize + 3;
+                let offset = (token >> (16 - bit_count)) as usize + 1;
+
+                if offset > difference {
+                    return;
+                }
+
+                // The source and destination may overlap, so bytes must
+                // be copied one by one.
+                for _ in 0..length.min(CHUNK_SIZE - difference) {
+                    result.push(result[result.len() - offset]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,37 @@
+/*! YARA module that parses PDF documents.
+
+This module walks the objects in a PDF document and exposes structural
+information about it, like its cross-reference sections and trailer, the
+number of objects and streams, the filters used by those streams, and
+whether the document contains features that are frequently abused by
+malicious documents, like JavaScript code, actions executed when the
+document is opened, or embedded files.
+
+Names in PDF documents can contain escape sequences (e.g. `/J#61vaScript`
+is equivalent to `/JavaScript`), the module decodes those sequences, which
+makes rules based on its output more resilient than rules based on literal
+strings.
+
+This module is based on the [`PDF 1.7 specification`][1].
+
+[1]: https://opensource.adobe.com/dc-acrobat-sdk-docs/pdfstandards/PDF32000_2008.pdf
+ */
+
+use crate::modules::prelude::*;
+use crate::modules::protos::pdf::*;
+
+mod parser;
+#[cfg(test)]
+mod tests;
+
+#[module_main]
+fn main(data: &[u8], _meta: Option<&[u8]>) -> PDF {
+    match parser::PdfParser::new(data).parse() {
+        Some(pdf) => pdf,
+        None => {
+            let mut pdf = PDF::new();
+            pdf.is_pdf = Some(false);
+            pdf
+        }
+    }
+}
@@ -0,0 +1,548 @@
+use memchr::memmem;
+
+use crate::modules::protos::pdf::{Trailer, Xref, PDF};
+
+/// Maximum nesting level for arrays and dictionaries. Objects nested deeper
+/// than this are ignored.
+const MAX_DEPTH: usize = 64;
+
+/// A token produced by the PDF lexer.
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Integer(i64),
+    Real,
+    Name(Vec<u8>),
+    String,
+    DictStart,
+    DictEnd,
+    ArrayStart,
+    ArrayEnd,
+    Keyword(&'a [u8]),
+}
+
+/// A PDF object. Only the object types that are relevant for the module are
+/// represented, the rest are [`Object::Other`].
+#[derive(Debug)]
+enum Object {
+    Integer(i64),
+    Name(Vec<u8>),
+    Ref(i64),
+    Array(Vec<Object>),
+    Dict(Vec<(Vec<u8>, Object)>),
+    Other,
+}
+
+impl Object {
+    fn get(&self, key: &[u8]) -> Option<&Object> {
+        match self {
+            Object::Dict(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_slice() == key)
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_integer(&self) -> Option<i64> {
+        match self {
+            Object::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    fn as_ref(&self) -> Option<i64> {
+        match self {
+            Object::Ref(r) => Some(*r),
+            _ => None,
+        }
+    }
+}
+
+/// A PDF parser.
+///
+/// The parser doesn't use the cross-reference table for locating objects,
+/// instead it walks the whole file sequentially, which allows processing
+/// documents with broken or missing cross-reference tables, and objects
+/// that are not referenced by any table (e.g. objects hidden in a previous
+/// revision of the document). Objects inside object streams are not
+/// parsed, as that would require decompressing the streams.
+pub struct PdfParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    result: PDF,
+}
+
+impl<'a> PdfParser<'a> {
+    /// Creates a new parser for the given data.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0, result: PDF::new() }
+    }
+
+    /// Parses the PDF document. Returns [`None`] if the data doesn't look
+    /// like a PDF.
+    pub fn parse(mut self) -> Option<PDF> {
+        // The header is not required to be at the very start of the file,
+        // readers accept some garbage before it.
+        let header_start = memmem::find(
+            &self.data[..self.data.len().min(1024)],
+            b"%PDF-",
+        )?;
+
+        let version = &self.data[header_start + 5..];
+        let version_len = version
+            .iter()
+            .take_while(|c| c.is_ascii_digit() || **c == b'.')
+            .count();
+
+        self.result.is_pdf = Some(true);
+        self.result.version = Some(
+            String::from_utf8_lossy(&version[..version_len]).into_owned(),
+        );
+
+        self.result.num_objects = Some(0);
+        self.result.num_streams = Some(0);
+        self.result.has_javascript = Some(false);
+        self.result.has_open_action = Some(false);
+        self.result.has_launch = Some(false);
+        self.result.has_embedded_files = Some(false);
+
+        self.pos = header_start;
+
+        // Start offsets of the last two tokens, used for determining where
+        // an indirect object starts (i.e: the offset of `N` in `N G obj`).
+        let mut history = [0_usize; 2];
+
+        loop {
+            self.skip_whitespace();
+            let token_start = self.pos;
+
+            let Some(token) = self.next_token() else {
+                break;
+            };
+
+            match token {
+                Token::Keyword(b"obj") => {
+                    self.result.num_objects =
+                        Some(self.result.num_objects.unwrap_or(0) + 1);
+                    self.parse_indirect_object(history[0]);
+                }
+                Token::Keyword(b"stream") => {
+                    self.skip_stream(None);
+                }
+                Token::Keyword(b"xref") => {
+                    self.parse_xref_table(token_start);
+                }
+                Token::Keyword(b"trailer") => {
+                    if let Some(trailer) = self.parse_object(0) {
+                        self.set_trailer(&trailer);
+                    }
+                }
+                Token::Keyword(b"startxref") => {
+                    if let Some(Token::Integer(offset)) = self.next_token() {
+                        self.result.startxref = offset.try_into().ok();
+                    }
+                }
+                _ => {}
+            }
+
+            history = [history[1], token_start];
+        }
+
+        Some(self.result)
+    }
+
+    /// Parses the object that follows the `obj` keyword, and the stream
+    /// associated to it, if any. `start` is the offset where the object
+    /// starts.
+    fn parse_indirect_object(&mut self, start: usize) {
+        let Some(object) = self.parse_object(0) else {
+            return;
+        };
+
+        let pos = self.pos;
+
+        if self.next_token() != Some(Token::Keyword(b"stream")) {
+            self.pos = pos;
+            return;
+        }
+
+        self.result.num_streams =
+            Some(self.result.num_streams.unwrap_or(0) + 1);
+
+        match object.get(b"Filter") {
+            Some(Object::Name(name)) => self.add_filter(name),
+            Some(Object::Array(filters)) => {
+                for filter in filters {
+                    if let Object::Name(name) = filter {
+                        self.add_filter(name);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(Object::Name(name)) = object.get(b"Type") {
+            if name == b"XRef" {
+                self.add_xref_stream(start, &object);
+            }
+        }
+
+        self.skip_stream(object.get(b"Length").and_then(Object::as_integer));
+    }
+
+    /// Parses a cross-reference table that starts at `start`. The `xref`
+    /// keyword has been already consumed.
+    fn parse_xref_table(&mut self, start: usize) {
+        let mut num_entries = 0_u64;
+
+        // The table contains one or more subsections, each of them starting
+        // with the number of the first object and the number of entries
+        // in the subsection, followed by the entries. Each entry contains
+        // an offset, a generation number, and a `n` or `f` keyword.
+        'subsections: loop {
+            let pos = self.pos;
+
+            let (Some(Token::Integer(_)), Some(Token::Integer(count))) =
+                (self.next_token(), self.next_token())
+            else {
+                self.pos = pos;
+                break;
+            };
+
+            for _ in 0..count {
+                let pos = self.pos;
+                match (self.next_token(), self.next_token(), self.next_token())
+                {
+                    (
+                        Some(Token::Integer(_)),
+                        Some(Token::Integer(_)),
+                        Some(Token::Keyword(b"n" | b"f")),
+                    ) => num_entries += 1,
+                    _ => {
+                        self.pos = pos;
+                        break 'subsections;
+                    }
+                }
+            }
+        }
+
+        let mut xref = Xref::new();
+
+        xref.offset = Some(start as u64);
+        xref.num_entries = Some(num_entries);
+        xref.is_stream = Some(false);
+
+        self.result.xrefs.push(xref);
+    }
+
+    fn add_xref_stream(&mut self, start: usize, dict: &Object) {
+        // The number of entries is the sum of the counts in /Index, which
+        // contains pairs of (first object, count). When /Index is missing
+        // the stream contains /Size entries.
+        let num_entries = match dict.get(b"Index") {
+            Some(Object::Array(index)) => index
+                .iter()
+                .skip(1)
+                .step_by(2)
+                .filter_map(Object::as_integer)
+                .map(|count| count.max(0) as u64)
+                .fold(0_u64, |acc, count| acc.saturating_add(count)),
+            _ => dict
+                .get(b"Size")
+                .and_then(Object::as_integer)
+                .map(|size| size.max(0) as u64)
+                .unwrap_or(0),
+        };
+
+        let mut xref = Xref::new();
+
+        xref.offset = Some(start as u64);
+        xref.num_entries = Some(num_entries);
+        xref.is_stream = Some(true);
+
+        self.result.xrefs.push(xref);
+        self.set_trailer(dict);
+    }
+
+    /// Sets the trailer from a trailer dictionary, or from the dictionary
+    /// of a cross-reference stream, which contains the same keys.
+    fn set_trailer(&mut self, dict: &Object) {
+        if !matches!(dict, Object::Dict(_)) {
+            return;
+        }
+
+        let mut trailer = Trailer::new();
+
+        trailer.size = dict.get(b"Size").and_then(Object::as_integer);
+        trailer.root = dict.get(b"Root").and_then(Object::as_ref);
+        trailer.info = dict.get(b"Info").and_then(Object::as_ref);
+        trailer.prev = dict.get(b"Prev").and_then(Object::as_integer);
+        trailer.encrypted = Some(dict.get(b"Encrypt").is_some());
+
+        self.result.trailer = Some(trailer).into();
+    }
+
+    fn add_filter(&mut self, name: &[u8]) {
+        let name = String::from_utf8_lossy(name);
+        if !self.result.filters.iter().any(|f| *f == name) {
+            self.result.filters.push(name.into_owned());
+        }
+    }
+
+    /// Skips the content of a stream. The `stream` keyword has been already
+    /// consumed.
+    fn skip_stream(&mut self, length: Option<i64>) {
+        // The `stream` keyword must be followed by CRLF or LF.
+        if self.data[self.pos..].starts_with(b"\r\n") {
+            self.pos += 2;
+        } else if self.data[self.pos..].starts_with(b"\n") {
+            self.pos += 1;
+        }
+
+        // Trust /Length only if `endstream` is found where expected.
+        // Otherwise, look for the next `endstream`.
+        if let Some(end) = length
+            .and_then(|length| usize::try_from(length).ok())
+            .and_then(|length| self.pos.checked_add(length))
+            .filter(|end| *end <= self.data.len())
+        {
+            let after = &self.data[end..];
+            let ws = after
+                .iter()
+                .take_while(|c| c.is_ascii_whitespace())
+                .count();
+            if after[ws..].starts_with(b"endstream") {
+                self.pos = end + ws + b"endstream".len();
+                return;
+            }
+        }
+
+        self.pos = match memmem::find(&self.data[self.pos..], b"endstream") {
+            Some(offset) => self.pos + offset + b"endstream".len(),
+            None => self.data.len(),
+        };
+    }
+
+    /// Parses the next object.
+    fn parse_object(&mut self, depth: usize) -> Option<Object> {
+        let token = self.next_token()?;
+        Some(self.parse_object_from(token, depth))
+    }
+
+    /// Parses an object that starts with the given token.
+    fn parse_object_from(&mut self, token: Token<'a>, depth: usize) -> Object {
+        match token {
+            Token::Integer(n) => {
+                // Check if the integer is the start of a reference in the
+                // form `N G R`.
+                let pos = self.pos;
+                if let (Some(Token::Integer(_)), Some(Token::Keyword(b"R"))) =
+                    (self.next_token(), self.next_token())
+                {
+                    Object::Ref(n)
+                } else {
+                    self.pos = pos;
+                    Object::Integer(n)
+                }
+            }
+            Token::Name(name) => Object::Name(name),
+            Token::ArrayStart if depth < MAX_DEPTH => {
+                let mut items = Vec::new();
+                while let Some(token) = self.next_token() {
+                    if token == Token::ArrayEnd {
+                        break;
+                    }
+                    items.push(self.parse_object_from(token, depth + 1));
+                }
+                Object::Array(items)
+            }
+            Token::DictStart if depth < MAX_DEPTH => {
+                let mut entries = Vec::new();
+                while let Some(token) = self.next_token() {
+                    match token {
+                        Token::DictEnd => break,
+                        Token::Name(key) => {
+                            let value = match self.next_token() {
+                                Some(Token::DictEnd) | None => {
+                                    entries.push((key, Object::Other));
+                                    break;
+                                }
+                                Some(token) => {
+                                    self.parse_object_from(token, depth + 1)
+                                }
+                            };
+                            entries.push((key, value));
+                        }
+                        _ => {}
+                    }
+                }
+                Object::Dict(entries)
+            }
+            _ => Object::Other,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.data.get(self.pos) {
+            match c {
+                c if is_whitespace(*c) => {
+                    self.pos += 1;
+                }
+                b'%' => {
+                    // Comments extend up to the end of the line.
+                    while let Some(c) = self.data.get(self.pos) {
+                        if *c == b'\r' || *c == b'\n' {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Returns the next token, or [`None`] if the end of the data was
+    /// reached.
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        self.skip_whitespace();
+
+        let data = self.data;
+        let c = *data.get(self.pos)?;
+
+        self.pos += 1;
+
+        let token = match c {
+            b'/' => {
+                let name = decode_name(self.regular_chars());
+                self.check_name(&name);
+                Token::Name(name)
+            }
+            b'(' => {
+                self.skip_literal_string();
+                Token::String
+            }
+            b'<' if data.get(self.pos) == Some(&b'<') => {
+                self.pos += 1;
+                Token::DictStart
+            }
+            b'<' => {
+                self.pos = match memchr::memchr(b'>', &data[self.pos..]) {
+                    Some(offset) => self.pos + offset + 1,
+                    None => data.len(),
+                };
+                Token::String
+            }
+            b'>' if data.get(self.pos) == Some(&b'>') => {
+                self.pos += 1;
+                Token::DictEnd
+            }
+            b'[' => Token::ArrayStart,
+            b']' => Token::ArrayEnd,
+            b'>' | b')' | b'{' | b'}' => Token::Keyword(&data[self.pos - 1..self.pos]),
+            _ => {
+                self.pos -= 1;
+                let chars = self.regular_chars();
+                if !matches!(chars[0], b'0'..=b'9' | b'+' | b'-' | b'.') {
+                    Token::Keyword(chars)
+                } else if let Some(i) = parse_integer(chars) {
+                    Token::Integer(i)
+                } else {
+                    Token::Real
+                }
+            }
+        };
+
+        Some(token)
+    }
+
+    /// Consumes regular characters (i.e: those that are not whitespaces
+    /// nor delimiters) and returns them.
+    fn regular_chars(&mut self) -> &'a [u8] {
+        let data = self.data;
+        let start = self.pos;
+        while let Some(c) = data.get(self.pos) {
+            if is_whitespace(*c) || is_delimiter(*c) {
+                break;
+            }
+            self.pos += 1;
+        }
+        // Make sure that some progress is made, even with a character
+        // that is not handled by the lexer.
+        if self.pos == start && self.pos < data.len() {
+            self.pos += 1;
+        }
+        &data[start..self.pos]
+    }
+
+    /// Skips a literal string. The opening parenthesis has been already
+    /// consumed. Literal strings can contain balanced parenthesis, and
+    /// parenthesis escaped with a backslash.
+    fn skip_literal_string(&mut self) {
+        let mut depth = 1;
+        while let Some(c) = self.data.get(self.pos) {
+            self.pos += 1;
+            match c {
+                b'\\' => self.pos += 1,
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.pos = self.pos.min(self.data.len());
+    }
+
+    /// Updates the result according to the names found in the document.
+    fn check_name(&mut self, name: &[u8]) {
+        match name {
+            b"JS" | b"JavaScript" => self.result.has_javascript = Some(true),
+            b"OpenAction" => self.result.has_open_action = Some(true),
+            b"Launch" => self.result.has_launch = Some(true),
+            b"EmbeddedFile" | b"EmbeddedFiles" => {
+                self.result.has_embedded_files = Some(true)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_integer(chars: &[u8]) -> Option<i64> {
+    std::str::from_utf8(chars).ok()?.parse().ok()
+}
+
+fn is_whitespace(c: u8) -> bool {
+    matches!(c, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn is_delimiter(c: u8) -> bool {
+    matches!(c, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+/// Decodes a name, replacing `#xx` escape sequences with the corresponding
+/// byte. Escape sequences are frequently used in malicious documents for
+/// hiding names like `/JavaScript` (e.g. `/J#61vaScript`).
+fn decode_name(name: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(name.len());
+    let mut i = 0;
+    while i < name.len() {
+        if name[i] == b'#' {
+            if let Some(b) = name
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                result.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        result.push(name[i]);
+        i += 1;
+    }
+    result
+}
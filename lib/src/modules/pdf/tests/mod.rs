@@ -0,0 +1,116 @@
+use crate::modules::tests::create_binary_from_zipped_ihex;
+use crate::tests::rule_false;
+use crate::tests::rule_true;
+use crate::tests::test_rule;
+
+#[test]
+fn test_pdf_module() {
+    let sample = create_binary_from_zipped_ihex(
+        "src/modules/pdf/tests/testdata/sample.pdf.in.zip",
+    );
+
+    let xrefstm = create_binary_from_zipped_ihex(
+        "src/modules/pdf/tests/testdata/xrefstm.pdf.in.zip",
+    );
+
+    rule_false!(
+        r#"
+        import "pdf"
+        rule test {
+          condition:
+            pdf.is_pdf
+        }
+        "#,
+        b"not a pdf"
+    );
+
+    rule_true!(
+        r#"
+        import "pdf"
+        rule test {
+          condition:
+            pdf.is_pdf and
+            pdf.version == "1.4" and
+            pdf.num_objects == 10 and
+            pdf.num_streams == 2 and
+            pdf.startxref == 951
+        }
+        "#,
+        &sample
+    );
+
+    // The document has an incremental update, so it has two cross-reference
+    // tables, and the trailer is the one from the update.
+    rule_true!(
+        r#"
+        import "pdf"
+        rule test {
+          condition:
+            pdf.xrefs[0].offset == 610 and
+            pdf.xrefs[0].num_entries == 9 and
+            not pdf.xrefs[0].is_stream and
+            pdf.xrefs[1].offset == 951 and
+            pdf.xrefs[1].num_entries == 3 and
+            pdf.trailer.size == 11 and
+            pdf.trailer.root == 1 and
+            pdf.trailer.info == 10 and
+            pdf.trailer.prev == 610 and
+            not pdf.trailer.encrypted
+        }
+        "#,
+        &sample
+    );
+
+    // The /JavaScript name is obfuscated as /J#61vaScript.
+    rule_true!(
+        r#"
+        import "pdf"
+        rule test {
+          condition:
+            pdf.has_javascript and
+            pdf.has_open_action and
+            pdf.has_launch and
+            pdf.has_embedded_files and
+            pdf.filters[0] == "ASCIIHexDecode" and
+            pdf.filters[1] == "FlateDecode"
+        }
+        "#,
+        &sample
+    );
+
+    // Names inside streams are not taken into account.
+    rule_false!(
+        r#"
+        import "pdf"
+        rule test {
+          condition:
+            pdf.has_javascript or
+            pdf.has_open_action or
+            pdf.has_launch or
+            pdf.has_embedded_files
+        }
+        "#,
+        &xrefstm
+    );
+
+    rule_true!(
+        r#"
+        import "pdf"
+        rule test {
+          condition:
+            pdf.version == "1.5" and
+            pdf.num_objects == 4 and
+            pdf.num_streams == 2 and
+            pdf.startxref == 198 and
+            pdf.xrefs[0].offset == 198 and
+            pdf.xrefs[0].num_entries == 5 and
+            pdf.xrefs[0].is_stream and
+            pdf.trailer.size == 5 and
+            pdf.trailer.root == 1 and
+            pdf.trailer.encrypted and
+            not defined pdf.trailer.info
+        }
+        "#,
+        &xrefstm
+    );
+}
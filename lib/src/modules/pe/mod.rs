@@ -28,6 +28,11 @@ mod authenticode;
 pub mod parser;
 mod rva2off;
 
+/// Returns false if `data` can't be a PE file.
+pub(crate) fn sniff(data: &[u8]) -> bool {
+    data.starts_with(b"MZ")
+}
+
 #[module_main]
 fn main(data: &[u8], _meta: Option<&[u8]>) -> PE {
     match parser::PE::parse(data) {
@@ -74,6 +79,19 @@ fn rva_to_offset(ctx: &ScanContext, rva: i64) -> Option<i64> {
     Some(offset.into())
 }
 
+/// Convert a file offset to a relative virtual address (RVA).
+#[module_export]
+fn offset_to_rva(ctx: &ScanContext, offset: i64) -> Option<i64> {
+    let pe = ctx.module_output::<PE>()?;
+    let rva = rva2off::offset_to_rva(
+        offset.try_into().ok()?,
+        pe.sections.as_slice(),
+        pe.file_alignment?,
+        pe.section_alignment?,
+    )?;
+    Some(rva.into())
+}
+
 /// Returns the PE checksum, as calculated by YARA.
 ///
 /// This is useful for comparing with the checksum appearing in the PE header
@@ -271,6 +289,25 @@ fn rich_toolid_version(
     rich_version_impl(ctx.module_output::<PE>()?, Some(toolid), Some(version))
 }
 
+/// Returns the MD5 hash of the rich signature.
+///
+/// The hash is computed over the decrypted rich signature data (i.e: the data
+/// in `pe.rich_signature.clear_data`). PE files produced by the same toolchain
+/// share the same rich hash, which makes it useful for clustering samples.
+///
+/// The resulting hash string is consistently in lowercase.
+#[module_export(name = "rich_signature.hash")]
+fn rich_hash(ctx: &mut ScanContext) -> Option<RuntimeString> {
+    let pe = ctx.module_output::<PE>()?;
+    let clear_data = pe.rich_signature.clear_data.as_deref()?;
+
+    let mut md5_hash = md5::Md5::default();
+    Digest::update(&mut md5_hash, clear_data);
+
+    let digest = format!("{:x}", md5_hash.finalize());
+    Some(RuntimeString::new(digest))
+}
+
 fn rich_version_impl(
     pe: &PE,
     toolid: Option<i64>,
@@ -9,7 +9,9 @@ use std::sync::OnceLock;
 
 use bstr::{BStr, ByteSlice};
 use digest;
+use digest::Digest;
 use itertools::Itertools;
+use md5::Md5;
 use memchr::memmem;
 use nom::branch::{alt, permutation};
 use nom::bytes::complete::{take, take_till};
@@ -28,6 +30,7 @@ use crate::modules::pe::authenticode::{
 };
 use crate::modules::pe::rva2off;
 use crate::modules::protos;
+use crate::modules::utils::entropy;
 
 type Error<'a> = nom::error::Error<&'a [u8]>;
 
@@ -77,6 +80,10 @@ pub struct PE<'a> {
     /// [`PE::get_dir_entries`] is called for the first time.
     dir_entries: OnceCell<Option<Vec<DirEntry>>>,
 
+    /// Entries in the debug directory. These entries are parsed lazily
+    /// when [`PE::get_dbg_entries`] is called for the first time.
+    dbg_entries: OnceCell<Option<Vec<DbgDirEntry>>>,
+
     /// Path to PDB file containing debug information for the PE.
     pdb_path: OnceCell<Option<&'a [u8]>>,
 
@@ -358,6 +365,14 @@ impl<'a> PE<'a> {
         *self.pdb_path.get_or_init(|| self.parse_dbg())
     }
 
+    /// Returns the entries in the debug directory.
+    pub fn get_dbg_entries(&self) -> &[DbgDirEntry] {
+        self.dbg_entries
+            .get_or_init(|| self.parse_dbg_entries())
+            .as_deref()
+            .unwrap_or_default()
+    }
+
     /// Returns a slice of [`Resource`] structures, one per each resource
     /// declared in the PE file.
     pub fn get_resources(&self) -> &[Resource<'a>] {
@@ -1564,16 +1579,20 @@ impl<'a> PE<'a> {
             .ok()
     }
 
-    /// Parses the PE debug information and extracts the PDB path.
-    fn parse_dbg(&self) -> Option<&'a [u8]> {
+    /// Parses the entries in the debug directory.
+    fn parse_dbg_entries(&self) -> Option<Vec<DbgDirEntry>> {
         let (_, _, dbg_section) =
             self.get_dir_entry_data(Self::IMAGE_DIRECTORY_ENTRY_DEBUG, true)?;
 
-        let entries = many0(Self::parse_dbg_dir_entry)(dbg_section)
+        many0(Self::parse_dbg_dir_entry)(dbg_section)
             .map(|(_, entries)| entries)
-            .ok()?;
+            .ok()
+    }
 
-        for entry in entries
+    /// Parses the PE debug information and extracts the PDB path.
+    fn parse_dbg(&self) -> Option<&'a [u8]> {
+        for entry in self
+            .get_dbg_entries()
             .iter()
             .filter(|entry| entry.type_ == Self::IMAGE_DEBUG_TYPE_CODEVIEW)
         {
@@ -2241,6 +2260,11 @@ impl From<PE<'_>> for protos::pe::PE {
         result.set_size_of_heap_reserve(pe.optional_hdr.size_of_heap_reserve);
         result.set_size_of_heap_commit(pe.optional_hdr.size_of_heap_commit);
         result.pdb_path = pe.get_pdb_path().map(|path| path.to_vec());
+        result.debug_directories = pe
+            .get_dbg_entries()
+            .iter()
+            .map(protos::pe::DebugDirectory::from)
+            .collect();
         result.set_number_of_rva_and_sizes(pe.optional_hdr.number_of_rva_and_sizes);
         result.set_image_base(pe.optional_hdr.image_base);
         result.set_size_of_image(pe.optional_hdr.size_of_image);
@@ -2276,9 +2300,14 @@ impl From<PE<'_>> for protos::pe::PE {
             .data_directories
             .extend(pe.get_dir_entries().iter().map(protos::pe::DirEntry::from));
 
-        result
-            .sections
-            .extend(pe.get_sections().iter().map(protos::pe::Section::from));
+        result.sections.extend(pe.get_sections().iter().map(|section| {
+            let mut sec = protos::pe::Section::from(section);
+            if let Some(data) = section.data(pe.data) {
+                sec.entropy = Some(entropy(data));
+                sec.md5 = Some(format!("{:x}", Md5::digest(data)));
+            }
+            sec
+        }));
 
         result
             .resources
@@ -2388,6 +2417,9 @@ impl From<PE<'_>> for protos::pe::PE {
         result.set_number_of_signatures(
             result.signatures.len().try_into().unwrap());
 
+        result.set_number_of_debug_directories(
+            result.debug_directories.len().try_into().unwrap());
+
         // The overlay offset is the offset where the last section ends. The
         // last section is not the last one in the section table, but the one
         // with the highest raw_data_offset + raw_data_size.
@@ -2409,6 +2441,7 @@ impl From<PE<'_>> for protos::pe::PE {
                 (Some(offset), Some(size)) if size > 0 => protos::pe::Overlay {
                     offset: Some(offset),
                     size: Some(size),
+                    entropy: Some(entropy(&pe.data[offset as usize..])),
                     ..Default::default()
                 },
                 _ => protos::pe::Overlay {
@@ -2512,6 +2545,27 @@ pub struct Section<'a> {
     characteristics: u32,
 }
 
+impl Section<'_> {
+    /// Returns the section's raw data, as contained in the file.
+    ///
+    /// If the section's raw data extends beyond the end of the file the
+    /// result is truncated. Returns `None` if the section doesn't have any
+    /// raw data in the file. Sections with uninitialized data (e.g: `.bss`)
+    /// usually have a raw data offset of zero, regardless of their raw data
+    /// size, these sections don't have raw data either.
+    pub fn data<'a>(&self, pe_data: &'a [u8]) -> Option<&'a [u8]> {
+        if self.raw_data_offset == 0 || self.raw_data_size == 0 {
+            return None;
+        }
+        let start = self.raw_data_offset as usize;
+        let end = min(
+            start.saturating_add(self.raw_data_size as usize),
+            pe_data.len(),
+        );
+        pe_data.get(start..end).filter(|data| !data.is_empty())
+    }
+}
+
 impl From<&Section<'_>> for protos::pe::Section {
     fn from(value: &Section) -> Self {
         let mut sec = protos::pe::Section::new();
@@ -2716,7 +2770,6 @@ impl From<&Resource<'_>> for protos::pe::Resource {
     }
 }
 
-#[allow(dead_code)]
 #[derive(Debug)]
 pub struct DbgDirEntry {
     /// Reserved.
@@ -2739,6 +2792,21 @@ pub struct DbgDirEntry {
     raw_data_offset: u32,
 }
 
+impl From<&DbgDirEntry> for protos::pe::DebugDirectory {
+    fn from(value: &DbgDirEntry) -> Self {
+        let mut entry = protos::pe::DebugDirectory::new();
+        entry.set_characteristics(value.characteristics);
+        entry.set_timestamp(value.timestamp);
+        entry.set_major_version(value.major_version.into());
+        entry.set_minor_version(value.minor_version.into());
+        entry.type_ = Some(EnumOrUnknown::from_i32(value.type_ as i32));
+        entry.set_raw_data_size(value.raw_data_size);
+        entry.set_virtual_address(value.virtual_address);
+        entry.set_raw_data_offset(value.raw_data_offset);
+        entry
+    }
+}
+
 /// Parser that reads a 32-bits or 64-bits unsigned integer, depending on
 /// its argument. The result is always an `u64`.
 fn uint(_32bits: bool) -> impl FnMut(&[u8]) -> IResult<&[u8], u64> {
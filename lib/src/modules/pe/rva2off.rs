@@ -55,32 +55,12 @@ pub(crate) fn rva_to_offset(
         // that are higher than the current one.
         if section_rva <= s.virtual_address() && (start..end).contains(&rva) {
             section_rva = s.virtual_address();
-            section_offset = s.raw_data_offset();
             section_raw_size = s.raw_data_size();
-
-            // According to the PE specification, file_alignment should
-            // be a power of 2 between 512 and 64KB, inclusive. And the
-            // default value is 512 (0x200). But PE files with lower values
-            // (like 64, 32, and even 1) do exist in the wild and are
-            // correctly handled by the Windows loader. For files with
-            // very small values of file_alignment see:
-            // http://www.phreedom.org/research/tinype/
-            //
-            // Also, according to Ero Carreras's pefile.py, file alignments
-            // greater than 512, are actually ignored and 512 is used
-            // instead.
-            let file_alignment = min(file_alignment, 0x200);
-
-            // Round down section_offset to a multiple of file_alignment.
-            if let Some(rem) = section_offset.checked_rem(file_alignment) {
-                section_offset -= rem;
-            }
-
-            if section_alignment >= 0x1000 {
-                // Round section_offset down to sector size (512 bytes).
-                section_offset =
-                    section_offset.saturating_sub(section_offset % 0x200);
-            }
+            section_offset = aligned_raw_data_offset(
+                s.raw_data_offset(),
+                file_alignment,
+                section_alignment,
+            );
         }
     }
 
@@ -103,6 +83,94 @@ pub(crate) fn rva_to_offset(
     Some(result)
 }
 
+/// Convert a file offset to a relative virtual address (RVA).
+///
+/// This is the inverse of [`rva_to_offset`]. Offsets that are not covered
+/// by the raw data of any section, but that are lower than the raw data
+/// offset of all sections (i.e: offsets within the PE headers), are mapped
+/// directly to an RVA with the same value.
+pub(crate) fn offset_to_rva(
+    offset: u32,
+    sections: &[impl Section],
+    file_alignment: u32,
+    section_alignment: u32,
+) -> Option<u32> {
+    // Sections with a raw data offset of zero don't have any data in the
+    // file (e.g: `.bss`), regardless of their raw data size.
+    let sections = sections
+        .iter()
+        .filter(|s| s.raw_data_offset() != 0 && s.raw_data_size() != 0);
+
+    // If multiple sections contain the offset, the last one is used, which
+    // is consistent with `rva_to_offset`.
+    let section = sections.clone().rev().find(|s| {
+        let start = aligned_raw_data_offset(
+            s.raw_data_offset(),
+            file_alignment,
+            section_alignment,
+        );
+        let end = start.saturating_add(s.raw_data_size());
+        (start..end).contains(&offset)
+    });
+
+    if let Some(s) = section {
+        let start = aligned_raw_data_offset(
+            s.raw_data_offset(),
+            file_alignment,
+            section_alignment,
+        );
+        return s.virtual_address().checked_add(offset - start);
+    }
+
+    let lowest_section_offset = sections
+        .map(|s| {
+            aligned_raw_data_offset(
+                s.raw_data_offset(),
+                file_alignment,
+                section_alignment,
+            )
+        })
+        .min();
+
+    match lowest_section_offset {
+        Some(lowest) if offset >= lowest => None,
+        _ => Some(offset),
+    }
+}
+
+/// Returns the raw data offset of a section, adjusted in the same way the
+/// Windows loader does.
+fn aligned_raw_data_offset(
+    mut offset: u32,
+    file_alignment: u32,
+    section_alignment: u32,
+) -> u32 {
+    // According to the PE specification, file_alignment should
+    // be a power of 2 between 512 and 64KB, inclusive. And the
+    // default value is 512 (0x200). But PE files with lower values
+    // (like 64, 32, and even 1) do exist in the wild and are
+    // correctly handled by the Windows loader. For files with
+    // very small values of file_alignment see:
+    // http://www.phreedom.org/research/tinype/
+    //
+    // Also, according to Ero Carreras's pefile.py, file alignments
+    // greater than 512, are actually ignored and 512 is used
+    // instead.
+    let file_alignment = min(file_alignment, 0x200);
+
+    // Round down offset to a multiple of file_alignment.
+    if let Some(rem) = offset.checked_rem(file_alignment) {
+        offset -= rem;
+    }
+
+    if section_alignment >= 0x1000 {
+        // Round offset down to sector size (512 bytes).
+        offset = offset.saturating_sub(offset % 0x200);
+    }
+
+    offset
+}
+
 impl Section for crate::modules::protos::pe::Section {
     fn virtual_address(&self) -> u32 {
         self.virtual_address.unwrap()
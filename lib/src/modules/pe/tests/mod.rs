@@ -26,6 +26,32 @@ fn rich_signature() {
         "#,
         &pe
     );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.rich_signature.hash() == "acc92f51ede1b8553e81789764e1a55c"
+        }
+        "#,
+        &pe
+    );
+
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/lnk/tests/testdata/lnk-overlay.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not defined pe.rich_signature.hash()
+        }
+        "#,
+        &pe
+    );
 }
 
 #[test]
@@ -486,6 +512,57 @@ fn rva_to_offset() {
         "#,
         &pe
     );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.offset_to_rva(1024) == 4096 and
+            pe.offset_to_rva(17409) == 20481 and
+            pe.offset_to_rva(16) == 16 and
+            pe.offset_to_rva(pe.rva_to_offset(pe.entry_point_raw)) == pe.entry_point_raw and
+            pe.rva_to_offset(pe.data_directories[pe.IMAGE_DIRECTORY_ENTRY_IMPORT].virtual_address) == 17408 and
+            not defined pe.offset_to_rva(-1)
+        }
+        "#,
+        &pe
+    );
+}
+
+#[test]
+fn section_entropy_and_md5() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/c6f9709feccf42f2d9e22057182fe185f177fb9daaa2649b4669a24f2ee7e3ba.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        import "math"
+        rule test {
+          condition:
+            pe.sections[pe.section_index("AUTO")].md5 == "0b407da741209bdb2957cbdd61754484" and
+            pe.sections[0].entropy == math.entropy(
+                pe.sections[0].raw_data_offset, pe.sections[0].raw_data_size) and
+            pe.sections[0].entropy > 6.45 and pe.sections[0].entropy < 6.46
+        }
+        "#,
+        &pe
+    );
+
+    // The `.bss` section doesn't have raw data in the file.
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not defined pe.sections[pe.section_index(".bss")].entropy and
+            not defined pe.sections[pe.section_index(".bss")].md5
+        }
+        "#,
+        &pe
+    );
 }
 
 #[test]
@@ -523,3 +600,141 @@ fn valid_on() {
         &pe
     );
 }
+
+#[test]
+fn signatures() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/130f9b2e65bfceae8d9e7cbe205471fc8ee34c3d9996f77baa3c3ab47a3b3068.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.is_signed and
+            pe.number_of_signatures == 2 and
+            pe.signatures[0].verified and
+            pe.signatures[0].issuer == "/C=BE/O=GlobalSign nv-sa/CN=GlobalSign GCC R45 EV CodeSigning CA 2020" and
+            pe.signatures[0].serial == "1f:7b:0d:e3:09:0e:e1:3a:43:63:15:a6" and
+            pe.signatures[0].not_before == 1619015067 and
+            pe.signatures[0].not_after == 1648238268 and
+            pe.signatures[0].digest == pe.signatures[0].file_digest
+        }
+        "#,
+        &pe
+    );
+
+    // This file is signed, but the signature is not valid because the file
+    // was modified after being signed.
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/3df167b04c52b47ae634b8114671ad3b7bf4e8af62a38a3d4bc0903f474ae2d9.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            not pe.is_signed and
+            pe.number_of_signatures == 2 and
+            for all sig in pe.signatures : (
+              not sig.verified
+            ) and
+            pe.signatures[0].serial == "26:27:9f:0f:2f:11:97:0d:cc:f6:3e:ba:88:f2:d4:c4" and
+            pe.signatures[0].digest != pe.signatures[0].file_digest
+        }
+        "#,
+        &pe
+    );
+}
+
+#[test]
+fn debug_directories() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/079a472d22290a94ebb212aa8015cdc8dd28a968c6b4d3b88acdd58ce2d3b885.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.number_of_debug_directories == 1 and
+            pe.debug_directories[0].type == pe.IMAGE_DEBUG_TYPE_CODEVIEW and
+            pe.debug_directories[0].raw_data_offset == 5968 and
+            pe.debug_directories[0].raw_data_size == 98 and
+            pe.pdb_path endswith "custprof.pdb"
+        }
+        "#,
+        &pe
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.overlay.offset == 10752 and
+            pe.overlay.size == 6048 and
+            pe.overlay.entropy > 7.2 and
+            pe.overlay.entropy < 7.4
+        }
+        "#,
+        &pe
+    );
+}
+
+#[test]
+fn export_details() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/23e72ce7e9cdbc80c0095484ebeb02f56b21e48fd67044e69e7a2ae76db631e5.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.number_of_exports == 4 and
+            pe.export_details[0].name == "DllGetClassObject" and
+            pe.export_details[0].ordinal == 1 and
+            pe.export_details[0].rva == 4144 and
+            pe.export_details[0].offset == 1072 and
+            not defined pe.export_details[0].forward_name and
+            pe.exports("SafeRef") and
+            pe.exports_index("SafeRef") == 3 and
+            pe.export_details[pe.exports_index("SafeRef")].forward_name == "COMSVCS.SafeRef" and
+            for 3 export in pe.export_details : (
+              export.forward_name startswith "COMSVCS."
+            )
+        }
+        "#,
+        &pe
+    );
+}
+
+#[test]
+fn delayed_import_details() {
+    let pe = create_binary_from_zipped_ihex(
+        "src/modules/pe/tests/testdata/2775d97f8bdb3311ace960a42eee35dbec84b9d71a6abbacb26c14e83f5897e4.in.zip",
+    );
+
+    rule_true!(
+        r#"
+        import "pe"
+        rule test {
+          condition:
+            pe.number_of_delayed_imports == 6 and
+            pe.number_of_delayed_imported_functions == 14 and
+            pe.delayed_import_details[0].library_name == "USER32.dll" and
+            pe.delayed_import_details[0].number_of_functions == 2 and
+            pe.delayed_import_details[0].functions[0].name == "CreateMenu" and
+            pe.delayed_import_details[0].functions[0].rva == 4268 and
+            pe.imports(pe.IMPORT_DELAYED, "USER32.dll", "CreateMenu") and
+            not pe.imports(pe.IMPORT_STANDARD, "USER32.dll", "CreateMenu")
+        }
+        "#,
+        &pe
+    );
+}
@@ -0,0 +1,309 @@
+/*! YARA modules implemented as WebAssembly plugins.
+
+Built-in modules are written in Rust and compiled into YARA-X, which means
+that adding a new module requires rebuilding YARA-X. Module plugins offer an
+alternative: a plugin is a WebAssembly module that parses the scanned data
+and returns a Protocol Buffer message with the result, exactly as the `main`
+function of a built-in module does. Plugins are loaded at runtime, and they
+are executed inside a sandbox. See [`ModulePlugin`] for the interface that
+plugins must implement.
+
+# Sandboxing
+
+Plugins run in their own WASM engine, isolated from the one that evaluates
+rule conditions. The amount of memory that a plugin can use and the number
+of instructions it can execute on each invocation are limited, a plugin that
+exceeds these limits simply produces no output.
+*/
+
+use std::fmt;
+
+use lazy_static::lazy_static;
+use protobuf::descriptor::FileDescriptorSet;
+use protobuf::reflect::{FileDescriptor, MessageDescriptor};
+use protobuf::{Message, MessageDyn};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use wasmtime::{
+    Config, Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use crate::modules::protos;
+use crate::modules::BUILTIN_MODULES;
+
+/// Maximum amount of memory that a plugin can use, in bytes. The scanned
+/// data is copied into the plugin's memory, so this limit also bounds the
+/// size of the data that a plugin can receive.
+const MAX_PLUGIN_MEMORY: usize = 1 << 30;
+
+/// Amount of fuel given to a plugin on each invocation. Roughly speaking,
+/// each WASM instruction executed by the plugin consumes one unit of fuel.
+const PLUGIN_FUEL: u64 = 10_000_000_000;
+
+lazy_static! {
+    /// WASM engine used for running module plugins. This engine is different
+    /// from [`crate::wasm::ENGINE`] because plugins are executed with fuel
+    /// metering, which is not necessary for the code emitted by the compiler.
+    static ref PLUGIN_ENGINE: Engine = {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        Engine::new(&config).unwrap()
+    };
+}
+
+/// Error returned while loading a module plugin.
+#[derive(Error, Debug)]
+pub enum PluginError {
+    /// The module name is not a valid identifier, or is already used by a
+    /// built-in module.
+    #[error("invalid module name `{0}`")]
+    InvalidName(String),
+
+    /// The WASM code is invalid or doesn't implement the plugin interface.
+    #[error("invalid plugin: {0}")]
+    InvalidWasm(String),
+
+    /// The protobuf descriptors are invalid.
+    #[error("invalid descriptors: {0}")]
+    InvalidDescriptors(#[from] protobuf::Error),
+
+    /// The root message is not defined in the descriptors.
+    #[error("message `{0}` not found in descriptors")]
+    UnknownMessage(String),
+}
+
+/// A YARA module implemented as a WebAssembly plugin.
+///
+/// Plugins are registered in the compiler with
+/// [`crate::Compiler::add_module_plugin`], and after that they can be
+/// imported by rules like any other module. The plugin is included in the
+/// compiled [`crate::Rules`], so the scanner doesn't need to know about it.
+///
+/// # Plugin interface
+///
+/// A plugin is a WASM module that satisfies the following requirements:
+///
+/// * It doesn't import anything. Plugins can't call any function provided by the
+///   host, they only receive the scanned data and return the result.
+/// * It exports its linear memory with the name `memory`.
+/// * It exports a function `yr_alloc(size: i32) -> i32` that allocates a buffer
+///   of the given size in the plugin's memory and returns its address. The
+///   scanned data is copied into this buffer.
+/// * It exports a function `yr_parse(ptr: i32, len: i32) -> i64` that receives
+///   the address and length of the scanned data, and returns the address of the
+///   serialized protobuf message in the upper 32 bits of the result and its
+///   length in the lower 32 bits. A zero result means that the plugin didn't
+///   produce any output for the scanned data.
+///
+/// Together with the WASM code, the plugin must provide the descriptors of the
+/// protobuf messages it produces, in the form of a serialized
+/// `FileDescriptorSet`. This is what `protoc --descriptor_set_out` generates.
+pub struct ModulePlugin {
+    name: String,
+    root_message: String,
+    wasm: Vec<u8>,
+    descriptors: Vec<u8>,
+    root_struct_descriptor: MessageDescriptor,
+    wasm_mod: Module,
+}
+
+impl ModulePlugin {
+    /// Creates a new module plugin.
+    ///
+    /// `name` is the name used in `import` statements, `wasm` is the plugin's
+    /// code in binary form, `descriptors` is a serialized `FileDescriptorSet`
+    /// that describes the protobuf messages produced by the plugin, and
+    /// `root_message` is the fully-qualified name of the message returned by
+    /// the plugin (e.g: `foo.Foo`).
+    pub fn new(
+        name: &str,
+        wasm: &[u8],
+        descriptors: &[u8],
+        root_message: &str,
+    ) -> Result<Self, PluginError> {
+        if !crate::variables::is_valid_identifier(name)
+            || BUILTIN_MODULES.contains_key(name)
+        {
+            return Err(PluginError::InvalidName(name.to_string()));
+        }
+
+        let root_struct_descriptor =
+            Self::load_descriptors(descriptors, root_message)?;
+
+        let wasm_mod = Module::from_binary(&PLUGIN_ENGINE, wasm)
+            .map_err(|err| PluginError::InvalidWasm(err.to_string()))?;
+
+        if wasm_mod.imports().len() > 0 {
+            return Err(PluginError::InvalidWasm(
+                "plugins can't import anything".to_string(),
+            ));
+        }
+
+        for export in ["memory", "yr_alloc", "yr_parse"] {
+            if wasm_mod.get_export(export).is_none() {
+                return Err(PluginError::InvalidWasm(format!(
+                    "missing export `{}`",
+                    export
+                )));
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            root_message: root_message.to_string(),
+            wasm: wasm.to_vec(),
+            descriptors: descriptors.to_vec(),
+            root_struct_descriptor,
+            wasm_mod,
+        })
+    }
+
+    /// Returns the module's name.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Returns the [`MessageDescriptor`] for the message produced by the
+    /// plugin.
+    pub(crate) fn root_struct_descriptor(&self) -> &MessageDescriptor {
+        &self.root_struct_descriptor
+    }
+
+    /// Runs the plugin with the given data, returning the protobuf message
+    /// produced by it.
+    ///
+    /// Returns `None` if the plugin didn't produce any output, or if it
+    /// failed in any way (e.g: it exceeded its resource limits, or returned
+    /// an invalid message).
+    pub(crate) fn invoke(&self, data: &[u8]) -> Option<Box<dyn MessageDyn>> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_PLUGIN_MEMORY)
+            .instances(1)
+            .build();
+
+        let mut store = Store::new(&PLUGIN_ENGINE, limits);
+
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store.set_fuel(PLUGIN_FUEL).ok()?;
+
+        let instance = Instance::new(&mut store, &self.wasm_mod, &[]).ok()?;
+        let memory = instance.get_memory(&mut store, "memory")?;
+
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "yr_alloc")
+            .ok()?;
+
+        let parse = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, "yr_parse")
+            .ok()?;
+
+        let len = i32::try_from(data.len()).ok()?;
+        let ptr = alloc.call(&mut store, len).ok()?;
+
+        memory.write(&mut store, ptr as u32 as usize, data).ok()?;
+
+        let result = parse.call(&mut store, (ptr, len)).ok()? as u64;
+
+        if result == 0 {
+            return None;
+        }
+
+        let output_ptr = (result >> 32) as usize;
+        let output_len = (result & 0xffffffff) as usize;
+
+        let output = memory
+            .data(&store)
+            .get(output_ptr..output_ptr.checked_add(output_len)?)?;
+
+        self.root_struct_descriptor.parse_from_bytes(output).ok()
+    }
+
+    /// Builds the file descriptors contained in a serialized
+    /// `FileDescriptorSet`, and returns the descriptor for `root_message`.
+    fn load_descriptors(
+        descriptors: &[u8],
+        root_message: &str,
+    ) -> Result<MessageDescriptor, PluginError> {
+        let descriptor_set = FileDescriptorSet::parse_from_bytes(descriptors)?;
+
+        // Files in the set can depend on the ones defined by YARA-X (i.e:
+        // `yara.proto`, which defines the module options), and on other
+        // files that appear before them in the set.
+        let mut files = vec![
+            protos::yara::file_descriptor().clone(),
+            protobuf::descriptor::file_descriptor().clone(),
+        ];
+
+        for file in descriptor_set.file {
+            let deps: Vec<FileDescriptor> = file
+                .dependency
+                .iter()
+                .filter_map(|dep| {
+                    files.iter().find(|f| f.proto().name() == dep).cloned()
+                })
+                .collect();
+
+            files.push(FileDescriptor::new_dynamic(file, &deps)?);
+        }
+
+        let full_name = format!(".{}", root_message);
+
+        files
+            .iter()
+            .find_map(|file| file.message_by_full_name(full_name.as_str()))
+            .ok_or_else(|| {
+                PluginError::UnknownMessage(root_message.to_string())
+            })
+    }
+}
+
+impl fmt::Debug for ModulePlugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModulePlugin")
+            .field("name", &self.name)
+            .field("root_message", &self.root_message)
+            .finish()
+    }
+}
+
+/// Serialized form of a [`ModulePlugin`].
+///
+/// Only the raw WASM code and descriptors are serialized, the compiled WASM
+/// module and the message descriptor are re-created while deserializing.
+#[derive(Serialize, Deserialize)]
+struct SerializedPlugin {
+    name: String,
+    root_message: String,
+    wasm: Vec<u8>,
+    descriptors: Vec<u8>,
+}
+
+impl Serialize for ModulePlugin {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        SerializedPlugin {
+            name: self.name.clone(),
+            root_message: self.root_message.clone(),
+            wasm: self.wasm.clone(),
+            descriptors: self.descriptors.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ModulePlugin {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let plugin = SerializedPlugin::deserialize(deserializer)?;
+        ModulePlugin::new(
+            plugin.name.as_str(),
+            plugin.wasm.as_slice(),
+            plugin.descriptors.as_slice(),
+            plugin.root_message.as_str(),
+        )
+        .map_err(serde::de::Error::custom)
+    }
+}
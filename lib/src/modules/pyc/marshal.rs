@@ -0,0 +1,321 @@
+/*! Reader for the `marshal` serialization format used by Python.
+
+The code object stored in a pyc file is serialized with the `marshal`
+module. Each object starts with a byte that indicates its type, if the
+most significant bit of this byte is set, the object is stored in a table
+of references, and it can be referenced later by its index in the table.
+
+See: https://github.com/python/cpython/blob/main/Python/marshal.c
+ */
+
+use std::rc::Rc;
+
+/// Flag that indicates that an object is added to the references table.
+const FLAG_REF: u8 = 0x80;
+
+/// Maximum height of an object, where the height is the length of the
+/// longest path from the object to one of the objects nested inside it,
+/// including those nested by means of references. Higher objects are
+/// considered invalid. As references allow building arbitrarily long
+/// chains of objects with little data, limiting the height is necessary
+/// for processing objects recursively without overflowing the stack.
+const MAX_HEIGHT: usize = 200;
+
+/// An object deserialized by [`Reader`]. Only the types that are relevant
+/// for the module are represented, the rest of them are [`Object::Other`].
+pub enum Object {
+    Null,
+    Int(i64),
+    Bytes(Vec<u8>),
+    Str(String),
+    Seq(Vec<Rc<Object>>),
+    Code(Box<Code>),
+    Other,
+}
+
+/// A code object.
+pub struct Code {
+    pub arg_count: i32,
+    pub flags: i32,
+    pub first_line: i32,
+    pub consts: Rc<Object>,
+    pub names: Rc<Object>,
+    pub varnames: Rc<Object>,
+    pub filename: Rc<Object>,
+    pub name: Rc<Object>,
+    pub qualname: Option<Rc<Object>>,
+}
+
+/// Reads objects serialized with the `marshal` module.
+pub struct Reader<'a> {
+    data: &'a [u8],
+    /// Python version that produced the data, as (major, minor). The
+    /// layout of code objects depends on it.
+    version: (u8, u8),
+    /// Objects in the references table, together with their height.
+    refs: Vec<Option<(Rc<Object>, usize)>>,
+    /// Strings interned in Python 2, which are referenced by index with
+    /// the `R` type.
+    interned: Vec<Rc<Object>>,
+    /// Number of objects currently being read, one inside the other.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8], version: (u8, u8)) -> Self {
+        Self {
+            data,
+            version,
+            refs: Vec::new(),
+            interned: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    /// Reads the next object, returns [`None`] if the data is not valid.
+    pub fn read_object(&mut self) -> Option<Rc<Object>> {
+        self.read().map(|(object, _)| object)
+    }
+
+    /// Reads the next object, returning it together with its height.
+    fn read(&mut self) -> Option<(Rc<Object>, usize)> {
+        if self.depth >= MAX_HEIGHT {
+            return None;
+        }
+        self.depth += 1;
+        let result = self.read_impl();
+        self.depth -= 1;
+        result.filter(|(_, height)| *height <= MAX_HEIGHT)
+    }
+
+    /// Reads a child of the object being read, and updates `height` with
+    /// the height of the child if it is higher.
+    fn read_child(&mut self, height: &mut usize) -> Option<Rc<Object>> {
+        let (object, h) = self.read()?;
+        *height = (*height).max(h);
+        Some(object)
+    }
+
+    fn read_impl(&mut self) -> Option<(Rc<Object>, usize)> {
+        let code = self.u8()?;
+
+        // Reserve the slot in the references table before reading the
+        // object, as objects inside it may be added to the table too.
+        let ref_index = if code & FLAG_REF != 0 {
+            self.refs.push(None);
+            Some(self.refs.len() - 1)
+        } else {
+            None
+        };
+
+        // Height of the highest child of the object.
+        let mut height = 0;
+
+        let object = match code & !FLAG_REF {
+            b'0' => Object::Null,
+            b'N' | b'F' | b'T' | b'S' | b'.' => Object::Other,
+            b'i' => Object::Int(self.i32()?.into()),
+            b'I' => Object::Int(i64::from_le_bytes(
+                self.take(8)?.try_into().unwrap(),
+            )),
+            // Long integers are stored as a sequence of 15-bits digits.
+            b'l' => {
+                let n = self.i32()?.unsigned_abs() as usize;
+                self.take(n.checked_mul(2)?)?;
+                Object::Other
+            }
+            // Floats and complex numbers in text form.
+            b'f' => {
+                self.short_data()?;
+                Object::Other
+            }
+            b'x' => {
+                self.short_data()?;
+                self.short_data()?;
+                Object::Other
+            }
+            // Floats and complex numbers in binary form.
+            b'g' => {
+                self.take(8)?;
+                Object::Other
+            }
+            b'y' => {
+                self.take(16)?;
+                Object::Other
+            }
+            // In Python 2 `s` is used for `str` objects, which are
+            // strings of bytes.
+            b's' if self.version.0 == 2 => Object::Str(self.string()?),
+            b's' => Object::Bytes(self.data()?.to_vec()),
+            b't' => {
+                let s = Rc::new(Object::Str(self.string()?));
+                self.interned.push(s.clone());
+                return self.add_ref(ref_index, s, 1);
+            }
+            b'R' => {
+                let index = usize::try_from(self.i32()?).ok()?;
+                let s = self.interned.get(index)?.clone();
+                return self.add_ref(ref_index, s, 1);
+            }
+            b'u' | b'a' | b'A' => Object::Str(self.string()?),
+            b'z' | b'Z' => Object::Str(
+                String::from_utf8_lossy(self.short_data()?).into_owned(),
+            ),
+            b'(' | b'[' | b'<' | b'>' => {
+                let n = usize::try_from(self.i32()?).ok()?;
+                Object::Seq(self.seq(n, &mut height)?)
+            }
+            b')' => {
+                let n = self.u8()? as usize;
+                Object::Seq(self.seq(n, &mut height)?)
+            }
+            b'{' => {
+                // Dictionaries are a sequence of key-value pairs that
+                // ends with a null key.
+                while !matches!(*self.read_child(&mut height)?, Object::Null)
+                {
+                    self.read_child(&mut height)?;
+                }
+                Object::Other
+            }
+            b'c' => Object::Code(Box::new(self.code(&mut height)?)),
+            b'r' => {
+                let index = usize::try_from(self.i32()?).ok()?;
+                // The reference may point to an object that is still
+                // being read, like a code object that contains a reference
+                // to itself.
+                return Some(
+                    self.refs
+                        .get(index)?
+                        .clone()
+                        .unwrap_or_else(|| (Rc::new(Object::Other), 1)),
+                );
+            }
+            _ => return None,
+        };
+
+        self.add_ref(ref_index, Rc::new(object), height + 1)
+    }
+
+    fn add_ref(
+        &mut self,
+        ref_index: Option<usize>,
+        object: Rc<Object>,
+        height: usize,
+    ) -> Option<(Rc<Object>, usize)> {
+        if let Some(index) = ref_index {
+            self.refs[index] = Some((object.clone(), height));
+        }
+        Some((object, height))
+    }
+
+    fn code(&mut self, height: &mut usize) -> Option<Code> {
+        // The number of 32-bits integers at the start of the code object
+        // depends on the version. In all versions the first one is the
+        // number of arguments and the last one are the flags.
+        let num_ints = match self.version {
+            (2, _) => 4,
+            (3, 0..=7) => 5,
+            (3, 8..=10) => 6,
+            _ => 5,
+        };
+
+        let mut ints = Vec::with_capacity(num_ints);
+
+        for _ in 0..num_ints {
+            ints.push(self.i32()?);
+        }
+
+        let _bytecode = self.read_child(height)?;
+        let consts = self.read_child(height)?;
+        let names = self.read_child(height)?;
+
+        let (varnames, filename, name, qualname);
+
+        if self.version >= (3, 11) {
+            varnames = self.read_child(height)?;
+            let _localspluskinds = self.read_child(height)?;
+            filename = self.read_child(height)?;
+            name = self.read_child(height)?;
+            qualname = Some(self.read_child(height)?);
+        } else {
+            varnames = self.read_child(height)?;
+            let _freevars = self.read_child(height)?;
+            let _cellvars = self.read_child(height)?;
+            filename = self.read_child(height)?;
+            name = self.read_child(height)?;
+            qualname = None;
+        }
+
+        let first_line = self.i32()?;
+
+        // Line number table, and since Python 3.11 the exception table.
+        self.read_child(height)?;
+
+        if self.version >= (3, 11) {
+            self.read_child(height)?;
+        }
+
+        Some(Code {
+            arg_count: ints[0],
+            flags: ints[num_ints - 1],
+            first_line,
+            consts,
+            names,
+            varnames,
+            filename,
+            name,
+            qualname,
+        })
+    }
+
+    fn seq(
+        &mut self,
+        n: usize,
+        height: &mut usize,
+    ) -> Option<Vec<Rc<Object>>> {
+        // Each item takes at least one byte, this prevents allocating
+        // a huge vector if `n` is too large.
+        if n > self.data.len() {
+            return None;
+        }
+        let mut items = Vec::with_capacity(n);
+        for _ in 0..n {
+            items.push(self.read_child(height)?);
+        }
+        Some(items)
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.data.len() {
+            return None;
+        }
+        let (data, remainder) = self.data.split_at(n);
+        self.data = remainder;
+        Some(data)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.take(4).map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    /// Reads data prefixed by its length as a 32-bits integer.
+    fn data(&mut self) -> Option<&'a [u8]> {
+        let n = usize::try_from(self.i32()?).ok()?;
+        self.take(n)
+    }
+
+    /// Reads data prefixed by its length as an 8-bits integer.
+    fn short_data(&mut self) -> Option<&'a [u8]> {
+        let n = self.u8()? as usize;
+        self.take(n)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        Some(String::from_utf8_lossy(self.data()?).into_owned())
+    }
+}
@@ -0,0 +1,65 @@
+/*! YARA module that parses compiled Python files (.pyc).
+
+This module parses the header of pyc files, which contains the magic number
+that identifies the version of Python that produced the file, and the
+timestamp or hash of the source file. It also deserializes the code object
+that follows the header, and exposes the names, constants and string
+literals of the module and of the functions and classes defined in it.
+
+Python programs packed with tools like PyInstaller contain their modules
+as pyc files, this module is useful for analyzing such modules once they
+have been extracted.
+
+See: https://github.com/python/cpython/blob/main/Lib/importlib/_bootstrap_external.py
+ */
+
+use crate::modules::prelude::*;
+use crate::modules::protos::pyc::*;
+
+mod marshal;
+mod parser;
+#[cfg(test)]
+mod tests;
+
+#[module_main]
+fn main(data: &[u8], _meta: Option<&[u8]>) -> Pyc {
+    match parser::parse(data) {
+        Some(mut pyc) => {
+            pyc.is_pyc = Some(true);
+            pyc
+        }
+        None => {
+            let mut pyc = Pyc::new();
+            pyc.is_pyc = Some(false);
+            pyc
+        }
+    }
+}
+
+/// Returns true if any code object in the file uses the given name, like
+/// the name of an imported module, a global variable or an attribute.
+#[module_export]
+fn has_name(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let pyc = ctx.module_output::<Pyc>()?;
+    let name = name.as_bstr(ctx);
+
+    Some(
+        pyc.code_objects
+            .iter()
+            .any(|code| code.names.iter().any(|n| name == n)),
+    )
+}
+
+/// Returns true if any code object in the file has a string constant with
+/// the given value.
+#[module_export]
+fn has_string(ctx: &ScanContext, s: RuntimeString) -> Option<bool> {
+    let pyc = ctx.module_output::<Pyc>()?;
+    let s = s.as_bstr(ctx);
+
+    Some(
+        pyc.code_objects
+            .iter()
+            .any(|code| code.string_constants.iter().any(|c| s == c)),
+    )
+}
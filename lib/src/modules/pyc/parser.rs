@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::modules::protos::pyc;
+use crate::modules::pyc::marshal::{Object, Reader};
+
+/// Limits the amount of data extracted from a file. Each code object and
+/// constant consumes one unit, plus the length of the constant in the case
+/// of strings and bytes. As objects can be referenced multiple times, this
+/// prevents specially crafted files from producing a huge output.
+const BUDGET: usize = 16 * 1024 * 1024;
+
+/// Returns the Python version that corresponds to a magic number, as
+/// (major, minor).
+///
+/// See: https://github.com/python/cpython/blob/main/Lib/importlib/_bootstrap_external.py
+fn version(magic: u16) -> Option<(u8, u8)> {
+    let version = match magic {
+        62161..=62170 => (2, 6),
+        62171..=62211 => (2, 7),
+        3000..=3131 => (3, 0),
+        3141..=3151 => (3, 1),
+        3160..=3180 => (3, 2),
+        3190..=3230 => (3, 3),
+        3250..=3310 => (3, 4),
+        3320..=3351 => (3, 5),
+        3360..=3379 => (3, 6),
+        3390..=3399 => (3, 7),
+        3400..=3419 => (3, 8),
+        3420..=3429 => (3, 9),
+        3430..=3449 => (3, 10),
+        3450..=3499 => (3, 11),
+        3500..=3549 => (3, 12),
+        3550..=3599 => (3, 13),
+        3600..=3649 => (3, 14),
+        _ => return None,
+    };
+    Some(version)
+}
+
+/// Parses a pyc file. Returns [`None`] if the header is not valid.
+pub fn parse(data: &[u8]) -> Option<pyc::Pyc> {
+    // The magic number is followed by `\r\n`.
+    let magic = u16::from_le_bytes(data.get(0..2)?.try_into().unwrap());
+
+    if data.get(2..4)? != b"\r\n" {
+        return None;
+    }
+
+    let version = version(magic)?;
+    let u32_at = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    };
+
+    let mut result = pyc::Pyc::new();
+
+    result.magic = Some(magic.into());
+    result.version = Some(format!("{}.{}", version.0, version.1));
+
+    // The size of the header depends on the version. Python 3.7 added the
+    // flags (PEP 552) which indicate if the pyc is based on the timestamp
+    // of the source file or on its hash, Python 3.3 added the size of the
+    // source file.
+    let header_size = if version >= (3, 7) {
+        let flags = u32_at(4)?;
+        result.flags = Some(flags);
+        if flags & 0x1 != 0 {
+            result.source_hash = Some(data.get(8..16)?.to_vec());
+        } else {
+            result.timestamp = Some(u32_at(8)?.into());
+            result.source_size = Some(u32_at(12)?);
+        }
+        16
+    } else if version >= (3, 3) {
+        result.timestamp = Some(u32_at(4)?.into());
+        result.source_size = Some(u32_at(8)?);
+        12
+    } else {
+        result.timestamp = Some(u32_at(4)?.into());
+        8
+    };
+
+    let mut reader = Reader::new(&data[header_size..], version);
+
+    if let Some(code) = reader.read_object() {
+        let mut walker = Walker { budget: BUDGET, visited: HashSet::new() };
+        walker.code_objects(&code, &mut result.code_objects);
+    }
+
+    Some(result)
+}
+
+/// Walks the code objects and their constants, producing [`pyc::Code`].
+struct Walker {
+    budget: usize,
+    /// Code objects already visited.
+    visited: HashSet<*const Object>,
+}
+
+impl Walker {
+    /// Consumes `n` units from the budget, returns false if the budget is
+    /// exhausted.
+    fn consume(&mut self, n: usize) -> bool {
+        match self.budget.checked_sub(n) {
+            Some(budget) => {
+                self.budget = budget;
+                true
+            }
+            None => {
+                self.budget = 0;
+                false
+            }
+        }
+    }
+
+    /// Converts a code object and the code objects nested inside it into
+    /// [`pyc::Code`], appending them to `result` in depth-first order.
+    fn code_objects(
+        &mut self,
+        object: &Rc<Object>,
+        result: &mut Vec<pyc::Code>,
+    ) {
+        let Object::Code(code) = object.as_ref() else {
+            return;
+        };
+
+        // Code objects can be referenced more than once, but they are
+        // processed only the first time.
+        if !self.visited.insert(Rc::as_ptr(object)) || !self.consume(1) {
+            return;
+        }
+
+        let mut item = pyc::Code::new();
+
+        item.name = string(&code.name);
+        item.qualname = code.qualname.as_ref().and_then(string);
+        item.filename = string(&code.filename);
+        item.first_line = u32::try_from(code.first_line).ok();
+        item.arg_count = u32::try_from(code.arg_count).ok();
+        item.flags = Some(code.flags as u32);
+        item.names = self.strings(&code.names);
+        item.varnames = self.strings(&code.varnames);
+
+        // Constants are collected first, nested code objects are processed
+        // after the current one has been added to the result.
+        let mut nested = Vec::new();
+        let mut seen = HashSet::new();
+
+        self.constants(&code.consts, &mut item, &mut nested, &mut seen);
+
+        result.push(item);
+
+        for code in nested {
+            self.code_objects(&code, result);
+        }
+    }
+
+    /// Collects the constants in `object`, which is usually a tuple.
+    /// Tuples and frozensets inside it are processed recursively, as they
+    /// are used for constants like `("a", "b")` or `x in {"a", "b"}`.
+    /// `seen` contains the sequences already processed for the current
+    /// code object.
+    fn constants(
+        &mut self,
+        object: &Rc<Object>,
+        code: &mut pyc::Code,
+        nested: &mut Vec<Rc<Object>>,
+        seen: &mut HashSet<*const Object>,
+    ) {
+        if !self.consume(1) {
+            return;
+        }
+        match object.as_ref() {
+            Object::Str(s) if self.consume(s.len()) => {
+                code.string_constants.push(s.clone())
+            }
+            Object::Bytes(b) if self.consume(b.len()) => {
+                code.bytes_constants.push(b.clone())
+            }
+            Object::Int(i) => code.int_constants.push(*i),
+            Object::Code(_) => nested.push(object.clone()),
+            Object::Seq(items) if seen.insert(Rc::as_ptr(object)) => {
+                for item in items {
+                    self.constants(item, code, nested, seen);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the strings in a tuple, like the names of variables.
+    fn strings(&mut self, object: &Rc<Object>) -> Vec<String> {
+        let mut result = Vec::new();
+        if let Object::Seq(items) = object.as_ref() {
+            for item in items {
+                if let Object::Str(s) = item.as_ref() {
+                    if !self.consume(1 + s.len()) {
+                        break;
+                    }
+                    result.push(s.clone());
+                }
+            }
+        }
+        result
+    }
+}
+
+fn string(object: &Rc<Object>) -> Option<String> {
+    match object.as_ref() {
+        Object::Str(s) => Some(s.clone()),
+        _ => None,
+    }
+}
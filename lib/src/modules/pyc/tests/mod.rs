@@ -0,0 +1,87 @@
+use crate::modules::tests::create_binary_from_zipped_ihex;
+use crate::tests::rule_false;
+use crate::tests::rule_true;
+use crate::tests::test_rule;
+
+#[test]
+fn test_pyc_module() {
+    let stealer = create_binary_from_zipped_ihex(
+        "src/modules/pyc/tests/testdata/stealer.pyc.in.zip",
+    );
+
+    let loader = create_binary_from_zipped_ihex(
+        "src/modules/pyc/tests/testdata/loader.pyc.in.zip",
+    );
+
+    rule_false!(
+        r#"
+        import "pyc"
+        rule test {
+          condition:
+            pyc.is_pyc
+        }
+        "#,
+        // Unknown magic number.
+        b"\x01\x00\r\n\x00\x00\x00\x00"
+    );
+
+    rule_true!(
+        r#"
+        import "pyc"
+        rule test {
+          condition:
+            pyc.is_pyc and
+            pyc.magic == 3495 and
+            pyc.version == "3.11" and
+            pyc.flags == 0 and
+            pyc.timestamp == 1709294400 and
+            pyc.source_size == 532 and
+            not defined pyc.source_hash and
+            pyc.code_objects[0].name == "<module>" and
+            pyc.code_objects[0].filename == "stealer.py" and
+            pyc.code_objects[1].name == "grab_tokens" and
+            pyc.code_objects[1].arg_count == 2 and
+            pyc.code_objects[1].first_line == 9 and
+            pyc.code_objects[1].varnames[0] == "path" and
+            pyc.code_objects[3].qualname == "Stealer.send" and
+            pyc.code_objects[0].bytes_constants[0] == "\x00secret" and
+            for any i in pyc.code_objects[0].int_constants : ( i == 10 )
+        }
+        "#,
+        &stealer
+    );
+
+    rule_true!(
+        r#"
+        import "pyc"
+        rule test {
+          condition:
+            pyc.has_name("requests") and
+            pyc.has_name("b64encode") and
+            not pyc.has_name("socket") and
+            pyc.has_string("https://discord.com/api/webhooks/123456/abcdef") and
+            pyc.has_string("Login Data") and
+            not pyc.has_string("requests")
+        }
+        "#,
+        &stealer
+    );
+
+    rule_true!(
+        r#"
+        import "pyc"
+        rule test {
+          condition:
+            pyc.is_pyc and
+            pyc.version == "3.13" and
+            pyc.flags == 3 and
+            pyc.source_hash == "\xc3\xd1\xa7\\c\xf9-\xcd" and
+            not defined pyc.timestamp and
+            not defined pyc.source_size and
+            pyc.has_name("marshal") and
+            pyc.has_name("decompress")
+        }
+        "#,
+        &loader
+    );
+}
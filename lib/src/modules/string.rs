@@ -10,7 +10,7 @@ fn main(_data: &[u8], _meta: Option<&[u8]>) -> String {
 #[module_export]
 fn to_int(ctx: &ScanContext, string: RuntimeString) -> Option<i64> {
     let string = string.to_str(ctx).ok()?;
-    string.parse::<i64>().ok()
+    parse_int(string, 0)
 }
 
 #[module_export(name = "to_int")]
@@ -20,11 +20,11 @@ fn to_int_base(
     base: i64,
 ) -> Option<i64> {
     let base: u32 = base.try_into().ok()?;
-    if !(2..=36).contains(&base) {
+    if base != 0 && !(2..=36).contains(&base) {
         return None;
     }
     let string = string.to_str(ctx).ok()?;
-    i64::from_str_radix(string, base).ok()
+    parse_int(string, base)
 }
 
 #[module_export]
@@ -32,6 +32,57 @@ fn length(ctx: &ScanContext, string: RuntimeString) -> Option<i64> {
     Some(string.as_bstr(ctx).len().try_into().unwrap())
 }
 
+#[module_export]
+fn hex(ctx: &ScanContext, string: RuntimeString) -> Option<RuntimeString> {
+    let mut result = std::string::String::new();
+    for b in string.as_bstr(ctx).iter() {
+        result.push_str(&format!("{:02x}", b));
+    }
+    Some(RuntimeString::new(result))
+}
+
+/// Parses an integer in the given base, with the same semantics than the
+/// `strtoll` function in C, except that the whole string must be a valid
+/// number.
+///
+/// If `base` is 0 the base is determined by the prefix of the number: "0x"
+/// for base 16, "0" for base 8, and base 10 otherwise. With base 16 the
+/// "0x" prefix is optional.
+fn parse_int(string: &str, mut base: u32) -> Option<i64> {
+    let string = string.trim_start();
+
+    let (sign, digits) = match string.as_bytes().first() {
+        Some(b'-') => ("-", &string[1..]),
+        Some(b'+') => ("", &string[1..]),
+        _ => ("", string),
+    };
+
+    let digits = match digits.get(..2) {
+        Some("0x") | Some("0X") if base == 0 || base == 16 => {
+            base = 16;
+            &digits[2..]
+        }
+        _ if base == 0 && digits.starts_with('0') && digits.len() > 1 => {
+            base = 8;
+            &digits[1..]
+        }
+        _ => digits,
+    };
+
+    if base == 0 {
+        base = 10;
+    }
+
+    // `from_str_radix` accepts a leading sign, which would allow strings
+    // like "--1" if not checked here.
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+
+    // The sign is prepended to the digits so that `i64::MIN` can be parsed.
+    i64::from_str_radix(format!("{sign}{digits}").as_str(), base).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::tests::rule_false;
@@ -98,5 +149,55 @@ mod tests {
             rule test { condition: string.to_int("-011", 8) == -9 }"#,
             &[]
         );
+
+        rule_true!(
+            r#"
+            import "string"
+            rule test {
+              condition:
+                string.to_int("0x10") == 16 and
+                string.to_int("0x10", 16) == 16 and
+                string.to_int("-010") == -8 and
+                string.to_int("+010", 0) == 8 and
+                string.to_int("-0x1A", 0) == -26 and
+                string.to_int("0") == 0 and
+                string.to_int(" 12") == 12 and
+                string.to_int("-9223372036854775808") ==
+                  -9223372036854775807 - 1
+            }"#,
+            &[]
+        );
+
+        rule_false!(
+            r#"
+            import "string"
+            rule test {
+              condition:
+                string.to_int("--1") == 1 or
+                string.to_int("-+1") == -1 or
+                string.to_int("0x") == 0 or
+                string.to_int("12 ") == 12 or
+                string.to_int("10", 1) == 10 or
+                string.to_int("10", 37) == 36 or
+                string.to_int("0x10", 10) == 16 or
+                defined string.to_int("")
+            }"#,
+            &[]
+        );
+    }
+
+    #[test]
+    fn hex() {
+        rule_true!(
+            r#"
+            import "string"
+            rule test {
+              condition:
+                string.hex("ABC") == "414243" and
+                string.hex("\x00\xff") == "00ff" and
+                string.hex("") == ""
+            }"#,
+            &[]
+        );
     }
 }
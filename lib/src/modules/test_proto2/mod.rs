@@ -103,6 +103,9 @@ fn main(data: &[u8], _meta: Option<&[u8]>) -> TestProto2 {
 
     test.set_bool_proto(true);
     test.set_file_size(data.len() as u64);
+    test.set_enumeration(
+        crate::modules::protos::test_proto2::test_proto2::Enumeration::ITEM_3,
+    );
 
     test.array_int64.push(1);
     test.array_int64.push(10);
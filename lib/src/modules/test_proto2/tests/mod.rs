@@ -106,6 +106,13 @@ fn test_proto2_module() {
     condition_true!(r#"test_proto2.Enumeration.ITEM_2 == 0x7fffffffffff"#);
     condition_true!(r#"test_proto2.Enumeration.ITEM_3 == -1"#);
 
+    // Fields of enum type contain the value of the enum item, not its tag
+    // number.
+    condition_true!(
+        r#"test_proto2.enumeration == test_proto2.Enumeration.ITEM_3"#
+    );
+    condition_true!(r#"test_proto2.enumeration == -1"#);
+
     condition_true!(r#"test_proto2.INLINE_0x1000 == 0x1000"#);
 
     condition_true!(
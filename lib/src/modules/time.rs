@@ -13,8 +13,19 @@ fn now(_ctx: &ScanContext) -> Option<i64> {
     Some(SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
 }
 
+#[module_export]
+fn file_mtime(ctx: &ScanContext) -> Option<i64> {
+    ctx.file_metadata?.mtime
+}
+
+#[module_export]
+fn file_ctime(ctx: &ScanContext) -> Option<i64> {
+    ctx.file_metadata?.ctime
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::tests::rule_false;
     use crate::tests::rule_true;
     use crate::tests::test_rule;
 
@@ -27,4 +38,37 @@ mod tests {
             &[]
         );
     }
+
+    #[test]
+    fn file_times() {
+        let rules = crate::compile(
+            r#"
+            import "time"
+            rule test {
+              condition:
+                time.file_mtime() > 0 and
+                time.file_mtime() <= time.now() and
+                time.file_ctime() > 0 and
+                time.file_ctime() <= time.now()
+            }"#,
+        )
+        .unwrap();
+
+        let mut scanner = crate::scanner::Scanner::new(&rules);
+
+        let results = scanner.scan_file("src/modules/time.rs").unwrap();
+        assert_eq!(results.matching_rules().len(), 1);
+
+        // When scanning data that doesn't come from a file the file times
+        // are undefined.
+        rule_false!(
+            r#"
+            import "time"
+            rule test {
+              condition:
+                defined time.file_mtime() or defined time.file_ctime()
+            }"#,
+            &[]
+        );
+    }
 }
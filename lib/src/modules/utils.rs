@@ -0,0 +1,87 @@
+/*! Utility functions shared by multiple modules. */
+
+/// Computes the Shannon entropy of `data`, in bits per byte.
+///
+/// The result goes from 0.0 (all bytes are equal) to 8.0 (all byte values
+/// are equally frequent). The entropy of an empty slice is 0.0.
+#[allow(dead_code)]
+pub(crate) fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut distribution = [0u64; 256];
+    for byte in data {
+        distribution[*byte as usize] += 1;
+    }
+
+    let mut entropy: f64 = 0.0;
+    for value in &distribution {
+        if *value != 0 {
+            let x = *value as f64 / data.len() as f64;
+            entropy -= x * f64::log2(x);
+        }
+    }
+
+    entropy
+}
+
+/// Decodes a string in MUTF-8 (Modified UTF-8), the encoding used by Java
+/// for serialized strings.
+///
+/// MUTF-8 differs from standard UTF-8 in the encoding of the null character,
+/// which uses two bytes (`C0 80`), and in characters outside the BMP, which
+/// are encoded as surrogate pairs where each surrogate uses three bytes.
+/// Invalid sequences are replaced with U+FFFD.
+#[allow(dead_code)]
+pub(crate) fn decode_mutf8(input: &[u8]) -> String {
+    // Most strings are plain ASCII, which are valid UTF-8 as well.
+    if let Ok(s) = std::str::from_utf8(input) {
+        return s.to_string();
+    }
+
+    let mut units = Vec::with_capacity(input.len());
+    let mut i = 0;
+
+    while i < input.len() {
+        let b = input[i];
+        let cont =
+            |n: usize| input.get(i + n).copied().filter(|c| c & 0xc0 == 0x80);
+        match b {
+            0x00..=0x7f => {
+                units.push(b as u16);
+                i += 1;
+            }
+            0xc0..=0xdf => match cont(1) {
+                Some(c1) => {
+                    units.push(((b as u16 & 0x1f) << 6) | (c1 as u16 & 0x3f));
+                    i += 2;
+                }
+                None => {
+                    units.push(0xfffd);
+                    i += 1;
+                }
+            },
+            0xe0..=0xef => match (cont(1), cont(2)) {
+                (Some(c1), Some(c2)) => {
+                    units.push(
+                        ((b as u16 & 0x0f) << 12)
+                            | ((c1 as u16 & 0x3f) << 6)
+                            | (c2 as u16 & 0x3f),
+                    );
+                    i += 3;
+                }
+                _ => {
+                    units.push(0xfffd);
+                    i += 1;
+                }
+            },
+            _ => {
+                units.push(0xfffd);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf16_lossy(&units)
+}
@@ -0,0 +1,65 @@
+/*! YARA module that parses WebAssembly binaries.
+
+This module parses WebAssembly modules in binary format, and exposes their
+sections, imports, exports and memories. It also provides functions for
+checking whether the module imports or exports some specific function.
+
+This module is based on the [`WebAssembly binary format specification`][1].
+
+[1]: https://webassembly.github.io/spec/core/binary/index.html
+ */
+
+use crate::modules::prelude::*;
+use crate::modules::protos::wasm::*;
+
+mod parser;
+#[cfg(test)]
+mod tests;
+
+/// Returns false if `data` can't be a WebAssembly module.
+pub(crate) fn sniff(data: &[u8]) -> bool {
+    data.starts_with(b"\0asm")
+}
+
+#[module_main]
+fn main(data: &[u8], _meta: Option<&[u8]>) -> Wasm {
+    match parser::WasmParser::new().parse(data) {
+        Ok(wasm) => wasm,
+        Err(_) => {
+            let mut wasm = Wasm::new();
+            wasm.is_wasm = Some(false);
+            wasm
+        }
+    }
+}
+
+/// Returns true if the module imports a function with the given name from
+/// the given module.
+#[module_export]
+fn imports_function(
+    ctx: &ScanContext,
+    module: RuntimeString,
+    name: RuntimeString,
+) -> Option<bool> {
+    let wasm = ctx.module_output::<Wasm>()?;
+    let module = module.as_bstr(ctx);
+    let name = name.as_bstr(ctx);
+
+    Some(wasm.imports.iter().any(|import| {
+        import.kind() == ExternalKind::FUNCTION
+            && import.module.as_ref().is_some_and(|m| module == m)
+            && import.name.as_ref().is_some_and(|n| name == n)
+    }))
+}
+
+/// Returns true if the module exports a function with the given name.
+#[module_export]
+fn exports_function(ctx: &ScanContext, name: RuntimeString) -> Option<bool> {
+    let wasm = ctx.module_output::<Wasm>()?;
+    let name = name.as_bstr(ctx);
+
+    Some(wasm.exports.iter().any(|export| {
+        export.kind() == ExternalKind::FUNCTION
+            && export.name.as_ref().is_some_and(|n| name == n)
+    }))
+}
@@ -0,0 +1,241 @@
+use nom::bytes::complete::{tag, take};
+use nom::combinator::{map, map_res, verify};
+use nom::multi::length_data;
+use nom::number::complete::{le_u32, u8};
+use nom::sequence::tuple;
+use nom::IResult;
+use protobuf::EnumOrUnknown;
+
+use crate::modules::protos::wasm::{
+    Export, ExternalKind, Import, Memory, Section, SectionId, Wasm,
+};
+
+/// Maximum number of items that are parsed from a vector. Vectors in
+/// WebAssembly are prefixed by their length, which can't be trusted.
+const MAX_ITEMS: u32 = 100_000;
+
+/// A WebAssembly binary parser.
+pub struct WasmParser {
+    result: Wasm,
+}
+
+impl WasmParser {
+    /// Creates a new parser for WebAssembly binaries.
+    pub fn new() -> Self {
+        Self { result: Wasm::default() }
+    }
+
+    /// Parses a WebAssembly binary and produces a [`Wasm`] protobuf
+    /// containing information about the module.
+    pub fn parse(
+        mut self,
+        data: &[u8],
+    ) -> Result<Wasm, nom::Err<nom::error::Error<&[u8]>>> {
+        let (mut input, (_magic, version)) =
+            tuple((tag(b"\0asm"), verify(le_u32, |v| *v == 1)))(data)?;
+
+        self.result.is_wasm = Some(true);
+        self.result.version = Some(version);
+
+        let mut num_functions = 0;
+
+        // Each section starts with a one-byte identifier and the size of
+        // its content.
+        while !input.is_empty() {
+            let (remainder, (id, content)) =
+                tuple((u8, length_data(uleb128_u32)))(input)?;
+
+            let offset = data.len() - remainder.len() - content.len();
+
+            input = remainder;
+
+            let mut section = Section::new();
+
+            section.id =
+                Some(EnumOrUnknown::<SectionId>::from_i32(id as i32));
+            section.offset = Some(offset as u64);
+            section.size = Some(content.len() as u64);
+
+            // Errors in the content of a section are not fatal, the
+            // section is simply skipped.
+            match id {
+                0 => {
+                    if let Ok((_, name)) = name(content) {
+                        section.name = Some(name.clone());
+                        self.result.custom_sections.push(name);
+                    }
+                }
+                2 => {
+                    let _ = self.parse_imports(content);
+                }
+                3 => {
+                    if let Ok((_, n)) = uleb128_u32(content) {
+                        num_functions = n;
+                    }
+                }
+                5 => {
+                    let _ = self.parse_memories(content);
+                }
+                7 => {
+                    let _ = self.parse_exports(content);
+                }
+                _ => {}
+            }
+
+            self.result.sections.push(section);
+        }
+
+        self.result.num_functions = Some(num_functions as u64);
+
+        Ok(self.result)
+    }
+
+    fn parse_imports<'a>(&mut self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let (mut input, count) = uleb128_u32(input)?;
+
+        for _ in 0..count.min(MAX_ITEMS) {
+            let (remainder, (module, name, kind)) =
+                tuple((name, name, u8))(input)?;
+
+            // Parse the import descriptor, which depends on the kind.
+            let remainder = match kind {
+                // Function: type index.
+                0 => uleb128_u32(remainder)?.0,
+                // Table: reference type and limits.
+                1 => limits(u8(remainder)?.0)?.0,
+                // Memory: limits.
+                2 => {
+                    let (remainder, mut memory) = limits(remainder)?;
+                    memory.imported = Some(true);
+                    self.result.memories.push(memory);
+                    remainder
+                }
+                // Global: value type and mutability.
+                3 => take(2_usize)(remainder)?.0,
+                // Tag: attribute and type index.
+                4 => uleb128_u32(u8(remainder)?.0)?.0,
+                _ => {
+                    return Err(nom::Err::Error(nom::error::Error::new(
+                        remainder,
+                        nom::error::ErrorKind::Switch,
+                    )))
+                }
+            };
+
+            let mut import = Import::new();
+
+            import.module = Some(module);
+            import.name = Some(name);
+            import.kind = Some(EnumOrUnknown::<ExternalKind>::from_i32(
+                kind as i32,
+            ));
+
+            self.result.imports.push(import);
+
+            input = remainder;
+        }
+
+        Ok((input, ()))
+    }
+
+    fn parse_exports<'a>(&mut self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let (mut input, count) = uleb128_u32(input)?;
+
+        for _ in 0..count.min(MAX_ITEMS) {
+            let (remainder, (name, kind, index)) =
+                tuple((name, u8, uleb128_u32))(input)?;
+
+            let mut export = Export::new();
+
+            export.name = Some(name);
+            export.kind = Some(EnumOrUnknown::<ExternalKind>::from_i32(
+                kind as i32,
+            ));
+            export.index = Some(index);
+
+            self.result.exports.push(export);
+
+            input = remainder;
+        }
+
+        Ok((input, ()))
+    }
+
+    fn parse_memories<'a>(
+        &mut self,
+        input: &'a [u8],
+    ) -> IResult<&'a [u8], ()> {
+        let (mut input, count) = uleb128_u32(input)?;
+
+        for _ in 0..count.min(MAX_ITEMS) {
+            let (remainder, mut memory) = limits(input)?;
+            memory.imported = Some(false);
+            self.result.memories.push(memory);
+            input = remainder;
+        }
+
+        Ok((input, ()))
+    }
+}
+
+/// Parses the limits of a memory or table.
+///
+/// Limits start with a flags byte, where bit 0 indicates if there's a
+/// maximum, bit 1 indicates that the memory is shared, and bit 2 indicates
+/// that the memory uses 64-bit indexes, in which case the limits are
+/// encoded as 64-bit integers.
+fn limits(input: &[u8]) -> IResult<&[u8], Memory> {
+    let (input, flags) = u8(input)?;
+
+    let has_max = flags & 0x01 != 0;
+    let shared = flags & 0x02 != 0;
+    let memory64 = flags & 0x04 != 0;
+
+    let (input, min) = uleb128_u64(input)?;
+
+    let (input, max) = if has_max {
+        map(uleb128_u64, Some)(input)?
+    } else {
+        (input, None)
+    };
+
+    let mut memory = Memory::new();
+
+    memory.min = Some(min);
+    memory.max = max;
+    memory.shared = Some(shared);
+    memory.memory64 = Some(memory64);
+
+    Ok((input, memory))
+}
+
+/// Parses a name, which is a UTF-8 string prefixed by its length.
+fn name(input: &[u8]) -> IResult<&[u8], String> {
+    map_res(length_data(uleb128_u32), |s: &[u8]| {
+        String::from_utf8(s.to_vec())
+    })(input)
+}
+
+fn uleb128_u32(input: &[u8]) -> IResult<&[u8], u32> {
+    map_res(uleb128_u64, u32::try_from)(input)
+}
+
+/// Parses an unsigned LEB128 integer of up to 64 bits.
+fn uleb128_u64(input: &[u8]) -> IResult<&[u8], u64> {
+    let mut result = 0_u64;
+    let mut input = input;
+
+    for i in 0..10 {
+        let (remainder, byte) = u8(input)?;
+        input = remainder;
+        result |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((input, result));
+        }
+    }
+
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::TooLarge,
+    )))
+}
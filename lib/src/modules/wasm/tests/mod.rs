@@ -0,0 +1,90 @@
+use crate::modules::tests::create_binary_from_zipped_ihex;
+use crate::tests::rule_false;
+use crate::tests::rule_true;
+use crate::tests::test_rule;
+
+#[test]
+fn test_wasm_module() {
+    let wasm = create_binary_from_zipped_ihex(
+        "src/modules/wasm/tests/testdata/miner.wasm.in.zip",
+    );
+
+    rule_false!(
+        r#"
+        import "wasm"
+        rule test {
+          condition:
+            wasm.is_wasm
+        }
+        "#,
+        b"\0asm\x02\0\0\0"
+    );
+
+    rule_true!(
+        r#"
+        import "wasm"
+        rule test {
+          condition:
+            wasm.is_wasm and
+            wasm.version == 1 and
+            wasm.num_functions == 2 and
+            wasm.sections[0].id == wasm.SectionId.TYPE_SECTION and
+            wasm.sections[0].offset == 10 and
+            wasm.sections[1].id == wasm.SectionId.IMPORT_SECTION and
+            wasm.sections[6].id == wasm.SectionId.CUSTOM_SECTION and
+            wasm.sections[6].name == "producers" and
+            wasm.custom_sections[1] == "sourceMappingURL"
+        }
+        "#,
+        &wasm
+    );
+
+    rule_true!(
+        r#"
+        import "wasm"
+        rule test {
+          condition:
+            wasm.imports[0].module == "env" and
+            wasm.imports[0].name == "log" and
+            wasm.imports[0].kind == wasm.ExternalKind.FUNCTION and
+            wasm.imports[1].kind == wasm.ExternalKind.MEMORY and
+            wasm.exports[0].name == "cryptonight_hash" and
+            wasm.exports[0].index == 2 and
+            wasm.exports[2].kind == wasm.ExternalKind.MEMORY
+        }
+        "#,
+        &wasm
+    );
+
+    rule_true!(
+        r#"
+        import "wasm"
+        rule test {
+          condition:
+            wasm.memories[0].imported and
+            wasm.memories[0].shared and
+            wasm.memories[0].min == 1 and
+            wasm.memories[0].max == 256 and
+            not wasm.memories[1].imported and
+            not wasm.memories[1].shared and
+            wasm.memories[1].min == 2 and
+            wasm.memories[1].max == 10
+        }
+        "#,
+        &wasm
+    );
+
+    rule_true!(
+        r#"
+        import "wasm"
+        rule test {
+          condition:
+            wasm.imports_function("wasi_snapshot_preview1", "fd_write") and
+            not wasm.imports_function("env", "memory") and
+            wasm.exports_function("cryptonight_hash") and
+            not wasm.exports_function("mem")
+        }
+        "#,
+        &wasm
+    );
+}
@@ -7,10 +7,10 @@ use regex_syntax::hir::Class;
 use regex_syntax::hir::ClassBytes;
 use regex_syntax::hir::ClassBytesRange;
 use regex_syntax::hir::ClassUnicode;
-use regex_syntax::hir::ClassUnicodeRange;
 use regex_syntax::hir::Dot;
 use regex_syntax::hir::HirKind;
 use regex_syntax::hir::Repetition;
+use regex_syntax::utf8::{Utf8Range, Utf8Sequences};
 
 use yara_x_parser::ast;
 
@@ -34,6 +34,17 @@ pub(crate) struct ChainedPattern {
     pub hir: Hir,
 }
 
+impl ChainedPattern {
+    /// Returns the gap between this pattern and the previous one in the
+    /// chain, measured in bytes, for the `wide` variant of the pattern.
+    ///
+    /// The gap in [`ChainedPattern::gap`] is expressed in characters, which
+    /// are two bytes long in wide strings.
+    pub fn wide_gap(&self) -> RangeInclusive<u32> {
+        self.gap.start().saturating_mul(2)..=self.gap.end().saturating_mul(2)
+    }
+}
+
 /// High level intermediate representation (HIR) for a regular expression.
 ///
 /// This is a thin wrapper around [`regex_syntax::hir::Hir`] that implements
@@ -355,6 +366,11 @@ impl<'a, H: Hasher> regex_syntax::hir::Visitor for HirHasher<'a, H> {
 ///
 /// For example `??` in an hex pattern, or `.` in a regexp that uses the `/s`
 /// modifier (i.e: `dot_matches_new_line` is true).
+///
+/// Unicode classes are never considered as a class containing all bytes,
+/// not even when they contain all characters. In Unicode mode (i.e: `(?u)`)
+/// the dot matches the UTF-8 encoding of any character, which can be
+/// multiple bytes long.
 pub fn any_byte(hir_kind: &HirKind) -> bool {
     match hir_kind {
         HirKind::Class(Class::Bytes(class)) => {
@@ -364,13 +380,6 @@ pub fn any_byte(hir_kind: &HirKind) -> bool {
                 false
             }
         }
-        HirKind::Class(Class::Unicode(class)) => {
-            if let Some(range) = class.ranges().first() {
-                range.start() == 0 as char && range.end() == char::MAX
-            } else {
-                false
-            }
-        }
         _ => false,
     }
 }
@@ -393,17 +402,89 @@ pub fn any_byte_except_newline(hir_kind: &HirKind) -> bool {
             ]);
             all_bytes_except_newline.eq(class)
         }
-        HirKind::Class(Class::Unicode(class)) => {
-            let all_bytes_except_newline = ClassUnicode::new([
-                ClassUnicodeRange::new(0x00 as char, 0x09 as char),
-                ClassUnicodeRange::new(0x0B as char, char::MAX),
-            ]);
-            all_bytes_except_newline.eq(class)
-        }
         _ => false,
     }
 }
 
+/// Converts a Unicode class into an equivalent expression that matches the
+/// UTF-8 encoding of the characters in the class, byte by byte.
+///
+/// For example, the class `[α-ω]` is converted into `\xCE[\xB1-\xBF] |
+/// \xCF[\x80-\x89]`. The resulting expression is a tree of alternations
+/// and concatenations where sequences that share the same leading byte range
+/// are grouped together. The characters that are encoded with a single byte
+/// (i.e: ASCII characters) are put together in a single byte class.
+///
+/// Notice that the result can be a Unicode class again. This happens when
+/// the expression ends up being an alternation of literals, where each
+/// literal is the UTF-8 encoding of a single character, as `regex_syntax`
+/// turns such alternations into a Unicode class.
+pub fn utf8_class_to_hir(class: &ClassUnicode) -> regex_syntax::hir::Hir {
+    let mut ascii = ClassBytes::empty();
+    let mut sequences = Vec::new();
+
+    for range in class.ranges() {
+        for seq in Utf8Sequences::new(range.start(), range.end()) {
+            match seq.as_slice() {
+                [byte] => {
+                    ascii.push(ClassBytesRange::new(byte.start, byte.end))
+                }
+                seq => sequences.push(seq.to_vec()),
+            }
+        }
+    }
+
+    let mut alternatives = Vec::new();
+
+    if !ascii.ranges().is_empty() {
+        alternatives.push(regex_syntax::hir::Hir::class(Class::Bytes(ascii)));
+    }
+
+    alternatives.push(utf8_sequences_to_hir(sequences.as_slice()));
+
+    regex_syntax::hir::Hir::alternation(alternatives)
+}
+
+/// Converts a sequence of UTF-8 sequences, sorted in ascending order, into
+/// the alternation of all of them. Sequences that start with the same range
+/// of bytes share a common prefix in the resulting expression.
+fn utf8_sequences_to_hir(
+    sequences: &[Vec<Utf8Range>],
+) -> regex_syntax::hir::Hir {
+    let mut alternatives = Vec::new();
+    let mut sequences = sequences;
+
+    while let Some(seq) = sequences.first() {
+        let first = seq[0];
+        let group_len = sequences
+            .iter()
+            .take_while(|seq| seq.first() == Some(&first))
+            .count();
+
+        let (group, rest) = sequences.split_at(group_len);
+        sequences = rest;
+
+        let first =
+            regex_syntax::hir::Hir::class(Class::Bytes(ClassBytes::new([
+                ClassBytesRange::new(first.start, first.end),
+            ])));
+
+        let tails: Vec<Vec<Utf8Range>> =
+            group.iter().map(|seq| seq[1..].to_vec()).collect();
+
+        if tails.iter().all(|tail| tail.is_empty()) {
+            alternatives.push(first);
+        } else {
+            alternatives.push(regex_syntax::hir::Hir::concat(vec![
+                first,
+                utf8_sequences_to_hir(tails.as_slice()),
+            ]));
+        }
+    }
+
+    regex_syntax::hir::Hir::alternation(alternatives)
+}
+
 /// Returns [`Some(HexByte)`] if the given [`ClassBytes`] represents a
 /// masked byte.
 ///
@@ -68,6 +68,44 @@ pub(crate) enum Error {
     FastIncompatible,
 }
 
+/// Semantics of word boundary assertions in regular expressions.
+///
+/// Determines which characters are considered word characters by `\b`,
+/// `\B`, `\b{start}` and `\b{end}` in regexp and hex patterns. This
+/// doesn't affect the regular expressions used with the `matches` operator
+/// in rule conditions.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Default, Eq, PartialEq, Debug,
+)]
+pub enum WordBoundary {
+    /// Word characters are ASCII letters and digits. The underscore is not
+    /// a word character.
+    Ascii,
+    /// Word characters are ASCII letters, digits and the underscore, as in
+    /// most regular expression engines. This is the default.
+    #[default]
+    Standard,
+    /// Same as [`WordBoundary::Standard`], but while matching the `wide`
+    /// variant of a pattern, a two-byte character is a word character only
+    /// if its second byte is zero. With [`WordBoundary::Standard`] only the
+    /// first byte is taken into account, which means that `\x41\x04` (the
+    /// Cyrillic letter `А` in UTF-16) is considered equal to `A`.
+    Wide,
+}
+
+impl WordBoundary {
+    /// Returns true if `byte` is a word character.
+    #[inline]
+    pub(crate) fn is_word_char(&self, byte: u8) -> bool {
+        match self {
+            WordBoundary::Ascii => byte.is_ascii_alphanumeric(),
+            WordBoundary::Standard | WordBoundary::Wide => {
+                byte == b'_' || byte.is_ascii_alphanumeric()
+            }
+        }
+    }
+}
+
 /// Represents an atom extracted from a regular expression.
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub(crate) struct RegexpAtom {
@@ -168,7 +206,9 @@ pub(crate) enum Action {
 ///
 /// When the iterator finds a byte that is expected to be zero, but it's
 /// not, it saves the number of valid pairs that were consumed before
-/// finding this invalid pair.
+/// finding this invalid pair. Invalid pairs are returned as the non-zero
+/// byte that was expected, unless [`WideIter::mask_invalid`] is used, in
+/// which case they are returned as a zero.
 ///
 /// ```ignore
 /// let error_pos = Cell::new(None);
@@ -208,6 +248,7 @@ where
     error_pos: &'a Cell<Option<usize>>,
     valid_pairs: usize,
     zero_first: bool,
+    mask_invalid: bool,
 }
 
 impl<'a, I> WideIter<'a, I>
@@ -218,14 +259,33 @@ where
     where
         I: Iterator<Item = &'a u8>,
     {
-        WideIter { iter, error_pos, valid_pairs: 0, zero_first: false }
+        WideIter {
+            iter,
+            error_pos,
+            valid_pairs: 0,
+            zero_first: false,
+            mask_invalid: false,
+        }
     }
 
     pub fn zero_first(iter: I, error_pos: &'a Cell<Option<usize>>) -> Self
     where
         I: Iterator<Item = &'a u8>,
     {
-        WideIter { iter, error_pos, valid_pairs: 0, zero_first: true }
+        WideIter {
+            iter,
+            error_pos,
+            valid_pairs: 0,
+            zero_first: true,
+            mask_invalid: false,
+        }
+    }
+
+    /// If `yes` is true, invalid pairs are returned as a zero instead of
+    /// the byte that was expected to be non-zero.
+    pub fn mask_invalid(mut self, yes: bool) -> Self {
+        self.mask_invalid = yes;
+        self
     }
 }
 
@@ -239,18 +299,23 @@ where
         let first_byte = self.iter.next()?;
         let second_byte = self.iter.next()?;
 
-        if self.zero_first {
-            if *first_byte != 0_u8 && self.error_pos.get().is_none() {
-                self.error_pos.set(Some(self.valid_pairs));
-            }
-            self.valid_pairs += 1;
-            Some(second_byte)
+        let (byte, zero) = if self.zero_first {
+            (second_byte, first_byte)
         } else {
-            if *second_byte != 0_u8 && self.error_pos.get().is_none() {
+            (first_byte, second_byte)
+        };
+
+        if *zero != 0_u8 {
+            if self.error_pos.get().is_none() {
                 self.error_pos.set(Some(self.valid_pairs));
             }
-            self.valid_pairs += 1;
-            Some(first_byte)
+            if self.mask_invalid {
+                self.valid_pairs += 1;
+                return Some(&0);
+            }
         }
+
+        self.valid_pairs += 1;
+        Some(byte)
     }
 }
@@ -6,6 +6,7 @@ More specifically, the compiler produces two instruction sequences, one that
 matches the regexp left-to-right, and another one that matches right-to-left.
 */
 
+use std::borrow::Cow;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
@@ -20,7 +21,8 @@ use bitvec::order::Lsb0;
 use regex_syntax::hir;
 use regex_syntax::hir::literal::Seq;
 use regex_syntax::hir::{
-    visit, Class, ClassBytes, Hir, HirKind, Literal, Look, Repetition, Visitor,
+    visit, Capture, Class, ClassBytes, Hir, HirKind, Literal, Look,
+    Repetition, Visitor,
 };
 
 use super::instr;
@@ -36,6 +38,11 @@ use crate::re::hir::HexByte;
 use crate::re::thompson::instr::{InstrParser, SplitId};
 use crate::re::{BckCodeLoc, Error, FwdCodeLoc, MAX_ALTERNATIVES};
 
+/// Bounded repetitions of any byte (e.g: `.{0,100}`) where the maximum number
+/// of repetitions is larger than this value are compiled into a single
+/// [`Instr::RepeatAny`] instruction instead of being unrolled.
+const REPEAT_ANY_THRESHOLD: u32 = 16;
+
 #[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
 pub(crate) struct CodeLoc {
     pub fwd: usize,
@@ -213,8 +220,10 @@ impl Compiler {
     ) -> Result<(InstrSeq, InstrSeq, Vec<RegexpAtom>), Error> {
         let start_loc = self.location();
 
+        let inner = remove_epsilon_cycles(&hir.inner);
+
         let (mut backward_code, mut forward_code, mut atoms) =
-            visit(&hir.inner, self)?;
+            visit(inner.as_ref(), self)?;
 
         forward_code.emit_instr(Instr::MATCH)?;
         backward_code.emit_instr(Instr::MATCH)?;
@@ -299,6 +308,29 @@ impl Compiler {
         }
     }
 
+    fn emit_repeat_any(
+        &mut self,
+        min: u16,
+        max: u16,
+        greedy: bool,
+        newline: bool,
+    ) -> CodeLoc {
+        CodeLoc {
+            fwd: self
+                .forward_code_mut()
+                .emit_repeat_any(min, max, greedy, newline),
+            bck_seq_id: self.backward_code().seq_id(),
+            bck: self
+                .backward_code_mut()
+                .emit_repeat_any(min, max, greedy, newline),
+        }
+    }
+
+    fn truncate(&mut self, location: CodeLoc) {
+        self.forward_code_mut().truncate(location.fwd);
+        self.backward_code_mut().truncate(location.bck);
+    }
+
     fn emit_clone(
         &mut self,
         start: CodeLoc,
@@ -348,13 +380,35 @@ impl Compiler {
                 // `regex-syntax` crate, and we should be able to handle it.
                 // See: https://github.com/rust-lang/regex/issues/1088
                 //
+                // Unicode classes are also produced by regexps that enable
+                // Unicode support with the `(?u)` flag, like `(?u:\p{Greek})`
+                // or `(?u:.)`.
+                //
                 // The first thing we do is trying to covert the unicode class
-                // into a byte class. If that's not possible, the alternative
-                // is converting the unicode class to an alternation of literals,
-                // where each literal is the UTF-8 encoding of one character in
-                // the class.
+                // into a byte class. If that's not possible, the class is
+                // converted into an expression that matches the UTF-8
+                // encoding of the characters in the class byte by byte. In
+                // the rare cases where `regex-syntax` turns that expression
+                // into a unicode class again, the alternative is converting
+                // the unicode class to an alternation of literals, where each
+                // literal is the UTF-8 encoding of one character in the class.
                 if let Some(class) = class.to_byte_class() {
-                    Ok(self.emit_class(&class))
+                    return Ok(self.emit_class(&class));
+                }
+
+                let utf8 = re::hir::utf8_class_to_hir(class);
+
+                if !matches!(utf8.kind(), HirKind::Class(Class::Unicode(_))) {
+                    let code_loc = self.location();
+                    // Atoms are extracted from the unicode class itself, not
+                    // from the expression generated for it. Incrementing
+                    // `zero_rep_depth` prevents the extraction of atoms
+                    // while visiting the expression, which can be very slow
+                    // for large classes like `\w` or `\p{L}`.
+                    self.zero_rep_depth += 1;
+                    self.visit_nested(&utf8)?;
+                    self.zero_rep_depth -= 1;
+                    Ok(code_loc)
                 } else {
                     let mut lits = Vec::new();
                     for range in class.ranges() {
@@ -385,16 +439,55 @@ impl Compiler {
         }
     }
 
+    /// Visits a HIR node that is not part of the HIR being compiled, but
+    /// that is generated while compiling some other node, emitting the code
+    /// for the node and all its children.
+    fn visit_nested(&mut self, hir: &Hir) -> Result<(), Error> {
+        self.visit_pre(hir)?;
+        match hir.kind() {
+            HirKind::Concat(children) => {
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        self.visit_concat_in()?;
+                    }
+                    self.visit_nested(child)?;
+                }
+            }
+            HirKind::Alternation(children) => {
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        self.visit_alternation_in()?;
+                    }
+                    self.visit_nested(child)?;
+                }
+            }
+            HirKind::Capture(capture) => self.visit_nested(&capture.sub)?,
+            HirKind::Repetition(rep) => self.visit_nested(&rep.sub)?,
+            _ => {}
+        }
+        self.visit_post(hir)
+    }
+
     fn visit_post_look(&mut self, look: &Look) -> Result<CodeLoc, Error> {
         Ok(match look {
             Look::Start => self.emit_instr(Instr::START)?,
             Look::End => self.emit_instr(Instr::END)?,
-            Look::WordAscii => self.emit_instr(Instr::WORD_BOUNDARY)?,
-            Look::WordAsciiNegate => {
+            // Word boundaries are always ASCII-aware, even when Unicode
+            // support is enabled with `(?u)`, because the VM doesn't
+            // decode UTF-8 while looking at the bytes around the current
+            // position.
+            Look::WordAscii | Look::WordUnicode => {
+                self.emit_instr(Instr::WORD_BOUNDARY)?
+            }
+            Look::WordAsciiNegate | Look::WordUnicodeNegate => {
                 self.emit_instr(Instr::WORD_BOUNDARY_NEG)?
             }
-            Look::WordStartAscii => self.emit_instr(Instr::WORD_START)?,
-            Look::WordEndAscii => self.emit_instr(Instr::WORD_END)?,
+            Look::WordStartAscii | Look::WordStartUnicode => {
+                self.emit_instr(Instr::WORD_START)?
+            }
+            Look::WordEndAscii | Look::WordEndUnicode => {
+                self.emit_instr(Instr::WORD_END)?
+            }
             _ => unreachable!("{:?}", look),
         })
     }
@@ -736,6 +829,33 @@ impl Compiler {
 
                 Ok(start)
             }
+            // e{min,max}   where e matches any byte and max is large
+            //
+            //     split end  ( only if min == 0 )
+            //     repeat_any max(min, 1) max
+            // end:
+            //
+            (min, Some(max), greedy) if is_repeat_any(rep) => {
+                let start = self.bookmarks.pop().unwrap();
+
+                // The code for `e` was emitted while visiting the child node,
+                // but it's replaced with a single repeat_any instruction.
+                self.truncate(start);
+                self.emit_repeat_any(
+                    min.max(1) as u16,
+                    max as u16,
+                    greedy,
+                    re::hir::any_byte(rep.sub.kind()),
+                );
+
+                if min == 0 {
+                    let split = self.bookmarks.pop().unwrap();
+                    self.patch_instr(&split, self.location().sub(&split)?);
+                    self.zero_rep_depth -= 1;
+                }
+
+                Ok(start)
+            }
             // e{min,max}
             //
             //     ... code for e ... -+
@@ -1313,6 +1433,43 @@ impl InstrSeq {
         location
     }
 
+    /// Adds a [`Instr::RepeatAny`] instruction at the end of the sequence and
+    /// returns the location where the newly added instruction resides.
+    pub fn emit_repeat_any(
+        &mut self,
+        min: u16,
+        max: u16,
+        greedy: bool,
+        newline: bool,
+    ) -> usize {
+        let location = self.location();
+
+        let mut flags = 0;
+
+        if greedy {
+            flags |= Instr::REPEAT_GREEDY;
+        }
+
+        if newline {
+            flags |= Instr::REPEAT_NEWLINE;
+        }
+
+        self.seq
+            .write_all(&[OPCODE_PREFIX, Instr::REPEAT_ANY, flags])
+            .unwrap();
+        self.seq.write_all(min.to_le_bytes().as_slice()).unwrap();
+        self.seq.write_all(max.to_le_bytes().as_slice()).unwrap();
+
+        location
+    }
+
+    /// Removes the code that goes from `location` to the end of the
+    /// sequence.
+    pub fn truncate(&mut self, location: usize) {
+        self.seq.get_mut().truncate(location);
+        self.seq.seek(SeekFrom::Start(location as u64)).unwrap();
+    }
+
     /// Emits a clone of the code that goes from `start` to `end`, both
     /// inclusive.
     ///
@@ -1560,6 +1717,17 @@ impl Display for InstrSeq {
                 Instr::WordEnd => {
                     writeln!(f, "{:05x}: WORD_END", addr)?;
                 }
+                Instr::RepeatAny { min, max, greedy, newline } => {
+                    writeln!(
+                        f,
+                        "{:05x}: REPEAT_ANY{}{} {} {}",
+                        addr,
+                        if greedy { "" } else { "_NON_GREEDY" },
+                        if newline { "" } else { "_NO_NEWLINE" },
+                        min,
+                        max,
+                    )?;
+                }
                 Instr::Match => {
                     writeln!(f, "{:05x}: MATCH", addr)?;
                     break;
@@ -1571,6 +1739,149 @@ impl Display for InstrSeq {
     }
 }
 
+/// Returns true if `rep` is a bounded repetition of any byte, like
+/// `.{0,100}` or `[^\n]{0,100}`, that must be compiled into a
+/// [`Instr::RepeatAny`] instruction.
+fn is_repeat_any(rep: &Repetition) -> bool {
+    match rep.max {
+        Some(max) if max > REPEAT_ANY_THRESHOLD && max <= u16::MAX.into() => {
+            re::hir::any_byte(rep.sub.kind())
+                || re::hir::any_byte_except_newline(rep.sub.kind())
+        }
+        _ => false,
+    }
+}
+
+/// Rewrites unbounded repetitions whose body can match the empty string.
+///
+/// In regexps like `(a*)*` or `(a|b?)+` the body of the outer repetition can
+/// match the empty string, which means that the code produced for them
+/// contains cycles formed exclusively by split and jump instructions (i.e:
+/// epsilon cycles). The Pike VM can't loop forever on these cycles, but it
+/// wastes time following them while computing epsilon closures. As the outer
+/// repetition already matches the empty string, the body can be replaced
+/// with an equivalent expression that doesn't match the empty string, for
+/// instance `(a*)*` is rewritten as `(a+)*`, and `(a|b?)+` as `(a|b)*`.
+///
+/// Only greedy repetitions are rewritten, as changing the number of times
+/// a non-greedy repetition is tried could change the matches found. This
+/// means that non-greedy repetitions of nullable expressions, like `(a*)*?`
+/// or `(a|b?)+?`, still produce code with epsilon cycles. They are matched
+/// correctly, but computing their epsilon closures is slower. If the regexp
+/// doesn't contain any epsilon cycle, it is returned unmodified.
+fn remove_epsilon_cycles(hir: &Hir) -> Cow<'_, Hir> {
+    if has_epsilon_cycles(hir) {
+        Cow::Owned(rewrite_epsilon_cycles(hir))
+    } else {
+        Cow::Borrowed(hir)
+    }
+}
+
+/// Returns true if `hir` contains some unbounded repetition whose body can
+/// match the empty string.
+fn has_epsilon_cycles(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Repetition(rep) => {
+            (rep.max.is_none() && is_nullable(&rep.sub))
+                || has_epsilon_cycles(&rep.sub)
+        }
+        HirKind::Capture(capture) => has_epsilon_cycles(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+            hirs.iter().any(has_epsilon_cycles)
+        }
+        _ => false,
+    }
+}
+
+/// See [`remove_epsilon_cycles`].
+fn rewrite_epsilon_cycles(hir: &Hir) -> Hir {
+    match hir.kind() {
+        HirKind::Repetition(rep) => {
+            let sub = rewrite_epsilon_cycles(&rep.sub);
+            // `e{n,}` where `e` matches the empty string is equivalent to
+            // `e'*`, where `e'` matches the same strings that `e`, except
+            // the empty one.
+            if rep.max.is_none() && rep.greedy && is_nullable(&sub) {
+                if let Some(sub) = non_empty(&sub) {
+                    return Hir::repetition(Repetition {
+                        min: 0,
+                        max: None,
+                        greedy: true,
+                        sub: Box::new(sub),
+                    });
+                }
+            }
+            Hir::repetition(Repetition {
+                min: rep.min,
+                max: rep.max,
+                greedy: rep.greedy,
+                sub: Box::new(sub),
+            })
+        }
+        HirKind::Capture(capture) => Hir::capture(Capture {
+            index: capture.index,
+            name: capture.name.clone(),
+            sub: Box::new(rewrite_epsilon_cycles(&capture.sub)),
+        }),
+        HirKind::Concat(hirs) => {
+            Hir::concat(hirs.iter().map(rewrite_epsilon_cycles).collect())
+        }
+        HirKind::Alternation(hirs) => {
+            Hir::alternation(hirs.iter().map(rewrite_epsilon_cycles).collect())
+        }
+        _ => hir.clone(),
+    }
+}
+
+/// Returns true if `hir` can match the empty string.
+#[inline]
+fn is_nullable(hir: &Hir) -> bool {
+    hir.properties().minimum_len() == Some(0)
+}
+
+/// Given a [`Hir`] returns another one that matches the same strings,
+/// except the empty one.
+///
+/// Returns `None` if such [`Hir`] can't be built, either because `hir` only
+/// matches the empty string, or because it is not easy to build (e.g: a
+/// concatenation where all the items can match the empty string).
+fn non_empty(hir: &Hir) -> Option<Hir> {
+    if !is_nullable(hir) {
+        return Some(hir.clone());
+    }
+    match hir.kind() {
+        HirKind::Capture(capture) => non_empty(&capture.sub).map(|sub| {
+            Hir::capture(Capture {
+                index: capture.index,
+                name: capture.name.clone(),
+                sub: Box::new(sub),
+            })
+        }),
+        // `e{n,m}` where `e` matches the empty string is equivalent to
+        // `e'{1,m}` once the empty string is excluded.
+        HirKind::Repetition(rep) if rep.greedy && rep.max != Some(0) => {
+            non_empty(&rep.sub).map(|sub| {
+                Hir::repetition(Repetition {
+                    min: 1,
+                    max: rep.max,
+                    greedy: true,
+                    sub: Box::new(sub),
+                })
+            })
+        }
+        HirKind::Alternation(alternatives) => {
+            let alternatives: Vec<Hir> =
+                alternatives.iter().filter_map(non_empty).collect();
+            if alternatives.is_empty() {
+                None
+            } else {
+                Some(Hir::alternation(alternatives))
+            }
+        }
+        _ => None,
+    }
+}
+
 /// Given a slice of [`Seq`] (sequence of literals), produce another [`Seq`]
 /// that is the concatenation of the first N sequences in the slice.
 ///
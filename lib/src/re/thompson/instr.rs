@@ -199,6 +199,16 @@ pub enum Instr<'a> {
     /// character and the following character is not a word character. This is a
     /// zero-length match.
     WordEnd,
+
+    /// Matches any byte (except newlines if `newline` is false) at least
+    /// `min` times and at most `max` times. Instead of being unrolled into
+    /// `max` copies of [`Instr::AnyByte`], large repetitions like `.{1,4096}`
+    /// are represented by this single instruction, and the VM keeps track of
+    /// the number of bytes matched by each thread. `min` is always >= 1,
+    /// repetitions that can be empty are preceded by a split instruction.
+    /// When `greedy` is true, threads that keep repeating have higher
+    /// priority than threads that continue at the next instruction.
+    RepeatAny { min: u16, max: u16, greedy: bool, newline: bool },
 }
 
 impl<'a> Instr<'a> {
@@ -218,6 +228,13 @@ impl<'a> Instr<'a> {
     pub const WORD_BOUNDARY_NEG: u8 = 0x0D;
     pub const WORD_START: u8 = 0x0E;
     pub const WORD_END: u8 = 0x0F;
+    pub const REPEAT_ANY: u8 = 0x10;
+
+    /// Flag that indicates that a [`Instr::RepeatAny`] instruction is greedy.
+    pub const REPEAT_GREEDY: u8 = 0x01;
+    /// Flag that indicates that a [`Instr::RepeatAny`] instruction accepts
+    /// newlines.
+    pub const REPEAT_NEWLINE: u8 = 0x02;
 }
 
 /// Parses a slice of bytes that contains Pike VM instructions, returning
@@ -309,6 +326,17 @@ impl<'a> InstrParser<'a> {
             }
             [OPCODE_PREFIX, Instr::WORD_START, ..] => (Instr::WordStart, 2),
             [OPCODE_PREFIX, Instr::WORD_END, ..] => (Instr::WordEnd, 2),
+            [OPCODE_PREFIX, Instr::REPEAT_ANY, flags, min_lo, min_hi, max_lo, max_hi, ..] => {
+                (
+                    Instr::RepeatAny {
+                        min: u16::from_le_bytes([min_lo, min_hi]),
+                        max: u16::from_le_bytes([max_lo, max_hi]),
+                        greedy: flags & Instr::REPEAT_GREEDY != 0,
+                        newline: flags & Instr::REPEAT_NEWLINE != 0,
+                    },
+                    3 + 2 * size_of::<u16>(),
+                )
+            }
             [OPCODE_PREFIX, Instr::MATCH, ..] => (Instr::Match, 2),
             [OPCODE_PREFIX, OPCODE_PREFIX, ..] => {
                 (Instr::Byte(OPCODE_PREFIX), 2)
@@ -2,11 +2,21 @@ use std::cell::Cell;
 use std::mem;
 
 use bitvec::array::BitArray;
+use rustc_hash::FxHashSet;
 
 use super::instr::{Instr, InstrParser};
 use crate::re::bitmapset::BitmapSet;
 use crate::re::thompson::instr::SplitId;
-use crate::re::{Action, CodeLoc, WideIter, DEFAULT_SCAN_LIMIT};
+use crate::re::{Action, CodeLoc, WideIter, WordBoundary, DEFAULT_SCAN_LIMIT};
+
+/// State of a [`PikeVM`] thread, identified by the instruction location, the
+/// repetition count and the position in the data.
+type State = (usize, u16, usize);
+
+/// Maximum number of dead states remembered by a [`PikeVM`]. When this limit
+/// is reached all the remembered states are forgotten. With 24 bytes per
+/// state, plus the overhead of the hash set, this is about 8MB.
+const MAX_DEAD_STATES: usize = 1 << 18;
 
 /// Represents a [Pike's VM](https://swtch.com/~rsc/regexp/regexp2.html) that
 /// executes VM code produced by the [compiler][`crate::re::compiler::Compiler`].
@@ -16,16 +26,26 @@ pub(crate) struct PikeVM<'r> {
     /// The set of currently active threads. Each item in this set is a
     /// position within the VM code, pointing to some VM instruction. Each item
     /// in the set is unique, the VM guarantees that there aren't two active
-    /// threads at the same VM instruction.
-    threads: BitmapSet,
+    /// threads at the same VM instruction, except for [`Instr::RepeatAny`],
+    /// where threads are distinguished by the number of bytes matched so far.
+    threads: ThreadSet,
     /// The set of threads that will become the active threads when the next
     /// byte is read from the input.
-    next_threads: BitmapSet,
+    next_threads: ThreadSet,
     /// Maximum number of bytes to scan. The VM will abort after ingesting
     /// this number of bytes from the input.
     scan_limit: u16,
+    /// Semantics of word boundary assertions. See [`PikeVM::word_boundary`].
+    word_boundary: WordBoundary,
     /// State for the [`epsilon_closure`] function.
     cache: EpsilonClosureState,
+    /// If true, the VM remembers the states that can't lead to a match. See
+    /// [`PikeVM::memoize`].
+    memoize: bool,
+    /// States that are known to not lead to a match.
+    dead_states: FxHashSet<State>,
+    /// States visited during the current call to [`PikeVM::try_match`].
+    visited_states: Vec<State>,
 }
 
 impl<'r> PikeVM<'r> {
@@ -33,10 +53,14 @@ impl<'r> PikeVM<'r> {
     pub fn new(code: &'r [u8]) -> Self {
         Self {
             code,
-            threads: BitmapSet::new(),
-            next_threads: BitmapSet::new(),
+            threads: ThreadSet::new(),
+            next_threads: ThreadSet::new(),
             cache: EpsilonClosureState::new(),
             scan_limit: DEFAULT_SCAN_LIMIT,
+            word_boundary: WordBoundary::default(),
+            memoize: false,
+            dead_states: FxHashSet::default(),
+            visited_states: Vec::new(),
         }
     }
 
@@ -57,6 +81,49 @@ impl<'r> PikeVM<'r> {
         self
     }
 
+    /// Specifies which characters are considered word characters by word
+    /// boundary assertions like `\b`. See [`WordBoundary`].
+    pub fn word_boundary(mut self, word_boundary: WordBoundary) -> Self {
+        self.word_boundary = word_boundary;
+        self
+    }
+
+    /// Enables or disables the memoization of states that don't lead to a
+    /// match.
+    ///
+    /// When a call to [`PikeVM::try_match`] doesn't find any match, all the
+    /// states visited during that call (i.e: pairs of instruction and position
+    /// in the data) are known to be dead ends. Subsequent calls that reach
+    /// one of those states don't need to explore it again. This bounds the
+    /// cost of matching patterns like `/a(b?c?)*d/` at many overlapping
+    /// starting points, which would be otherwise quadratic, at the expense of
+    /// the memory used for remembering the dead states.
+    ///
+    /// As states are identified by their position in the data, all calls
+    /// to [`PikeVM::try_match`] must receive slices of the same data, split
+    /// at different points. Memoization is not applied to wide patterns.
+    ///
+    /// The number of remembered states is limited, see [`MAX_DEAD_STATES`],
+    /// and [`PikeVM::memory_usage`] tells how much memory they use.
+    pub fn memoize(mut self, yes: bool) -> Self {
+        self.memoize = yes;
+        self
+    }
+
+    /// Returns the number of bytes used for memoizing dead states.
+    pub fn memory_usage(&self) -> usize {
+        // Each entry in the hash set has an additional control byte.
+        self.dead_states.capacity() * (mem::size_of::<State>() + 1)
+            + self.visited_states.capacity() * mem::size_of::<State>()
+    }
+
+    /// Forgets all the memoized dead states, releasing the memory used by
+    /// them.
+    pub fn clear_dead_states(&mut self) {
+        self.dead_states = FxHashSet::default();
+        self.visited_states = Vec::new();
+    }
+
     /// Executes VM code starting at the `start` location and calls `f` for
     /// each match found. The `right` slice contains the bytes at the right
     /// of the starting point (i.e: from the starting point until the end of
@@ -85,19 +152,32 @@ impl<'r> PikeVM<'r> {
     ) where
         C: CodeLoc,
     {
+        // With `WordBoundary::Wide`, two-byte characters where the second
+        // byte is not zero are seen as a zero by word boundary assertions,
+        // which means that they are not word characters. Matches that
+        // contain such characters are discarded anyway.
+        let mask_invalid = self.word_boundary == WordBoundary::Wide;
+
         match (start.backwards(), wide) {
             // Going forward, not wide.
-            (false, false) => {
-                self.try_match_impl(start, right.iter(), left.iter().rev(), f)
-            }
+            (false, false) => self.try_match_impl(
+                start,
+                right.iter(),
+                left.iter().rev(),
+                Some(left.len()),
+                f,
+            ),
             // Going forward, wide.
             (false, true) => {
                 let error_fwd = Cell::new(None);
                 let error_bck = Cell::new(None);
                 self.try_match_impl(
                     start,
-                    WideIter::non_zero_first(right.iter(), &error_fwd),
-                    WideIter::zero_first(left.iter().rev(), &error_bck),
+                    WideIter::non_zero_first(right.iter(), &error_fwd)
+                        .mask_invalid(mask_invalid),
+                    WideIter::zero_first(left.iter().rev(), &error_bck)
+                        .mask_invalid(mask_invalid),
+                    None,
                     |match_len| match error_fwd.get() {
                         Some(pos) if pos < match_len => Action::Stop,
                         _ => f(match_len * 2),
@@ -105,17 +185,24 @@ impl<'r> PikeVM<'r> {
                 )
             }
             // Going backward, not wide.
-            (true, false) => {
-                self.try_match_impl(start, left.iter().rev(), right.iter(), f)
-            }
+            (true, false) => self.try_match_impl(
+                start,
+                left.iter().rev(),
+                right.iter(),
+                Some(left.len()),
+                f,
+            ),
             // Going backward, wide.
             (true, true) => {
                 let error_fwd = Cell::new(None);
                 let error_bck = Cell::new(None);
                 self.try_match_impl(
                     start,
-                    WideIter::zero_first(left.iter().rev(), &error_fwd),
-                    WideIter::non_zero_first(right.iter(), &error_bck),
+                    WideIter::zero_first(left.iter().rev(), &error_fwd)
+                        .mask_invalid(mask_invalid),
+                    WideIter::non_zero_first(right.iter(), &error_bck)
+                        .mask_invalid(mask_invalid),
+                    None,
                     |match_len| match error_fwd.get() {
                         Some(pos) if pos < match_len => Action::Stop,
                         _ => f(match_len * 2),
@@ -152,11 +239,16 @@ impl<'r> PikeVM<'r> {
     /// that appear right before the start of `fwd_input` for matching some
     /// look-around assertions that need information about the surrounding
     /// bytes.
+    ///
+    /// `origin` is the position within the data where `fwd_input` starts,
+    /// which is used for memoizing dead states. If `None`, memoization is
+    /// not applied.
     fn try_match_impl<'a, C, F, B>(
         &mut self,
         start: C,
         mut fwd_input: F,
         mut bck_input: B,
+        origin: Option<usize>,
         mut f: impl FnMut(usize) -> Action,
     ) where
         C: CodeLoc,
@@ -167,6 +259,13 @@ impl<'r> PikeVM<'r> {
         let mut current_pos = 0;
         let mut curr_byte = fwd_input.next();
 
+        let backwards = start.backwards();
+        let origin = origin.filter(|_| self.memoize);
+        // True if some match was found, or if the VM was aborted because
+        // the scan limit was reached. In both cases the visited states can't
+        // be considered dead states.
+        let mut maybe_alive = false;
+
         // Make sure that the list of threads is empty when this function is
         // called.
         debug_assert!(self.threads.is_empty());
@@ -176,6 +275,7 @@ impl<'r> PikeVM<'r> {
             start,
             curr_byte,
             bck_input.next(),
+            self.word_boundary,
             &mut self.cache,
             &mut self.threads,
         );
@@ -183,9 +283,22 @@ impl<'r> PikeVM<'r> {
         while !self.threads.is_empty() {
             let next_byte = fwd_input.next();
 
-            for ip in self.threads.iter() {
+            for &(ip, count) in self.threads.iter() {
+                if let Some(origin) = origin {
+                    let pos = if backwards {
+                        origin - current_pos
+                    } else {
+                        origin + current_pos
+                    };
+                    let state = (ip, count, pos);
+                    if self.dead_states.contains(&state) {
+                        continue;
+                    }
+                    self.visited_states.push(state);
+                }
+
                 let (instr, size) = InstrParser::decode_instr(unsafe {
-                    self.code.get_unchecked(*ip..)
+                    self.code.get_unchecked(ip..)
                 });
 
                 let is_match = match instr {
@@ -205,19 +318,55 @@ impl<'r> PikeVM<'r> {
                     Instr::ClassRanges(class) => {
                         matches!(curr_byte, Some(b) if class.contains(*b))
                     }
-                    Instr::Match => match f(current_pos) {
-                        Action::Stop => break,
-                        Action::Continue => false,
-                    },
+                    Instr::Match => {
+                        maybe_alive = true;
+                        match f(current_pos) {
+                            Action::Stop => break,
+                            Action::Continue => false,
+                        }
+                    }
+                    Instr::RepeatAny { min, max, greedy, newline } => {
+                        if !matches!(curr_byte, Some(b) if newline || *b != b'\n')
+                        {
+                            continue;
+                        }
+                        let count = count + 1;
+                        // The thread keeps repeating while it hasn't matched
+                        // `max` bytes, and can continue at the next
+                        // instruction once it has matched at least `min`
+                        // bytes. Which of the two options is tried first
+                        // depends on the greediness of the repetition.
+                        let repeat = count < max;
+                        let exit = count >= min;
+                        if repeat && (greedy || !exit) {
+                            self.next_threads.insert_counted(ip, count);
+                        }
+                        if exit {
+                            epsilon_closure(
+                                self.code,
+                                C::from(ip + size),
+                                next_byte,
+                                curr_byte,
+                                self.word_boundary,
+                                &mut self.cache,
+                                &mut self.next_threads,
+                            );
+                        }
+                        if repeat && !greedy && exit {
+                            self.next_threads.insert_counted(ip, count);
+                        }
+                        false
+                    }
                     _ => unreachable!(),
                 };
 
                 if is_match {
                     epsilon_closure(
                         self.code,
-                        C::from(*ip + size),
+                        C::from(ip + size),
                         next_byte,
                         curr_byte,
+                        self.word_boundary,
                         &mut self.cache,
                         &mut self.next_threads,
                     );
@@ -231,10 +380,96 @@ impl<'r> PikeVM<'r> {
             self.next_threads.clear();
 
             if current_pos >= self.scan_limit.into() {
+                maybe_alive |= !self.threads.is_empty();
                 self.threads.clear();
                 break;
             }
         }
+
+        if maybe_alive || self.visited_states.len() > MAX_DEAD_STATES {
+            self.visited_states.clear();
+        } else {
+            if self.dead_states.len() + self.visited_states.len()
+                > MAX_DEAD_STATES
+            {
+                self.dead_states = FxHashSet::default();
+            }
+            self.dead_states.extend(self.visited_states.drain(..));
+        }
+    }
+}
+
+/// The set of threads in a [`PikeVM`].
+///
+/// Each thread is identified by the location of the VM instruction it is
+/// about to execute and, for threads at some [`Instr::RepeatAny`]
+/// instruction, by the number of bytes matched by that instruction so far.
+/// The set guarantees that there aren't two threads in the same state, and
+/// it keeps the threads in insertion order, which is also their priority
+/// order.
+pub(crate) struct ThreadSet {
+    /// Threads in insertion order, as (location, count) pairs. The count is
+    /// always zero for threads that are not at a [`Instr::RepeatAny`].
+    threads: Vec<(usize, u16)>,
+    /// Locations of threads that are not at a [`Instr::RepeatAny`].
+    locations: BitmapSet,
+    /// Threads that are at a [`Instr::RepeatAny`].
+    counted: FxHashSet<(usize, u16)>,
+}
+
+impl ThreadSet {
+    pub fn new() -> Self {
+        Self {
+            threads: Vec::new(),
+            locations: BitmapSet::new(),
+            counted: FxHashSet::default(),
+        }
+    }
+
+    /// Adds a thread at the instruction in the given location.
+    #[inline]
+    pub fn insert(&mut self, ip: usize) {
+        if self.locations.insert(ip) {
+            self.threads.push((ip, 0));
+        }
+    }
+
+    /// Adds a thread at the [`Instr::RepeatAny`] instruction in the given
+    /// location, after `count` bytes have been matched by the instruction.
+    #[inline]
+    pub fn insert_counted(&mut self, ip: usize, count: u16) {
+        if self.counted.insert((ip, count)) {
+            self.threads.push((ip, count));
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.threads.is_empty()
+    }
+
+    /// Removes all threads in the set.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.threads.clear();
+        self.locations.clear();
+        if !self.counted.is_empty() {
+            self.counted.clear();
+        }
+    }
+
+    /// Returns an iterator for the threads in the set, as (location, count)
+    /// pairs.
+    ///
+    /// Threads are returned in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = &(usize, u16)> {
+        self.threads.iter()
+    }
+
+    /// Returns the locations of the threads in the set.
+    #[cfg(test)]
+    pub fn into_vec(self) -> Vec<usize> {
+        self.threads.into_iter().map(|(ip, _)| ip).collect()
     }
 }
 
@@ -310,13 +545,14 @@ pub(crate) fn epsilon_closure<C: CodeLoc>(
     start: C,
     curr_byte: Option<&u8>,
     prev_byte: Option<&u8>,
+    word_boundary: WordBoundary,
     state: &mut EpsilonClosureState,
-    closure: &mut BitmapSet,
+    closure: &mut ThreadSet,
 ) {
     state.threads.push(start.location());
     state.dirty = true;
 
-    let is_word_char = |c: u8| c == b'_' || c.is_ascii_alphanumeric();
+    let is_word_char = |c: u8| word_boundary.is_word_char(c);
 
     while let Some(ip) = state.threads.pop() {
         let (instr, size) =
@@ -332,6 +568,10 @@ pub(crate) fn epsilon_closure<C: CodeLoc>(
             | Instr::Match => {
                 closure.insert(ip);
             }
+            Instr::RepeatAny { min, .. } => {
+                debug_assert!(min > 0);
+                closure.insert_counted(ip, 0);
+            }
             Instr::SplitA(id, offset) => {
                 if !state.executed(id) {
                     state
@@ -3,12 +3,11 @@ use pretty_assertions::assert_eq;
 
 use crate::compiler::Atom;
 use crate::re;
-use crate::re::bitmapset::BitmapSet;
-use crate::re::{BckCodeLoc, FwdCodeLoc};
+use crate::re::{Action, BckCodeLoc, FwdCodeLoc, WordBoundary};
 use crate::types::Regexp;
 
 use super::compiler::{CodeLoc, Compiler, RegexpAtom};
-use super::pikevm::{epsilon_closure, EpsilonClosureState};
+use super::pikevm::{epsilon_closure, EpsilonClosureState, PikeVM, ThreadSet};
 
 macro_rules! assert_re_code {
     ($re:expr, $fwd:expr, $bck:expr, $atoms:expr, $fwd_closure:expr, $bck_closure:expr) => {{
@@ -24,7 +23,7 @@ macro_rules! assert_re_code {
         assert_eq!($bck, bck_code.to_string());
         assert_eq!($atoms, atoms);
 
-        let mut fwd_closure = BitmapSet::new();
+        let mut fwd_closure = ThreadSet::new();
         let mut cache = EpsilonClosureState::new();
 
         epsilon_closure(
@@ -32,18 +31,20 @@ macro_rules! assert_re_code {
             FwdCodeLoc::try_from(0_usize).unwrap(),
             None,
             None,
+            WordBoundary::default(),
             &mut cache,
             &mut fwd_closure,
         );
 
         assert_eq!($fwd_closure, fwd_closure.into_vec());
 
-        let mut bck_closure = BitmapSet::new();
+        let mut bck_closure = ThreadSet::new();
         epsilon_closure(
             bck_code.as_ref(),
             BckCodeLoc::try_from(0_usize).unwrap(),
             None,
             None,
+            WordBoundary::default(),
             &mut cache,
             &mut bck_closure,
         );
@@ -853,67 +854,43 @@ fn re_code_16() {
 
 #[test]
 fn re_code_17() {
+    // `(|abc){3,}` can match the empty string, it is rewritten as `(abc)*`
+    // for removing the epsilon cycle.
     assert_re_code!(
         "(?s)(|abc){3,}",
         // Forward code
         r#"
-00000: SPLIT_N(0) 0000d 00013
-0000d: JUMP 00016
-00013: LIT 0x61
-00014: LIT 0x62
-00015: LIT 0x63
-00016: SPLIT_N(1) 00023 00029
-00023: JUMP 0002c
-00029: LIT 0x61
-0002a: LIT 0x62
-0002b: LIT 0x63
-0002c: SPLIT_B(2) 00016
-00034: SPLIT_N(3) 00041 00047
-00041: JUMP 0004a
-00047: LIT 0x61
-00048: LIT 0x62
-00049: LIT 0x63
-0004a: MATCH
+00000: SPLIT_A(0) 00011
+00008: LIT 0x61
+00009: LIT 0x62
+0000a: LIT 0x63
+0000b: JUMP 00000
+00011: MATCH
 "#,
         // Backward code
         r#"
-00000: SPLIT_N(0) 0000d 00013
-0000d: JUMP 00016
-00013: LIT 0x63
-00014: LIT 0x62
-00015: LIT 0x61
-00016: SPLIT_N(1) 00023 00029
-00023: JUMP 0002c
-00029: LIT 0x63
-0002a: LIT 0x62
-0002b: LIT 0x61
-0002c: SPLIT_B(2) 00016
-00034: SPLIT_N(3) 00041 00047
-00041: JUMP 0004a
-00047: LIT 0x63
-00048: LIT 0x62
-00049: LIT 0x61
-0004a: MATCH
+00000: SPLIT_A(0) 00011
+00008: LIT 0x63
+00009: LIT 0x62
+0000a: LIT 0x61
+0000b: JUMP 00000
+00011: MATCH
 "#,
         // Atoms
         vec![
-            RegexpAtom {
-                atom: Atom::inexact(vec![]),
-                code_loc: CodeLoc { fwd: 0, bck_seq_id: 0, bck: 0x4A },
-            },
             RegexpAtom {
                 atom: Atom::inexact(vec![0x61, 0x62, 0x63]),
-                code_loc: CodeLoc { fwd: 0, bck_seq_id: 0, bck: 0x4A },
+                code_loc: CodeLoc { fwd: 0x00, bck_seq_id: 0, bck: 0x11 }
             },
             RegexpAtom {
-                atom: Atom::inexact(vec![0x61, 0x62, 0x63, 0x61]),
-                code_loc: CodeLoc { fwd: 0, bck_seq_id: 0, bck: 0x4A },
-            },
+                atom: Atom::exact(vec![]),
+                code_loc: CodeLoc { fwd: 0x00, bck_seq_id: 0, bck: 0x11 }
+            }
         ],
         // Epsilon closure starting at forward code 0.
-        vec![0x4a, 0x47, 0x29, 0x13],
+        vec![0x08, 0x11],
         // Epsilon closure starting at backward code 0.
-        vec![0x4a, 0x47, 0x29, 0x13]
+        vec![0x08, 0x11]
     );
 }
 
@@ -1355,3 +1332,225 @@ fn re_atoms() {
         400
     );
 }
+
+#[test]
+fn re_code_23() {
+    // The epsilon cycle in `(a*)*` is removed by rewriting the regexp as
+    // `(a+)*`.
+    assert_re_code!(
+        "(?s)(a*)*",
+        // Forward code
+        r#"
+00000: SPLIT_A(0) 00017
+00008: LIT 0x61
+00009: SPLIT_B(1) 00008
+00011: JUMP 00000
+00017: MATCH
+"#,
+        // Backward code
+        r#"
+00000: SPLIT_A(0) 00017
+00008: LIT 0x61
+00009: SPLIT_B(1) 00008
+00011: JUMP 00000
+00017: MATCH
+"#,
+        // Atoms
+        vec![
+            RegexpAtom {
+                atom: Atom::inexact(vec![0x61]),
+                code_loc: CodeLoc { fwd: 0x00, bck_seq_id: 0, bck: 0x17 }
+            },
+            RegexpAtom {
+                atom: Atom::exact(vec![]),
+                code_loc: CodeLoc { fwd: 0x00, bck_seq_id: 0, bck: 0x17 }
+            }
+        ],
+        // Epsilon closure starting at forward code 0.
+        vec![0x08, 0x17],
+        // Epsilon closure starting at backward code 0.
+        vec![0x08, 0x17]
+    );
+}
+
+#[test]
+fn re_code_24() {
+    // The epsilon cycle in `(a|b?)+` is removed by rewriting the regexp as
+    // `(a|b)*`.
+    assert_re_code!(
+        "(?s)(a|b?)+c",
+        // Forward code
+        r#"
+00000: SPLIT_A(0) 00013
+00008: CLASS_RANGES [0x61-0x62] 
+0000d: JUMP 00000
+00013: LIT 0x63
+00014: MATCH
+"#,
+        // Backward code
+        r#"
+00000: LIT 0x63
+00001: SPLIT_A(0) 00014
+00009: CLASS_RANGES [0x61-0x62] 
+0000e: JUMP 00001
+00014: MATCH
+"#,
+        // Atoms
+        vec![RegexpAtom {
+            atom: Atom::inexact(vec![0x63]),
+            code_loc: CodeLoc { fwd: 0x13, bck_seq_id: 0, bck: 0x01 }
+        }],
+        // Epsilon closure starting at forward code 0.
+        vec![0x08, 0x13],
+        // Epsilon closure starting at backward code 0.
+        vec![0x00]
+    );
+}
+
+#[test]
+fn re_code_25() {
+    // Large bounded repetitions of any byte are not unrolled.
+    assert_re_code!(
+        "(?s)abc.{0,1000}def",
+        // Forward code
+        r#"
+00000: LIT 0x61
+00001: LIT 0x62
+00002: LIT 0x63
+00003: SPLIT_A(0) 00012
+0000b: REPEAT_ANY 1 1000
+00012: LIT 0x64
+00013: LIT 0x65
+00014: LIT 0x66
+00015: MATCH
+"#,
+        // Backward code
+        r#"
+00000: LIT 0x66
+00001: LIT 0x65
+00002: LIT 0x64
+00003: SPLIT_A(0) 00012
+0000b: REPEAT_ANY 1 1000
+00012: LIT 0x63
+00013: LIT 0x62
+00014: LIT 0x61
+00015: MATCH
+"#,
+        // Atoms
+        vec![RegexpAtom {
+            atom: Atom::inexact(b"abc"),
+            code_loc: CodeLoc { fwd: 0x00, bck_seq_id: 0, bck: 0x15 }
+        }],
+        // Epsilon closure starting at forward code 0.
+        vec![0x00],
+        // Epsilon closure starting at backward code 0.
+        vec![0x00]
+    );
+}
+
+#[test]
+fn re_code_26() {
+    assert_re_code!(
+        "abc[^\\n]{0,30}?def",
+        // Forward code
+        r#"
+00000: LIT 0x61
+00001: LIT 0x62
+00002: LIT 0x63
+00003: SPLIT_B(0) 00012
+0000b: REPEAT_ANY_NON_GREEDY_NO_NEWLINE 1 30
+00012: LIT 0x64
+00013: LIT 0x65
+00014: LIT 0x66
+00015: MATCH
+"#,
+        // Backward code
+        r#"
+00000: LIT 0x66
+00001: LIT 0x65
+00002: LIT 0x64
+00003: SPLIT_B(0) 00012
+0000b: REPEAT_ANY_NON_GREEDY_NO_NEWLINE 1 30
+00012: LIT 0x63
+00013: LIT 0x62
+00014: LIT 0x61
+00015: MATCH
+"#,
+        // Atoms
+        vec![RegexpAtom {
+            atom: Atom::inexact(b"abc"),
+            code_loc: CodeLoc { fwd: 0x00, bck_seq_id: 0, bck: 0x15 }
+        }],
+        // Epsilon closure starting at forward code 0.
+        vec![0x00],
+        // Epsilon closure starting at backward code 0.
+        vec![0x00]
+    );
+}
+
+/// Returns the matches of `re` at every offset in `data`.
+fn pikevm_matches(
+    re: &str,
+    data: &[u8],
+    scan_limit: u16,
+    memoize: bool,
+) -> Vec<(usize, usize)> {
+    let parser = re::parser::Parser::new();
+
+    let (fwd_code, _, _) = Compiler::new()
+        .compile_internal(
+            &parser.parse(&Regexp::new(format!("/{}/s", re))).unwrap(),
+        )
+        .unwrap();
+
+    let mut pike_vm =
+        PikeVM::new(fwd_code.as_ref()).scan_limit(scan_limit).memoize(memoize);
+
+    let mut matches = Vec::new();
+
+    for offset in 0..data.len() {
+        pike_vm.try_match(
+            FwdCodeLoc::try_from(0_usize).unwrap(),
+            &data[offset..],
+            &data[..offset],
+            false,
+            |len| {
+                matches.push((offset, len));
+                Action::Continue
+            },
+        );
+    }
+
+    if memoize {
+        assert!(pike_vm.memory_usage() > 0);
+        pike_vm.clear_dead_states();
+        assert_eq!(pike_vm.memory_usage(), 0);
+    }
+
+    matches
+}
+
+#[test]
+fn pikevm_memoize() {
+    // The scan limit is reached from the offsets that are far from the
+    // `d`, the states visited from there must not be memoized as dead.
+    let data = [b"a".repeat(40), b"bcd".to_vec(), b"a".repeat(40)].concat();
+
+    for (re, scan_limit) in [
+        ("a(b?c?)*d", 16),
+        ("a(b?c?)*d", 4096),
+        ("a[^x]{0,100}d", 16),
+        ("a.*?b", 8),
+        ("(a|b)+c", 20),
+    ] {
+        let matches = pikevm_matches(re, &data, scan_limit, false);
+        assert!(!matches.is_empty(), "no matches for /{}/", re);
+        assert_eq!(
+            matches,
+            pikevm_matches(re, &data, scan_limit, true),
+            "/{}/ with scan limit {}",
+            re,
+            scan_limit
+        );
+    }
+}
@@ -0,0 +1,245 @@
+/*! Aggregation of results from multiple related scans.
+
+Scanning a sample frequently involves multiple scans: the original file,
+the layers extracted from it, the memory regions of the process it created,
+etc. [`AggregatedResults`] merges the results of all these scans into a
+single report, where each matching rule appears only once, together with
+the list of objects where it matched.
+*/
+
+use std::ops::Range;
+
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::scanner::ScanResults;
+
+/// Results of multiple scans merged into a single report.
+///
+/// Each scan is identified by an origin, which is an arbitrary string that
+/// describes the scanned object (e.g: `sample.exe`, `sample.exe/overlay`,
+/// `pid:1234/0x7ff00000`). Encoding the lineage of derived objects in their
+/// origins is up to the caller, the aggregated results simply record which
+/// origins were scanned, and in which of them each rule matched.
+///
+/// Rules are identified by their namespace and identifier, so the results
+/// being merged can come from different [`crate::Rules`], as long as the
+/// rules with the same name are the same rule.
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::Scanner;
+/// use yara_x::aggregate::AggregatedResults;
+///
+/// let rules = yara_x::compile(
+///     r#"rule test { strings: $a = "foo" condition: $a }"#,
+/// )
+/// .unwrap();
+///
+/// let mut scanner = Scanner::new(&rules);
+/// let mut aggregated = AggregatedResults::new();
+///
+/// aggregated.add("sample", &scanner.scan(b"foo").unwrap());
+/// aggregated.add("sample/layer1", &scanner.scan(b"bar").unwrap());
+/// aggregated.add("sample/layer2", &scanner.scan(b"foofoo").unwrap());
+///
+/// let rule = aggregated.rules().next().unwrap();
+///
+/// assert_eq!(rule.identifier(), "test");
+/// assert_eq!(
+///     rule.origins().collect::<Vec<_>>(),
+///     ["sample", "sample/layer2"]
+/// );
+/// ```
+#[derive(Debug, Default, Serialize)]
+pub struct AggregatedResults {
+    origins: Vec<String>,
+    rules: Vec<AggregatedRule>,
+    /// Maps (namespace, identifier) pairs to indexes in `rules`.
+    #[serde(skip)]
+    rules_index: FxHashMap<(String, String), usize>,
+}
+
+impl AggregatedResults {
+    /// Creates an empty [`AggregatedResults`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the results of scanning the object identified by `origin`.
+    ///
+    /// Adding results for an origin that was already added merges the new
+    /// results with the existing ones.
+    pub fn add<'a>(&mut self, origin: &str, results: &'a ScanResults<'a, '_>) {
+        if !self.origins.iter().any(|o| o == origin) {
+            self.origins.push(origin.to_string());
+        }
+
+        for rule in results.matching_rules() {
+            let key =
+                (rule.namespace().to_string(), rule.identifier().to_string());
+
+            let index = *self.rules_index.entry(key).or_insert_with(|| {
+                self.rules.push(AggregatedRule {
+                    namespace: rule.namespace().to_string(),
+                    identifier: rule.identifier().to_string(),
+                    tags: rule
+                        .tags()
+                        .map(|tag| tag.identifier().to_string())
+                        .collect(),
+                    hits: Vec::new(),
+                });
+                self.rules.len() - 1
+            });
+
+            let hits = &mut self.rules[index].hits;
+
+            let hit = match hits.iter().position(|hit| hit.origin == origin) {
+                Some(pos) => &mut hits[pos],
+                None => {
+                    hits.push(RuleHit {
+                        origin: origin.to_string(),
+                        patterns: Vec::new(),
+                    });
+                    hits.last_mut().unwrap()
+                }
+            };
+
+            for pattern in rule.patterns() {
+                let matches = pattern.matches();
+                if matches.len() == 0 {
+                    continue;
+                }
+
+                let identifier = pattern.identifier();
+
+                let pos = match hit
+                    .patterns
+                    .iter()
+                    .position(|p| p.identifier == identifier)
+                {
+                    Some(pos) => pos,
+                    None => {
+                        hit.patterns.push(PatternHit {
+                            identifier: identifier.to_string(),
+                            matches: Vec::new(),
+                        });
+                        hit.patterns.len() - 1
+                    }
+                };
+
+                let pattern_hit = &mut hit.patterns[pos];
+
+                // The same range may be found when adding results for an
+                // origin that was already added. Sorting and removing
+                // duplicates once is cheaper than checking each match
+                // individually, as patterns can have lots of matches.
+                pattern_hit.matches.extend(matches.map(|m| m.range()));
+                pattern_hit.matches.sort_unstable_by_key(|r| (r.start, r.end));
+                pattern_hit.matches.dedup();
+            }
+        }
+    }
+
+    /// Returns the origins of all the results added so far, including those
+    /// where no rule matched, in the order in which they were added.
+    pub fn origins(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.origins.iter().map(|origin| origin.as_str())
+    }
+
+    /// Returns the rules that matched in any of the scans, in the order in
+    /// which they were found for the first time.
+    pub fn rules(&self) -> impl ExactSizeIterator<Item = &AggregatedRule> {
+        self.rules.iter()
+    }
+
+    /// Returns the rule with the given namespace and identifier, if it
+    /// matched in any of the scans.
+    pub fn get(
+        &self,
+        namespace: &str,
+        identifier: &str,
+    ) -> Option<&AggregatedRule> {
+        self.rules_index
+            .get(&(namespace.to_string(), identifier.to_string()))
+            .map(|index| &self.rules[*index])
+    }
+}
+
+/// A rule that matched in one or more of the scans merged in
+/// [`AggregatedResults`].
+#[derive(Debug, Serialize)]
+pub struct AggregatedRule {
+    namespace: String,
+    identifier: String,
+    tags: Vec<String>,
+    hits: Vec<RuleHit>,
+}
+
+impl AggregatedRule {
+    /// Returns the rule's namespace.
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_str()
+    }
+
+    /// Returns the rule's identifier.
+    pub fn identifier(&self) -> &str {
+        self.identifier.as_str()
+    }
+
+    /// Returns the rule's tags.
+    pub fn tags(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.tags.iter().map(|tag| tag.as_str())
+    }
+
+    /// Returns the origins where the rule matched.
+    pub fn origins(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.hits.iter().map(|hit| hit.origin.as_str())
+    }
+
+    /// Returns the details about the rule matching in each origin.
+    pub fn hits(&self) -> impl ExactSizeIterator<Item = &RuleHit> {
+        self.hits.iter()
+    }
+}
+
+/// Describes a rule matching in some specific origin.
+#[derive(Debug, Serialize)]
+pub struct RuleHit {
+    origin: String,
+    patterns: Vec<PatternHit>,
+}
+
+impl RuleHit {
+    /// Returns the origin where the rule matched.
+    pub fn origin(&self) -> &str {
+        self.origin.as_str()
+    }
+
+    /// Returns the patterns that matched in this origin. Patterns without
+    /// matches are not included.
+    pub fn patterns(&self) -> impl ExactSizeIterator<Item = &PatternHit> {
+        self.patterns.iter()
+    }
+}
+
+/// Describes the matches of a pattern in some specific origin.
+#[derive(Debug, Serialize)]
+pub struct PatternHit {
+    identifier: String,
+    matches: Vec<Range<usize>>,
+}
+
+impl PatternHit {
+    /// Returns the pattern's identifier (e.g: `$a`).
+    pub fn identifier(&self) -> &str {
+        self.identifier.as_str()
+    }
+
+    /// Returns the ranges where the pattern matched, relative to the
+    /// scanned object, sorted by offset.
+    pub fn matches(&self) -> impl ExactSizeIterator<Item = Range<usize>> + '_ {
+        self.matches.iter().cloned()
+    }
+}
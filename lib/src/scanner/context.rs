@@ -1,16 +1,15 @@
 use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::mem;
 use std::ops::{Range, RangeInclusive};
 use std::ptr::NonNull;
 use std::rc::Rc;
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use std::{cmp, fs};
 
 #[cfg(feature = "logging")]
 use log::*;
-#[cfg(feature = "rules-profiling")]
-use std::time::Duration;
-#[cfg(any(feature = "logging", feature = "rules-profiling"))]
-use std::time::Instant;
 
 use base64::Engine;
 use bitvec::order::Lsb0;
@@ -26,15 +25,20 @@ use crate::compiler::{
     NamespaceId, PatternId, RegexpId, RuleId, Rules, SubPattern,
     SubPatternAtom, SubPatternFlagSet, SubPatternFlags, SubPatternId,
 };
+use crate::models::MatchVariant;
 use crate::re::fast::FastVM;
 use crate::re::thompson::PikeVM;
 use crate::re::Action;
 use crate::scanner::matches::{Match, PatternMatches, UnconfirmedMatch};
-use crate::scanner::ScanError;
 use crate::scanner::HEARTBEAT_COUNTER;
+use crate::scanner::{ModuleStats, ScanError, ScanPhase, ScanProgress};
 use crate::types::{Array, Map, Struct};
 use crate::wasm::MATCHING_RULES_BITMAP_BASE;
 
+/// When a progress callback is set, the progress of the pattern search is
+/// reported every time this number of bytes is processed.
+const PROGRESS_CHUNK_SIZE: usize = 4 << 20;
+
 /// Structure that holds information about the current scan.
 pub(crate) struct ScanContext<'r> {
     /// Pointer to the WASM store.
@@ -48,6 +52,14 @@ pub(crate) struct ScanContext<'r> {
     pub scanned_data: *const u8,
     /// Length of data being scanned.
     pub scanned_data_len: usize,
+    /// Metadata of the scanned file. This is `None` when the scanned data
+    /// doesn't come from a file, like in [`crate::Scanner::scan`].
+    pub file_metadata: Option<FileMetadata>,
+    /// Values taken by the traced sub-expressions of rule conditions during
+    /// the scan. The N-th item in this vector corresponds to the N-th item
+    /// in [`Rules::condition_traces`]. This is empty unless the rules were
+    /// compiled with condition tracing enabled.
+    pub condition_traces: Vec<TraceCounters>,
     /// Vector containing the IDs of the non-private rules that matched,
     /// including both global and non-global ones. The rules are added first
     /// to the `matching_rules` map, and then moved to this vector once the
@@ -95,12 +107,30 @@ pub(crate) struct ScanContext<'r> {
     /// that matches stored in `Vec<UnconfirmedMatch>` are sorted by matching
     /// offset.
     pub unconfirmed_matches: FxHashMap<SubPatternId, Vec<UnconfirmedMatch>>,
-    /// Set that contains the PatternId for those patterns that have reached
+    /// Map that contains the PatternId for those patterns that have reached
     /// the maximum number of matches indicated by `max_matches_per_pattern`.
-    pub limit_reached: FxHashSet<PatternId>,
+    /// Values are the number of matches that were found after reaching the
+    /// limit, and therefore were not recorded.
+    pub suppressed_matches: FxHashMap<PatternId, usize>,
+    /// Identifier of the current scan, which is unique across all scanners.
+    /// Modules use it for determining whether the values they cached during
+    /// a scan are still valid.
+    pub scan_id: u64,
     /// When [`HEARTBEAT_COUNTER`] is larger than this value, the scan is
     /// aborted due to a timeout.
     pub deadline: u64,
+    /// Maximum number of bytes that can be allocated on behalf of the
+    /// current scan, as set with [`crate::ScanOptions::max_memory`].
+    pub max_memory: Option<usize>,
+    /// Number of bytes allocated on behalf of the current scan, excluding
+    /// pattern matches, which are accounted by [`PatternMatches`] itself.
+    /// This is updated every time that some module output, unconfirmed
+    /// match or string is created, so that [`ScanContext::check_memory_limit`]
+    /// doesn't need to traverse the allocated objects. The size of module
+    /// outputs is computed only when `max_memory` is set.
+    pub allocated_memory: usize,
+    /// Error that caused the pattern search phase to be aborted, if any.
+    pub search_error: Option<ScanError>,
     /// Hash map that serves as a cache for regexps used in expressions like
     /// `some_var matches /foobar/`. Compiling a regexp is a expensive
     /// operation. Instead of compiling the regexp each time the expression
@@ -109,6 +139,35 @@ pub(crate) struct ScanContext<'r> {
     pub regexp_cache: RefCell<FxHashMap<RegexpId, Regex>>,
     /// Callback invoked every time a YARA rule calls `console.log`.
     pub console_log: Option<Box<dyn FnMut(String) + 'r>>,
+    /// Statistics about the invocations of each module, accumulated over
+    /// all the scans done by the scanner. Keys are module names.
+    pub module_stats: IndexMap<&'r str, ModuleStats>,
+    /// Callback invoked for reporting the progress of the scan.
+    pub progress_callback: Option<Box<dyn FnMut(ScanProgress) + 'r>>,
+    /// Rules disabled with [`crate::Scanner::disable_rule`].
+    pub disabled_rules: FxHashSet<RuleId>,
+    /// Maximum time that the evaluation of a rule's condition can take, as
+    /// set with [`crate::Scanner::set_rule_timeout`].
+    pub rule_timeout: Option<Duration>,
+    /// Rule whose condition is being evaluated, and the time in which the
+    /// evaluation started. This is updated by [`ScanContext::check_rule_timeout`].
+    pub rule_eval_start: Option<(RuleId, Instant)>,
+    /// Rules that were aborted because their evaluation took longer than
+    /// `rule_timeout`.
+    pub timed_out_rules: Vec<RuleId>,
+    /// Number of atoms found during the current scan.
+    pub atoms_fired: usize,
+    /// Number of regexp executions done for verifying the atoms found
+    /// during the current scan.
+    pub regexp_executions: usize,
+    /// Number of rules whose conditions were evaluated during the current
+    /// scan.
+    pub rules_evaluated: usize,
+    /// Time spent by each module parsing the data in the current scan.
+    pub module_parse_times: Vec<(&'r str, Duration)>,
+    /// Patterns that are used only by disabled rules. These patterns are
+    /// not searched for during the scan.
+    pub disabled_patterns: FxHashSet<PatternId>,
     /// Hash map that tracks the time spend on each pattern. Keys are pattern
     /// PatternIds and values are the cumulative time spent on verifying each
     /// pattern.
@@ -123,8 +182,10 @@ impl<'r> ScanContext<'r> {
 
         for r in self.compiled_rules.rules() {
             let mut rule_time = Duration::default();
-            for (_, pattern_id) in r.patterns.iter() {
-                if let Some(d) = self.time_spent_in_pattern.get(pattern_id) {
+            for pattern in r.patterns.iter() {
+                if let Some(d) =
+                    self.time_spent_in_pattern.get(&pattern.pattern_id)
+                {
                     rule_time += *d;
                 }
             }
@@ -147,6 +208,46 @@ impl<'r> ScanContext<'r> {
     }
 }
 
+/// Metadata of a file scanned with [`crate::Scanner::scan_file`].
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct FileMetadata {
+    /// Last modification time, as a Unix timestamp.
+    pub mtime: Option<i64>,
+    /// In Unix systems, the last time the file's metadata changed. In
+    /// Windows, the creation time. Expressed as a Unix timestamp.
+    pub ctime: Option<i64>,
+}
+
+impl From<&fs::Metadata> for FileMetadata {
+    fn from(metadata: &fs::Metadata) -> Self {
+        let timestamp = |t: std::time::SystemTime| {
+            t.duration_since(std::time::UNIX_EPOCH)
+                .ok()
+                .and_then(|d| d.as_secs().try_into().ok())
+        };
+
+        #[cfg(unix)]
+        let ctime = {
+            use std::os::unix::fs::MetadataExt;
+            Some(metadata.ctime())
+        };
+
+        #[cfg(not(unix))]
+        let ctime = metadata.created().ok().and_then(timestamp);
+
+        Self { mtime: metadata.modified().ok().and_then(timestamp), ctime }
+    }
+}
+
+/// Number of times that a traced sub-expression of a rule condition was
+/// evaluated to each of the possible values during a scan.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TraceCounters {
+    pub times_true: usize,
+    pub times_false: usize,
+    pub times_undefined: usize,
+}
+
 impl ScanContext<'_> {
     /// Returns a slice with the data being scanned.
     pub(crate) fn scanned_data<'a>(&self) -> &'a [u8] {
@@ -217,6 +318,21 @@ impl ScanContext<'_> {
         }
     }
 
+    /// Invokes the progress callback, if any.
+    pub(crate) fn report_progress(
+        &mut self,
+        phase: ScanPhase,
+        bytes_processed: usize,
+    ) {
+        if let Some(progress_callback) = &mut self.progress_callback {
+            progress_callback(ScanProgress {
+                phase,
+                bytes_processed,
+                total_bytes: self.scanned_data_len,
+            })
+        }
+    }
+
     pub(crate) fn store_struct(
         &mut self,
         s: Rc<Struct>,
@@ -243,20 +359,85 @@ impl ScanContext<'_> {
         s: Rc<BString>,
     ) -> RuntimeObjectHandle {
         let obj_ref = RuntimeObjectHandle(Rc::<BString>::as_ptr(&s) as i64);
+        self.allocated_memory += s.len();
         self.runtime_objects.insert_full(obj_ref, RuntimeObject::String(s));
         obj_ref
     }
 
+    /// Called during the scan process every time that a traced sub-expression
+    /// of a rule condition is evaluated. `value` is `None` if the expression
+    /// is undefined.
+    pub(crate) fn track_trace_value(
+        &mut self,
+        trace_id: usize,
+        value: Option<bool>,
+    ) {
+        let counters = &mut self.condition_traces[trace_id];
+        match value {
+            Some(true) => counters.times_true += 1,
+            Some(false) => counters.times_false += 1,
+            None => counters.times_undefined += 1,
+        }
+    }
+
+    /// Enables or disables a rule.
+    ///
+    /// This also updates the set of patterns that are not searched for
+    /// during the scan because they are used only by disabled rules.
+    pub(crate) fn set_rule_enabled(&mut self, rule_id: RuleId, enabled: bool) {
+        let changed = if enabled {
+            self.disabled_rules.remove(&rule_id)
+        } else {
+            self.disabled_rules.insert(rule_id)
+        };
+
+        if !changed {
+            return;
+        }
+
+        self.disabled_patterns.clear();
+
+        if self.disabled_rules.is_empty() {
+            return;
+        }
+
+        // A pattern can be shared by multiple rules, it's disabled only if
+        // all the rules that use it are disabled.
+        let mut enabled_patterns = FxHashSet::default();
+
+        for (rule_id, rule) in self.compiled_rules.rules().iter().enumerate() {
+            let patterns = rule.patterns.iter().map(|p| p.pattern_id);
+            if self.disabled_rules.contains(&rule_id.into()) {
+                self.disabled_patterns.extend(patterns);
+            } else {
+                enabled_patterns.extend(patterns);
+            }
+        }
+
+        self.disabled_patterns
+            .retain(|pattern_id| !enabled_patterns.contains(pattern_id));
+    }
+
     /// Called during the scan process when a global rule didn't match.
     ///
     /// When this happens any other rule in the same namespace that matched
-    /// previously is reset to a non-matching state.
-    pub(crate) fn track_global_rule_no_match(&mut self, rule_id: RuleId) {
+    /// previously is reset to a non-matching state, and the function returns
+    /// `true`, indicating that the remaining rules in the namespace must not
+    /// be evaluated. Disabled global rules don't affect the rest of the
+    /// namespace, in that case the function returns `false`.
+    pub(crate) fn track_global_rule_no_match(
+        &mut self,
+        rule_id: RuleId,
+    ) -> bool {
         let rule = self.compiled_rules.get(rule_id);
 
         // This function must be called only for global rules.
         debug_assert!(rule.is_global);
 
+        if self.disabled_rules.contains(&rule_id) {
+            return false;
+        }
+
         // All the rules that matched previously, and are in the same
         // namespace as the non-matching rule, must be removed from the
         // `matching_rules` map. Also, their corresponding bits in
@@ -276,11 +457,46 @@ impl ScanContext<'_> {
                 bits.set(rule_id.into(), false);
             }
         }
+
+        true
+    }
+
+    /// Called periodically while evaluating the loops in the condition of
+    /// the rule identified by `rule_id`.
+    ///
+    /// Returns `true` if the evaluation of the rule has been running for
+    /// longer than `rule_timeout`. In that case the rule is added to
+    /// `timed_out_rules`. The first call for a rule marks the start of its
+    /// evaluation.
+    pub(crate) fn check_rule_timeout(&mut self, rule_id: RuleId) -> bool {
+        let timeout = match self.rule_timeout {
+            Some(timeout) => timeout,
+            None => return false,
+        };
+
+        match self.rule_eval_start {
+            Some((id, start)) if id == rule_id => {
+                if start.elapsed() > timeout {
+                    self.timed_out_rules.push(rule_id);
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => {
+                self.rule_eval_start = Some((rule_id, Instant::now()));
+                false
+            }
+        }
     }
 
     /// Called during the scan process when a rule has matched for tracking
     /// the matching rules.
     pub(crate) fn track_rule_match(&mut self, rule_id: RuleId) {
+        if self.disabled_rules.contains(&rule_id) {
+            return;
+        }
+
         let rule = self.compiled_rules.get(rule_id);
 
         #[cfg(feature = "logging")]
@@ -338,7 +554,7 @@ impl ScanContext<'_> {
         bits.set(pattern_id.into(), true);
 
         if !self.pattern_matches.add(pattern_id, match_, replace_if_longer) {
-            self.limit_reached.insert(pattern_id);
+            *self.suppressed_matches.entry(pattern_id).or_default() += 1;
         }
     }
 
@@ -354,16 +570,24 @@ impl ScanContext<'_> {
     /// without looking for any of the patterns. If it must be called, it will be
     /// called only once.
     pub(crate) fn search_for_patterns(&mut self) -> Result<(), ScanError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("pattern_search").entered();
+
+        // The memory limit may have been exceeded by module outputs.
+        self.check_memory_limit()?;
+
         let scanned_data = self.scanned_data();
 
         // Verify the anchored pattern first. These are patterns that can match
         // at a single known offset within the data.
         self.verify_anchored_patterns();
 
-        let ac = self.compiled_rules.ac_automaton();
+        let atom_searcher = self.compiled_rules.atom_searcher();
 
         let mut vm = VM {
-            pike_vm: PikeVM::new(self.compiled_rules.re_code()),
+            pike_vm: PikeVM::new(self.compiled_rules.re_code())
+                .word_boundary(self.compiled_rules.word_boundary())
+                .memoize(true),
             fast_vm: FastVM::new(self.compiled_rules.re_code()),
         };
 
@@ -375,221 +599,293 @@ impl ScanContext<'_> {
         #[cfg(feature = "logging")]
         let mut atom_matches = 0_usize;
 
-        for ac_match in ac.find_overlapping_iter(scanned_data) {
-            #[cfg(feature = "logging")]
-            {
-                atom_matches += 1;
-            }
+        // When a progress callback is set, the data is searched in chunks,
+        // and the progress is reported after each chunk. Each chunk is
+        // extended backwards by the length of the longest atom, so that atoms
+        // that cross the boundary between chunks are found. Matches that end
+        // before the start of the current chunk were already found while
+        // searching the previous chunk, and are ignored.
+        let (chunk_size, overlap) = if self.progress_callback.is_some() {
+            let longest_atom =
+                atoms.iter().map(|atom| atom.len()).max().unwrap_or(0);
+            (PROGRESS_CHUNK_SIZE, longest_atom)
+        } else {
+            (usize::MAX, 0)
+        };
 
-            if HEARTBEAT_COUNTER.load(Ordering::Relaxed) >= self.deadline {
-                #[cfg(feature = "logging")]
-                info!(
-                    "Scan timeout after: {:?}",
-                    Instant::elapsed(&scan_start)
-                );
-                return Err(ScanError::Timeout);
-            }
+        let mut chunk_start = 0_usize;
+
+        loop {
+            let chunk_end = cmp::min(
+                chunk_start.saturating_add(chunk_size),
+                scanned_data.len(),
+            );
 
-            let atom =
-                unsafe { atoms.get_unchecked(ac_match.pattern().as_usize()) };
+            let span = chunk_start.saturating_sub(overlap)..chunk_end;
 
-            // Subtract the backtrack value from the offset where the atom
-            // matched. If the result is negative the atom can't be inside
-            // the scanned data and therefore is not a possible match.
-            let atom_pos = if let Some(atom_pos) =
-                ac_match.start().checked_sub(atom.backtrack())
+            for atom_match in
+                atom_searcher.find_overlapping(scanned_data, span)
             {
-                atom_pos
-            } else {
-                continue;
-            };
+                if chunk_start > 0 && atom_match.end() <= chunk_start {
+                    continue;
+                }
 
-            // Each atom belongs to a sub-pattern.
-            let sub_pattern_id = atom.sub_pattern_id();
+                self.atoms_fired += 1;
 
-            // Each sub-pattern belongs to a pattern.
-            let (pattern_id, sub_pattern) =
-                &self.compiled_rules.get_sub_pattern(sub_pattern_id);
+                #[cfg(feature = "logging")]
+                {
+                    atom_matches += 1;
+                }
 
-            // Check if the potentially matching pattern has reached the
-            // maximum number of allowed matches. In that case continue without
-            // verifying the match. `get_unchecked` is used for performance
-            // reasons, the number of bits in the bit vector is guaranteed to
-            // be the number of patterns.
-            if self.limit_reached.contains(pattern_id) {
-                continue;
-            }
+                if HEARTBEAT_COUNTER.load(Ordering::Relaxed) >= self.deadline {
+                    #[cfg(feature = "logging")]
+                    info!(
+                        "Scan timeout after: {:?}",
+                        Instant::elapsed(&scan_start)
+                    );
+                    return Err(ScanError::Timeout);
+                }
 
-            #[cfg(feature = "rules-profiling")]
-            let verification_start = Instant::now();
-
-            // If the atom is exact no further verification is needed, except
-            // for making sure that the fullword requirements are met. An exact
-            // atom is enough to guarantee that the whole sub-pattern matched.
-            #[cfg(feature = "exact-atoms")]
-            if atom.is_exact() {
-                let flags = match sub_pattern {
-                    SubPattern::Literal { flags, .. }
-                    | SubPattern::LiteralChainHead { flags, .. }
-                    | SubPattern::LiteralChainTail { flags, .. }
-                    | SubPattern::Regexp { flags, .. }
-                    | SubPattern::RegexpChainHead { flags, .. }
-                    | SubPattern::RegexpChainTail { flags, .. } => flags,
-                    _ => unreachable!(),
+                // The dead states memoized by the Pike VM count towards the
+                // memory limit, but they are just a cache, so they are
+                // discarded instead of aborting the scan.
+                if self.max_memory.is_some_and(|max_memory| {
+                    self.memory_usage() + vm.pike_vm.memory_usage()
+                        > max_memory
+                }) {
+                    vm.pike_vm.clear_dead_states();
+                }
+
+                self.check_memory_limit()?;
+
+                let atom = unsafe { atoms.get_unchecked(atom_match.atom()) };
+
+                // Subtract the backtrack value from the offset where the atom
+                // matched. If the result is negative the atom can't be inside
+                // the scanned data and therefore is not a possible match.
+                let atom_pos = if let Some(atom_pos) =
+                    atom_match.start().checked_sub(atom.backtrack())
+                {
+                    atom_pos
+                } else {
+                    continue;
                 };
 
-                let match_range = atom_pos..atom_pos + atom.len();
+                // Each atom belongs to a sub-pattern.
+                let sub_pattern_id = atom.sub_pattern_id();
 
-                if verify_full_word(scanned_data, &match_range, *flags, None) {
-                    self.handle_sub_pattern_match(
-                        sub_pattern_id,
-                        sub_pattern,
-                        *pattern_id,
-                        Match { range: match_range, xor_key: None },
-                    );
-                }
+                // Each sub-pattern belongs to a pattern.
+                let (pattern_id, sub_pattern) =
+                    &self.compiled_rules.get_sub_pattern(sub_pattern_id);
 
-                continue;
-            }
+                // Patterns used only by disabled rules are ignored.
+                if !self.disabled_patterns.is_empty()
+                    && self.disabled_patterns.contains(pattern_id)
+                {
+                    continue;
+                }
 
-            match sub_pattern {
-                SubPattern::Literal { pattern, flags, .. }
-                | SubPattern::LiteralChainHead { pattern, flags, .. }
-                | SubPattern::LiteralChainTail { pattern, flags, .. } => {
-                    if let Some(match_) = verify_literal_match(
-                        self.compiled_rules
-                            .lit_pool()
-                            .get_bytes(*pattern)
-                            .unwrap(),
+                #[cfg(feature = "rules-profiling")]
+                let verification_start = Instant::now();
+
+                // If the atom is exact no further verification is needed, except
+                // for making sure that the fullword requirements are met. An exact
+                // atom is enough to guarantee that the whole sub-pattern matched.
+                #[cfg(feature = "exact-atoms")]
+                if atom.is_exact() {
+                    let flags = match sub_pattern {
+                        SubPattern::Literal { flags, .. }
+                        | SubPattern::LiteralChainHead { flags, .. }
+                        | SubPattern::LiteralChainTail { flags, .. }
+                        | SubPattern::Regexp { flags, .. }
+                        | SubPattern::RegexpChainHead { flags, .. }
+                        | SubPattern::RegexpChainTail { flags, .. } => flags,
+                        _ => unreachable!(),
+                    };
+
+                    let match_range = atom_pos..atom_pos + atom.len();
+
+                    if verify_full_word(
                         scanned_data,
-                        atom_pos,
+                        &match_range,
                         *flags,
+                        None,
                     ) {
                         self.handle_sub_pattern_match(
                             sub_pattern_id,
                             sub_pattern,
                             *pattern_id,
-                            match_,
+                            Match {
+                                range: match_range,
+                                xor_key: None,
+                                variant: plain_or_wide(*flags),
+                            },
                         );
                     }
+
+                    continue;
                 }
-                SubPattern::Regexp { flags, .. }
-                | SubPattern::RegexpChainHead { flags, .. }
-                | SubPattern::RegexpChainTail { flags, .. } => {
-                    verify_regexp_match(
-                        &mut vm,
-                        scanned_data,
-                        atom_pos,
-                        atom,
-                        *flags,
-                        |match_| {
+
+                match sub_pattern {
+                    SubPattern::Literal { pattern, flags, .. }
+                    | SubPattern::LiteralChainHead {
+                        pattern, flags, ..
+                    }
+                    | SubPattern::LiteralChainTail {
+                        pattern, flags, ..
+                    } => {
+                        if let Some(match_) = verify_literal_match(
+                            self.compiled_rules
+                                .lit_pool()
+                                .get_bytes(*pattern)
+                                .unwrap(),
+                            scanned_data,
+                            atom_pos,
+                            *flags,
+                        ) {
                             self.handle_sub_pattern_match(
                                 sub_pattern_id,
                                 sub_pattern,
                                 *pattern_id,
                                 match_,
                             );
-                        },
-                    )
-                }
-
-                SubPattern::Xor { pattern, flags } => {
-                    if let Some(match_) = verify_xor_match(
-                        self.compiled_rules
-                            .lit_pool()
-                            .get_bytes(*pattern)
-                            .unwrap(),
-                        scanned_data,
-                        atom_pos,
-                        atom,
-                        *flags,
-                    ) {
-                        self.handle_sub_pattern_match(
-                            sub_pattern_id,
-                            sub_pattern,
-                            *pattern_id,
-                            match_,
-                        );
+                        }
                     }
-                }
-
-                SubPattern::Base64 { pattern, padding }
-                | SubPattern::Base64Wide { pattern, padding } => {
-                    if let Some(match_) = verify_base64_match(
-                        self.compiled_rules
-                            .lit_pool()
-                            .get_bytes(*pattern)
-                            .unwrap(),
-                        scanned_data,
-                        (*padding).into(),
-                        atom_pos,
-                        None,
-                        matches!(sub_pattern, SubPattern::Base64Wide { .. }),
-                    ) {
-                        self.handle_sub_pattern_match(
-                            sub_pattern_id,
-                            sub_pattern,
-                            *pattern_id,
-                            match_,
-                        );
+                    SubPattern::Regexp { flags, .. }
+                    | SubPattern::RegexpChainHead { flags, .. }
+                    | SubPattern::RegexpChainTail { flags, .. } => {
+                        self.regexp_executions += 1;
+                        verify_regexp_match(
+                            &mut vm,
+                            scanned_data,
+                            atom_pos,
+                            atom,
+                            *flags,
+                            |match_| {
+                                self.handle_sub_pattern_match(
+                                    sub_pattern_id,
+                                    sub_pattern,
+                                    *pattern_id,
+                                    match_,
+                                );
+                            },
+                        )
                     }
-                }
 
-                SubPattern::CustomBase64 { pattern, alphabet, padding }
-                | SubPattern::CustomBase64Wide {
-                    pattern,
-                    alphabet,
-                    padding,
-                } => {
-                    let alphabet = self
-                        .compiled_rules
-                        .lit_pool()
-                        .get_str(*alphabet)
-                        .map(|alphabet| {
-                            // `Alphabet::new` validates the string again. This
-                            // is not really necessary as we already know that
-                            // the string represents a valid alphabet, it would
-                            // be better if could use the private function
-                            // `Alphabet::from_str_unchecked`
-                            base64::alphabet::Alphabet::new(alphabet).unwrap()
-                        });
+                    SubPattern::Xor { pattern, flags } => {
+                        if let Some(match_) = verify_xor_match(
+                            self.compiled_rules
+                                .lit_pool()
+                                .get_bytes(*pattern)
+                                .unwrap(),
+                            scanned_data,
+                            atom_pos,
+                            atom,
+                            *flags,
+                        ) {
+                            self.handle_sub_pattern_match(
+                                sub_pattern_id,
+                                sub_pattern,
+                                *pattern_id,
+                                match_,
+                            );
+                        }
+                    }
 
-                    assert!(alphabet.is_some());
+                    SubPattern::Base64 { pattern, padding }
+                    | SubPattern::Base64Wide { pattern, padding } => {
+                        if let Some(match_) = verify_base64_match(
+                            self.compiled_rules
+                                .lit_pool()
+                                .get_bytes(*pattern)
+                                .unwrap(),
+                            scanned_data,
+                            (*padding).into(),
+                            atom_pos,
+                            None,
+                            matches!(
+                                sub_pattern,
+                                SubPattern::Base64Wide { .. }
+                            ),
+                        ) {
+                            self.handle_sub_pattern_match(
+                                sub_pattern_id,
+                                sub_pattern,
+                                *pattern_id,
+                                match_,
+                            );
+                        }
+                    }
 
-                    if let Some(match_) = verify_base64_match(
-                        self.compiled_rules
-                            .lit_pool()
-                            .get_bytes(*pattern)
-                            .unwrap(),
-                        scanned_data,
-                        (*padding).into(),
-                        atom_pos,
+                    SubPattern::CustomBase64 {
+                        pattern,
                         alphabet,
-                        matches!(
-                            sub_pattern,
-                            SubPattern::CustomBase64Wide { .. }
-                        ),
-                    ) {
-                        self.handle_sub_pattern_match(
-                            sub_pattern_id,
-                            sub_pattern,
-                            *pattern_id,
-                            match_,
-                        );
+                        padding,
+                    }
+                    | SubPattern::CustomBase64Wide {
+                        pattern,
+                        alphabet,
+                        padding,
+                    } => {
+                        let alphabet = self
+                            .compiled_rules
+                            .lit_pool()
+                            .get_str(*alphabet)
+                            .map(|alphabet| {
+                                // `Alphabet::new` validates the string again. This
+                                // is not really necessary as we already know that
+                                // the string represents a valid alphabet, it would
+                                // be better if could use the private function
+                                // `Alphabet::from_str_unchecked`
+                                base64::alphabet::Alphabet::new(alphabet)
+                                    .unwrap()
+                            });
+
+                        assert!(alphabet.is_some());
+
+                        if let Some(match_) = verify_base64_match(
+                            self.compiled_rules
+                                .lit_pool()
+                                .get_bytes(*pattern)
+                                .unwrap(),
+                            scanned_data,
+                            (*padding).into(),
+                            atom_pos,
+                            alphabet,
+                            matches!(
+                                sub_pattern,
+                                SubPattern::CustomBase64Wide { .. }
+                            ),
+                        ) {
+                            self.handle_sub_pattern_match(
+                                sub_pattern_id,
+                                sub_pattern,
+                                *pattern_id,
+                                match_,
+                            );
+                        }
                     }
+                };
+
+                #[cfg(feature = "rules-profiling")]
+                {
+                    let time_spent = Instant::elapsed(&verification_start);
+                    self.time_spent_in_pattern
+                        .entry(*pattern_id)
+                        .and_modify(|t| {
+                            *t += time_spent;
+                        })
+                        .or_insert(time_spent);
                 }
-            };
+            }
 
-            #[cfg(feature = "rules-profiling")]
-            {
-                let time_spent = Instant::elapsed(&verification_start);
-                self.time_spent_in_pattern
-                    .entry(*pattern_id)
-                    .and_modify(|t| {
-                        *t += time_spent;
-                    })
-                    .or_insert(time_spent);
+            self.report_progress(ScanPhase::PatternSearch, chunk_end);
+
+            if chunk_end == scanned_data.len() {
+                break;
             }
+
+            chunk_start = chunk_end;
         }
 
         #[cfg(feature = "logging")]
@@ -606,7 +902,33 @@ impl ScanContext<'_> {
                 }
             }
         }
-        Ok(())
+
+        self.check_memory_limit()
+    }
+
+    /// Returns an estimate of the memory allocated on behalf of the current
+    /// scan, in bytes.
+    ///
+    /// This includes the matches found so far, the outputs produced by
+    /// modules and the strings created during the evaluation of conditions.
+    /// The estimate is maintained incrementally, so calling this function
+    /// is cheap.
+    #[inline]
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.pattern_matches.memory_usage() + self.allocated_memory
+    }
+
+    /// Returns [`ScanError::OutOfMemory`] if the memory allocated on behalf
+    /// of the current scan exceeds the limit set with
+    /// [`crate::ScanOptions::max_memory`].
+    #[inline]
+    pub(crate) fn check_memory_limit(&self) -> Result<(), ScanError> {
+        match self.max_memory {
+            Some(max_memory) if self.memory_usage() > max_memory => {
+                Err(ScanError::OutOfMemory)
+            }
+            _ => Ok(()),
+        }
     }
 
     fn verify_anchored_patterns(&mut self) {
@@ -620,16 +942,21 @@ impl ScanContext<'_> {
                 SubPattern::Literal {
                     pattern,
                     flags,
-                    anchored_at: Some(offset),
+                    anchored_at: Some(anchor),
                     ..
                 } => {
+                    let offset = match anchor.offset(self.scanned_data().len())
+                    {
+                        Some(offset) => offset,
+                        None => continue,
+                    };
                     if let Some(match_) = verify_literal_match(
                         self.compiled_rules
                             .lit_pool()
                             .get_bytes(*pattern)
                             .unwrap(),
                         self.scanned_data(),
-                        *offset,
+                        offset,
                         *flags,
                     ) {
                         self.handle_sub_pattern_match(
@@ -681,7 +1008,8 @@ impl ScanContext<'_> {
                     .push(UnconfirmedMatch {
                         range: match_.range,
                         chain_length: 0,
-                    })
+                    });
+                self.allocated_memory += mem::size_of::<UnconfirmedMatch>();
             }
             SubPattern::LiteralChainTail {
                 chained_to, gap, flags, ..
@@ -713,6 +1041,8 @@ impl ScanContext<'_> {
                                 range: match_.range,
                                 chain_length: 0,
                             });
+                        self.allocated_memory +=
+                            mem::size_of::<UnconfirmedMatch>();
                     }
                 }
             }
@@ -782,6 +1112,7 @@ impl ScanContext<'_> {
                             Match {
                                 range: match_range.start..tail_match_range.end,
                                 xor_key: None,
+                                variant: plain_or_wide(*flags),
                             },
                             flags.contains(SubPatternFlags::GreedyRegexp),
                         );
@@ -860,6 +1191,16 @@ impl ScanContext<'_> {
     }
 }
 
+/// Returns the [`MatchVariant`] for a match produced by a sub-pattern with
+/// the given flags.
+fn plain_or_wide(flags: SubPatternFlagSet) -> MatchVariant {
+    if flags.contains(SubPatternFlags::Wide) {
+        MatchVariant::Wide
+    } else {
+        MatchVariant::Plain
+    }
+}
+
 /// Verifies if a literal `pattern` matches at `atom_pos` in `scanned_data`.
 ///
 /// Returns a [`Match`] if the match was confirmed or [`None`] if otherwise.
@@ -899,6 +1240,7 @@ fn verify_literal_match(
             // The end of the range is exclusive.
             range: atom_pos..match_end,
             xor_key: None,
+            variant: plain_or_wide(flags),
         })
     } else {
         None
@@ -1019,7 +1361,11 @@ fn verify_regexp_match(
                     let range =
                         atom_pos - bck_match_len..atom_pos + fwd_match_len;
                     if verify_full_word(scanned_data, &range, flags, None) {
-                        f(Match { range, xor_key: None });
+                        f(Match {
+                            range,
+                            xor_key: None,
+                            variant: plain_or_wide(flags),
+                        });
                     }
                     Action::Continue
                 },
@@ -1034,7 +1380,11 @@ fn verify_regexp_match(
                     let range =
                         atom_pos - bck_match_len..atom_pos + fwd_match_len;
                     if verify_full_word(scanned_data, &range, flags, None) {
-                        f(Match { range, xor_key: None });
+                        f(Match {
+                            range,
+                            xor_key: None,
+                            variant: plain_or_wide(flags),
+                        });
                     }
                     Action::Continue
                 },
@@ -1043,7 +1393,7 @@ fn verify_regexp_match(
     } else {
         let range = atom_pos..atom_pos + fwd_match_len;
         if verify_full_word(scanned_data, &range, flags, None) {
-            f(Match { range, xor_key: None });
+            f(Match { range, xor_key: None, variant: plain_or_wide(flags) });
         }
     }
 }
@@ -1090,7 +1440,11 @@ fn verify_xor_match(
     }
 
     if &scanned_data[match_range.clone()] == pattern.as_bytes() {
-        Some(Match { range: match_range, xor_key: Some(key) })
+        Some(Match {
+            range: match_range,
+            xor_key: Some(key),
+            variant: plain_or_wide(flags),
+        })
     } else {
         None
     }
@@ -1238,6 +1592,11 @@ fn verify_base64_match(
             Some(Match {
                 range: atom_pos..atom_pos + match_len,
                 xor_key: None,
+                variant: if wide {
+                    MatchVariant::Base64Wide
+                } else {
+                    MatchVariant::Base64
+                },
             })
         } else {
             None
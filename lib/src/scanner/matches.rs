@@ -1,10 +1,12 @@
 use std::collections::hash_map::Entry;
+use std::mem;
 use std::ops::{Range, RangeInclusive};
 
 use core::slice::Iter;
 use rustc_hash::FxHashMap;
 
 use crate::compiler::PatternId;
+use crate::models::MatchVariant;
 
 /// Represents the match of a pattern.
 #[derive(Debug, Clone)]
@@ -15,6 +17,8 @@ pub(crate) struct Match {
     /// where `k` is the XOR key (it may be 0). For any other type of
     /// pattern this is `None`.
     pub xor_key: Option<u8>,
+    /// Variant of the pattern that produced the match.
+    pub variant: MatchVariant,
 }
 
 /// Represents the list of matches for a pattern.
@@ -218,6 +222,12 @@ impl PatternMatches {
         self.matches.is_empty()
     }
 
+    /// Returns the number of bytes allocated for storing matches.
+    #[inline]
+    pub fn memory_usage(&self) -> usize {
+        self.capacity * mem::size_of::<Match>()
+    }
+
     /// Clears the matches for all patterns.
     ///
     /// To optimize performance, the memory allocated for storing matches
@@ -252,7 +262,9 @@ impl PatternMatches {
     ///
     /// This function returns `true` if the new match was added, or `false`
     /// if the pattern already reached the maximum number of matches and
-    /// therefore the new match was not added.
+    /// therefore the new match was not added. Matches that start at the
+    /// same offset as some existing match are always accepted, as they
+    /// don't increase the number of matches.
     pub fn add(
         &mut self,
         pattern_id: PatternId,
@@ -262,7 +274,9 @@ impl PatternMatches {
         match self.matches.entry(pattern_id) {
             Entry::Occupied(mut entry) => {
                 let matches = entry.get_mut();
-                if matches.len() < self.max_matches_per_pattern {
+                if matches.len() < self.max_matches_per_pattern
+                    || matches.search(m.range.start).is_ok()
+                {
                     self.capacity -= matches.capacity();
                     matches.add(m, replace_if_longer);
                     self.capacity += matches.capacity();
@@ -284,6 +298,7 @@ impl PatternMatches {
 
 #[cfg(test)]
 mod test {
+    use crate::models::MatchVariant;
     use crate::scanner::matches::{Match, MatchList};
     use std::ops::Range;
 
@@ -291,11 +306,46 @@ mod test {
     fn match_list() {
         let mut ml = MatchList::with_capacity(5);
 
-        ml.add(Match { range: (2..10), xor_key: None }, false);
-        ml.add(Match { range: (1..10), xor_key: None }, false);
-        ml.add(Match { range: (4..10), xor_key: None }, false);
-        ml.add(Match { range: (3..10), xor_key: None }, false);
-        ml.add(Match { range: (5..10), xor_key: None }, false);
+        ml.add(
+            Match {
+                range: (2..10),
+                xor_key: None,
+                variant: MatchVariant::Plain,
+            },
+            false,
+        );
+        ml.add(
+            Match {
+                range: (1..10),
+                xor_key: None,
+                variant: MatchVariant::Plain,
+            },
+            false,
+        );
+        ml.add(
+            Match {
+                range: (4..10),
+                xor_key: None,
+                variant: MatchVariant::Plain,
+            },
+            false,
+        );
+        ml.add(
+            Match {
+                range: (3..10),
+                xor_key: None,
+                variant: MatchVariant::Plain,
+            },
+            false,
+        );
+        ml.add(
+            Match {
+                range: (5..10),
+                xor_key: None,
+                variant: MatchVariant::Plain,
+            },
+            false,
+        );
 
         assert_eq!(
             ml.iter().map(|m| m.range.clone()).collect::<Vec<Range<usize>>>(),
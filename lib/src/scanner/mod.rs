@@ -4,9 +4,9 @@ The scanner takes the rules produces by the compiler and scans data with them.
 */
 
 use std::cell::RefCell;
-use std::collections::{hash_map, HashMap};
+use std::collections::HashMap;
 use std::io::Read;
-use std::ops::Deref;
+use std::ops::{Deref, Range};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::ptr::{null, NonNull};
@@ -14,12 +14,13 @@ use std::rc::Rc;
 use std::slice::Iter;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Once;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{cmp, fs, thread};
 
 use bitvec::prelude::*;
 use fmmap::{MmapFile, MmapFileExt};
 use indexmap::IndexMap;
+use protobuf::reflect::MessageDescriptor;
 use protobuf::{CodedInputStream, MessageDyn};
 use rustc_hash::{FxHashMap, FxHashSet};
 use thiserror::Error;
@@ -30,20 +31,27 @@ use wasmtime::{
 
 use crate::compiler::{RuleId, Rules};
 use crate::models::Rule;
-use crate::modules::{Module, BUILTIN_MODULES};
+use crate::modules::BUILTIN_MODULES;
 use crate::scanner::matches::PatternMatches;
 use crate::types::{Struct, TypeValue};
 use crate::variables::VariableError;
 use crate::wasm::{ENGINE, MATCHING_RULES_BITMAP_BASE};
 use crate::{modules, wasm, Variable};
 
+pub(crate) use crate::scanner::context::FileMetadata;
 pub(crate) use crate::scanner::context::RuntimeObject;
 pub(crate) use crate::scanner::context::RuntimeObjectHandle;
 pub(crate) use crate::scanner::context::ScanContext;
+pub(crate) use crate::scanner::context::TraceCounters;
 pub(crate) use crate::scanner::matches::Match;
+pub use crate::scanner::pool::PooledScanner;
+pub use crate::scanner::pool::ScannerPool;
 
+pub(crate) mod aggregate;
 mod context;
 mod matches;
+mod pool;
+pub(crate) mod testing;
 
 #[cfg(test)]
 mod tests;
@@ -78,12 +86,25 @@ pub enum ScanError {
         /// Error that occurred
         err: protobuf::Error,
     },
+    /// Could not parse the JSON representation of the protobuf message for
+    /// some YARA module.
+    #[error("can not parse JSON for YARA module `{module}`: {err}")]
+    JsonError {
+        /// Module name.
+        module: String,
+        /// Error that occurred
+        err: protobuf_json_mapping::ParseError,
+    },
     /// The module is unknown.
     #[error("unknown module `{module}`")]
     UnknownModule {
         /// Module name.
         module: String,
     },
+    /// The scan was aborted because the memory allocated on behalf of
+    /// the scan exceeded the limit set with [`ScanOptions::max_memory`].
+    #[error("memory limit exceeded")]
+    OutOfMemory,
 }
 
 /// Global counter that gets incremented every 1 second by a dedicated thread.
@@ -94,6 +115,9 @@ static HEARTBEAT_COUNTER: AtomicU64 = AtomicU64::new(0);
 /// Used for spawning the thread that increments `HEARTBEAT_COUNTER`.
 static INIT_HEARTBEAT: Once = Once::new();
 
+/// Global counter used for assigning a unique identifier to each scan.
+static SCAN_ID: AtomicU64 = AtomicU64::new(1);
+
 pub enum ScannedData<'a> {
     Slice(&'a [u8]),
     Vec(Vec<u8>),
@@ -114,6 +138,7 @@ impl<'a> AsRef<[u8]> for ScannedData<'a> {
 #[derive(Debug, Default)]
 pub struct ScanOptions<'a> {
     module_metadata: HashMap<&'a str, &'a [u8]>,
+    max_memory: Option<usize>,
 }
 
 impl<'a> ScanOptions<'a> {
@@ -122,7 +147,7 @@ impl<'a> ScanOptions<'a> {
     ///
     /// Use other methods to add additional information.
     pub fn new() -> Self {
-        Self { module_metadata: Default::default() }
+        Self { module_metadata: Default::default(), max_memory: None }
     }
 
     /// Adds metadata for a YARA module.
@@ -134,6 +159,265 @@ impl<'a> ScanOptions<'a> {
         self.module_metadata.insert(module_name, metadata);
         self
     }
+
+    /// Sets the maximum amount of memory, in bytes, that can be allocated
+    /// on behalf of the scan.
+    ///
+    /// This accounts for the memory used by pattern matches, the data
+    /// produced by YARA modules, and the strings created while evaluating
+    /// rule conditions. The memory used by the scanner itself and by the
+    /// compiled rules is not included. If the limit is exceeded, the scan
+    /// is aborted with [`ScanError::OutOfMemory`].
+    ///
+    /// The memory used for caching intermediate results while verifying
+    /// regular expressions also counts towards the limit, but the cache is
+    /// emptied instead of aborting the scan.
+    ///
+    /// The data produced by a module is measured when the module finishes
+    /// parsing the scanned data, and the scan is aborted before any other
+    /// module runs if the limit has been exceeded at that point.
+    pub fn max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+}
+
+/// Phases of a scan operation.
+///
+/// See [`Scanner::progress_callback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanPhase {
+    /// A YARA module is parsing the scanned data.
+    ModuleParsing,
+    /// The scanned data is being searched for patterns.
+    PatternSearch,
+    /// Rule conditions are being evaluated.
+    ConditionEvaluation,
+}
+
+/// Statistics about the invocations of a YARA module.
+///
+/// See [`Scanner::module_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModuleStats {
+    parsed: usize,
+    skipped: usize,
+}
+
+impl ModuleStats {
+    /// Number of scans in which the module parsed the scanned data.
+    #[inline]
+    pub fn parsed(&self) -> usize {
+        self.parsed
+    }
+
+    /// Number of scans in which the module didn't parse the scanned data
+    /// because it was not in the file format expected by the module.
+    #[inline]
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+}
+
+/// Statistics about one or more scans.
+///
+/// The statistics for a single scan are returned by [`ScanResults::stats`].
+/// Statistics from multiple scans can be aggregated with [`ScanStats::merge`],
+/// which is useful for monitoring the scanner over a long period of time.
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::{compile, Scanner, ScanStats};
+/// let rules = compile(r#"rule test { strings: $a = "foo" condition: $a }"#).unwrap();
+/// let mut scanner = Scanner::new(&rules);
+/// let mut stats = ScanStats::default();
+///
+/// for data in [b"foo".as_slice(), b"bar foo".as_slice()] {
+///     stats.merge(&scanner.scan(data).unwrap().stats());
+/// }
+///
+/// assert_eq!(stats.num_scans(), 2);
+/// assert_eq!(stats.bytes_scanned(), 10);
+/// assert_eq!(stats.rules_evaluated(), 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanStats {
+    num_scans: usize,
+    bytes_scanned: usize,
+    atoms_fired: usize,
+    regexp_executions: usize,
+    rules_evaluated: usize,
+    rule_timeouts: usize,
+    module_parse_times: Vec<(String, Duration)>,
+}
+
+impl ScanStats {
+    /// Number of scans included in these statistics.
+    #[inline]
+    pub fn num_scans(&self) -> usize {
+        self.num_scans
+    }
+
+    /// Number of bytes scanned.
+    #[inline]
+    pub fn bytes_scanned(&self) -> usize {
+        self.bytes_scanned
+    }
+
+    /// Number of atoms found while searching for patterns. Each atom found
+    /// in the data is a potential match that must be verified.
+    #[inline]
+    pub fn atoms_fired(&self) -> usize {
+        self.atoms_fired
+    }
+
+    /// Number of times that a regular expression was executed for verifying
+    /// a potential match.
+    #[inline]
+    pub fn regexp_executions(&self) -> usize {
+        self.regexp_executions
+    }
+
+    /// Number of rules whose conditions were evaluated. Rules that are not
+    /// evaluated because a global rule in the same namespace didn't match
+    /// are not included.
+    #[inline]
+    pub fn rules_evaluated(&self) -> usize {
+        self.rules_evaluated
+    }
+
+    /// Number of rules that were aborted because they exceeded the timeout
+    /// set with [`Scanner::set_rule_timeout`].
+    #[inline]
+    pub fn rule_timeouts(&self) -> usize {
+        self.rule_timeouts
+    }
+
+    /// Returns the time spent by each module parsing the scanned data.
+    pub fn module_parse_times(
+        &self,
+    ) -> impl Iterator<Item = (&str, Duration)> + '_ {
+        self.module_parse_times
+            .iter()
+            .map(|(module, time)| (module.as_str(), *time))
+    }
+
+    /// Adds the statistics in `other` to these ones.
+    pub fn merge(&mut self, other: &ScanStats) {
+        self.num_scans += other.num_scans;
+        self.bytes_scanned += other.bytes_scanned;
+        self.atoms_fired += other.atoms_fired;
+        self.regexp_executions += other.regexp_executions;
+        self.rules_evaluated += other.rules_evaluated;
+        self.rule_timeouts += other.rule_timeouts;
+
+        for (module, time) in &other.module_parse_times {
+            match self.module_parse_times.iter_mut().find(|(m, _)| m == module)
+            {
+                Some((_, t)) => *t += *time,
+                None => self.module_parse_times.push((module.clone(), *time)),
+            }
+        }
+    }
+}
+
+/// Time spent by the scanner on a rule.
+///
+/// See [`Scanner::most_expensive_rules`].
+#[cfg(feature = "rules-profiling")]
+#[derive(Debug, Clone, Copy)]
+pub struct RuleProfile<'r> {
+    namespace: &'r str,
+    rule: &'r str,
+    pattern_matching_time: Duration,
+}
+
+#[cfg(feature = "rules-profiling")]
+impl<'r> RuleProfile<'r> {
+    /// Namespace of the rule.
+    #[inline]
+    pub fn namespace(&self) -> &'r str {
+        self.namespace
+    }
+
+    /// Identifier of the rule.
+    #[inline]
+    pub fn rule(&self) -> &'r str {
+        self.rule
+    }
+
+    /// Time spent verifying the matches of the rule's patterns.
+    #[inline]
+    pub fn pattern_matching_time(&self) -> Duration {
+        self.pattern_matching_time
+    }
+}
+
+/// Progress of a scan operation.
+///
+/// See [`Scanner::progress_callback`].
+#[derive(Debug, Clone, Copy)]
+pub struct ScanProgress {
+    phase: ScanPhase,
+    bytes_processed: usize,
+    total_bytes: usize,
+}
+
+impl ScanProgress {
+    /// Current phase of the scan.
+    #[inline]
+    pub fn phase(&self) -> ScanPhase {
+        self.phase
+    }
+
+    /// Number of bytes that have been searched for patterns so far.
+    #[inline]
+    pub fn bytes_processed(&self) -> usize {
+        self.bytes_processed
+    }
+
+    /// Size of the scanned data.
+    #[inline]
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+}
+
+/// An architecture slice in a FAT Mach-O binary.
+///
+/// See [`Scanner::scan_macho_slices`].
+#[cfg(feature = "macho-module")]
+#[derive(Debug, Clone)]
+pub struct MachoSlice {
+    index: usize,
+    cputype: u32,
+    cpusubtype: u32,
+    range: Range<usize>,
+}
+
+#[cfg(feature = "macho-module")]
+impl MachoSlice {
+    /// Index of the slice in the FAT header. This is also the index of the
+    /// slice in `macho.fat_arch`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// CPU type of the slice.
+    pub fn cputype(&self) -> u32 {
+        self.cputype
+    }
+
+    /// CPU subtype of the slice.
+    pub fn cpusubtype(&self) -> u32 {
+        self.cpusubtype
+    }
+
+    /// Range occupied by the slice within the FAT binary.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
 }
 
 /// Scans data with already compiled YARA rules.
@@ -146,6 +430,8 @@ pub struct Scanner<'r> {
     wasm_store: Pin<Box<Store<ScanContext<'r>>>>,
     wasm_main_func: TypedFunc<(), i32>,
     filesize: Global,
+    max_loop_iterations: Global,
+    rules_evaluated: Global,
     timeout: Option<Duration>,
 }
 
@@ -173,10 +459,26 @@ impl<'r> Scanner<'r> {
                 runtime_objects: IndexMap::new(),
                 compiled_rules: rules,
                 console_log: None,
+                progress_callback: None,
+                disabled_rules: FxHashSet::default(),
+                rule_timeout: None,
+                rule_eval_start: None,
+                timed_out_rules: Vec::new(),
+                atoms_fired: 0,
+                regexp_executions: 0,
+                rules_evaluated: 0,
+                module_parse_times: Vec::new(),
+                disabled_patterns: FxHashSet::default(),
+                module_stats: IndexMap::new(),
                 current_struct: None,
                 root_struct: rules.globals().make_root(),
                 scanned_data: null(),
                 scanned_data_len: 0,
+                file_metadata: None,
+                condition_traces: vec![
+                    TraceCounters::default();
+                    rules.condition_traces().len()
+                ],
                 private_matching_rules: Vec::new(),
                 non_private_matching_rules: Vec::new(),
                 matching_rules: IndexMap::new(),
@@ -185,8 +487,12 @@ impl<'r> Scanner<'r> {
                 user_provided_module_outputs: FxHashMap::default(),
                 pattern_matches: PatternMatches::new(),
                 unconfirmed_matches: FxHashMap::default(),
+                scan_id: 0,
                 deadline: 0,
-                limit_reached: FxHashSet::default(),
+                suppressed_matches: FxHashMap::default(),
+                max_memory: None,
+                allocated_memory: 0,
+                search_error: None,
                 regexp_cache: RefCell::new(FxHashMap::default()),
                 #[cfg(feature = "rules-profiling")]
                 time_spent_in_pattern: FxHashMap::default(),
@@ -226,6 +532,45 @@ impl<'r> Scanner<'r> {
         )
         .unwrap();
 
+        // Global variable that counts the loop iterations executed while
+        // evaluating a rule's condition. The WASM code resets it before
+        // evaluating each rule.
+        let loop_iterations = Global::new(
+            wasm_store.as_context_mut(),
+            GlobalType::new(ValType::I64, Mutability::Var),
+            Val::I64(0),
+        )
+        .unwrap();
+
+        // Global variable that contains the maximum number of iterations
+        // allowed while evaluating a rule's condition. By default there's
+        // no limit.
+        let max_loop_iterations = Global::new(
+            wasm_store.as_context_mut(),
+            GlobalType::new(ValType::I64, Mutability::Var),
+            Val::I64(i64::MAX),
+        )
+        .unwrap();
+
+        // Global variable that contains the maximum number of iterations
+        // allowed for the rule being evaluated. The WASM code sets it to
+        // `max_loop_iterations` before evaluating each rule.
+        let loop_limit = Global::new(
+            wasm_store.as_context_mut(),
+            GlobalType::new(ValType::I64, Mutability::Var),
+            Val::I64(i64::MAX),
+        )
+        .unwrap();
+
+        // Global variable that counts the rules whose conditions were
+        // evaluated. It is reset before each scan.
+        let rules_evaluated = Global::new(
+            wasm_store.as_context_mut(),
+            GlobalType::new(ValType::I64, Mutability::Var),
+            Val::I64(0),
+        )
+        .unwrap();
+
         // Compute the base offset for the bitmap that contains matching
         // information for patterns. This bitmap has 1 bit per pattern, the
         // N-th bit is set if pattern with PatternId = N matched. The bitmap
@@ -274,6 +619,34 @@ impl<'r> Scanner<'r> {
                 timeout_occurred,
             )
             .unwrap()
+            .define(
+                wasm_store.as_context(),
+                "yara_x",
+                "loop_iterations",
+                loop_iterations,
+            )
+            .unwrap()
+            .define(
+                wasm_store.as_context(),
+                "yara_x",
+                "max_loop_iterations",
+                max_loop_iterations,
+            )
+            .unwrap()
+            .define(
+                wasm_store.as_context(),
+                "yara_x",
+                "loop_limit",
+                loop_limit,
+            )
+            .unwrap()
+            .define(
+                wasm_store.as_context(),
+                "yara_x",
+                "rules_evaluated",
+                rules_evaluated,
+            )
+            .unwrap()
             .define(
                 wasm_store.as_context(),
                 "yara_x",
@@ -298,7 +671,14 @@ impl<'r> Scanner<'r> {
 
         wasm_store.data_mut().main_memory = Some(main_memory);
 
-        Self { wasm_store, wasm_main_func, filesize, timeout: None }
+        Self {
+            wasm_store,
+            wasm_main_func,
+            filesize,
+            max_loop_iterations,
+            rules_evaluated,
+            timeout: None,
+        }
     }
 
     /// Sets a timeout for scan operations.
@@ -314,15 +694,147 @@ impl<'r> Scanner<'r> {
         self
     }
 
+    /// Sets the maximum time that the evaluation of a single rule can take.
+    ///
+    /// This bounds the time spent in the loops of each rule's condition. A
+    /// rule that exceeds the limit is aborted and doesn't match, but the
+    /// scan continues with the remaining rules. Rules aborted this way are
+    /// returned by [`ScanResults::timed_out_rules`]. This is independent of
+    /// the timeout set with [`Scanner::set_timeout`], which aborts the
+    /// whole scan.
+    ///
+    /// The time is checked periodically while the rule's loops are being
+    /// executed, and the time spent before entering the first loop is not
+    /// taken into account.
+    pub fn set_rule_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.wasm_store.data_mut().rule_timeout = Some(timeout);
+        self
+    }
+
     /// Sets the maximum number of matches per pattern.
     ///
-    /// When some pattern reaches the maximum number of patterns it won't
-    /// produce more matches.
+    /// When some pattern reaches the maximum number of matches, further
+    /// matches are not recorded, but they are still counted. The number of
+    /// matches that were not recorded can be obtained with
+    /// [`crate::Pattern::suppressed_matches`]. By default, the limit is
+    /// 1,000,000 matches per pattern.
     pub fn max_matches_per_pattern(&mut self, n: usize) -> &mut Self {
         self.wasm_store.data_mut().pattern_matches.max_matches_per_pattern(n);
         self
     }
 
+    /// Sets the maximum number of loop iterations while evaluating the
+    /// condition of a rule.
+    ///
+    /// Loops like `for all i in (0..filesize) : (...)` can take a very long
+    /// time with large files. The limit applies to each rule separately,
+    /// and includes the iterations of all the loops in the rule's condition,
+    /// including `of` expressions. When the limit is exceeded the loop is
+    /// aborted and its result is undefined, which usually means that the
+    /// rule doesn't match. By default there's no limit.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x::{compile, Scanner};
+    /// let rules = compile(
+    ///     "rule test { condition: for all i in (0..filesize) : (true) }",
+    /// )
+    /// .unwrap();
+    ///
+    /// let mut scanner = Scanner::new(&rules);
+    ///
+    /// assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+    ///
+    /// scanner.max_loop_iterations(2);
+    ///
+    /// assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 0);
+    /// ```
+    pub fn max_loop_iterations(&mut self, n: u64) -> &mut Self {
+        self.max_loop_iterations
+            .set(
+                self.wasm_store.as_context_mut(),
+                Val::I64(n.try_into().unwrap_or(i64::MAX)),
+            )
+            .unwrap();
+        self
+    }
+
+    /// Returns statistics about the invocations of each module imported by
+    /// the rules, accumulated over all the scans done with this scanner.
+    ///
+    /// This allows knowing which modules actually parsed the scanned data.
+    /// Modules that parse a specific file format, like `pe` or `elf`, are
+    /// skipped when the scanned data is not in that format.
+    pub fn module_stats(
+        &self,
+    ) -> impl Iterator<Item = (&'r str, ModuleStats)> + '_ {
+        self.wasm_store
+            .data()
+            .module_stats
+            .iter()
+            .map(|(name, stats)| (*name, *stats))
+    }
+
+    /// Returns the `n` rules on which the scanner spent the most time,
+    /// accumulated over all the scans done with this scanner.
+    ///
+    /// The time spent on a rule is the time spent verifying the matches of
+    /// its patterns. The time spent evaluating conditions is not included.
+    /// Rules are sorted by the time spent on them, in descending order.
+    ///
+    /// This is available only when the `rules-profiling` feature is enabled.
+    /// Notice that profiling has a noticeable impact on performance.
+    #[cfg(feature = "rules-profiling")]
+    pub fn most_expensive_rules(&self, n: usize) -> Vec<RuleProfile<'r>> {
+        self.wasm_store
+            .data()
+            .most_expensive_rules()
+            .into_iter()
+            .take(n)
+            .map(|(namespace, rule, pattern_matching_time)| RuleProfile {
+                namespace,
+                rule,
+                pattern_matching_time,
+            })
+            .collect()
+    }
+
+    /// Disables the rule with the given namespace and name.
+    ///
+    /// Disabled rules never match, and the patterns that are used only by
+    /// disabled rules are not searched for in the scanned data. This allows
+    /// turning off a rule without recompiling the rules. Rules that depend
+    /// on a disabled rule see it as a non-matching rule, but a disabled
+    /// global rule doesn't prevent other rules in its namespace from
+    /// matching.
+    ///
+    /// Returns `false` if no rule with the given namespace and name exists.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use yara_x::{compile, Scanner};
+    /// let rules = compile(r#"rule test { strings: $a = "foo" condition: $a }"#).unwrap();
+    /// let mut scanner = Scanner::new(&rules);
+    ///
+    /// assert!(scanner.disable_rule("default", "test"));
+    /// assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 0);
+    ///
+    /// assert!(scanner.enable_rule("default", "test"));
+    /// assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+    /// ```
+    pub fn disable_rule(&mut self, namespace: &str, name: &str) -> bool {
+        self.set_rule_enabled(namespace, name, false)
+    }
+
+    /// Enables a rule previously disabled with [`Scanner::disable_rule`].
+    ///
+    /// Returns `false` if no rule with the given namespace and name exists.
+    pub fn enable_rule(&mut self, namespace: &str, name: &str) -> bool {
+        self.set_rule_enabled(namespace, name, true)
+    }
+
     /// Sets a callback that is invoked every time a YARA rule calls the
     /// `console` module.
     ///
@@ -338,12 +850,37 @@ impl<'r> Scanner<'r> {
         self
     }
 
+    /// Sets a callback that is invoked periodically for reporting the
+    /// progress of each scan.
+    ///
+    /// The callback is invoked before each module parses the scanned data,
+    /// when the evaluation of conditions starts, and while the data is being
+    /// searched for patterns, after every few megabytes. This allows
+    /// displaying progress bars or detecting stalled scans while scanning
+    /// large inputs. The [`ScanProgress`] passed to the callback indicates
+    /// the current phase and how many bytes have been searched for patterns
+    /// so far.
+    ///
+    /// Notice that the pattern search is done only if some condition
+    /// depends on the patterns, and the conditions are evaluated both
+    /// before and after the search. Therefore, the callback may receive
+    /// [`ScanPhase::ConditionEvaluation`] twice, once with no bytes processed
+    /// and once after the pattern search completes.
+    pub fn progress_callback<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnMut(ScanProgress) + 'r,
+    {
+        self.wasm_store.data_mut().progress_callback =
+            Some(Box::new(callback));
+        self
+    }
+
     /// Scans in-memory data.
     pub fn scan<'a>(
         &'a mut self,
         data: &'a [u8],
     ) -> Result<ScanResults<'a, 'r>, ScanError> {
-        self.scan_impl(ScannedData::Slice(data), None)
+        self.scan_impl(ScannedData::Slice(data), None, None)
     }
 
     /// Scans a file.
@@ -354,7 +891,8 @@ impl<'r> Scanner<'r> {
     where
         P: AsRef<Path>,
     {
-        self.scan_impl(Self::load_file(target.as_ref())?, None)
+        let (data, metadata) = Self::load_file(target.as_ref())?;
+        self.scan_impl(data, Some(metadata), None)
     }
 
     /// Like [`Scanner::scan`], but allows to specify additional scan options.
@@ -363,7 +901,7 @@ impl<'r> Scanner<'r> {
         data: &'a [u8],
         options: ScanOptions<'opts>,
     ) -> Result<ScanResults<'a, 'r>, ScanError> {
-        self.scan_impl(ScannedData::Slice(data), Some(options))
+        self.scan_impl(ScannedData::Slice(data), None, Some(options))
     }
 
     /// Like [`Scanner::scan_file`], but allows to specify additional scan
@@ -376,7 +914,67 @@ impl<'r> Scanner<'r> {
     where
         P: AsRef<Path>,
     {
-        self.scan_impl(Self::load_file(target.as_ref())?, Some(options))
+        let (data, metadata) = Self::load_file(target.as_ref())?;
+        self.scan_impl(data, Some(metadata), Some(options))
+    }
+
+    /// Scans each architecture slice in a FAT Mach-O binary independently.
+    ///
+    /// Rules are evaluated once per slice, as if each slice was a separate
+    /// file. This means that offsets and `filesize` are relative to the
+    /// slice, so patterns that appear only in one architecture are correctly
+    /// reported by rules that use offsets, like `$a at entrypoint`. The
+    /// `macho` module describes the single-architecture binary in the
+    /// slice, but it still exposes the FAT header in `macho.fat_arch`, and
+    /// the index of the current slice in `macho.fat_slice`.
+    ///
+    /// `callback` is invoked with the results for each slice. If `data` is
+    /// not a FAT binary it is scanned as a whole, and `callback` is invoked
+    /// once with `None` as the slice.
+    #[cfg(feature = "macho-module")]
+    pub fn scan_macho_slices<F>(
+        &mut self,
+        data: &[u8],
+        mut callback: F,
+    ) -> Result<(), ScanError>
+    where
+        F: FnMut(Option<&MachoSlice>, ScanResults<'_, 'r>),
+    {
+        let Some(slices) = modules::macho_fat_slices(data) else {
+            callback(
+                None,
+                self.scan_impl(ScannedData::Slice(data), None, None)?,
+            );
+            return Ok(());
+        };
+
+        let uses_macho = self
+            .wasm_store
+            .data()
+            .compiled_rules
+            .imports()
+            .any(|module| module == "macho");
+
+        for (range, output) in slices {
+            let arch = &output.fat_arch[output.fat_slice() as usize];
+            let slice = MachoSlice {
+                index: output.fat_slice() as usize,
+                cputype: arch.cputype(),
+                cpusubtype: arch.cpusubtype(),
+                range: range.clone(),
+            };
+
+            if uses_macho {
+                self.set_module_output(Box::new(output))?;
+            }
+
+            let results =
+                self.scan_impl(ScannedData::Slice(&data[range]), None, None)?;
+
+            callback(Some(&slice), results);
+        }
+
+        Ok(())
     }
 
     /// Sets the value of a global variable.
@@ -462,9 +1060,8 @@ impl<'r> Scanner<'r> {
 
         // Check if the protobuf message passed to this function corresponds
         // with any of the existing modules.
-        if !BUILTIN_MODULES
-            .iter()
-            .any(|m| m.1.root_struct_descriptor.full_name() == full_name)
+        if !module_descriptors(self.wasm_store.data().compiled_rules)
+            .any(|(_, descriptor)| descriptor.full_name() == full_name)
         {
             return Err(ScanError::UnknownModule {
                 module: full_name.to_string(),
@@ -479,6 +1076,47 @@ impl<'r> Scanner<'r> {
         Ok(())
     }
 
+    /// Sets the outputs for the modules from those captured in a previous
+    /// scan with [`ScanResults::capture_module_outputs`].
+    ///
+    /// This is intended for scanning the same data with multiple sets of
+    /// rules, as the data is parsed by each module only once. Modules that
+    /// are imported by the rules used by this scanner, but that are not
+    /// included in `outputs`, parse the data as usual. Like with
+    /// [`Scanner::set_module_output`], the outputs are used only in the next
+    /// scan, and this function must be called again before each scan.
+    ///
+    /// The caller is responsible for making sure that the outputs were
+    /// captured while scanning the same data that will be scanned next.
+    ///
+    /// ```rust
+    /// # use yara_x::{compile, Scanner};
+    /// let rules_1 = compile(r#"import "pe" rule a { condition: pe.is_pe }"#).unwrap();
+    /// let rules_2 = compile(r#"import "pe" rule b { condition: not pe.is_pe }"#).unwrap();
+    ///
+    /// let mut scanner_1 = Scanner::new(&rules_1);
+    /// let mut scanner_2 = Scanner::new(&rules_2);
+    ///
+    /// let outputs = scanner_1.scan(b"foo").unwrap().capture_module_outputs();
+    ///
+    /// // The `pe` module won't parse the data again.
+    /// scanner_2.use_module_outputs(&outputs);
+    /// assert_eq!(scanner_2.scan(b"foo").unwrap().matching_rules().len(), 1);
+    /// ```
+    pub fn use_module_outputs(
+        &mut self,
+        outputs: &CapturedModuleOutputs,
+    ) -> &mut Self {
+        let ctx = self.wasm_store.data_mut();
+        for output in &outputs.outputs {
+            ctx.user_provided_module_outputs.insert(
+                output.descriptor_dyn().full_name().to_string(),
+                output.clone_box(),
+            );
+        }
+        self
+    }
+
     /// Similar to [`Scanner::set_module_output`], but receives a module name
     /// and the protobuf message as raw data.
     ///
@@ -490,46 +1128,83 @@ impl<'r> Scanner<'r> {
         name: &str,
         data: &[u8],
     ) -> Result<(), ScanError> {
-        // Try to find the module by name first, if not found, then try
-        // to find a module where the fully-qualified name for its protobuf
-        // message matches the `name` arguments.
-        let descriptor = if let Some(module) = BUILTIN_MODULES.get(name) {
-            Some(&module.root_struct_descriptor)
-        } else {
-            BUILTIN_MODULES.values().find_map(|module| {
-                if module.root_struct_descriptor.full_name() == name {
-                    Some(&module.root_struct_descriptor)
-                } else {
-                    None
-                }
-            })
-        };
-
-        if descriptor.is_none() {
-            return Err(ScanError::UnknownModule { module: name.to_string() });
-        }
-
+        let descriptor = self.module_output_descriptor(name)?;
         let mut is = CodedInputStream::from_bytes(data);
 
         // Default recursion limit is 100, that's not enough for some deeply
         // nested structures like the process tree in the `vt` module.
         is.set_recursion_limit(500);
 
+        self.set_module_output(descriptor.parse_from(&mut is).map_err(
+            |err| ScanError::ProtoError { module: name.to_string(), err },
+        )?)
+    }
+
+    /// Similar to [`Scanner::set_module_output_raw`], but the protobuf
+    /// message is provided in JSON form, using the canonical [JSON mapping]
+    /// for protobuf messages.
+    ///
+    /// JSON makes it possible to write the output of a module by hand, which
+    /// is specially useful in test fixtures. Fields in the JSON that don't
+    /// exist in the protobuf message produce an error.
+    ///
+    /// [JSON mapping]: https://protobuf.dev/programming-guides/proto3/#json
+    pub fn set_module_output_json(
+        &mut self,
+        name: &str,
+        json: &str,
+    ) -> Result<(), ScanError> {
+        let descriptor = self.module_output_descriptor(name)?;
+
         self.set_module_output(
-            descriptor.unwrap().parse_from(&mut is).map_err(|err| {
-                ScanError::ProtoError { module: name.to_string(), err }
-            })?,
+            protobuf_json_mapping::parse_dyn_from_str(&descriptor, json)
+                .map_err(|err| ScanError::JsonError {
+                    module: name.to_string(),
+                    err,
+                })?,
         )
     }
+
+    /// Returns the descriptor for the protobuf message produced by a module.
+    ///
+    /// `name` can be either the YARA module name (i.e: "pe", "elf", "dotnet",
+    /// etc.) or the fully-qualified name for the protobuf message associated
+    /// to the module (i.e: "pe.PE", "elf.ELF", "dotnet.Dotnet", etc.).
+    fn module_output_descriptor(
+        &self,
+        name: &str,
+    ) -> Result<MessageDescriptor, ScanError> {
+        // Try to find the module by name first, if not found, then try
+        // to find a module where the fully-qualified name for its protobuf
+        // message matches the `name` arguments.
+        let rules = self.wasm_store.data().compiled_rules;
+        module_descriptor(rules, name)
+            .or_else(|| {
+                module_descriptors(rules).find_map(|(_, descriptor)| {
+                    if descriptor.full_name() == name {
+                        Some(descriptor)
+                    } else {
+                        None
+                    }
+                })
+            })
+            .cloned()
+            .ok_or_else(|| ScanError::UnknownModule {
+                module: name.to_string(),
+            })
+    }
 }
 
 impl<'r> Scanner<'r> {
-    fn load_file(path: &Path) -> Result<ScannedData<'static>, ScanError> {
+    fn load_file(
+        path: &Path,
+    ) -> Result<(ScannedData<'static>, FileMetadata), ScanError> {
         let mut file = fs::File::open(path).map_err(|err| {
             ScanError::OpenError { path: path.to_path_buf(), source: err }
         })?;
 
-        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let metadata = file.metadata().ok();
+        let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
 
         let mut buffered_file;
         let mapped_file;
@@ -549,14 +1224,38 @@ impl<'r> Scanner<'r> {
             ScannedData::Mmap(mapped_file)
         };
 
-        Ok(data)
+        let metadata =
+            metadata.as_ref().map(FileMetadata::from).unwrap_or_default();
+
+        Ok((data, metadata))
+    }
+
+    fn set_rule_enabled(
+        &mut self,
+        namespace: &str,
+        name: &str,
+        enabled: bool,
+    ) -> bool {
+        let ctx = self.wasm_store.data_mut();
+        match ctx.compiled_rules.find_rule(namespace, name) {
+            Some(rule_id) => {
+                ctx.set_rule_enabled(rule_id, enabled);
+                true
+            }
+            None => false,
+        }
     }
 
     fn scan_impl<'a, 'opts>(
         &'a mut self,
         data: ScannedData<'a>,
+        file_metadata: Option<FileMetadata>,
         options: Option<ScanOptions<'opts>>,
     ) -> Result<ScanResults<'a, 'r>, ScanError> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("scan", size = data.as_ref().len()).entered();
+
         // Clear information about matches found in a previous scan, if any.
         self.reset();
 
@@ -612,50 +1311,96 @@ impl<'r> Scanner<'r> {
             )
             .unwrap();
 
+        self.rules_evaluated
+            .set(self.wasm_store.as_context_mut(), Val::I64(0))
+            .unwrap();
+
         let ctx = self.wasm_store.data_mut();
 
+        ctx.scan_id = SCAN_ID.fetch_add(1, Ordering::Relaxed);
         ctx.deadline =
             HEARTBEAT_COUNTER.load(Ordering::Relaxed) + timeout_secs;
         ctx.scanned_data = data.as_ref().as_ptr();
         ctx.scanned_data_len = data.as_ref().len();
+        ctx.file_metadata = file_metadata;
+        ctx.max_memory =
+            options.as_ref().and_then(|options| options.max_memory);
+        ctx.allocated_memory = 0;
 
         // Free all runtime objects left around by previous scans.
         ctx.runtime_objects.clear();
 
-        for module_name in ctx.compiled_rules.imports() {
-            // Lookup the module in the list of built-in modules.
-            let module =
-                modules::BUILTIN_MODULES.get(module_name).unwrap_or_else(
-                    || panic!("module `{}` not found", module_name),
-                );
+        let compiled_rules = ctx.compiled_rules;
+
+        for module_name in compiled_rules.imports() {
+            // Lookup the module in the list of built-in modules, if not found
+            // it must be one of the module plugins included in the rules.
+            let module = modules::BUILTIN_MODULES.get(module_name);
+            let plugin = compiled_rules.module_plugin(module_name);
+
+            let root_struct_descriptor = match (module, plugin) {
+                (Some(module), _) => &module.root_struct_descriptor,
+                (None, Some(plugin)) => plugin.root_struct_descriptor(),
+                (None, None) => panic!("module `{}` not found", module_name),
+            };
+
+            let root_struct_name = root_struct_descriptor.full_name();
+
+            ctx.report_progress(ScanPhase::ModuleParsing, 0);
 
-            let root_struct_name = module.root_struct_descriptor.full_name();
+            #[cfg(feature = "tracing")]
+            let _span =
+                tracing::info_span!("module_parsing", module = module_name)
+                    .entered();
+
+            let parse_start = Instant::now();
 
             // If the user already provided some output for the module by
             // calling `Scanner::set_module_output`, use that output. If not,
             // call the module's main function (if the module has a main
-            // function) for getting its output.
+            // function) or run the plugin for getting its output.
             let module_output = if let Some(output) =
                 ctx.user_provided_module_outputs.remove(root_struct_name)
             {
                 Some(output)
-            } else {
+            } else if let Some(module) = module {
                 let meta = options.as_ref().and_then(|options| {
                     options.module_metadata.get(module_name).copied()
                 });
 
-                module.main_fn.map(|main_fn| main_fn(data.as_ref(), meta))
+                // If the data is not in the format expected by the module,
+                // the module's main function receives an empty slice, which
+                // produces the same output without the cost of parsing.
+                let parse = module
+                    .sniff_fn
+                    .map_or(true, |sniff_fn| sniff_fn(data.as_ref()));
+
+                let stats = ctx.module_stats.entry(module_name).or_default();
+
+                if parse {
+                    stats.parsed += 1;
+                } else {
+                    stats.skipped += 1;
+                }
+
+                let module_data = if parse { data.as_ref() } else { &[] };
+
+                module.main_fn.map(|main_fn| main_fn(module_data, meta))
+            } else {
+                plugin.and_then(|plugin| plugin.invoke(data.as_ref()))
             };
 
+            ctx.module_parse_times.push((module_name, parse_start.elapsed()));
+
             if let Some(module_output) = &module_output {
                 // Make sure that the module is returning a protobuf message of
                 // the expected type.
                 debug_assert_eq!(
                     module_output.descriptor_dyn().full_name(),
-                    module.root_struct_descriptor.full_name(),
+                    root_struct_name,
                     "main function of module `{}` must return `{}`, but returned `{}`",
                     module_name,
-                    module.root_struct_descriptor.full_name(),
+                    root_struct_name,
                     module_output.descriptor_dyn().full_name(),
                 );
 
@@ -667,8 +1412,23 @@ impl<'r> Scanner<'r> {
                     module_output.is_initialized_dyn(),
                     "module `{}` returned a protobuf `{}` where some required fields are not initialized ",
                     module_name,
-                    module.root_struct_descriptor.full_name()
+                    root_struct_name
                 );
+
+                // Computing the size of the module output is not free, do it
+                // only if there's some memory limit to enforce. The limit is
+                // checked before building the structure from the output and
+                // before running other modules, so that the scan is aborted
+                // as soon as possible.
+                if ctx.max_memory.is_some() {
+                    ctx.allocated_memory +=
+                        module_output.compute_size_dyn() as usize;
+                    if let Err(err) = ctx.check_memory_limit() {
+                        ctx.scanned_data = null();
+                        ctx.scanned_data_len = 0;
+                        return Err(err);
+                    }
+                }
             }
 
             // When constant folding is enabled we don't need to generate
@@ -686,7 +1446,7 @@ impl<'r> Scanner<'r> {
                 !cfg!(feature = "constant-folding");
 
             let module_struct = Struct::from_proto_descriptor_and_msg(
-                &module.root_struct_descriptor,
+                root_struct_descriptor,
                 module_output.as_deref(),
                 generate_fields_for_enums,
             );
@@ -705,20 +1465,37 @@ impl<'r> Scanner<'r> {
             );
         }
 
+        // Outputs provided for modules that are not imported by the rules
+        // are not used, but they are consumed anyway.
+        ctx.user_provided_module_outputs.clear();
+
         // Invoke the main function, which evaluates the rules' conditions. It
         // calls ScanContext::search_for_patterns (which does the Aho-Corasick
         // scanning) only if necessary.
         //
         // This will return Err(ScanError::Timeout), when the scan timeout is
         // reached while WASM code is being executed. If the timeout occurs
-        // while ScanContext::search_for_patterns is being executed, the result
-        // will be Ok(1). If the scan completes successfully the result is
-        // Ok(0).`
-        let func_result =
-            self.wasm_main_func.call(self.wasm_store.as_context_mut(), ());
+        // while ScanContext::search_for_patterns is being executed, or the
+        // pattern search is aborted for some other reason, the result will
+        // be Ok(1) and the error will be stored in `search_error`. If the
+        // scan completes successfully the result is Ok(0).
+        self.wasm_store
+            .data_mut()
+            .report_progress(ScanPhase::ConditionEvaluation, 0);
+
+        let func_result = {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("condition_evaluation").entered();
+            self.wasm_main_func.call(self.wasm_store.as_context_mut(), ())
+        };
+
+        let rules_evaluated =
+            self.rules_evaluated.get(self.wasm_store.as_context_mut());
 
         let ctx = self.wasm_store.data_mut();
 
+        ctx.rules_evaluated = rules_evaluated.unwrap_i64() as usize;
+
         // Set pointer to data back to nil. This means that accessing
         // `scanned_data` from within `ScanResults` is not possible.
         ctx.scanned_data = null();
@@ -747,9 +1524,26 @@ impl<'r> Scanner<'r> {
             }
         }
 
+        // Sort the matching rules by `RuleId`, which puts them in the order
+        // in which they were declared. The keys in the `matching_rules` map
+        // are kept from one scan to the next one, so the order in which the
+        // map yields the namespaces depends on previous scans.
+        ctx.private_matching_rules.sort_unstable();
+        ctx.non_private_matching_rules.sort_unstable();
+
         match func_result {
-            Ok(0) => Ok(ScanResults::new(self.wasm_store.data(), data)),
-            Ok(1) => Err(ScanError::Timeout),
+            Ok(0) => {
+                // Strings created while evaluating conditions may have taken
+                // the memory usage above the limit.
+                self.wasm_store.data().check_memory_limit()?;
+                Ok(ScanResults::new(self.wasm_store.data(), data))
+            }
+            Ok(1) => Err(self
+                .wasm_store
+                .data_mut()
+                .search_error
+                .take()
+                .unwrap_or(ScanError::Timeout)),
             Ok(_) => unreachable!(),
             Err(err) if err.is::<ScanError>() => {
                 Err(err.downcast::<ScanError>().unwrap())
@@ -769,13 +1563,26 @@ impl<'r> Scanner<'r> {
         let num_rules = ctx.compiled_rules.num_rules();
         let num_patterns = ctx.compiled_rules.num_patterns();
 
-        // Clear the array that tracks the patterns that reached the maximum
-        // number of patterns.
-        ctx.limit_reached.clear();
+        // Clear the map that tracks the patterns that reached the maximum
+        // number of matches.
+        ctx.suppressed_matches.clear();
 
         // Clear the unconfirmed matches.
         ctx.unconfirmed_matches.clear();
 
+        // Reset the values recorded for traced sub-expressions.
+        ctx.condition_traces.fill(TraceCounters::default());
+
+        // Forget the rules that timed out in the previous scan.
+        ctx.timed_out_rules.clear();
+        ctx.rule_eval_start = None;
+
+        // Reset the statistics about the previous scan.
+        ctx.atoms_fired = 0;
+        ctx.regexp_executions = 0;
+        ctx.rules_evaluated = 0;
+        ctx.module_parse_times.clear();
+
         // If some pattern or rule matched, clear the matches. Notice that a
         // rule may match without any pattern being matched, because there
         // are rules without patterns, or that match if the pattern is not
@@ -812,7 +1619,10 @@ impl<'r> Scanner<'r> {
 
 /// Results of a scan operation.
 ///
-/// Allows iterating over both the matching and non-matching rules.
+/// Allows iterating over both the matching and non-matching rules. Rules
+/// are always yielded in the order in which they were added to the compiler,
+/// and the matches of each pattern are sorted by offset, so the results
+/// of scanning the same data with the same rules are always the same.
 pub struct ScanResults<'a, 'r> {
     ctx: &'a ScanContext<'r>,
     data: ScannedData<'a>,
@@ -823,17 +1633,48 @@ impl<'a, 'r> ScanResults<'a, 'r> {
         Self { ctx, data }
     }
 
-    /// Returns an iterator that yields the matching rules in arbitrary order.
+    /// Returns an iterator that yields the matching rules in the order in
+    /// which they were declared.
     pub fn matching_rules(&'a self) -> MatchingRules<'a, 'r> {
         MatchingRules::new(self.ctx, &self.data)
     }
 
-    /// Returns an iterator that yields the non-matching rules in arbitrary
-    /// order.
+    /// Returns an iterator that yields the non-matching rules in the order
+    /// in which they were declared.
     pub fn non_matching_rules(&'a self) -> NonMatchingRules<'a, 'r> {
         NonMatchingRules::new(self.ctx, &self.data)
     }
 
+    /// Returns an iterator that yields the rules that were aborted because
+    /// their evaluation took longer than the limit set with
+    /// [`Scanner::set_rule_timeout`], in the order in which they were
+    /// declared. These rules are also returned by
+    /// [`ScanResults::non_matching_rules`], unless they are private.
+    pub fn timed_out_rules(&'a self) -> TimedOutRules<'a, 'r> {
+        TimedOutRules::new(self.ctx, &self.data)
+    }
+
+    /// Returns statistics about the scan.
+    ///
+    /// Statistics from multiple scans can be aggregated with
+    /// [`ScanStats::merge`].
+    pub fn stats(&self) -> ScanStats {
+        ScanStats {
+            num_scans: 1,
+            bytes_scanned: self.data.as_ref().len(),
+            atoms_fired: self.ctx.atoms_fired,
+            regexp_executions: self.ctx.regexp_executions,
+            rules_evaluated: self.ctx.rules_evaluated,
+            rule_timeouts: self.ctx.timed_out_rules.len(),
+            module_parse_times: self
+                .ctx
+                .module_parse_times
+                .iter()
+                .map(|(module, time)| (module.to_string(), *time))
+                .collect(),
+        }
+    }
+
     /// Returns the protobuf produced by a YARA module after processing the
     /// data.
     ///
@@ -843,12 +1684,10 @@ impl<'a, 'r> ScanResults<'a, 'r> {
         &self,
         module_name: &str,
     ) -> Option<&'a dyn MessageDyn> {
-        let module = BUILTIN_MODULES.get(module_name)?;
-        let module_output = self
-            .ctx
-            .module_outputs
-            .get(module.root_struct_descriptor.full_name())?
-            .as_ref();
+        let descriptor =
+            module_descriptor(self.ctx.compiled_rules, module_name)?;
+        let module_output =
+            self.ctx.module_outputs.get(descriptor.full_name())?.as_ref();
         Some(module_output)
     }
 
@@ -859,6 +1698,20 @@ impl<'a, 'r> ScanResults<'a, 'r> {
     pub fn module_outputs(&self) -> ModuleOutputs<'a, 'r> {
         ModuleOutputs::new(self.ctx)
     }
+
+    /// Returns a copy of the outputs produced by YARA modules during the
+    /// scan, which can be passed to [`Scanner::use_module_outputs`] for
+    /// reusing them in subsequent scans of the same data.
+    pub fn capture_module_outputs(&self) -> CapturedModuleOutputs {
+        CapturedModuleOutputs {
+            outputs: self
+                .ctx
+                .module_outputs
+                .values()
+                .map(|output| output.clone_box())
+                .collect(),
+        }
+    }
 }
 
 /// Iterator that yields the rules that matched during a scan.
@@ -897,6 +1750,42 @@ impl<'a, 'r> ExactSizeIterator for MatchingRules<'a, 'r> {
     }
 }
 
+/// Iterator that yields the rules that timed out during a scan.
+pub struct TimedOutRules<'a, 'r> {
+    ctx: &'a ScanContext<'r>,
+    data: &'a ScannedData<'a>,
+    iterator: Iter<'a, RuleId>,
+}
+
+impl<'a, 'r> TimedOutRules<'a, 'r> {
+    fn new(ctx: &'a ScanContext<'r>, data: &'a ScannedData<'a>) -> Self {
+        Self { ctx, data, iterator: ctx.timed_out_rules.iter() }
+    }
+}
+
+impl<'a, 'r> Iterator for TimedOutRules<'a, 'r> {
+    type Item = Rule<'a, 'r>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rule_id = *self.iterator.next()?;
+        let rules = self.ctx.compiled_rules;
+        let rule_info = rules.get(rule_id);
+        Some(Rule {
+            ctx: Some(self.ctx),
+            data: Some(self.data),
+            rule_info,
+            rules,
+        })
+    }
+}
+
+impl<'a, 'r> ExactSizeIterator for TimedOutRules<'a, 'r> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iterator.len()
+    }
+}
+
 /// Iterator that yields the rules that didn't match during a scan.
 pub struct NonMatchingRules<'a, 'r> {
     ctx: &'a ScanContext<'r>,
@@ -929,10 +1818,10 @@ impl<'a, 'r> NonMatchingRules<'a, 'r> {
             ctx,
             data,
             iterator: matching_rules_bitmap.iter_zeros(),
-            // The number of non-matching rules is the total number of rules
-            // minus the number of matching rules, both private and non-private.
-            len: ctx.compiled_rules.num_rules()
-                - ctx.private_matching_rules.len()
+            // The number of non-matching rules is the number of non-private
+            // rules minus the number of non-private matching rules. Private
+            // rules are never returned by this iterator.
+            len: ctx.compiled_rules.num_non_private_rules()
                 - ctx.non_private_matching_rules.len(),
         }
     }
@@ -943,13 +1832,13 @@ impl<'a, 'r> Iterator for NonMatchingRules<'a, 'r> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            self.len = self.len.saturating_sub(1);
             let rule_id = RuleId::from(self.iterator.next()?);
             let rules = self.ctx.compiled_rules;
             let rule_info = rules.get(rule_id);
             // Private rules are not returned, if the current rule is private
             // keep in the loop and try with the next one.
             if !rule_info.is_private {
+                self.len -= 1;
                 return Some(Rule {
                     ctx: Some(self.ctx),
                     data: Some(self.data),
@@ -968,11 +1857,46 @@ impl<'a, 'r> ExactSizeIterator for NonMatchingRules<'a, 'r> {
     }
 }
 
+/// Outputs produced by YARA modules during a scan.
+///
+/// See [`ScanResults::capture_module_outputs`] and
+/// [`Scanner::use_module_outputs`].
+#[derive(Debug, Default)]
+pub struct CapturedModuleOutputs {
+    outputs: Vec<Box<dyn MessageDyn>>,
+}
+
+impl CapturedModuleOutputs {
+    /// Returns the number of module outputs.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Returns true if no module produced any output.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
+
+impl Clone for CapturedModuleOutputs {
+    fn clone(&self) -> Self {
+        Self {
+            outputs: self
+                .outputs
+                .iter()
+                .map(|output| output.clone_box())
+                .collect(),
+        }
+    }
+}
+
 /// Iterator that returns the outputs produced by YARA modules.
 pub struct ModuleOutputs<'a, 'r> {
     ctx: &'a ScanContext<'r>,
     len: usize,
-    iterator: hash_map::Iter<'a, &'a str, Module>,
+    iterator: Box<dyn Iterator<Item = (&'a str, &'a MessageDescriptor)> + 'a>,
 }
 
 impl<'a, 'r> ModuleOutputs<'a, 'r> {
@@ -980,7 +1904,7 @@ impl<'a, 'r> ModuleOutputs<'a, 'r> {
         Self {
             ctx,
             len: ctx.module_outputs.len(),
-            iterator: BUILTIN_MODULES.iter(),
+            iterator: Box::new(module_descriptors(ctx.compiled_rules)),
         }
     }
 }
@@ -997,14 +1921,42 @@ impl<'a, 'r> Iterator for ModuleOutputs<'a, 'r> {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let (name, module) = self.iterator.next()?;
-            if let Some(module_output) = self
-                .ctx
-                .module_outputs
-                .get(module.root_struct_descriptor.full_name())
+            let (name, descriptor) = self.iterator.next()?;
+            if let Some(module_output) =
+                self.ctx.module_outputs.get(descriptor.full_name())
             {
-                return Some((*name, module_output.as_ref()));
+                return Some((name, module_output.as_ref()));
             }
         }
     }
 }
+
+/// Returns an iterator that yields tuples composed of a module name and the
+/// [`MessageDescriptor`] for the module's root structure. This includes both
+/// the built-in modules and the module plugins included in `rules`.
+fn module_descriptors(
+    rules: &Rules,
+) -> impl Iterator<Item = (&str, &MessageDescriptor)> {
+    BUILTIN_MODULES
+        .iter()
+        .map(|(name, module)| (*name, &module.root_struct_descriptor))
+        .chain(
+            rules.module_plugins().map(|plugin| {
+                (plugin.name(), plugin.root_struct_descriptor())
+            }),
+        )
+}
+
+/// Returns the [`MessageDescriptor`] for the root structure of the module
+/// with the given name, which can be either a built-in module or a module
+/// plugin included in `rules`.
+fn module_descriptor<'a>(
+    rules: &'a Rules,
+    name: &str,
+) -> Option<&'a MessageDescriptor> {
+    if let Some(module) = BUILTIN_MODULES.get(name) {
+        Some(&module.root_struct_descriptor)
+    } else {
+        rules.module_plugin(name).map(|plugin| plugin.root_struct_descriptor())
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+use crate::compiler::Rules;
+use crate::scanner::{ScanProgress, Scanner};
+
+/// Function invoked for configuring the scanners created by a pool.
+type InitFn<'r> = dyn Fn(&mut Scanner<'r>) + Send + Sync + 'r;
+
+/// Callback shared by all the scanners in a pool for `console.log`.
+type ConsoleLogFn<'r> = dyn Fn(String) + Send + Sync + 'r;
+
+/// Callback shared by all the scanners in a pool for reporting progress.
+type ProgressFn<'r> = dyn Fn(ScanProgress) + Send + Sync + 'r;
+
+// The pool must be shareable among threads. This fails to compile if some
+// change makes it otherwise.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ScannerPool<'static>>();
+};
+
+/// A pool of reusable scanners that can be shared among multiple threads.
+///
+/// Creating a [`Scanner`] is relatively expensive, as it involves
+/// instantiating the WASM module that evaluates the rule conditions. In
+/// applications that scan many small files concurrently, like servers
+/// handling scan requests, it's better to create scanners once and reuse
+/// them. `ScannerPool` does exactly that: each call to [`ScannerPool::get`]
+/// returns a scanner that is used exclusively by the caller, and that is
+/// put back into the pool when the returned [`PooledScanner`] is dropped.
+/// New scanners are created only when all the existing ones are in use.
+///
+/// Scanners returned to the pool retain their configuration (e.g. timeout,
+/// values for global variables, etc). If some setting is changed for a
+/// particular scan, it should be restored before the scanner goes back to
+/// the pool, or use [`ScannerPool::with_init`] for configuring all the
+/// scanners in the same way when they are created.
+///
+/// The only exception are the callbacks set with [`Scanner::console_log`]
+/// and [`Scanner::progress_callback`], which are removed from the scanner
+/// when it goes back to the pool, as they are not required to be [`Send`].
+/// Callbacks that must be used by all the scanners in the pool are set
+/// with [`ScannerPool::console_log`] and [`ScannerPool::progress_callback`].
+///
+/// # Example
+///
+/// ```rust
+/// # use yara_x::ScannerPool;
+/// let rules = yara_x::compile(r#"rule test { strings: $a = "foo" condition: $a }"#).unwrap();
+/// let pool = ScannerPool::new(&rules);
+///
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| {
+///             let mut scanner = pool.get();
+///             let results = scanner.scan(b"foo").unwrap();
+///             assert_eq!(results.matching_rules().len(), 1);
+///         });
+///     }
+/// });
+/// ```
+pub struct ScannerPool<'r> {
+    rules: &'r Rules,
+    init: Option<Box<InitFn<'r>>>,
+    console_log: Option<Arc<ConsoleLogFn<'r>>>,
+    progress_callback: Option<Arc<ProgressFn<'r>>>,
+    scanners: Mutex<Vec<IdleScanner<'r>>>,
+}
+
+impl<'r> ScannerPool<'r> {
+    /// Creates a new, empty, pool of scanners for the given rules.
+    pub fn new(rules: &'r Rules) -> Self {
+        Self {
+            rules,
+            init: None,
+            console_log: None,
+            progress_callback: None,
+            scanners: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Creates a new, empty, pool of scanners for the given rules, where
+    /// `init` is invoked for configuring each scanner after creating it.
+    pub fn with_init<F>(rules: &'r Rules, init: F) -> Self
+    where
+        F: Fn(&mut Scanner<'r>) + Send + Sync + 'r,
+    {
+        Self {
+            rules,
+            init: Some(Box::new(init)),
+            console_log: None,
+            progress_callback: None,
+            scanners: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Sets a callback that is invoked every time a YARA rule calls the
+    /// `console` module, in any of the scanners in the pool.
+    ///
+    /// This is equivalent to [`Scanner::console_log`], but the callback
+    /// is shared by multiple threads, so it must be [`Send`] and [`Sync`].
+    pub fn console_log<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(String) + Send + Sync + 'r,
+    {
+        self.console_log = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets a callback that is invoked periodically for reporting the
+    /// progress of the scans performed by any of the scanners in the pool.
+    ///
+    /// This is equivalent to [`Scanner::progress_callback`], but the
+    /// callback is shared by multiple threads, so it must be [`Send`] and
+    /// [`Sync`].
+    pub fn progress_callback<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(ScanProgress) + Send + Sync + 'r,
+    {
+        self.progress_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Returns the rules used by the scanners in this pool.
+    pub fn rules(&self) -> &'r Rules {
+        self.rules
+    }
+
+    /// Returns the number of idle scanners in the pool.
+    pub fn idle(&self) -> usize {
+        self.scanners.lock().unwrap().len()
+    }
+
+    /// Takes a scanner from the pool, or creates a new one if all the
+    /// scanners in the pool are in use.
+    ///
+    /// The scanner goes back to the pool when the returned [`PooledScanner`]
+    /// is dropped.
+    pub fn get(&self) -> PooledScanner<'_, 'r> {
+        let scanner = self.scanners.lock().unwrap().pop();
+        let mut scanner = match scanner {
+            Some(IdleScanner(scanner)) => scanner,
+            None => {
+                let mut scanner = Scanner::new(self.rules);
+                if let Some(init) = &self.init {
+                    init(&mut scanner);
+                }
+                scanner
+            }
+        };
+        if let Some(console_log) = &self.console_log {
+            let console_log = console_log.clone();
+            scanner.console_log(move |msg| console_log(msg));
+        }
+        if let Some(progress_callback) = &self.progress_callback {
+            let progress_callback = progress_callback.clone();
+            scanner.progress_callback(move |progress| {
+                progress_callback(progress)
+            });
+        }
+        PooledScanner { pool: self, scanner: Some(scanner) }
+    }
+}
+
+impl fmt::Debug for ScannerPool<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScannerPool").field("idle", &self.idle()).finish()
+    }
+}
+
+/// A scanner that is waiting in a [`ScannerPool`] for being used.
+struct IdleScanner<'r>(Scanner<'r>);
+
+// SAFETY: `Scanner` is not `Send` because its `ScanContext` contains raw
+// pointers, `Rc`s and the user-provided callbacks, which are not required
+// to be `Send`. Idle scanners can be moved to another thread because:
+//
+// * The raw pointers point either to the scanner's own WASM store, which
+//   is pinned and moves together with the scanner, or to the scanned data,
+//   which is reset at the end of each scan.
+// * The `Rc`s are created by the scanner itself, and no clone of them
+//   outlives the scan, as `ScanResults` borrow the scanner.
+// * The callbacks are removed before the scanner goes back to the pool
+//   (see `PooledScanner::drop`).
+//
+// `PooledScanner` is not `Send`, so scanners taken from the pool remain
+// in the same thread until they return to the pool.
+unsafe impl Send for IdleScanner<'_> {}
+
+/// A [`Scanner`] borrowed from a [`ScannerPool`].
+///
+/// This type dereferences to [`Scanner`], and puts the scanner back into
+/// the pool when dropped.
+pub struct PooledScanner<'p, 'r> {
+    pool: &'p ScannerPool<'r>,
+    scanner: Option<Scanner<'r>>,
+}
+
+impl<'r> Deref for PooledScanner<'_, 'r> {
+    type Target = Scanner<'r>;
+
+    fn deref(&self) -> &Self::Target {
+        self.scanner.as_ref().unwrap()
+    }
+}
+
+impl<'r> DerefMut for PooledScanner<'_, 'r> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.scanner.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledScanner<'_, '_> {
+    fn drop(&mut self) {
+        if let Some(mut scanner) = self.scanner.take() {
+            // The callbacks may not be `Send`, they are dropped here, in the
+            // thread where they were set, before the scanner goes back to
+            // the pool.
+            let ctx = scanner.wasm_store.data_mut();
+            ctx.console_log = None;
+            ctx.progress_callback = None;
+            // If the mutex was poisoned the scanner is simply dropped.
+            if let Ok(mut scanners) = self.pool.scanners.lock() {
+                scanners.push(IdleScanner(scanner));
+            }
+        }
+    }
+}
@@ -0,0 +1,427 @@
+/*! Unit testing of rules.
+
+Rules can be accompanied by a test manifest that describes which rules are
+expected to match, or not to match, some data. This allows having regression
+tests for rules, ensuring that a change in a rule doesn't break its detection
+capabilities or introduces false positives.
+
+Manifests are JSON documents like this one:
+
+```json
+{
+  "tests": [
+    {
+      "name": "detects the sample",
+      "file": "samples/malware.bin",
+      "matches": ["malware_family"],
+      "not_matches": ["goodware"]
+    },
+    {
+      "hex": "4D 5A 90 00",
+      "matches": ["is_mz"]
+    }
+  ]
+}
+```
+
+Each test scans either the file indicated by `file`, which is relative to the
+directory where the manifest is located, or the data encoded in `hex`. Rules
+are referred to by their identifiers, optionally qualified with the namespace
+(e.g: `my_namespace.malware_family`).
+
+When a rule doesn't produce the expected result, the failure includes the
+sub-expressions of the rule's condition that caused the divergence, provided
+that the rules were compiled with [`crate::Compiler::condition_tracing`].
+*/
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::models::{Rule, TracedExpr};
+use crate::scanner::{ScanError, Scanner};
+use crate::Rules;
+
+/// Error returned while loading or running tests.
+#[derive(Error, Debug)]
+pub enum TestError {
+    /// Could not read the test manifest.
+    #[error("can not read `{path}`: {source}")]
+    ReadError {
+        /// Path of the manifest.
+        path: PathBuf,
+        /// Error that occurred.
+        source: std::io::Error,
+    },
+    /// The test manifest is not valid.
+    #[error("invalid test manifest: {0}")]
+    InvalidManifest(#[from] serde_json::Error),
+    /// A test in the manifest is not valid.
+    #[error("invalid test `{test}`: {reason}")]
+    InvalidTest {
+        /// Name of the test.
+        test: String,
+        /// Reason why the test is not valid.
+        reason: String,
+    },
+    /// A test refers to a rule that doesn't exist.
+    #[error("unknown rule `{rule}` in test `{test}`")]
+    UnknownRule {
+        /// Name of the test.
+        test: String,
+        /// Name of the rule.
+        rule: String,
+    },
+    /// Error while scanning the data for a test.
+    #[error("error in test `{test}`: {source}")]
+    ScanError {
+        /// Name of the test.
+        test: String,
+        /// Error that occurred.
+        source: ScanError,
+    },
+}
+
+/// A set of tests for some rules.
+///
+/// See the [module documentation][`crate::testing`] for a description of the
+/// manifest format.
+///
+/// # Example
+///
+/// ```rust
+/// use yara_x::testing::TestManifest;
+///
+/// let mut compiler = yara_x::Compiler::new();
+///
+/// compiler
+///     .condition_tracing(true)
+///     .add_source(r#"rule test { strings: $a = "foo" condition: $a }"#)
+///     .unwrap();
+///
+/// let rules = compiler.build();
+///
+/// let manifest = TestManifest::from_json(
+///     br#"{ "tests": [ { "hex": "66 6f 6f", "matches": ["test"] } ] }"#,
+/// )
+/// .unwrap();
+///
+/// let report = manifest.run(&rules).unwrap();
+///
+/// assert!(report.passed());
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestManifest {
+    tests: Vec<TestCase>,
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestCase {
+    name: Option<String>,
+    file: Option<PathBuf>,
+    hex: Option<String>,
+    #[serde(default)]
+    matches: Vec<String>,
+    #[serde(default)]
+    not_matches: Vec<String>,
+}
+
+impl TestManifest {
+    /// Creates a [`TestManifest`] from its JSON representation.
+    ///
+    /// Paths to the files scanned by the tests are relative to the current
+    /// directory, use [`TestManifest::base_dir`] for changing it.
+    pub fn from_json(json: &[u8]) -> Result<Self, TestError> {
+        Ok(serde_json::from_slice(json)?)
+    }
+
+    /// Reads a [`TestManifest`] from a JSON file.
+    ///
+    /// Paths to the files scanned by the tests are relative to the directory
+    /// where the manifest is located.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, TestError> {
+        let path = path.as_ref();
+
+        let json = fs::read(path).map_err(|err| TestError::ReadError {
+            path: path.to_path_buf(),
+            source: err,
+        })?;
+
+        let base_dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        Ok(Self::from_json(json.as_slice())?.base_dir(base_dir))
+    }
+
+    /// Sets the directory that paths to scanned files are relative to.
+    pub fn base_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.base_dir = dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Returns the number of tests in the manifest.
+    pub fn len(&self) -> usize {
+        self.tests.len()
+    }
+
+    /// Returns true if the manifest doesn't contain any test.
+    pub fn is_empty(&self) -> bool {
+        self.tests.is_empty()
+    }
+
+    /// Runs the tests with the given rules.
+    ///
+    /// Returns an error if some test is not valid, like tests that refer to
+    /// rules that don't exist in `rules`. Tests that run successfully but
+    /// where some rule doesn't produce the expected result are reported as
+    /// failures in the returned [`TestReport`].
+    pub fn run(&self, rules: &Rules) -> Result<TestReport, TestError> {
+        let mut scanner = Scanner::new(rules);
+        let mut report = TestReport { num_checks: 0, failures: Vec::new() };
+
+        for (i, test) in self.tests.iter().enumerate() {
+            let name = test.name(i);
+
+            let expected = test
+                .matches
+                .iter()
+                .map(|rule| (rule, true))
+                .chain(test.not_matches.iter().map(|rule| (rule, false)));
+
+            for (rule, _) in expected.clone() {
+                if !rules.iter().any(|r| rule_has_name(&r, rule)) {
+                    return Err(TestError::UnknownRule {
+                        test: name,
+                        rule: rule.clone(),
+                    });
+                }
+            }
+
+            let data = match &test.hex {
+                Some(hex) => Some(parse_hex(hex).ok_or_else(|| {
+                    TestError::InvalidTest {
+                        test: name.clone(),
+                        reason: "`hex` is not a valid hex string".to_string(),
+                    }
+                })?),
+                None => None,
+            };
+
+            let scan_results = match (&test.file, &data) {
+                (Some(file), None) => {
+                    scanner.scan_file(self.base_dir.join(file))
+                }
+                (None, Some(data)) => scanner.scan(data.as_slice()),
+                _ => {
+                    return Err(TestError::InvalidTest {
+                        test: name,
+                        reason: "either `file` or `hex` must be specified"
+                            .to_string(),
+                    })
+                }
+            }
+            .map_err(|err| TestError::ScanError {
+                test: name.clone(),
+                source: err,
+            })?;
+
+            for (rule_name, expected_match) in expected {
+                report.num_checks += 1;
+
+                let matching = scan_results
+                    .matching_rules()
+                    .find(|r| rule_has_name(r, rule_name));
+
+                let rule = match (matching, expected_match) {
+                    (Some(_), true) => continue,
+                    (Some(rule), false) => rule,
+                    (None, false) => continue,
+                    (None, true) => scan_results
+                        .non_matching_rules()
+                        .find(|r| rule_has_name(r, rule_name))
+                        .unwrap(),
+                };
+
+                report.failures.push(TestFailure {
+                    test: name.clone(),
+                    namespace: rule.namespace().to_string(),
+                    rule: rule.identifier().to_string(),
+                    expected_match,
+                    diverged: diverged_exprs(&rule, expected_match),
+                });
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl TestCase {
+    /// Returns the name of the test. Tests without an explicit name are
+    /// named after the scanned file, or after their position in the
+    /// manifest.
+    fn name(&self, index: usize) -> String {
+        if let Some(name) = &self.name {
+            name.clone()
+        } else if let Some(file) = &self.file {
+            file.display().to_string()
+        } else {
+            format!("#{}", index + 1)
+        }
+    }
+}
+
+/// Results of running the tests in a [`TestManifest`].
+#[derive(Debug)]
+pub struct TestReport {
+    num_checks: usize,
+    failures: Vec<TestFailure>,
+}
+
+impl TestReport {
+    /// Returns true if all the rules produced the expected results.
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Returns the total number of rules that were checked, adding up all
+    /// the tests in the manifest.
+    pub fn num_checks(&self) -> usize {
+        self.num_checks
+    }
+
+    /// Returns the checks that failed.
+    pub fn failures(&self) -> impl ExactSizeIterator<Item = &TestFailure> {
+        self.failures.iter()
+    }
+}
+
+/// A rule that didn't produce the expected result in some test.
+#[derive(Debug)]
+pub struct TestFailure {
+    test: String,
+    namespace: String,
+    rule: String,
+    expected_match: bool,
+    diverged: Vec<DivergedExpr>,
+}
+
+impl TestFailure {
+    /// Name of the test where the failure occurred.
+    pub fn test(&self) -> &str {
+        self.test.as_str()
+    }
+
+    /// Namespace of the rule that failed.
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_str()
+    }
+
+    /// Identifier of the rule that failed.
+    pub fn rule(&self) -> &str {
+        self.rule.as_str()
+    }
+
+    /// Returns true if the rule was expected to match, but it didn't. Returns
+    /// false if the rule was expected to not match, but it did.
+    pub fn expected_match(&self) -> bool {
+        self.expected_match
+    }
+
+    /// Returns the sub-expressions of the rule's condition that caused the
+    /// rule to produce an unexpected result.
+    ///
+    /// These are the innermost sub-expressions that never evaluated to
+    /// `true` when the rule was expected to match, or that evaluated to
+    /// `true` when the rule was not expected to match. This is empty if the
+    /// rules were not compiled with [`crate::Compiler::condition_tracing`].
+    pub fn diverged(&self) -> impl ExactSizeIterator<Item = &DivergedExpr> {
+        self.diverged.iter()
+    }
+}
+
+/// A sub-expression of a rule's condition that caused a test failure.
+///
+/// See [`TestFailure::diverged`].
+#[derive(Debug)]
+pub struct DivergedExpr {
+    source: String,
+    span: Range<usize>,
+}
+
+impl DivergedExpr {
+    /// Returns the source code of the expression.
+    pub fn source(&self) -> &str {
+        self.source.as_str()
+    }
+
+    /// Returns the range of the expression within the source code where
+    /// the rule was declared.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+/// Returns true if `rule` is identified by `name`, which can be either the
+/// rule's identifier or the identifier qualified with the namespace (e.g:
+/// `namespace.identifier`).
+fn rule_has_name(rule: &Rule, name: &str) -> bool {
+    match name.split_once('.') {
+        Some((namespace, identifier)) => {
+            rule.namespace() == namespace && rule.identifier() == identifier
+        }
+        None => rule.identifier() == name,
+    }
+}
+
+/// Returns the innermost sub-expressions in the condition of `rule` that
+/// don't have the value expected for the rule's result.
+fn diverged_exprs(rule: &Rule, expected_match: bool) -> Vec<DivergedExpr> {
+    let diverged: Vec<TracedExpr> = rule
+        .condition_trace()
+        .filter(|expr| {
+            expr.evaluated() && (expr.times_true() > 0) != expected_match
+        })
+        .collect();
+
+    let contains = |outer: &Range<usize>, inner: &Range<usize>| {
+        outer != inner && outer.start <= inner.start && inner.end <= outer.end
+    };
+
+    let mut result: Vec<DivergedExpr> = Vec::new();
+
+    for expr in diverged.iter() {
+        let span = expr.span();
+        if diverged.iter().any(|inner| contains(&span, &inner.span())) {
+            continue;
+        }
+        if result.iter().any(|e| e.span == span) {
+            continue;
+        }
+        result.push(DivergedExpr { source: expr.source().to_string(), span });
+    }
+
+    result
+}
+
+/// Parses a string with hex digits, where whitespaces are ignored. Returns
+/// `None` if the string is not valid.
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+
+    Some(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+}
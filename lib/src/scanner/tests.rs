@@ -1,12 +1,16 @@
+use std::time::Duration;
+
 use pretty_assertions::assert_eq;
 use protobuf::MessageDyn;
 use protobuf::{Message, MessageFull};
 use serde_json::json;
 
-use crate::models::MetaValue;
+use crate::aggregate::AggregatedResults;
+use crate::models::{MatchVariant, MetaValue};
 use crate::mods;
+use crate::testing::{TestError, TestManifest};
 use crate::variables::VariableError;
-use crate::Scanner;
+use crate::{ScanError, ScanOptions, ScanPhase, Scanner, ScannerPool};
 
 #[test]
 fn iterators() {
@@ -164,6 +168,101 @@ fn xor_matches() {
     assert_eq!(matches, [("$a", 0..11, Some(1))])
 }
 
+#[test]
+fn xor_matches_with_key_range() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            strings:
+                $a = "mississippi" xor(0-2)
+                $b = "mississippi" xor(2-3) wide
+            condition:
+                $a or $b
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut matches = vec![];
+
+    for matching_rule in Scanner::new(&rules)
+        // "mississippi" xor 0, xor 1, xor 3 and wide xor 2.
+        .scan(
+            b"mississippi lhrrhrrhqqh njppjppjssj \
+              o\x02k\x02q\x02q\x02k\x02q\x02q\x02k\x02r\x02r\x02k\x02",
+        )
+        .expect("scan should not fail")
+        .matching_rules()
+    {
+        for pattern in matching_rule.patterns() {
+            matches.extend(
+                pattern
+                    .matches()
+                    .map(|x| (pattern.identifier(), x.range(), x.xor_key())),
+            )
+        }
+    }
+
+    // "njppjppjssj" is "mississippi" xor 3, which is outside the range of
+    // keys for `$a`, and `$b` is wide.
+    assert_eq!(
+        matches,
+        [
+            ("$a", 0..11, Some(0)),
+            ("$a", 12..23, Some(1)),
+            ("$b", 36..58, Some(2))
+        ]
+    )
+}
+
+#[test]
+fn match_variants() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            strings:
+                $a = "foobar" ascii wide
+                $b = "foobar" base64 base64wide
+                $c = "mississippi" xor(1) wide
+                $d = { 71 75 78 }
+            condition:
+                any of them
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut matches = vec![];
+
+    for matching_rule in Scanner::new(&rules)
+        .scan(
+            b"foobar f\x00o\x00o\x00b\x00a\x00r\x00 Zm9vYmFy \
+              Z\x00m\x009\x00v\x00Y\x00m\x00F\x00y\x00 \
+              l\x01h\x01r\x01r\x01h\x01r\x01r\x01h\x01q\x01q\x01h\x01 qux",
+        )
+        .expect("scan should not fail")
+        .matching_rules()
+    {
+        for pattern in matching_rule.patterns() {
+            matches.extend(pattern.matches().map(|x| {
+                (pattern.identifier(), x.range(), x.variant(), x.xor_key())
+            }))
+        }
+    }
+
+    assert_eq!(
+        matches,
+        [
+            ("$a", 0..6, MatchVariant::Plain, None),
+            ("$a", 7..19, MatchVariant::Wide, None),
+            ("$b", 20..28, MatchVariant::Base64, None),
+            ("$b", 29..45, MatchVariant::Base64Wide, None),
+            ("$c", 46..68, MatchVariant::Wide, Some(1)),
+            ("$d", 69..72, MatchVariant::Plain, None),
+        ]
+    )
+}
+
 #[cfg(feature = "test_proto2-module")]
 #[test]
 fn reuse_scanner() {
@@ -499,6 +598,41 @@ fn private_rules() {
     assert_eq!(scan_results.non_matching_rules().len(), 0);
 }
 
+#[test]
+fn private_non_matching_rules() {
+    let rules = crate::compile(
+        r#"
+        private rule test_1 {
+            condition:
+                filesize > 0
+        }
+
+        rule test_2 {
+            condition:
+                filesize > 0
+        }
+
+        rule test_3 {
+            condition:
+                filesize == 0
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(&[]).expect("scan should not fail");
+
+    // The private rule doesn't match, but it must not be reported nor
+    // counted as a non-matching rule.
+    let mut non_matching = scan_results.non_matching_rules();
+
+    assert_eq!(non_matching.len(), 1);
+    assert_eq!(non_matching.next().unwrap().identifier(), "test_2");
+    assert_eq!(non_matching.len(), 0);
+    assert!(non_matching.next().is_none());
+}
+
 #[test]
 fn max_matches_per_pattern() {
     let mut compiler = crate::Compiler::new();
@@ -525,14 +659,15 @@ fn max_matches_per_pattern() {
 
     assert_eq!(scan_results.matching_rules().len(), 1);
 
-    let mut matches = scan_results
+    let pattern = scan_results
         .matching_rules()
         .next()
         .unwrap()
         .patterns()
         .next()
-        .unwrap()
-        .matches();
+        .unwrap();
+
+    let mut matches = pattern.matches();
 
     // Only one match is returned for pattern $a because the limit has been
     // set to 1.
@@ -543,9 +678,107 @@ fn max_matches_per_pattern() {
 
     assert!(matches.next().is_none());
 
+    // The other two matches were found, but not recorded.
+    assert_eq!(pattern.suppressed_matches(), 2);
+
     // If the scanner is used again it should produce results because the
     // number of matches must be reset to 0 for the new scan.
-    assert_eq!(scanner.scan(b"foo").unwrap().matching_rules().len(), 1);
+    let scan_results = scanner.scan(b"foo").unwrap();
+    let pattern = scan_results
+        .matching_rules()
+        .next()
+        .unwrap()
+        .patterns()
+        .next()
+        .unwrap();
+
+    assert_eq!(pattern.matches().len(), 1);
+    assert_eq!(pattern.suppressed_matches(), 0);
+}
+
+#[test]
+fn match_context() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            strings:
+                $a = "foo"
+            condition:
+                $a
+        }"#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    let scan_results = scanner.scan(b"0123foo45").unwrap();
+    let rule = scan_results.matching_rules().next().unwrap();
+    let pattern = rule.patterns().next().unwrap();
+    let match_ = pattern.matches().next().unwrap();
+
+    assert_eq!(match_.context(2), (b"23".as_slice(), b"45".as_slice()));
+    assert_eq!(match_.context(8), (b"0123".as_slice(), b"45".as_slice()));
+    assert_eq!(match_.context(0), (b"".as_slice(), b"".as_slice()));
+}
+
+#[test]
+fn max_memory() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            strings:
+              $a = "foo"
+            condition:
+              $a
+        }
+        "#,
+    )
+    .unwrap();
+
+    let data = b"foo".repeat(10_000);
+    let mut scanner = Scanner::new(&rules);
+
+    assert!(matches!(
+        scanner.scan_with_options(&data, ScanOptions::new().max_memory(1024)),
+        Err(ScanError::OutOfMemory)
+    ));
+
+    // With enough memory the scan succeeds, and the limit doesn't apply to
+    // subsequent scans that don't set it.
+    let scan_results = scanner
+        .scan_with_options(&data, ScanOptions::new().max_memory(1 << 20))
+        .unwrap();
+
+    assert_eq!(scan_results.matching_rules().len(), 1);
+
+    let scan_results = scanner.scan(&data).unwrap();
+    assert_eq!(scan_results.matching_rules().len(), 1);
+}
+
+#[cfg(feature = "test_proto2-module")]
+#[test]
+fn max_memory_module_outputs() {
+    let rules = crate::compile(
+        r#"
+        import "test_proto2"
+        rule test {
+            condition:
+              test_proto2.file_size == 3
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    assert!(matches!(
+        scanner.scan_with_options(b"foo", ScanOptions::new().max_memory(1)),
+        Err(ScanError::OutOfMemory)
+    ));
+
+    // The scanner is still usable after the scan was aborted.
+    let scan_results = scanner.scan(b"foo").unwrap();
+    assert_eq!(scan_results.matching_rules().len(), 1);
 }
 
 #[test]
@@ -624,6 +857,24 @@ fn set_module_output() {
         .unwrap();
     let scan_results = scanner.scan(b"").expect("scan should not fail");
     assert_eq!(scan_results.matching_rules().len(), 1);
+
+    // Set the output for the PE module from its JSON representation.
+    scanner
+        .set_module_output_json("pe", r#"{"is_pe": true, "entry_point": 1}"#)
+        .unwrap();
+    let scan_results = scanner.scan(b"").expect("scan should not fail");
+    assert_eq!(scan_results.matching_rules().len(), 1);
+
+    // Unknown fields in the JSON are not accepted.
+    assert_eq!(
+        scanner
+            .set_module_output_json("pe", r#"{"is_pe": true, "foo": 1}"#)
+            .err()
+            .unwrap()
+            .to_string()
+            .as_str(),
+        "can not parse JSON for YARA module `pe`: Unknown field name: `foo` at 1:17"
+    );
 }
 
 #[test]
@@ -649,3 +900,930 @@ fn namespaces() {
     assert_eq!(matching_rules[1].identifier(), "bar");
     assert_eq!(matching_rules[1].namespace(), "bar");
 }
+
+/// Builds a module plugin named `foo` that produces `foo.Foo` messages. The
+/// plugin simply returns the scanned data as is, so the scanned data must be
+/// a serialized `foo.Foo` message.
+fn echo_plugin() -> crate::ModulePlugin {
+    use protobuf::descriptor::field_descriptor_proto::{Label, Type};
+    use protobuf::descriptor::{
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+        FileDescriptorSet,
+    };
+    use walrus::ir::{BinaryOp, UnaryOp};
+    use walrus::{FunctionBuilder, ModuleConfig, ValType};
+
+    let mut module = walrus::Module::with_config(ModuleConfig::new());
+    let memory = module.memories.add_local(false, false, 1, None, None);
+
+    // yr_alloc(size: i32) -> i32 always returns the same address.
+    let size = module.locals.add(ValType::I32);
+    let mut alloc = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32],
+        &[ValType::I32],
+    );
+    alloc.func_body().i32_const(1024);
+    let alloc = alloc.finish(vec![size], &mut module.funcs);
+
+    // yr_parse(ptr: i32, len: i32) -> i64 returns (ptr << 32) | len.
+    let ptr = module.locals.add(ValType::I32);
+    let len = module.locals.add(ValType::I32);
+    let mut parse = FunctionBuilder::new(
+        &mut module.types,
+        &[ValType::I32, ValType::I32],
+        &[ValType::I64],
+    );
+    parse
+        .func_body()
+        .local_get(ptr)
+        .unop(UnaryOp::I64ExtendUI32)
+        .i64_const(32)
+        .binop(BinaryOp::I64Shl)
+        .local_get(len)
+        .unop(UnaryOp::I64ExtendUI32)
+        .binop(BinaryOp::I64Or);
+    let parse = parse.finish(vec![ptr, len], &mut module.funcs);
+
+    module.exports.add("memory", memory);
+    module.exports.add("yr_alloc", alloc);
+    module.exports.add("yr_parse", parse);
+
+    let mut field = FieldDescriptorProto::new();
+    field.set_name("answer".to_string());
+    field.set_number(1);
+    field.set_type(Type::TYPE_INT64);
+    field.set_label(Label::LABEL_OPTIONAL);
+
+    let mut message = DescriptorProto::new();
+    message.set_name("Foo".to_string());
+    message.field.push(field);
+
+    let mut file = FileDescriptorProto::new();
+    file.set_name("foo.proto".to_string());
+    file.set_package("foo".to_string());
+    file.set_syntax("proto2".to_string());
+    file.message_type.push(message);
+
+    let mut descriptors = FileDescriptorSet::new();
+    descriptors.file.push(file);
+
+    crate::ModulePlugin::new(
+        "foo",
+        module.emit_wasm().as_slice(),
+        descriptors.write_to_bytes().unwrap().as_slice(),
+        "foo.Foo",
+    )
+    .unwrap()
+}
+
+#[test]
+fn module_plugin() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .add_module_plugin(echo_plugin())
+        .add_source(
+            r#"
+        import "foo"
+        rule test {
+            condition:
+              foo.answer == 42
+        }
+        "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+
+    // A serialized `foo.Foo` message where `answer` is 42.
+    let data = [0x08, 0x2a];
+
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(&data).expect("scan should not fail");
+    assert_eq!(scan_results.matching_rules().len(), 1);
+
+    let output = scan_results
+        .module_output("foo")
+        .expect("foo should produce some output");
+
+    assert_eq!(output.descriptor_dyn().full_name(), "foo.Foo");
+    assert_eq!(scan_results.module_outputs().count(), 1);
+
+    let scan_results = scanner.scan(b"").expect("scan should not fail");
+    assert_eq!(scan_results.matching_rules().len(), 0);
+
+    // Plugins are serialized together with the rules.
+    let rules = crate::Rules::deserialize(rules.serialize().unwrap()).unwrap();
+    let mut scanner = Scanner::new(&rules);
+    let scan_results = scanner.scan(&data).expect("scan should not fail");
+    assert_eq!(scan_results.matching_rules().len(), 1);
+}
+
+#[test]
+fn invalid_module_plugin() {
+    assert_eq!(
+        crate::ModulePlugin::new("pe", &[], &[], "pe.PE")
+            .err()
+            .unwrap()
+            .to_string(),
+        "invalid module name `pe`"
+    );
+
+    assert_eq!(
+        crate::ModulePlugin::new("foo", &[], &[], "foo.Foo")
+            .err()
+            .unwrap()
+            .to_string(),
+        "message `foo.Foo` not found in descriptors"
+    );
+}
+
+#[test]
+fn aggregated_results() {
+    let rules = crate::compile(
+        r#"
+rule foo : t1 { strings: $a = "foo" $b = "bar" condition: $a or $b }
+rule bar { strings: $a = "bar" condition: $a }
+rule baz { condition: false }
+"#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+    let mut aggregated = AggregatedResults::new();
+
+    aggregated.add("sample", &scanner.scan(b"foo foo").unwrap());
+    aggregated.add("sample/layer1", &scanner.scan(b"qux").unwrap());
+    aggregated.add("sample/layer2", &scanner.scan(b"bar").unwrap());
+    aggregated.add("sample", &scanner.scan(b"foo bar").unwrap());
+
+    assert_eq!(
+        aggregated.origins().collect::<Vec<_>>(),
+        vec!["sample", "sample/layer1", "sample/layer2"]
+    );
+
+    assert_eq!(
+        aggregated.rules().map(|r| r.identifier()).collect::<Vec<_>>(),
+        vec!["foo", "bar"]
+    );
+
+    assert!(aggregated.get("default", "baz").is_none());
+
+    let foo = aggregated.get("default", "foo").unwrap();
+
+    assert_eq!(foo.tags().collect::<Vec<_>>(), vec!["t1"]);
+    assert_eq!(
+        foo.origins().collect::<Vec<_>>(),
+        vec!["sample", "sample/layer2"]
+    );
+
+    assert_eq!(
+        serde_json::to_value(foo).unwrap(),
+        json!({
+            "namespace": "default",
+            "identifier": "foo",
+            "tags": ["t1"],
+            "hits": [
+                {
+                    "origin": "sample",
+                    "patterns": [
+                        {
+                            "identifier": "$a",
+                            "matches": [
+                                {"start": 0, "end": 3},
+                                {"start": 4, "end": 7}
+                            ]
+                        },
+                        {
+                            "identifier": "$b",
+                            "matches": [{"start": 4, "end": 7}]
+                        }
+                    ]
+                },
+                {
+                    "origin": "sample/layer2",
+                    "patterns": [
+                        {
+                            "identifier": "$b",
+                            "matches": [{"start": 0, "end": 3}]
+                        }
+                    ]
+                }
+            ]
+        })
+    );
+}
+
+#[test]
+fn condition_trace() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .condition_tracing(true)
+        .add_source(
+            r#"
+rule test {
+  strings:
+    $a = "foo"
+    $b = "bar"
+  condition:
+    $a and (not $b or filesize > 100) and uint8(1000) == 0
+}
+
+rule test_undef {
+  condition:
+    uint8(1000) == 0 or for any i in (0..2) : (i == 1)
+}
+"#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+    let results = scanner.scan(b"foo bar").unwrap();
+
+    let trace = |rule: crate::Rule| {
+        rule.condition_trace()
+            .map(|expr| {
+                (
+                    expr.source().to_string(),
+                    expr.times_true(),
+                    expr.times_false(),
+                    expr.times_undefined(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut non_matching = results.non_matching_rules();
+
+    assert_eq!(
+        trace(non_matching.next().unwrap()),
+        vec![
+            (
+                "$a and (not $b or filesize > 100) and uint8(1000) == 0"
+                    .to_string(),
+                0,
+                1,
+                0
+            ),
+            ("$a".to_string(), 1, 0, 0),
+            ("not $b or filesize > 100".to_string(), 0, 1, 0),
+            ("not $b".to_string(), 0, 1, 0),
+            ("$b".to_string(), 1, 0, 0),
+            ("filesize > 100".to_string(), 0, 1, 0),
+            ("uint8(1000) == 0".to_string(), 0, 0, 0),
+        ]
+    );
+
+    let mut matching = results.matching_rules();
+
+    assert_eq!(
+        trace(matching.next().unwrap()),
+        vec![
+            (
+                "uint8(1000) == 0 or for any i in (0..2) : (i == 1)"
+                    .to_string(),
+                1,
+                0,
+                0
+            ),
+            ("uint8(1000) == 0".to_string(), 0, 0, 1),
+            ("for any i in (0..2) : (i == 1)".to_string(), 1, 0, 0),
+        ]
+    );
+
+    // Values recorded in a previous scan are not carried to the next one.
+    let results = scanner.scan(b"").unwrap();
+    let mut non_matching = results.non_matching_rules();
+
+    assert_eq!(
+        trace(non_matching.next().unwrap())[1],
+        ("$a".to_string(), 0, 1, 0),
+    );
+}
+
+#[test]
+fn test_manifest() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .condition_tracing(true)
+        .add_source(
+            r#"
+rule test {
+  strings:
+    $a = "foo"
+    $b = "bar"
+  condition:
+    $a and (not $b or filesize > 100)
+}
+"#,
+        )
+        .unwrap()
+        .new_namespace("other")
+        .add_source(r#"rule mz { condition: uint16(0) == 0x5A4D }"#)
+        .unwrap();
+
+    let rules = compiler.build();
+
+    let manifest = TestManifest::from_json(
+        br#"{
+  "tests": [
+    { "name": "only foo", "hex": "66 6f 6f", "matches": ["test"] },
+    { "name": "foo bar", "hex": "666f6f 626172", "matches": ["test"] },
+    { "hex": "4D 5A", "matches": ["other.mz"], "not_matches": ["test"] },
+    { "hex": "66 6f 6f", "matches": ["mz"], "not_matches": ["test"] }
+  ]
+}"#,
+    )
+    .unwrap();
+
+    assert_eq!(manifest.len(), 4);
+
+    let report = manifest.run(&rules).unwrap();
+
+    assert!(!report.passed());
+    assert_eq!(report.num_checks(), 6);
+
+    let failures = report
+        .failures()
+        .map(|f| {
+            (
+                f.test().to_string(),
+                f.namespace().to_string(),
+                f.rule().to_string(),
+                f.expected_match(),
+                f.diverged().map(|e| e.source().to_string()).collect(),
+            )
+        })
+        .collect::<Vec<(_, _, _, _, Vec<_>)>>();
+
+    assert_eq!(
+        failures,
+        vec![
+            (
+                "foo bar".to_string(),
+                "default".to_string(),
+                "test".to_string(),
+                true,
+                vec!["not $b".to_string(), "filesize > 100".to_string()]
+            ),
+            (
+                "#4".to_string(),
+                "other".to_string(),
+                "mz".to_string(),
+                true,
+                vec!["uint16(0) == 0x5A4D".to_string()]
+            ),
+            (
+                "#4".to_string(),
+                "default".to_string(),
+                "test".to_string(),
+                false,
+                vec!["$a".to_string(), "not $b".to_string()]
+            ),
+        ]
+    );
+
+    let manifest = TestManifest::from_json(
+        br#"{ "tests": [ { "hex": "00", "matches": ["unknown"] } ] }"#,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        manifest.run(&rules),
+        Err(TestError::UnknownRule { test, rule })
+            if test == "#1" && rule == "unknown"
+    ));
+
+    let manifest = TestManifest::from_json(
+        br#"{ "tests": [ { "hex": "0", "matches": ["test"] } ] }"#,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        manifest.run(&rules),
+        Err(TestError::InvalidTest { .. })
+    ));
+
+    assert!(matches!(
+        TestManifest::from_json(br#"{ "tests": [ { "foo": 1 } ] }"#),
+        Err(TestError::InvalidManifest(_))
+    ));
+}
+
+#[test]
+fn scanner_pool() {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<crate::Rules>();
+    assert_send_sync::<ScannerPool>();
+
+    let mut compiler = crate::Compiler::new();
+    compiler.define_global("bar", false).unwrap();
+    compiler
+        .add_source(
+            r#"
+        import "console"
+        rule test {
+            strings:
+              $a = "foo"
+            condition:
+              $a and bar and console.log("foo")
+        }
+        "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let logged = std::sync::atomic::AtomicUsize::new(0);
+
+    let mut pool = ScannerPool::with_init(&rules, |scanner| {
+        scanner.set_global("bar", true).unwrap();
+    });
+
+    pool.console_log(|msg| {
+        assert_eq!(msg, "foo");
+        logged.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    });
+
+    // Scanners created by the threads in the first round are used by the
+    // threads in the second round.
+    for _ in 0..2 {
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..10 {
+                        let mut scanner = pool.get();
+                        let results = scanner.scan(b"foo").unwrap();
+                        assert_eq!(results.matching_rules().len(), 1);
+                    }
+                });
+            }
+        });
+    }
+
+    assert_eq!(logged.load(std::sync::atomic::Ordering::Relaxed), 80);
+
+    // At most one scanner per thread was created.
+    assert!(pool.idle() >= 1 && pool.idle() <= 4);
+
+    // Scanners are reused.
+    let idle = pool.idle();
+    drop(pool.get());
+    assert_eq!(pool.idle(), idle);
+
+    // Callbacks that are not `Send` can be set on a scanner taken from the
+    // pool. They replace the pool's callback while the scanner is in use.
+    let messages = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut scanner = pool.get();
+    let messages_clone = messages.clone();
+    scanner.console_log(move |msg| messages_clone.borrow_mut().push(msg));
+    scanner.scan(b"foo").unwrap();
+    drop(scanner);
+
+    assert_eq!(messages.borrow().as_slice(), &["foo".to_string()]);
+    assert_eq!(logged.load(std::sync::atomic::Ordering::Relaxed), 80);
+
+    // Once back in the pool, the scanner uses the pool's callback again.
+    pool.get().scan(b"foo").unwrap();
+    assert_eq!(messages.borrow().len(), 1);
+    assert_eq!(logged.load(std::sync::atomic::Ordering::Relaxed), 81);
+}
+
+#[test]
+fn progress_callback() {
+    let rules = crate::compile(
+        r#"
+        rule test {
+            strings:
+              $a = "foobar"
+            condition:
+              #a == 3
+        }
+        "#,
+    )
+    .unwrap();
+
+    // Put some matches right at the boundaries between chunks processed by
+    // the pattern search.
+    let mut data = vec![0_u8; 10 << 20];
+    data[(4 << 20) - 6..4 << 20].copy_from_slice(b"foobar");
+    data[(8 << 20) - 3..(8 << 20) + 3].copy_from_slice(b"foobar");
+    data[(10 << 20) - 6..].copy_from_slice(b"foobar");
+
+    let progress = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut scanner = Scanner::new(&rules);
+
+    let p = progress.clone();
+    scanner.progress_callback(move |progress| {
+        p.lock().unwrap().push((progress.phase(), progress.bytes_processed()))
+    });
+
+    let scan_results = scanner.scan(&data).unwrap();
+    assert_eq!(scan_results.matching_rules().len(), 1);
+
+    assert_eq!(
+        progress.lock().unwrap().as_slice(),
+        &[
+            (ScanPhase::ConditionEvaluation, 0),
+            (ScanPhase::PatternSearch, 4 << 20),
+            (ScanPhase::PatternSearch, 8 << 20),
+            (ScanPhase::PatternSearch, 10 << 20),
+            (ScanPhase::ConditionEvaluation, 10 << 20),
+        ]
+    );
+}
+
+#[cfg(all(feature = "pe-module", feature = "elf-module"))]
+#[test]
+fn module_stats() {
+    let rules = crate::compile(
+        r#"
+        import "pe"
+        import "elf"
+        rule test {
+            condition:
+              not pe.is_pe
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    // The output of modules that don't parse the data is the same as
+    // when the data is parsed.
+    let scan_results = scanner.scan(b"foo").unwrap();
+    assert_eq!(scan_results.matching_rules().len(), 1);
+
+    let scan_results = scanner.scan(b"\x7fELF").unwrap();
+    assert_eq!(scan_results.matching_rules().len(), 1);
+
+    let stats: Vec<_> = scanner
+        .module_stats()
+        .map(|(name, stats)| (name, stats.parsed(), stats.skipped()))
+        .collect();
+
+    assert_eq!(stats, vec![("pe", 0, 2), ("elf", 1, 1)]);
+}
+
+#[cfg(feature = "test_proto2-module")]
+#[test]
+fn use_module_outputs() {
+    let rules_1 = crate::compile(
+        r#"
+        import "test_proto2"
+        rule test_1 {
+            condition:
+                test_proto2.file_size == 3
+        }
+        "#,
+    )
+    .unwrap();
+
+    let rules_2 = crate::compile(
+        r#"
+        import "test_proto2"
+        rule test_2 {
+            condition:
+                test_proto2.file_size == 3
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner_1 = Scanner::new(&rules_1);
+    let mut scanner_2 = Scanner::new(&rules_2);
+
+    let outputs = scanner_1.scan(b"123").unwrap().capture_module_outputs();
+    assert_eq!(outputs.len(), 1);
+
+    // The data scanned by `scanner_2` is different, but the module output
+    // captured from `scanner_1` is used instead of the one that would be
+    // produced by parsing the data.
+    scanner_2.use_module_outputs(&outputs);
+    assert_eq!(scanner_2.scan(b"123456").unwrap().matching_rules().len(), 1);
+
+    // The outputs are used only for a single scan.
+    assert_eq!(scanner_2.scan(b"123456").unwrap().matching_rules().len(), 0);
+}
+
+#[test]
+#[cfg(feature = "rules-profiling")]
+fn most_expensive_rules() {
+    let rules = crate::compile(
+        r#"
+rule foo { strings: $a = /foo[0-9]+bar/ condition: $a }
+rule bar { condition: true }
+"#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.scan(b"foo123bar foo456").unwrap();
+    scanner.scan(b"foo").unwrap();
+
+    let profiles = scanner.most_expensive_rules(1);
+
+    assert_eq!(profiles.len(), 1);
+    assert_eq!(profiles[0].namespace(), "default");
+    assert_eq!(profiles[0].rule(), "foo");
+    assert!(!profiles[0].pattern_matching_time().is_zero());
+    assert_eq!(scanner.most_expensive_rules(10).len(), 2);
+}
+
+#[test]
+fn disable_rule() {
+    let rules = crate::compile(
+        r#"
+        global rule global_rule {
+            condition:
+                filesize > 100
+        }
+        rule foo {
+            strings:
+                $a = "foo"
+                $b = "bar"
+            condition:
+                $a or $b
+        }
+        rule bar {
+            strings:
+                $a = "bar"
+            condition:
+                $a
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut scanner = Scanner::new(&rules);
+
+    // The global rule doesn't match, so no other rule matches.
+    let scan_results = scanner.scan(b"foobar").unwrap();
+    assert_eq!(scan_results.matching_rules().len(), 0);
+
+    // A disabled global rule doesn't prevent other rules from matching.
+    assert!(scanner.disable_rule("default", "global_rule"));
+
+    let scan_results = scanner.scan(b"foobar").unwrap();
+    assert_eq!(
+        scan_results
+            .matching_rules()
+            .map(|r| r.identifier())
+            .collect::<Vec<_>>(),
+        vec!["foo", "bar"]
+    );
+
+    // Pattern `$a = "bar"` is shared with rule `bar`, which is still
+    // enabled, so it's still searched for.
+    assert!(scanner.disable_rule("default", "foo"));
+    assert!(!scanner.disable_rule("default", "qux"));
+    assert!(!scanner.disable_rule("other", "foo"));
+
+    let scan_results = scanner.scan(b"foobar").unwrap();
+    assert_eq!(
+        scan_results
+            .matching_rules()
+            .map(|r| r.identifier())
+            .collect::<Vec<_>>(),
+        vec!["bar"]
+    );
+
+    // Pattern `$a = "foo"` is used only by the disabled rule, it's not
+    // searched for.
+    let foo = scan_results
+        .non_matching_rules()
+        .find(|r| r.identifier() == "foo")
+        .unwrap();
+
+    assert_eq!(
+        foo.patterns().map(|p| p.matches().len()).collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+
+    assert!(scanner.enable_rule("default", "foo"));
+    assert!(scanner.enable_rule("default", "global_rule"));
+
+    let scan_results = scanner.scan(b"foobar").unwrap();
+    assert_eq!(scan_results.matching_rules().len(), 0);
+}
+
+#[test]
+fn max_loop_iterations() {
+    let rules = crate::compile(
+        r#"
+        rule loop {
+          condition:
+            for all i in (0..filesize-1) : (uint8(i) != 0xff)
+        }
+        rule nested {
+          condition:
+            for any i in (1..2) : (
+               for any j in (0..filesize-1) : (uint8(j) == i)
+            )
+        }
+        rule other {
+          condition:
+            for all i in (0..10) : (i <= 10)
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut data = [0_u8; 100];
+    data[99] = 2;
+
+    let mut scanner = Scanner::new(&rules);
+
+    let matching_rules = |scanner: &mut Scanner| {
+        scanner
+            .scan(&data)
+            .unwrap()
+            .matching_rules()
+            .map(|r| r.identifier().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matching_rules(&mut scanner), ["loop", "nested", "other"]);
+
+    // Rule `loop` needs 100 iterations, `nested` needs 200 (100 for each
+    // iteration of the outer loop) and `other` needs 11. The limit applies
+    // to each rule separately.
+    scanner.max_loop_iterations(99);
+    assert_eq!(matching_rules(&mut scanner), ["other"]);
+
+    scanner.max_loop_iterations(100);
+    assert_eq!(matching_rules(&mut scanner), ["loop", "other"]);
+
+    scanner.max_loop_iterations(200);
+    assert_eq!(matching_rules(&mut scanner), ["loop", "nested", "other"]);
+}
+
+#[test]
+fn rule_timeout() {
+    let rules = crate::compile(
+        r#"
+        rule slow {
+          condition:
+            for all i in (0..filesize-1) : (
+               for all j in (0..filesize-1) : (uint8(i) == uint8(j))
+            )
+        }
+        rule fast {
+          condition:
+            for all i in (0..10) : (i <= 10)
+        }
+        "#,
+    )
+    .unwrap();
+
+    let data = vec![0_u8; 1_000_000];
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.set_rule_timeout(Duration::from_millis(100));
+
+    let scan_results = scanner.scan(&data).unwrap();
+
+    assert_eq!(
+        scan_results
+            .matching_rules()
+            .map(|r| r.identifier())
+            .collect::<Vec<_>>(),
+        vec!["fast"]
+    );
+
+    assert_eq!(
+        scan_results
+            .timed_out_rules()
+            .map(|r| r.identifier())
+            .collect::<Vec<_>>(),
+        vec!["slow"]
+    );
+
+    // Rules that timed out in a previous scan are not reported again.
+    let scan_results = scanner.scan(b"").unwrap();
+
+    assert_eq!(scan_results.timed_out_rules().len(), 0);
+}
+
+#[test]
+fn deterministic_order() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .new_namespace("foo")
+        .add_source(
+            r#"
+            rule a { strings: $a = "a" condition: $a }
+            rule b { condition: true }
+            "#,
+        )
+        .unwrap()
+        .new_namespace("bar")
+        .add_source(
+            r#"
+            rule c { condition: true }
+            rule d { strings: $b = "b" $a = "a" condition: any of them }
+            "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    let matching_rules = |scanner: &mut Scanner, data: &[u8]| {
+        scanner
+            .scan(data)
+            .unwrap()
+            .matching_rules()
+            .map(|r| format!("{}.{}", r.namespace(), r.identifier()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matching_rules(&mut scanner, b""), ["foo.b", "bar.c"]);
+
+    // Rules are returned in declaration order, regardless of the rules that
+    // matched in previous scans.
+    let mut scanner = Scanner::new(&rules);
+
+    scanner.disable_rule("foo", "b");
+    assert_eq!(matching_rules(&mut scanner, b""), ["bar.c"]);
+
+    scanner.enable_rule("foo", "b");
+    assert_eq!(
+        matching_rules(&mut scanner, b"aba"),
+        ["foo.a", "foo.b", "bar.c", "bar.d"]
+    );
+
+    let scan_results = scanner.scan(b"aba").unwrap();
+    let rule_d = scan_results.matching_rules().last().unwrap();
+
+    assert_eq!(
+        rule_d
+            .patterns()
+            .map(|p| {
+                (
+                    p.identifier(),
+                    p.matches().map(|m| m.range().start).collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>(),
+        [("$b", vec![1]), ("$a", vec![0, 2])]
+    );
+}
+
+#[test]
+fn scan_stats() {
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .add_source(
+            r#"
+            import "pe"
+            rule a { strings: $a = "foo" condition: $a }
+            rule b { strings: $b = /b[a-z]+r/ condition: $b }
+            "#,
+        )
+        .unwrap()
+        .new_namespace("other")
+        .add_source(
+            r#"
+            global rule c { condition: filesize > 100 }
+            rule d { condition: true }
+            "#,
+        )
+        .unwrap();
+
+    let rules = compiler.build();
+    let mut scanner = Scanner::new(&rules);
+
+    let stats = scanner.scan(b"foo bar").unwrap().stats();
+
+    assert_eq!(stats.num_scans(), 1);
+    assert_eq!(stats.bytes_scanned(), 7);
+    assert_eq!(stats.atoms_fired(), 2);
+    assert_eq!(stats.regexp_executions(), 1);
+    // Rule `d` is not evaluated because the global rule `c` didn't match.
+    assert_eq!(stats.rules_evaluated(), 3);
+    assert_eq!(stats.rule_timeouts(), 0);
+    assert_eq!(
+        stats.module_parse_times().map(|(m, _)| m).collect::<Vec<_>>(),
+        ["pe"]
+    );
+
+    let mut aggregated = stats.clone();
+
+    aggregated.merge(&scanner.scan(b"foo").unwrap().stats());
+
+    assert_eq!(aggregated.num_scans(), 2);
+    assert_eq!(aggregated.bytes_scanned(), 10);
+    assert_eq!(aggregated.atoms_fired(), 3);
+    assert_eq!(aggregated.regexp_executions(), 1);
+    assert_eq!(aggregated.rules_evaluated(), 6);
+    assert_eq!(aggregated.module_parse_times().count(), 1);
+}
@@ -211,6 +211,55 @@ fn arithmetic_operations() {
     condition_true!("7 % 4 % 2 == 1");
 }
 
+#[test]
+fn arithmetic_overflow() {
+    // Integer operations that overflow produce an undefined result instead
+    // of wrapping around.
+    condition_true!("0x7fffffffffffffff + 0 == 0x7fffffffffffffff");
+    condition_true!("-0x7fffffffffffffff - 1 < 0");
+    condition_false!("0x7fffffffffffffff + 1 == 0");
+    condition_false!("0x7fffffffffffffff + 1 != 0");
+    condition_true!("not defined (0x7fffffffffffffff + 1)");
+    condition_true!("not defined (-0x7fffffffffffffff - 2)");
+    condition_true!("not defined (0x4000000000000000 * 2)");
+    condition_true!("not defined -(-0x7fffffffffffffff - 1)");
+    condition_true!("not defined ((-0x7fffffffffffffff - 1) \\ -1)");
+    condition_true!("defined ((-0x7fffffffffffffff - 1) % -1)");
+    condition_true!("defined (0x7fffffffffffffff * -1 - 1)");
+
+    // The same, but with operands that are not known at compile time.
+    let data = [0x00, 0x00];
+
+    condition_true!("not defined (filesize + 0x7ffffffffffffffe)", &data);
+    condition_true!("defined (filesize + 0x7ffffffffffffffd)", &data);
+    condition_true!("not defined (-0x7fffffffffffffff - filesize)", &data);
+    condition_true!("defined (-0x7fffffffffffffff - (filesize - 1))", &data);
+    condition_true!("not defined (filesize * 0x4000000000000000)", &data);
+    condition_true!(
+        "filesize * 0x3fffffffffffffff == 0x7ffffffffffffffe",
+        &data
+    );
+    condition_true!("not defined (filesize * -0x4000000000000001)", &data);
+    condition_true!("filesize * -0x4000000000000000 < 0", &data);
+    condition_true!(
+        "defined (-(filesize - 2) * (-0x7fffffffffffffff - 1))",
+        &data
+    );
+    condition_true!(
+        "not defined (-(filesize - 1) * (-0x7fffffffffffffff - 1))",
+        &data
+    );
+    condition_true!(
+        "not defined ((-0x7fffffffffffffff - 1) \\ (1 - filesize))",
+        &data
+    );
+    condition_true!(
+        "not defined -(-0x7fffffffffffffff - filesize + 1)",
+        &data
+    );
+    condition_true!("1.0 * filesize * 0x7fffffffffffffff > 0", &data);
+}
+
 #[test]
 fn test_comparison_operations() {
     condition_true!("2 > 1");
@@ -277,6 +326,14 @@ fn string_operations() {
     condition_true!(r#""foo" iequals "FOO""#);
     condition_true!(r#""foo" iequals "FoO""#);
     condition_false!(r#""foo" iequals "bar""#);
+    condition_false!(r#""foo" iequals "fo""#);
+    condition_false!(r#""fo" istartswith "FOO""#);
+    condition_false!(r#""fo" iendswith "FOO""#);
+    condition_true!(r#""FOO\xC3\x89" iendswith "o\xC3\x89""#);
+    condition_true!(r#""\xFFfOo\x00" icontains "FoO\x00""#);
+
+    // Case-insensitive comparisons only take ASCII letters into account.
+    condition_false!(r#""\xC3\x89" iequals "\xC3\xA9""#);
 
     condition_true!(r#""foo" matches /foo/"#);
     condition_true!(r#""foo" matches /FOO/i"#);
@@ -372,6 +429,12 @@ fn uintxx() {
     condition_false!("uint16(19) != 0", &data);
     condition_false!("uint32(17) == 0", &data);
     condition_false!("uint32(17) != 0", &data);
+
+    condition_true!("uint64(2) == 0x0a09080706050403", &data);
+    condition_true!("uint64be(2) == 0x030405060708090a", &data);
+
+    condition_false!("uint64be(13) == 0", &data);
+    condition_false!("uint64be(13) != 0", &data);
 }
 
 #[test]
@@ -412,6 +475,35 @@ fn intxx() {
     condition_false!("int16(19) != 0", &data);
     condition_false!("int32(17) == 0", &data);
     condition_false!("int32(17) != 0", &data);
+
+    condition_true!("int64(0) == 0x0807060504030201", &data);
+    condition_true!("int64(12) == -1", &data);
+    condition_true!("int64be(0) == 0x0102030405060708", &data);
+    condition_true!("int64be(12) == -1", &data);
+
+    condition_false!("int64(13) == 0", &data);
+    condition_false!("int64(13) != 0", &data);
+}
+
+#[test]
+fn floatxx() {
+    let mut data = Vec::new();
+
+    data.extend_from_slice(&1.5_f32.to_le_bytes());
+    data.extend_from_slice(&(-2.25_f32).to_be_bytes());
+    data.extend_from_slice(&3.125_f64.to_le_bytes());
+    data.extend_from_slice(&(-0.5_f64).to_be_bytes());
+
+    condition_true!("float32(0) == 1.5", &data);
+    condition_true!("float32be(4) == -2.25", &data);
+    condition_true!("float64(8) == 3.125", &data);
+    condition_true!("float64be(16) == -0.5", &data);
+    condition_true!("float32(0) + float64(8) == 4.625", &data);
+
+    condition_false!("float32(21) == 0.0", &data);
+    condition_false!("float32(21) != 0.0", &data);
+    condition_false!("float64(17) == 0.0", &data);
+    condition_false!("float64(17) != 0.0", &data);
 }
 
 #[test]
@@ -521,6 +613,20 @@ fn with() {
     condition_true!(r#"with foo = 1 + 1, bar = 2 + 2 : (foo + bar == 6)"#);
     condition_false!(r#"with foo = 1 + 1, bar = 2 + 2 : (foo + bar == 7)"#);
 
+    // Variables can be bound to undefined values.
+    rule_true!(
+        r#"rule test { condition: with foo = uint32(filesize) : (not defined foo) }"#,
+        b"foo"
+    );
+    rule_true!(
+        r#"rule test { condition: with foo = uint32(filesize) : (foo == 0 or true) }"#,
+        b"foo"
+    );
+    rule_true!(
+        r#"rule test { condition: with foo = uint8(0), bar = filesize : (foo == 0x66 and bar == 3) }"#,
+        b"foo"
+    );
+
     #[cfg(feature = "test_proto2-module")]
     condition_true!(r#"with foo = test_proto2.array_int64[0]: (foo == 1)"#);
 
@@ -1133,6 +1239,11 @@ fn regexp_patterns_1() {
     pattern_match!(r#"/a(.*)*/"#, b"a", b"a");
     pattern_match!(r#"/a(.*){2}/"#, b"a", b"a");
     pattern_match!(r#"/a(.*){2,4}/"#, b"a", b"a");
+    pattern_match!(r#"/a(b*)*c/"#, b"xabbbc", b"abbbc");
+    pattern_match!(r#"/a(b*)+c/"#, b"xac", b"ac");
+    pattern_match!(r#"/a(b|c?)+d/"#, b"abcbd", b"abcbd");
+    pattern_match!(r#"/a(|bc){3,}d/"#, b"abcbcd", b"abcbcd");
+    pattern_match!(r#"/a(b?c?)*/"#, b"abccbx", b"abccb");
 
     // TODO: known issue related to exact atoms. The matching string
     // should be "abbb" and not "abb". When the `exact-atoms` feature
@@ -1470,6 +1581,140 @@ fn regexp_patterns_5() {
     );
 }
 
+#[test]
+fn regexp_large_repetitions() {
+    let data = |gap: usize, filler: u8| {
+        [b"abc".as_slice(), vec![filler; gap].as_slice(), b"def"].concat()
+    };
+
+    pattern_true!(r#"/abc.{1000}def/s"#, data(1000, b'x').as_slice());
+    pattern_false!(r#"/abc.{1000}def/s"#, data(999, b'x').as_slice());
+    pattern_false!(r#"/abc.{1000}def/s"#, data(1001, b'x').as_slice());
+
+    pattern_true!(r#"/abc.{20,30}def/s"#, data(20, b'x').as_slice());
+    pattern_true!(r#"/abc.{20,30}def/s"#, data(30, b'x').as_slice());
+    pattern_false!(r#"/abc.{20,30}def/s"#, data(19, b'x').as_slice());
+    pattern_false!(r#"/abc.{20,30}def/s"#, data(31, b'x').as_slice());
+
+    // When unrolled, this repetition needs more split instructions than
+    // the maximum allowed in a single regexp.
+    pattern_true!(r#"/abc.{0,10000}def/s"#, data(0, b'x').as_slice());
+    pattern_true!(r#"/abc.{0,10000}def/s"#, data(3000, b'x').as_slice());
+
+    pattern_true!(r#"/abc.{0,100}def/"#, data(50, b'x').as_slice());
+    pattern_false!(r#"/abc.{0,100}def/"#, data(50, b'\n').as_slice());
+    pattern_true!(r#"/abc.{0,100}def/s"#, data(50, b'\n').as_slice());
+
+    pattern_match!(
+        r#"/a.{0,100}b/s"#,
+        b"axxbxxbxx".as_slice(),
+        b"axxbxxb".as_slice()
+    );
+
+    pattern_match!(
+        r#"/a.{0,100}?b/s"#,
+        b"axxbxxbxx".as_slice(),
+        b"axxb".as_slice()
+    );
+
+    let wide =
+        [b"a\x00b\x00".as_slice(), &[b'x', 0].repeat(25), b"c\x00d\x00"]
+            .concat();
+
+    pattern_match!(r#"/ab.{20,30}cd/ wide"#, wide.as_slice(), wide.as_slice());
+
+    rule_true!(
+        r#"rule test {
+            strings:
+                $a = { 61 62 63 [0-1000] 64 65 66 }
+            condition:
+                $a and !a == 1006
+        }"#,
+        data(1000, 0xAA).as_slice()
+    );
+}
+
+#[test]
+fn regexp_dead_states() {
+    // The PikeVM remembers the states that didn't lead to a match while
+    // verifying some atom, and doesn't explore them again while verifying
+    // other atoms. Make sure that this doesn't cause matches to be lost.
+    let data =
+        ["a".repeat(100), "\n".to_string(), "a".repeat(100), "y".to_string()]
+            .concat();
+
+    rule_true!(
+        r#"rule test {
+            strings:
+                $a = /a[^\nx]*y/
+            condition:
+                #a == 100 and @a[1] == 101 and !a[1] == 101
+        }"#,
+        data.as_bytes()
+    );
+
+    let data =
+        ["\n", "abcd".repeat(100).as_str(), "z", "abcd".repeat(100).as_str()]
+            .concat();
+
+    rule_true!(
+        r#"rule test {
+            strings:
+                $a = /z[^\nx]*abcd/
+            condition:
+                #a == 1 and @a[1] == 401 and !a[1] == 401
+        }"#,
+        data.as_bytes()
+    );
+
+    rule_true!(
+        r#"rule test {
+            strings:
+                $a = /a(b?c?)*d/
+            condition:
+                #a == 2 and @a[1] == 4 and @a[2] == 11
+        }"#,
+        b"aaaaabcbcdaabcbcbcd"
+    );
+}
+
+#[test]
+fn regexp_unicode() {
+    // Unicode support is enabled with the `(?u)` flag. Unicode classes are
+    // compiled into expressions that match the UTF-8 encoding of the
+    // characters in the class.
+    pattern_match!(
+        r#"/(?u)\p{Greek}+/"#,
+        "abc αβγω xyz".as_bytes(),
+        "αβγω".as_bytes()
+    );
+    pattern_match!(
+        r#"/(?u)a\p{Cyrillic}b/"#,
+        "aЖb".as_bytes(),
+        "aЖb".as_bytes()
+    );
+    pattern_false!(r#"/(?u)a\p{Cyrillic}b/"#, "aαb".as_bytes());
+    pattern_match!(r#"/(?u)a.b/"#, "a🙈b".as_bytes(), "a🙈b".as_bytes());
+    pattern_match!(r#"/(?u)a.b/"#, "aéb".as_bytes(), "aéb".as_bytes());
+    pattern_false!(r#"/(?u)a.b/"#, b"a\xFFb");
+    pattern_false!(r#"/(?u)a.b/"#, b"a\nb");
+    pattern_match!(r#"/(?su)a.b/"#, b"a\nb", b"a\nb");
+    pattern_match!(r#"/(?u)a[^x]b/"#, "aωb".as_bytes(), "aωb".as_bytes());
+    pattern_match!(r#"/(?iu)café/"#, "CAFÉ".as_bytes(), "CAFÉ".as_bytes());
+    pattern_match!(
+        r#"/(?u)\w+@/"#,
+        "Ñandú@example".as_bytes(),
+        "Ñandú@".as_bytes()
+    );
+
+    pattern_match!(r#"/(?u)\bfoo\b/"#, b"a foo b", b"foo");
+    pattern_false!(r#"/(?u)\bfoo\b/"#, b"afoob");
+
+    // Without `(?u)` the dot matches a single byte.
+    pattern_false!(r#"/a.b/"#, "a🙈b".as_bytes());
+    pattern_match!(r#"/a.b/"#, b"a\xFFb", b"a\xFFb");
+}
+
 #[test]
 fn regexp_nocase() {
     pattern_match!(r#"/abc/ nocase"#, b"ABC", b"ABC");
@@ -1664,6 +1909,130 @@ fn regexp_wide() {
     pattern_true!(r"/foobar\b/ wide", b"f\x00o\x00o\x00b\x00a\x00r\x00x");
     pattern_false!(r"/foobar\B/ wide", b"f\x00o\x00o\x00b\x00a\x00r\x00x");
     pattern_true!(r"/foobar$/ wide", b"f\x00o\x00o\x00b\x00a\x00r\x00x");
+
+    // Regexps with large gaps are split into chained sub-patterns. In the
+    // wide variant the gap between them is twice as large, in bytes.
+    let wide = |s: &[u8]| s.iter().flat_map(|b| [*b, 0]).collect::<Vec<_>>();
+
+    let data = [wide(b"abcd"), wide(&[b'x'; 250]), wide(b"efgh")].concat();
+    pattern_true!(r"/abcd.{10,300}efgh/s wide", data.as_slice());
+    pattern_true!(r"/abcd.{250}.*efgh/s wide", data.as_slice());
+    pattern_false!(r"/abcd.{251,300}efgh/s wide", data.as_slice());
+
+    let data = [wide(b"abcd"), wide(&[b'x'; 6]), wide(b"efgh")].concat();
+    pattern_false!(r"/abcd.{10,300}efgh/s wide", data.as_slice());
+}
+
+#[test]
+fn regexp_word_boundary() {
+    use crate::WordBoundary;
+
+    let matches = |word_boundary, pattern: &str, data: &[u8]| {
+        let mut compiler = crate::Compiler::new();
+        compiler.word_boundary(word_boundary);
+        compiler
+            .add_source(
+                format!(
+                    "rule test {{ strings: $a = {} condition: $a }}",
+                    pattern
+                )
+                .as_str(),
+            )
+            .unwrap();
+        let rules = compiler.build();
+        let mut scanner = crate::scanner::Scanner::new(&rules);
+        scanner.scan(data).unwrap().matching_rules().len() == 1
+    };
+
+    // The underscore is a word character, except with `WordBoundary::Ascii`.
+    assert!(!matches(WordBoundary::Standard, r"/\bfoo\b/", b"_foo_"));
+    assert!(matches(WordBoundary::Ascii, r"/\bfoo\b/", b"_foo_"));
+    assert!(matches(WordBoundary::Ascii, r"/\Bfoo\B/", b"xfoox"));
+    assert!(!matches(WordBoundary::Ascii, r"/\Bfoo\B/", b"_foo_"));
+    assert!(matches(WordBoundary::Ascii, r"/\b{start}foo/", b"_foo"));
+    assert!(matches(WordBoundary::Ascii, r"/foo\b{end}/", b"foo_"));
+
+    // With `WordBoundary::Wide` two-byte characters where the second byte
+    // is not zero are not word characters while matching wide patterns.
+    assert!(!matches(
+        WordBoundary::Standard,
+        r"/\bfoo\b/ wide",
+        b"\x41\x04f\x00o\x00o\x00\x41\x04"
+    ));
+    assert!(matches(
+        WordBoundary::Wide,
+        r"/\bfoo\b/ wide",
+        b"\x41\x04f\x00o\x00o\x00\x41\x04"
+    ));
+    assert!(!matches(
+        WordBoundary::Wide,
+        r"/\bfoo\b/ wide",
+        b"A\x00f\x00o\x00o\x00"
+    ));
+    assert!(!matches(
+        WordBoundary::Wide,
+        r"/\bfoo\b/ wide",
+        b"f\x00o\x00o\x00_\x00"
+    ));
+
+    // Non-wide patterns are not affected by `WordBoundary::Wide`.
+    assert!(matches(WordBoundary::Wide, r"/\bfoo\b/", b"\x41\x04foo\x00"));
+    assert!(!matches(WordBoundary::Wide, r"/\bfoo\b/", b"Afoo"));
+}
+
+#[test]
+fn hex_alternatives_with_jumps() {
+    pattern_match!(
+        r#"{ (AA [1-3] BB | CC) DD }"#,
+        &[0x00, 0xAA, 0x01, 0x02, 0xBB, 0xDD],
+        &[0xAA, 0x01, 0x02, 0xBB, 0xDD]
+    );
+    pattern_match!(
+        r#"{ (AA [1-3] BB | CC) DD }"#,
+        &[0x00, 0xCC, 0xDD],
+        &[0xCC, 0xDD]
+    );
+    pattern_false!(r#"{ (AA [1-3] BB | CC) DD }"#, &[0xAA, 0xBB, 0xDD]);
+    pattern_false!(
+        r#"{ (AA [1-3] BB | CC) DD }"#,
+        &[0xAA, 0x01, 0x02, 0x03, 0x04, 0xBB, 0xDD]
+    );
+
+    // Nested alternatives with jumps of different lengths.
+    pattern_match!(
+        r#"{ (AA [1-3] (BB [2] EE | FF) | CC [-] 00) DD }"#,
+        &[0xAA, 0x01, 0xBB, 0x01, 0x02, 0xEE, 0xDD],
+        &[0xAA, 0x01, 0xBB, 0x01, 0x02, 0xEE, 0xDD]
+    );
+    pattern_match!(
+        r#"{ (AA [1-3] (BB [2] EE | FF) | CC [-] 00) DD }"#,
+        &[0xAA, 0x01, 0x02, 0x03, 0xFF, 0xDD],
+        &[0xAA, 0x01, 0x02, 0x03, 0xFF, 0xDD]
+    );
+    pattern_match!(
+        r#"{ (AA [1-3] (BB [2] EE | FF) | CC [-] 00) DD }"#,
+        &[0xCC, 0x01, 0x02, 0x03, 0x00, 0xDD],
+        &[0xCC, 0x01, 0x02, 0x03, 0x00, 0xDD]
+    );
+    pattern_false!(
+        r#"{ (AA [1-3] (BB [2] EE | FF) | CC [-] 00) DD }"#,
+        &[0xAA, 0x01, 0xBB, 0x01, 0xEE, 0xDD]
+    );
+
+    // Large jumps inside alternatives.
+    pattern_match!(
+        r#"{ 11 (AA [1-300] (BB [2] EE | FF) | CC [0-2] 00) DD }"#,
+        &[0x11, 0xCC, 0x00, 0xDD],
+        &[0x11, 0xCC, 0x00, 0xDD]
+    );
+
+    let data = [&[0x11, 0xAA], [0x00; 250].as_slice(), &[0xFF, 0xDD]].concat();
+
+    pattern_match!(
+        r#"{ 11 (AA [1-300] (BB [2] EE | FF) | CC [0-2] 00) DD }"#,
+        data.as_slice(),
+        data.as_slice()
+    );
 }
 
 #[test]
@@ -1910,6 +2279,66 @@ fn match_at() {
         b"foobar"
     );
 
+    rule_true!(
+        r#"
+        rule test {
+            strings:
+                $a = "foo"
+            condition:
+                $a at filesize - 3
+        }
+        "#,
+        b"barfoo"
+    );
+
+    rule_false!(
+        r#"
+        rule test {
+            strings:
+                $a = "foo"
+            condition:
+                $a at filesize - 3
+        }
+        "#,
+        b"foobar"
+    );
+
+    rule_false!(
+        r#"
+        rule test {
+            strings:
+                $a = "foo"
+            condition:
+                $a at filesize - 10
+        }
+        "#,
+        b"foobar"
+    );
+
+    rule_true!(
+        r#"
+        rule test {
+            strings:
+                $a = "foo"
+            condition:
+                $a at filesize - 3 and $a at 0
+        }
+        "#,
+        b"foofoo"
+    );
+
+    rule_true!(
+        r#"
+        rule test {
+            strings:
+                $a = { 00 00 00 00 }
+            condition:
+                $a at filesize - 4 and not $a at 0
+        }
+        "#,
+        b"\x00\x00\x00\x01\x00\x00\x00\x00"
+    );
+
     #[cfg(feature = "test_proto2-module")]
     rule_false!(
         r#"
@@ -2293,6 +2722,22 @@ fn match_offset() {
         "#,
         b"foo"
     );
+
+    rule_true!(
+        r#"
+        rule test {
+            strings:
+                $a = "foo"
+            condition:
+                // Indexes greater than the number of matches produce an
+                // undefined value.
+                defined @a[2] and not defined @a[3] and
+                defined !a[2] and not defined !a[3] and
+                #a in (0..5) == 2 and #a in (6..100) == 0
+        }
+        "#,
+        b"foofoo"
+    );
 }
 
 #[test]
@@ -2674,6 +3119,40 @@ fn base64() {
         b"Xk7tWkDw"
     );
 
+    pattern_true!(
+        r#""foobar" base64("./ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789")"#,
+        b"cEXtZ0Hfae" // base64("xfoobar") with custom alphabet
+    );
+
+    pattern_true!(
+        r#""foobar" base64("./ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789")"#,
+        b"cFfkZ07gWVG" // base64("xxfoobar") with custom alphabet
+    );
+
+    // With a custom alphabet the pattern doesn't match the data encoded
+    // with the standard alphabet.
+    pattern_false!(
+        r#""foobar" base64("./ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789")"#,
+        b"Zm9vYmFy" // base64("foobar")
+    );
+
+    pattern_true!(
+        r#""foobar" base64wide("./ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789")"#,
+        // base64("foobar") with custom alphabet in wide form
+        b"X\x00k\x007\x00t\x00W\x00k\x00D\x00w\x00"
+    );
+
+    pattern_true!(
+        r#""foobar" base64wide("./ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789")"#,
+        // base64("xxfoobar") with custom alphabet in wide form
+        b"c\x00F\x00f\x00k\x00Z\x000\x007\x00g\x00W\x00V\x00G\x00"
+    );
+
+    pattern_false!(
+        r#""foobar" base64wide("./ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789")"#,
+        b"Xk7tWkDw" // base64("foobar") with custom alphabet, not wide
+    );
+
     // When `base64` is combined with `wide` the latter if applied first,
     // so it must match base64("f\x00o\x00o\x00b\x00a\x00r\x00").
     pattern_true!(
@@ -3143,6 +3622,101 @@ fn of() {
     );
 }
 
+#[test]
+fn of_percentage() {
+    let rule = |condition: &str| {
+        format!(
+            r#"
+            rule test {{
+              strings:
+                $a = "foo"
+                $b = "bar"
+                $c = "baz"
+              condition: {condition}
+            }}
+            "#
+        )
+    };
+
+    // The number of required patterns is rounded up, 34% of 3 patterns
+    // means 2 patterns.
+    rule_true!(rule("33% of them").as_str(), b"foo");
+    rule_false!(rule("34% of them").as_str(), b"foo");
+    rule_true!(rule("34% of them").as_str(), b"foobar");
+    rule_true!(rule("66% of ($a, $b) and not $c").as_str(), b"foobar");
+    rule_false!(rule("51% of ($a, $b) and not $c").as_str(), b"foo");
+    rule_true!(rule("50% of ($a, $b) and not $c").as_str(), b"foo");
+
+    // Percentages not known at compile time.
+    rule_true!(rule("(filesize * 10)% of them").as_str(), b"foobar");
+    rule_false!(rule("(filesize * 10)% of them").as_str(), b"foobarba");
+    rule_true!(rule("(filesize * 10)% of them").as_str(), b"foobarbaz");
+
+    // Percentages over boolean expressions.
+    rule_true!(rule("50% of (true, false) and none of them").as_str(), b"");
+    rule_false!(rule("51% of (true, false) and none of them").as_str(), b"");
+}
+
+#[test]
+fn of_rules() {
+    let rule = |condition: &str| {
+        format!(
+            r#"
+            private rule family_a {{ condition: true }}
+            private rule family_b {{ condition: false }}
+            private rule family_c {{ condition: true }}
+            private rule other {{ condition: false }}
+            rule test {{ condition: {condition} }}
+            "#
+        )
+    };
+
+    rule_true!(rule("2 of (family_*)").as_str());
+    rule_false!(rule("all of (family_*)").as_str());
+    rule_true!(rule("any of (other, family_c*)").as_str());
+    rule_false!(rule("any of (other, family_b*)").as_str());
+    rule_true!(rule("2 of (default.*)").as_str());
+    rule_false!(rule("3 of (default.*)").as_str());
+    rule_true!(rule("none of (default.other*)").as_str());
+    rule_true!(rule("50% of (family_*)").as_str());
+    rule_true!(rule("66% of (family_*)").as_str());
+    rule_false!(rule("67% of (family_*)").as_str());
+    rule_true!(rule("0% of (other)").as_str());
+
+    // Rules matching more than one item are counted only once.
+    rule_false!(rule("3 of (family_a, family_*)").as_str());
+
+    // Rules in other namespaces can be referenced by qualifying them with
+    // the namespace.
+    let mut compiler = crate::Compiler::new();
+
+    compiler
+        .new_namespace("foo")
+        .add_source("rule foo_1 { condition: true }")
+        .unwrap()
+        .new_namespace("bar")
+        .add_source("rule bar_1 { condition: all of (foo.*) }")
+        .unwrap()
+        .add_source("rule bar_2 { condition: any of (foo.foo*, bar_1) }")
+        .unwrap();
+
+    assert!(compiler
+        .add_source("rule bar_3 { condition: any of (foo_*) }")
+        .is_err());
+
+    let rules = compiler.build();
+    let mut scanner = crate::scanner::Scanner::new(&rules);
+    let scan_results = scanner.scan(&[]).unwrap();
+
+    assert_eq!(
+        scan_results
+            .matching_rules()
+            .map(|r| r.identifier())
+            .collect::<Vec<_>>(),
+        vec!["foo_1", "bar_1", "bar_2"]
+    );
+}
+
 #[test]
 fn rule_reuse_1() {
     let rules = crate::compile(
@@ -3320,6 +3894,28 @@ fn test_defined_2() {
     );
 }
 
+#[test]
+fn test_defined_4() {
+    // Reading beyond the end of the scanned data produces an undefined value.
+    rule_true!(
+        r#"rule test { condition: defined uint8(0) and defined uint16(1) }"#,
+        b"foo"
+    );
+    rule_true!(r#"rule test { condition: not defined uint32(0) }"#, b"foo");
+    rule_true!(
+        r#"rule test { condition: not defined uint8(filesize) }"#,
+        b"foo"
+    );
+    rule_true!(
+        r#"rule test { condition: not defined (uint16be(filesize - 1) == 0) }"#,
+        b"foo"
+    );
+    rule_true!(
+        r#"rule test { condition: defined uint8(filesize) or true }"#,
+        b"foo"
+    );
+}
+
 #[test]
 #[cfg(feature = "test_proto3-module")]
 fn test_defined_3() {
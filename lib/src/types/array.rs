@@ -11,17 +11,43 @@ pub(crate) enum Array {
     Floats(Vec<f64>),
     Bools(Vec<bool>),
     Strings(Vec<Rc<BString>>),
-    Structs(Vec<Rc<Struct>>),
+    Structs {
+        // The deputy is a structure that acts as a representative of the
+        // items in the array. It has the same fields as the actual items,
+        // but those fields contain no data. The deputy is optional because
+        // it is present only at compile time, when the `items` vector is
+        // empty. This way the type of the items is known even if the array
+        // doesn't contain any data.
+        deputy: Option<Rc<Struct>>,
+        items: Vec<Rc<Struct>>,
+    },
 }
 
 impl Array {
+    /// Returns a value that acts as a representative of the items in the
+    /// array. This value only contains type information, not actual data.
+    ///
+    /// # Panics
+    ///
+    /// If this is an array of structures that doesn't have a deputy and
+    /// doesn't contain any items.
     pub fn deputy(&self) -> TypeValue {
+        self.try_deputy().unwrap()
+    }
+
+    /// Like [`Array::deputy`], but returns `None` if the type of the items
+    /// is unknown. This happens only with arrays of structures that don't
+    /// have a deputy and don't contain any items.
+    pub fn try_deputy(&self) -> Option<TypeValue> {
         match self {
-            Array::Integers(_) => TypeValue::Integer(Value::Unknown),
-            Array::Floats(_) => TypeValue::Float(Value::Unknown),
-            Array::Bools(_) => TypeValue::Bool(Value::Unknown),
-            Array::Strings(_) => TypeValue::String(Value::Unknown),
-            Array::Structs(s) => TypeValue::Struct(s.first().unwrap().clone()),
+            Array::Integers(_) => Some(TypeValue::Integer(Value::Unknown)),
+            Array::Floats(_) => Some(TypeValue::Float(Value::Unknown)),
+            Array::Bools(_) => Some(TypeValue::Bool(Value::Unknown)),
+            Array::Strings(_) => Some(TypeValue::String(Value::Unknown)),
+            Array::Structs { deputy, items } => deputy
+                .as_ref()
+                .or(items.first())
+                .map(|s| TypeValue::Struct(s.clone())),
         }
     }
 
@@ -31,7 +57,7 @@ impl Array {
             Array::Floats(a) => a.len(),
             Array::Bools(a) => a.len(),
             Array::Strings(a) => a.len(),
-            Array::Structs(a) => a.len(),
+            Array::Structs { items, .. } => items.len(),
         }
     }
 
@@ -68,8 +94,8 @@ impl Array {
     }
 
     pub fn as_struct_array(&self) -> &Vec<Rc<Struct>> {
-        if let Self::Structs(v) = self {
-            v
+        if let Self::Structs { items, .. } = self {
+            items
         } else {
             panic!()
         }
@@ -37,6 +37,20 @@ impl Map {
         }
     }
 
+    /// Returns the deputy value if present, or any of the values in the map
+    /// otherwise. Returns `None` if the map doesn't have a deputy and is
+    /// empty.
+    pub fn try_deputy(&self) -> Option<TypeValue> {
+        match self {
+            Map::IntegerKeys { deputy, map } => {
+                deputy.as_ref().or_else(|| map.values().next()).cloned()
+            }
+            Map::StringKeys { deputy, map } => {
+                deputy.as_ref().or_else(|| map.values().next()).cloned()
+            }
+        }
+    }
+
     /// Returns the map as an [`IndexMap`] with integer keys.
     ///
     /// # Panics
@@ -1,4 +1,5 @@
 use std::fmt::{Debug, Display, Formatter};
+use std::mem;
 use std::rc::Rc;
 
 use bstr::BString;
@@ -209,19 +210,30 @@ impl TypeValue {
             (Self::Float(_), Self::Float(_)) => true,
             (Self::String(_), Self::String(_)) => true,
             (Self::Bool(_), Self::Bool(_)) => true,
+            // Arrays and maps that don't have a deputy nor items don't have
+            // information about the type of their items. They are
+            // considered equal to any other array or map of the same kind.
             (Self::Array(a), Self::Array(b)) => {
-                a.deputy().eq_type(&b.deputy())
+                match (a.try_deputy(), b.try_deputy()) {
+                    (Some(a), Some(b)) => a.eq_type(&b),
+                    _ => {
+                        mem::discriminant(a.as_ref())
+                            == mem::discriminant(b.as_ref())
+                    }
+                }
             }
             (Self::Map(a), Self::Map(b)) => match (a.as_ref(), b.as_ref()) {
-                (Map::StringKeys { .. }, Map::StringKeys { .. }) => {
-                    a.deputy().eq_type(&b.deputy())
-                }
-                (Map::IntegerKeys { .. }, Map::IntegerKeys { .. }) => {
-                    a.deputy().eq_type(&b.deputy())
+                (Map::StringKeys { .. }, Map::StringKeys { .. })
+                | (Map::IntegerKeys { .. }, Map::IntegerKeys { .. }) => {
+                    match (a.try_deputy(), b.try_deputy()) {
+                        (Some(a), Some(b)) => a.eq_type(&b),
+                        _ => true,
+                    }
                 }
                 _ => false,
             },
             (Self::Struct(a), Self::Struct(b)) => a.eq(b),
+            (Self::Func(a), Self::Func(b)) => a.signatures() == b.signatures(),
             _ => false,
         }
     }
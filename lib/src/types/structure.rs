@@ -562,12 +562,29 @@ impl Struct {
     ///
     /// What this function returns is the value associated to an enum item,
     /// returning the value set via the `(yara.enum_value).i64` option, if any,
-    /// or the tag number.
+    /// or the tag number. Tag numbers are sign-extended, so negative tag
+    /// numbers produce negative values, while values set via the option are
+    /// used verbatim.
     fn enum_value(enum_value_descriptor: &EnumValueDescriptor) -> i64 {
         enum_value
             .get(&enum_value_descriptor.proto().options)
             .and_then(|options| options.i64)
-            .unwrap_or_else(|| enum_value_descriptor.value() as i64)
+            .unwrap_or_else(|| i64::from(enum_value_descriptor.value()))
+    }
+
+    /// Given an [`EnumDescriptor`] and the tag number of one of its items,
+    /// returns the value associated to the item, as returned by
+    /// [`Struct::enum_value`].
+    ///
+    /// Fields of enum type contain the tag number of the item, which is not
+    /// necessarily the value of the item in YARA. If the enum doesn't have an
+    /// item with the given tag number (which can happen with proto3 open
+    /// enums), the tag number is returned.
+    fn enum_item_value(enum_descriptor: &EnumDescriptor, number: i32) -> i64 {
+        enum_descriptor
+            .value_by_number(number)
+            .map(|item| Self::enum_value(&item))
+            .unwrap_or_else(|| i64::from(number))
     }
 
     /// Given a [`FieldDescriptor`] returns the name that this field will
@@ -811,12 +828,17 @@ impl Struct {
                     Array::Strings(vec![])
                 }
             }
-            RuntimeType::Enum(_) => {
+            RuntimeType::Enum(enum_descriptor) => {
                 if let Some(repeated) = repeated {
                     Array::Integers(
                         repeated
                             .into_iter()
-                            .map(|value| value.to_enum_value().unwrap() as i64)
+                            .map(|value| {
+                                Self::enum_item_value(
+                                    enum_descriptor,
+                                    value.to_enum_value().unwrap(),
+                                )
+                            })
                             .collect(),
                     )
                 } else {
@@ -825,8 +847,9 @@ impl Struct {
             }
             RuntimeType::Message(msg_descriptor) => {
                 if let Some(repeated) = repeated {
-                    Array::Structs(
-                        repeated
+                    Array::Structs {
+                        deputy: None,
+                        items: repeated
                             .into_iter()
                             .map(|value| {
                                 Rc::new(Self::from_proto_descriptor_and_value(
@@ -836,15 +859,18 @@ impl Struct {
                                 ))
                             })
                             .collect(),
-                    )
+                    }
                 } else {
-                    Array::Structs(vec![Rc::new(
-                        Struct::from_proto_descriptor_and_msg(
-                            msg_descriptor,
-                            None,
-                            enum_as_fields,
-                        ),
-                    )])
+                    Array::Structs {
+                        deputy: Some(Rc::new(
+                            Struct::from_proto_descriptor_and_msg(
+                                msg_descriptor,
+                                None,
+                                enum_as_fields,
+                            ),
+                        )),
+                        items: vec![],
+                    }
                 }
             }
         };
@@ -971,7 +997,7 @@ impl Struct {
             ReflectValueRef::U64(v) => v as i64,
             ReflectValueRef::I32(v) => v as i64,
             ReflectValueRef::I64(v) => v,
-            ReflectValueRef::Enum(_, v) => v as i64,
+            ReflectValueRef::Enum(d, v) => Self::enum_item_value(&d, v),
             _ => panic!(),
         }
     }
@@ -1090,4 +1116,259 @@ mod tests {
         // on each structure.
         assert_ne!(a, b);
     }
+
+    #[test]
+    fn struct_array_deputy() {
+        use protobuf::MessageFull;
+
+        use crate::modules::protos::test_proto2::TestProto2;
+
+        // A structure created from the descriptor alone, without data.
+        let s = Struct::from_proto_descriptor_and_msg(
+            &TestProto2::descriptor(),
+            None,
+            true,
+        );
+
+        let array = match &s.field_by_name("array_struct").unwrap().type_value
+        {
+            TypeValue::Array(array) => array.clone(),
+            _ => panic!(),
+        };
+
+        // The array is empty, but its deputy has the type of the items.
+        assert_eq!(array.len(), 0);
+
+        let deputy = match array.deputy() {
+            TypeValue::Struct(deputy) => deputy,
+            _ => panic!(),
+        };
+
+        assert_eq!(
+            deputy.field_by_name("nested_int64_one").unwrap().type_value.ty(),
+            Type::Integer
+        );
+
+        assert_eq!(
+            deputy
+                .field_by_name("nested_array_int64")
+                .unwrap()
+                .type_value
+                .ty(),
+            Type::Array
+        );
+    }
+
+    /// A tiny pseudo-random number generator (xorshift64), good enough for
+    /// generating test data in a reproducible way.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    /// Returns a random value of the given type.
+    fn random_value(
+        ty: &protobuf::reflect::RuntimeType,
+        rng: &mut Rng,
+    ) -> protobuf::reflect::ReflectValueBox {
+        use protobuf::reflect::{ReflectValueBox, RuntimeType};
+        match ty {
+            RuntimeType::I32 => ReflectValueBox::I32(rng.next() as i32),
+            RuntimeType::I64 => ReflectValueBox::I64(rng.next() as i64),
+            RuntimeType::U32 => ReflectValueBox::U32(rng.next() as u32),
+            RuntimeType::U64 => ReflectValueBox::U64(rng.next() >> 1),
+            RuntimeType::F32 => {
+                ReflectValueBox::F32(rng.below(1000) as f32 / 8.0)
+            }
+            RuntimeType::F64 => {
+                ReflectValueBox::F64(rng.below(1000) as f64 / 8.0)
+            }
+            RuntimeType::Bool => ReflectValueBox::Bool(rng.below(2) == 1),
+            RuntimeType::String => {
+                ReflectValueBox::String(format!("s{}", rng.below(100)))
+            }
+            RuntimeType::VecU8 => {
+                ReflectValueBox::Bytes(vec![rng.below(256) as u8; 3])
+            }
+            RuntimeType::Enum(e) => {
+                let values = e.values().collect::<Vec<_>>();
+                let value = &values[rng.below(values.len() as u64) as usize];
+                ReflectValueBox::Enum(e.clone(), value.value())
+            }
+            RuntimeType::Message(m) => {
+                ReflectValueBox::Message(random_message(m, rng))
+            }
+        }
+    }
+
+    /// Returns a random message of the given type. Each field is set with
+    /// probability 1/2, repeated fields and maps have 0 to 2 items.
+    fn random_message(
+        msg_descriptor: &protobuf::reflect::MessageDescriptor,
+        rng: &mut Rng,
+    ) -> Box<dyn protobuf::MessageDyn> {
+        use protobuf::reflect::RuntimeFieldType;
+        let mut msg = msg_descriptor.new_instance();
+        for fd in msg_descriptor.fields() {
+            match fd.runtime_field_type() {
+                RuntimeFieldType::Singular(ty) => {
+                    if rng.below(2) == 0 {
+                        fd.set_singular_field(
+                            msg.as_mut(),
+                            random_value(&ty, rng),
+                        );
+                    }
+                }
+                RuntimeFieldType::Repeated(ty) => {
+                    for _ in 0..rng.below(3) {
+                        fd.mut_repeated(msg.as_mut())
+                            .push(random_value(&ty, rng));
+                    }
+                }
+                RuntimeFieldType::Map(key_ty, value_ty) => {
+                    for _ in 0..rng.below(3) {
+                        fd.mut_map(msg.as_mut()).insert(
+                            random_value(&key_ty, rng),
+                            random_value(&value_ty, rng),
+                        );
+                    }
+                }
+            }
+        }
+        msg
+    }
+
+    /// Checks that the value of every field in `msg` was correctly
+    /// translated into the corresponding field in `s`.
+    fn check_values(
+        msg_descriptor: &protobuf::reflect::MessageDescriptor,
+        msg: &dyn protobuf::MessageDyn,
+        s: &Struct,
+    ) {
+        use protobuf::reflect::{ReflectFieldRef, ReflectValueRef};
+
+        fn check_value(value: ReflectValueRef, type_value: &TypeValue) {
+            match (value, type_value) {
+                (ReflectValueRef::Message(m), TypeValue::Struct(s)) => {
+                    check_values(&m.descriptor_dyn(), &*m, s)
+                }
+                (ReflectValueRef::F32(f), TypeValue::Float(v)) => {
+                    assert_eq!(v.extract(), Some(&(f as f64)))
+                }
+                (ReflectValueRef::F64(f), TypeValue::Float(v)) => {
+                    assert_eq!(v.extract(), Some(&f))
+                }
+                (ReflectValueRef::Bool(b), TypeValue::Bool(v)) => {
+                    assert_eq!(v.extract(), Some(&b))
+                }
+                (ReflectValueRef::String(x), TypeValue::String(v)) => {
+                    assert_eq!(
+                        v.extract().map(|s| s.as_slice()),
+                        Some(x.as_bytes())
+                    )
+                }
+                (ReflectValueRef::Bytes(x), TypeValue::String(v)) => {
+                    assert_eq!(v.extract().map(|s| s.as_slice()), Some(x))
+                }
+                (value, TypeValue::Integer(v)) => {
+                    assert_eq!(v.extract(), Some(&Struct::value_as_i64(value)))
+                }
+                (value, type_value) => {
+                    panic!("unexpected value {:?} for {:?}", value, type_value)
+                }
+            }
+        }
+
+        for fd in msg_descriptor.fields() {
+            if Struct::ignore_field(&fd) {
+                continue;
+            }
+            let field = s
+                .field_by_name(Struct::field_name(&fd).as_str())
+                .unwrap_or_else(|| panic!("missing field `{}`", fd.name()));
+            match (fd.get_reflect(msg), &field.type_value) {
+                (ReflectFieldRef::Optional(value), type_value) => {
+                    if let Some(value) = value.value() {
+                        check_value(value, type_value)
+                    }
+                }
+                (ReflectFieldRef::Repeated(repeated), TypeValue::Array(a)) => {
+                    assert_eq!(repeated.len(), a.len());
+                    for (i, value) in repeated.into_iter().enumerate() {
+                        let item = match a.as_ref() {
+                            Array::Integers(v) => {
+                                TypeValue::var_integer_from(v[i])
+                            }
+                            Array::Floats(v) => {
+                                TypeValue::var_float_from(v[i])
+                            }
+                            Array::Bools(v) => TypeValue::var_bool_from(v[i]),
+                            Array::Strings(v) => {
+                                TypeValue::String(Value::Var(v[i].clone()))
+                            }
+                            Array::Structs { items, .. } => {
+                                TypeValue::Struct(items[i].clone())
+                            }
+                        };
+                        check_value(value, &item);
+                    }
+                }
+                (ReflectFieldRef::Map(map), TypeValue::Map(m)) => {
+                    assert_eq!(map.len(), m.len());
+                }
+                (_, type_value) => {
+                    panic!(
+                        "unexpected type {:?} for `{}`",
+                        type_value,
+                        fd.name()
+                    )
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_messages() {
+        use crate::modules::BUILTIN_MODULES;
+
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+
+        // For every module, generate random messages and check that the
+        // structures created from them are consistent with the structure
+        // created from the descriptor alone, which is the one used while
+        // compiling rules.
+        for module in BUILTIN_MODULES.values() {
+            let msg_descriptor = &module.root_struct_descriptor;
+            // The structure created from the descriptor alone.
+            let deputy = Struct::from_proto_descriptor_and_msg(
+                msg_descriptor,
+                None,
+                true,
+            );
+
+            for _ in 0..100 {
+                let msg = random_message(msg_descriptor, &mut rng);
+                let s = Struct::from_proto_descriptor_and_msg(
+                    msg_descriptor,
+                    Some(msg.as_ref()),
+                    true,
+                );
+                // Both structures must have the same fields, with the same
+                // types.
+                assert!(deputy == s);
+                // Values in the message must be present in the structure.
+                check_values(msg_descriptor, msg.as_ref(), &s);
+            }
+        }
+    }
 }
@@ -211,7 +211,10 @@ impl TryFrom<&serde_json::Value> for Variable {
                         array = Some(Array::Strings(Vec::new()));
                         break;
                     } else if v.is_object() {
-                        array = Some(Array::Structs(Vec::new()));
+                        array = Some(Array::Structs {
+                            deputy: None,
+                            items: Vec::new(),
+                        });
                         break;
                     } else if v.is_array() {
                         // Arrays can't be nested.
@@ -276,7 +279,7 @@ impl TryFrom<&serde_json::Value> for Variable {
                             };
                         }
                     }
-                    Array::Structs(ref mut structs) => {
+                    Array::Structs { items: ref mut structs, .. } => {
                         for v in values {
                             match v.as_object() {
                                 Some(v) => {
@@ -156,6 +156,10 @@ impl WasmModuleBuilder {
         global_var!(module, filesize, I64);
         global_var!(module, pattern_search_done, I32);
         global_var!(module, timeout_occurred, I32);
+        global_var!(module, loop_iterations, I64);
+        global_var!(module, max_loop_iterations, I64);
+        global_var!(module, loop_limit, I64);
+        global_var!(module, rules_evaluated, I64);
 
         let (main_memory, _) = module.add_import_memory(
             "yara_x",
@@ -173,6 +177,10 @@ impl WasmModuleBuilder {
             filesize,
             pattern_search_done,
             timeout_occurred,
+            loop_iterations,
+            max_loop_iterations,
+            loop_limit,
+            rules_evaluated,
             i64_tmp_a: module.locals.add(I64),
             i64_tmp_b: module.locals.add(I64),
             i32_tmp: module.locals.add(I32),
@@ -283,7 +291,7 @@ impl WasmModuleBuilder {
             |then_| {
                 // The condition is false. For normal rules we don't do anything,
                 // but for global rules we must call `global_rule_no_match`, and
-                // return 1.
+                // return 1 if `global_rule_no_match` returns true.
                 //
                 // By returning 1 the function that contains the logic for this
                 // rule exits immediately, preventing that any other rule in the
@@ -292,15 +300,22 @@ impl WasmModuleBuilder {
                 // This guarantees that any global rule that returns false, forces
                 // any other rule in the same namespace to be false. The purpose of
                 // calling  `global_rule_no_match` is reverting any previous matches
-                // that occurred in the same namespace.
+                // that occurred in the same namespace. `global_rule_no_match`
+                // returns false when the rule was disabled at scan time, in that
+                // case the remaining rules are executed as usual.
                 if self.global_rule {
                     then_
                         // Call `global_rule_no_match`.
                         .i32_const(self.rule_id.into())
                         .call(global_rule_no_match)
-                        // Return 1.
-                        .i32_const(1)
-                        .return_();
+                        .if_else(
+                            None,
+                            |then_| {
+                                // Return 1.
+                                then_.i32_const(1).return_();
+                            },
+                            |_| {},
+                        );
                 }
             },
             |else_| {
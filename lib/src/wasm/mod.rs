@@ -94,7 +94,7 @@ use yara_x_macros::wasm_export;
 
 use crate::compiler::{LiteralId, PatternId, RegexpId, RuleId};
 use crate::modules::BUILTIN_MODULES;
-use crate::scanner::{RuntimeObjectHandle, ScanContext, ScanError};
+use crate::scanner::{RuntimeObjectHandle, ScanContext, ScanPhase};
 use crate::types::{
     Array, Func, FuncSignature, Map, Struct, TypeValue, Value,
 };
@@ -154,9 +154,9 @@ impl WasmExport {
     /// The fully qualified name includes not only the function's name, but
     /// also the module's name (e.g: `my_module.my_struct.my_func@ii@i`)
     pub fn fully_qualified_mangled_name(&self) -> String {
-        for (module_name, module) in BUILTIN_MODULES.iter() {
-            if let Some(rust_module_name) = module.rust_module_name {
-                if self.rust_module_path.contains(rust_module_name) {
+        if let Some(rust_module_name) = self.rust_module_name() {
+            for (module_name, module) in BUILTIN_MODULES.iter() {
+                if module.rust_module_name == Some(rust_module_name) {
                     return format!("{}.{}", module_name, self.mangled_name);
                 }
             }
@@ -164,6 +164,15 @@ impl WasmExport {
         self.mangled_name.to_owned()
     }
 
+    /// Returns the name of the Rust module where the function resides, if
+    /// the function belongs to a YARA module (e.g: `test_proto2` for
+    /// functions in `yara_x::modules::test_proto2` and its submodules).
+    pub fn rust_module_name(&self) -> Option<&'static str> {
+        self.rust_module_path
+            .strip_prefix("yara_x::modules::")
+            .and_then(|path| path.split("::").next())
+    }
+
     /// Returns true if this export comes from YARA itself, not for a YARA
     /// module.
     pub fn builtin(&self) -> bool {
@@ -698,6 +707,23 @@ pub(crate) struct WasmSymbols {
     /// phase.
     pub timeout_occurred: walrus::GlobalId,
 
+    /// Global variable that counts the loop iterations executed while
+    /// evaluating the current rule's condition.
+    pub loop_iterations: walrus::GlobalId,
+
+    /// Global variable that contains the maximum number of loop iterations
+    /// allowed while evaluating a rule's condition.
+    pub max_loop_iterations: walrus::GlobalId,
+
+    /// Global variable that contains the maximum number of loop iterations
+    /// allowed for the current rule. It is set to `max_loop_iterations`
+    /// before evaluating each rule, and to zero when the rule times out.
+    pub loop_limit: walrus::GlobalId,
+
+    /// Global variable that counts the rules whose conditions were evaluated
+    /// during the current scan.
+    pub rules_evaluated: walrus::GlobalId,
+
     /// Local variables used for temporary storage.
     pub i64_tmp_a: walrus::LocalId,
     pub i64_tmp_b: walrus::LocalId,
@@ -768,18 +794,43 @@ pub(crate) fn log_rule_eval_start(
 
 /// Invoked from WASM for triggering the pattern search phase.
 ///
-/// Returns `true` on success and `false` when a timeout occurs.
+/// Returns `true` on success and `false` when the search is aborted, either
+/// due to a timeout or because the memory limit was exceeded. In the latter
+/// case the error is stored in [`ScanContext::search_error`].
 #[wasm_export]
 pub(crate) fn search_for_patterns(
     caller: &mut Caller<'_, ScanContext>,
 ) -> bool {
-    match caller.data_mut().search_for_patterns() {
-        Ok(_) => true,
-        Err(ScanError::Timeout) => false,
-        Err(_) => unreachable!(),
+    let ctx = caller.data_mut();
+    match ctx.search_for_patterns() {
+        Ok(_) => {
+            // The evaluation of conditions resumes after the pattern search.
+            ctx.report_progress(
+                ScanPhase::ConditionEvaluation,
+                ctx.scanned_data_len,
+            );
+            true
+        }
+        Err(err) => {
+            caller.data_mut().search_error = Some(err);
+            false
+        }
     }
 }
 
+/// Invoked from WASM every 1024 loop iterations while evaluating the
+/// condition of the rule identified by the given [`RuleId`].
+///
+/// Returns `true` if the rule exceeded the time limit set with
+/// [`crate::Scanner::set_rule_timeout`], which aborts the rule's loops.
+#[wasm_export]
+pub(crate) fn rule_timeout_check(
+    caller: &mut Caller<'_, ScanContext>,
+    rule_id: RuleId,
+) -> bool {
+    caller.data_mut().check_rule_timeout(rule_id)
+}
+
 /// Invoked from WASM to notify when a rule matches.
 #[wasm_export]
 pub(crate) fn rule_match(
@@ -790,12 +841,36 @@ pub(crate) fn rule_match(
 }
 
 /// Invoked from WASM to notify when a global rule doesn't match.
+///
+/// Returns `true` if the remaining rules in the namespace must be skipped.
 #[wasm_export]
 pub(crate) fn global_rule_no_match(
     caller: &mut Caller<'_, ScanContext>,
     rule_id: RuleId,
+) -> bool {
+    caller.data_mut().track_global_rule_no_match(rule_id)
+}
+
+/// Invoked from WASM to record the value of a traced sub-expression of a
+/// rule condition. Returns the received value unchanged.
+#[wasm_export]
+pub(crate) fn trace_expr(
+    caller: &mut Caller<'_, ScanContext>,
+    trace_id: i32,
+    value: bool,
+) -> bool {
+    caller.data_mut().track_trace_value(trace_id as usize, Some(value));
+    value
+}
+
+/// Invoked from WASM to record that a traced sub-expression of a rule
+/// condition is undefined.
+#[wasm_export]
+pub(crate) fn trace_expr_undef(
+    caller: &mut Caller<'_, ScanContext>,
+    trace_id: i32,
 ) {
-    caller.data_mut().track_global_rule_no_match(rule_id);
+    caller.data_mut().track_trace_value(trace_id as usize, None);
 }
 
 /// Invoked from WASM to ask whether a pattern matches at a given file
@@ -1485,3 +1560,34 @@ gen_xint_fn!(int32, i32, from_le_bytes);
 gen_xint_fn!(int8be, i8, from_be_bytes);
 gen_xint_fn!(int16be, i16, from_be_bytes);
 gen_xint_fn!(int32be, i32, from_be_bytes);
+gen_xint_fn!(int64, i64, from_le_bytes);
+gen_xint_fn!(int64be, i64, from_be_bytes);
+
+// `uint64` and `uint64be` return the integer as an i64, which means that
+// values greater than i64::MAX are interpreted as negative numbers.
+gen_xint_fn!(uint64, u64, from_le_bytes);
+gen_xint_fn!(uint64be, u64, from_be_bytes);
+
+macro_rules! gen_float_fn {
+    ($name:ident, $return_type:ty, $from_fn:ident) => {
+        #[wasm_export(public = true)]
+        pub(crate) fn $name(
+            caller: &mut Caller<'_, ScanContext>,
+            offset: i64,
+        ) -> Option<f64> {
+            let offset = usize::try_from(offset).ok()?;
+            caller
+                .data()
+                .scanned_data()
+                .get(offset..offset + mem::size_of::<$return_type>())
+                .map(|bytes| {
+                    <$return_type>::$from_fn(bytes.try_into().unwrap()) as f64
+                })
+        }
+    };
+}
+
+gen_float_fn!(float32, f32, from_le_bytes);
+gen_float_fn!(float64, f64, from_le_bytes);
+gen_float_fn!(float32be, f32, from_be_bytes);
+gen_float_fn!(float64be, f64, from_be_bytes);
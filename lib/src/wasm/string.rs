@@ -230,8 +230,9 @@ impl RuntimeString {
         case_insensitive: bool,
     ) -> bool {
         if case_insensitive {
-            let this = self.as_bstr(ctx).to_lowercase();
-            let other = other.as_bstr(ctx).to_lowercase();
+            // Case-insensitive comparisons are ASCII-only, like in YARA.
+            let this = self.as_bstr(ctx).to_ascii_lowercase();
+            let other = other.as_bstr(ctx).to_ascii_lowercase();
             this.contains_str(other)
         } else {
             self.as_bstr(ctx).contains_str(other.as_bstr(ctx))
@@ -246,9 +247,10 @@ impl RuntimeString {
         case_insensitive: bool,
     ) -> bool {
         if case_insensitive {
-            let this = self.as_bstr(ctx).to_lowercase();
-            let other = other.as_bstr(ctx).to_lowercase();
-            this.starts_with_str(other)
+            let this = self.as_bstr(ctx);
+            let other = other.as_bstr(ctx);
+            this.len() >= other.len()
+                && this[..other.len()].eq_ignore_ascii_case(other)
         } else {
             self.as_bstr(ctx).starts_with_str(other.as_bstr(ctx))
         }
@@ -262,9 +264,10 @@ impl RuntimeString {
         case_insensitive: bool,
     ) -> bool {
         if case_insensitive {
-            let this = self.as_bstr(ctx).to_lowercase();
-            let other = other.as_bstr(ctx).to_lowercase();
-            this.ends_with_str(other)
+            let this = self.as_bstr(ctx);
+            let other = other.as_bstr(ctx);
+            this.len() >= other.len()
+                && this[this.len() - other.len()..].eq_ignore_ascii_case(other)
         } else {
             self.as_bstr(ctx).ends_with_str(other.as_bstr(ctx))
         }
@@ -278,9 +281,7 @@ impl RuntimeString {
         case_insensitive: bool,
     ) -> bool {
         if case_insensitive {
-            let this = self.as_bstr(ctx).to_lowercase();
-            let other = other.as_bstr(ctx).to_lowercase();
-            this.eq(&other)
+            self.as_bstr(ctx).eq_ignore_ascii_case(other.as_bstr(ctx))
         } else {
             self.as_bstr(ctx).eq(other.as_bstr(ctx))
         }
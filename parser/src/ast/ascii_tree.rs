@@ -60,6 +60,17 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
         Expr::False { .. } => Leaf(vec!["false".to_string()]),
         Expr::Entrypoint { .. } => Leaf(vec!["entrypoint".to_string()]),
         Expr::Filesize { .. } => Leaf(vec!["filesize".to_string()]),
+        // Literals that were not created by the parser have an empty
+        // `literal`, in that case the value is shown instead.
+        Expr::LiteralString(lit) if lit.literal.is_empty() => {
+            Leaf(vec![format!("{:?}", lit.value)])
+        }
+        Expr::LiteralFloat(lit) if lit.literal.is_empty() => {
+            Leaf(vec![lit.value.to_string()])
+        }
+        Expr::LiteralInteger(lit) if lit.literal.is_empty() => {
+            Leaf(vec![lit.value.to_string()])
+        }
         Expr::LiteralString(lit) => Leaf(vec![lit.literal.to_string()]),
         Expr::LiteralFloat(lit) => Leaf(vec![lit.literal.to_string()]),
         Expr::LiteralInteger(lit) => Leaf(vec![lit.literal.to_string()]),
@@ -300,6 +311,23 @@ pub(crate) fn expr_ascii_tree(expr: &Expr) -> Tree {
                     "<items: boolean_expr_set>".to_string(),
                     set.iter().map(expr_ascii_tree).collect(),
                 ),
+                OfItems::RuleSet(set) => Node(
+                    "<items: rule_set>".to_string(),
+                    vec![Leaf(
+                        set.iter()
+                            .map(|item| {
+                                format!(
+                                    "{}{}{}",
+                                    item.namespace
+                                        .map(|ns| format!("{}.", ns))
+                                        .unwrap_or_default(),
+                                    item.identifier,
+                                    if item.wildcard { "*" } else { "" }
+                                )
+                            })
+                            .collect(),
+                    )],
+                ),
             };
 
             let mut children = vec![
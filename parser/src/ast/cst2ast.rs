@@ -411,8 +411,22 @@ impl<'src> Builder<'src> {
         self.begin(IMPORT_STMT)?;
         let span = self.expect(IMPORT_KW)?;
         let (module_name, module_name_span) = self.utf8_string_lit()?;
+
+        let alias = if let Event::Token { kind: AS_KW, .. } = self.peek() {
+            self.expect(AS_KW)?;
+            Some(self.identifier()?)
+        } else {
+            None
+        };
+
         self.end(IMPORT_STMT)?;
-        Ok(Import { module_name, span: span.combine(&module_name_span) })
+
+        let span = match &alias {
+            Some(alias) => span.combine(&alias.span()),
+            None => span.combine(&module_name_span),
+        };
+
+        Ok(Import { module_name, alias, span })
     }
 
     fn rule_decl(&mut self) -> Result<Rule<'src>, Abort> {
@@ -1003,6 +1017,9 @@ impl<'src> Builder<'src> {
             Event::Begin(BOOLEAN_EXPR_TUPLE) => {
                 OfItems::BoolExprTuple(self.boolean_expr_tuple()?)
             }
+            Event::Begin(RULE_IDENT_TUPLE) => {
+                OfItems::RuleSet(self.rule_ident_tuple()?)
+            }
             event => panic!("unexpected {:?}", event),
         };
 
@@ -1415,6 +1432,51 @@ impl<'src> Builder<'src> {
         Ok(items)
     }
 
+    fn rule_ident_tuple(&mut self) -> Result<Vec<RuleSetItem<'src>>, Abort> {
+        self.begin(RULE_IDENT_TUPLE)?;
+        self.expect(L_PAREN)?;
+
+        let item = |s: &mut Self| -> Result<RuleSetItem<'src>, Abort> {
+            let ident = s.identifier()?;
+            let mut span = ident.span();
+            let mut namespace = None;
+            let mut identifier = ident.name;
+
+            if matches!(s.peek(), Event::Token { kind: DOT, .. }) {
+                span = span.combine(&s.expect(DOT)?);
+                namespace = Some(ident.name);
+                identifier = "";
+                if matches!(s.peek(), Event::Token { kind: IDENT, .. }) {
+                    let ident = s.identifier()?;
+                    span = span.combine(&ident.span());
+                    identifier = ident.name;
+                }
+            }
+
+            let wildcard =
+                if matches!(s.peek(), Event::Token { kind: ASTERISK, .. }) {
+                    span = span.combine(&s.expect(ASTERISK)?);
+                    true
+                } else {
+                    false
+                };
+
+            Ok(RuleSetItem { span, namespace, identifier, wildcard })
+        };
+
+        let mut items = vec![item(self)?];
+
+        while let Event::Token { kind: COMMA, .. } = self.peek() {
+            self.expect(COMMA)?;
+            items.push(item(self)?);
+        }
+
+        self.expect(R_PAREN)?;
+        self.end(RULE_IDENT_TUPLE)?;
+
+        Ok(items)
+    }
+
     fn integer_lit<T>(&mut self) -> Result<(T, &'src str, Span), Abort>
     where
         T: Num + Bounded + CheckedMul + FromPrimitive + std::fmt::Display,
@@ -0,0 +1,262 @@
+use crate::ast::{Expr, Iterable, MatchAnchor, OfItems, Quantifier};
+
+/// Each of the events produced by [`DepthFirstSearch`].
+#[derive(Debug)]
+pub enum DFSEvent<'a, 'src> {
+    /// An expression is entered, before visiting its children.
+    Enter(&'a Expr<'src>),
+    /// An expression is left, after visiting its children.
+    Leave(&'a Expr<'src>),
+}
+
+/// An iterator that conducts a Depth First Search (DFS) traversal of an
+/// expression in the AST.
+///
+/// This iterator yields [`DFSEvent::Enter`] when entering an expression, and
+/// [`DFSEvent::Leave`] upon exiting it. Children are visited in the same
+/// order in which they appear in the source code. For example, for the
+/// expression `a and (b or c)` the sequence of events would be:
+///
+/// ```text
+/// Enter(a and (b or c))
+/// Enter(a)
+/// Leave(a)
+/// Enter(b or c)
+/// Enter(b)
+/// Leave(b)
+/// Enter(c)
+/// Leave(c)
+/// Leave(b or c)
+/// Leave(a and (b or c))
+/// ```
+///
+/// This is the value returned by [`Expr::dfs_iter`].
+pub struct DepthFirstSearch<'a, 'src> {
+    stack: Vec<DFSEvent<'a, 'src>>,
+}
+
+impl<'a, 'src> DepthFirstSearch<'a, 'src> {
+    /// Creates a new [`DepthFirstSearch`] that traverses the given expression.
+    pub fn new(expr: &'a Expr<'src>) -> Self {
+        Self { stack: vec![DFSEvent::Enter(expr)] }
+    }
+
+    /// Prunes the search tree, preventing the traversal from visiting the
+    /// children of the current expression.
+    ///
+    /// If `prune` is called immediately after a [`DFSEvent::Enter`], none
+    /// of the children of the expression that was just entered are visited,
+    /// and the next event will be the corresponding [`DFSEvent::Leave`].
+    ///
+    /// If `prune` is called right after a [`DFSEvent::Leave`], the remaining
+    /// siblings of the expression that was just left are not visited, and
+    /// the next event will be the [`DFSEvent::Leave`] for its parent.
+    pub fn prune(&mut self) {
+        // Remove all DFSEvent::Enter from the stack until a DFSEvent::Leave.
+        while let Some(DFSEvent::Enter(_)) = self.stack.last() {
+            self.stack.pop();
+        }
+    }
+}
+
+impl<'a, 'src> Iterator for DepthFirstSearch<'a, 'src> {
+    type Item = DFSEvent<'a, 'src>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.stack.pop()?;
+
+        if let DFSEvent::Enter(expr) = next {
+            self.stack.push(DFSEvent::Leave(expr));
+            // Children are pushed in reverse order, so that they are
+            // popped from the stack in the order they appear in the code.
+            let mut children = Vec::new();
+            for_each_child(expr, |child| children.push(child));
+            for child in children.into_iter().rev() {
+                self.stack.push(DFSEvent::Enter(child));
+            }
+        }
+
+        Some(next)
+    }
+}
+
+/// Calls `$f` for each of the direct children of `$expr`, in the order in
+/// which they appear in the source code.
+///
+/// The children are passed to `$f` as `&Expr` or `&mut Expr`, depending on
+/// whether `mut` is specified.
+macro_rules! for_each_child {
+    ($expr:expr, $f:expr $(, $mut:tt)?) => {{
+        let f = $f;
+
+        macro_rules! quantifier {
+            ($quantifier:expr) => {
+                match $quantifier {
+                    Quantifier::Percentage(expr) | Quantifier::Expr(expr) => {
+                        f(expr)
+                    }
+                    Quantifier::None { .. }
+                    | Quantifier::All { .. }
+                    | Quantifier::Any { .. } => {}
+                }
+            };
+        }
+
+        macro_rules! anchor {
+            ($anchor:expr) => {
+                match $anchor {
+                    Some(MatchAnchor::At(at)) => f(& $($mut)? at.expr),
+                    Some(MatchAnchor::In(in_)) => {
+                        f(& $($mut)? in_.range.lower_bound);
+                        f(& $($mut)? in_.range.upper_bound);
+                    }
+                    None => {}
+                }
+            };
+        }
+
+        match $expr {
+            Expr::True { .. }
+            | Expr::False { .. }
+            | Expr::Filesize { .. }
+            | Expr::Entrypoint { .. }
+            | Expr::LiteralString(_)
+            | Expr::LiteralInteger(_)
+            | Expr::LiteralFloat(_)
+            | Expr::Regexp(_)
+            | Expr::Ident(_) => {}
+
+            Expr::PatternMatch(pattern_match) => {
+                anchor!(& $($mut)? pattern_match.anchor);
+            }
+
+            Expr::PatternCount(ident) => {
+                if let Some(range) = & $($mut)? ident.range {
+                    f(& $($mut)? range.lower_bound);
+                    f(& $($mut)? range.upper_bound);
+                }
+            }
+
+            Expr::PatternOffset(ident) | Expr::PatternLength(ident) => {
+                if let Some(index) = & $($mut)? ident.index {
+                    f(index);
+                }
+            }
+
+            Expr::Lookup(lookup) => {
+                f(& $($mut)? lookup.primary);
+                f(& $($mut)? lookup.index);
+            }
+
+            Expr::FieldAccess(expr)
+            | Expr::And(expr)
+            | Expr::Or(expr)
+            | Expr::Add(expr)
+            | Expr::Sub(expr)
+            | Expr::Mul(expr)
+            | Expr::Div(expr)
+            | Expr::Mod(expr) => {
+                for operand in & $($mut)? expr.operands {
+                    f(operand);
+                }
+            }
+
+            Expr::FuncCall(func_call) => {
+                f(& $($mut)? func_call.callable);
+                for arg in & $($mut)? func_call.args {
+                    f(arg);
+                }
+            }
+
+            Expr::Defined(expr)
+            | Expr::Not(expr)
+            | Expr::Minus(expr)
+            | Expr::BitwiseNot(expr) => {
+                f(& $($mut)? expr.operand);
+            }
+
+            Expr::Shl(expr)
+            | Expr::Shr(expr)
+            | Expr::BitwiseAnd(expr)
+            | Expr::BitwiseOr(expr)
+            | Expr::BitwiseXor(expr)
+            | Expr::Eq(expr)
+            | Expr::Ne(expr)
+            | Expr::Lt(expr)
+            | Expr::Gt(expr)
+            | Expr::Le(expr)
+            | Expr::Ge(expr)
+            | Expr::Contains(expr)
+            | Expr::IContains(expr)
+            | Expr::StartsWith(expr)
+            | Expr::IStartsWith(expr)
+            | Expr::EndsWith(expr)
+            | Expr::IEndsWith(expr)
+            | Expr::IEquals(expr)
+            | Expr::Matches(expr) => {
+                f(& $($mut)? expr.lhs);
+                f(& $($mut)? expr.rhs);
+            }
+
+            Expr::Of(of) => {
+                quantifier!(& $($mut)? of.quantifier);
+                if let OfItems::BoolExprTuple(exprs) = & $($mut)? of.items {
+                    for expr in exprs {
+                        f(expr);
+                    }
+                }
+                anchor!(& $($mut)? of.anchor);
+            }
+
+            Expr::ForOf(for_of) => {
+                quantifier!(& $($mut)? for_of.quantifier);
+                f(& $($mut)? for_of.condition);
+            }
+
+            Expr::ForIn(for_in) => {
+                quantifier!(& $($mut)? for_in.quantifier);
+                match & $($mut)? for_in.iterable {
+                    Iterable::Range(range) => {
+                        f(& $($mut)? range.lower_bound);
+                        f(& $($mut)? range.upper_bound);
+                    }
+                    Iterable::ExprTuple(exprs) => {
+                        for expr in exprs {
+                            f(expr);
+                        }
+                    }
+                    Iterable::Expr(expr) => f(expr),
+                }
+                f(& $($mut)? for_in.condition);
+            }
+
+            Expr::With(with) => {
+                for declaration in & $($mut)? with.declarations {
+                    f(& $($mut)? declaration.expression);
+                }
+                f(& $($mut)? with.condition);
+            }
+        }
+    }};
+}
+
+/// Calls `f` for each of the direct children of `expr`.
+fn for_each_child<'a, 'src>(
+    expr: &'a Expr<'src>,
+    mut f: impl FnMut(&'a Expr<'src>),
+) {
+    for_each_child!(expr, &mut f)
+}
+
+/// Calls `f` for `expr` and all its descendants, in post-order.
+pub(crate) fn visit_mut<'src>(
+    expr: &mut Expr<'src>,
+    f: &mut impl FnMut(&mut Expr<'src>),
+) {
+    for_each_child!(
+        expr,
+        &mut |child: &mut Expr<'src>| visit_mut(child, f),
+        mut
+    );
+    f(expr)
+}
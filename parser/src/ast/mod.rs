@@ -3,6 +3,14 @@
 Each structure or enum in this module corresponds to some construct in the YARA
 language, like a rule, expression, identifier, import statement, etc.
 
+Besides obtaining an AST by parsing source code with [`Parser::into_ast`],
+an AST can be constructed programmatically. Nodes created with the `new`
+functions in this module have empty spans, as they don't correspond to any
+source code. Such an AST can be passed directly to the compiler, without
+converting it to text and parsing it again.
+
+Expressions can be traversed with [`Expr::dfs_iter`], and modified in place
+with [`Expr::visit_mut`].
 */
 
 use std::borrow::Cow;
@@ -23,10 +31,16 @@ use crate::{Parser, Span};
 
 mod ascii_tree;
 mod cst2ast;
+mod dfs;
 mod errors;
 
+pub use dfs::DFSEvent;
+pub use dfs::DepthFirstSearch;
 pub use errors::Error;
 
+#[cfg(test)]
+mod tests;
+
 /// Abstract Syntax Tree (AST) for YARA rules.
 pub struct AST<'src> {
     /// The list of imports.
@@ -45,6 +59,11 @@ impl<'src> From<Parser<'src>> for AST<'src> {
 }
 
 impl<'src> AST<'src> {
+    /// Creates an [`AST`] with the given imports and rules.
+    pub fn new(imports: Vec<Import<'src>>, rules: Vec<Rule<'src>>) -> Self {
+        Self { imports, rules, errors: Vec::new() }
+    }
+
     /// Returns the import statements in the AST.
     #[inline]
     pub fn imports(&self) -> &[Import<'src>] {
@@ -57,6 +76,12 @@ impl<'src> AST<'src> {
         self.rules.as_slice()
     }
 
+    /// Returns the rules in the AST as a mutable slice.
+    #[inline]
+    pub fn rules_mut(&mut self) -> &mut [Rule<'src>] {
+        self.rules.as_mut_slice()
+    }
+
     /// Returns the errors found while parsing the source code.
     #[inline]
     pub fn errors(&self) -> &[Error] {
@@ -94,6 +119,22 @@ impl Debug for AST<'_> {
 pub struct Import<'src> {
     span: Span,
     pub module_name: &'src str,
+    /// Alias for the module, as in `import "pe" as p`.
+    pub alias: Option<Ident<'src>>,
+}
+
+impl<'src> Import<'src> {
+    /// Creates an import statement for the given module.
+    pub fn new(module_name: &'src str, alias: Option<Ident<'src>>) -> Self {
+        Self { span: Span::default(), module_name, alias }
+    }
+
+    /// Returns the name used for referring to the module in rule
+    /// conditions, which is the alias if the import has one, or the module
+    /// name if otherwise.
+    pub fn name(&self) -> &'src str {
+        self.alias.as_ref().map_or(self.module_name, |alias| alias.name)
+    }
 }
 
 /// A YARA rule.
@@ -151,12 +192,12 @@ impl<'src> Display for MetaValue<'src> {
 #[derive(Debug, Clone, Default)]
 pub struct Ident<'src> {
     span: Span,
-    #[doc(hidden)]
+    /// The identifier's name.
     pub name: &'src str,
 }
 
 impl<'src> Ident<'src> {
-    #[doc(hidden)]
+    /// Creates an identifier with the given name.
     pub fn new(name: &'src str) -> Self {
         Self { name, span: Default::default() }
     }
@@ -178,6 +219,13 @@ pub struct IdentWithRange<'src> {
     pub range: Option<Range<'src>>,
 }
 
+impl<'src> IdentWithRange<'src> {
+    /// Creates an identifier with an optional range.
+    pub fn new(ident: Ident<'src>, range: Option<Range<'src>>) -> Self {
+        Self { span: Span::default(), ident, range }
+    }
+}
+
 /// An expression where an identifier can be accompanied by an index
 /// (e.g. `@a[2]`).
 ///
@@ -190,6 +238,13 @@ pub struct IdentWithIndex<'src> {
     pub index: Option<Expr<'src>>,
 }
 
+impl<'src> IdentWithIndex<'src> {
+    /// Creates an identifier with an optional index.
+    pub fn new(ident: Ident<'src>, index: Option<Expr<'src>>) -> Self {
+        Self { span: Span::default(), ident, index }
+    }
+}
+
 /// Types of patterns (a.k.a. strings) that can appear in a YARA rule.
 ///
 /// Possible types are: text patterns, hex patterns and regular expressions.
@@ -279,7 +334,7 @@ pub struct HexByte {
 }
 
 impl HexByte {
-    #[doc(hidden)]
+    /// Creates a byte with the given value and mask.
     pub fn new(value: u8, mask: u8) -> Self {
         Self { value, mask, span: Span::default() }
     }
@@ -295,7 +350,7 @@ pub struct HexAlternative {
 }
 
 impl HexAlternative {
-    #[doc(hidden)]
+    /// Creates an alternative with the given sequences of tokens.
     pub fn new(alternatives: Vec<HexTokens>) -> Self {
         Self { alternatives, span: Span::default() }
     }
@@ -310,7 +365,7 @@ pub struct HexJump {
 }
 
 impl HexJump {
-    #[doc(hidden)]
+    /// Creates a jump with the given bounds.
     pub fn new(start: Option<u16>, end: Option<u16>) -> Self {
         Self { start, end, span: Span::default() }
     }
@@ -337,6 +392,17 @@ pub struct Of<'src> {
     pub anchor: Option<MatchAnchor<'src>>,
 }
 
+impl<'src> Of<'src> {
+    /// Creates an `of` expression.
+    pub fn new(
+        quantifier: Quantifier<'src>,
+        items: OfItems<'src>,
+        anchor: Option<MatchAnchor<'src>>,
+    ) -> Self {
+        Self { span: Span::default(), quantifier, items, anchor }
+    }
+}
+
 /// A `for .. of` expression (e.g `for all of them : (..)`,
 /// `for 1 of ($a,$b) : (..)`)
 #[derive(Debug)]
@@ -347,6 +413,17 @@ pub struct ForOf<'src> {
     pub condition: Expr<'src>,
 }
 
+impl<'src> ForOf<'src> {
+    /// Creates a `for .. of` expression.
+    pub fn new(
+        quantifier: Quantifier<'src>,
+        pattern_set: PatternSet<'src>,
+        condition: Expr<'src>,
+    ) -> Self {
+        Self { span: Span::default(), quantifier, pattern_set, condition }
+    }
+}
+
 /// A `for .. in` expression (e.g `for all x in iterator : (..)`)
 #[derive(Debug)]
 pub struct ForIn<'src> {
@@ -357,11 +434,30 @@ pub struct ForIn<'src> {
     pub condition: Expr<'src>,
 }
 
+impl<'src> ForIn<'src> {
+    /// Creates a `for .. in` expression.
+    pub fn new(
+        quantifier: Quantifier<'src>,
+        variables: Vec<Ident<'src>>,
+        iterable: Iterable<'src>,
+        condition: Expr<'src>,
+    ) -> Self {
+        Self {
+            span: Span::default(),
+            quantifier,
+            variables,
+            iterable,
+            condition,
+        }
+    }
+}
+
 /// Items in a `of` expression.
 #[derive(Debug)]
 pub enum OfItems<'src> {
     PatternSet(PatternSet<'src>),
     BoolExprTuple(Vec<Expr<'src>>),
+    RuleSet(Vec<RuleSetItem<'src>>),
 }
 
 /// A `with` expression (e.g `with foo = 1 + 1 : (..)`)
@@ -372,6 +468,16 @@ pub struct With<'src> {
     pub condition: Expr<'src>,
 }
 
+impl<'src> With<'src> {
+    /// Creates a `with` expression.
+    pub fn new(
+        declarations: Vec<WithDeclaration<'src>>,
+        condition: Expr<'src>,
+    ) -> Self {
+        Self { span: Span::default(), declarations, condition }
+    }
+}
+
 /// Items in a `with` expression.
 #[derive(Debug)]
 pub struct WithDeclaration<'src> {
@@ -380,6 +486,13 @@ pub struct WithDeclaration<'src> {
     pub expression: Expr<'src>,
 }
 
+impl<'src> WithDeclaration<'src> {
+    /// Creates a declaration for a `with` expression.
+    pub fn new(identifier: Ident<'src>, expression: Expr<'src>) -> Self {
+        Self { span: Span::default(), identifier, expression }
+    }
+}
+
 /// A quantifier used in `for` and `of` expressions.
 #[derive(Debug)]
 pub enum Quantifier<'src> {
@@ -425,7 +538,13 @@ pub struct PatternSetItem<'src> {
     pub wildcard: bool,
 }
 
-impl PatternSetItem<'_> {
+impl<'src> PatternSetItem<'src> {
+    /// Creates an item for a set of patterns. If `wildcard` is true the
+    /// item matches every pattern whose identifier starts with `identifier`.
+    pub fn new(identifier: &'src str, wildcard: bool) -> Self {
+        Self { span: Span::default(), identifier, wildcard }
+    }
+
     /// Returns true if `ident` matches this [`PatternSetItem`].
     ///
     /// For example, identifiers `$a` and `$abc` both match the
@@ -439,6 +558,52 @@ impl PatternSetItem<'_> {
     }
 }
 
+/// Each individual item in a set of rules.
+///
+/// In the rule set `(foo, bar*, baz.*)`, `foo`, `bar*` and `baz.*` are
+/// represented by a [`RuleSetItem`]. An item like `baz.*` refers to all
+/// the rules in namespace `baz`, in that case `identifier` is empty.
+#[derive(Debug)]
+pub struct RuleSetItem<'src> {
+    span: Span,
+    pub namespace: Option<&'src str>,
+    pub identifier: &'src str,
+    pub wildcard: bool,
+}
+
+impl<'src> RuleSetItem<'src> {
+    /// Creates an item for a set of rules. If `wildcard` is true the item
+    /// matches every rule whose identifier starts with `identifier`.
+    pub fn new(
+        namespace: Option<&'src str>,
+        identifier: &'src str,
+        wildcard: bool,
+    ) -> Self {
+        Self { span: Span::default(), namespace, identifier, wildcard }
+    }
+
+    /// Returns true if a rule with the given namespace and identifier
+    /// matches this [`RuleSetItem`].
+    ///
+    /// `current_namespace` is the namespace used when the item doesn't
+    /// specify any.
+    pub fn matches(
+        &self,
+        current_namespace: &str,
+        namespace: &str,
+        ident: &str,
+    ) -> bool {
+        if self.namespace.unwrap_or(current_namespace) != namespace {
+            return false;
+        }
+        if self.wildcard {
+            ident.starts_with(self.identifier)
+        } else {
+            ident == self.identifier
+        }
+    }
+}
+
 /// An expression in the AST.
 #[derive(Debug)]
 pub enum Expr<'src> {
@@ -604,7 +769,8 @@ pub struct PatternModifiers<'src> {
 }
 
 impl<'src> PatternModifiers<'src> {
-    pub(crate) fn new(modifiers: Vec<PatternModifier<'src>>) -> Self {
+    /// Creates a set of modifiers.
+    pub fn new(modifiers: Vec<PatternModifier<'src>>) -> Self {
         Self { modifiers }
     }
 
@@ -785,6 +951,13 @@ pub struct At<'src> {
     pub expr: Expr<'src>,
 }
 
+impl<'src> At<'src> {
+    /// Creates an `at <expr>` anchor.
+    pub fn new(expr: Expr<'src>) -> Self {
+        Self { span: Span::default(), expr }
+    }
+}
+
 /// A pair of values conforming a range (e.g. `(0..10)`).
 #[derive(Debug)]
 pub struct Range<'src> {
@@ -793,6 +966,13 @@ pub struct Range<'src> {
     pub upper_bound: Expr<'src>,
 }
 
+impl<'src> Range<'src> {
+    /// Creates a range with the given bounds.
+    pub fn new(lower_bound: Expr<'src>, upper_bound: Expr<'src>) -> Self {
+        Self { span: Span::default(), lower_bound, upper_bound }
+    }
+}
+
 /// In expressions like `$a in (0..10)`, this struct represents the anchor
 /// e.g. `in <range>`).
 #[derive(Debug)]
@@ -801,6 +981,13 @@ pub struct In<'src> {
     pub range: Range<'src>,
 }
 
+impl<'src> In<'src> {
+    /// Creates an `in <range>` anchor.
+    pub fn new(range: Range<'src>) -> Self {
+        Self { span: Span::default(), range }
+    }
+}
+
 /// An expression representing a function call.
 #[derive(Debug)]
 pub struct FuncCall<'src> {
@@ -810,7 +997,17 @@ pub struct FuncCall<'src> {
     pub args: Vec<Expr<'src>>,
 }
 
-impl FuncCall<'_> {
+impl<'src> FuncCall<'src> {
+    /// Creates a call to `callable` with the given arguments.
+    pub fn new(callable: Expr<'src>, args: Vec<Expr<'src>>) -> Self {
+        Self {
+            span: Span::default(),
+            args_span: Span::default(),
+            callable,
+            args,
+        }
+    }
+
     /// Span covered by the function's arguments in the source code.
     pub fn args_span(&self) -> Span {
         self.args_span.clone()
@@ -825,6 +1022,13 @@ pub struct Lookup<'src> {
     pub index: Expr<'src>,
 }
 
+impl<'src> Lookup<'src> {
+    /// Creates a lookup of `index` in `primary`.
+    pub fn new(primary: Expr<'src>, index: Expr<'src>) -> Self {
+        Self { span: Span::default(), primary, index }
+    }
+}
+
 /// A literal string (e.g: `"abcd"`).
 #[derive(Debug)]
 pub struct LiteralString<'src> {
@@ -837,7 +1041,15 @@ pub struct LiteralString<'src> {
     pub value: Cow<'src, BStr>,
 }
 
-impl LiteralString<'_> {
+impl<'src> LiteralString<'src> {
+    /// Creates a literal string with the given value.
+    ///
+    /// As the literal doesn't come from source code, [`LiteralString::literal`]
+    /// is empty.
+    pub fn new<V: Into<Cow<'src, BStr>>>(value: V) -> Self {
+        Self { span: Span::default(), literal: "", value: value.into() }
+    }
+
     pub fn as_str(&self) -> Result<&str, Utf8Error> {
         match &self.value {
             // SAFETY: When the literal string is borrowed from the original
@@ -862,6 +1074,16 @@ pub struct LiteralInteger<'src> {
     pub value: i64,
 }
 
+impl LiteralInteger<'_> {
+    /// Creates a literal integer with the given value.
+    ///
+    /// As the literal doesn't come from source code,
+    /// [`LiteralInteger::literal`] is empty.
+    pub fn new(value: i64) -> Self {
+        Self { span: Span::default(), literal: "", value }
+    }
+}
+
 /// A literal float (e.g: `2.0`, `3.14`).
 #[derive(Debug)]
 pub struct LiteralFloat<'src> {
@@ -872,6 +1094,16 @@ pub struct LiteralFloat<'src> {
     pub value: f64,
 }
 
+impl LiteralFloat<'_> {
+    /// Creates a literal float with the given value.
+    ///
+    /// As the literal doesn't come from source code, [`LiteralFloat::literal`]
+    /// is empty.
+    pub fn new(value: f64) -> Self {
+        Self { span: Span::default(), literal: "", value }
+    }
+}
+
 /// A regular expression in a YARA rule.
 ///
 /// Used both as part of a [`RegexpPattern`] and as the right operand
@@ -891,6 +1123,42 @@ pub struct Regexp<'src> {
     pub dot_matches_new_line: bool,
 }
 
+impl<'src> Regexp<'src> {
+    /// Creates a regular expression from its literal form, which includes
+    /// the opening and closing slashes, and optionally the modifiers `i`
+    /// and `s` (e.g: `/ab.*cd/i`).
+    ///
+    /// # Panics
+    ///
+    /// If `literal` is not enclosed in slashes, or has modifiers other than
+    /// `i` and `s`.
+    pub fn new(literal: &'src str) -> Self {
+        let closing_slash = literal.rfind('/').unwrap_or(0);
+
+        assert!(
+            literal.starts_with('/') && closing_slash > 0,
+            "regular expressions must be enclosed in slashes: {}",
+            literal
+        );
+
+        let modifiers = &literal[closing_slash + 1..];
+
+        assert!(
+            modifiers.chars().all(|c| c == 'i' || c == 's'),
+            "invalid regular expression modifiers: {}",
+            modifiers
+        );
+
+        Self {
+            span: Span::default(),
+            literal,
+            src: &literal[1..closing_slash],
+            case_insensitive: modifiers.contains('i'),
+            dot_matches_new_line: modifiers.contains('s'),
+        }
+    }
+}
+
 /// An expression with a single operand.
 #[derive(Debug)]
 pub struct UnaryExpr<'src> {
@@ -898,6 +1166,13 @@ pub struct UnaryExpr<'src> {
     pub operand: Expr<'src>,
 }
 
+impl<'src> UnaryExpr<'src> {
+    /// Creates an expression with a single operand.
+    pub fn new(operand: Expr<'src>) -> Self {
+        Self { span: Span::default(), operand }
+    }
+}
+
 /// An expression with two operands.
 #[derive(Debug)]
 pub struct BinaryExpr<'src> {
@@ -942,6 +1217,39 @@ impl<'src> NAryExpr<'src> {
     }
 }
 
+impl<'src> Expr<'src> {
+    /// Returns an iterator that traverses the expression and all its
+    /// descendants in depth-first order.
+    ///
+    /// See [`DepthFirstSearch`] for details.
+    pub fn dfs_iter(&self) -> DepthFirstSearch<'_, 'src> {
+        DepthFirstSearch::new(self)
+    }
+
+    /// Calls `f` for this expression and every one of its descendants,
+    /// allowing them to be modified in place.
+    ///
+    /// Expressions are visited in post-order, children are visited before
+    /// their parents. If `f` replaces an expression, the new expression
+    /// is not visited again.
+    ///
+    /// ```rust
+    /// # use yara_x_parser::ast::Expr;
+    /// # use yara_x_parser::Parser;
+    /// let mut ast = Parser::new(b"rule test { condition: not false }").into_ast();
+    ///
+    /// // Replace every `false` with `true`.
+    /// ast.rules_mut()[0].condition.visit_mut(&mut |expr| {
+    ///     if let Expr::False { span } = expr {
+    ///         *expr = Expr::True { span: span.clone() };
+    ///     }
+    /// });
+    /// ```
+    pub fn visit_mut<F: FnMut(&mut Expr<'src>)>(&mut self, f: &mut F) {
+        dfs::visit_mut(self, f)
+    }
+}
+
 impl<'src> From<Vec<Expr<'src>>> for NAryExpr<'src> {
     fn from(value: Vec<Expr<'src>>) -> Self {
         Self { operands: value }
@@ -1062,6 +1370,18 @@ impl WithSpan for OfItems<'_> {
         match self {
             OfItems::PatternSet(patterns) => patterns.span(),
             OfItems::BoolExprTuple(tuple) => tuple.span(),
+            OfItems::RuleSet(items) => {
+                let span =
+                    items.first().map(|item| item.span()).unwrap_or_default();
+
+                if items.len() == 1 {
+                    return span;
+                }
+
+                span.combine(
+                    &items.last().map(|item| item.span()).unwrap_or_default(),
+                )
+            }
         }
     }
 }
@@ -1203,6 +1523,12 @@ impl WithSpan for PatternSetItem<'_> {
     }
 }
 
+impl WithSpan for RuleSetItem<'_> {
+    fn span(&self) -> Span {
+        self.span.clone()
+    }
+}
+
 impl WithSpan for Quantifier<'_> {
     fn span(&self) -> Span {
         match self {
@@ -0,0 +1,83 @@
+use crate::ast::{DFSEvent, Expr, LiteralInteger};
+use crate::Parser;
+
+#[test]
+fn dfs_iter() {
+    let ast =
+        Parser::new(b"rule test { condition: 1 + 2 == 3 and not false }")
+            .into_ast();
+
+    let mut dfs = ast.rules()[0].condition.dfs_iter();
+    let mut events = Vec::new();
+
+    while let Some(event) = dfs.next() {
+        match event {
+            DFSEvent::Enter(expr) => {
+                events.push(format!("enter {}", expr_name(expr)));
+                // Don't visit the operands of the `not` expression.
+                if matches!(expr, Expr::Not(_)) {
+                    dfs.prune();
+                }
+            }
+            DFSEvent::Leave(expr) => {
+                events.push(format!("leave {}", expr_name(expr)));
+            }
+        }
+    }
+
+    assert_eq!(
+        events,
+        vec![
+            "enter and",
+            "enter eq",
+            "enter add",
+            "enter 1",
+            "leave 1",
+            "enter 2",
+            "leave 2",
+            "leave add",
+            "enter 3",
+            "leave 3",
+            "leave eq",
+            "enter not",
+            "leave not",
+            "leave and",
+        ]
+    );
+}
+
+#[test]
+fn visit_mut() {
+    let mut ast =
+        Parser::new(b"rule test { condition: 1 + 2 == 3 }").into_ast();
+
+    // Increment every integer literal.
+    ast.rules_mut()[0].condition.visit_mut(&mut |expr| {
+        if let Expr::LiteralInteger(lit) = expr {
+            *expr = Expr::LiteralInteger(Box::new(LiteralInteger::new(
+                lit.value + 1,
+            )));
+        }
+    });
+
+    let mut integers = Vec::new();
+
+    for event in ast.rules()[0].condition.dfs_iter() {
+        if let DFSEvent::Enter(Expr::LiteralInteger(lit)) = event {
+            integers.push(lit.value);
+        }
+    }
+
+    assert_eq!(integers, vec![2, 3, 4]);
+}
+
+fn expr_name(expr: &Expr) -> String {
+    match expr {
+        Expr::And(_) => "and".to_string(),
+        Expr::Eq(_) => "eq".to_string(),
+        Expr::Add(_) => "add".to_string(),
+        Expr::Not(_) => "not".to_string(),
+        Expr::LiteralInteger(lit) => lit.value.to_string(),
+        _ => unreachable!(),
+    }
+}
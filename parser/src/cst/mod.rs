@@ -10,6 +10,13 @@ code analysis tools, and similar applications. However, a key limitation is
 that the CST does not account for operator associativity or precedence rules.
 Expressions are represented in the CST exactly as they appear in the source
 code, without any grouping based on operator precedence.
+
+The CST is obtained with [`Parser::into_cst`]. Starting at [`CST::root`], the
+tree can be navigated with methods like [`Node::children`], [`Node::parent`]
+or [`Node::next_sibling`], or traversed in depth-first order with
+[`Node::walk`]. Every [`Token`] has its [`SyntaxKind`], its text and its
+[`Span`], so whitespaces and comments can be inspected just like any other
+token.
  */
 use rowan::{GreenNodeBuilder, GreenToken, SyntaxNode};
 use std::fmt::{Debug, Display, Formatter};
@@ -178,9 +185,6 @@ impl rowan::Language for YARA {
 }
 
 /// A Concrete Syntax Tree (CST).
-///
-/// NOTE: This API is still unstable and should not be used by third-party code.
-#[doc(hidden)]
 pub struct CST {
     tree: rowan::SyntaxNode<YARA>,
     errors: Vec<(Span, String)>,
@@ -207,6 +211,16 @@ impl CST {
     pub fn root(&self) -> Node<Immutable> {
         Node::new(self.tree.clone())
     }
+
+    /// Returns the errors found while parsing the source code.
+    ///
+    /// Each error is accompanied by the span of the source code where it
+    /// occurred. Even if there are errors the CST covers the whole source
+    /// code, the portions that couldn't be parsed are put under nodes of
+    /// kind [`SyntaxKind::ERROR`].
+    pub fn errors(&self) -> &[(Span, String)] {
+        self.errors.as_slice()
+    }
 }
 
 impl From<Parser<'_>> for CST {
@@ -252,9 +266,6 @@ impl From<Parser<'_>> for CST {
 }
 
 /// Sibling traversal direction.
-///
-/// NOTE: This API is still unstable and should not be used by third-party code.
-#[doc(hidden)]
 pub enum Direction {
     Next,
     Prev,
@@ -270,10 +281,7 @@ pub enum Direction {
 /// Instead, we use the [`Text`] type to represent a logically
 /// contiguous portion of the code, even though it is physically
 /// composed of non-contiguous chunks, each owned by a [`Token`].
-///
-/// NOTE: This API is still unstable and should not be used by third-party code.
 #[derive(PartialEq, Eq)]
-#[doc(hidden)]
 pub struct Text(rowan::SyntaxText);
 
 impl Text {
@@ -349,12 +357,14 @@ impl PartialEq<&'_ str> for Text {
     }
 }
 
+/// Marker type for CST nodes and tokens that can be modified.
+///
+/// See [`Node::into_mut`].
 #[derive(Clone, Debug, PartialEq, Eq)]
-#[doc(hidden)]
 pub struct Mutable;
 
+/// Marker type for CST nodes and tokens that can't be modified.
 #[derive(Clone, Debug, PartialEq, Eq)]
-#[doc(hidden)]
 pub struct Immutable;
 
 /// A token in the CST.
@@ -363,10 +373,7 @@ pub struct Immutable;
 /// the grammar, such as keywords, identifiers, whitespaces, punctuation, etc.
 ///
 /// The inner (non-leave) nodes in the CST are of type [`Node`].
-///
-/// NOTE: This API is still unstable and should not be used by third-party code.
 #[derive(PartialEq, Eq)]
-#[doc(hidden)]
 pub struct Token<M> {
     inner: rowan::SyntaxToken<YARA>,
     _state: PhantomData<M>,
@@ -533,10 +540,7 @@ impl Token<Mutable> {
 /// Either a  [`Node`] or a [`Token`].
 ///
 /// In a CST, nodes are the inner nodes of the tree, leaves are tokens.
-///
-/// NOTE: This API is still unstable and should not be used by third-party code.
 #[derive(PartialEq, Eq, Debug)]
-#[doc(hidden)]
 pub enum NodeOrToken<M> {
     Node(Node<M>),
     Token(Token<M>),
@@ -623,10 +627,7 @@ impl<M> From<NodeOrToken<M>> for rowan::SyntaxElement<YARA> {
 /// non-terminal symbols in the grammar.
 ///
 /// The leaves in a CST are of type [`Token`].
-///
-/// NOTE: This API is still unstable and should not be used by third-party code.
 #[derive(Clone, Debug, PartialEq, Eq)]
-#[doc(hidden)]
 pub struct Node<M> {
     inner: rowan::SyntaxNode<YARA>,
     _mutability: PhantomData<M>,
@@ -828,6 +829,42 @@ impl<M> Node<M> {
         // real siblings.
         self.inner.siblings_with_tokens(direction).skip(1).map(|x| x.into())
     }
+
+    /// Returns an iterator that traverses the subtree rooted at this node
+    /// in depth-first order, including tokens.
+    ///
+    /// The iterator produces a [`WalkEvent::Enter`] when a node or token is
+    /// entered, and a [`WalkEvent::Leave`] after all its descendants have
+    /// been visited. The first event is the one entering this node, and the
+    /// last event is the one leaving it.
+    ///
+    /// ```rust
+    /// # use yara_x_parser::cst::{NodeOrToken, SyntaxKind, WalkEvent};
+    /// # use yara_x_parser::Parser;
+    /// let cst = Parser::new(b"rule test { condition: true } // foo").into_cst();
+    ///
+    /// // Collect the comments in the source code.
+    /// let comments: Vec<String> = cst
+    ///     .root()
+    ///     .walk()
+    ///     .filter_map(|event| match event {
+    ///         WalkEvent::Enter(NodeOrToken::Token(token))
+    ///             if token.kind() == SyntaxKind::COMMENT =>
+    ///         {
+    ///             Some(token.text().to_string())
+    ///         }
+    ///         _ => None,
+    ///     })
+    ///     .collect();
+    ///
+    /// assert_eq!(comments, vec!["// foo"]);
+    /// ```
+    pub fn walk(&self) -> Walk<M> {
+        Walk {
+            inner: self.inner.preorder_with_tokens(),
+            _mutability: PhantomData,
+        }
+    }
 }
 
 impl Node<Immutable> {
@@ -848,9 +885,6 @@ impl Node<Mutable> {
 /// nodes, not tokens.
 ///
 /// This is the value returned by [`Node::children`].
-///
-/// NOTE: This API is still unstable and should not be used by third-party code.
-#[doc(hidden)]
 pub struct Nodes<M> {
     inner: rowan::SyntaxNodeChildren<YARA>,
     _mutability: PhantomData<M>,
@@ -868,9 +902,6 @@ impl<M> Iterator for Nodes<M> {
 /// and tokens.
 ///
 /// This is the value returned by [`Node::children_with_tokens`].
-///
-/// NOTE: This API is still unstable and should not be used by third-party code.
-#[doc(hidden)]
 pub struct NodesAndTokens<M> {
     inner: rowan::SyntaxElementChildren<YARA>,
     _mutability: PhantomData<M>,
@@ -883,3 +914,40 @@ impl<M> Iterator for NodesAndTokens<M> {
         self.inner.next().map(|x| x.into())
     }
 }
+
+/// Each of the events produced by [`Walk`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum WalkEvent<M> {
+    /// A node or token is entered, before visiting its descendants.
+    Enter(NodeOrToken<M>),
+    /// A node or token is left, after visiting its descendants.
+    Leave(NodeOrToken<M>),
+}
+
+/// An iterator that traverses a CST subtree in depth-first order.
+///
+/// This is the value returned by [`Node::walk`].
+pub struct Walk<M> {
+    inner: rowan::api::PreorderWithTokens<YARA>,
+    _mutability: PhantomData<M>,
+}
+
+impl<M> Walk<M> {
+    /// Prevents the traversal from visiting the descendants of the node
+    /// that was just entered. The next event will be the one leaving that
+    /// node.
+    pub fn skip_subtree(&mut self) {
+        self.inner.skip_subtree()
+    }
+}
+
+impl<M> Iterator for Walk<M> {
+    type Item = WalkEvent<M>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|event| match event {
+            rowan::WalkEvent::Enter(x) => WalkEvent::Enter(x.into()),
+            rowan::WalkEvent::Leave(x) => WalkEvent::Leave(x.into()),
+        })
+    }
+}
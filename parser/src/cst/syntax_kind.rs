@@ -44,6 +44,7 @@ pub enum SyntaxKind {
     WIDE_KW,
     XOR_KW,
     WITH_KW,
+    AS_KW,
 
     // Arithmetic operators
     ADD,
@@ -136,6 +137,7 @@ pub enum SyntaxKind {
     EXPR_TUPLE,
     BOOLEAN_EXPR_TUPLE,
     PATTERN_IDENT_TUPLE,
+    RULE_IDENT_TUPLE,
 
     HEX_ALTERNATIVE,
     HEX_JUMP,
@@ -215,6 +217,7 @@ impl SyntaxKind {
             SyntaxKind::WIDE_KW => TokenId::WIDE_KW,
             SyntaxKind::XOR_KW => TokenId::XOR_KW,
             SyntaxKind::WITH_KW => TokenId::WITH_KW,
+            SyntaxKind::AS_KW => TokenId::AS_KW,
 
             SyntaxKind::ADD => TokenId::PLUS,
             SyntaxKind::SUB => TokenId::HYPHEN,
@@ -317,6 +320,7 @@ impl From<&Token> for SyntaxKind {
             Token::WIDE_KW(_) => SyntaxKind::WIDE_KW,
             Token::XOR_KW(_) => SyntaxKind::XOR_KW,
             Token::WITH_KW(_) => SyntaxKind::WITH_KW,
+            Token::AS_KW(_) => SyntaxKind::AS_KW,
 
             // Bitwise operators
             Token::SHL(_) => SyntaxKind::SHL,
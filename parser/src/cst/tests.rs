@@ -1,4 +1,4 @@
-use crate::cst::SyntaxKind;
+use crate::cst::{NodeOrToken, SyntaxKind, WalkEvent};
 use crate::{Parser, Span};
 
 #[test]
@@ -189,3 +189,45 @@ fn cst_4() {
     // After detaching the RULE_DECL node, SOURCE_CODE is empty.
     assert_eq!(source_file.last_token().map(|x| x.kind()), None);
 }
+
+#[test]
+fn cst_5() {
+    let cst = Parser::new(b"rule test { condition: true }").into_cst();
+
+    assert!(cst.errors().is_empty());
+
+    let mut walk = cst.root().walk();
+    let mut kinds = Vec::new();
+
+    while let Some(event) = walk.next() {
+        if let WalkEvent::Enter(node_or_token) = event {
+            kinds.push(node_or_token.kind());
+            // Don't visit the contents of the condition block.
+            if let NodeOrToken::Node(node) = node_or_token {
+                if node.kind() == SyntaxKind::CONDITION_BLK {
+                    walk.skip_subtree();
+                }
+            }
+        }
+    }
+
+    assert_eq!(
+        kinds,
+        vec![
+            SyntaxKind::SOURCE_FILE,
+            SyntaxKind::RULE_DECL,
+            SyntaxKind::RULE_KW,
+            SyntaxKind::WHITESPACE,
+            SyntaxKind::IDENT,
+            SyntaxKind::WHITESPACE,
+            SyntaxKind::L_BRACE,
+            SyntaxKind::WHITESPACE,
+            SyntaxKind::CONDITION_BLK,
+            SyntaxKind::WHITESPACE,
+            SyntaxKind::R_BRACE,
+        ]
+    );
+
+    let cst = Parser::new(b"rule test { condition: }").into_cst();
+    assert_eq!(cst.errors().len(), 1);
+}
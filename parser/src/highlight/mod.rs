@@ -0,0 +1,191 @@
+/*! Classification of YARA source code for syntax highlighting.
+
+The tokens are produced by the same parser used for building the CST and the
+AST, so the classification follows exactly the grammar accepted by the
+compiler. For instance, `00` is classified as [`HighlightKind::HexByte`]
+inside a hex pattern, and as [`HighlightKind::Number`] in a condition.
+
+The parser recovers from syntax errors, therefore source code that contains
+errors is classified too. Tokens that are not valid in any context are
+classified as [`HighlightKind::Unknown`].
+
+```rust
+# use yara_x_parser::highlight::HighlightKind;
+# use yara_x_parser::{Parser, Span};
+let highlights: Vec<_> = Parser::new(b"rule test { condition: true }")
+    .into_highlights()
+    .map(|h| (h.kind, h.span))
+    .collect();
+
+assert_eq!(highlights[0], (HighlightKind::Keyword, Span(0..4)));
+assert_eq!(highlights[1], (HighlightKind::Identifier, Span(5..9)));
+```
+ */
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::cst::{CSTStream, Event, SyntaxKind};
+use crate::{Parser, Span};
+
+#[cfg(test)]
+mod tests;
+
+/// Each of the categories in which tokens are classified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum HighlightKind {
+    /// Keywords, like `rule`, `condition`, `and`, `true`, `filesize`, etc.
+    Keyword,
+    /// Identifiers, like rule names, tags, module names, variables, etc.
+    Identifier,
+    /// Pattern identifiers (e.g: `$a`), and their count (e.g: `#a`),
+    /// offset (e.g: `@a`) and length (e.g: `!a`).
+    PatternIdentifier,
+    /// Literal strings, including the quotes.
+    String,
+    /// Regular expressions, including the slashes and the modifiers.
+    Regexp,
+    /// Integer and float literals.
+    Number,
+    /// Bytes in hex patterns (e.g: `4D`, `?A`, `??`).
+    HexByte,
+    /// Comments.
+    Comment,
+    /// Operators, like `+`, `==`, `<<`, etc.
+    Operator,
+    /// Punctuation, like braces, brackets, parenthesis, commas, etc.
+    Punctuation,
+    /// Anything that is not a valid token.
+    Unknown,
+}
+
+/// A token in the source code, classified for syntax highlighting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Highlight {
+    /// The token's category.
+    pub kind: HighlightKind,
+    /// The token's position in the source code.
+    pub span: Span,
+}
+
+/// An iterator that returns the tokens in the source code, classified for
+/// syntax highlighting.
+///
+/// Tokens are returned in the order they appear in the source code.
+/// Whitespaces and newlines are not returned.
+///
+/// This is the value returned by [`Parser::into_highlights`].
+pub struct Highlights<'src> {
+    stream: CSTStream<'src>,
+}
+
+impl<'src> Highlights<'src> {
+    /// Returns the source code associated to this iterator.
+    #[inline]
+    pub fn source(&self) -> &'src [u8] {
+        self.stream.source()
+    }
+}
+
+impl<'src> From<Parser<'src>> for Highlights<'src> {
+    /// Creates a [`Highlights`] from the given parser.
+    fn from(parser: Parser<'src>) -> Self {
+        Self {
+            stream: CSTStream::from(parser).whitespaces(false).newlines(false),
+        }
+    }
+}
+
+impl Iterator for Highlights<'_> {
+    type Item = Highlight;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Event::Token { kind, span } = self.stream.next()? {
+                return Some(Highlight { kind: highlight_kind(kind), span });
+            }
+        }
+    }
+}
+
+/// Returns the [`HighlightKind`] for a token of the given kind.
+fn highlight_kind(kind: SyntaxKind) -> HighlightKind {
+    match kind {
+        SyntaxKind::ALL_KW
+        | SyntaxKind::AND_KW
+        | SyntaxKind::ANY_KW
+        | SyntaxKind::AS_KW
+        | SyntaxKind::ASCII_KW
+        | SyntaxKind::AT_KW
+        | SyntaxKind::BASE64_KW
+        | SyntaxKind::BASE64WIDE_KW
+        | SyntaxKind::CONDITION_KW
+        | SyntaxKind::CONTAINS_KW
+        | SyntaxKind::DEFINED_KW
+        | SyntaxKind::ENDSWITH_KW
+        | SyntaxKind::ENTRYPOINT_KW
+        | SyntaxKind::FALSE_KW
+        | SyntaxKind::FILESIZE_KW
+        | SyntaxKind::FOR_KW
+        | SyntaxKind::FULLWORD_KW
+        | SyntaxKind::GLOBAL_KW
+        | SyntaxKind::ICONTAINS_KW
+        | SyntaxKind::IENDSWITH_KW
+        | SyntaxKind::IEQUALS_KW
+        | SyntaxKind::IMPORT_KW
+        | SyntaxKind::IN_KW
+        | SyntaxKind::ISTARTSWITH_KW
+        | SyntaxKind::MATCHES_KW
+        | SyntaxKind::META_KW
+        | SyntaxKind::NOCASE_KW
+        | SyntaxKind::NONE_KW
+        | SyntaxKind::NOT_KW
+        | SyntaxKind::OF_KW
+        | SyntaxKind::OR_KW
+        | SyntaxKind::PRIVATE_KW
+        | SyntaxKind::RULE_KW
+        | SyntaxKind::STARTSWITH_KW
+        | SyntaxKind::STRINGS_KW
+        | SyntaxKind::THEM_KW
+        | SyntaxKind::TRUE_KW
+        | SyntaxKind::WIDE_KW
+        | SyntaxKind::WITH_KW
+        | SyntaxKind::XOR_KW => HighlightKind::Keyword,
+
+        SyntaxKind::IDENT => HighlightKind::Identifier,
+
+        SyntaxKind::PATTERN_IDENT
+        | SyntaxKind::PATTERN_COUNT
+        | SyntaxKind::PATTERN_OFFSET
+        | SyntaxKind::PATTERN_LENGTH => HighlightKind::PatternIdentifier,
+
+        SyntaxKind::STRING_LIT => HighlightKind::String,
+        SyntaxKind::REGEXP => HighlightKind::Regexp,
+
+        SyntaxKind::INTEGER_LIT | SyntaxKind::FLOAT_LIT => {
+            HighlightKind::Number
+        }
+
+        SyntaxKind::HEX_BYTE => HighlightKind::HexByte,
+        SyntaxKind::COMMENT => HighlightKind::Comment,
+
+        SyntaxKind::L_BRACE
+        | SyntaxKind::R_BRACE
+        | SyntaxKind::L_BRACKET
+        | SyntaxKind::R_BRACKET
+        | SyntaxKind::L_PAREN
+        | SyntaxKind::R_PAREN
+        | SyntaxKind::COLON
+        | SyntaxKind::COMMA
+        | SyntaxKind::DOT => HighlightKind::Punctuation,
+
+        SyntaxKind::UNKNOWN | SyntaxKind::INVALID_UTF8 => {
+            HighlightKind::Unknown
+        }
+
+        // Any other token is an operator, like `+`, `==`, `=` or `~`.
+        _ => HighlightKind::Operator,
+    }
+}
@@ -0,0 +1,92 @@
+use crate::highlight::HighlightKind::*;
+use crate::highlight::{Highlight, HighlightKind};
+use crate::Parser;
+
+fn highlights(src: &str) -> Vec<(HighlightKind, &str)> {
+    Parser::new(src.as_bytes())
+        .into_highlights()
+        .map(|Highlight { kind, span }| (kind, &src[span.range()]))
+        .collect()
+}
+
+#[test]
+fn highlight_1() {
+    assert_eq!(
+        highlights(
+            r#"
+// Some comment
+rule test : tag {
+  strings:
+    $a = "foo" wide
+    $b = { 00 ?? [2-4] }
+    $c = /ba+r/i
+  condition:
+    #a > 2 and $b at 00 or pe.number_of_sections == 1.5
+}"#
+        ),
+        vec![
+            (Comment, "// Some comment"),
+            (Keyword, "rule"),
+            (Identifier, "test"),
+            (Punctuation, ":"),
+            (Identifier, "tag"),
+            (Punctuation, "{"),
+            (Keyword, "strings"),
+            (Punctuation, ":"),
+            (PatternIdentifier, "$a"),
+            (Operator, "="),
+            (String, "\"foo\""),
+            (Keyword, "wide"),
+            (PatternIdentifier, "$b"),
+            (Operator, "="),
+            (Punctuation, "{"),
+            (HexByte, "00"),
+            (HexByte, "??"),
+            (Punctuation, "["),
+            (Number, "2"),
+            (Operator, "-"),
+            (Number, "4"),
+            (Punctuation, "]"),
+            (Punctuation, "}"),
+            (PatternIdentifier, "$c"),
+            (Operator, "="),
+            (Regexp, "/ba+r/i"),
+            (Keyword, "condition"),
+            (Punctuation, ":"),
+            (PatternIdentifier, "#a"),
+            (Operator, ">"),
+            (Number, "2"),
+            (Keyword, "and"),
+            (PatternIdentifier, "$b"),
+            (Keyword, "at"),
+            (Number, "00"),
+            (Keyword, "or"),
+            (Identifier, "pe"),
+            (Punctuation, "."),
+            (Identifier, "number_of_sections"),
+            (Operator, "=="),
+            (Number, "1.5"),
+            (Punctuation, "}"),
+        ]
+    );
+}
+
+#[test]
+fn highlight_2() {
+    // Source code with errors is classified too.
+    assert_eq!(
+        highlights("rule test { condition: true and } rule ` "),
+        vec![
+            (Keyword, "rule"),
+            (Identifier, "test"),
+            (Punctuation, "{"),
+            (Keyword, "condition"),
+            (Punctuation, ":"),
+            (Keyword, "true"),
+            (Keyword, "and"),
+            (Punctuation, "}"),
+            (Keyword, "rule"),
+            (Unknown, "`"),
+        ]
+    );
+}
@@ -19,6 +19,9 @@ providing a more accurate representation of expressions.
 
 Deciding whether to use a CST or AST depends on the kind of problem you want to
 solve.
+
+For tools that only need to colorize the source code, the [`highlight`] module
+classifies the tokens in the source code without building any tree.
  */
 
 use std::fmt::{Display, Formatter};
@@ -31,6 +34,7 @@ use serde::Serialize;
 
 pub mod ast;
 pub mod cst;
+pub mod highlight;
 
 mod parser;
 mod tokenizer;
@@ -27,6 +27,7 @@ use crate::cst::syntax_stream::SyntaxStream;
 use crate::cst::SyntaxKind::*;
 use crate::cst::{syntax_stream, CST};
 use crate::cst::{CSTStream, Event, SyntaxKind};
+use crate::highlight::Highlights;
 use crate::parser::token_stream::TokenStream;
 use crate::tokenizer::{Token, TokenId, Tokenizer};
 use crate::Span;
@@ -60,11 +61,7 @@ impl<'src> Parser<'src> {
     }
 
     /// Consumes the parser and returns a Concrete Syntax Tree (CST).
-    ///
-    /// NOTE: This API is still unstable and should not be used by
-    /// third-party code.
     #[inline]
-    #[doc(hidden)]
     pub fn into_cst(self) -> CST {
         CST::from(self)
     }
@@ -75,6 +72,13 @@ impl<'src> Parser<'src> {
     pub fn into_cst_stream(self) -> CSTStream<'src> {
         CSTStream::from(self)
     }
+
+    /// Consumes the parser and returns the tokens in the source code,
+    /// classified for syntax highlighting.
+    #[inline]
+    pub fn into_highlights(self) -> Highlights<'src> {
+        Highlights::from(self)
+    }
 }
 
 /// Describes the state of the parser.
@@ -1034,12 +1038,13 @@ impl<'src> ParserImpl<'src> {
     /// Parses an import statement.
     ///
     /// ```text
-    /// IMPORT_STMT ::= `import` STRING_LIT
+    /// IMPORT_STMT ::= `import` STRING_LIT (`as` IDENT)?
     /// ```
     fn import_stmt(&mut self) -> &mut Self {
         self.begin(IMPORT_STMT)
             .expect(t!(IMPORT_KW))
             .expect(t!(STRING_LIT))
+            .if_next(t!(AS_KW), |p| p.expect(t!(AS_KW)).expect(t!(IDENT)))
             .end()
     }
 
@@ -1560,7 +1565,8 @@ impl<'src> ParserImpl<'src> {
     /// ```text
     /// OF_EXPR := QUANTIFIER (
     ///     `of` ( `them` | PATTERN_IDENT_TUPLE ) ( `at` EXPR | `in` RANGE )? |
-    ///     BOOLEAN_EXPR_TUPLE
+    ///     BOOLEAN_EXPR_TUPLE |
+    ///     RULE_IDENT_TUPLE
     /// )
     /// ``
     fn of_expr(&mut self) -> &mut Self {
@@ -1579,6 +1585,7 @@ impl<'src> ParserImpl<'src> {
             .alt(|p| {
                 p.boolean_expr_tuple().not(|p| p.expect(t!(AT_KW | IN_KW)))
             })
+            .alt(|p| p.rule_ident_tuple().not(|p| p.expect(t!(AT_KW | IN_KW))))
             .end_alt()
             .end()
     }
@@ -1716,6 +1723,40 @@ impl<'src> ParserImpl<'src> {
             .expect(t!(R_PAREN))
             .end()
     }
+
+    /// Parses a tuple of rule identifiers, possibly with wildcards.
+    ///
+    /// ```text
+    /// RULE_IDENT_TUPLE := `(` RULE_IDENT ( `,` RULE_IDENT )* `)`
+    /// RULE_IDENT := IDENT ( `*` | `.` ( `*` | IDENT `*`? ) )?
+    /// ```
+    ///
+    /// Tuples where all the items are plain identifiers, like `(foo, bar)`,
+    /// are parsed as BOOLEAN_EXPR_TUPLE, this production is used only when
+    /// some item contains a wildcard or a namespace.
+    fn rule_ident_tuple(&mut self) -> &mut Self {
+        self.begin(RULE_IDENT_TUPLE)
+            .expect(t!(L_PAREN))
+            .then(|p| p.rule_ident())
+            .zero_or_more(|p| p.expect(t!(COMMA)).then(|p| p.rule_ident()))
+            .expect(t!(R_PAREN))
+            .end()
+    }
+
+    fn rule_ident(&mut self) -> &mut Self {
+        self.expect(t!(IDENT)).opt(|p| {
+            p.begin_alt()
+                .alt(|p| p.expect(t!(ASTERISK)))
+                .alt(|p| {
+                    p.expect(t!(DOT))
+                        .begin_alt()
+                        .alt(|p| p.expect(t!(ASTERISK)))
+                        .alt(|p| p.expect(t!(IDENT)).opt_expect(t!(ASTERISK)))
+                        .end_alt()
+                })
+                .end_alt()
+        })
+    }
 }
 
 struct Bookmark {
@@ -65,6 +65,10 @@ pub struct Tokenizer<'src> {
     /// at the "c", from the lexer standpoint the span for the "c" is 0..1,
     /// but `lexer_starting_pos` will be 2, so the real span for "c" is 2..3.
     lexer_starting_pos: usize,
+    /// Indicates how much of an import statement has been seen so far. This
+    /// is used for recognizing the `as` keyword, see
+    /// [`Tokenizer::import_alias`].
+    import_stmt: ImportStmt,
 }
 
 impl<'src> Tokenizer<'src> {
@@ -76,6 +80,7 @@ impl<'src> Tokenizer<'src> {
             source,
             lexer_starting_pos: 0,
             mode: Mode::Normal(Logos::lexer(source)),
+            import_stmt: ImportStmt::None,
         }
     }
 
@@ -91,11 +96,12 @@ impl<'src> Tokenizer<'src> {
             match &mut self.mode {
                 Mode::Normal(lexer) => match lexer.next()? {
                     Ok(token) => {
-                        return Some(convert_normal_token(
+                        let token = convert_normal_token(
                             token,
                             Span::from(lexer.span())
                                 .offset(self.lexer_starting_pos),
-                        ));
+                        );
+                        return Some(self.import_alias(token));
                     }
                     Err(()) => return Some(self.unexpected_token()),
                 },
@@ -195,6 +201,33 @@ impl<'src> Tokenizer<'src> {
 }
 
 impl<'src> Tokenizer<'src> {
+    /// Receives the tokens produced in normal mode and converts the `as`
+    /// identifier into the [`Token::AS_KW`] keyword when it follows an
+    /// `import` keyword and a string literal, as in `import "pe" as p`.
+    ///
+    /// Anywhere else `as` is an ordinary identifier. This way rules that
+    /// use `as` as an identifier, which was valid before import aliases
+    /// were introduced, remain valid.
+    fn import_alias(&mut self, token: Token) -> Token {
+        if token.is_trivia() {
+            return token;
+        }
+        let (import_stmt, token) = match (&self.import_stmt, token) {
+            (_, token @ Token::IMPORT_KW(_)) => (ImportStmt::Keyword, token),
+            (ImportStmt::Keyword, token @ Token::STRING_LIT(_)) => {
+                (ImportStmt::ModuleName, token)
+            }
+            (ImportStmt::ModuleName, Token::IDENT(span))
+                if self.source[span.range()].eq(b"as") =>
+            {
+                (ImportStmt::None, Token::AS_KW(span))
+            }
+            (_, token) => (ImportStmt::None, token),
+        };
+        self.import_stmt = import_stmt;
+        token
+    }
+
     fn unexpected_token(&mut self) -> Token {
         let lexer = match &mut self.mode {
             Mode::Normal(lexer) => lexer,
@@ -256,6 +289,16 @@ enum Mode<'src> {
     HexJump(logos::Lexer<'src, HexJumpToken<'src>>),
 }
 
+/// State of the tokenizer with respect to import statements.
+enum ImportStmt {
+    /// Not in an import statement.
+    None,
+    /// After the `import` keyword.
+    Keyword,
+    /// After the module name in an import statement.
+    ModuleName,
+}
+
 /// Tokens recognized in normal mode.
 #[allow(clippy::upper_case_acronyms)]
 #[derive(logos::Logos, Debug, PartialEq)]
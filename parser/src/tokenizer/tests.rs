@@ -21,6 +21,28 @@ fn keywords() {
     assert_eq!(lexer.next_token(), None);
 }
 
+#[test]
+fn import_alias() {
+    let mut lexer = super::Tokenizer::new(r#"import "pe" as as"#.as_bytes());
+
+    assert_eq!(lexer.next_token(), Some(Token::IMPORT_KW(Span(0..6))));
+    assert_eq!(lexer.next_token(), Some(Token::WHITESPACE(Span(6..7))));
+    assert_eq!(lexer.next_token(), Some(Token::STRING_LIT(Span(7..11))));
+    assert_eq!(lexer.next_token(), Some(Token::WHITESPACE(Span(11..12))));
+    assert_eq!(lexer.next_token(), Some(Token::AS_KW(Span(12..14))));
+    assert_eq!(lexer.next_token(), Some(Token::WHITESPACE(Span(14..15))));
+    assert_eq!(lexer.next_token(), Some(Token::IDENT(Span(15..17))));
+    assert_eq!(lexer.next_token(), None);
+
+    // `as` is a keyword only after `import STRING_LIT`.
+    let mut lexer = super::Tokenizer::new(r#""pe" as"#.as_bytes());
+
+    assert_eq!(lexer.next_token(), Some(Token::STRING_LIT(Span(0..4))));
+    assert_eq!(lexer.next_token(), Some(Token::WHITESPACE(Span(4..5))));
+    assert_eq!(lexer.next_token(), Some(Token::IDENT(Span(5..7))));
+    assert_eq!(lexer.next_token(), None);
+}
+
 #[test]
 fn identifiers() {
     let mut lexer = super::Tokenizer::new(
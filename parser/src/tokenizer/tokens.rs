@@ -45,6 +45,7 @@ pub(crate) enum TokenId {
     WIDE_KW,
     XOR_KW,
     WITH_KW,
+    AS_KW,
 
     // Bitwise operators.
     SHL,
@@ -154,6 +155,7 @@ impl TokenId {
             TokenId::WIDE_KW => "`wide`",
             TokenId::XOR_KW => "`xor`",
             TokenId::WITH_KW => "`with`",
+            TokenId::AS_KW => "`as`",
 
             // Bitwise operators.
             TokenId::SHL => "`<<`",
@@ -254,6 +256,7 @@ pub(crate) enum Token {
     WIDE_KW(Span) = TokenId::WIDE_KW as u8,
     XOR_KW(Span) = TokenId::XOR_KW as u8,
     WITH_KW(Span) = TokenId::WITH_KW as u8,
+    AS_KW(Span) = TokenId::AS_KW as u8,
 
     // Bitwise operators.
     SHL(Span) = TokenId::SHL as u8,
@@ -373,6 +376,7 @@ impl Token {
             | Token::WIDE_KW(span)
             | Token::XOR_KW(span)
             | Token::WITH_KW(span)
+            | Token::AS_KW(span)
 
             // Bitwise operators
             | Token::SHL(span)